@@ -1,4 +1,5 @@
 use crate::muxing::{StreamMuxer, StreamMuxerEvent};
+use crate::transport::upgrade::UpgradeTimings;
 use futures::{AsyncRead, AsyncWrite};
 use pin_project::pin_project;
 use std::error::Error;
@@ -11,6 +12,7 @@ use std::task::{Context, Poll};
 /// Abstract `StreamMuxer`.
 pub struct StreamMuxerBox {
     inner: Pin<Box<dyn StreamMuxer<Substream = SubstreamBox, Error = io::Error> + Send>>,
+    upgrade_timings: Option<UpgradeTimings>,
 }
 
 impl fmt::Debug for StreamMuxerBox {
@@ -97,9 +99,24 @@ impl StreamMuxerBox {
 
         StreamMuxerBox {
             inner: Box::pin(wrap),
+            upgrade_timings: None,
         }
     }
 
+    /// Records the instants at which the phases of upgrading the connection completed.
+    pub(crate) fn with_upgrade_timings(mut self, timings: UpgradeTimings) -> Self {
+        self.upgrade_timings = Some(timings);
+        self
+    }
+
+    /// The instants at which the phases of upgrading the connection completed, if it was upgraded
+    /// by a [`Builder`](crate::transport::upgrade::Builder) and boxed with
+    /// [`Multiplexed::boxed`](crate::transport::upgrade::Multiplexed::boxed) or
+    /// [`Multiplexed::box_muxer`](crate::transport::upgrade::Multiplexed::box_muxer).
+    pub fn upgrade_timings(&self) -> Option<UpgradeTimings> {
+        self.upgrade_timings
+    }
+
     fn project(
         self: Pin<&mut Self>,
     ) -> Pin<&mut (dyn StreamMuxer<Substream = SubstreamBox, Error = io::Error> + Send)> {
@@ -26,8 +26,8 @@ use crate::{
     connection::ConnectedPoint,
     muxing::{StreamMuxer, StreamMuxerBox},
     transport::{
-        and_then::AndThen, boxed::boxed, timeout::TransportTimeout, ListenerId, Transport,
-        TransportError, TransportEvent,
+        and_then::AndThen, boxed::boxed, map::Map, timeout::TransportTimeout, ListenerId,
+        Transport, TransportError, TransportEvent,
     },
     upgrade::{
         self, apply_inbound, apply_outbound, InboundConnectionUpgrade, InboundUpgradeApply,
@@ -45,6 +45,7 @@ use std::{
     task::{Context, Poll},
     time::Duration,
 };
+use web_time::Instant;
 
 /// A `Builder` facilitates upgrading of a [`Transport`] for use with
 /// a `Swarm`.
@@ -109,12 +110,28 @@ where
         Authenticated(Builder::new(
             self.inner.and_then(move |conn, endpoint| Authenticate {
                 inner: upgrade::apply(conn, upgrade, endpoint, version),
+                connected: Instant::now(),
             }),
             version,
         ))
     }
 }
 
+/// The instants at which the phases of upgrading a connection with a [`Builder`] completed.
+///
+/// They are recorded in the [`StreamMuxerBox`] of the connections of a transport boxed with
+/// [`Multiplexed::boxed`] or [`Multiplexed::box_muxer`], see
+/// [`StreamMuxerBox::upgrade_timings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpgradeTimings {
+    /// The connection was established by the underlying transport.
+    pub connected: Instant,
+    /// The remote was authenticated.
+    pub authenticated: Instant,
+    /// The stream multiplexer was negotiated.
+    pub multiplexed: Instant,
+}
+
 /// An upgrade that authenticates the remote peer, typically
 /// in the context of negotiating a secure channel.
 ///
@@ -127,23 +144,30 @@ where
 {
     #[pin]
     inner: EitherUpgrade<C, U>,
+    connected: Instant,
 }
 
-impl<C, U> Future for Authenticate<C, U>
+impl<C, U, D, E> Future for Authenticate<C, U>
 where
     C: AsyncRead + AsyncWrite + Unpin,
-    U: InboundConnectionUpgrade<Negotiated<C>>
-        + OutboundConnectionUpgrade<
-            Negotiated<C>,
-            Output = <U as InboundConnectionUpgrade<Negotiated<C>>>::Output,
-            Error = <U as InboundConnectionUpgrade<Negotiated<C>>>::Error,
-        >,
+    U: InboundConnectionUpgrade<Negotiated<C>, Output = (PeerId, D), Error = E>,
+    U: OutboundConnectionUpgrade<Negotiated<C>, Output = (PeerId, D), Error = E>,
 {
-    type Output = <EitherUpgrade<C, U> as Future>::Output;
+    type Output = Result<(PeerId, D, UpgradeTimings), UpgradeError<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        Future::poll(this.inner, cx)
+        let (i, d) = match ready!(Future::poll(this.inner, cx)) {
+            Ok(v) => v,
+            Err(err) => return Poll::Ready(Err(err)),
+        };
+        let now = Instant::now();
+        let timings = UpgradeTimings {
+            connected: *this.connected,
+            authenticated: now,
+            multiplexed: now,
+        };
+        Poll::Ready(Ok((i, d, timings)))
     }
 }
 
@@ -158,6 +182,7 @@ where
     U: InboundConnectionUpgrade<Negotiated<C>> + OutboundConnectionUpgrade<Negotiated<C>>,
 {
     peer_id: Option<PeerId>,
+    timings: UpgradeTimings,
     #[pin]
     upgrade: EitherUpgrade<C, U>,
 }
@@ -168,7 +193,7 @@ where
     U: InboundConnectionUpgrade<Negotiated<C>, Output = M, Error = E>,
     U: OutboundConnectionUpgrade<Negotiated<C>, Output = M, Error = E>,
 {
-    type Output = Result<(PeerId, M), UpgradeError<E>>;
+    type Output = Result<(PeerId, M, UpgradeTimings), UpgradeError<E>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
@@ -180,7 +205,11 @@ where
             .peer_id
             .take()
             .expect("Multiplex future polled after completion.");
-        Poll::Ready(Ok((i, m)))
+        let timings = UpgradeTimings {
+            multiplexed: Instant::now(),
+            ..*this.timings
+        };
+        Poll::Ready(Ok((i, m, timings)))
     }
 }
 
@@ -205,7 +234,7 @@ where
     ///   * Transport output: `(PeerId, C) -> (PeerId, D)`.
     pub fn apply<C, D, U, E>(self, upgrade: U) -> Authenticated<Upgrade<T, U>>
     where
-        T: Transport<Output = (PeerId, C)>,
+        T: Transport<Output = (PeerId, C, UpgradeTimings)>,
         C: AsyncRead + AsyncWrite + Unpin,
         D: AsyncRead + AsyncWrite + Unpin,
         U: InboundConnectionUpgrade<Negotiated<C>, Output = D, Error = E>,
@@ -231,9 +260,14 @@ where
     pub fn multiplex<C, M, U, E>(
         self,
         upgrade: U,
-    ) -> Multiplexed<AndThen<T, impl FnOnce((PeerId, C), ConnectedPoint) -> Multiplex<C, U> + Clone>>
+    ) -> Multiplexed<
+        AndThen<
+            T,
+            impl FnOnce((PeerId, C, UpgradeTimings), ConnectedPoint) -> Multiplex<C, U> + Clone,
+        >,
+    >
     where
-        T: Transport<Output = (PeerId, C)>,
+        T: Transport<Output = (PeerId, C, UpgradeTimings)>,
         C: AsyncRead + AsyncWrite + Unpin,
         M: StreamMuxer,
         U: InboundConnectionUpgrade<Negotiated<C>, Output = M, Error = E>,
@@ -241,10 +275,11 @@ where
         E: Error + 'static,
     {
         let version = self.0.version;
-        Multiplexed(self.0.inner.and_then(move |(i, c), endpoint| {
+        Multiplexed(self.0.inner.and_then(move |(i, c, timings), endpoint| {
             let upgrade = upgrade::apply(c, upgrade, endpoint, version);
             Multiplex {
                 peer_id: Some(i),
+                timings,
                 upgrade,
             }
         }))
@@ -264,9 +299,14 @@ where
     pub fn multiplex_ext<C, M, U, E, F>(
         self,
         up: F,
-    ) -> Multiplexed<AndThen<T, impl FnOnce((PeerId, C), ConnectedPoint) -> Multiplex<C, U> + Clone>>
+    ) -> Multiplexed<
+        AndThen<
+            T,
+            impl FnOnce((PeerId, C, UpgradeTimings), ConnectedPoint) -> Multiplex<C, U> + Clone,
+        >,
+    >
     where
-        T: Transport<Output = (PeerId, C)>,
+        T: Transport<Output = (PeerId, C, UpgradeTimings)>,
         C: AsyncRead + AsyncWrite + Unpin,
         M: StreamMuxer,
         U: InboundConnectionUpgrade<Negotiated<C>, Output = M, Error = E>,
@@ -275,13 +315,18 @@ where
         F: for<'a> FnOnce(&'a PeerId, &'a ConnectedPoint) -> U + Clone,
     {
         let version = self.0.version;
-        Multiplexed(self.0.inner.and_then(move |(peer_id, c), endpoint| {
-            let upgrade = upgrade::apply(c, up(&peer_id, &endpoint), endpoint, version);
-            Multiplex {
-                peer_id: Some(peer_id),
-                upgrade,
-            }
-        }))
+        Multiplexed(
+            self.0
+                .inner
+                .and_then(move |(peer_id, c, timings), endpoint| {
+                    let upgrade = upgrade::apply(c, up(&peer_id, &endpoint), endpoint, version);
+                    Multiplex {
+                        peer_id: Some(peer_id),
+                        timings,
+                        upgrade,
+                    }
+                }),
+        )
     }
 }
 
@@ -296,7 +341,7 @@ impl<T> Multiplexed<T> {
     /// the [`StreamMuxer`] and custom transport errors.
     pub fn boxed<M>(self) -> super::Boxed<(PeerId, StreamMuxerBox)>
     where
-        T: Transport<Output = (PeerId, M)> + Sized + Send + Unpin + 'static,
+        T: Transport<Output = (PeerId, M, UpgradeTimings)> + Sized + Send + Unpin + 'static,
         T::Dial: Send + 'static,
         T::ListenerUpgrade: Send + 'static,
         T::Error: Send + Sync,
@@ -304,7 +349,23 @@ impl<T> Multiplexed<T> {
         M::Substream: Send + 'static,
         M::Error: Send + Sync + 'static,
     {
-        boxed(self.map(|(i, m), _| (i, StreamMuxerBox::new(m))))
+        boxed(self.box_muxer())
+    }
+
+    /// Boxes the [`StreamMuxer`] of the connections, recording the [`UpgradeTimings`] of each
+    /// connection in its [`StreamMuxerBox`].
+    #[allow(clippy::type_complexity)]
+    pub fn box_muxer<M>(
+        self,
+    ) -> Map<T, fn((PeerId, M, UpgradeTimings), ConnectedPoint) -> (PeerId, StreamMuxerBox)>
+    where
+        T: Transport<Output = (PeerId, M, UpgradeTimings)>,
+        M: StreamMuxer + Send + 'static,
+        M::Substream: Send + 'static,
+        M::Error: Send + Sync + 'static,
+    {
+        self.0
+            .map(|(i, m, timings), _| (i, StreamMuxerBox::new(m).with_upgrade_timings(timings)))
     }
 
     /// Adds a timeout to the setup and protocol upgrade process for all
@@ -326,17 +387,17 @@ impl<T> Multiplexed<T> {
     }
 }
 
-impl<T> Transport for Multiplexed<T>
+impl<T, M> Transport for Multiplexed<T>
 where
-    T: Transport,
+    T: Transport<Output = (PeerId, M, UpgradeTimings)>,
 {
-    type Output = T::Output;
+    type Output = (PeerId, M);
     type Error = T::Error;
-    type ListenerUpgrade = T::ListenerUpgrade;
-    type Dial = T::Dial;
+    type ListenerUpgrade = future::MapOk<T::ListenerUpgrade, StripTimings<M>>;
+    type Dial = future::MapOk<T::Dial, StripTimings<M>>;
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.0.dial(addr)
+        Ok(self.0.dial(addr)?.map_ok(strip_timings))
     }
 
     fn remove_listener(&mut self, id: ListenerId) -> bool {
@@ -347,7 +408,7 @@ where
         &mut self,
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.0.dial_as_listener(addr)
+        Ok(self.0.dial_as_listener(addr)?.map_ok(strip_timings))
     }
 
     fn listen_on(
@@ -366,10 +427,20 @@ where
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
-        self.project().0.poll(cx)
+        self.project()
+            .0
+            .poll(cx)
+            .map(|event| event.map_upgrade(|upgrade| upgrade.map_ok(strip_timings as _)))
     }
 }
 
+/// Drops the [`UpgradeTimings`] from the output of a [`Multiplexed`] transport.
+type StripTimings<M> = fn((PeerId, M, UpgradeTimings)) -> (PeerId, M);
+
+fn strip_timings<M>((i, m, _): (PeerId, M, UpgradeTimings)) -> (PeerId, M) {
+    (i, m)
+}
+
 /// An inbound or outbound upgrade.
 type EitherUpgrade<C, U> = future::Either<InboundUpgradeApply<C, U>, OutboundUpgradeApply<C, U>>;
 
@@ -392,14 +463,14 @@ impl<T, U> Upgrade<T, U> {
 
 impl<T, C, D, U, E> Transport for Upgrade<T, U>
 where
-    T: Transport<Output = (PeerId, C)>,
+    T: Transport<Output = (PeerId, C, UpgradeTimings)>,
     T::Error: 'static,
     C: AsyncRead + AsyncWrite + Unpin,
     U: InboundConnectionUpgrade<Negotiated<C>, Output = D, Error = E>,
     U: OutboundConnectionUpgrade<Negotiated<C>, Output = D, Error = E> + Clone,
     E: Error + 'static,
 {
-    type Output = (PeerId, D);
+    type Output = (PeerId, D, UpgradeTimings);
     type Error = TransportUpgradeError<T::Error, E>;
     type ListenerUpgrade = ListenerUpgradeFuture<T::ListenerUpgrade, U, C>;
     type Dial = DialUpgradeFuture<T::Dial, U, C>;
@@ -506,17 +577,17 @@ where
     C: AsyncRead + AsyncWrite + Unpin,
 {
     future: Pin<Box<F>>,
-    upgrade: future::Either<Option<U>, (PeerId, OutboundUpgradeApply<C, U>)>,
+    upgrade: future::Either<Option<U>, (PeerId, UpgradeTimings, OutboundUpgradeApply<C, U>)>,
 }
 
 impl<F, U, C, D> Future for DialUpgradeFuture<F, U, C>
 where
-    F: TryFuture<Ok = (PeerId, C)>,
+    F: TryFuture<Ok = (PeerId, C, UpgradeTimings)>,
     C: AsyncRead + AsyncWrite + Unpin,
     U: OutboundConnectionUpgrade<Negotiated<C>, Output = D>,
     U::Error: Error,
 {
-    type Output = Result<(PeerId, D), TransportUpgradeError<F::Error, U::Error>>;
+    type Output = Result<(PeerId, D, UpgradeTimings), TransportUpgradeError<F::Error, U::Error>>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // We use a `this` variable because the compiler can't mutably borrow multiple times
@@ -526,25 +597,26 @@ where
         loop {
             this.upgrade = match this.upgrade {
                 future::Either::Left(ref mut up) => {
-                    let (i, c) = match ready!(TryFuture::try_poll(this.future.as_mut(), cx)
-                        .map_err(TransportUpgradeError::Transport))
-                    {
-                        Ok(v) => v,
-                        Err(err) => return Poll::Ready(Err(err)),
-                    };
+                    let (i, c, timings) =
+                        match ready!(TryFuture::try_poll(this.future.as_mut(), cx)
+                            .map_err(TransportUpgradeError::Transport))
+                        {
+                            Ok(v) => v,
+                            Err(err) => return Poll::Ready(Err(err)),
+                        };
                     let u = up
                         .take()
                         .expect("DialUpgradeFuture is constructed with Either::Left(Some).");
-                    future::Either::Right((i, apply_outbound(c, u, upgrade::Version::V1)))
+                    future::Either::Right((i, timings, apply_outbound(c, u, upgrade::Version::V1)))
                 }
-                future::Either::Right((i, ref mut up)) => {
+                future::Either::Right((i, timings, ref mut up)) => {
                     let d = match ready!(
                         Future::poll(Pin::new(up), cx).map_err(TransportUpgradeError::Upgrade)
                     ) {
                         Ok(d) => d,
                         Err(err) => return Poll::Ready(Err(err)),
                     };
-                    return Poll::Ready(Ok((i, d)));
+                    return Poll::Ready(Ok((i, d, timings)));
                 }
             }
         }
@@ -565,17 +637,17 @@ where
     U: InboundConnectionUpgrade<Negotiated<C>>,
 {
     future: Pin<Box<F>>,
-    upgrade: future::Either<Option<U>, (PeerId, InboundUpgradeApply<C, U>)>,
+    upgrade: future::Either<Option<U>, (PeerId, UpgradeTimings, InboundUpgradeApply<C, U>)>,
 }
 
 impl<F, U, C, D> Future for ListenerUpgradeFuture<F, U, C>
 where
-    F: TryFuture<Ok = (PeerId, C)>,
+    F: TryFuture<Ok = (PeerId, C, UpgradeTimings)>,
     C: AsyncRead + AsyncWrite + Unpin,
     U: InboundConnectionUpgrade<Negotiated<C>, Output = D>,
     U::Error: Error,
 {
-    type Output = Result<(PeerId, D), TransportUpgradeError<F::Error, U::Error>>;
+    type Output = Result<(PeerId, D, UpgradeTimings), TransportUpgradeError<F::Error, U::Error>>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // We use a `this` variable because the compiler can't mutably borrow multiple times
@@ -585,25 +657,26 @@ where
         loop {
             this.upgrade = match this.upgrade {
                 future::Either::Left(ref mut up) => {
-                    let (i, c) = match ready!(TryFuture::try_poll(this.future.as_mut(), cx)
-                        .map_err(TransportUpgradeError::Transport))
-                    {
-                        Ok(v) => v,
-                        Err(err) => return Poll::Ready(Err(err)),
-                    };
+                    let (i, c, timings) =
+                        match ready!(TryFuture::try_poll(this.future.as_mut(), cx)
+                            .map_err(TransportUpgradeError::Transport))
+                        {
+                            Ok(v) => v,
+                            Err(err) => return Poll::Ready(Err(err)),
+                        };
                     let u = up
                         .take()
                         .expect("ListenerUpgradeFuture is constructed with Either::Left(Some).");
-                    future::Either::Right((i, apply_inbound(c, u)))
+                    future::Either::Right((i, timings, apply_inbound(c, u)))
                 }
-                future::Either::Right((i, ref mut up)) => {
+                future::Either::Right((i, timings, ref mut up)) => {
                     let d = match ready!(TryFuture::try_poll(Pin::new(up), cx)
                         .map_err(TransportUpgradeError::Upgrade))
                     {
                         Ok(v) => v,
                         Err(err) => return Poll::Ready(Err(err)),
                     };
-                    return Poll::Ready(Ok((i, d)));
+                    return Poll::Ready(Ok((i, d, timings)));
                 }
             }
         }
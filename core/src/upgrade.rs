@@ -63,6 +63,7 @@ mod either;
 mod error;
 mod pending;
 mod ready;
+mod security_policy;
 mod select;
 
 pub(crate) use apply::{
@@ -72,7 +73,13 @@ pub(crate) use error::UpgradeError;
 use futures::future::Future;
 
 pub use self::{
-    denied::DeniedUpgrade, pending::PendingUpgrade, ready::ReadyUpgrade, select::SelectUpgrade,
+    denied::DeniedUpgrade,
+    pending::PendingUpgrade,
+    ready::ReadyUpgrade,
+    security_policy::{
+        EnforceSecurityPolicy, EnforceSecurityPolicyFuture, SecurityPolicy, SecurityPolicyError,
+    },
+    select::SelectUpgrade,
 };
 pub use crate::Negotiated;
 pub use multistream_select::{NegotiatedComplete, NegotiationError, ProtocolError, Version};
@@ -0,0 +1,305 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade, UpgradeInfo};
+use futures::{prelude::*, ready};
+use libp2p_identity::PeerId;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// The security protocols allowed for the connections to remotes, enforced by
+/// [`EnforceSecurityPolicy`].
+///
+/// By default, all security protocols are allowed.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityPolicy {
+    /// The protocols allowed for all remotes, `None` if all protocols are allowed.
+    allowed: Option<HashSet<String>>,
+    /// The protocols required for specific remotes, on top of `allowed`.
+    required: HashMap<PeerId, HashSet<String>>,
+}
+
+impl SecurityPolicy {
+    /// Creates a policy allowing all security protocols.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allows the given security protocols for all remotes, e.g. never `/plaintext/2.0.0`
+    /// even if the transport supports it.
+    ///
+    /// The other protocols are not even offered during the negotiation.
+    pub fn allow_only<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.allowed = Some(protocols.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Requires one of the given security protocols for the connections to the given peer.
+    ///
+    /// The protocols allowed via [`SecurityPolicy::allow_only`] still apply, i.e. the requirements
+    /// of a peer can only further restrict them.
+    pub fn require_for_peer<I, P>(mut self, peer_id: PeerId, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.required
+            .insert(peer_id, protocols.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns whether the given security protocol is allowed for the connections to the given
+    /// peer.
+    pub fn is_allowed(&self, peer_id: &PeerId, protocol: &str) -> bool {
+        self.is_offered(protocol)
+            && self
+                .required
+                .get(peer_id)
+                .map_or(true, |required| required.contains(protocol))
+    }
+
+    /// Returns whether the given security protocol may be negotiated with any remote.
+    fn is_offered(&self, protocol: &str) -> bool {
+        self.allowed
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(protocol))
+    }
+}
+
+/// Authentication upgrade enforcing a [`SecurityPolicy`] on the upgrade `U`, which must produce
+/// the [`PeerId`] of the remote.
+///
+/// The protocols not allowed by [`SecurityPolicy::allow_only`] are not negotiated. If the
+/// negotiated protocol is not allowed for the authenticated remote, the upgrade fails with
+/// [`SecurityPolicyError::Denied`].
+#[derive(Debug, Clone)]
+pub struct EnforceSecurityPolicy<U> {
+    inner: U,
+    policy: Arc<SecurityPolicy>,
+}
+
+impl<U> EnforceSecurityPolicy<U> {
+    /// Enforces the given policy on the given authentication upgrade.
+    pub fn new(inner: U, policy: SecurityPolicy) -> Self {
+        Self {
+            inner,
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl<U> UpgradeInfo for EnforceSecurityPolicy<U>
+where
+    U: UpgradeInfo,
+{
+    type Info = U::Info;
+    type InfoIter = Vec<U::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.inner
+            .protocol_info()
+            .into_iter()
+            .filter(|protocol| self.policy.is_offered(protocol.as_ref()))
+            .collect()
+    }
+}
+
+impl<C, U, D, E> InboundConnectionUpgrade<C> for EnforceSecurityPolicy<U>
+where
+    U: InboundConnectionUpgrade<C, Output = (PeerId, D), Error = E>,
+{
+    type Output = (PeerId, D);
+    type Error = SecurityPolicyError<E>;
+    type Future = EnforceSecurityPolicyFuture<U::Future>;
+
+    fn upgrade_inbound(self, socket: C, info: Self::Info) -> Self::Future {
+        EnforceSecurityPolicyFuture {
+            protocol: info.as_ref().to_owned(),
+            inner: self.inner.upgrade_inbound(socket, info),
+            policy: self.policy,
+        }
+    }
+}
+
+impl<C, U, D, E> OutboundConnectionUpgrade<C> for EnforceSecurityPolicy<U>
+where
+    U: OutboundConnectionUpgrade<C, Output = (PeerId, D), Error = E>,
+{
+    type Output = (PeerId, D);
+    type Error = SecurityPolicyError<E>;
+    type Future = EnforceSecurityPolicyFuture<U::Future>;
+
+    fn upgrade_outbound(self, socket: C, info: Self::Info) -> Self::Future {
+        EnforceSecurityPolicyFuture {
+            protocol: info.as_ref().to_owned(),
+            inner: self.inner.upgrade_outbound(socket, info),
+            policy: self.policy,
+        }
+    }
+}
+
+/// Future of the upgrade of an [`EnforceSecurityPolicy`].
+#[pin_project::pin_project]
+pub struct EnforceSecurityPolicyFuture<F> {
+    #[pin]
+    inner: F,
+    protocol: String,
+    policy: Arc<SecurityPolicy>,
+}
+
+impl<F, D, E> Future for EnforceSecurityPolicyFuture<F>
+where
+    F: TryFuture<Ok = (PeerId, D), Error = E>,
+{
+    type Output = Result<(PeerId, D), SecurityPolicyError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let (peer_id, io) =
+            ready!(this.inner.try_poll(cx)).map_err(SecurityPolicyError::Upgrade)?;
+
+        if !this.policy.is_allowed(&peer_id, this.protocol) {
+            tracing::debug!(
+                peer=%peer_id,
+                protocol=%this.protocol,
+                "Denying connection not meeting the security policy"
+            );
+            return Poll::Ready(Err(SecurityPolicyError::Denied {
+                peer_id,
+                protocol: std::mem::take(this.protocol),
+            }));
+        }
+
+        Poll::Ready(Ok((peer_id, io)))
+    }
+}
+
+/// Error of an [`EnforceSecurityPolicy`] upgrade.
+#[derive(Debug)]
+pub enum SecurityPolicyError<E> {
+    /// The authentication upgrade failed.
+    Upgrade(E),
+    /// The negotiated security protocol is not allowed for the remote.
+    Denied {
+        /// The authenticated remote.
+        peer_id: PeerId,
+        /// The negotiated security protocol.
+        protocol: String,
+    },
+}
+
+impl<E> fmt::Display for SecurityPolicyError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecurityPolicyError::Upgrade(e) => write!(f, "Authentication error: {e}"),
+            SecurityPolicyError::Denied { peer_id, protocol } => write!(
+                f,
+                "Security protocol {protocol} is not allowed for peer {peer_id}"
+            ),
+        }
+    }
+}
+
+impl<E> Error for SecurityPolicyError<E>
+where
+    E: Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SecurityPolicyError::Upgrade(e) => Some(e),
+            SecurityPolicyError::Denied { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter;
+    use void::Void;
+
+    /// An authentication upgrade identifying the remote as the given peer.
+    #[derive(Clone)]
+    struct Authenticate(PeerId);
+
+    impl UpgradeInfo for Authenticate {
+        type Info = &'static str;
+        type InfoIter = [&'static str; 2];
+
+        fn protocol_info(&self) -> Self::InfoIter {
+            ["/noise", "/plaintext/2.0.0"]
+        }
+    }
+
+    impl OutboundConnectionUpgrade<()> for Authenticate {
+        type Output = (PeerId, ());
+        type Error = Void;
+        type Future = future::Ready<Result<(PeerId, ()), Void>>;
+
+        fn upgrade_outbound(self, _: (), _: Self::Info) -> Self::Future {
+            future::ready(Ok((self.0, ())))
+        }
+    }
+
+    #[test]
+    fn disallowed_protocols_are_not_offered() {
+        let upgrade = EnforceSecurityPolicy::new(
+            Authenticate(PeerId::random()),
+            SecurityPolicy::new().allow_only(iter::once("/noise")),
+        );
+
+        assert_eq!(upgrade.protocol_info(), vec!["/noise"]);
+    }
+
+    #[test]
+    fn connection_to_peer_requiring_other_protocol_is_denied() {
+        let strict = PeerId::random();
+        let other = PeerId::random();
+        let policy = SecurityPolicy::new().require_for_peer(strict, iter::once("/tls/1.0.0"));
+
+        let result = futures::executor::block_on(
+            EnforceSecurityPolicy::new(Authenticate(strict), policy.clone())
+                .upgrade_outbound((), "/noise"),
+        );
+        assert!(matches!(
+            result,
+            Err(SecurityPolicyError::Denied { peer_id, protocol })
+                if peer_id == strict && protocol == "/noise"
+        ));
+
+        let result = futures::executor::block_on(
+            EnforceSecurityPolicy::new(Authenticate(other), policy).upgrade_outbound((), "/noise"),
+        );
+        assert!(matches!(result, Ok((peer_id, ())) if peer_id == other));
+    }
+}
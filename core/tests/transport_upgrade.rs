@@ -121,8 +121,12 @@ fn upgrade_pipeline() {
     };
 
     let client = async move {
-        let (peer, _mplex) = dialer_transport.dial(listen_addr2).unwrap().await.unwrap();
+        let (peer, mplex) = dialer_transport.dial(listen_addr2).unwrap().await.unwrap();
         assert_eq!(peer, listener_id);
+
+        let timings = mplex.upgrade_timings().unwrap();
+        assert!(timings.connected <= timings.authenticated);
+        assert!(timings.authenticated <= timings.multiplexed);
     };
 
     async_std::task::spawn(server);
@@ -139,6 +139,33 @@ impl SecretKey {
     }
 }
 
+#[cfg(all(feature = "webcrypto", target_arch = "wasm32"))]
+impl SecretKey {
+    /// Encode the secret key into a PKCS#8 DER-encoded byte buffer.
+    pub(crate) fn encode_pkcs8(&self) -> Vec<u8> {
+        use p256::pkcs8::EncodePrivateKey as _;
+
+        self.0
+            .to_pkcs8_der()
+            .expect("Encoding to pkcs#8 format to succeed")
+            .as_bytes()
+            .to_vec()
+    }
+
+    /// Try to decode a secret key from a PKCS#8 DER-encoded byte buffer, zeroize the buffer on success.
+    pub(crate) fn try_decode_pkcs8(buf: &mut [u8]) -> Result<Self, DecodingError> {
+        use p256::pkcs8::DecodePrivateKey as _;
+
+        match SigningKey::from_pkcs8_der(buf) {
+            Ok(key) => {
+                buf.zeroize();
+                Ok(SecretKey(key))
+            }
+            Err(e) => Err(DecodingError::failed_to_parse("ECDSA", e)),
+        }
+    }
+}
+
 impl fmt::Debug for SecretKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "SecretKey")
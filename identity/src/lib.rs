@@ -58,6 +58,13 @@ pub mod rsa;
 #[cfg(feature = "secp256k1")]
 pub mod secp256k1;
 
+#[cfg(all(
+    feature = "webcrypto",
+    target_arch = "wasm32",
+    any(feature = "ecdsa", feature = "ed25519")
+))]
+pub mod webcrypto;
+
 mod error;
 mod keypair;
 #[cfg(feature = "peerid")]
@@ -0,0 +1,243 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Key generation and signing backed by the [WebCrypto API] of the browser.
+//!
+//! Generating keys and signing messages through the `SubtleCrypto` interface of the browser
+//! uses its native implementations instead of the pure Rust ones compiled to WebAssembly.
+//! The WebCrypto API is asynchronous, hence so are the functions of this module.
+//!
+//! Keys generated here are regular [`Keypair`]s and can be used with all other APIs of this
+//! crate. To sign many messages with the same key, create a [`Signer`] once, which imports the
+//! secret key into the WebCrypto API as a non-extractable key.
+//!
+//! Ed25519 requires a browser supporting the `Ed25519` algorithm of the WebCrypto API.
+//!
+//! [WebCrypto API]: https://developer.mozilla.org/en-US/docs/Web/API/SubtleCrypto
+
+use crate::{KeyType, Keypair};
+use js_sys::{Array, Reflect, Uint8Array};
+use std::{error, fmt};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Crypto, CryptoKey, SubtleCrypto};
+
+/// The algorithm name of Ed25519 in the WebCrypto API.
+#[cfg(feature = "ed25519")]
+const ED25519: &str = "Ed25519";
+
+/// The PKCS#8 encoding of an Ed25519 secret key, without the trailing 32 bytes of the key.
+#[cfg(feature = "ed25519")]
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// Generate a new random Ed25519 keypair using the WebCrypto API.
+#[cfg(feature = "ed25519")]
+pub async fn generate_ed25519() -> Result<Keypair, Error> {
+    let promise = subtle()?
+        .generate_key_with_str(ED25519, true, &usages(&["sign", "verify"]))
+        .map_err(|e| Error::js("failed to generate Ed25519 key", e))?;
+    let mut pkcs8 = export_private_key(promise).await?;
+
+    let secret = pkcs8
+        .strip_prefix(&ED25519_PKCS8_PREFIX[..])
+        .ok_or_else(|| Error::new("unexpected PKCS#8 encoding of Ed25519 key"))?
+        .to_vec();
+    zeroize::Zeroize::zeroize(&mut pkcs8);
+    let secret = crate::ed25519::SecretKey::try_from_bytes(secret)
+        .map_err(|e| Error::new(format!("invalid Ed25519 key: {e}")))?;
+
+    Ok(crate::ed25519::Keypair::from(secret).into())
+}
+
+/// Generate a new random ECDSA keypair on the `secp256r1` curve using the WebCrypto API.
+#[cfg(feature = "ecdsa")]
+pub async fn generate_ecdsa() -> Result<Keypair, Error> {
+    let algorithm = web_sys::EcKeyGenParams::new("ECDSA", "P-256");
+    let promise = subtle()?
+        .generate_key_with_object(&algorithm, true, &usages(&["sign", "verify"]))
+        .map_err(|e| Error::js("failed to generate ECDSA key", e))?;
+    let mut pkcs8 = export_private_key(promise).await?;
+
+    let secret = crate::ecdsa::SecretKey::try_decode_pkcs8(&mut pkcs8)
+        .map_err(|e| Error::new(format!("invalid ECDSA key: {e}")))?;
+
+    Ok(crate::ecdsa::Keypair::from(secret).into())
+}
+
+/// Signs messages with the secret key of a [`Keypair`] using the WebCrypto API.
+///
+/// The secret key is imported into the WebCrypto API once, on creation of the [`Signer`].
+#[derive(Debug)]
+pub struct Signer {
+    key: CryptoKey,
+    key_type: KeyType,
+}
+
+impl Signer {
+    /// Import the secret key of the given keypair into the WebCrypto API.
+    ///
+    /// Fails for key types not supported by the WebCrypto API, i.e. `secp256k1`.
+    pub async fn new(keypair: &Keypair) -> Result<Signer, Error> {
+        let key_type = keypair.key_type();
+        let promise = match key_type {
+            #[cfg(feature = "ed25519")]
+            KeyType::Ed25519 => {
+                let keypair = keypair
+                    .clone()
+                    .try_into_ed25519()
+                    .expect("key type to be Ed25519");
+                let mut pkcs8 = ED25519_PKCS8_PREFIX.to_vec();
+                pkcs8.extend_from_slice(keypair.secret().as_ref());
+                let promise = subtle()?.import_key_with_str(
+                    "pkcs8",
+                    &Uint8Array::from(&pkcs8[..]),
+                    ED25519,
+                    false,
+                    &usages(&["sign"]),
+                );
+                zeroize::Zeroize::zeroize(&mut pkcs8);
+                promise
+            }
+            #[cfg(feature = "ecdsa")]
+            KeyType::Ecdsa => {
+                let keypair = keypair
+                    .clone()
+                    .try_into_ecdsa()
+                    .expect("key type to be ECDSA");
+                let mut pkcs8 = keypair.secret().encode_pkcs8();
+                let mut algorithm = web_sys::EcKeyImportParams::new("ECDSA");
+                algorithm.named_curve("P-256");
+                let promise = subtle()?.import_key_with_object(
+                    "pkcs8",
+                    &Uint8Array::from(&pkcs8[..]),
+                    &algorithm,
+                    false,
+                    &usages(&["sign"]),
+                );
+                zeroize::Zeroize::zeroize(&mut pkcs8);
+                promise
+            }
+            key_type => {
+                return Err(Error::new(format!(
+                    "{key_type} keys are not supported by the WebCrypto API"
+                )))
+            }
+        };
+        let key = JsFuture::from(promise.map_err(|e| Error::js("failed to import key", e))?)
+            .await
+            .map_err(|e| Error::js("failed to import key", e))?
+            .unchecked_into();
+
+        Ok(Signer { key, key_type })
+    }
+
+    /// Sign a message, producing a signature in the same format as [`Keypair::sign`].
+    pub async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let subtle = subtle()?;
+        let promise = match self.key_type {
+            #[cfg(feature = "ed25519")]
+            KeyType::Ed25519 => subtle.sign_with_str_and_u8_array(ED25519, &self.key, msg),
+            #[cfg(feature = "ecdsa")]
+            KeyType::Ecdsa => {
+                let algorithm = web_sys::EcdsaParams::new("ECDSA", &JsValue::from_str("SHA-256"));
+                subtle.sign_with_object_and_u8_array(&algorithm, &self.key, msg)
+            }
+            _ => unreachable!("only supported key types are imported"),
+        };
+        let signature = JsFuture::from(promise.map_err(|e| Error::js("failed to sign", e))?)
+            .await
+            .map_err(|e| Error::js("failed to sign", e))?;
+        let signature = Uint8Array::new(&signature).to_vec();
+
+        match self.key_type {
+            // The WebCrypto API produces the raw `r || s` encoding, libp2p uses DER.
+            #[cfg(feature = "ecdsa")]
+            KeyType::Ecdsa => p256::ecdsa::Signature::from_slice(&signature)
+                .map(|s| s.to_der().as_bytes().to_vec())
+                .map_err(|e| Error::new(format!("invalid ECDSA signature: {e}"))),
+            _ => Ok(signature),
+        }
+    }
+}
+
+/// An error of the WebCrypto API.
+#[derive(Debug)]
+pub struct Error {
+    msg: String,
+}
+
+impl Error {
+    fn new(msg: impl Into<String>) -> Self {
+        Self { msg: msg.into() }
+    }
+
+    fn js(what: &'static str, error: JsValue) -> Self {
+        Self {
+            msg: format!("{what}: {error:?}"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WebCrypto error: {}", self.msg)
+    }
+}
+
+impl error::Error for Error {}
+
+/// Returns the `SubtleCrypto` interface of the current global scope, i.e. window or worker.
+fn subtle() -> Result<SubtleCrypto, Error> {
+    let crypto = Reflect::get(&js_sys::global(), &JsValue::from_str("crypto"))
+        .map_err(|e| Error::js("failed to access the WebCrypto API", e))?;
+    if crypto.is_undefined() {
+        return Err(Error::new("the WebCrypto API is not available"));
+    }
+
+    Ok(crypto.unchecked_into::<Crypto>().subtle())
+}
+
+fn usages(usages: &[&str]) -> JsValue {
+    usages
+        .iter()
+        .map(|usage| JsValue::from_str(usage))
+        .collect::<Array>()
+        .into()
+}
+
+/// Awaits the generation of a key pair and exports its private key in PKCS#8 encoding.
+async fn export_private_key(promise: js_sys::Promise) -> Result<Vec<u8>, Error> {
+    let pair = JsFuture::from(promise)
+        .await
+        .map_err(|e| Error::js("failed to generate key", e))?;
+    let private_key: CryptoKey = Reflect::get(&pair, &JsValue::from_str("privateKey"))
+        .map_err(|e| Error::js("failed to access generated key", e))?
+        .unchecked_into();
+    let promise = subtle()?
+        .export_key("pkcs8", &private_key)
+        .map_err(|e| Error::js("failed to export key", e))?;
+    let pkcs8 = JsFuture::from(promise)
+        .await
+        .map_err(|e| Error::js("failed to export key", e))?;
+
+    Ok(Uint8Array::new(&pkcs8).to_vec())
+}
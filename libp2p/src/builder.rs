@@ -1,6 +1,9 @@
 use std::marker::PhantomData;
 
+pub use profile::SwarmProfile;
+
 mod phase;
+mod profile;
 mod select_muxer;
 mod select_security;
 
@@ -279,6 +282,39 @@ mod tests {
             .build();
     }
 
+    #[test]
+    #[cfg(all(
+        feature = "tokio",
+        feature = "tcp",
+        feature = "tls",
+        feature = "noise",
+        feature = "yamux",
+        feature = "quic"
+    ))]
+    fn tcp_quic_profile() {
+        for profile in [
+            crate::SwarmProfile::Mobile,
+            crate::SwarmProfile::Server,
+            crate::SwarmProfile::Browser,
+        ] {
+            let _ = SwarmBuilder::with_new_identity()
+                .with_tokio()
+                .with_tcp(
+                    profile.tcp_config(),
+                    (libp2p_tls::Config::new, libp2p_noise::Config::new),
+                    libp2p_yamux::Config::default,
+                )
+                .unwrap()
+                .with_quic_config(|config| profile.quic_config(config))
+                .with_behaviour(|_| {
+                    libp2p_connection_limits::Behaviour::new(profile.connection_limits())
+                })
+                .unwrap()
+                .with_swarm_profile(profile, |config| config)
+                .build();
+        }
+    }
+
     #[test]
     #[cfg(all(
         feature = "async-std",
@@ -9,10 +9,9 @@ pub struct BuildPhase<T, B> {
     pub(crate) behaviour: B,
     pub(crate) transport: T,
     pub(crate) swarm_config: libp2p_swarm::Config,
+    pub(crate) connection_timeout: std::time::Duration,
 }
 
-const CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
-
 impl<Provider, T: AuthenticatedMultiplexedTransport, B: libp2p_swarm::NetworkBehaviour>
     SwarmBuilder<Provider, BuildPhase<T, B>>
 {
@@ -20,7 +19,7 @@ impl<Provider, T: AuthenticatedMultiplexedTransport, B: libp2p_swarm::NetworkBeh
         Swarm::new(
             libp2p_core::transport::timeout::TransportTimeout::new(
                 self.phase.transport,
-                CONNECTION_TIMEOUT,
+                self.phase.connection_timeout,
             )
             .boxed(),
             self.phase.behaviour,
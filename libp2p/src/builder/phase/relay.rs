@@ -1,7 +1,6 @@
 use std::marker::PhantomData;
 
 #[cfg(feature = "relay")]
-use libp2p_core::muxing::StreamMuxerBox;
 use libp2p_core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade};
 #[cfg(feature = "relay")]
 use libp2p_core::Transport;
@@ -83,7 +82,7 @@ impl<Provider, T: AuthenticatedMultiplexedTransport> SwarmBuilder<Provider, Rela
             .upgrade(libp2p_core::upgrade::Version::V1Lazy)
             .authenticate(security_upgrade.into_security_upgrade(&self.keypair)?)
             .multiplex(multiplexer_upgrade.into_multiplexer_upgrade())
-            .map(|(p, c), _| (p, StreamMuxerBox::new(c)));
+            .box_muxer();
 
         Ok(SwarmBuilder {
             phase: BandwidthLoggingPhase {
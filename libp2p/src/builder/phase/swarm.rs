@@ -7,6 +7,9 @@ pub struct SwarmPhase<T, B> {
     pub(crate) transport: T,
 }
 
+#[cfg(any(feature = "async-std", feature = "tokio", feature = "wasm-bindgen"))]
+const CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 macro_rules! impl_with_swarm_config {
     ($providerKebabCase:literal, $providerPascalCase:ty, $config:expr) => {
         #[cfg(feature = $providerKebabCase)]
@@ -20,6 +23,27 @@ macro_rules! impl_with_swarm_config {
                         behaviour: self.phase.behaviour,
                         transport: self.phase.transport,
                         swarm_config: constructor($config),
+                        connection_timeout: CONNECTION_TIMEOUT,
+                    },
+                    keypair: self.keypair,
+                    phantom: std::marker::PhantomData,
+                }
+            }
+
+            /// Configures the [`Swarm`](libp2p_swarm::Swarm) and the connection timeout of the
+            /// transport according to the given [`SwarmProfile`](crate::SwarmProfile). The
+            /// profile can be overridden in the given constructor.
+            pub fn with_swarm_profile(
+                self,
+                profile: crate::SwarmProfile,
+                constructor: impl FnOnce(libp2p_swarm::Config) -> libp2p_swarm::Config,
+            ) -> SwarmBuilder<$providerPascalCase, BuildPhase<T, B>> {
+                SwarmBuilder {
+                    phase: BuildPhase {
+                        behaviour: self.phase.behaviour,
+                        transport: self.phase.transport,
+                        swarm_config: constructor(profile.swarm_config($config)),
+                        connection_timeout: profile.connection_timeout(),
                     },
                     keypair: self.keypair,
                     phantom: std::marker::PhantomData,
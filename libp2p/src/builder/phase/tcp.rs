@@ -4,7 +4,7 @@ use crate::SwarmBuilder;
     not(target_arch = "wasm32"),
     any(feature = "tcp", feature = "websocket")
 ))]
-use libp2p_core::muxing::{StreamMuxer, StreamMuxerBox};
+use libp2p_core::muxing::StreamMuxer;
 #[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
 use libp2p_core::Transport;
 #[cfg(all(
@@ -85,7 +85,7 @@ macro_rules! impl_tcp_builder {
                                 security_upgrade.into_security_upgrade(&self.keypair)?,
                             )
                             .multiplex(multiplexer_upgrade.into_multiplexer_upgrade())
-                            .map(|(p, c), _| (p, StreamMuxerBox::new(c))),
+                            .box_muxer(),
                     },
                     keypair: self.keypair,
                     phantom: PhantomData,
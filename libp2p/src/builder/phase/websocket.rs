@@ -1,7 +1,7 @@
 use super::*;
 use crate::SwarmBuilder;
 #[cfg(all(not(target_arch = "wasm32"), feature = "websocket"))]
-use libp2p_core::muxing::{StreamMuxer, StreamMuxerBox};
+use libp2p_core::muxing::StreamMuxer;
 use libp2p_core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade};
 #[cfg(all(not(target_arch = "wasm32"), feature = "websocket"))]
 use libp2p_core::Transport;
@@ -97,7 +97,7 @@ macro_rules! impl_websocket_builder {
                     .upgrade(libp2p_core::upgrade::Version::V1Lazy)
                     .authenticate(security_upgrade)
                     .multiplex(multiplexer_upgrade.into_multiplexer_upgrade())
-                    .map(|(p, c), _| (p, StreamMuxerBox::new(c)));
+                    .box_muxer();
 
                 Ok(SwarmBuilder {
                     keypair: self.keypair,
@@ -0,0 +1,139 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::{
+    num::{NonZeroU8, NonZeroUsize},
+    time::Duration,
+};
+
+/// Tuned defaults for common deployment classes.
+///
+/// A profile configures the [`Swarm`](libp2p_swarm::Swarm) via
+/// [`SwarmBuilder::with_swarm_profile`](crate::SwarmBuilder), and provides the matching connection
+/// limits and transport configurations. All of them can be overridden afterwards.
+///
+/// ```
+/// # use libp2p::{connection_limits, SwarmBuilder, SwarmProfile};
+/// # use std::time::Duration;
+/// #
+/// # #[cfg(all(not(target_arch = "wasm32"), feature = "tokio", feature = "tcp", feature = "noise", feature = "yamux"))]
+/// # fn build_swarm() -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = SwarmProfile::Mobile;
+/// let swarm = SwarmBuilder::with_new_identity()
+///     .with_tokio()
+///     .with_tcp(
+///         profile.tcp_config(),
+///         libp2p_noise::Config::new,
+///         libp2p_yamux::Config::default,
+///     )?
+///     .with_behaviour(|_| connection_limits::Behaviour::new(profile.connection_limits()))?
+///     .with_swarm_profile(profile, |cfg| {
+///         // Override the profile here.
+///         cfg.with_idle_connection_timeout(Duration::from_secs(30))
+///     })
+///     .build();
+/// #     Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwarmProfile {
+    /// Nodes on mobile devices, with few connections over unreliable networks and a limited
+    /// battery. Idle connections are closed quickly and timeouts are generous.
+    Mobile,
+    /// Publicly reachable nodes with many long-lived connections, e.g. bootstrap or relay nodes.
+    Server,
+    /// Nodes in browsers, which are limited to few connections over browser transports.
+    Browser,
+}
+
+impl SwarmProfile {
+    /// Applies the profile to the given [`libp2p_swarm::Config`].
+    pub fn swarm_config(self, config: libp2p_swarm::Config) -> libp2p_swarm::Config {
+        let (idle_timeout, dial_concurrency, negotiating_inbound_streams, notify_buffer) =
+            match self {
+                SwarmProfile::Mobile => (Duration::from_secs(10), 2, 32, 8),
+                SwarmProfile::Server => (Duration::from_secs(60), 8, 256, 32),
+                SwarmProfile::Browser => (Duration::from_secs(30), 2, 16, 8),
+            };
+
+        config
+            .with_idle_connection_timeout(idle_timeout)
+            .with_dial_concurrency_factor(NonZeroU8::new(dial_concurrency).expect("> 0"))
+            .with_max_negotiating_inbound_streams(negotiating_inbound_streams)
+            .with_notify_handler_buffer_size(NonZeroUsize::new(notify_buffer).expect("> 0"))
+            .with_substream_open_timeout(self.connection_timeout())
+    }
+
+    /// The timeout of establishing and upgrading a connection, applied by the [`SwarmBuilder`]
+    /// to the transport.
+    ///
+    /// [`SwarmBuilder`]: crate::SwarmBuilder
+    pub fn connection_timeout(self) -> Duration {
+        match self {
+            SwarmProfile::Mobile | SwarmProfile::Browser => Duration::from_secs(20),
+            SwarmProfile::Server => Duration::from_secs(10),
+        }
+    }
+
+    /// The connection limits of the profile, to be enforced by a
+    /// [`connection_limits::Behaviour`](libp2p_connection_limits::Behaviour).
+    pub fn connection_limits(self) -> libp2p_connection_limits::ConnectionLimits {
+        let (pending_incoming, pending_outgoing, established, established_per_peer) = match self {
+            SwarmProfile::Mobile => (8, 8, 32, 1),
+            SwarmProfile::Server => (256, 128, 1024, 4),
+            SwarmProfile::Browser => (4, 4, 16, 1),
+        };
+
+        libp2p_connection_limits::ConnectionLimits::default()
+            .with_max_pending_incoming(Some(pending_incoming))
+            .with_max_pending_outgoing(Some(pending_outgoing))
+            .with_max_established(Some(established))
+            .with_max_established_per_peer(Some(established_per_peer))
+    }
+
+    /// The TCP transport configuration of the profile.
+    #[cfg(all(not(target_arch = "wasm32"), feature = "tcp"))]
+    pub fn tcp_config(self) -> libp2p_tcp::Config {
+        let config = libp2p_tcp::Config::default().nodelay(true);
+        match self {
+            SwarmProfile::Server => config.listen_backlog(4096),
+            SwarmProfile::Mobile | SwarmProfile::Browser => config.listen_backlog(64),
+        }
+    }
+
+    /// Applies the profile to the given QUIC transport configuration, e.g. via
+    /// [`SwarmBuilder::with_quic_config`](crate::SwarmBuilder).
+    #[cfg(all(not(target_arch = "wasm32"), feature = "quic"))]
+    pub fn quic_config(self, mut config: libp2p_quic::Config) -> libp2p_quic::Config {
+        match self {
+            SwarmProfile::Mobile | SwarmProfile::Browser => {
+                // Fewer keep-alive packets save battery, longer timeouts cope with lossy networks.
+                config.handshake_timeout = self.connection_timeout() / 2;
+                config.max_idle_timeout = 30 * 1000;
+                config.keep_alive_interval = Duration::from_secs(15);
+                config.max_concurrent_stream_limit = 64;
+            }
+            SwarmProfile::Server => {
+                config.max_concurrent_stream_limit = 1024;
+            }
+        }
+        config
+    }
+}
@@ -84,6 +84,12 @@ pub use libp2p_metrics as metrics;
 #[cfg(feature = "noise")]
 #[doc(inline)]
 pub use libp2p_noise as noise;
+#[cfg(feature = "peer-sampling")]
+#[doc(inline)]
+pub use libp2p_peer_sampling as peer_sampling;
+#[cfg(feature = "peer-store")]
+#[doc(inline)]
+pub use libp2p_peer_store as peer_store;
 #[cfg(feature = "ping")]
 #[doc(inline)]
 pub use libp2p_ping as ping;
@@ -105,6 +111,9 @@ pub use libp2p_rendezvous as rendezvous;
 #[cfg(feature = "request-response")]
 #[doc(inline)]
 pub use libp2p_request_response as request_response;
+#[cfg(feature = "service-discovery")]
+#[doc(inline)]
+pub use libp2p_service_discovery as service_discovery;
 #[doc(inline)]
 pub use libp2p_swarm as swarm;
 #[cfg(feature = "tcp")]
@@ -149,7 +158,7 @@ pub mod bandwidth;
 #[cfg(doc)]
 pub mod tutorials;
 
-pub use self::builder::SwarmBuilder;
+pub use self::builder::{SwarmBuilder, SwarmProfile};
 pub use self::core::{
     transport::TransportError,
     upgrade::{InboundUpgrade, OutboundUpgrade},
@@ -61,21 +61,25 @@
 //! # }
 //! ```
 
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade, UpgradeInfo};
 use libp2p_core::{Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::{
     dummy, CloseConnection, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler,
     THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
+use parking_lot::RwLock;
 use std::collections::{HashSet, VecDeque};
-use std::fmt;
+use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
+use std::{fmt, iter, option};
 use void::Void;
 
 /// A [`NetworkBehaviour`] that can act as an allow or block list.
 #[derive(Default, Debug)]
 pub struct Behaviour<S> {
-    state: S,
+    state: Arc<RwLock<S>>,
     close_connections: VecDeque<PeerId>,
     waker: Option<Waker>,
 }
@@ -95,7 +99,7 @@ pub struct BlockedPeers {
 impl Behaviour<AllowedPeers> {
     /// Allow connections to the given peer.
     pub fn allow_peer(&mut self, peer: PeerId) {
-        self.state.peers.insert(peer);
+        self.state.write().peers.insert(peer);
         if let Some(waker) = self.waker.take() {
             waker.wake()
         }
@@ -105,7 +109,7 @@ impl Behaviour<AllowedPeers> {
     ///
     /// All active connections to this peer will be closed immediately.
     pub fn disallow_peer(&mut self, peer: PeerId) {
-        self.state.peers.remove(&peer);
+        self.state.write().peers.remove(&peer);
         self.close_connections.push_back(peer);
         if let Some(waker) = self.waker.take() {
             waker.wake()
@@ -118,7 +122,7 @@ impl Behaviour<BlockedPeers> {
     ///
     /// All active connections to this peer will be closed immediately.
     pub fn block_peer(&mut self, peer: PeerId) {
-        self.state.peers.insert(peer);
+        self.state.write().peers.insert(peer);
         self.close_connections.push_back(peer);
         if let Some(waker) = self.waker.take() {
             waker.wake()
@@ -127,13 +131,41 @@ impl Behaviour<BlockedPeers> {
 
     /// Unblock connections to a given peer.
     pub fn unblock_peer(&mut self, peer: PeerId) {
-        self.state.peers.remove(&peer);
+        self.state.write().peers.remove(&peer);
         if let Some(waker) = self.waker.take() {
             waker.wake()
         }
     }
 }
 
+impl<S> Behaviour<S> {
+    /// Returns a [`PeerFilter`] enforcing the list on the connections of a transport before a
+    /// stream multiplexer is negotiated.
+    ///
+    /// The list is otherwise only enforced once a connection is fully established for dials whose
+    /// peer id is unknown, i.e. given neither in the dial options nor as a `/p2p` suffix of the
+    /// addresses, so the remote may briefly consider the connection established.
+    /// Connections to denied peers instead fail to negotiate a stream multiplexer, e.g.:
+    ///
+    /// ```rust
+    /// # use libp2p_allow_block_list::{Behaviour, BlockedPeers};
+    /// # use libp2p_core::{transport::MemoryTransport, upgrade::Version, Transport};
+    /// # use libp2p_identity::Keypair;
+    /// let behaviour = Behaviour::<BlockedPeers>::default();
+    /// let filter = behaviour.peer_filter();
+    /// let transport = MemoryTransport::default()
+    ///     .upgrade(Version::V1)
+    ///     .authenticate(libp2p_plaintext::Config::new(&Keypair::generate_ed25519()))
+    ///     .multiplex_ext(move |peer, _| filter.multiplex(peer, libp2p_yamux::Config::default()))
+    ///     .boxed();
+    /// ```
+    pub fn peer_filter(&self) -> PeerFilter<S> {
+        PeerFilter {
+            state: self.state.clone(),
+        }
+    }
+}
+
 /// A connection to this peer is not explicitly allowed and was thus [`denied`](ConnectionDenied).
 #[derive(Debug)]
 pub struct NotAllowed {
@@ -186,6 +218,98 @@ impl Enforce for BlockedPeers {
     }
 }
 
+/// A handle to the list of a [`Behaviour`] enforcing it on the connections of a transport as soon
+/// as the remote is authenticated, see [`Behaviour::peer_filter`].
+pub struct PeerFilter<S> {
+    state: Arc<RwLock<S>>,
+}
+
+impl<S> Clone for PeerFilter<S> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl PeerFilter<AllowedPeers> {
+    /// Wraps the stream multiplexer upgrade of a connection to the given authenticated peer,
+    /// failing its negotiation if the peer is not allowed.
+    ///
+    /// To be applied via [`Authenticated::multiplex_ext`](libp2p_core::transport::upgrade::Authenticated::multiplex_ext).
+    pub fn multiplex<U>(&self, peer: &PeerId, upgrade: U) -> FilteredUpgrade<U> {
+        filter(&self.state, peer, upgrade)
+    }
+}
+
+impl PeerFilter<BlockedPeers> {
+    /// Wraps the stream multiplexer upgrade of a connection to the given authenticated peer,
+    /// failing its negotiation if the peer is blocked.
+    ///
+    /// To be applied via [`Authenticated::multiplex_ext`](libp2p_core::transport::upgrade::Authenticated::multiplex_ext).
+    pub fn multiplex<U>(&self, peer: &PeerId, upgrade: U) -> FilteredUpgrade<U> {
+        filter(&self.state, peer, upgrade)
+    }
+}
+
+fn filter<S, U>(state: &RwLock<S>, peer: &PeerId, upgrade: U) -> FilteredUpgrade<U>
+where
+    S: Enforce,
+{
+    FilteredUpgrade {
+        inner: upgrade,
+        denied: state.read().enforce(peer).is_err(),
+    }
+}
+
+/// A connection upgrade negotiating no protocol if the remote is denied by a [`PeerFilter`].
+#[derive(Debug, Clone)]
+pub struct FilteredUpgrade<U> {
+    inner: U,
+    denied: bool,
+}
+
+impl<U> UpgradeInfo for FilteredUpgrade<U>
+where
+    U: UpgradeInfo,
+{
+    type Info = U::Info;
+    type InfoIter = iter::Flatten<option::IntoIter<U::InfoIter>>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        (!self.denied)
+            .then(|| self.inner.protocol_info())
+            .into_iter()
+            .flatten()
+    }
+}
+
+impl<C, U> InboundConnectionUpgrade<C> for FilteredUpgrade<U>
+where
+    U: InboundConnectionUpgrade<C>,
+{
+    type Output = U::Output;
+    type Error = U::Error;
+    type Future = U::Future;
+
+    fn upgrade_inbound(self, socket: C, info: Self::Info) -> Self::Future {
+        self.inner.upgrade_inbound(socket, info)
+    }
+}
+
+impl<C, U> OutboundConnectionUpgrade<C> for FilteredUpgrade<U>
+where
+    U: OutboundConnectionUpgrade<C>,
+{
+    type Output = U::Output;
+    type Error = U::Error;
+    type Future = U::Future;
+
+    fn upgrade_outbound(self, socket: C, info: Self::Info) -> Self::Future {
+        self.inner.upgrade_outbound(socket, info)
+    }
+}
+
 impl<S> NetworkBehaviour for Behaviour<S>
 where
     S: Enforce,
@@ -200,7 +324,7 @@ where
         _: &Multiaddr,
         _: &Multiaddr,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        self.state.enforce(&peer)?;
+        self.state.read().enforce(&peer)?;
 
         Ok(dummy::ConnectionHandler)
     }
@@ -209,11 +333,18 @@ where
         &mut self,
         _: ConnectionId,
         peer: Option<PeerId>,
-        _: &[Multiaddr],
+        addresses: &[Multiaddr],
         _: Endpoint,
     ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        let state = self.state.read();
         if let Some(peer) = peer {
-            self.state.enforce(&peer)?;
+            state.enforce(&peer)?;
+        }
+        // Dials without a peer id may still name the peer in the addresses.
+        for address in addresses {
+            if let Some(Protocol::P2p(peer)) = address.iter().last() {
+                state.enforce(&peer)?;
+            }
         }
 
         Ok(vec![])
@@ -226,7 +357,7 @@ where
         _: &Multiaddr,
         _: Endpoint,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        self.state.enforce(&peer)?;
+        self.state.read().enforce(&peer)?;
 
         Ok(dummy::ConnectionHandler)
     }
@@ -261,6 +392,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use libp2p_core::{transport::MemoryTransport, upgrade::Version, Transport};
+    use libp2p_identity::Keypair;
     use libp2p_swarm::{dial_opts::DialOpts, DialError, ListenError, Swarm, SwarmEvent};
     use libp2p_swarm_test::SwarmExt;
 
@@ -278,6 +411,68 @@ mod tests {
         assert!(cause.downcast::<Blocked>().is_ok());
     }
 
+    #[async_std::test]
+    async fn cannot_dial_address_of_blocked_peer() {
+        let mut dialer = Swarm::new_ephemeral(|_| Behaviour::<BlockedPeers>::default());
+        let mut listener = Swarm::new_ephemeral(|_| Behaviour::<BlockedPeers>::default());
+        listener.listen().with_memory_addr_external().await;
+        let address = listener
+            .external_addresses()
+            .next()
+            .cloned()
+            .unwrap()
+            .with_p2p(*listener.local_peer_id())
+            .unwrap();
+
+        dialer.behaviour_mut().block_peer(*listener.local_peer_id());
+
+        let DialError::Denied { cause } = dialer
+            .dial(DialOpts::unknown_peer_id().address(address).build())
+            .unwrap_err()
+        else {
+            panic!("unexpected dial error")
+        };
+        assert!(cause.downcast::<Blocked>().is_ok());
+    }
+
+    #[async_std::test]
+    async fn filtered_connection_to_blocked_peer_fails_before_multiplexing() {
+        let identity = Keypair::generate_ed25519();
+        let behaviour = Behaviour::<BlockedPeers>::default();
+        let filter = behaviour.peer_filter();
+        let transport = MemoryTransport::default()
+            .upgrade(Version::V1)
+            .authenticate(libp2p_plaintext::Config::new(&identity))
+            .multiplex_ext(move |peer, _| filter.multiplex(peer, libp2p_yamux::Config::default()))
+            .boxed();
+        let mut dialer = Swarm::new(
+            transport,
+            behaviour,
+            identity.public().to_peer_id(),
+            libp2p_swarm::Config::with_async_std_executor(),
+        );
+        let mut listener = Swarm::new_ephemeral(|_| Behaviour::<BlockedPeers>::default());
+        listener.listen().with_memory_addr_external().await;
+        let address = listener.external_addresses().next().cloned().unwrap();
+
+        dialer.behaviour_mut().block_peer(*listener.local_peer_id());
+        dialer
+            .dial(DialOpts::unknown_peer_id().address(address).build())
+            .unwrap();
+
+        // Neither side considers the connection established.
+        let (
+            [SwarmEvent::OutgoingConnectionError {
+                error: DialError::Transport(_),
+                ..
+            }],
+            [SwarmEvent::IncomingConnection { .. }, SwarmEvent::IncomingConnectionError { .. }],
+        ) = libp2p_swarm_test::drive(&mut dialer, &mut listener).await
+        else {
+            panic!("unexpected events")
+        };
+    }
+
     #[async_std::test]
     async fn can_dial_unblocked_peer() {
         let mut dialer = Swarm::new_ephemeral(|_| Behaviour::<BlockedPeers>::default());
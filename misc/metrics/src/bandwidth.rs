@@ -1,4 +1,4 @@
-use crate::protocol_stack;
+use crate::{protocol_stack, PeerTraffic};
 use futures::{
     future::{MapOk, TryFutureExt},
     io::{IoSlice, IoSliceMut},
@@ -29,6 +29,7 @@ pub struct Transport<T> {
     #[pin]
     transport: T,
     metrics: Family<Labels, Counter>,
+    peer_traffic: Option<PeerTraffic>,
 }
 
 impl<T> Transport<T> {
@@ -43,7 +44,17 @@ impl<T> Transport<T> {
                 metrics.clone(),
             );
 
-        Transport { transport, metrics }
+        Transport {
+            transport,
+            metrics,
+            peer_traffic: None,
+        }
+    }
+
+    /// Additionally record the bandwidth usage of each connection in the given [`PeerTraffic`].
+    pub fn with_peer_traffic(mut self, peer_traffic: PeerTraffic) -> Self {
+        self.peer_traffic = Some(peer_traffic);
+        self
     }
 }
 
@@ -86,10 +97,12 @@ where
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
         let metrics = ConnectionMetrics::from_family_and_addr(&self.metrics, &addr);
+        let peer_traffic = self.peer_traffic.clone();
         Ok(self
             .transport
             .dial(addr.clone())?
             .map_ok(Box::new(|(peer_id, stream_muxer)| {
+                let metrics = metrics.with_peer_traffic(peer_id, peer_traffic);
                 (peer_id, Muxer::new(stream_muxer, metrics))
             })))
     }
@@ -99,10 +112,12 @@ where
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>> {
         let metrics = ConnectionMetrics::from_family_and_addr(&self.metrics, &addr);
+        let peer_traffic = self.peer_traffic.clone();
         Ok(self
             .transport
             .dial_as_listener(addr.clone())?
             .map_ok(Box::new(|(peer_id, stream_muxer)| {
+                let metrics = metrics.with_peer_traffic(peer_id, peer_traffic);
                 (peer_id, Muxer::new(stream_muxer, metrics))
             })))
     }
@@ -125,9 +140,11 @@ where
             }) => {
                 let metrics =
                     ConnectionMetrics::from_family_and_addr(this.metrics, &send_back_addr);
+                let peer_traffic = this.peer_traffic.clone();
                 Poll::Ready(TransportEvent::Incoming {
                     listener_id,
                     upgrade: upgrade.map_ok(Box::new(|(peer_id, stream_muxer)| {
+                        let metrics = metrics.with_peer_traffic(peer_id, peer_traffic);
                         (peer_id, Muxer::new(stream_muxer, metrics))
                     })),
                     local_addr,
@@ -147,6 +164,7 @@ where
 struct ConnectionMetrics {
     outbound: Counter,
     inbound: Counter,
+    peer_traffic: Option<(PeerId, PeerTraffic)>,
 }
 
 impl ConnectionMetrics {
@@ -169,7 +187,32 @@ impl ConnectionMetrics {
             });
             m.clone()
         };
-        ConnectionMetrics { outbound, inbound }
+        ConnectionMetrics {
+            outbound,
+            inbound,
+            peer_traffic: None,
+        }
+    }
+
+    fn with_peer_traffic(mut self, peer_id: PeerId, peer_traffic: Option<PeerTraffic>) -> Self {
+        self.peer_traffic = peer_traffic.map(|p| (peer_id, p));
+        self
+    }
+
+    fn inc_inbound(&self, num_bytes: usize) {
+        let num_bytes = u64::try_from(num_bytes).unwrap_or(u64::MAX);
+        self.inbound.inc_by(num_bytes);
+        if let Some((peer_id, peer_traffic)) = &self.peer_traffic {
+            peer_traffic.record_inbound_bytes(*peer_id, num_bytes);
+        }
+    }
+
+    fn inc_outbound(&self, num_bytes: usize) {
+        let num_bytes = u64::try_from(num_bytes).unwrap_or(u64::MAX);
+        self.outbound.inc_by(num_bytes);
+        if let Some((peer_id, peer_traffic)) = &self.peer_traffic {
+            peer_traffic.record_outbound_bytes(*peer_id, num_bytes);
+        }
     }
 }
 
@@ -253,9 +296,7 @@ impl<SMInner: AsyncRead> AsyncRead for InstrumentedStream<SMInner> {
     ) -> Poll<io::Result<usize>> {
         let this = self.project();
         let num_bytes = ready!(this.inner.poll_read(cx, buf))?;
-        this.metrics
-            .inbound
-            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        this.metrics.inc_inbound(num_bytes);
         Poll::Ready(Ok(num_bytes))
     }
 
@@ -266,9 +307,7 @@ impl<SMInner: AsyncRead> AsyncRead for InstrumentedStream<SMInner> {
     ) -> Poll<io::Result<usize>> {
         let this = self.project();
         let num_bytes = ready!(this.inner.poll_read_vectored(cx, bufs))?;
-        this.metrics
-            .inbound
-            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        this.metrics.inc_inbound(num_bytes);
         Poll::Ready(Ok(num_bytes))
     }
 }
@@ -281,9 +320,7 @@ impl<SMInner: AsyncWrite> AsyncWrite for InstrumentedStream<SMInner> {
     ) -> Poll<io::Result<usize>> {
         let this = self.project();
         let num_bytes = ready!(this.inner.poll_write(cx, buf))?;
-        this.metrics
-            .outbound
-            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        this.metrics.inc_outbound(num_bytes);
         Poll::Ready(Ok(num_bytes))
     }
 
@@ -294,9 +331,7 @@ impl<SMInner: AsyncWrite> AsyncWrite for InstrumentedStream<SMInner> {
     ) -> Poll<io::Result<usize>> {
         let this = self.project();
         let num_bytes = ready!(this.inner.poll_write_vectored(cx, bufs))?;
-        this.metrics
-            .outbound
-            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        this.metrics.inc_outbound(num_bytes);
         Poll::Ready(Ok(num_bytes))
     }
 
@@ -119,6 +119,9 @@ impl super::Recorder<libp2p_identify::Event> for Metrics {
             libp2p_identify::Event::Sent { .. } => {
                 self.sent.inc();
             }
+            libp2p_identify::Event::ObservedAddrConfirmed { .. }
+            | libp2p_identify::Event::PushRateLimited { .. }
+            | libp2p_identify::Event::ProtocolsChanged { .. } => {}
         }
     }
 }
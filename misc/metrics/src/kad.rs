@@ -271,6 +271,7 @@ enum QueryType {
     StartProviding,
     RepublishProvider,
     GetRecord,
+    GetRecords,
     PutRecord,
     RepublishRecord,
 }
@@ -296,6 +297,9 @@ impl From<&libp2p_kad::QueryResult> for QueryResult {
             libp2p_kad::QueryResult::GetRecord(_) => QueryResult {
                 r#type: QueryType::GetRecord,
             },
+            libp2p_kad::QueryResult::GetRecords(_) => QueryResult {
+                r#type: QueryType::GetRecords,
+            },
             libp2p_kad::QueryResult::PutRecord(_) => QueryResult {
                 r#type: QueryType::PutRecord,
             },
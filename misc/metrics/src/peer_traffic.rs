@@ -0,0 +1,353 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::Recorder;
+use libp2p_identity::PeerId;
+use libp2p_swarm::SwarmEvent;
+use prometheus_client::collector::Collector;
+use prometheus_client::encoding::{DescriptorEncoder, EncodeMetric};
+use prometheus_client::metrics::counter::ConstCounter;
+use prometheus_client::metrics::gauge::ConstGauge;
+use prometheus_client::metrics::MetricType;
+use prometheus_client::registry::{Registry, Unit};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Per-peer traffic metrics with bounded cardinality.
+///
+/// Tracks the number of bytes and messages exchanged with each connected peer,
+/// but only exports per-peer series for the `top_n` peers with the most traffic.
+/// The set of exported peers is re-evaluated at most once per refresh interval.
+/// Traffic of all peers, including disconnected ones, is additionally exported
+/// in aggregate.
+///
+/// Bytes are recorded automatically when passed to
+/// [`BandwidthTransport::with_peer_traffic`](crate::BandwidthTransport::with_peer_traffic).
+/// Messages have to be recorded by the application, e.g. on protocol events.
+/// Peers are forgotten when recording a [`SwarmEvent::ConnectionClosed`] for
+/// their last connection or when calling [`PeerTraffic::remove_peer`].
+///
+/// ```
+/// use prometheus_client::registry::Registry;
+/// use libp2p_metrics::PeerTraffic;
+/// use std::time::Duration;
+/// let mut registry = Registry::default();
+/// let peer_traffic = PeerTraffic::new(&mut registry, 10, Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PeerTraffic(Arc<Mutex<Inner>>);
+
+#[derive(Debug)]
+struct Inner {
+    top_n: usize,
+    refresh_interval: Duration,
+    last_refresh: Option<Instant>,
+    /// The peers currently exported individually.
+    top: Vec<PeerId>,
+    peers: HashMap<PeerId, Traffic>,
+    /// Traffic of peers that have since been removed.
+    removed: Traffic,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Traffic {
+    inbound_bytes: u64,
+    outbound_bytes: u64,
+    inbound_messages: u64,
+    outbound_messages: u64,
+}
+
+impl Traffic {
+    fn total_bytes(&self) -> u64 {
+        self.inbound_bytes.saturating_add(self.outbound_bytes)
+    }
+
+    fn total_messages(&self) -> u64 {
+        self.inbound_messages.saturating_add(self.outbound_messages)
+    }
+
+    fn add(&mut self, other: &Traffic) {
+        self.inbound_bytes = self.inbound_bytes.saturating_add(other.inbound_bytes);
+        self.outbound_bytes = self.outbound_bytes.saturating_add(other.outbound_bytes);
+        self.inbound_messages = self.inbound_messages.saturating_add(other.inbound_messages);
+        self.outbound_messages = self
+            .outbound_messages
+            .saturating_add(other.outbound_messages);
+    }
+}
+
+impl PeerTraffic {
+    /// Create a new [`PeerTraffic`] exporting the `top_n` peers with the most traffic,
+    /// re-evaluated at most every `refresh_interval`.
+    pub fn new(registry: &mut Registry, top_n: usize, refresh_interval: Duration) -> Self {
+        let peer_traffic = PeerTraffic(Arc::new(Mutex::new(Inner {
+            top_n,
+            refresh_interval,
+            last_refresh: None,
+            top: Vec::new(),
+            peers: HashMap::new(),
+            removed: Traffic::default(),
+        })));
+
+        registry
+            .sub_registry_with_prefix("libp2p")
+            .sub_registry_with_prefix("peer_traffic")
+            .register_collector(Box::new(peer_traffic.clone()));
+
+        peer_traffic
+    }
+
+    /// Record `bytes` received from `peer`.
+    pub fn record_inbound_bytes(&self, peer: PeerId, bytes: u64) {
+        let mut inner = self.0.lock().unwrap();
+        let traffic = inner.peers.entry(peer).or_default();
+        traffic.inbound_bytes = traffic.inbound_bytes.saturating_add(bytes);
+    }
+
+    /// Record `bytes` sent to `peer`.
+    pub fn record_outbound_bytes(&self, peer: PeerId, bytes: u64) {
+        let mut inner = self.0.lock().unwrap();
+        let traffic = inner.peers.entry(peer).or_default();
+        traffic.outbound_bytes = traffic.outbound_bytes.saturating_add(bytes);
+    }
+
+    /// Record a message received from `peer`.
+    pub fn record_inbound_message(&self, peer: PeerId) {
+        let mut inner = self.0.lock().unwrap();
+        let traffic = inner.peers.entry(peer).or_default();
+        traffic.inbound_messages = traffic.inbound_messages.saturating_add(1);
+    }
+
+    /// Record a message sent to `peer`.
+    pub fn record_outbound_message(&self, peer: PeerId) {
+        let mut inner = self.0.lock().unwrap();
+        let traffic = inner.peers.entry(peer).or_default();
+        traffic.outbound_messages = traffic.outbound_messages.saturating_add(1);
+    }
+
+    /// Stop tracking `peer`, retaining its traffic in the aggregates only.
+    pub fn remove_peer(&self, peer: &PeerId) {
+        let mut inner = self.0.lock().unwrap();
+        if let Some(traffic) = inner.peers.remove(peer) {
+            inner.removed.add(&traffic);
+        }
+    }
+}
+
+impl Inner {
+    fn refresh_top(&mut self, now: Instant) {
+        let due = self.last_refresh.map_or(true, |last| {
+            now.duration_since(last) >= self.refresh_interval
+        });
+        if !due {
+            return;
+        }
+
+        let mut peers = self.peers.iter().collect::<Vec<_>>();
+        peers.sort_by(|(_, a), (_, b)| {
+            b.total_bytes()
+                .cmp(&a.total_bytes())
+                .then_with(|| b.total_messages().cmp(&a.total_messages()))
+        });
+        self.top = peers
+            .into_iter()
+            .take(self.top_n)
+            .map(|(peer, _)| *peer)
+            .collect();
+        self.last_refresh = Some(now);
+    }
+}
+
+impl Collector for PeerTraffic {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let mut inner = self.0.lock().unwrap();
+        inner.refresh_top(Instant::now());
+
+        let top = inner
+            .top
+            .iter()
+            .filter_map(|peer| {
+                inner
+                    .peers
+                    .get(peer)
+                    .map(|traffic| (peer.to_string(), traffic))
+            })
+            .collect::<Vec<_>>();
+
+        let mut aggregate = inner.removed;
+        for traffic in inner.peers.values() {
+            aggregate.add(traffic);
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "bandwidth",
+                "Bytes exchanged with the peers with the most traffic, by peer and direction",
+                Some(&Unit::Bytes),
+                MetricType::Counter,
+            )?;
+            for (peer, traffic) in &top {
+                for (direction, bytes) in [
+                    ("inbound", traffic.inbound_bytes),
+                    ("outbound", traffic.outbound_bytes),
+                ] {
+                    let labels = [("peer", peer.as_str()), ("direction", direction)];
+                    let metric_encoder = family_encoder.encode_family(&labels)?;
+                    ConstCounter::new(bytes).encode(metric_encoder)?;
+                }
+            }
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "messages",
+                "Messages exchanged with the peers with the most traffic, by peer and direction",
+                None,
+                MetricType::Counter,
+            )?;
+            for (peer, traffic) in &top {
+                for (direction, messages) in [
+                    ("inbound", traffic.inbound_messages),
+                    ("outbound", traffic.outbound_messages),
+                ] {
+                    let labels = [("peer", peer.as_str()), ("direction", direction)];
+                    let metric_encoder = family_encoder.encode_family(&labels)?;
+                    ConstCounter::new(messages).encode(metric_encoder)?;
+                }
+            }
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "aggregate_bandwidth",
+                "Bytes exchanged with all peers, by direction",
+                Some(&Unit::Bytes),
+                MetricType::Counter,
+            )?;
+            for (direction, bytes) in [
+                ("inbound", aggregate.inbound_bytes),
+                ("outbound", aggregate.outbound_bytes),
+            ] {
+                let labels = [("direction", direction)];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstCounter::new(bytes).encode(metric_encoder)?;
+            }
+        }
+
+        {
+            let mut family_encoder = encoder.encode_descriptor(
+                "aggregate_messages",
+                "Messages exchanged with all peers, by direction",
+                None,
+                MetricType::Counter,
+            )?;
+            for (direction, messages) in [
+                ("inbound", aggregate.inbound_messages),
+                ("outbound", aggregate.outbound_messages),
+            ] {
+                let labels = [("direction", direction)];
+                let metric_encoder = family_encoder.encode_family(&labels)?;
+                ConstCounter::new(messages).encode(metric_encoder)?;
+            }
+        }
+
+        {
+            let metric_encoder = encoder.encode_descriptor(
+                "tracked_peers",
+                "Number of peers traffic is currently tracked for",
+                None,
+                MetricType::Gauge,
+            )?;
+            ConstGauge::new(i64::try_from(inner.peers.len()).unwrap_or(i64::MAX))
+                .encode(metric_encoder)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<TBvEv> Recorder<SwarmEvent<TBvEv>> for PeerTraffic {
+    fn record(&self, event: &SwarmEvent<TBvEv>) {
+        if let SwarmEvent::ConnectionClosed {
+            peer_id,
+            num_established,
+            ..
+        } = event
+        {
+            if *num_established == 0 {
+                self.remove_peer(peer_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus_client::encoding::text::encode;
+
+    fn encoded(registry: &Registry) -> String {
+        let mut buffer = String::new();
+        encode(&mut buffer, registry).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn exports_only_top_n_peers() {
+        let mut registry = Registry::default();
+        let peer_traffic = PeerTraffic::new(&mut registry, 2, Duration::from_secs(60));
+
+        let peers = (0..3).map(|_| PeerId::random()).collect::<Vec<_>>();
+        peer_traffic.record_inbound_bytes(peers[0], 100);
+        peer_traffic.record_outbound_bytes(peers[1], 300);
+        peer_traffic.record_inbound_bytes(peers[2], 200);
+        peer_traffic.record_outbound_message(peers[0]);
+
+        let metrics = encoded(&registry);
+        assert!(!metrics.contains(&peers[0].to_string()));
+        assert!(metrics.contains(&peers[1].to_string()));
+        assert!(metrics.contains(&peers[2].to_string()));
+        assert!(metrics.contains(
+            "libp2p_peer_traffic_aggregate_bandwidth_bytes_total{direction=\"inbound\"} 300"
+        ));
+        assert!(metrics
+            .contains("libp2p_peer_traffic_aggregate_messages_total{direction=\"outbound\"} 1"));
+        assert!(metrics.contains("libp2p_peer_traffic_tracked_peers 3"));
+    }
+
+    #[test]
+    fn aggregates_retain_traffic_of_removed_peers() {
+        let mut registry = Registry::default();
+        let peer_traffic = PeerTraffic::new(&mut registry, 1, Duration::ZERO);
+
+        let peer = PeerId::random();
+        peer_traffic.record_outbound_bytes(peer, 42);
+        peer_traffic.remove_peer(&peer);
+
+        let metrics = encoded(&registry);
+        assert!(!metrics.contains(&peer.to_string()));
+        assert!(metrics.contains(
+            "libp2p_peer_traffic_aggregate_bandwidth_bytes_total{direction=\"outbound\"} 42"
+        ));
+        assert!(metrics.contains("libp2p_peer_traffic_tracked_peers 0"));
+    }
+}
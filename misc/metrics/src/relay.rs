@@ -60,6 +60,7 @@ enum EventType {
     CircuitReqAccepted,
     CircuitReqAcceptFailed,
     CircuitClosed,
+    LivenessCheckFailed,
 }
 
 impl From<&libp2p_relay::Event> for EventType {
@@ -87,6 +88,7 @@ impl From<&libp2p_relay::Event> for EventType {
             #[allow(deprecated)]
             libp2p_relay::Event::CircuitReqAcceptFailed { .. } => EventType::CircuitReqAcceptFailed,
             libp2p_relay::Event::CircuitClosed { .. } => EventType::CircuitClosed,
+            libp2p_relay::Event::LivenessCheckFailed { .. } => EventType::LivenessCheckFailed,
         }
     }
 }
@@ -36,6 +36,7 @@ pub(crate) struct Metrics {
 
     connections_established: Family<ConnectionLabels, Counter>,
     connections_establishment_duration: Family<ConnectionLabels, Histogram>,
+    connections_establishment_phase_duration: Family<ConnectionPhaseLabels, Histogram>,
     connections_duration: Family<ConnectionClosedLabels, Histogram>,
 
     new_listen_addr: Family<AddressLabels, Counter>,
@@ -153,6 +154,18 @@ impl Metrics {
             connections_establishment_duration.clone(),
         );
 
+        let connections_establishment_phase_duration = {
+            let constructor: fn() -> Histogram =
+                || Histogram::new(exponential_buckets(0.001, 1.5, 24));
+            Family::new_with_constructor(constructor)
+        };
+        sub_registry.register_with_unit(
+            "connections_establishment_phase_duration",
+            "Time the phases of establishing connections took",
+            Unit::Seconds,
+            connections_establishment_phase_duration.clone(),
+        );
+
         let connections_duration = {
             let constructor: fn() -> Histogram =
                 || Histogram::new(exponential_buckets(0.01, 3.0, 20));
@@ -179,6 +192,7 @@ impl Metrics {
             dial_attempt,
             outgoing_connection_error,
             connections_establishment_duration,
+            connections_establishment_phase_duration,
             connections_duration,
             connections: Default::default(),
         }
@@ -192,6 +206,7 @@ impl<TBvEv> super::Recorder<SwarmEvent<TBvEv>> for Metrics {
             SwarmEvent::ConnectionEstablished {
                 endpoint,
                 established_in: time_taken,
+                phases,
                 connection_id,
                 ..
             } => {
@@ -203,6 +218,21 @@ impl<TBvEv> super::Recorder<SwarmEvent<TBvEv>> for Metrics {
                 self.connections_establishment_duration
                     .get_or_create(&labels)
                     .observe(time_taken.as_secs_f64());
+                for (phase, duration) in [
+                    (ConnectionPhase::Transport, Some(phases.transport)),
+                    (ConnectionPhase::Security, phases.security),
+                    (ConnectionPhase::Muxer, phases.muxer),
+                ] {
+                    let Some(duration) = duration else {
+                        continue;
+                    };
+                    self.connections_establishment_phase_duration
+                        .get_or_create(&ConnectionPhaseLabels {
+                            phase,
+                            connection: labels.clone(),
+                        })
+                        .observe(duration.as_secs_f64());
+                }
                 self.connections
                     .lock()
                     .expect("lock not to be poisoned")
@@ -348,6 +378,20 @@ struct ConnectionLabels {
     protocols: String,
 }
 
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct ConnectionPhaseLabels {
+    phase: ConnectionPhase,
+    #[prometheus(flatten)]
+    connection: ConnectionLabels,
+}
+
+#[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
+enum ConnectionPhase {
+    Transport,
+    Security,
+    Muxer,
+}
+
 #[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
 struct ConnectionClosedLabels {
     cause: Option<ConnectionError>,
@@ -0,0 +1,666 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::codec::{Codec, Message, PeerInfo};
+use futures::FutureExt;
+use futures_timer::Delay;
+use libp2p_core::{Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_request_response::{self as request_response, OutboundRequestId, ProtocolSupport};
+use libp2p_swarm::{
+    ConnectionDenied, ConnectionId, ExternalAddresses, FromSwarm, NetworkBehaviour, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use rand::seq::IteratorRandom;
+use std::collections::{HashMap, VecDeque};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The configuration of the peer sampling [`Behaviour`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    active_view_capacity: usize,
+    passive_view_capacity: usize,
+    active_random_walk_length: u32,
+    passive_random_walk_length: u32,
+    shuffle_interval: Duration,
+    shuffle_active: usize,
+    shuffle_passive: usize,
+    request_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            active_view_capacity: 5,
+            passive_view_capacity: 30,
+            active_random_walk_length: 6,
+            passive_random_walk_length: 3,
+            shuffle_interval: Duration::from_secs(10),
+            shuffle_active: 3,
+            shuffle_passive: 4,
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Config {
+    /// Sets the maximum number of peers in the active view.
+    ///
+    /// * Default to 5.
+    pub fn with_active_view_capacity(mut self, capacity: usize) -> Self {
+        self.active_view_capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum number of peers in the passive view.
+    ///
+    /// * Default to 30.
+    pub fn with_passive_view_capacity(mut self, capacity: usize) -> Self {
+        self.passive_view_capacity = capacity;
+        self
+    }
+
+    /// Sets the number of hops a join is forwarded through the active views of other nodes.
+    ///
+    /// The same length is used for the random walk of shuffles.
+    ///
+    /// * Default to 6.
+    pub fn with_active_random_walk_length(mut self, length: u32) -> Self {
+        self.active_random_walk_length = length;
+        self
+    }
+
+    /// Sets the number of remaining hops of a forwarded join at which the joining node is
+    /// added to the passive view.
+    ///
+    /// * Default to 3.
+    pub fn with_passive_random_walk_length(mut self, length: u32) -> Self {
+        self.passive_random_walk_length = length;
+        self
+    }
+
+    /// Sets the interval at which a sample of the views is exchanged with a random active peer.
+    ///
+    /// Missing peers in the active view are replaced from the passive view at the same interval.
+    ///
+    /// * Default to 10 seconds.
+    pub fn with_shuffle_interval(mut self, interval: Duration) -> Self {
+        self.shuffle_interval = interval;
+        self
+    }
+
+    /// Sets the number of peers of the active view included in a shuffle.
+    ///
+    /// * Default to 3.
+    pub fn with_shuffle_active(mut self, count: usize) -> Self {
+        self.shuffle_active = count;
+        self
+    }
+
+    /// Sets the number of peers of the passive view included in a shuffle.
+    ///
+    /// * Default to 4.
+    pub fn with_shuffle_passive(mut self, count: usize) -> Self {
+        self.shuffle_passive = count;
+        self
+    }
+
+    /// Sets the timeout of requests to other peers.
+    ///
+    /// An active peer not answering a request in time is removed from the active view.
+    ///
+    /// * Default to 10 seconds.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+}
+
+/// The events emitted by the peer sampling [`Behaviour`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A peer was added to the active view.
+    NeighborUp { peer_id: PeerId },
+    /// A peer was removed from the active view.
+    NeighborDown { peer_id: PeerId },
+    /// Joining the overlay through the contact node failed.
+    JoinFailed { peer_id: PeerId },
+}
+
+/// An outbound request whose outcome affects the views.
+#[derive(Debug)]
+enum PendingRequest {
+    /// A join through the contact node.
+    Join(PeerInfo),
+    /// A request to add the local node to the active view of the peer.
+    Neighbor(PeerInfo),
+    /// A request to a peer of the active view, which is removed if the request fails.
+    Active(PeerId),
+}
+
+/// A [`NetworkBehaviour`] maintaining a random sample of the peers of an overlay, following
+/// the HyParView membership protocol.
+///
+/// Every node keeps a small active view of peers it exchanges messages with and a larger
+/// passive view of backup peers. The views are refreshed by periodically shuffling samples
+/// with random peers. A peer of the active view whose requests fail is replaced by a peer of
+/// the passive view.
+pub struct Behaviour {
+    inner: request_response::Behaviour<Codec>,
+    local_peer_id: PeerId,
+    config: Config,
+
+    active: HashMap<PeerId, Vec<Multiaddr>>,
+    passive: HashMap<PeerId, Vec<Multiaddr>>,
+    pending_requests: HashMap<OutboundRequestId, PendingRequest>,
+
+    external_addresses: ExternalAddresses,
+    events: VecDeque<Event>,
+    shuffle_delay: Delay,
+}
+
+impl Behaviour {
+    /// Creates a new peer sampling behaviour.
+    pub fn new(local_peer_id: PeerId, config: Config) -> Self {
+        Self {
+            inner: request_response::Behaviour::with_codec(
+                Codec,
+                [(crate::PROTOCOL_NAME, ProtocolSupport::Full)],
+                request_response::Config::default().with_request_timeout(config.request_timeout),
+            ),
+            local_peer_id,
+            active: HashMap::new(),
+            passive: HashMap::new(),
+            pending_requests: HashMap::new(),
+            external_addresses: Default::default(),
+            events: VecDeque::new(),
+            shuffle_delay: Delay::new(config.shuffle_interval),
+            config,
+        }
+    }
+
+    /// Joins the overlay through the given contact node.
+    ///
+    /// The contact node is added to the active view once it accepted the join, otherwise
+    /// [`Event::JoinFailed`] is emitted.
+    pub fn join(&mut self, peer_id: PeerId, addresses: Vec<Multiaddr>) {
+        if peer_id == self.local_peer_id {
+            return;
+        }
+        let request_id = self.inner.send_request(
+            &peer_id,
+            Message::Join {
+                addresses: self.local_addresses(),
+            },
+        );
+        self.pending_requests.insert(
+            request_id,
+            PendingRequest::Join(PeerInfo { peer_id, addresses }),
+        );
+    }
+
+    /// Returns the peers of the active view.
+    pub fn active_view(&self) -> impl Iterator<Item = &PeerId> {
+        self.active.keys()
+    }
+
+    /// Returns the peers of the passive view.
+    pub fn passive_view(&self) -> impl Iterator<Item = &PeerId> {
+        self.passive.keys()
+    }
+
+    /// Returns up to `n` peers chosen uniformly at random from both views.
+    ///
+    /// This can e.g. be used to find candidates for the mesh of a gossipsub topic.
+    pub fn sample(&self, n: usize) -> Vec<PeerId> {
+        self.active
+            .keys()
+            .chain(self.passive.keys())
+            .copied()
+            .choose_multiple(&mut rand::thread_rng(), n)
+    }
+
+    fn local_addresses(&self) -> Vec<Multiaddr> {
+        self.external_addresses.iter().cloned().collect()
+    }
+
+    fn local_info(&self) -> PeerInfo {
+        PeerInfo {
+            peer_id: self.local_peer_id,
+            addresses: self.local_addresses(),
+        }
+    }
+
+    fn send_to_active(&mut self, peer_id: PeerId, message: Message) {
+        let request_id = self.inner.send_request(&peer_id, message);
+        self.pending_requests
+            .insert(request_id, PendingRequest::Active(peer_id));
+    }
+
+    fn send_neighbor(&mut self, peer: PeerInfo, high_priority: bool) {
+        let request_id = self.inner.send_request(
+            &peer.peer_id,
+            Message::Neighbor {
+                high_priority,
+                addresses: self.local_addresses(),
+            },
+        );
+        self.pending_requests
+            .insert(request_id, PendingRequest::Neighbor(peer));
+    }
+
+    /// Chooses a random peer of the active view that is not among `exclude`.
+    fn random_active(&self, exclude: &[PeerId]) -> Option<PeerId> {
+        self.active
+            .keys()
+            .filter(|p| !exclude.contains(p))
+            .choose(&mut rand::thread_rng())
+            .copied()
+    }
+
+    fn add_active(&mut self, peer: PeerInfo) {
+        if peer.peer_id == self.local_peer_id {
+            return;
+        }
+        if let Some(addresses) = self.active.get_mut(&peer.peer_id) {
+            if !peer.addresses.is_empty() {
+                *addresses = peer.addresses;
+            }
+            return;
+        }
+        if self.active.len() >= self.config.active_view_capacity {
+            if let Some(dropped) = self.random_active(&[]) {
+                self.inner.send_request(&dropped, Message::Disconnect);
+                self.remove_active(dropped, true);
+            }
+        }
+
+        let known = self.passive.remove(&peer.peer_id).unwrap_or_default();
+        let addresses = if peer.addresses.is_empty() {
+            known
+        } else {
+            peer.addresses
+        };
+        self.active.insert(peer.peer_id, addresses);
+        self.events.push_back(Event::NeighborUp {
+            peer_id: peer.peer_id,
+        });
+    }
+
+    /// Removes a peer from the active view, moving it to the passive view if `keep` is set.
+    fn remove_active(&mut self, peer_id: PeerId, keep: bool) {
+        let Some(addresses) = self.active.remove(&peer_id) else {
+            return;
+        };
+        self.events.push_back(Event::NeighborDown { peer_id });
+        if keep {
+            self.add_passive(PeerInfo { peer_id, addresses });
+        }
+    }
+
+    fn add_passive(&mut self, peer: PeerInfo) {
+        if peer.peer_id == self.local_peer_id || self.active.contains_key(&peer.peer_id) {
+            return;
+        }
+        if let Some(addresses) = self.passive.get_mut(&peer.peer_id) {
+            if !peer.addresses.is_empty() {
+                *addresses = peer.addresses;
+            }
+            return;
+        }
+        if self.config.passive_view_capacity == 0 {
+            return;
+        }
+        if self.passive.len() >= self.config.passive_view_capacity {
+            if let Some(evicted) = self.passive.keys().choose(&mut rand::thread_rng()).copied() {
+                self.passive.remove(&evicted);
+            }
+        }
+        self.passive.insert(peer.peer_id, peer.addresses);
+    }
+
+    /// Promotes a random peer of the passive view if the active view is not full.
+    ///
+    /// Only one promotion is attempted at a time.
+    fn fill_active_view(&mut self) {
+        if self.active.len() >= self.config.active_view_capacity
+            || self
+                .pending_requests
+                .values()
+                .any(|r| matches!(r, PendingRequest::Neighbor(_)))
+        {
+            return;
+        }
+        let Some((peer_id, addresses)) = self
+            .passive
+            .iter()
+            .choose(&mut rand::thread_rng())
+            .map(|(p, a)| (*p, a.clone()))
+        else {
+            return;
+        };
+
+        let high_priority = self.active.is_empty();
+        self.send_neighbor(PeerInfo { peer_id, addresses }, high_priority);
+    }
+
+    /// Sends a sample of the views to a random peer of the active view.
+    fn shuffle(&mut self) {
+        let Some(target) = self.random_active(&[]) else {
+            return;
+        };
+        let mut rng = rand::thread_rng();
+        let mut peers = self
+            .active
+            .iter()
+            .filter(|(p, _)| **p != target)
+            .choose_multiple(&mut rng, self.config.shuffle_active);
+        peers.extend(
+            self.passive
+                .iter()
+                .choose_multiple(&mut rng, self.config.shuffle_passive),
+        );
+        let peers = peers
+            .into_iter()
+            .map(|(peer_id, addresses)| PeerInfo {
+                peer_id: *peer_id,
+                addresses: addresses.clone(),
+            })
+            .collect();
+
+        let message = Message::Shuffle {
+            origin: self.local_info(),
+            peers,
+            ttl: self.config.active_random_walk_length,
+        };
+        self.send_to_active(target, message);
+    }
+
+    /// Handles a request and returns the response.
+    fn handle_request(&mut self, peer_id: PeerId, request: Message) -> Message {
+        match request {
+            Message::Join { addresses } => {
+                let joined = PeerInfo { peer_id, addresses };
+                self.add_active(joined.clone());
+
+                let others = self
+                    .active
+                    .keys()
+                    .filter(|p| **p != peer_id)
+                    .copied()
+                    .collect::<Vec<_>>();
+                for other in others {
+                    let message = Message::ForwardJoin {
+                        peer: joined.clone(),
+                        ttl: self.config.active_random_walk_length,
+                    };
+                    self.send_to_active(other, message);
+                }
+            }
+            Message::ForwardJoin { peer, ttl } => {
+                if peer.peer_id == self.local_peer_id || self.active.contains_key(&peer.peer_id) {
+                    return Message::Ack;
+                }
+                if ttl == self.config.passive_random_walk_length {
+                    self.add_passive(peer.clone());
+                }
+                match self.random_active(&[peer_id, peer.peer_id]) {
+                    Some(next) if ttl > 0 => {
+                        let message = Message::ForwardJoin { peer, ttl: ttl - 1 };
+                        self.send_to_active(next, message);
+                    }
+                    _ => self.send_neighbor(peer, true),
+                }
+            }
+            Message::Neighbor {
+                high_priority,
+                addresses,
+            } => {
+                let accepted = high_priority
+                    || self.active.contains_key(&peer_id)
+                    || self.active.len() < self.config.active_view_capacity;
+                if accepted {
+                    self.add_active(PeerInfo { peer_id, addresses });
+                }
+                return Message::NeighborReply { accepted };
+            }
+            Message::Disconnect => {
+                self.remove_active(peer_id, true);
+                self.fill_active_view();
+            }
+            Message::Shuffle { origin, peers, ttl } => {
+                if origin.peer_id == self.local_peer_id {
+                    return Message::Ack;
+                }
+                match self.random_active(&[peer_id, origin.peer_id]) {
+                    Some(next) if ttl > 0 => {
+                        let message = Message::Shuffle {
+                            origin,
+                            peers,
+                            ttl: ttl - 1,
+                        };
+                        self.send_to_active(next, message);
+                    }
+                    _ => {
+                        let reply = self
+                            .passive
+                            .iter()
+                            .choose_multiple(&mut rand::thread_rng(), peers.len() + 1)
+                            .into_iter()
+                            .map(|(peer_id, addresses)| PeerInfo {
+                                peer_id: *peer_id,
+                                addresses: addresses.clone(),
+                            })
+                            .collect();
+                        let origin_id = origin.peer_id;
+                        // The origin is integrated first, such that its addresses are known
+                        // when dialing it for the reply.
+                        self.add_passive(origin);
+                        for peer in peers {
+                            self.add_passive(peer);
+                        }
+                        self.inner
+                            .send_request(&origin_id, Message::ShuffleReply { peers: reply });
+                    }
+                }
+            }
+            Message::ShuffleReply { peers } => {
+                for peer in peers {
+                    self.add_passive(peer);
+                }
+            }
+            Message::NeighborReply { .. } | Message::Ack => {}
+        }
+
+        Message::Ack
+    }
+
+    fn handle_response(&mut self, request_id: OutboundRequestId, response: Message) {
+        match self.pending_requests.remove(&request_id) {
+            Some(PendingRequest::Join(contact)) => self.add_active(contact),
+            Some(PendingRequest::Neighbor(peer)) => {
+                if let Message::NeighborReply { accepted: true } = response {
+                    self.add_active(peer);
+                }
+            }
+            Some(PendingRequest::Active(_)) | None => {}
+        }
+    }
+
+    fn handle_outbound_failure(&mut self, request_id: OutboundRequestId) {
+        match self.pending_requests.remove(&request_id) {
+            Some(PendingRequest::Join(contact)) => {
+                self.events.push_back(Event::JoinFailed {
+                    peer_id: contact.peer_id,
+                });
+            }
+            Some(PendingRequest::Neighbor(peer)) => {
+                self.passive.remove(&peer.peer_id);
+                self.fill_active_view();
+            }
+            Some(PendingRequest::Active(peer_id)) => {
+                self.remove_active(peer_id, false);
+                self.fill_active_view();
+            }
+            None => {}
+        }
+    }
+
+    /// Returns the known addresses of a peer.
+    fn addresses_of_peer(&self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        if let Some(addresses) = self.active.get(peer_id).or(self.passive.get(peer_id)) {
+            return addresses.clone();
+        }
+        self.pending_requests
+            .values()
+            .find_map(|r| match r {
+                PendingRequest::Join(peer) | PendingRequest::Neighbor(peer)
+                    if &peer.peer_id == peer_id =>
+                {
+                    Some(peer.addresses.clone())
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler =
+        <request_response::Behaviour<Codec> as NetworkBehaviour>::ConnectionHandler;
+
+    type ToSwarm = Event;
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        _addresses: &[Multiaddr],
+        _effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        let Some(peer) = maybe_peer else {
+            return Ok(vec![]);
+        };
+
+        Ok(self.addresses_of_peer(&peer))
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_outbound_connection(connection_id, peer, addr, role_override)
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner
+            .on_connection_handler_event(peer_id, connection_id, event);
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        self.external_addresses.on_swarm_event(&event);
+        self.inner.on_swarm_event(event);
+    }
+
+    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self, cx))]
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(ToSwarm::GenerateEvent(event));
+            }
+
+            if self.shuffle_delay.poll_unpin(cx).is_ready() {
+                self.shuffle_delay.reset(self.config.shuffle_interval);
+                self.shuffle();
+                self.fill_active_view();
+                continue;
+            }
+
+            match self.inner.poll(cx) {
+                Poll::Ready(ToSwarm::GenerateEvent(request_response::Event::Message {
+                    peer,
+                    message:
+                        request_response::Message::Request {
+                            request, channel, ..
+                        },
+                })) => {
+                    let response = self.handle_request(peer, request);
+                    if self.inner.send_response(channel, response).is_err() {
+                        tracing::debug!(%peer, "Failed to respond, connection closed");
+                    }
+                }
+                Poll::Ready(ToSwarm::GenerateEvent(request_response::Event::Message {
+                    message:
+                        request_response::Message::Response {
+                            request_id,
+                            response,
+                        },
+                    ..
+                })) => self.handle_response(request_id, response),
+                Poll::Ready(ToSwarm::GenerateEvent(request_response::Event::OutboundFailure {
+                    peer,
+                    request_id,
+                    error,
+                })) => {
+                    tracing::debug!(%peer, "Request failed: {error}");
+                    self.handle_outbound_failure(request_id);
+                }
+                Poll::Ready(ToSwarm::GenerateEvent(
+                    request_response::Event::InboundFailure { .. }
+                    | request_response::Event::ResponseSent { .. },
+                )) => {}
+                Poll::Ready(other) => {
+                    return Poll::Ready(
+                        other.map_out(|_| unreachable!("we manually map `GenerateEvent` variants")),
+                    );
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
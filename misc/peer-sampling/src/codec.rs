@@ -0,0 +1,320 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use async_trait::async_trait;
+use asynchronous_codec::{FramedRead, FramedWrite};
+use futures::{AsyncRead, AsyncWrite, SinkExt, StreamExt};
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use libp2p_swarm::StreamProtocol;
+use quick_protobuf_codec::Codec as ProtobufCodec;
+use std::io;
+
+const MAX_MESSAGE_LEN_BYTES: usize = 64 * 1024;
+
+/// A peer together with the addresses it can be reached at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    pub(crate) peer_id: PeerId,
+    pub(crate) addresses: Vec<Multiaddr>,
+}
+
+/// The messages of the HyParView protocol.
+///
+/// Every message is sent as a request, [`Message::NeighborReply`] answers a
+/// [`Message::Neighbor`] request and all other requests are answered with a [`Message::Ack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// A new node asks the contact node to join the overlay.
+    Join { addresses: Vec<Multiaddr> },
+    /// The join of `peer` is propagated through the overlay by a random walk.
+    ForwardJoin { peer: PeerInfo, ttl: u32 },
+    /// Asks the remote to add the local node to its active view.
+    Neighbor {
+        high_priority: bool,
+        addresses: Vec<Multiaddr>,
+    },
+    /// Whether a [`Message::Neighbor`] request was accepted.
+    NeighborReply { accepted: bool },
+    /// Notifies the remote that the local node removed it from its active view.
+    Disconnect,
+    /// A sample of the views of `origin`, propagated by a random walk.
+    Shuffle {
+        origin: PeerInfo,
+        peers: Vec<PeerInfo>,
+        ttl: u32,
+    },
+    /// A sample of the passive view of the node accepting a [`Message::Shuffle`].
+    ShuffleReply { peers: Vec<PeerInfo> },
+    /// Acknowledges a request.
+    Ack,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Codec;
+
+#[async_trait]
+impl libp2p_request_response::Codec for Codec {
+    type Protocol = StreamProtocol;
+    type Request = Message;
+    type Response = Message;
+
+    async fn read_request<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Message>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn read_response<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Message>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        req: Message,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, req).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        res: Message,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_message(io, res).await
+    }
+}
+
+async fn read_message<T>(io: &mut T) -> io::Result<Message>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let message = FramedRead::new(
+        io,
+        ProtobufCodec::<proto::Message>::new(MAX_MESSAGE_LEN_BYTES),
+    )
+    .next()
+    .await
+    .ok_or(io::ErrorKind::UnexpectedEof)??;
+
+    Message::try_from(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+async fn write_message<T>(io: &mut T, message: Message) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin + Send,
+{
+    let mut framed = FramedWrite::new(
+        io,
+        ProtobufCodec::<proto::Message>::new(MAX_MESSAGE_LEN_BYTES),
+    );
+    framed.send(proto::Message::from(message)).await?;
+    framed.close().await?;
+
+    Ok(())
+}
+
+impl From<PeerInfo> for proto::Peer {
+    fn from(peer: PeerInfo) -> Self {
+        proto::Peer {
+            id: Some(peer.peer_id.to_bytes()),
+            addrs: peer.addresses.into_iter().map(|a| a.to_vec()).collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::Peer> for PeerInfo {
+    type Error = ConversionError;
+
+    fn try_from(peer: proto::Peer) -> Result<Self, Self::Error> {
+        let peer_id = PeerId::from_bytes(&peer.id.ok_or(ConversionError::MissingField("id"))?)
+            .map_err(|_| ConversionError::InvalidPeerId)?;
+        // Malformed addresses are skipped rather than rejecting the whole peer.
+        let addresses = peer
+            .addrs
+            .into_iter()
+            .filter_map(|a| Multiaddr::try_from(a).ok())
+            .collect();
+
+        Ok(PeerInfo { peer_id, addresses })
+    }
+}
+
+fn addresses_to_proto(addresses: Vec<Multiaddr>) -> proto::Peer {
+    proto::Peer {
+        id: None,
+        addrs: addresses.into_iter().map(|a| a.to_vec()).collect(),
+    }
+}
+
+fn addresses_from_proto(peer: Option<proto::Peer>) -> Vec<Multiaddr> {
+    peer.map(|p| p.addrs)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|a| Multiaddr::try_from(a).ok())
+        .collect()
+}
+
+impl From<Message> for proto::Message {
+    fn from(message: Message) -> Self {
+        use proto::MessageType;
+
+        let mut msg = proto::Message::default();
+        match message {
+            Message::Join { addresses } => {
+                msg.type_pb = Some(MessageType::JOIN);
+                msg.peer = Some(addresses_to_proto(addresses));
+            }
+            Message::ForwardJoin { peer, ttl } => {
+                msg.type_pb = Some(MessageType::FORWARD_JOIN);
+                msg.peer = Some(peer.into());
+                msg.ttl = Some(ttl);
+            }
+            Message::Neighbor {
+                high_priority,
+                addresses,
+            } => {
+                msg.type_pb = Some(MessageType::NEIGHBOR);
+                msg.peer = Some(addresses_to_proto(addresses));
+                msg.high_priority = Some(high_priority);
+            }
+            Message::NeighborReply { accepted } => {
+                msg.type_pb = Some(MessageType::NEIGHBOR_REPLY);
+                msg.accepted = Some(accepted);
+            }
+            Message::Disconnect => msg.type_pb = Some(MessageType::DISCONNECT),
+            Message::Shuffle { origin, peers, ttl } => {
+                msg.type_pb = Some(MessageType::SHUFFLE);
+                msg.peer = Some(origin.into());
+                msg.peers = peers.into_iter().map(proto::Peer::from).collect();
+                msg.ttl = Some(ttl);
+            }
+            Message::ShuffleReply { peers } => {
+                msg.type_pb = Some(MessageType::SHUFFLE_REPLY);
+                msg.peers = peers.into_iter().map(proto::Peer::from).collect();
+            }
+            Message::Ack => msg.type_pb = Some(MessageType::ACK),
+        }
+        msg
+    }
+}
+
+impl TryFrom<proto::Message> for Message {
+    type Error = ConversionError;
+
+    fn try_from(message: proto::Message) -> Result<Self, Self::Error> {
+        use proto::MessageType;
+
+        let message = match message
+            .type_pb
+            .ok_or(ConversionError::MissingField("type"))?
+        {
+            MessageType::JOIN => Message::Join {
+                addresses: addresses_from_proto(message.peer),
+            },
+            MessageType::FORWARD_JOIN => Message::ForwardJoin {
+                peer: message
+                    .peer
+                    .ok_or(ConversionError::MissingField("peer"))?
+                    .try_into()?,
+                ttl: message.ttl.ok_or(ConversionError::MissingField("ttl"))?,
+            },
+            MessageType::NEIGHBOR => Message::Neighbor {
+                high_priority: message.high_priority.unwrap_or_default(),
+                addresses: addresses_from_proto(message.peer),
+            },
+            MessageType::NEIGHBOR_REPLY => Message::NeighborReply {
+                accepted: message.accepted.unwrap_or_default(),
+            },
+            MessageType::DISCONNECT => Message::Disconnect,
+            MessageType::SHUFFLE => Message::Shuffle {
+                origin: message
+                    .peer
+                    .ok_or(ConversionError::MissingField("peer"))?
+                    .try_into()?,
+                peers: peers_from_proto(message.peers),
+                ttl: message.ttl.ok_or(ConversionError::MissingField("ttl"))?,
+            },
+            MessageType::SHUFFLE_REPLY => Message::ShuffleReply {
+                peers: peers_from_proto(message.peers),
+            },
+            MessageType::ACK => Message::Ack,
+        };
+
+        Ok(message)
+    }
+}
+
+/// Converts the peers of a shuffle, skipping invalid entries.
+fn peers_from_proto(peers: Vec<proto::Peer>) -> Vec<PeerInfo> {
+    peers
+        .into_iter()
+        .filter_map(|p| PeerInfo::try_from(p).ok())
+        .collect()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConversionError {
+    #[error("The message is missing the required field {0}")]
+    MissingField(&'static str),
+    #[error("The peer id is invalid")]
+    InvalidPeerId,
+}
+
+mod proto {
+    #![allow(unreachable_pub)]
+    include!("generated/mod.rs");
+    pub(crate) use self::peer_sampling::pb::{mod_Message::*, Message};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffle_roundtrip() {
+        let peer = |port: u16| PeerInfo {
+            peer_id: PeerId::random(),
+            addresses: vec![format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap()],
+        };
+        let message = Message::Shuffle {
+            origin: peer(1),
+            peers: vec![peer(2), peer(3)],
+            ttl: 4,
+        };
+
+        let actual = Message::try_from(proto::Message::from(message.clone())).unwrap();
+
+        assert_eq!(actual, message);
+    }
+}
@@ -0,0 +1,159 @@
+// Automatically generated rust module for 'rpc.proto' file
+
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(unused_imports)]
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+
+use quick_protobuf::{MessageInfo, MessageRead, MessageWrite, BytesReader, Writer, WriterBackend, Result};
+use quick_protobuf::sizeofs::*;
+use super::super::*;
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Message {
+    pub type_pb: Option<peer_sampling::pb::mod_Message::MessageType>,
+    pub peer: Option<peer_sampling::pb::mod_Message::Peer>,
+    pub ttl: Option<u32>,
+    pub high_priority: Option<bool>,
+    pub accepted: Option<bool>,
+    pub peers: Vec<peer_sampling::pb::mod_Message::Peer>,
+}
+
+impl<'a> MessageRead<'a> for Message {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(8) => msg.type_pb = Some(r.read_enum(bytes)?),
+                Ok(18) => msg.peer = Some(r.read_message::<peer_sampling::pb::mod_Message::Peer>(bytes)?),
+                Ok(24) => msg.ttl = Some(r.read_uint32(bytes)?),
+                Ok(32) => msg.high_priority = Some(r.read_bool(bytes)?),
+                Ok(40) => msg.accepted = Some(r.read_bool(bytes)?),
+                Ok(50) => msg.peers.push(r.read_message::<peer_sampling::pb::mod_Message::Peer>(bytes)?),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Message {
+    fn get_size(&self) -> usize {
+        0
+        + self.type_pb.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.peer.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
+        + self.ttl.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.high_priority.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.accepted.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.peers.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.type_pb { w.write_with_tag(8, |w| w.write_enum(*s as i32))?; }
+        if let Some(ref s) = self.peer { w.write_with_tag(18, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.ttl { w.write_with_tag(24, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.high_priority { w.write_with_tag(32, |w| w.write_bool(*s))?; }
+        if let Some(ref s) = self.accepted { w.write_with_tag(40, |w| w.write_bool(*s))?; }
+        for s in &self.peers { w.write_with_tag(50, |w| w.write_message(s))?; }
+        Ok(())
+    }
+}
+
+pub mod mod_Message {
+
+use super::*;
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Peer {
+    pub id: Option<Vec<u8>>,
+    pub addrs: Vec<Vec<u8>>,
+}
+
+impl<'a> MessageRead<'a> for Peer {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.id = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(18) => msg.addrs.push(r.read_bytes(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Peer {
+    fn get_size(&self) -> usize {
+        0
+        + self.id.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.addrs.iter().map(|s| 1 + sizeof_len((s).len())).sum::<usize>()
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.id { w.write_with_tag(10, |w| w.write_bytes(&**s))?; }
+        for s in &self.addrs { w.write_with_tag(18, |w| w.write_bytes(&**s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MessageType {
+    JOIN = 0,
+    FORWARD_JOIN = 1,
+    NEIGHBOR = 2,
+    NEIGHBOR_REPLY = 3,
+    DISCONNECT = 4,
+    SHUFFLE = 5,
+    SHUFFLE_REPLY = 6,
+    ACK = 7,
+}
+
+impl Default for MessageType {
+    fn default() -> Self {
+        MessageType::JOIN
+    }
+}
+
+impl From<i32> for MessageType {
+    fn from(i: i32) -> Self {
+        match i {
+            0 => MessageType::JOIN,
+            1 => MessageType::FORWARD_JOIN,
+            2 => MessageType::NEIGHBOR,
+            3 => MessageType::NEIGHBOR_REPLY,
+            4 => MessageType::DISCONNECT,
+            5 => MessageType::SHUFFLE,
+            6 => MessageType::SHUFFLE_REPLY,
+            7 => MessageType::ACK,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for MessageType {
+    fn from(s: &'a str) -> Self {
+        match s {
+            "JOIN" => MessageType::JOIN,
+            "FORWARD_JOIN" => MessageType::FORWARD_JOIN,
+            "NEIGHBOR" => MessageType::NEIGHBOR,
+            "NEIGHBOR_REPLY" => MessageType::NEIGHBOR_REPLY,
+            "DISCONNECT" => MessageType::DISCONNECT,
+            "SHUFFLE" => MessageType::SHUFFLE,
+            "SHUFFLE_REPLY" => MessageType::SHUFFLE_REPLY,
+            "ACK" => MessageType::ACK,
+            _ => Self::default(),
+        }
+    }
+}
+
+}
+
@@ -0,0 +1,47 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of a gossip-based peer sampling service for libp2p, following the
+//! [HyParView] membership protocol.
+//!
+//! Every node maintains a small, symmetric *active view* of peers and a larger *passive view*
+//! of backup peers. A new node joins the overlay through any node already part of it. The
+//! join is propagated by random walks, such that the new node ends up in the views of random
+//! nodes of the overlay. Both views are refreshed by periodically exchanging samples with
+//! random peers, which keeps the views a continuously refreshed random sample of the overlay.
+//!
+//! The active view can be used as the neighbours of an unstructured overlay or to find
+//! candidates for e.g. gossipsub meshes, see [`Behaviour::sample`]. The behaviour does not
+//! keep connections to the peers of the active view alive on its own. A peer of the active
+//! view is considered failed once a request to it fails and is replaced by a peer of the
+//! passive view.
+//!
+//! [HyParView]: https://asc.di.fct.unl.pt/~jleitao/pdf/dsn07-leitao.pdf
+
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+mod behaviour;
+mod codec;
+
+pub use behaviour::{Behaviour, Config, Event};
+use libp2p_swarm::StreamProtocol;
+
+/// The protocol name of the peer sampling protocol.
+pub const PROTOCOL_NAME: StreamProtocol = StreamProtocol::new("/hyparview/1.0.0");
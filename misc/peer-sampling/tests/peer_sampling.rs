@@ -0,0 +1,117 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::future;
+use futures::FutureExt;
+use libp2p_peer_sampling::{Behaviour, Config, Event};
+use libp2p_swarm::{Swarm, SwarmEvent};
+use libp2p_swarm_test::SwarmExt;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+#[async_std::test]
+async fn joined_nodes_become_neighbors() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarms = [
+        new_swarm(Config::default()).await,
+        new_swarm(Config::default()).await,
+        new_swarm(Config::default()).await,
+    ];
+    let contact = *swarms[0].local_peer_id();
+    let contact_addr = swarms[0].external_addresses().cloned().collect::<Vec<_>>();
+
+    swarms[1]
+        .behaviour_mut()
+        .join(contact, contact_addr.clone());
+    drive_until(&mut swarms, |swarms| active_views(swarms) == [1, 1, 0]).await;
+
+    // The join of the third node is forwarded to the second one, which has no other neighbors.
+    swarms[2].behaviour_mut().join(contact, contact_addr);
+    drive_until(&mut swarms, |swarms| active_views(swarms) == [2, 2, 2]).await;
+}
+
+#[async_std::test]
+async fn full_active_view_moves_dropped_peer_to_passive_view() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarms = [
+        new_swarm(Config::default().with_active_view_capacity(1)).await,
+        new_swarm(Config::default()).await,
+        new_swarm(Config::default()).await,
+    ];
+    let contact = *swarms[0].local_peer_id();
+    let contact_addr = swarms[0].external_addresses().cloned().collect::<Vec<_>>();
+
+    swarms[1]
+        .behaviour_mut()
+        .join(contact, contact_addr.clone());
+    drive_until(&mut swarms, |swarms| active_views(swarms) == [1, 1, 0]).await;
+
+    swarms[2].behaviour_mut().join(contact, contact_addr);
+    drive_until(&mut swarms, |swarms| {
+        swarms[1].behaviour().passive_view().any(|p| *p == contact)
+    })
+    .await;
+
+    assert_eq!(
+        swarms[0].behaviour().active_view().collect::<Vec<_>>(),
+        [swarms[2].local_peer_id()]
+    );
+    assert_eq!(swarms[1].behaviour().active_view().count(), 0);
+}
+
+async fn new_swarm(config: Config) -> Swarm<Behaviour> {
+    let mut swarm = Swarm::new_ephemeral(|key| {
+        Behaviour::new(
+            key.public().to_peer_id(),
+            // Keep the shuffles from changing the views during the tests.
+            config.with_shuffle_interval(Duration::from_secs(60)),
+        )
+    });
+    swarm.listen().with_memory_addr_external().await;
+    swarm
+}
+
+fn active_views(swarms: &[Swarm<Behaviour>]) -> Vec<usize> {
+    swarms
+        .iter()
+        .map(|s| s.behaviour().active_view().count())
+        .collect()
+}
+
+/// Drives all swarms until the predicate holds.
+async fn drive_until(
+    swarms: &mut [Swarm<Behaviour>],
+    predicate: impl Fn(&[Swarm<Behaviour>]) -> bool,
+) {
+    while !predicate(swarms) {
+        let event = future::select_all(swarms.iter_mut().map(|s| s.next_swarm_event().boxed()))
+            .await
+            .0;
+        if let SwarmEvent::Behaviour(Event::JoinFailed { peer_id }) = event {
+            panic!("joining through {peer_id} failed");
+        }
+    }
+}
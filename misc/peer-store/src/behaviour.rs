@@ -0,0 +1,366 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::channel::mpsc;
+use futures::{FutureExt, Stream, StreamExt};
+use futures_timer::Delay;
+use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_swarm::{
+    dummy, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, StreamProtocol, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use web_time::Instant;
+
+/// The configuration of the peer store [`Behaviour`].
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    address_ttl: Duration,
+    cleanup_interval: Duration,
+    subscription_buffer: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address_ttl: Duration::from_secs(60 * 60),
+            cleanup_interval: Duration::from_secs(30),
+            subscription_buffer: 256,
+        }
+    }
+}
+
+impl Config {
+    /// Sets the time after which an address expires unless it is reported or confirmed by a
+    /// connection again.
+    ///
+    /// Defaults to one hour.
+    pub fn with_address_ttl(mut self, ttl: Duration) -> Self {
+        self.address_ttl = ttl;
+        self
+    }
+
+    /// Sets the interval in which expired addresses are removed.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn with_cleanup_interval(mut self, interval: Duration) -> Self {
+        self.cleanup_interval = interval;
+        self
+    }
+
+    /// Sets the number of events buffered for each [`Subscription`]. A subscription that
+    /// falls further behind is closed, see [`Subscription`].
+    ///
+    /// Defaults to 256.
+    pub fn with_subscription_buffer(mut self, buffer: usize) -> Self {
+        self.subscription_buffer = buffer;
+        self
+    }
+}
+
+/// A change of the peer store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A new address of a peer was recorded.
+    AddressAdded { peer_id: PeerId, address: Multiaddr },
+    /// An address of a peer expired, as it was not reported again within the TTL.
+    AddressExpired { peer_id: PeerId, address: Multiaddr },
+    /// An address of a peer was removed via [`Behaviour::remove_address`].
+    AddressRemoved { peer_id: PeerId, address: Multiaddr },
+    /// The protocols supported by a peer changed.
+    ProtocolsChanged {
+        peer_id: PeerId,
+        /// The protocols the peer newly supports.
+        added: Vec<StreamProtocol>,
+        /// The protocols the peer no longer supports.
+        removed: Vec<StreamProtocol>,
+    },
+}
+
+/// A stream of the [`Event`]s of a peer store, created by [`Behaviour::subscribe`].
+///
+/// The events are buffered until they are polled, up to [`Config::with_subscription_buffer`]
+/// events. If the buffer is full when a new event occurs, the subscription is closed instead of
+/// silently missing the event: the stream ends after the buffered events. The subscriber is
+/// then out of sync with the store and should read its current content and subscribe again.
+///
+/// Drop the subscription to unsubscribe.
+#[derive(Debug)]
+pub struct Subscription(mpsc::Receiver<Event>);
+
+impl Stream for Subscription {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// What the store knows about a peer.
+#[derive(Debug, Default)]
+struct PeerRecord {
+    /// The addresses of the peer and the time at which they expire.
+    addresses: HashMap<Multiaddr, Instant>,
+    protocols: HashSet<StreamProtocol>,
+}
+
+/// A [`NetworkBehaviour`] storing the addresses and protocols of known peers.
+///
+/// See the [crate documentation](crate) for details.
+pub struct Behaviour {
+    config: Config,
+    records: HashMap<PeerId, PeerRecord>,
+    subscribers: Vec<mpsc::Sender<Event>>,
+    pending_events: VecDeque<Event>,
+    cleanup: Delay,
+    waker: Option<Waker>,
+}
+
+impl Behaviour {
+    /// Creates a new peer store with the given configuration.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            records: HashMap::new(),
+            subscribers: Vec::new(),
+            pending_events: VecDeque::new(),
+            cleanup: Delay::new(config.cleanup_interval),
+            waker: None,
+        }
+    }
+
+    /// Subscribes to the changes of the store.
+    ///
+    /// Only the changes after the subscription are reported, the current content of the store
+    /// can be read via [`Behaviour::addresses_of_peer`] and [`Behaviour::protocols_of_peer`].
+    pub fn subscribe(&mut self) -> Subscription {
+        let (tx, rx) = mpsc::channel(self.config.subscription_buffer);
+        self.subscribers.push(tx);
+        Subscription(rx)
+    }
+
+    /// Records an address of a peer, or resets the expiry of a known address.
+    ///
+    /// Returns `true` if the address was not known before.
+    pub fn add_address(&mut self, peer_id: PeerId, address: Multiaddr) -> bool {
+        let expires = Instant::now() + self.config.address_ttl;
+        let record = self.records.entry(peer_id).or_default();
+        if record.addresses.insert(address.clone(), expires).is_some() {
+            return false;
+        }
+        tracing::trace!(peer=%peer_id, %address, "Recorded new address of peer");
+        self.push_event(Event::AddressAdded { peer_id, address });
+        true
+    }
+
+    /// Removes an address of a peer.
+    ///
+    /// Returns `true` if the address was known.
+    pub fn remove_address(&mut self, peer_id: &PeerId, address: &Multiaddr) -> bool {
+        let Some(record) = self.records.get_mut(peer_id) else {
+            return false;
+        };
+        if record.addresses.remove(address).is_none() {
+            return false;
+        }
+        self.remove_if_empty(peer_id);
+        self.push_event(Event::AddressRemoved {
+            peer_id: *peer_id,
+            address: address.clone(),
+        });
+        true
+    }
+
+    /// Sets the protocols supported by a peer, e.g. as reported by identify.
+    pub fn set_protocols<I>(&mut self, peer_id: PeerId, protocols: I)
+    where
+        I: IntoIterator<Item = StreamProtocol>,
+    {
+        let protocols = protocols.into_iter().collect::<HashSet<_>>();
+        let record = self.records.entry(peer_id).or_default();
+        let added = protocols
+            .difference(&record.protocols)
+            .cloned()
+            .collect::<Vec<_>>();
+        let removed = record
+            .protocols
+            .difference(&protocols)
+            .cloned()
+            .collect::<Vec<_>>();
+        record.protocols = protocols;
+        self.remove_if_empty(&peer_id);
+
+        if !added.is_empty() || !removed.is_empty() {
+            self.push_event(Event::ProtocolsChanged {
+                peer_id,
+                added,
+                removed,
+            });
+        }
+    }
+
+    /// Returns the known addresses of a peer.
+    pub fn addresses_of_peer(&self, peer_id: &PeerId) -> impl Iterator<Item = &Multiaddr> {
+        self.records
+            .get(peer_id)
+            .into_iter()
+            .flat_map(|record| record.addresses.keys())
+    }
+
+    /// Returns the known protocols of a peer.
+    pub fn protocols_of_peer(&self, peer_id: &PeerId) -> impl Iterator<Item = &StreamProtocol> {
+        self.records
+            .get(peer_id)
+            .into_iter()
+            .flat_map(|record| record.protocols.iter())
+    }
+
+    /// Returns the peers the store knows addresses or protocols of.
+    pub fn peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.records.keys()
+    }
+
+    fn remove_if_empty(&mut self, peer_id: &PeerId) {
+        if self
+            .records
+            .get(peer_id)
+            .is_some_and(|record| record.addresses.is_empty() && record.protocols.is_empty())
+        {
+            self.records.remove(peer_id);
+        }
+    }
+
+    fn remove_expired_addresses(&mut self) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        for (peer_id, record) in &mut self.records {
+            record.addresses.retain(|address, expires| {
+                if *expires > now {
+                    return true;
+                }
+                expired.push((*peer_id, address.clone()));
+                false
+            });
+        }
+        for (peer_id, address) in expired {
+            tracing::trace!(peer=%peer_id, %address, "Address of peer expired");
+            self.remove_if_empty(&peer_id);
+            self.push_event(Event::AddressExpired { peer_id, address });
+        }
+    }
+
+    fn push_event(&mut self, event: Event) {
+        self.subscribers
+            .retain_mut(|subscriber| match subscriber.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(e) if e.is_full() => {
+                    tracing::debug!("Closing lagging peer store subscription");
+                    false
+                }
+                Err(_) => false,
+            });
+        self.pending_events.push_back(event);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = Event;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        _: &[Multiaddr],
+        _: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        Ok(maybe_peer
+            .map(|peer_id| self.addresses_of_peer(&peer_id).cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::NewExternalAddrOfPeer(event) => {
+                self.add_address(event.peer_id, event.addr.clone());
+            }
+            // The address we successfully dialed is confirmed to be reachable.
+            FromSwarm::ConnectionEstablished(event) => {
+                if let ConnectedPoint::Dialer { address, .. } = event.endpoint {
+                    self.add_address(event.peer_id, address.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        void::unreachable(event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        while self.cleanup.poll_unpin(cx).is_ready() {
+            self.cleanup.reset(self.config.cleanup_interval);
+            self.remove_expired_addresses();
+        }
+
+        if let Some(event) = self.pending_events.pop_front() {
+            return Poll::Ready(ToSwarm::GenerateEvent(event));
+        }
+
+        self.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
@@ -0,0 +1,38 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! A store of the addresses and protocols of known peers, shared by the behaviours of a
+//! [`Swarm`](libp2p_swarm::Swarm).
+//!
+//! The [`Behaviour`] records the addresses of peers reported to the swarm, e.g. by identify or
+//! kademlia, as well as the addresses of the peers it dialed successfully. Addresses which are
+//! not reported again within the configured TTL expire. The protocols of a peer are recorded
+//! via [`Behaviour::set_protocols`]. The stored addresses are used when dialing a peer.
+//!
+//! Changes to the store are reported as [`Event`]s, both to the swarm and to every
+//! [`Subscription`] obtained via [`Behaviour::subscribe`], such that other components can react
+//! to them, e.g. redial a wanted peer once a new address of it appears, without polling the
+//! store.
+
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+mod behaviour;
+
+pub use behaviour::{Behaviour, Config, Event, Subscription};
@@ -0,0 +1,117 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::{FutureExt, StreamExt};
+use libp2p_identity::PeerId;
+use libp2p_peer_store::{Behaviour, Config, Event};
+use libp2p_swarm::{StreamProtocol, Swarm};
+use libp2p_swarm_test::SwarmExt;
+use std::time::Duration;
+
+#[async_std::test]
+async fn subscription_reports_address_of_dialed_peer() {
+    let mut listener = Swarm::new_ephemeral(|_| Behaviour::new(Config::default()));
+    let mut dialer = Swarm::new_ephemeral(|_| Behaviour::new(Config::default()));
+    let mut subscription = dialer.behaviour_mut().subscribe();
+    listener.listen().with_memory_addr_external().await;
+    let listener_id = *listener.local_peer_id();
+
+    dialer.connect(&mut listener).await;
+
+    let Event::AddressAdded { peer_id, address } = subscription.next().await.unwrap() else {
+        panic!("expected a new address")
+    };
+    assert_eq!(peer_id, listener_id);
+    assert_eq!(
+        dialer
+            .behaviour()
+            .addresses_of_peer(&listener_id)
+            .collect::<Vec<_>>(),
+        [&address]
+    );
+}
+
+#[async_std::test]
+async fn addresses_expire() {
+    let config = Config::default()
+        .with_address_ttl(Duration::from_millis(10))
+        .with_cleanup_interval(Duration::from_millis(10));
+    let mut swarm = Swarm::new_ephemeral(|_| Behaviour::new(config));
+    let peer_id = PeerId::random();
+    let address = "/memory/1234".parse().unwrap();
+    swarm.behaviour_mut().add_address(peer_id, address);
+
+    assert!(matches!(
+        swarm.next_behaviour_event().await,
+        Event::AddressAdded { .. }
+    ));
+    assert!(matches!(
+        swarm.next_behaviour_event().await,
+        Event::AddressExpired { peer_id: p, .. } if p == peer_id
+    ));
+    assert_eq!(swarm.behaviour().peers().count(), 0);
+}
+
+#[test]
+fn protocol_changes_are_reported() {
+    let mut store = Behaviour::new(Config::default());
+    let mut subscription = store.subscribe();
+    let peer_id = PeerId::random();
+    let ping = StreamProtocol::new("/ipfs/ping/1.0.0");
+    let identify = StreamProtocol::new("/ipfs/id/1.0.0");
+
+    store.set_protocols(peer_id, [ping.clone()]);
+    store.set_protocols(peer_id, [ping.clone()]);
+    store.set_protocols(peer_id, [identify.clone()]);
+
+    assert_eq!(
+        subscription.next().now_or_never().unwrap(),
+        Some(Event::ProtocolsChanged {
+            peer_id,
+            added: vec![ping.clone()],
+            removed: vec![],
+        })
+    );
+    assert_eq!(
+        subscription.next().now_or_never().unwrap(),
+        Some(Event::ProtocolsChanged {
+            peer_id,
+            added: vec![identify],
+            removed: vec![ping],
+        })
+    );
+    assert!(subscription.next().now_or_never().is_none());
+}
+
+#[test]
+fn lagging_subscription_is_closed() {
+    let mut store = Behaviour::new(Config::default().with_subscription_buffer(0));
+    let mut subscription = store.subscribe();
+    let peer_id = PeerId::random();
+
+    store.add_address(peer_id, "/memory/1".parse().unwrap());
+    store.add_address(peer_id, "/memory/2".parse().unwrap());
+
+    assert!(matches!(
+        subscription.next().now_or_never().unwrap(),
+        Some(Event::AddressAdded { .. })
+    ));
+    assert_eq!(subscription.next().now_or_never().unwrap(), None);
+}
@@ -0,0 +1,705 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::FutureExt;
+use futures_timer::Delay;
+use libp2p_core::{Endpoint, Multiaddr};
+use libp2p_identify as identify;
+use libp2p_identity::PeerId;
+use libp2p_kad::{self as kad, store::MemoryStore};
+use libp2p_swarm::{
+    dial_opts::{DialOpts, PeerCondition},
+    ConnectionClosed, ConnectionDenied, ConnectionHandlerSelect, ConnectionId, DialFailure,
+    FromSwarm, NetworkBehaviour, StreamProtocol, THandler, THandlerInEvent, THandlerOutEvent,
+    ToSwarm,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use web_time::Instant;
+
+/// The prefix of the data hashed into the keys of services.
+const KEY_PREFIX: &[u8] = b"/libp2p/service/";
+
+/// The configuration of the service discovery [`Behaviour`].
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    lookup_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            lookup_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Config {
+    /// Sets the time after which a lookup of [`Behaviour::find_service`] finishes, even if
+    /// providers are still being verified.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn with_lookup_timeout(mut self, timeout: Duration) -> Self {
+        self.lookup_timeout = timeout;
+        self
+    }
+}
+
+/// A service advertised via [`Behaviour::advertise`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    /// The name under which the service is looked up.
+    pub name: String,
+    /// The protocol of the service, which providers must support.
+    pub protocol: StreamProtocol,
+    /// Application-specific metadata, e.g. the capacity of the service.
+    pub metadata: Vec<u8>,
+}
+
+impl Service {
+    /// Creates a service without metadata.
+    pub fn new(name: impl Into<String>, protocol: StreamProtocol) -> Self {
+        Self {
+            name: name.into(),
+            protocol,
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Sets the metadata of the service.
+    pub fn with_metadata(mut self, metadata: impl Into<Vec<u8>>) -> Self {
+        self.metadata = metadata.into();
+        self
+    }
+}
+
+/// A live and verified provider of a service, found via [`Behaviour::find_service`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provider {
+    pub peer_id: PeerId,
+    /// The protocol of the service, confirmed to be supported by the provider.
+    pub protocol: StreamProtocol,
+    /// The metadata the provider advertised the service with.
+    pub metadata: Vec<u8>,
+    /// The listen addresses of the provider, as reported by identify.
+    pub listen_addrs: Vec<Multiaddr>,
+}
+
+/// The identifier of a lookup started via [`Behaviour::find_service`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LookupId(u64);
+
+/// The events of the service discovery [`Behaviour`].
+#[derive(Debug)]
+pub enum Event {
+    /// Advertising a service via [`Behaviour::advertise`] finished.
+    Advertised {
+        service: String,
+        result: Result<(), kad::AddProviderError>,
+    },
+    /// A live provider of a service was found and verified.
+    ProviderFound {
+        lookup: LookupId,
+        service: String,
+        provider: Provider,
+    },
+    /// A lookup finished, either because all providers were verified or because it timed out.
+    LookupFinished {
+        lookup: LookupId,
+        service: String,
+        /// The number of providers found.
+        providers: usize,
+    },
+    /// An event of the Kademlia behaviour, including the progress of the queries of the
+    /// service discovery.
+    Kademlia(kad::Event),
+    /// An event of the identify behaviour.
+    Identify(identify::Event),
+}
+
+#[derive(NetworkBehaviour)]
+#[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+struct Inner {
+    kademlia: kad::Behaviour<MemoryStore>,
+    identify: identify::Behaviour,
+}
+
+/// A lookup of the providers of a service.
+#[derive(Debug)]
+struct Lookup {
+    service: String,
+    /// Whether the lookup of the provider records finished.
+    providers_done: bool,
+    /// The providers being verified, along with their record once it was received.
+    candidates: HashMap<PeerId, Option<Service>>,
+    /// The verified providers.
+    found: HashSet<PeerId>,
+    deadline: Instant,
+}
+
+/// A [`NetworkBehaviour`] advertising local services in the DHT and looking up the verified
+/// providers of services.
+pub struct Behaviour {
+    inner: Inner,
+    config: Config,
+    local_peer_id: PeerId,
+    advertised: HashMap<String, Service>,
+    /// The queries publishing provider records, by the name of their service.
+    provide_queries: HashMap<kad::QueryId, String>,
+    lookups: HashMap<LookupId, Lookup>,
+    provider_queries: HashMap<kad::QueryId, LookupId>,
+    record_queries: HashMap<kad::QueryId, (LookupId, PeerId)>,
+    /// The latest identify information of the connected peers.
+    identified: HashMap<PeerId, identify::Info>,
+    next_lookup_id: u64,
+    events: VecDeque<ToSwarm<Event, THandlerInEvent<Self>>>,
+    timeout_check: Delay,
+}
+
+impl Behaviour {
+    /// Creates a service discovery behaviour driving the given Kademlia and identify behaviours
+    /// of the local peer.
+    pub fn new(
+        local_peer_id: PeerId,
+        kademlia: kad::Behaviour<MemoryStore>,
+        identify: identify::Behaviour,
+        config: Config,
+    ) -> Self {
+        Self {
+            inner: Inner { kademlia, identify },
+            config,
+            local_peer_id,
+            advertised: HashMap::new(),
+            provide_queries: HashMap::new(),
+            lookups: HashMap::new(),
+            provider_queries: HashMap::new(),
+            record_queries: HashMap::new(),
+            identified: HashMap::new(),
+            next_lookup_id: 0,
+            events: VecDeque::new(),
+            timeout_check: Delay::new(Duration::from_secs(1)),
+        }
+    }
+
+    /// Advertises a local service, replacing the service of the same name, if any.
+    ///
+    /// The local peer is announced as provider of the service, and the protocol and metadata of
+    /// the service are published as a record of the local peer. Both are republished by
+    /// Kademlia. The outcome is reported as [`Event::Advertised`].
+    pub fn advertise(&mut self, service: Service) -> Result<(), kad::store::Error> {
+        let record = kad::Record {
+            key: record_key(&service.name, &self.local_peer_id),
+            value: encode_service(&service),
+            publisher: Some(self.local_peer_id),
+            expires: None,
+        };
+        self.inner.kademlia.put_record(record, kad::Quorum::One)?;
+        let query = self
+            .inner
+            .kademlia
+            .start_providing(provider_key(&service.name))?;
+        self.provide_queries.insert(query, service.name.clone());
+        self.advertised.insert(service.name.clone(), service);
+        Ok(())
+    }
+
+    /// Stops advertising the service of the given name, returning it if it was advertised.
+    ///
+    /// The provider record and the record of the service expire in the DHT unless they are
+    /// republished.
+    pub fn stop_advertising(&mut self, name: &str) -> Option<Service> {
+        let service = self.advertised.remove(name)?;
+        self.inner
+            .kademlia
+            .stop_providing(&provider_key(&service.name));
+        self.inner
+            .kademlia
+            .remove_record(&record_key(&service.name, &self.local_peer_id));
+        Some(service)
+    }
+
+    /// Returns the services advertised by the local peer.
+    pub fn advertised(&self) -> impl Iterator<Item = &Service> {
+        self.advertised.values()
+    }
+
+    /// Looks up the providers of the service of the given name.
+    ///
+    /// Every provider is dialed unless connected already, and reported as
+    /// [`Event::ProviderFound`] once identify confirmed that it supports the protocol of the
+    /// service. The lookup ends with [`Event::LookupFinished`].
+    pub fn find_service(&mut self, name: impl Into<String>) -> LookupId {
+        let service = name.into();
+        let lookup = LookupId(self.next_lookup_id);
+        self.next_lookup_id += 1;
+
+        let query = self.inner.kademlia.get_providers(provider_key(&service));
+        self.provider_queries.insert(query, lookup);
+        self.lookups.insert(
+            lookup,
+            Lookup {
+                service,
+                providers_done: false,
+                candidates: HashMap::new(),
+                found: HashSet::new(),
+                deadline: Instant::now() + self.config.lookup_timeout,
+            },
+        );
+        lookup
+    }
+
+    /// Returns the Kademlia behaviour, e.g. to add the addresses of bootstrap peers.
+    pub fn kademlia(&self) -> &kad::Behaviour<MemoryStore> {
+        &self.inner.kademlia
+    }
+
+    /// Returns the Kademlia behaviour mutably.
+    pub fn kademlia_mut(&mut self) -> &mut kad::Behaviour<MemoryStore> {
+        &mut self.inner.kademlia
+    }
+
+    /// Returns the identify behaviour.
+    pub fn identify(&self) -> &identify::Behaviour {
+        &self.inner.identify
+    }
+
+    /// Returns the identify behaviour mutably.
+    pub fn identify_mut(&mut self) -> &mut identify::Behaviour {
+        &mut self.inner.identify
+    }
+
+    fn on_kademlia_event(&mut self, event: &kad::Event) {
+        let kad::Event::OutboundQueryProgressed {
+            id, result, step, ..
+        } = event
+        else {
+            return;
+        };
+
+        match result {
+            kad::QueryResult::StartProviding(result) => {
+                let Some(service) = self.provide_queries.remove(id) else {
+                    return;
+                };
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::Advertised {
+                        service,
+                        result: result.clone().map(|_| ()),
+                    }));
+            }
+            kad::QueryResult::GetProviders(result) => {
+                let Some(lookup) = self.provider_queries.get(id).copied() else {
+                    return;
+                };
+                if let Ok(kad::GetProvidersOk::FoundProviders { providers, .. }) = result {
+                    for provider in providers {
+                        self.on_provider(lookup, *provider);
+                    }
+                }
+                if step.last || result.is_err() {
+                    self.provider_queries.remove(id);
+                    if let Some(l) = self.lookups.get_mut(&lookup) {
+                        l.providers_done = true;
+                    }
+                    self.try_finish(lookup);
+                }
+            }
+            kad::QueryResult::GetRecord(result) => {
+                let Some((lookup, provider)) = self.record_queries.get(id).copied() else {
+                    return;
+                };
+                if let Ok(kad::GetRecordOk::FoundRecord(kad::PeerRecord { record, .. })) = result {
+                    self.on_record(lookup, provider, record);
+                }
+                if step.last || result.is_err() {
+                    self.record_queries.remove(id);
+                    // The provider cannot be verified without its record.
+                    if let Some(l) = self.lookups.get_mut(&lookup) {
+                        if matches!(l.candidates.get(&provider), Some(None)) {
+                            tracing::debug!(peer=%provider, service=%l.service, "No record of provider");
+                            l.candidates.remove(&provider);
+                        }
+                    }
+                    self.try_finish(lookup);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts verifying a provider found by a lookup.
+    fn on_provider(&mut self, lookup: LookupId, provider: PeerId) {
+        let Some(l) = self.lookups.get_mut(&lookup) else {
+            return;
+        };
+        if provider == self.local_peer_id
+            || l.found.contains(&provider)
+            || l.candidates.contains_key(&provider)
+        {
+            return;
+        }
+        l.candidates.insert(provider, None);
+
+        let query = self
+            .inner
+            .kademlia
+            .get_record(record_key(&l.service, &provider));
+        self.record_queries.insert(query, (lookup, provider));
+        if !self.identified.contains_key(&provider) {
+            self.events.push_back(ToSwarm::Dial {
+                opts: DialOpts::peer_id(provider)
+                    .condition(PeerCondition::DisconnectedAndNotDialing)
+                    .build(),
+            });
+        }
+    }
+
+    fn on_record(&mut self, lookup: LookupId, provider: PeerId, record: &kad::Record) {
+        let Some(l) = self.lookups.get_mut(&lookup) else {
+            return;
+        };
+        let Some(candidate @ None) = l.candidates.get_mut(&provider) else {
+            return;
+        };
+        if record
+            .publisher
+            .is_some_and(|publisher| publisher != provider)
+        {
+            return;
+        }
+        match decode_service(&l.service, &record.value) {
+            Some(service) => *candidate = Some(service),
+            None => {
+                tracing::debug!(peer=%provider, service=%l.service, "Malformed record of provider");
+                l.candidates.remove(&provider);
+                return;
+            }
+        }
+        self.try_verify(lookup, provider);
+    }
+
+    /// Reports the provider if both its record and its identify information are known and the
+    /// latter confirms the protocol of the former.
+    fn try_verify(&mut self, lookup: LookupId, provider: PeerId) {
+        let Some(l) = self.lookups.get_mut(&lookup) else {
+            return;
+        };
+        let (Some(Some(service)), Some(info)) =
+            (l.candidates.get(&provider), self.identified.get(&provider))
+        else {
+            return;
+        };
+
+        if info.protocols.contains(&service.protocol) {
+            let provider = Provider {
+                peer_id: provider,
+                protocol: service.protocol.clone(),
+                metadata: service.metadata.clone(),
+                listen_addrs: info.listen_addrs.clone(),
+            };
+            l.found.insert(provider.peer_id);
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::ProviderFound {
+                    lookup,
+                    service: l.service.clone(),
+                    provider,
+                }));
+        } else {
+            tracing::debug!(
+                peer=%provider,
+                service=%l.service,
+                protocol=%service.protocol,
+                "Provider does not support the protocol of the service"
+            );
+        }
+        l.candidates.remove(&provider);
+        self.try_finish(lookup);
+    }
+
+    /// Finishes the lookup once all providers are found and verified.
+    fn try_finish(&mut self, lookup: LookupId) {
+        let Some(l) = self.lookups.get(&lookup) else {
+            return;
+        };
+        if l.providers_done && l.candidates.is_empty() {
+            self.finish(lookup);
+        }
+    }
+
+    fn finish(&mut self, lookup: LookupId) {
+        let Some(l) = self.lookups.remove(&lookup) else {
+            return;
+        };
+
+        // Stop the queries of the lookup which are still running.
+        let kademlia = &mut self.inner.kademlia;
+        let mut stop = |query: &kad::QueryId| {
+            if let Some(mut query) = kademlia.query_mut(query) {
+                query.finish();
+            }
+        };
+        self.provider_queries.retain(|query, l| {
+            if *l == lookup {
+                stop(query);
+            }
+            *l != lookup
+        });
+        self.record_queries.retain(|query, (l, _)| {
+            if *l == lookup {
+                stop(query);
+            }
+            *l != lookup
+        });
+
+        self.events
+            .push_back(ToSwarm::GenerateEvent(Event::LookupFinished {
+                lookup,
+                service: l.service,
+                providers: l.found.len(),
+            }));
+    }
+
+    fn on_identify_event(&mut self, event: &identify::Event) {
+        let identify::Event::Received { peer_id, info, .. } = event else {
+            return;
+        };
+        self.identified.insert(*peer_id, info.clone());
+
+        let lookups = self
+            .lookups
+            .iter()
+            .filter(|(_, l)| l.candidates.contains_key(peer_id))
+            .map(|(lookup, _)| *lookup)
+            .collect::<Vec<_>>();
+        for lookup in lookups {
+            self.try_verify(lookup, *peer_id);
+        }
+    }
+
+    /// Gives up verifying the given provider, which cannot be connected to.
+    fn drop_candidate(&mut self, provider: &PeerId) {
+        let lookups = self
+            .lookups
+            .iter_mut()
+            .filter_map(|(lookup, l)| l.candidates.remove(provider).map(|_| *lookup))
+            .collect::<Vec<_>>();
+        for lookup in lookups {
+            self.try_finish(lookup);
+        }
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = ConnectionHandlerSelect<
+        THandler<kad::Behaviour<MemoryStore>>,
+        THandler<identify::Behaviour>,
+    >;
+    type ToSwarm = Event;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.inner
+            .handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.inner.handle_pending_outbound_connection(
+            connection_id,
+            maybe_peer,
+            addresses,
+            effective_role,
+        )
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_outbound_connection(connection_id, peer, addr, role_override)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        self.inner.on_swarm_event(event);
+
+        match event {
+            FromSwarm::ConnectionClosed(ConnectionClosed {
+                peer_id,
+                remaining_established: 0,
+                ..
+            }) => {
+                self.identified.remove(&peer_id);
+            }
+            FromSwarm::DialFailure(DialFailure {
+                peer_id: Some(peer_id),
+                ..
+            }) if !self.identified.contains_key(&peer_id) => {
+                self.drop_candidate(&peer_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner
+            .on_connection_handler_event(peer_id, connection_id, event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(event);
+            }
+
+            if self.timeout_check.poll_unpin(cx).is_ready() {
+                self.timeout_check.reset(Duration::from_secs(1));
+                let now = Instant::now();
+                let expired = self
+                    .lookups
+                    .iter()
+                    .filter(|(_, l)| l.deadline <= now)
+                    .map(|(lookup, _)| *lookup)
+                    .collect::<Vec<_>>();
+                for lookup in expired {
+                    self.finish(lookup);
+                }
+                continue;
+            }
+
+            match self.inner.poll(cx) {
+                Poll::Ready(ToSwarm::GenerateEvent(InnerEvent::Kademlia(event))) => {
+                    self.on_kademlia_event(&event);
+                    self.events
+                        .push_back(ToSwarm::GenerateEvent(Event::Kademlia(event)));
+                }
+                Poll::Ready(ToSwarm::GenerateEvent(InnerEvent::Identify(event))) => {
+                    self.on_identify_event(&event);
+                    self.events
+                        .push_back(ToSwarm::GenerateEvent(Event::Identify(event)));
+                }
+                Poll::Ready(event) => {
+                    return Poll::Ready(event.map_out(|_| unreachable!("handled above")));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// The key of the provider records of a service.
+fn provider_key(name: &str) -> kad::RecordKey {
+    let digest = Sha256::new()
+        .chain_update(KEY_PREFIX)
+        .chain_update(name)
+        .finalize();
+    kad::RecordKey::new(&digest)
+}
+
+/// The key of the record of a service advertised by the given peer.
+fn record_key(name: &str, peer_id: &PeerId) -> kad::RecordKey {
+    let mut key = provider_key(name).to_vec();
+    key.extend_from_slice(&peer_id.to_bytes());
+    kad::RecordKey::new(&key)
+}
+
+/// Encodes the protocol and metadata of a service as the length of the protocol as big-endian
+/// `u16`, the protocol and the metadata.
+fn encode_service(service: &Service) -> Vec<u8> {
+    let protocol = service.protocol.as_ref().as_bytes();
+    let mut value = Vec::with_capacity(2 + protocol.len() + service.metadata.len());
+    value.extend_from_slice(&(protocol.len() as u16).to_be_bytes());
+    value.extend_from_slice(protocol);
+    value.extend_from_slice(&service.metadata);
+    value
+}
+
+fn decode_service(name: &str, value: &[u8]) -> Option<Service> {
+    if value.len() < 2 {
+        return None;
+    }
+    let (len, rest) = value.split_at(2);
+    let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+    if rest.len() < len {
+        return None;
+    }
+    let (protocol, metadata) = rest.split_at(len);
+    let protocol =
+        StreamProtocol::try_from_owned(String::from_utf8(protocol.to_vec()).ok()?).ok()?;
+    Some(Service {
+        name: name.to_owned(),
+        protocol,
+        metadata: metadata.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_roundtrips_through_record() {
+        let service = Service::new("relay", StreamProtocol::new("/relay/1.0.0"))
+            .with_metadata(b"capacity=10".to_vec());
+
+        assert_eq!(
+            decode_service("relay", &encode_service(&service)),
+            Some(service)
+        );
+        assert_eq!(decode_service("relay", &[0, 10, b'/']), None);
+    }
+}
@@ -0,0 +1,40 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Advertisement and lookup of services over the Kademlia DHT.
+//!
+//! A [`Service`] is a named protocol along with application-specific metadata, e.g. a relay
+//! or a storage service. The [`Behaviour`] advertises the local services as provider records
+//! under keys derived from their names, and publishes their protocol and metadata as a record
+//! of the local peer, see [`Behaviour::advertise`].
+//!
+//! [`Behaviour::find_service`] looks up the providers of a service and only reports those
+//! which are live and verified: the provider is connected to, and identify confirms that it
+//! supports the protocol of the service. Stale provider records of peers which went away are
+//! thereby filtered out.
+//!
+//! The [`Behaviour`] drives its own Kademlia and identify behaviours, whose events are passed
+//! through as [`Event::Kademlia`] and [`Event::Identify`].
+
+#![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
+
+mod behaviour;
+
+pub use behaviour::{Behaviour, Config, Event, LookupId, Provider, Service};
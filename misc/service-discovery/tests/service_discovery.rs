@@ -0,0 +1,111 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_identify as identify;
+use libp2p_identity::Keypair;
+use libp2p_kad::{self as kad, store::MemoryStore};
+use libp2p_service_discovery::{Behaviour, Config, Event, Service};
+use libp2p_swarm::{StreamProtocol, Swarm, SwarmEvent};
+use libp2p_swarm_test::SwarmExt;
+
+fn new_behaviour(key: Keypair) -> Behaviour {
+    let peer_id = key.public().to_peer_id();
+    let mut kademlia = kad::Behaviour::new(peer_id, MemoryStore::new(peer_id));
+    kademlia.set_mode(Some(kad::Mode::Server));
+    let identify = identify::Behaviour::new(identify::Config::new(
+        "/test/1.0.0".to_owned(),
+        key.public(),
+    ));
+
+    Behaviour::new(peer_id, kademlia, identify, Config::default())
+}
+
+#[async_std::test]
+async fn only_providers_supporting_the_protocol_are_found() {
+    let mut provider = Swarm::new_ephemeral(new_behaviour);
+    let mut seeker = Swarm::new_ephemeral(new_behaviour);
+    provider.listen().with_memory_addr_external().await;
+    seeker.connect(&mut provider).await;
+
+    let provider_id = *provider.local_peer_id();
+    // The provider supports the Kademlia protocol, but not the relay protocol.
+    provider
+        .behaviour_mut()
+        .advertise(
+            Service::new("dht", StreamProtocol::new("/ipfs/kad/1.0.0"))
+                .with_metadata(b"v1".to_vec()),
+        )
+        .unwrap();
+    provider
+        .behaviour_mut()
+        .advertise(Service::new("relay", StreamProtocol::new("/relay/1.0.0")))
+        .unwrap();
+    async_std::task::spawn(provider.loop_on_next());
+
+    seeker
+        .wait(|e| match e {
+            SwarmEvent::Behaviour(Event::Kademlia(kad::Event::RoutingUpdated { peer, .. })) => {
+                (peer == provider_id).then_some(())
+            }
+            _ => None,
+        })
+        .await;
+
+    let lookup = seeker.behaviour_mut().find_service("dht");
+    let found = seeker
+        .wait(|e| match e {
+            SwarmEvent::Behaviour(Event::ProviderFound {
+                lookup: l,
+                provider,
+                ..
+            }) if l == lookup => Some(provider),
+            _ => None,
+        })
+        .await;
+    assert_eq!(found.peer_id, provider_id);
+    assert_eq!(found.metadata, b"v1");
+    let providers = seeker
+        .wait(|e| match e {
+            SwarmEvent::Behaviour(Event::LookupFinished {
+                lookup: l,
+                providers,
+                ..
+            }) if l == lookup => Some(providers),
+            _ => None,
+        })
+        .await;
+    assert_eq!(providers, 1);
+
+    let lookup = seeker.behaviour_mut().find_service("relay");
+    let providers = seeker
+        .wait(|e| match e {
+            SwarmEvent::Behaviour(Event::ProviderFound { lookup: l, .. }) if l == lookup => {
+                panic!("provider not supporting the protocol must not be found")
+            }
+            SwarmEvent::Behaviour(Event::LookupFinished {
+                lookup: l,
+                providers,
+                ..
+            }) if l == lookup => Some(providers),
+            _ => None,
+        })
+        .await;
+    assert_eq!(providers, 0);
+}
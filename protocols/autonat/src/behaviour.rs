@@ -35,8 +35,8 @@ use libp2p_request_response::{
 };
 use libp2p_swarm::{
     behaviour::{AddressChange, ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm},
-    ConnectionDenied, ConnectionId, ListenAddresses, NetworkBehaviour, THandler, THandlerInEvent,
-    THandlerOutEvent, ToSwarm,
+    ConnectionDenied, ConnectionId, ExternalAddresses, ListenAddresses, NetworkBehaviour, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
 use std::{
     collections::{HashMap, HashSet, VecDeque},
@@ -82,6 +82,14 @@ pub struct Config {
     /// private ip address. Note that this does not apply for servers that are added via
     /// [`Behaviour::add_server`].
     pub only_global_ips: bool,
+    /// Probe the reachability of each [`TransportKind`] separately.
+    ///
+    /// Every probe only includes the candidate addresses of a single transport, cycling through
+    /// the transports of all candidate addresses. The status of each transport is tracked
+    /// separately and reported through [`Event::TransportStatusChanged`]. External addresses
+    /// of a transport that flipped to [`NatStatus::Private`] are expired.
+    /// The overall [`NatStatus`] is public as soon as one transport is public.
+    pub probe_per_transport: bool,
 }
 
 impl Default for Config {
@@ -99,6 +107,7 @@ impl Default for Config {
             throttle_clients_peer_max: 3,
             throttle_clients_period: Duration::from_secs(1),
             only_global_ips: true,
+            probe_per_transport: false,
         }
     }
 }
@@ -117,6 +126,29 @@ impl NatStatus {
     }
 }
 
+/// Transport whose reachability is tracked separately if [`Config::probe_per_transport`] is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TransportKind {
+    Tcp,
+    Quic,
+    /// Any other transport, e.g. WebRTC or addresses without an IP layer.
+    Other,
+}
+
+impl TransportKind {
+    /// Returns the transport the given address is dialed over.
+    pub fn from_address(address: &Multiaddr) -> Self {
+        address
+            .iter()
+            .find_map(|p| match p {
+                Protocol::Tcp(_) => Some(TransportKind::Tcp),
+                Protocol::Quic | Protocol::QuicV1 => Some(TransportKind::Quic),
+                _ => None,
+            })
+            .unwrap_or(TransportKind::Other)
+    }
+}
+
 /// Unique identifier for a probe.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ProbeId(usize);
@@ -143,6 +175,16 @@ pub enum Event {
         /// New status.
         new: NatStatus,
     },
+    /// The assumed NAT status of a single transport changed.
+    ///
+    /// Only reported if [`Config::probe_per_transport`] is enabled.
+    TransportStatusChanged {
+        transport: TransportKind,
+        /// Former status.
+        old: NatStatus,
+        /// New status.
+        new: NatStatus,
+    },
 }
 
 /// [`NetworkBehaviour`] for AutoNAT.
@@ -176,6 +218,12 @@ pub struct Behaviour {
     // Confidence in the assumed NAT status.
     confidence: usize,
 
+    // Assumed NAT status and confidence per transport, if probing per transport.
+    transport_status: HashMap<TransportKind, (NatStatus, usize)>,
+
+    // Transport that was probed last, if probing per transport.
+    last_probed_transport: Option<TransportKind>,
+
     // Timer for the next probe.
     schedule_probe: Delay,
 
@@ -190,8 +238,8 @@ pub struct Behaviour {
         ),
     >,
 
-    // Ongoing outbound probes and mapped to the inner request id.
-    ongoing_outbound: HashMap<OutboundRequestId, ProbeId>,
+    // Ongoing outbound probes and the probed transport, mapped to the inner request id.
+    ongoing_outbound: HashMap<OutboundRequestId, (ProbeId, Option<TransportKind>)>,
 
     // Connected peers with the observed address of each connection.
     // If the endpoint of a connection is relayed or not global (in case of Config::only_global_ips),
@@ -211,6 +259,7 @@ pub struct Behaviour {
     probe_id: ProbeId,
 
     listen_addresses: ListenAddresses,
+    external_addresses: ExternalAddresses,
     other_candidates: HashSet<Multiaddr>,
 }
 
@@ -233,12 +282,15 @@ impl Behaviour {
             connected: HashMap::default(),
             nat_status: NatStatus::Unknown,
             confidence: 0,
+            transport_status: HashMap::default(),
+            last_probed_transport: None,
             throttled_servers: Vec::new(),
             throttled_clients: Vec::new(),
             last_probe: None,
             pending_actions: VecDeque::new(),
             probe_id: ProbeId(0),
             listen_addresses: Default::default(),
+            external_addresses: Default::default(),
             other_candidates: Default::default(),
         }
     }
@@ -262,6 +314,16 @@ impl Behaviour {
         self.confidence
     }
 
+    /// Assumed NAT status of the given transport.
+    ///
+    /// Always [`NatStatus::Unknown`] unless [`Config::probe_per_transport`] is enabled.
+    pub fn transport_status(&self, transport: TransportKind) -> NatStatus {
+        self.transport_status
+            .get(&transport)
+            .map(|(status, _)| status.clone())
+            .unwrap_or(NatStatus::Unknown)
+    }
+
     /// Add a peer to the list over servers that may be used for probes.
     /// These peers are used for dial-request even if they are currently not connection, in which case a connection will be
     /// establish before sending the dial-request.
@@ -296,10 +358,13 @@ impl Behaviour {
             throttled_servers: &mut self.throttled_servers,
             nat_status: &mut self.nat_status,
             confidence: &mut self.confidence,
+            transport_status: &mut self.transport_status,
+            last_probed_transport: &mut self.last_probed_transport,
             ongoing_outbound: &mut self.ongoing_outbound,
             last_probe: &mut self.last_probe,
             schedule_probe: &mut self.schedule_probe,
             listen_addresses: &self.listen_addresses,
+            external_addresses: &self.external_addresses,
             other_candidates: &self.other_candidates,
         }
     }
@@ -519,6 +584,7 @@ impl NetworkBehaviour for Behaviour {
 
     fn on_swarm_event(&mut self, event: FromSwarm) {
         self.listen_addresses.on_swarm_event(&event);
+        self.external_addresses.on_swarm_event(&event);
         self.inner.on_swarm_event(event);
 
         match event {
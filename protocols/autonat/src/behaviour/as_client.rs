@@ -22,17 +22,18 @@ use crate::ResponseError;
 
 use super::{
     Action, AutoNatCodec, Config, DialRequest, DialResponse, Event, HandleInnerEvent, NatStatus,
-    ProbeId,
+    ProbeId, TransportKind,
 };
 use futures::FutureExt;
 use futures_timer::Delay;
 use libp2p_core::Multiaddr;
 use libp2p_identity::PeerId;
 use libp2p_request_response::{self as request_response, OutboundFailure, OutboundRequestId};
-use libp2p_swarm::{ConnectionId, ListenAddresses, ToSwarm};
+use libp2p_swarm::{ConnectionId, ExternalAddresses, ListenAddresses, ToSwarm};
 use rand::{seq::SliceRandom, thread_rng};
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    ops::Bound,
     task::{Context, Poll},
     time::Duration,
 };
@@ -91,10 +92,14 @@ pub(crate) struct AsClient<'a> {
     pub(crate) throttled_servers: &'a mut Vec<(PeerId, Instant)>,
     pub(crate) nat_status: &'a mut NatStatus,
     pub(crate) confidence: &'a mut usize,
-    pub(crate) ongoing_outbound: &'a mut HashMap<OutboundRequestId, ProbeId>,
+    pub(crate) transport_status: &'a mut HashMap<TransportKind, (NatStatus, usize)>,
+    pub(crate) last_probed_transport: &'a mut Option<TransportKind>,
+    pub(crate) ongoing_outbound:
+        &'a mut HashMap<OutboundRequestId, (ProbeId, Option<TransportKind>)>,
     pub(crate) last_probe: &'a mut Option<Instant>,
     pub(crate) schedule_probe: &'a mut Delay,
     pub(crate) listen_addresses: &'a ListenAddresses,
+    pub(crate) external_addresses: &'a ExternalAddresses,
     pub(crate) other_candidates: &'a HashSet<Multiaddr>,
 }
 
@@ -114,7 +119,7 @@ impl<'a> HandleInnerEvent for AsClient<'a> {
             } => {
                 tracing::debug!(?response, "Outbound dial-back request returned response");
 
-                let probe_id = self
+                let (probe_id, transport) = self
                     .ongoing_outbound
                     .remove(&request_id)
                     .expect("OutboundRequestId exists.");
@@ -136,11 +141,21 @@ impl<'a> HandleInnerEvent for AsClient<'a> {
 
                 actions.push_back(ToSwarm::GenerateEvent(Event::OutboundProbe(event)));
 
-                if let Some(old) = self.handle_reported_status(response.result.clone().into()) {
-                    actions.push_back(ToSwarm::GenerateEvent(Event::StatusChanged {
-                        old,
-                        new: self.nat_status.clone(),
-                    }));
+                match transport {
+                    Some(transport) => actions.extend(self.handle_reported_transport_status(
+                        transport,
+                        response.result.clone().into(),
+                    )),
+                    None => {
+                        if let Some(old) =
+                            self.handle_reported_status(response.result.clone().into())
+                        {
+                            actions.push_back(ToSwarm::GenerateEvent(Event::StatusChanged {
+                                old,
+                                new: self.nat_status.clone(),
+                            }));
+                        }
+                    }
                 }
 
                 if let Ok(address) = response.result {
@@ -162,6 +177,7 @@ impl<'a> HandleInnerEvent for AsClient<'a> {
                 let probe_id = self
                     .ongoing_outbound
                     .remove(&request_id)
+                    .map(|(probe_id, _)| probe_id)
                     .unwrap_or_else(|| self.probe_id.next());
 
                 self.schedule_probe.reset(Duration::ZERO);
@@ -185,15 +201,24 @@ impl<'a> AsClient<'a> {
             Poll::Ready(()) => {
                 self.schedule_probe.reset(self.config.retry_interval);
 
-                let addresses = self
+                let mut addresses: Vec<_> = self
                     .other_candidates
                     .iter()
                     .chain(self.listen_addresses.iter())
                     .cloned()
                     .collect();
 
+                let transport = if self.config.probe_per_transport {
+                    self.next_transport(&addresses)
+                } else {
+                    None
+                };
+                if let Some(transport) = transport {
+                    addresses.retain(|a| TransportKind::from_address(a) == transport);
+                }
+
                 let probe_id = self.probe_id.next();
-                let event = match self.do_probe(probe_id, addresses) {
+                let event = match self.do_probe(probe_id, addresses, transport) {
                     Ok(peer) => OutboundProbeEvent::Request { probe_id, peer },
                     Err(error) => {
                         self.handle_reported_status(NatStatus::Unknown);
@@ -239,6 +264,31 @@ impl<'a> AsClient<'a> {
                 self.schedule_next_probe(Duration::ZERO);
             }
         }
+        for (status, confidence) in self.transport_status.values_mut() {
+            if matches!(status, NatStatus::Public(public_address) if public_address == addr) {
+                *confidence = 0;
+                *status = NatStatus::Unknown;
+            }
+        }
+    }
+
+    // Select the transport for the next probe, cycling through the transports of the given addresses.
+    fn next_transport(&mut self, addresses: &[Multiaddr]) -> Option<TransportKind> {
+        let transports = addresses
+            .iter()
+            .map(TransportKind::from_address)
+            .collect::<BTreeSet<_>>();
+        let next = self
+            .last_probed_transport
+            .and_then(|last| {
+                transports
+                    .range((Bound::Excluded(last), Bound::Unbounded))
+                    .next()
+            })
+            .or_else(|| transports.first())
+            .copied();
+        *self.last_probed_transport = next;
+        next
     }
 
     // Select a random server for the probe.
@@ -272,6 +322,7 @@ impl<'a> AsClient<'a> {
         &mut self,
         probe_id: ProbeId,
         addresses: Vec<Multiaddr>,
+        transport: Option<TransportKind>,
     ) -> Result<PeerId, OutboundProbeError> {
         let _ = self.last_probe.insert(Instant::now());
         if addresses.is_empty() {
@@ -290,7 +341,8 @@ impl<'a> AsClient<'a> {
         );
         self.throttled_servers.push((server, Instant::now()));
         tracing::debug!(peer=%server, "Send dial-back request to peer");
-        self.ongoing_outbound.insert(request_id, probe_id);
+        self.ongoing_outbound
+            .insert(request_id, (probe_id, transport));
         Ok(server)
     }
 
@@ -310,44 +362,130 @@ impl<'a> AsClient<'a> {
     fn handle_reported_status(&mut self, reported_status: NatStatus) -> Option<NatStatus> {
         self.schedule_next_probe(self.config.retry_interval);
 
-        if matches!(reported_status, NatStatus::Unknown) {
-            return None;
+        let confirmed =
+            !matches!(reported_status, NatStatus::Unknown) && reported_status == *self.nat_status;
+        let flipped = adapt_status(
+            self.nat_status,
+            self.confidence,
+            reported_status,
+            self.config.confidence_max,
+        );
+        if confirmed && *self.confidence >= self.config.confidence_max {
+            // Delay with (usually longer) refresh-interval.
+            self.schedule_next_probe(self.config.refresh_interval);
         }
 
-        if reported_status == *self.nat_status {
-            if *self.confidence < self.config.confidence_max {
-                *self.confidence += 1;
-            }
-            // Delay with (usually longer) refresh-interval.
-            if *self.confidence >= self.config.confidence_max {
-                self.schedule_next_probe(self.config.refresh_interval);
+        flipped
+    }
+
+    // Adapt the NAT status of the probed transport to the status reported by the latest probe,
+    // and derive the overall NAT status from the status of all transports.
+    fn handle_reported_transport_status(
+        &mut self,
+        transport: TransportKind,
+        reported_status: NatStatus,
+    ) -> VecDeque<Action> {
+        let mut actions = VecDeque::new();
+
+        let (status, confidence) = self
+            .transport_status
+            .entry(transport)
+            .or_insert((NatStatus::Unknown, 0));
+        if let Some(old) = adapt_status(
+            status,
+            confidence,
+            reported_status,
+            self.config.confidence_max,
+        ) {
+            let new = status.clone();
+            if matches!(new, NatStatus::Private) {
+                // Stop advertising addresses that the world cannot reach.
+                actions.extend(
+                    self.external_addresses
+                        .iter()
+                        .filter(|a| TransportKind::from_address(a) == transport)
+                        .cloned()
+                        .map(ToSwarm::ExternalAddrExpired),
+                );
             }
-            return None;
+            actions.push_back(ToSwarm::GenerateEvent(Event::TransportStatusChanged {
+                transport,
+                old,
+                new,
+            }));
         }
 
-        if reported_status.is_public() && self.nat_status.is_public() {
-            // Different address than the currently assumed public address was reported.
-            // Switch address, but don't report as flipped.
-            *self.nat_status = reported_status;
-            return None;
+        // The local node is public if any of its transports is.
+        let (status, confidence) = self
+            .transport_status
+            .values()
+            .filter(|(s, _)| !matches!(s, NatStatus::Unknown))
+            .max_by_key(|(s, c)| (s.is_public(), *c))
+            .cloned()
+            .unwrap_or((NatStatus::Unknown, 0));
+        *self.confidence = confidence;
+        if std::mem::discriminant(&status) != std::mem::discriminant(self.nat_status) {
+            let old = std::mem::replace(self.nat_status, status);
+            actions.push_back(ToSwarm::GenerateEvent(Event::StatusChanged {
+                old,
+                new: self.nat_status.clone(),
+            }));
+        } else {
+            *self.nat_status = status;
         }
-        if *self.confidence > 0 {
-            // Reduce confidence but keep old status.
-            *self.confidence -= 1;
-            return None;
+
+        let all_confident = self
+            .transport_status
+            .values()
+            .all(|(_, c)| *c >= self.config.confidence_max);
+        if all_confident {
+            self.schedule_next_probe(self.config.refresh_interval);
+        } else {
+            self.schedule_next_probe(self.config.retry_interval);
         }
 
-        tracing::debug!(
-            old_status=?self.nat_status,
-            new_status=?reported_status,
-            "Flipped assumed NAT status"
-        );
+        actions
+    }
+}
+
+// Adapt the confidence and NAT status to the reported status.
+// Return the old status if it flipped.
+fn adapt_status(
+    nat_status: &mut NatStatus,
+    confidence: &mut usize,
+    reported_status: NatStatus,
+    confidence_max: usize,
+) -> Option<NatStatus> {
+    if matches!(reported_status, NatStatus::Unknown) {
+        return None;
+    }
 
-        let old_status = self.nat_status.clone();
-        *self.nat_status = reported_status;
+    if reported_status == *nat_status {
+        if *confidence < confidence_max {
+            *confidence += 1;
+        }
+        return None;
+    }
 
-        Some(old_status)
+    if reported_status.is_public() && nat_status.is_public() {
+        // Different address than the currently assumed public address was reported.
+        // Switch address, but don't report as flipped.
+        *nat_status = reported_status;
+        return None;
+    }
+    if *confidence > 0 {
+        // Reduce confidence but keep old status.
+        *confidence -= 1;
+        return None;
     }
+
+    tracing::debug!(
+        old_status=?nat_status,
+        new_status=?reported_status,
+        "Flipped assumed NAT status"
+    );
+
+    Some(std::mem::replace(nat_status, reported_status))
 }
 
 impl From<Result<Multiaddr, ResponseError>> for NatStatus {
@@ -28,7 +28,7 @@ mod protocol;
 pub use self::{
     behaviour::{
         Behaviour, Config, Event, InboundProbeError, InboundProbeEvent, NatStatus,
-        OutboundProbeError, OutboundProbeEvent, ProbeId,
+        OutboundProbeError, OutboundProbeEvent, ProbeId, TransportKind,
     },
     protocol::{ResponseError, DEFAULT_PROTOCOL_NAME},
 };
@@ -21,6 +21,7 @@
 use async_std::task::JoinHandle;
 use libp2p_autonat::{
     Behaviour, Config, Event, NatStatus, OutboundProbeError, OutboundProbeEvent, ResponseError,
+    TransportKind,
 };
 use libp2p_core::Multiaddr;
 use libp2p_identity::PeerId;
@@ -410,6 +411,67 @@ async fn test_global_ips_config() {
     }
 }
 
+#[async_std::test]
+async fn test_probe_per_transport() {
+    let mut client = Swarm::new_ephemeral(|key| {
+        Behaviour::new(
+            key.public().to_peer_id(),
+            Config {
+                retry_interval: TEST_RETRY_INTERVAL,
+                refresh_interval: TEST_REFRESH_INTERVAL,
+                confidence_max: MAX_CONFIDENCE,
+                only_global_ips: false,
+                throttle_server_period: Duration::ZERO,
+                boot_delay: Duration::ZERO,
+                probe_per_transport: true,
+                ..Default::default()
+            },
+        )
+    });
+
+    let (server_id, addr, _) = new_server_swarm().await;
+    client.behaviour_mut().add_server(server_id, Some(addr));
+
+    match client.next_behaviour_event().await {
+        Event::OutboundProbe(OutboundProbeEvent::Error { error, .. }) => {
+            assert!(matches!(error, OutboundProbeError::NoAddresses));
+        }
+        other => panic!("Unexpected behaviour event: {other:?}."),
+    }
+
+    client.listen().await;
+    // The server has no QUIC transport and thus can't dial this address.
+    client
+        .behaviour_mut()
+        .probe_address("/ip4/127.0.0.1/udp/1/quic-v1".parse().unwrap());
+
+    let mut tcp = None;
+    let mut quic = None;
+    while tcp.is_none() || quic.is_none() {
+        if let Event::TransportStatusChanged { transport, new, .. } =
+            client.next_behaviour_event().await
+        {
+            match transport {
+                TransportKind::Tcp => tcp = Some(new),
+                TransportKind::Quic => quic = Some(new),
+                TransportKind::Other => {}
+            }
+        }
+    }
+
+    assert!(tcp.unwrap().is_public());
+    assert_eq!(quic, Some(NatStatus::Private));
+    assert!(client
+        .behaviour()
+        .transport_status(TransportKind::Tcp)
+        .is_public());
+    assert_eq!(
+        client.behaviour().transport_status(TransportKind::Quic),
+        NatStatus::Private
+    );
+    assert!(client.behaviour().nat_status().is_public());
+}
+
 async fn new_server_swarm() -> (PeerId, Multiaddr, JoinHandle<()>) {
     let mut swarm = Swarm::new_ephemeral(|key| {
         Behaviour::new(
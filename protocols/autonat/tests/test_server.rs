@@ -96,6 +96,7 @@ async fn test_dial_back() {
                 num_established,
                 concurrent_dial_errors,
                 established_in: _,
+                phases: _,
                 connection_id: _,
             } => {
                 assert_eq!(peer_id, client_id);
@@ -22,54 +22,105 @@ use std::{
     cmp::{max, Ordering},
     collections::HashSet,
     collections::VecDeque,
-    collections::{BTreeSet, HashMap},
+    collections::{hash_map::Entry, BTreeSet, HashMap},
     fmt,
     net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        Arc,
+    },
     task::{Context, Poll},
     time::Duration,
 };
 
-use futures::StreamExt;
+use futures::future::{self, BoxFuture, Either};
+use futures::stream::FuturesUnordered;
+use futures::{Future, FutureExt, StreamExt};
 use futures_ticker::Ticker;
+use futures_timer::Delay;
 use prometheus_client::registry::Registry;
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
 
-use libp2p_core::{multiaddr::Protocol::Ip4, multiaddr::Protocol::Ip6, Endpoint, Multiaddr};
+use libp2p_core::{
+    multiaddr::Protocol::Ip4, multiaddr::Protocol::Ip6, Endpoint, Multiaddr, PeerRecord,
+    SignedEnvelope,
+};
 use libp2p_identity::Keypair;
 use libp2p_identity::PeerId;
 use libp2p_swarm::{
-    behaviour::{AddressChange, ConnectionClosed, ConnectionEstablished, FromSwarm},
+    behaviour::{
+        AddressChange, ConnectionClosed, ConnectionEstablished, DisconnectRequested, FromSwarm,
+    },
     dial_opts::DialOpts,
-    ConnectionDenied, ConnectionId, NetworkBehaviour, NotifyHandler, THandler, THandlerInEvent,
-    THandlerOutEvent, ToSwarm,
+    CloseConnection, ConnectionDenied, ConnectionId, NetworkBehaviour, NotifyHandler, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
 use web_time::{Instant, SystemTime};
 
 use crate::backoff::BackoffStorage;
-use crate::config::{Config, ValidationMode};
+use crate::choking::Choking;
+use crate::chunking::{Chunk, Reassembly};
+use crate::config::{Config, RelayedMeshPolicy, SlowPeerPolicy, TopicMeshParams, ValidationMode};
+use crate::delivery::{DeliveryOutcome, PendingDelivery};
+use crate::duplicate_cache::DuplicateCache;
 use crate::gossip_promises::GossipPromises;
 use crate::handler::{Handler, HandlerEvent, HandlerIn};
+use crate::latency::{DeliveryLatencies, LatencyPercentiles};
 use crate::mcache::MessageCache;
 use crate::metrics::{Churn, Config as MetricsConfig, Inclusion, Metrics, Penalty};
-use crate::peer_score::{PeerScore, PeerScoreParams, PeerScoreThresholds, RejectReason};
-use crate::protocol::SIGNING_PREFIX;
+use crate::peer_score::{
+    PeerScore, PeerScoreBreakdown, PeerScoreParams, PeerScoreSnapshot, PeerScoreThresholds,
+    RejectReason,
+};
+use crate::protocol::{invalid_report_signature_bytes, verify_invalid_report, SIGNING_PREFIX};
+use crate::sharding::{ShardSubscriptions, ShardedTopics};
 use crate::subscription_filter::{AllowAllSubscriptionFilter, TopicSubscriptionFilter};
-use crate::time_cache::DuplicateCache;
+use crate::time_cache::TimeDuplicateCache;
 use crate::topic::{Hasher, Topic, TopicHash};
+use crate::tracer::{TraceEvent, TraceSink};
 use crate::transform::{DataTransform, IdentityTransform};
+use crate::types::{unix_millis, PeerConnections, PeerKind, RpcOut};
 use crate::types::{
-    ControlAction, Message, MessageAcceptance, MessageId, PeerInfo, RawMessage, Subscription,
-    SubscriptionAction,
+    ControlAction, ControlBudget, ControlTokenBucket, MeshJoinReason, MeshLeaveReason, Message,
+    MessageAcceptance, MessageId, PeerInfo, RawMessage, Subscription, SubscriptionAction,
 };
-use crate::types::{PeerConnections, PeerKind, RpcOut};
+use crate::IdentTopic;
 use crate::{rpc_proto::proto, TopicScoreParams};
-use crate::{PublishError, SubscriptionError, ValidationError};
+use crate::{
+    ConfigBuilderError, IncompatibleValidationMode, PublishError, SubscriptionError,
+    ValidationError,
+};
 use quick_protobuf::{MessageWrite, Writer};
 use std::{cmp::Ordering::Equal, fmt::Debug};
 
 #[cfg(test)]
 mod tests;
 
+/// The maximum number of message ids we remember per peer from its IDONTWANT messages.
+const IDONTWANT_CAP: usize = 10_000;
+/// The time after which message ids received in IDONTWANT messages are forgotten.
+const IDONTWANT_TIMEOUT: Duration = Duration::from_secs(3);
+/// The maximum number of subscriptions dropped by the subscription filter we remember per peer.
+const FILTERED_SUBSCRIPTIONS_CAP: usize = 256;
+/// The change of the gossip factor of a topic per heartbeat if [`Config::adaptive_gossip_factor`]
+/// is set.
+const GOSSIP_FACTOR_STEP: f64 = 0.05;
+/// The number of duplicates per message below which the mesh of a topic is considered unhealthy,
+/// hence the gossip factor increased.
+const UNHEALTHY_DUPLICATES_PER_MESSAGE: f64 = 1.0;
+/// The number of duplicates per message above which the mesh of a topic is considered healthy,
+/// hence the gossip factor decreased.
+const HEALTHY_DUPLICATES_PER_MESSAGE: f64 = 2.0;
+
+/// An asynchronous validator of the messages of a topic.
+///
+/// See [`Behaviour::set_topic_validator`].
+type Validator = Box<dyn Fn(&Message) -> BoxFuture<'static, MessageAcceptance> + Send + 'static>;
+
+/// A validation of a received message by a [`Validator`], together with the message, its id
+/// and the peer it was received from.
+type PendingValidation = BoxFuture<'static, (MessageId, PeerId, Message, MessageAcceptance)>;
+
 /// Determines if published messages should be signed or not.
 ///
 /// Without signing, a number of privacy preserving modes can be selected.
@@ -143,6 +194,106 @@ pub enum Event {
     },
     /// A peer that does not support gossipsub has connected.
     GossipsubNotSupported { peer_id: PeerId },
+    /// The subscription of a remote to a topic was rejected by the subscription filter, either
+    /// when it was received or after [`Behaviour::update_subscription_filter`].
+    SubscriptionFiltered {
+        /// Remote whose subscription was rejected.
+        peer_id: PeerId,
+        /// The topic of the subscription.
+        topic: TopicHash,
+    },
+    /// The outbound queue of a peer stayed saturated for
+    /// [`Config::saturated_queue_heartbeats`] heartbeats, i.e. the peer does not keep up with
+    /// the messages sent to it.
+    ///
+    /// Emitted again every [`Config::saturated_queue_heartbeats`] heartbeats while the queue
+    /// stays saturated.
+    OutboundQueueSaturated {
+        /// The slow peer.
+        peer_id: PeerId,
+        /// The current length of the outbound queue of the peer.
+        queue_len: usize,
+    },
+    /// The outbound queue of a peer reached [`Config::outbound_queue_high_watermark`].
+    ///
+    /// Messages forwarded to the peer are sent after control messages and our own publishes,
+    /// hence are the first to be delayed.
+    OutboundQueueHigh {
+        /// The slow peer.
+        peer_id: PeerId,
+        /// The current length of the outbound queue of the peer.
+        queue_len: usize,
+    },
+    /// The outbound queue of a peer drained to [`Config::outbound_queue_low_watermark`] after
+    /// an [`Event::OutboundQueueHigh`].
+    OutboundQueueLow {
+        /// The peer that caught up.
+        peer_id: PeerId,
+        /// The current length of the outbound queue of the peer.
+        queue_len: usize,
+    },
+    /// The scores of all connected peers, emitted every [`Config::score_report_ticks`]
+    /// heartbeats if peer scoring is enabled.
+    ScoreReport {
+        /// The score of each connected peer, broken down into its components.
+        scores: HashMap<PeerId, PeerScoreBreakdown>,
+    },
+    /// A peer was added to the mesh of a topic, emitted if [`Config::mesh_events`] is enabled.
+    MeshPeerAdded {
+        /// The peer added to the mesh.
+        peer_id: PeerId,
+        /// The topic of the mesh.
+        topic: TopicHash,
+        /// Why the peer was added.
+        reason: MeshJoinReason,
+    },
+    /// A peer was removed from the mesh of a topic, emitted if [`Config::mesh_events`] is enabled.
+    MeshPeerRemoved {
+        /// The peer removed from the mesh.
+        peer_id: PeerId,
+        /// The topic of the mesh.
+        topic: TopicHash,
+        /// Why the peer was removed.
+        reason: MeshLeaveReason,
+    },
+    /// A peer exceeded one of its control message budgets and got penalized. Emitted at most once
+    /// per peer, budget and heartbeat.
+    BudgetExceeded {
+        /// The flooding peer.
+        peer_id: PeerId,
+        /// The exceeded budget.
+        budget: ControlBudget,
+    },
+    /// The mesh of a topic has fewer outbound peers than [`Config::mesh_outbound_min`] (D_out)
+    /// after the heartbeat tried to graft outbound peers, e.g. because a node behind a NAT has
+    /// too few outbound connections. Emitted if [`Config::mesh_events`] is enabled, once until
+    /// the quota is met again.
+    OutboundQuotaNotMet {
+        /// The topic of the mesh.
+        topic: TopicHash,
+        /// The number of outbound peers in the mesh.
+        outbound: usize,
+        /// The required number of outbound peers in the mesh.
+        mesh_outbound_min: usize,
+    },
+    /// The outcome of the delivery of a message published via
+    /// [`Behaviour::publish_with_delivery_report`].
+    DeliveryReport {
+        /// The id of the published message.
+        message_id: MessageId,
+        /// Whether the message was written to the required number of peers.
+        outcome: DeliveryOutcome,
+        /// The peers the message was written to.
+        peers: Vec<PeerId>,
+    },
+    /// The teardown of a topic started via [`Behaviour::unsubscribe_with_prune_wait`] completed.
+    UnsubscribeCompleted {
+        /// The topic unsubscribed from.
+        topic: TopicHash,
+        /// Whether [`Config::unsubscribe_drain_timeout`] elapsed before all queued messages of
+        /// the topic were written.
+        timed_out: bool,
+    },
 }
 
 /// A data structure for storing configuration for publishing messages. See [`MessageAuthenticity`]
@@ -249,14 +400,29 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// Information used for publishing messages.
     publish_config: PublishConfig,
 
-    /// An LRU Time cache for storing seen messages (based on their ID). This cache prevents
-    /// duplicates from being propagated to the application and on the network.
-    duplicate_cache: DuplicateCache<MessageId>,
+    /// A cache for storing seen messages (based on their ID), by default an LRU Time cache. This
+    /// cache prevents duplicates from being propagated to the application and on the network.
+    duplicate_cache: Box<dyn DuplicateCache>,
 
     /// A set of connected peers, indexed by their [`PeerId`] tracking both the [`PeerKind`] and
     /// the set of [`ConnectionId`]s.
     connected_peers: HashMap<PeerId, PeerConnections>,
 
+    /// The length of the outbound queues of connected peers, shared with their connection
+    /// handlers.
+    outbound_queue_lens: HashMap<PeerId, Arc<AtomicUsize>>,
+
+    /// The number of consecutive heartbeats the outbound queue of a peer has been saturated for.
+    saturated_queues: HashMap<PeerId, usize>,
+
+    /// Mesh peers only receiving `IHAVE`s instead of messages until their saturated outbound
+    /// queue drains, see [`SlowPeerPolicy::GossipOnly`].
+    gossip_only_peers: HashSet<PeerId>,
+
+    /// Peers whose outbound queue reached [`Config::outbound_queue_high_watermark`] and did not
+    /// drain to [`Config::outbound_queue_low_watermark`] since.
+    congested_queues: HashSet<PeerId>,
+
     /// A map of all connected peers - A map of topic hash to a list of gossipsub peer Ids.
     topic_peers: HashMap<TopicHash, BTreeSet<PeerId>>,
 
@@ -267,6 +433,18 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// forward messages to, outside of the scoring system.
     explicit_peers: HashSet<PeerId>,
 
+    /// Messages retained for disconnected explicit peers, replayed once they reconnect. Only
+    /// maintained if [`Config::explicit_peer_replay_window`] is set.
+    explicit_peer_backlogs: HashMap<PeerId, ExplicitPeerBacklog>,
+
+    /// Subscriptions of peers rejected by the user, together with whether the peer is currently
+    /// subscribed to the topic.
+    rejected_subscriptions: HashMap<PeerId, HashMap<TopicHash, bool>>,
+
+    /// Subscriptions of peers dropped by the subscription filter, accepted once the filter
+    /// allows them after [`Behaviour::update_subscription_filter`].
+    filtered_subscriptions: HashMap<PeerId, HashSet<TopicHash>>,
+
     /// A list of peers that have been blacklisted by the user.
     /// Messages are not sent to and are rejected from these peers.
     blacklisted_peers: HashSet<PeerId>,
@@ -280,6 +458,30 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// The last publish time for fanout topics.
     fanout_last_pub: HashMap<TopicHash, Instant>,
 
+    /// The last time each mesh peer delivered a first-seen message, per topic. Peers are tracked
+    /// from the first heartbeat they are observed in the mesh. Only maintained if
+    /// [`Config::stale_mesh_peer_timeout`] is set.
+    mesh_last_delivery: HashMap<TopicHash, HashMap<PeerId, Instant>>,
+
+    /// The last time an already seen message was received on a topic, indicating activity on the
+    /// topic. Only maintained if [`Config::stale_mesh_peer_timeout`] is set.
+    topic_last_duplicate: HashMap<TopicHash, Instant>,
+
+    /// The number of first-seen messages and duplicates received on each topic since the last
+    /// heartbeat. Only maintained if [`Config::adaptive_gossip_factor`] is set.
+    topic_deliveries: HashMap<TopicHash, (usize, usize)>,
+
+    /// The gossip factor of each topic adapted to the health of its mesh. Only maintained if
+    /// [`Config::adaptive_gossip_factor`] is set.
+    gossip_factors: HashMap<TopicHash, f64>,
+
+    /// The mesh maintenance parameters of the topics, see [`Behaviour::set_topic_mesh_params`].
+    mesh_params: MeshParams,
+
+    /// The choked mesh peers in both directions and the deliveries of mesh peers. Only maintained
+    /// if [`Config::choke_ticks`] is set.
+    choking: Choking,
+
     ///Storage for backoffs
     backoffs: BackoffStorage,
 
@@ -299,6 +501,25 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// be removed from this list which may result in a true outbound rediscovery.
     px_peers: HashSet<PeerId>,
 
+    /// The dials to peers found through peer exchange delayed by [`Config::px_dial_jitter`],
+    /// along with the time they are due.
+    px_dial_queue: Vec<(Instant, DialOpts)>,
+
+    /// Fires once the earliest dial of `px_dial_queue` is due, reset whenever a dial is queued.
+    px_dial_timer: Option<Delay>,
+
+    /// The number of peers found through peer exchange dialed since the last heartbeat, see
+    /// [`Config::max_px_dials_per_heartbeat`].
+    px_dials_in_heartbeat: usize,
+
+    /// The topics in whose mesh peers were when their connection dropped, along with the time it
+    /// dropped, see [`Config::mesh_reconnect_window`].
+    disconnected_mesh_peers: HashMap<PeerId, (Instant, HashSet<TopicHash>)>,
+
+    /// The signed peer records we know of, sent along with the peers exchanged in PX. Records are
+    /// forgotten once the peer disconnects.
+    signed_peer_records: HashMap<PeerId, PeerRecord>,
+
     /// Set of connected outbound peers (we only consider true outbound peers found through
     /// discovery and not by PX).
     outbound_peers: HashSet<PeerId>,
@@ -313,13 +534,57 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// Counts the number of `IWANT` that we sent the each peer since the last heartbeat.
     count_sent_iwant: HashMap<PeerId, usize>,
 
+    /// Counts the number of messages sent in response to the IWANT requests of each peer since the
+    /// last heartbeat.
+    count_iwant_responses: HashMap<PeerId, usize>,
+
+    /// The token buckets limiting the control messages of each peer. Only maintained if
+    /// [`Config::control_messages_per_second`] is set.
+    control_token_buckets: HashMap<PeerId, ControlTokenBucket>,
+
+    /// The budgets exceeded by peers since the last heartbeat.
+    exceeded_budgets: HashSet<(PeerId, ControlBudget)>,
+
+    /// The topics whose mesh does not meet the outbound quota, see
+    /// [`Event::OutboundQuotaNotMet`].
+    outbound_quota_not_met: HashSet<TopicHash>,
+
+    /// The families of sharded topics subscribed via [`Behaviour::subscribe_sharded`], by prefix.
+    sharded_topics: HashMap<String, ShardSubscriptions>,
+
+    /// The chunked messages being reassembled, if chunking is enabled.
+    chunk_reassembly: Option<Reassembly>,
+
+    /// The chunks of the reassembled messages awaiting validation, along with the peers they were
+    /// received from, by chunk group.
+    chunk_validations: HashMap<MessageId, Vec<(MessageId, PeerId)>>,
+
+    /// The latencies of the recent deliveries per subscribed topic, for messages carrying a
+    /// timestamp.
+    delivery_latencies: DeliveryLatencies,
+
+    /// The messages published via [`Behaviour::publish_with_delivery_report`] whose delivery was
+    /// not reported yet.
+    pending_deliveries: HashMap<MessageId, PendingDelivery>,
+
+    /// The topics torn down via [`Behaviour::unsubscribe_with_prune_wait`], along with the
+    /// connections whose queues were not drained yet and the time the teardown started.
+    topic_drains: HashMap<TopicHash, (HashSet<(PeerId, ConnectionId)>, Instant)>,
+
+    /// Counts the number of invalid message reports sent to each peer since the last heartbeat.
+    count_sent_invalid_reports: HashMap<PeerId, usize>,
+
+    /// Counts the number of invalid message reports received from each peer since the last
+    /// heartbeat.
+    count_received_invalid_reports: HashMap<PeerId, usize>,
+
     /// Keeps track of IWANT messages that we are awaiting to send.
     /// This is used to prevent sending duplicate IWANT messages for the same message.
     pending_iwant_msgs: HashSet<MessageId>,
 
     /// Short term cache for published message ids. This is used for penalizing peers sending
     /// our own messages back if the messages are anonymous or use a random author.
-    published_message_ids: DuplicateCache<MessageId>,
+    published_message_ids: TimeDuplicateCache<MessageId>,
 
     /// The filter used to handle message subscriptions.
     subscription_filter: F,
@@ -331,6 +596,15 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
 
     /// Keep track of a set of internal metrics relating to gossipsub.
     metrics: Option<Metrics>,
+
+    /// The sink recording the events of the router, set with [`Behaviour::set_tracer`].
+    tracer: Option<Box<dyn TraceSink>>,
+
+    /// The validators of the messages of each topic.
+    topic_validators: HashMap<TopicHash, Validator>,
+
+    /// The ongoing validations of received messages by the topic validators.
+    pending_validations: FuturesUnordered<PendingValidation>,
 }
 
 impl<D, F> Behaviour<D, F>
@@ -437,17 +711,30 @@ where
 
         Ok(Behaviour {
             metrics: metrics.map(|(registry, cfg)| Metrics::new(registry, cfg)),
+            tracer: None,
             events: VecDeque::new(),
             control_pool: HashMap::new(),
             publish_config: privacy.into(),
-            duplicate_cache: DuplicateCache::new(config.duplicate_cache_time()),
+            duplicate_cache: Box::new(TimeDuplicateCache::new(config.duplicate_cache_time())),
             topic_peers: HashMap::new(),
             peer_topics: HashMap::new(),
             explicit_peers: HashSet::new(),
+            explicit_peer_backlogs: HashMap::new(),
+            rejected_subscriptions: HashMap::new(),
+            filtered_subscriptions: HashMap::new(),
             blacklisted_peers: HashSet::new(),
             mesh: HashMap::new(),
             fanout: HashMap::new(),
             fanout_last_pub: HashMap::new(),
+            mesh_last_delivery: HashMap::new(),
+            topic_last_duplicate: HashMap::new(),
+            topic_deliveries: HashMap::new(),
+            gossip_factors: HashMap::new(),
+            mesh_params: MeshParams {
+                default: TopicMeshParams::from(&config),
+                topics: HashMap::new(),
+            },
+            choking: Choking::default(),
             backoffs: BackoffStorage::new(
                 &config.prune_backoff(),
                 config.heartbeat_interval(),
@@ -460,16 +747,47 @@ where
             ),
             heartbeat_ticks: 0,
             px_peers: HashSet::new(),
+            px_dial_queue: Vec::new(),
+            px_dial_timer: None,
+            px_dials_in_heartbeat: 0,
+            disconnected_mesh_peers: HashMap::new(),
+            signed_peer_records: HashMap::new(),
             outbound_peers: HashSet::new(),
             peer_score: None,
             count_received_ihave: HashMap::new(),
             count_sent_iwant: HashMap::new(),
+            count_iwant_responses: HashMap::new(),
+            control_token_buckets: HashMap::new(),
+            exceeded_budgets: HashSet::new(),
+            outbound_quota_not_met: HashSet::new(),
+            sharded_topics: HashMap::new(),
+            chunk_reassembly: config.max_chunked_message_size().map(|max_size| {
+                Reassembly::new(
+                    max_size,
+                    config.max_pending_chunked_messages(),
+                    config.chunk_reassembly_timeout(),
+                )
+            }),
+            chunk_validations: HashMap::new(),
+            delivery_latencies: DeliveryLatencies::new(config.delivery_latency_samples()),
+            pending_deliveries: HashMap::new(),
+            topic_drains: HashMap::new(),
+            count_sent_invalid_reports: HashMap::new(),
+            count_received_invalid_reports: HashMap::new(),
             pending_iwant_msgs: HashSet::new(),
             connected_peers: HashMap::new(),
-            published_message_ids: DuplicateCache::new(config.published_message_ids_cache_time()),
+            outbound_queue_lens: HashMap::new(),
+            saturated_queues: HashMap::new(),
+            gossip_only_peers: HashSet::new(),
+            congested_queues: HashSet::new(),
+            published_message_ids: TimeDuplicateCache::new(
+                config.published_message_ids_cache_time(),
+            ),
             config,
             subscription_filter,
             data_transform,
+            topic_validators: HashMap::new(),
+            pending_validations: FuturesUnordered::new(),
         })
     }
 }
@@ -509,6 +827,36 @@ where
         self.connected_peers.iter().map(|(k, v)| (k, &v.kind))
     }
 
+    /// Percentiles of the latency from publishing to delivering the recent messages of a topic,
+    /// see [`ConfigBuilder::delivery_timestamp_fn`](crate::ConfigBuilder::delivery_timestamp_fn).
+    ///
+    /// Returns `None` if no message of the topic was measured since subscribing to it.
+    pub fn delivery_latency(&self, topic: &TopicHash) -> Option<LatencyPercentiles> {
+        self.delivery_latencies.percentiles(topic)
+    }
+
+    /// Hints whether the connection to the given peer is metered, e.g. a cellular link, in which
+    /// case the peer is treated like a relayed peer by the [`Config::relayed_mesh_policy`]. The
+    /// hint is dropped once the peer disconnects.
+    ///
+    /// Returns `false` if the peer is not connected.
+    pub fn set_peer_metered(&mut self, peer_id: &PeerId, metered: bool) -> bool {
+        match self.connected_peers.get_mut(peer_id) {
+            Some(connections) => {
+                connections.metered = metered;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of messages waiting to be sent to a given peer, if it is connected.
+    pub fn outbound_queue_len(&self, peer_id: &PeerId) -> Option<usize> {
+        self.outbound_queue_lens
+            .get(peer_id)
+            .map(|len| len.load(AtomicOrdering::Relaxed))
+    }
+
     /// Returns the gossipsub score for a given peer, if one exists.
     pub fn peer_score(&self, peer_id: &PeerId) -> Option<f64> {
         self.peer_score
@@ -516,6 +864,53 @@ where
             .map(|(score, ..)| score.score(peer_id))
     }
 
+    /// Returns the gossipsub score for a given peer broken down into its components, if peer
+    /// scoring is enabled.
+    pub fn peer_score_breakdown(&self, peer_id: &PeerId) -> Option<PeerScoreBreakdown> {
+        self.peer_score
+            .as_ref()
+            .map(|(score, ..)| score.score_breakdown(peer_id))
+    }
+
+    /// Lists the topics in whose mesh a peer is.
+    pub fn peer_mesh_topics<'a>(
+        &'a self,
+        peer_id: &'a PeerId,
+    ) -> impl Iterator<Item = &'a TopicHash> + 'a {
+        self.mesh
+            .iter()
+            .filter(move |(_, peers)| peers.contains(peer_id))
+            .map(|(topic_hash, _)| topic_hash)
+    }
+
+    /// Lists the fanout peers of a topic we publish to without being subscribed.
+    pub fn fanout_peers(&self, topic_hash: &TopicHash) -> impl Iterator<Item = &PeerId> {
+        self.fanout
+            .get(topic_hash)
+            .into_iter()
+            .flat_map(|x| x.iter())
+    }
+
+    /// Returns the gossip factor currently used when emitting gossip for a topic.
+    ///
+    /// This is [`Config::gossip_factor`] unless it is adapted to the health of the mesh, see
+    /// [`Config::adaptive_gossip_factor`].
+    pub fn gossip_factor(&self, topic_hash: &TopicHash) -> f64 {
+        self.gossip_factors
+            .get(topic_hash)
+            .copied()
+            .unwrap_or_else(|| self.config.gossip_factor())
+    }
+
+    /// Returns the time until which a peer is backed off from the mesh of a topic, if any.
+    ///
+    /// The peer is not grafted to the mesh of the topic again before the back off expired.
+    pub fn peer_backoff(&self, topic_hash: &TopicHash, peer_id: &PeerId) -> Option<Instant> {
+        self.backoffs
+            .get_backoff_time(topic_hash, peer_id)
+            .filter(|expires| *expires > Instant::now())
+    }
+
     /// Subscribe to a topic.
     ///
     /// Returns [`Ok(true)`] if the subscription worked. Returns [`Ok(false)`] if we were already
@@ -526,6 +921,8 @@ where
         if !self.subscription_filter.can_subscribe(&topic_hash) {
             return Err(SubscriptionError::NotAllowed);
         }
+        self.check_validation_mode(&topic_hash)
+            .map_err(SubscriptionError::IncompatibleValidationMode)?;
 
         if self.mesh.contains_key(&topic_hash) {
             tracing::debug!(%topic, "Topic is already in the mesh");
@@ -575,48 +972,242 @@ where
         Ok(true)
     }
 
-    /// Publishes a message with multiple topics to the network.
-    pub fn publish(
+    /// Unsubscribes from a topic like [`Behaviour::unsubscribe`], but tears it down gracefully:
+    /// the PRUNEs are sent to the mesh peers right away instead of on the next heartbeat, and
+    /// [`Event::UnsubscribeCompleted`] is emitted once the messages of the topic queued for all
+    /// peers, including the PRUNEs, were written, or after [`Config::unsubscribe_drain_timeout`].
+    ///
+    /// Returns `Ok(false)` without emitting an event if we were not subscribed to the topic.
+    pub fn unsubscribe_with_prune_wait<H: Hasher>(
         &mut self,
-        topic: impl Into<TopicHash>,
-        data: impl Into<Vec<u8>>,
-    ) -> Result<MessageId, PublishError> {
-        let data = data.into();
-        let topic = topic.into();
+        topic: &Topic<H>,
+    ) -> Result<bool, PublishError> {
+        let topic_hash = topic.hash();
+        let mesh_peers = self.mesh.get(&topic_hash).cloned().unwrap_or_default();
+        if !self.unsubscribe(topic)? {
+            return Ok(false);
+        }
 
-        // Transform the data before building a raw_message.
-        let transformed_data = self
-            .data_transform
-            .outbound_transform(&topic, data.clone())?;
+        for peer in mesh_peers {
+            for control in self.control_pool.remove(&peer).unwrap_or_default() {
+                self.send_message(peer, RpcOut::Control(control));
+            }
+        }
 
-        let raw_message = self.build_raw_message(topic, transformed_data)?;
+        let mut connections = HashSet::new();
+        for (peer_id, peer) in &self.connected_peers {
+            if peer.kind == PeerKind::NotSupported {
+                continue;
+            }
+            for connection_id in &peer.connections {
+                connections.insert((*peer_id, *connection_id));
+                self.events.push_back(ToSwarm::NotifyHandler {
+                    peer_id: *peer_id,
+                    event: HandlerIn::Drain(topic_hash.clone()),
+                    handler: NotifyHandler::One(*connection_id),
+                });
+            }
+        }
+        if connections.is_empty() {
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::UnsubscribeCompleted {
+                    topic: topic_hash,
+                    timed_out: false,
+                }));
+        } else {
+            self.topic_drains
+                .insert(topic_hash, (connections, Instant::now()));
+        }
 
-        // calculate the message id from the un-transformed data
-        let msg_id = self.config.message_id(&Message {
-            source: raw_message.source,
-            data, // the uncompressed form
-            sequence_number: raw_message.sequence_number,
-            topic: raw_message.topic.clone(),
-        });
+        Ok(true)
+    }
 
-        // check that the size doesn't exceed the max transmission size
-        if raw_message.raw_protobuf_len() > self.config.max_transmit_size() {
-            return Err(PublishError::MessageTooLarge);
+    /// Marks the queue of the given connection as drained of the messages of the given topic,
+    /// completing its teardown once all queues are.
+    fn on_topic_drained(
+        &mut self,
+        topic: &TopicHash,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+    ) {
+        let Some((connections, _)) = self.topic_drains.get_mut(topic) else {
+            return;
+        };
+        connections.remove(&(peer_id, connection_id));
+        if connections.is_empty() {
+            self.topic_drains.remove(topic);
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::UnsubscribeCompleted {
+                    topic: topic.clone(),
+                    timed_out: false,
+                }));
         }
+    }
 
-        // Check the if the message has been published before
-        if self.duplicate_cache.contains(&msg_id) {
-            // This message has already been seen. We don't re-publish messages that have already
-            // been published on the network.
-            tracing::warn!(
-                message=%msg_id,
-                "Not publishing a message that has already been published"
-            );
-            return Err(PublishError::Duplicate);
+    /// Subscribes the given key to its shards of a family of sharded topics.
+    ///
+    /// The shards are rotated on the heartbeat once an epoch ends, see [`ShardedTopics`]. A family
+    /// is identified by its prefix, subscribing a key to a family with a known prefix replaces its
+    /// configuration.
+    ///
+    /// Returns the topics of the key in the current epoch.
+    pub fn subscribe_sharded(
+        &mut self,
+        topics: &ShardedTopics,
+        key: impl Into<Vec<u8>>,
+    ) -> Result<Vec<TopicHash>, SubscriptionError> {
+        let key = key.into();
+        let now = SystemTime::now();
+        let epoch = topics.epoch_at(now);
+        let key_topics = topics
+            .shards(&key, epoch)
+            .into_iter()
+            .map(|shard| topics.topic(shard))
+            .collect::<Vec<_>>();
+        for topic in &key_topics {
+            let topic_hash = topic.hash();
+            if !self.subscription_filter.can_subscribe(&topic_hash) {
+                return Err(SubscriptionError::NotAllowed);
+            }
+            self.check_validation_mode(&topic_hash)
+                .map_err(SubscriptionError::IncompatibleValidationMode)?;
         }
 
-        tracing::trace!(message=%msg_id, "Publishing message");
+        let subscriptions = self
+            .sharded_topics
+            .entry(topics.prefix().to_owned())
+            .or_insert_with(|| ShardSubscriptions::new(topics.clone()));
+        subscriptions.topics = topics.clone();
+        subscriptions.keys.insert(key);
+        self.rotate_sharded_topics(topics.prefix(), now);
+
+        Ok(key_topics.into_iter().map(|topic| topic.hash()).collect())
+    }
+
+    /// Unsubscribes the given key from its shards of the family of sharded topics with the given
+    /// prefix. The topics still subscribed on behalf of other keys are kept.
+    ///
+    /// Returns `true` if the key was subscribed.
+    pub fn unsubscribe_sharded(&mut self, prefix: &str, key: &[u8]) -> bool {
+        let Some(subscriptions) = self.sharded_topics.get_mut(prefix) else {
+            return false;
+        };
+        if !subscriptions.keys.remove(key) {
+            return false;
+        }
+        self.rotate_sharded_topics(prefix, SystemTime::now());
+        if self.sharded_topics[prefix].keys.is_empty() {
+            self.sharded_topics.remove(prefix);
+        }
+        true
+    }
+
+    /// The topics currently subscribed on behalf of the keys of the family of sharded topics with
+    /// the given prefix.
+    pub fn sharded_topics(&self, prefix: &str) -> impl Iterator<Item = &TopicHash> {
+        self.sharded_topics
+            .get(prefix)
+            .into_iter()
+            .flat_map(|subscriptions| subscriptions.subscribed.iter())
+    }
+
+    /// Subscribes the topics of a family of sharded topics needed at the given time, and
+    /// unsubscribes those no longer needed.
+    fn rotate_sharded_topics(&mut self, prefix: &str, now: SystemTime) {
+        let Some(subscriptions) = self.sharded_topics.get_mut(prefix) else {
+            return;
+        };
+        let needed = subscriptions.topics.topics_at(&subscriptions.keys, now);
+        let stale = subscriptions
+            .subscribed
+            .difference(&needed)
+            .cloned()
+            .collect::<Vec<_>>();
+        let missing = needed
+            .difference(&subscriptions.subscribed)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for topic_hash in stale {
+            tracing::debug!(topic=%topic_hash, "Leaving sharded topic");
+            let _ = self.unsubscribe(&IdentTopic::new(topic_hash.as_str()));
+            if let Some(subscriptions) = self.sharded_topics.get_mut(prefix) {
+                subscriptions.subscribed.remove(&topic_hash);
+            }
+        }
+        for topic_hash in missing {
+            match self.subscribe(&IdentTopic::new(topic_hash.as_str())) {
+                Ok(true) => {
+                    tracing::debug!(topic=%topic_hash, "Joined sharded topic");
+                    if let Some(subscriptions) = self.sharded_topics.get_mut(prefix) {
+                        subscriptions.subscribed.insert(topic_hash);
+                    }
+                }
+                // Subscribed independently of the sharding, hence not ours to leave.
+                Ok(false) => {}
+                Err(error) => {
+                    tracing::debug!(topic=%topic_hash, %error, "Failed to join sharded topic");
+                }
+            }
+        }
+    }
+
+    /// Publishes a message with multiple topics to the network.
+    pub fn publish(
+        &mut self,
+        topic: impl Into<TopicHash>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<MessageId, PublishError> {
+        let topic = topic.into();
+        let data = data.into();
+        let (msg_id, raw_message) = match self.config.max_chunked_message_size() {
+            Some(max_size) => match self.build_published_message(topic.clone(), data.clone()) {
+                Err(PublishError::MessageTooLarge) => {
+                    return self.publish_chunked(topic, data, max_size)
+                }
+                result => result?,
+            },
+            None => self.build_published_message(topic, data)?,
+        };
+
+        let recipient_peers = self.publish_recipients(&raw_message)?;
+        Ok(self.send_published_message(msg_id, raw_message, recipient_peers, false))
+    }
+
+    /// Publishes a message like [`Behaviour::publish`], reporting via [`Event::DeliveryReport`]
+    /// once it was written to `min_peers` of its recipients, once too few recipients remain to do
+    /// so, or once `timeout` elapsed, checked on every heartbeat.
+    ///
+    /// [`Behaviour::publish`] returning `Ok` only means that the message was queued, the report
+    /// allows to retry publishing messages which did not reach enough peers. Messages exceeding
+    /// [`Config::max_transmit_size`] are not chunked.
+    pub fn publish_with_delivery_report(
+        &mut self,
+        topic: impl Into<TopicHash>,
+        data: impl Into<Vec<u8>>,
+        min_peers: usize,
+        timeout: Duration,
+    ) -> Result<MessageId, PublishError> {
+        let (msg_id, raw_message) = self.build_published_message(topic.into(), data.into())?;
+        let recipient_peers = self.publish_recipients(&raw_message)?;
+
+        self.pending_deliveries.insert(
+            msg_id.clone(),
+            PendingDelivery::new(
+                recipient_peers.iter().copied(),
+                min_peers,
+                Instant::now() + timeout,
+            ),
+        );
+        Ok(self.send_published_message(msg_id, raw_message, recipient_peers, true))
+    }
 
+    /// Selects the peers to send a message we publish to, among the mesh, fanout, explicit and
+    /// floodsub peers, or all peers of the topic when flood publishing.
+    fn publish_recipients(
+        &mut self,
+        raw_message: &RawMessage,
+    ) -> Result<HashSet<PeerId>, PublishError> {
         let topic_hash = raw_message.topic.clone();
 
         let mut recipient_peers = HashSet::new();
@@ -643,7 +1234,7 @@ where
                             }
                         } else {
                             // We have no fanout peers, select mesh_n of them and add them to the fanout
-                            let mesh_n = self.config.mesh_n();
+                            let mesh_n = self.mesh_params.get(&topic_hash).mesh_n;
                             let new_peers = get_random_peers(
                                 &self.topic_peers,
                                 &self.connected_peers,
@@ -693,55 +1284,252 @@ where
             }
         }
 
-        if recipient_peers.is_empty() {
+        // Disconnected explicit peers catch up on the message once they reconnect.
+        let retained = self.retain_for_explicit_peers(raw_message, None);
+
+        if recipient_peers.is_empty() && !retained {
             return Err(PublishError::InsufficientPeers);
         }
 
-        // If the message isn't a duplicate and we have sent it to some peers add it to the
-        // duplicate cache and memcache.
-        self.duplicate_cache.insert(msg_id.clone());
-        self.mcache.put(&msg_id, raw_message.clone());
+        Ok(recipient_peers)
+    }
 
-        // If the message is anonymous or has a random author add it to the published message ids
-        // cache.
-        if let PublishConfig::RandomAuthor | PublishConfig::Anonymous = self.publish_config {
-            if !self.config.allow_self_origin() {
-                self.published_message_ids.insert(msg_id.clone());
-            }
+    /// Publishes a message exceeding [`Config::max_transmit_size`] as chunks, see
+    /// [`ConfigBuilder::max_chunked_message_size`](crate::ConfigBuilder::max_chunked_message_size).
+    /// Returns the id of the chunk group.
+    fn publish_chunked(
+        &mut self,
+        topic: TopicHash,
+        data: Vec<u8>,
+        max_size: usize,
+    ) -> Result<MessageId, PublishError> {
+        let transformed_data = self.data_transform.outbound_transform(&topic, data)?;
+        if transformed_data.len() > max_size {
+            return Err(PublishError::MessageTooLarge);
         }
 
-        // Send to peers we know are subscribed to the topic.
-        for peer_id in recipient_peers.iter() {
-            tracing::trace!(peer=%peer_id, "Sending message to peer");
-            self.send_message(*peer_id, RpcOut::Publish(raw_message.clone()));
+        // The group is bound to the author of the signed chunks.
+        let PublishConfig::Signing { author, .. } = &self.publish_config else {
+            unreachable!("chunking to require the strict validation mode and thus signing");
+        };
+        let mut group = author.to_bytes();
+        group.extend_from_slice(&rand::random::<[u8; 16]>());
+        let group = MessageId(group);
+        let expires_at = self
+            .config
+            .topic_message_ttl(&topic)
+            .map(|ttl| unix_millis(SystemTime::now() + ttl));
+
+        // Measure a chunk without data to know how much data fits into a chunk, leaving room for
+        // the length of the data.
+        let mut probe = self.build_raw_message(
+            topic.clone(),
+            Vec::new(),
+            Some(Chunk {
+                group: group.clone(),
+                index: u32::MAX,
+                count: u32::MAX,
+            }),
+        )?;
+        probe.expires_at = expires_at;
+        let chunk_size = self
+            .config
+            .max_transmit_size()
+            .saturating_sub(probe.raw_protobuf_len() + 4);
+        if chunk_size == 0 {
+            return Err(PublishError::MessageTooLarge);
+        }
+        let count = u32::try_from(transformed_data.len().div_ceil(chunk_size))
+            .map_err(|_| PublishError::MessageTooLarge)?;
+
+        // Build all chunks before sending any of them.
+        let mut chunks = Vec::with_capacity(count as usize);
+        for (index, data) in transformed_data.chunks(chunk_size).enumerate() {
+            let chunk = Chunk {
+                group: group.clone(),
+                index: index as u32,
+                count,
+            };
+            let mut raw_message =
+                self.build_raw_message(topic.clone(), data.to_vec(), Some(chunk.clone()))?;
+            raw_message.expires_at = expires_at;
+            chunks.push((chunk.message_id(), raw_message));
         }
 
-        tracing::debug!(message=%msg_id, "Published message");
-
-        if let Some(metrics) = self.metrics.as_mut() {
-            metrics.register_published_message(&topic_hash);
+        for (msg_id, raw_message) in chunks {
+            let recipient_peers = self.publish_recipients(&raw_message)?;
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(TraceEvent::PublishMessage {
+                    message_id: msg_id.clone(),
+                    topic: raw_message.topic.clone(),
+                });
+            }
+            self.send_published_message(msg_id, raw_message, recipient_peers, false);
         }
 
-        Ok(msg_id)
+        tracing::debug!(message=%group, chunks=%count, "Published chunked message");
+        Ok(group)
     }
 
-    /// This function should be called when [`Config::validate_messages()`] is `true` after
-    /// the message got validated by the caller. Messages are stored in the ['Memcache'] and
-    /// validation is expected to be fast enough that the messages should still exist in the cache.
-    /// There are three possible validation outcomes and the outcome is given in acceptance.
-    ///
-    /// If acceptance = [`MessageAcceptance::Accept`] the message will get propagated to the
-    /// network. The `propagation_source` parameter indicates who the message was received by and
-    /// will not be forwarded back to that peer.
-    ///
-    /// If acceptance = [`MessageAcceptance::Reject`] the message will be deleted from the memcache
-    /// and the P₄ penalty will be applied to the `propagation_source`.
-    //
-    /// If acceptance = [`MessageAcceptance::Ignore`] the message will be deleted from the memcache
-    /// but no P₄ penalty will be applied.
-    ///
-    /// This function will return true if the message was found in the cache and false if was not
-    /// in the cache anymore.
+    /// Publishes a message to the given peers only, bypassing the selection of the mesh, fanout
+    /// and flood publishing peers.
+    ///
+    /// The message is recorded in the message cache as any published message, such that it is
+    /// gossiped and forwarded by the normal protocol afterwards, e.g. to send a block to known
+    /// validators first. Peers which are not connected are skipped.
+    pub fn publish_to(
+        &mut self,
+        topic: impl Into<TopicHash>,
+        data: impl Into<Vec<u8>>,
+        peers: impl IntoIterator<Item = PeerId>,
+    ) -> Result<MessageId, PublishError> {
+        let (msg_id, raw_message) = self.build_published_message(topic.into(), data.into())?;
+
+        let recipient_peers = peers
+            .into_iter()
+            .filter(|peer| {
+                self.connected_peers.contains_key(peer)
+                    && self
+                        .config
+                        .is_peer_allowed_on_topic(&raw_message.topic, peer)
+            })
+            .collect::<HashSet<_>>();
+        if recipient_peers.is_empty() {
+            return Err(PublishError::InsufficientPeers);
+        }
+
+        Ok(self.send_published_message(msg_id, raw_message, recipient_peers, false))
+    }
+
+    /// Checks that the messages we publish on the given topic pass the validation of the topic.
+    fn check_validation_mode(&self, topic: &TopicHash) -> Result<(), IncompatibleValidationMode> {
+        match self.config.topic_validation_mode(topic) {
+            ValidationMode::Strict => match self.publish_config {
+                PublishConfig::Signing { .. } => Ok(()),
+                _ => Err(IncompatibleValidationMode::SignatureRequired),
+            },
+            ValidationMode::Anonymous => match self.publish_config {
+                PublishConfig::Anonymous if self.config.has_message_id_fn(topic) => Ok(()),
+                PublishConfig::Anonymous => Err(IncompatibleValidationMode::MessageIdFnRequired),
+                _ => Err(IncompatibleValidationMode::AuthorPresent),
+            },
+            ValidationMode::Permissive | ValidationMode::None => Ok(()),
+        }
+    }
+
+    /// Builds a message we publish and checks that it can be published.
+    fn build_published_message(
+        &mut self,
+        topic: TopicHash,
+        data: Vec<u8>,
+    ) -> Result<(MessageId, RawMessage), PublishError> {
+        self.check_validation_mode(&topic)
+            .map_err(PublishError::IncompatibleValidationMode)?;
+
+        // Transform the data before building a raw_message.
+        let transformed_data = self
+            .data_transform
+            .outbound_transform(&topic, data.clone())?;
+
+        let mut raw_message = self.build_raw_message(topic, transformed_data, None)?;
+        if let Some(ttl) = self.config.topic_message_ttl(&raw_message.topic) {
+            raw_message.expires_at = Some(unix_millis(SystemTime::now() + ttl));
+        }
+
+        // calculate the message id from the un-transformed data
+        let msg_id = self.config.message_id(&Message {
+            source: raw_message.source,
+            data, // the uncompressed form
+            sequence_number: raw_message.sequence_number,
+            topic: raw_message.topic.clone(),
+        });
+
+        // check that the size doesn't exceed the max transmission size
+        if raw_message.raw_protobuf_len() > self.config.max_transmit_size() {
+            return Err(PublishError::MessageTooLarge);
+        }
+
+        // Check the if the message has been published before
+        if self.duplicate_cache.contains(&msg_id) {
+            // This message has already been seen. We don't re-publish messages that have already
+            // been published on the network.
+            tracing::warn!(
+                message=%msg_id,
+                "Not publishing a message that has already been published"
+            );
+            return Err(PublishError::Duplicate);
+        }
+
+        tracing::trace!(message=%msg_id, "Publishing message");
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(TraceEvent::PublishMessage {
+                message_id: msg_id.clone(),
+                topic: raw_message.topic.clone(),
+            });
+        }
+
+        Ok((msg_id, raw_message))
+    }
+
+    /// Records a message we publish in the caches and sends it to the given peers, reporting its
+    /// delivery to each of them if `report` is set.
+    fn send_published_message(
+        &mut self,
+        msg_id: MessageId,
+        raw_message: RawMessage,
+        recipient_peers: HashSet<PeerId>,
+        report: bool,
+    ) -> MessageId {
+        // If the message isn't a duplicate and we have sent it to some peers add it to the
+        // duplicate cache and memcache.
+        self.duplicate_cache.insert(msg_id.clone());
+        self.mcache.put(&msg_id, raw_message.clone());
+
+        // If the message is anonymous or has a random author add it to the published message ids
+        // cache.
+        if let PublishConfig::RandomAuthor | PublishConfig::Anonymous = self.publish_config {
+            if !self.config.allow_self_origin() {
+                self.published_message_ids.insert(msg_id.clone());
+            }
+        }
+
+        // Send to peers we know are subscribed to the topic.
+        for peer_id in recipient_peers.iter() {
+            tracing::trace!(peer=%peer_id, "Sending message to peer");
+            if report {
+                self.send_reported_message(*peer_id, msg_id.clone(), raw_message.clone());
+            } else {
+                self.send_message(*peer_id, RpcOut::Publish(raw_message.clone()));
+            }
+        }
+
+        tracing::debug!(message=%msg_id, "Published message");
+
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.register_published_message(&raw_message.topic);
+        }
+
+        msg_id
+    }
+
+    /// This function should be called when [`Config::validate_messages()`] is `true` after
+    /// the message got validated by the caller. Messages are stored in the ['Memcache'] and
+    /// validation is expected to be fast enough that the messages should still exist in the cache.
+    /// There are three possible validation outcomes and the outcome is given in acceptance.
+    ///
+    /// If acceptance = [`MessageAcceptance::Accept`] the message will get propagated to the
+    /// network. The `propagation_source` parameter indicates who the message was received by and
+    /// will not be forwarded back to that peer.
+    ///
+    /// If acceptance = [`MessageAcceptance::Reject`] the message will be deleted from the memcache
+    /// and the P₄ penalty will be applied to the `propagation_source`.
+    //
+    /// If acceptance = [`MessageAcceptance::Ignore`] the message will be deleted from the memcache
+    /// but no P₄ penalty will be applied.
+    ///
+    /// This function will return true if the message was found in the cache and false if was not
+    /// in the cache anymore.
     ///
     /// This should only be called once per message.
     pub fn report_message_validation_result(
@@ -750,6 +1538,16 @@ where
         propagation_source: &PeerId,
         acceptance: MessageAcceptance,
     ) -> Result<bool, PublishError> {
+        // The result for a chunked message applies to each of its chunks.
+        if let Some(chunks) = self.chunk_validations.remove(msg_id) {
+            let mut found = false;
+            for (chunk_id, chunk_source) in chunks {
+                found |=
+                    self.report_message_validation_result(&chunk_id, &chunk_source, acceptance)?;
+            }
+            return Ok(found);
+        }
+
         let reject_reason = match acceptance {
             MessageAcceptance::Accept => {
                 let (raw_message, originating_peers) = match self.mcache.validate(msg_id) {
@@ -789,6 +1587,15 @@ where
                 metrics.register_msg_validation(&raw_message.topic, &acceptance);
             }
 
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(TraceEvent::RejectMessage {
+                    message_id: Some(msg_id.clone()),
+                    received_from: *propagation_source,
+                    topic: raw_message.topic.clone(),
+                    reason: reject_reason.trace_name().to_owned(),
+                });
+            }
+
             // Tell peer_score about reject
             // Reject the original source, and any duplicates we've seen from other peers.
             if let Some((peer_score, ..)) = &mut self.peer_score {
@@ -802,6 +1609,15 @@ where
                     peer_score.reject_message(peer, msg_id, &raw_message.topic, reject_reason);
                 }
             }
+
+            if let RejectReason::ValidationFailed = reject_reason {
+                self.report_invalid_message(
+                    msg_id,
+                    &raw_message.topic,
+                    propagation_source,
+                    &originating_peers,
+                );
+            }
             Ok(true)
         } else {
             tracing::warn!(message=%msg_id, "Rejected message not in cache");
@@ -809,20 +1625,383 @@ where
         }
     }
 
+    /// Registers an asynchronous validator for the messages of a topic, replacing any validator
+    /// previously registered for the topic.
+    ///
+    /// Received messages of the topic are handed to the validator, regardless of
+    /// [`Config::validate_messages()`]. Only once the returned future resolves to
+    /// [`MessageAcceptance::Accept`] is the message reported via [`Event::Message`] and
+    /// forwarded to the network. Rejected and ignored messages are handled as if reported via
+    /// [`Behaviour::report_message_validation_result`].
+    ///
+    /// At most [`Config::max_concurrent_validations()`] messages are validated at once; further
+    /// messages are ignored. Validations that don't complete within
+    /// [`Config::validation_timeout()`] are abandoned and the message ignored.
+    pub fn set_topic_validator<H, V, Fut>(&mut self, topic: &Topic<H>, validator: V)
+    where
+        H: Hasher,
+        V: Fn(&Message) -> Fut + Send + 'static,
+        Fut: Future<Output = MessageAcceptance> + Send + 'static,
+    {
+        self.topic_validators.insert(
+            topic.hash(),
+            Box::new(move |message| validator(message).boxed()),
+        );
+    }
+
+    /// Removes the validator registered for the messages of a topic.
+    ///
+    /// Returns `true` if a validator was registered. Messages whose validation is already
+    /// ongoing are still handled according to the outcome of their validation.
+    pub fn remove_topic_validator<H: Hasher>(&mut self, topic: &Topic<H>) -> bool {
+        self.topic_validators.remove(&topic.hash()).is_some()
+    }
+
+    /// Starts the validation of a received message by the validator of its topic.
+    fn start_validation(
+        &mut self,
+        msg_id: MessageId,
+        message: Message,
+        propagation_source: PeerId,
+    ) {
+        let Some(validator) = self.topic_validators.get(&message.topic) else {
+            return;
+        };
+
+        if self.pending_validations.len() >= self.config.max_concurrent_validations() {
+            tracing::debug!(
+                message=%msg_id,
+                "Too many concurrent validations, ignoring message"
+            );
+            let _ = self.report_message_validation_result(
+                &msg_id,
+                &propagation_source,
+                MessageAcceptance::Ignore,
+            );
+            return;
+        }
+
+        let validation = validator(&message);
+        let timeout = Delay::new(self.config.validation_timeout());
+        self.pending_validations.push(
+            async move {
+                let acceptance = match future::select(validation, timeout).await {
+                    Either::Left((acceptance, _)) => acceptance,
+                    Either::Right(_) => {
+                        tracing::debug!(message=%msg_id, "Validation timed out, ignoring message");
+                        MessageAcceptance::Ignore
+                    }
+                };
+                (msg_id, propagation_source, message, acceptance)
+            }
+            .boxed(),
+        );
+    }
+
+    /// Handles the outcome of the validation of a received message by a validator.
+    fn on_validation_done(
+        &mut self,
+        msg_id: MessageId,
+        propagation_source: PeerId,
+        message: Message,
+        acceptance: MessageAcceptance,
+    ) {
+        if let MessageAcceptance::Accept = acceptance {
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(TraceEvent::DeliverMessage {
+                    message_id: msg_id.clone(),
+                    received_from: propagation_source,
+                    topic: message.topic.clone(),
+                });
+            }
+            self.record_delivery_latency(&message);
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::Message {
+                    propagation_source,
+                    message_id: msg_id.clone(),
+                    message,
+                }));
+        }
+
+        if let Err(e) =
+            self.report_message_validation_result(&msg_id, &propagation_source, acceptance)
+        {
+            tracing::error!(message=%msg_id, "Failed to forward validated message: {:?}", e);
+        }
+    }
+
     /// Adds a new peer to the list of explicitly connected peers.
+    ///
+    /// This takes effect immediately: the peer is dialed if we are not connected to it, pruned
+    /// from the meshes it is part of and its connections are kept alive. If the peer disconnects, it
+    /// is dialed again every [`Config::check_explicit_peers_ticks`] heartbeats. Its messages are
+    /// accepted regardless of its score.
     pub fn add_explicit_peer(&mut self, peer_id: &PeerId) {
         tracing::debug!(peer=%peer_id, "Adding explicit peer");
 
-        self.explicit_peers.insert(*peer_id);
+        if self.explicit_peers.insert(*peer_id) {
+            // We don't GRAFT explicit peers, hence prune it from the meshes it is part of.
+            let mut topics = Vec::new();
+            for (topic_hash, peers) in self.mesh.iter_mut() {
+                if peers.remove(peer_id) {
+                    if let Some(m) = self.metrics.as_mut() {
+                        m.peers_removed(topic_hash, Churn::Explicit, 1)
+                    }
+                    mesh_peers_removed(
+                        &self.config,
+                        &mut self.events,
+                        topic_hash,
+                        [peer_id],
+                        MeshLeaveReason::Explicit,
+                    );
+                    topics.push(topic_hash.clone());
+                }
+            }
+            if !topics.is_empty() {
+                self.send_graft_prune(
+                    HashMap::new(),
+                    HashMap::from([(*peer_id, topics)]),
+                    HashSet::from([*peer_id]),
+                );
+            }
+            self.notify_explicit_peer(peer_id, true);
+        }
 
         self.check_explicit_peer_connection(peer_id);
     }
 
     /// This removes the peer from explicitly connected peers, note that this does not disconnect
     /// the peer.
+    ///
+    /// The peer is treated as any other peer from now on, i.e. it is no longer dialed again and
+    /// may be grafted to the meshes.
     pub fn remove_explicit_peer(&mut self, peer_id: &PeerId) {
         tracing::debug!(peer=%peer_id, "Removing explicit peer");
-        self.explicit_peers.remove(peer_id);
+        if self.explicit_peers.remove(peer_id) {
+            self.notify_explicit_peer(peer_id, false);
+        }
+        self.explicit_peer_backlogs.remove(peer_id);
+    }
+
+    /// Informs the handlers of all connections to a peer whether the peer is an explicit peer.
+    fn notify_explicit_peer(&mut self, peer_id: &PeerId, explicit: bool) {
+        if let Some(connections) = self.connected_peers.get(peer_id) {
+            for connection_id in &connections.connections {
+                self.events.push_back(ToSwarm::NotifyHandler {
+                    peer_id: *peer_id,
+                    event: HandlerIn::Explicit(explicit),
+                    handler: NotifyHandler::One(*connection_id),
+                });
+            }
+        }
+    }
+
+    /// Rejects the subscription of a peer to a topic, without otherwise blocking the peer.
+    ///
+    /// The subscription of the peer to the topic is ignored and the peer is never grafted to the
+    /// mesh of the topic, hence no messages of the topic are exchanged with it. If the peer is
+    /// currently subscribed, it is removed from the topic and pruned from its mesh.
+    ///
+    /// Returns `true` if the subscription was not already rejected.
+    pub fn reject_peer_subscription(&mut self, peer_id: &PeerId, topic_hash: TopicHash) -> bool {
+        let topics = self.rejected_subscriptions.entry(*peer_id).or_default();
+        if topics.contains_key(&topic_hash) {
+            return false;
+        }
+        tracing::debug!(peer=%peer_id, topic=%topic_hash, "Rejecting subscription of peer");
+
+        let subscribed = self
+            .peer_topics
+            .get_mut(peer_id)
+            .is_some_and(|topics| topics.remove(&topic_hash));
+        topics.insert(topic_hash.clone(), subscribed);
+        if subscribed {
+            self.remove_peer_from_topic(peer_id, topic_hash);
+        }
+
+        true
+    }
+
+    /// Removes a peer whose subscription is no longer accepted from the topic, pruning it from
+    /// the mesh of the topic.
+    fn remove_peer_from_topic(&mut self, peer_id: &PeerId, topic_hash: TopicHash) {
+        if let Some(peers) = self.topic_peers.get_mut(&topic_hash) {
+            peers.remove(peer_id);
+            if let Some(m) = self.metrics.as_mut() {
+                m.set_topic_peers(&topic_hash, peers.len());
+            }
+        }
+        if let Some(peers) = self.fanout.get_mut(&topic_hash) {
+            peers.remove(peer_id);
+        }
+        if self
+            .mesh
+            .get_mut(&topic_hash)
+            .is_some_and(|peers| peers.remove(peer_id))
+        {
+            if let Some(m) = self.metrics.as_mut() {
+                m.peers_removed(&topic_hash, Churn::Unsub, 1)
+            }
+            mesh_peers_removed(
+                &self.config,
+                &mut self.events,
+                &topic_hash,
+                [peer_id],
+                MeshLeaveReason::Unsubscribed,
+            );
+            self.send_graft_prune(
+                HashMap::new(),
+                HashMap::from([(*peer_id, vec![topic_hash])]),
+                HashSet::from([*peer_id]),
+            );
+        }
+    }
+
+    /// Allows a subscription rejected via [`Behaviour::reject_peer_subscription`] again.
+    ///
+    /// If the peer is currently subscribed to the topic, the subscription is accepted as if it
+    /// was just received.
+    ///
+    /// Returns `true` if the subscription was rejected.
+    pub fn allow_peer_subscription(&mut self, peer_id: &PeerId, topic_hash: &TopicHash) -> bool {
+        let Some(topics) = self.rejected_subscriptions.get_mut(peer_id) else {
+            return false;
+        };
+        let Some(subscribed) = topics.remove(topic_hash) else {
+            return false;
+        };
+        if topics.is_empty() {
+            self.rejected_subscriptions.remove(peer_id);
+        }
+        tracing::debug!(peer=%peer_id, topic=%topic_hash, "Allowing subscription of peer");
+
+        if subscribed && self.peer_topics.contains_key(peer_id) {
+            let subscription = Subscription {
+                action: SubscriptionAction::Subscribe,
+                topic_hash: topic_hash.clone(),
+            };
+            self.handle_received_subscriptions(&[subscription], peer_id);
+        }
+
+        true
+    }
+
+    /// Returns the subscription filter.
+    pub fn subscription_filter(&self) -> &F {
+        &self.subscription_filter
+    }
+
+    /// Updates the subscription filter, e.g. the rules of a
+    /// [`DynamicSubscriptionFilter`](crate::DynamicSubscriptionFilter), and applies the updated
+    /// filter to the subscriptions of all connected peers.
+    ///
+    /// Peers subscribed to a topic the filter no longer allows are removed from the topic and
+    /// pruned from its mesh, emitting [`Event::SubscriptionFiltered`]. Subscriptions dropped by
+    /// the filter before are accepted if the filter now allows them, as if they were just
+    /// received.
+    pub fn update_subscription_filter(&mut self, update: impl FnOnce(&mut F)) {
+        update(&mut self.subscription_filter);
+
+        let mut filtered = Vec::new();
+        for (peer_id, topics) in &self.peer_topics {
+            for topic_hash in topics {
+                let subscription = Subscription {
+                    action: SubscriptionAction::Subscribe,
+                    topic_hash: topic_hash.clone(),
+                };
+                if !self
+                    .subscription_filter
+                    .allow_incoming_subscription(&subscription)
+                {
+                    filtered.push((*peer_id, topic_hash.clone()));
+                }
+            }
+        }
+        for (peer_id, topic_hash) in filtered {
+            tracing::debug!(peer=%peer_id, topic=%topic_hash, "Subscription of peer filtered");
+            if let Some(topics) = self.peer_topics.get_mut(&peer_id) {
+                topics.remove(&topic_hash);
+            }
+            let topics = self.filtered_subscriptions.entry(peer_id).or_default();
+            if topics.len() < FILTERED_SUBSCRIPTIONS_CAP {
+                topics.insert(topic_hash.clone());
+            }
+            self.remove_peer_from_topic(&peer_id, topic_hash.clone());
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::SubscriptionFiltered {
+                    peer_id,
+                    topic: topic_hash,
+                }));
+        }
+
+        let mut allowed = Vec::new();
+        for (peer_id, topics) in &mut self.filtered_subscriptions {
+            topics.retain(|topic_hash| {
+                let subscription = Subscription {
+                    action: SubscriptionAction::Subscribe,
+                    topic_hash: topic_hash.clone(),
+                };
+                if self
+                    .subscription_filter
+                    .allow_incoming_subscription(&subscription)
+                {
+                    allowed.push((*peer_id, subscription));
+                    return false;
+                }
+                true
+            });
+        }
+        self.filtered_subscriptions
+            .retain(|_, topics| !topics.is_empty());
+        for (peer_id, subscription) in allowed {
+            self.handle_received_subscriptions(&[subscription], &peer_id);
+        }
+    }
+
+    /// Sets the sink recording the publish, deliver, duplicate, reject, graft and prune events of
+    /// the router, replacing any previously set sink.
+    ///
+    /// See [`FileTracer`](crate::FileTracer) and [`RemoteTracer`](crate::RemoteTracer) for sinks
+    /// compatible with the go-libp2p-pubsub tracing tools.
+    pub fn set_tracer(&mut self, tracer: impl TraceSink) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Sets the cache storing the ids of the seen messages, replacing the in-memory cache bounded
+    /// by [`Config::duplicate_cache_time`].
+    ///
+    /// The ids recorded in the previous cache are not carried over, so the cache should be set
+    /// before joining the network. See [`BloomDuplicateCache`](crate::BloomDuplicateCache).
+    pub fn set_duplicate_cache(&mut self, duplicate_cache: impl DuplicateCache) {
+        self.duplicate_cache = Box::new(duplicate_cache);
+    }
+
+    /// Retains a message for the disconnected explicit peers subscribed to its topic, apart from
+    /// the peer we received it from and its author.
+    ///
+    /// Returns `true` if the message was retained for any peer.
+    fn retain_for_explicit_peers(
+        &mut self,
+        message: &RawMessage,
+        propagation_source: Option<&PeerId>,
+    ) -> bool {
+        let max_messages = self.config.explicit_peer_replay_max_messages();
+        let now = Instant::now();
+        let mut retained = false;
+        for (peer_id, backlog) in self.explicit_peer_backlogs.iter_mut() {
+            if Some(peer_id) == propagation_source
+                || Some(peer_id) == message.source.as_ref()
+                || !backlog.topics.contains(&message.topic)
+            {
+                continue;
+            }
+            if backlog.messages.len() >= max_messages {
+                backlog.messages.pop_front();
+            }
+            backlog.messages.push_back((now, message.clone()));
+            retained = true;
+        }
+        retained
     }
 
     /// Blacklists a peer. All messages from this peer will be rejected and any message that was
@@ -888,11 +2067,115 @@ where
         }
     }
 
+    /// Updates the scoring parameters of a topic at runtime, e.g. to adjust the weights of a
+    /// topic at a fork. The scores of all peers are recomputed with the new parameters, while the
+    /// mesh and the counters of the peers are retained. Counters exceeding the caps of the new
+    /// parameters are capped.
+    ///
+    /// Returns an error if the parameters are not valid or if peer scoring is not active.
+    pub fn update_topic_score_params<H: Hasher>(
+        &mut self,
+        topic: &Topic<H>,
+        params: TopicScoreParams,
+    ) -> Result<(), &'static str> {
+        params.validate()?;
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            peer_score.set_topic_params(topic.hash(), params);
+            Ok(())
+        } else {
+            Err("Peer score must be initialised with `with_peer_score()`")
+        }
+    }
+
+    /// Updates the peer score thresholds at runtime. The new thresholds apply to all subsequent
+    /// decisions, e.g. whether to accept gossip from or publish to a peer.
+    ///
+    /// Returns an error if the thresholds are not valid or if peer scoring is not active.
+    pub fn update_peer_score_thresholds(
+        &mut self,
+        thresholds: PeerScoreThresholds,
+    ) -> Result<(), &'static str> {
+        thresholds.validate()?;
+        if let Some((_, current, ..)) = &mut self.peer_score {
+            *current = thresholds;
+            Ok(())
+        } else {
+            Err("Peer score must be initialised with `with_peer_score()`")
+        }
+    }
+
+    /// Adds the signed peer record of a connected peer, e.g. as received via identify. The record is
+    /// sent along with the peer when exchanging it in PX, allowing the receivers to dial it.
+    ///
+    /// A record older than the one already known for the peer is ignored.
+    pub fn add_signed_peer_record(&mut self, record: PeerRecord) {
+        match self.signed_peer_records.entry(record.peer_id()) {
+            Entry::Occupied(mut entry) => {
+                if entry.get().seq() < record.seq() {
+                    entry.insert(record);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(record);
+            }
+        }
+    }
+
+    /// Overrides the mesh maintenance parameters of the [`Config`] for a topic, e.g. to maintain a
+    /// larger mesh for a topic carrying much data. The mesh of the topic is adjusted to the new
+    /// parameters during the next heartbeat.
+    ///
+    /// Returns an error if the parameters are not valid.
+    pub fn set_topic_mesh_params<H: Hasher>(
+        &mut self,
+        topic: &Topic<H>,
+        params: TopicMeshParams,
+    ) -> Result<(), ConfigBuilderError> {
+        params.validate()?;
+        self.mesh_params.topics.insert(topic.hash(), params);
+        Ok(())
+    }
+
+    /// Removes the mesh maintenance parameters of a topic set via
+    /// [`Behaviour::set_topic_mesh_params`], such that the parameters of the [`Config`] apply to
+    /// the topic again.
+    pub fn remove_topic_mesh_params<H: Hasher>(&mut self, topic: &Topic<H>) {
+        self.mesh_params.topics.remove(&topic.hash());
+    }
+
+    /// Returns the mesh maintenance parameters applying to a topic.
+    pub fn topic_mesh_params(&self, topic_hash: &TopicHash) -> &TopicMeshParams {
+        self.mesh_params.get(topic_hash)
+    }
+
     /// Returns a scoring parameters for a topic if existent.
     pub fn get_topic_params<H: Hasher>(&self, topic: &Topic<H>) -> Option<&TopicScoreParams> {
         self.peer_score.as_ref()?.0.get_topic_params(&topic.hash())
     }
 
+    /// Exports the score state of all known peers, e.g. to retain it across restarts
+    /// via [`Behaviour::import_peer_scores`]. Returns `None` if scoring is not active.
+    pub fn export_peer_scores(&self) -> Option<PeerScoreSnapshot> {
+        self.peer_score
+            .as_ref()
+            .map(|(peer_score, ..)| peer_score.export())
+    }
+
+    /// Imports the score state of peers from a snapshot taken with
+    /// [`Behaviour::export_peer_scores`], treating the peers as disconnected.
+    ///
+    /// The counters are decayed for the time elapsed since the snapshot was taken. Peers whose
+    /// score is already tracked are left untouched, and nothing is imported if the snapshot is
+    /// older than [`PeerScoreParams::retain_score`]. Returns false if scoring is not active.
+    pub fn import_peer_scores(&mut self, snapshot: PeerScoreSnapshot) -> bool {
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            peer_score.import(snapshot);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Sets the application specific score for a peer. Returns true if scoring is active and
     /// the peer is connected or if the score of the peer is not yet expired, false otherwise.
     pub fn set_application_score(&mut self, peer_id: &PeerId, new_score: f64) -> bool {
@@ -936,13 +2219,20 @@ where
 
             // Add up to mesh_n of them them to the mesh
             // NOTE: These aren't randomly added, currently FIFO
-            let add_peers = std::cmp::min(peers.len(), self.config.mesh_n());
+            let add_peers = std::cmp::min(peers.len(), self.mesh_params.get(topic_hash).mesh_n);
             tracing::debug!(
                 topic=%topic_hash,
                 "JOIN: Adding {:?} peers from the fanout for topic",
                 add_peers
             );
             added_peers.extend(peers.iter().take(add_peers));
+            mesh_peers_added(
+                &self.config,
+                &mut self.events,
+                topic_hash,
+                peers.iter().take(add_peers),
+                MeshJoinReason::Fanout,
+            );
 
             self.mesh.insert(
                 topic_hash.clone(),
@@ -959,13 +2249,14 @@ where
         }
 
         // check if we need to get more peers, which we randomly select
-        if added_peers.len() < self.config.mesh_n() {
+        if added_peers.len() < self.mesh_params.get(topic_hash).mesh_n {
             // get the peers
-            let new_peers = get_random_peers(
+            let new_peers = get_random_mesh_peers(
                 &self.topic_peers,
                 &self.connected_peers,
                 topic_hash,
-                self.config.mesh_n() - added_peers.len(),
+                self.mesh_params.get(topic_hash).mesh_n - added_peers.len(),
+                self.config.relayed_mesh_policy(),
                 |peer| {
                     !added_peers.contains(peer)
                         && !self.explicit_peers.contains(peer)
@@ -974,6 +2265,13 @@ where
                 },
             );
             added_peers.extend(new_peers.clone());
+            mesh_peers_added(
+                &self.config,
+                &mut self.events,
+                topic_hash,
+                &new_peers,
+                MeshJoinReason::Joined,
+            );
             // add them to the mesh
             tracing::debug!(
                 "JOIN: Inserting {:?} random peers into the mesh",
@@ -994,6 +2292,12 @@ where
             if let Some((peer_score, ..)) = &mut self.peer_score {
                 peer_score.graft(&peer_id, topic_hash.clone());
             }
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(TraceEvent::Graft {
+                    peer_id,
+                    topic: topic_hash.clone(),
+                });
+            }
             Self::control_pool_add(
                 &mut self.control_pool,
                 peer_id,
@@ -1061,7 +2365,13 @@ where
                 |p| p != peer && !self.score_below_threshold(p, |_| 0.0).0,
             )
             .into_iter()
-            .map(|p| PeerInfo { peer_id: Some(p) })
+            .map(|p| PeerInfo {
+                peer_id: Some(p),
+                signed_peer_record: self
+                    .signed_peer_records
+                    .get(&p)
+                    .map(|record| record.to_signed_envelope().into_protobuf_encoding()),
+            })
             .collect()
         } else {
             Vec::new()
@@ -1086,12 +2396,23 @@ where
     /// Gossipsub LEAVE(topic) - Notifies mesh\[topic\] peers with PRUNE messages.
     fn leave(&mut self, topic_hash: &TopicHash) {
         tracing::debug!(topic=%topic_hash, "Running LEAVE for topic");
+        self.topic_deliveries.remove(topic_hash);
+        self.gossip_factors.remove(topic_hash);
+        self.outbound_quota_not_met.remove(topic_hash);
+        self.delivery_latencies.remove(topic_hash);
 
         // If our mesh contains the topic, send prune to peers and delete it from the mesh
         if let Some((_, peers)) = self.mesh.remove_entry(topic_hash) {
             if let Some(m) = self.metrics.as_mut() {
                 m.left(topic_hash)
             }
+            mesh_peers_removed(
+                &self.config,
+                &mut self.events,
+                topic_hash,
+                &peers,
+                MeshLeaveReason::Left,
+            );
             for peer in peers {
                 // Send a PRUNE control message
                 tracing::debug!(%peer, "LEAVE: Sending PRUNE to peer");
@@ -1099,6 +2420,12 @@ where
                 let control =
                     self.make_prune(topic_hash, &peer, self.config.do_px(), on_unsubscribe);
                 Self::control_pool_add(&mut self.control_pool, peer, control);
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.trace(TraceEvent::Prune {
+                        peer_id: peer,
+                        topic: topic_hash.clone(),
+                    });
+                }
 
                 // If the peer did not previously exist in any mesh, inform the handler
                 peer_removed_from_mesh(
@@ -1164,6 +2491,15 @@ where
             return;
         }
 
+        // Announcements of choked peers rank them for unchoking, even if they are not followed up.
+        if self.config.choke_ticks().is_some() {
+            let now = Instant::now();
+            for (topic_hash, message_ids) in &ihave_msgs {
+                self.choking
+                    .announced(topic_hash, peer_id, message_ids, now);
+            }
+        }
+
         // IHAVE flood protection
         let peer_have = self.count_received_ihave.entry(*peer_id).or_insert(0);
         *peer_have += 1;
@@ -1174,6 +2510,7 @@ where
             interval; ignoring",
                 *peer_have
             );
+            self.budget_exceeded(peer_id, ControlBudget::IHave);
             return;
         }
 
@@ -1255,6 +2592,9 @@ where
                 // Add all messages to the pending list
                 self.pending_iwant_msgs.insert(message_id.clone());
             }
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.register_iwants_sent(&iwant_ids_vec);
+            }
 
             if let Some((_, _, _, gossip_promises)) = &mut self.peer_score {
                 gossip_promises.add_promise(
@@ -1303,12 +2643,32 @@ where
                 .get_with_iwant_counts(&id, peer_id)
                 .map(|(msg, count)| (msg.clone(), count))
             {
-                if count > self.config.gossip_retransimission() {
+                if msg.is_expired(SystemTime::now()) {
+                    tracing::debug!(
+                        peer=%peer_id,
+                        message=%id,
+                        "IWANT: Message expired; ignoring request"
+                    );
+                } else if !self.config.is_peer_allowed_on_topic(&msg.topic, peer_id) {
+                    tracing::debug!(
+                        peer=%peer_id,
+                        message=%id,
+                        "IWANT: Peer is not allowed on the topic of the message; ignoring request"
+                    );
+                } else if count > self.config.gossip_retransimission() {
                     tracing::debug!(
                         peer=%peer_id,
                         message=%id,
                         "IWANT: Peer has asked for message too many times; ignoring request"
                     );
+                } else if !self.take_iwant_response(peer_id) {
+                    tracing::debug!(
+                        peer=%peer_id,
+                        "IWANT: Peer has requested too many messages within this heartbeat \
+                        interval; ignoring further requests"
+                    );
+                    self.budget_exceeded(peer_id, ControlBudget::IWant);
+                    break;
                 } else {
                     tracing::debug!(peer=%peer_id, "IWANT: Sending cached messages to peer");
                     self.send_message(*peer_id, RpcOut::Forward(msg));
@@ -1318,15 +2678,143 @@ where
         tracing::debug!(peer=%peer_id, "Completed IWANT handling for peer");
     }
 
+    /// Counts a message sent in response to an IWANT request of the peer, returning whether the
+    /// [`Config::max_iwant_responses`] budget of the peer allows it.
+    fn take_iwant_response(&mut self, peer_id: &PeerId) -> bool {
+        let Some(max_responses) = self.config.max_iwant_responses() else {
+            return true;
+        };
+        let responses = self.count_iwant_responses.entry(*peer_id).or_default();
+        if *responses >= max_responses {
+            return false;
+        }
+        *responses += 1;
+        true
+    }
+
+    /// Takes a token from the bucket of the peer for one of its control messages, returning
+    /// whether the [`Config::control_messages_per_second`] budget of the peer allows it.
+    fn take_control_token(&mut self, peer_id: &PeerId, now: Instant) -> bool {
+        let Some(per_second) = self.config.control_messages_per_second() else {
+            return true;
+        };
+        let burst = self.config.control_messages_burst();
+        self.control_token_buckets
+            .entry(*peer_id)
+            .or_insert_with(|| ControlTokenBucket::new(burst, now))
+            .try_take(per_second, burst, now)
+    }
+
+    /// Penalizes a peer for exceeding a control message budget and reports it, at most once per
+    /// budget and heartbeat.
+    fn budget_exceeded(&mut self, peer_id: &PeerId, budget: ControlBudget) {
+        if !self.exceeded_budgets.insert((*peer_id, budget)) {
+            return;
+        }
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            peer_score.add_penalty(peer_id, 1);
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.register_score_penalty(Penalty::BudgetExceeded);
+            }
+        }
+        self.events
+            .push_back(ToSwarm::GenerateEvent(Event::BudgetExceeded {
+                peer_id: *peer_id,
+                budget,
+            }));
+    }
+
+    /// Handles an IDONTWANT control message. Remembers the message ids so that the messages are
+    /// not forwarded to the peer.
+    fn handle_idontwant(&mut self, peer_id: &PeerId, message_ids: Vec<MessageId>) {
+        let Some(peer) = self.connected_peers.get_mut(peer_id) else {
+            tracing::error!(peer=%peer_id, "IDONTWANT: received message from an unknown peer");
+            return;
+        };
+
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.register_idontwant(message_ids.len());
+        }
+
+        let now = Instant::now();
+        for message_id in message_ids {
+            if peer.dont_send.len() >= IDONTWANT_CAP {
+                tracing::debug!(
+                    peer=%peer_id,
+                    "IDONTWANT: too many message ids remembered for peer; ignoring further ids"
+                );
+                break;
+            }
+            peer.dont_send.insert(message_id, now);
+        }
+    }
+
+    /// Sends an IDONTWANT for a received message to the gossipsub v1.2 mesh peers of its topic,
+    /// apart from the peer we received it from and its author.
+    fn send_idontwant(
+        &mut self,
+        message_id: &MessageId,
+        message: &RawMessage,
+        propagation_source: &PeerId,
+    ) {
+        let Some(mesh_peers) = self.mesh.get(&message.topic) else {
+            return;
+        };
+
+        let recipient_peers = mesh_peers
+            .iter()
+            .filter(|peer_id| {
+                *peer_id != propagation_source
+                    && Some(*peer_id) != message.source.as_ref()
+                    && self
+                        .connected_peers
+                        .get(peer_id)
+                        .is_some_and(|peer| peer.kind == PeerKind::Gossipsubv1_2)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        for peer_id in recipient_peers {
+            tracing::debug!(peer=%peer_id, message=%message_id, "Sending IDONTWANT to peer");
+            self.send_message(
+                peer_id,
+                RpcOut::Control(ControlAction::IDontWant {
+                    message_ids: vec![message_id.clone()],
+                }),
+            );
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.register_idontwant_sent(&message.topic);
+            }
+        }
+    }
+
     /// Handles GRAFT control messages. If subscribed to the topic, adds the peer to mesh, if not,
     /// responds with PRUNE messages.
     fn handle_graft(&mut self, peer_id: &PeerId, topics: Vec<TopicHash>) {
         tracing::debug!(peer=%peer_id, "Handling GRAFT message for peer");
 
-        let mut to_prune_topics = HashSet::new();
+        // a GRAFT starts the mesh membership afresh, unchoked in both directions
+        for topic_hash in &topics {
+            self.choking.reset(topic_hash, peer_id);
+        }
 
         let mut do_px = self.config.do_px();
 
+        // we don't GRAFT peers whose subscription to the topic was rejected or which are not
+        // allowed on the topic, nor PX to them
+        let (rejected, topics): (Vec<_>, Vec<_>) = topics.into_iter().partition(|topic| {
+            !self.config.is_peer_allowed_on_topic(topic, peer_id)
+                || self
+                    .rejected_subscriptions
+                    .get(peer_id)
+                    .is_some_and(|topics| topics.contains_key(topic))
+        });
+        if !rejected.is_empty() {
+            tracing::debug!(peer=%peer_id, "GRAFT: ignoring topics with rejected subscriptions");
+            do_px = false;
+        }
+        let mut to_prune_topics = rejected.into_iter().collect::<HashSet<_>>();
+
         // For each topic, if a peer has grafted us, then we necessarily must be in their mesh
         // and they must be subscribed to the topic. Ensure we have recorded the mapping.
         for topic in &topics {
@@ -1344,9 +2832,17 @@ where
         if self.explicit_peers.contains(peer_id) {
             tracing::warn!(peer=%peer_id, "GRAFT: ignoring request from direct peer");
             // this is possibly a bug from non-reciprocal configuration; send a PRUNE for all topics
-            to_prune_topics = topics.into_iter().collect();
+            to_prune_topics.extend(topics);
             // but don't PX
             do_px = false
+        } else if self.config.relayed_mesh_policy() == RelayedMeshPolicy::Forbid
+            && self
+                .connected_peers
+                .get(peer_id)
+                .is_some_and(|connections| connections.is_relayed())
+        {
+            tracing::debug!(peer=%peer_id, "GRAFT: ignoring request from relayed peer");
+            to_prune_topics.extend(topics);
         } else {
             let (below_zero, score) = self.score_below_threshold(peer_id, |_| 0.0);
             let now = Instant::now();
@@ -1414,7 +2910,7 @@ where
 
                     // check mesh upper bound and only allow graft if the upper bound is not reached or
                     // if it is an outbound peer
-                    if peers.len() >= self.config.mesh_n_high()
+                    if peers.len() >= self.mesh_params.get(&topic_hash).mesh_n_high
                         && !self.outbound_peers.contains(peer_id)
                     {
                         to_prune_topics.insert(topic_hash.clone());
@@ -1432,6 +2928,13 @@ where
                         if let Some(m) = self.metrics.as_mut() {
                             m.peers_included(&topic_hash, Inclusion::Subscribed, 1)
                         }
+                        mesh_peers_added(
+                            &self.config,
+                            &mut self.events,
+                            &topic_hash,
+                            [peer_id],
+                            MeshJoinReason::Grafted,
+                        );
                     }
 
                     // If the peer did not previously exist in any mesh, inform the handler
@@ -1444,6 +2947,12 @@ where
                         &self.connected_peers,
                     );
 
+                    if let Some(tracer) = &mut self.tracer {
+                        tracer.trace(TraceEvent::Graft {
+                            peer_id: *peer_id,
+                            topic: topic_hash.clone(),
+                        });
+                    }
                     if let Some((peer_score, ..)) = &mut self.peer_score {
                         peer_score.graft(peer_id, topic_hash);
                     }
@@ -1497,13 +3006,36 @@ where
                     topic=%topic_hash,
                     "PRUNE: Removing peer from the mesh for topic"
                 );
+                let leave_reason = match reason {
+                    Churn::Dc => MeshLeaveReason::Disconnected,
+                    Churn::BadScore => MeshLeaveReason::BadScore,
+                    Churn::Prune => MeshLeaveReason::Pruned,
+                    Churn::Unsub => MeshLeaveReason::Unsubscribed,
+                    Churn::Excess => MeshLeaveReason::Excess,
+                    Churn::Stale => MeshLeaveReason::Stale,
+                    Churn::Explicit => MeshLeaveReason::Explicit,
+                    Churn::SlowPeer => MeshLeaveReason::SlowPeer,
+                };
                 if let Some(m) = self.metrics.as_mut() {
                     m.peers_removed(topic_hash, reason, 1)
                 }
+                mesh_peers_removed(
+                    &self.config,
+                    &mut self.events,
+                    topic_hash,
+                    [peer_id],
+                    leave_reason,
+                );
 
                 if let Some((peer_score, ..)) = &mut self.peer_score {
                     peer_score.prune(peer_id, topic_hash.clone());
                 }
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.trace(TraceEvent::Prune {
+                        peer_id: *peer_id,
+                        topic: topic_hash.clone(),
+                    });
+                }
 
                 update_backoff = true;
 
@@ -1540,6 +3072,7 @@ where
             self.score_below_threshold(peer_id, |pst| pst.accept_px_threshold);
         for (topic_hash, px, backoff) in prune_data {
             self.remove_peer_from_mesh(peer_id, &topic_hash, backoff, true, Churn::Prune);
+            self.choking.reset(&topic_hash, peer_id);
 
             if self.mesh.contains_key(&topic_hash) {
                 //connect to px peers
@@ -1555,46 +3088,266 @@ where
                         continue;
                     }
 
-                    // NOTE: We cannot dial any peers from PX currently as we typically will not
-                    // know their multiaddr. Until SignedRecords are spec'd this
-                    // remains a stub. By default `config.prune_peers()` is set to zero and
-                    // this is skipped. If the user modifies this, this will only be able to
-                    // dial already known peers (from an external discovery mechanism for
-                    // example).
+                    // NOTE: Peers are dialed on the addresses of their signed peer record.
+                    // Without a record, we are only able to dial already known peers (from an
+                    // external discovery mechanism for example). By default
+                    // `config.prune_peers()` is set to zero and this is skipped.
                     if self.config.prune_peers() > 0 {
                         self.px_connect(px);
                     }
                 }
             }
         }
-        tracing::debug!(peer=%peer_id, "Completed PRUNE handling for peer");
+        tracing::debug!(peer=%peer_id, "Completed PRUNE handling for peer");
+    }
+
+    /// Notifies the mesh peers of the topic of a message rejected by the application, except the
+    /// peers which sent us the message, with a signed report if the invalid message reports
+    /// extension is enabled.
+    fn report_invalid_message(
+        &mut self,
+        msg_id: &MessageId,
+        topic_hash: &TopicHash,
+        propagation_source: &PeerId,
+        originating_peers: &HashSet<PeerId>,
+    ) {
+        let Some(max_reports) = self.config.invalid_message_reports() else {
+            return;
+        };
+        let PublishConfig::Signing {
+            keypair,
+            inline_key,
+            ..
+        } = &self.publish_config
+        else {
+            tracing::debug!("INVALID: can't report messages without signing key");
+            return;
+        };
+        let signature_bytes =
+            invalid_report_signature_bytes(msg_id, topic_hash, propagation_source);
+        let signature = match keypair.sign(&signature_bytes) {
+            Ok(signature) => signature,
+            Err(e) => {
+                tracing::warn!(message=%msg_id, "INVALID: failed to sign report: {e}");
+                return;
+            }
+        };
+        let report = ControlAction::Invalid {
+            message_id: msg_id.clone(),
+            topic_hash: topic_hash.clone(),
+            propagation_source: *propagation_source,
+            signature,
+            key: inline_key.clone(),
+        };
+
+        let peers = self
+            .mesh
+            .get(topic_hash)
+            .into_iter()
+            .flatten()
+            .filter(|peer| *peer != propagation_source && !originating_peers.contains(*peer))
+            .copied()
+            .collect::<Vec<_>>();
+        for peer_id in peers {
+            let sent = self.count_sent_invalid_reports.entry(peer_id).or_default();
+            if *sent >= max_reports {
+                tracing::debug!(peer=%peer_id, "INVALID: report limit reached for peer");
+                continue;
+            }
+            *sent += 1;
+            tracing::debug!(
+                peer=%peer_id,
+                message=%msg_id,
+                source=%propagation_source,
+                "INVALID: Reporting invalid message"
+            );
+            self.send_message(peer_id, RpcOut::Control(report.clone()));
+        }
+    }
+
+    /// Handles an INVALID report of a peer in our mesh, penalizing the reported propagation source
+    /// if the message has not been validated locally yet.
+    fn handle_invalid_report(&mut self, peer_id: &PeerId, report: ControlAction) {
+        let Some(max_reports) = self.config.invalid_message_reports() else {
+            tracing::debug!(peer=%peer_id, "INVALID: ignoring invalid message reports extension message");
+            return;
+        };
+        let ControlAction::Invalid {
+            message_id,
+            topic_hash,
+            propagation_source,
+            ..
+        } = &report
+        else {
+            return;
+        };
+        if !self
+            .mesh
+            .get(topic_hash)
+            .is_some_and(|peers| peers.contains(peer_id))
+        {
+            tracing::debug!(
+                peer=%peer_id,
+                topic=%topic_hash,
+                "INVALID: ignoring report of peer not in mesh"
+            );
+            return;
+        }
+        if let (true, score) = self.score_below_threshold(peer_id, |_| 0.0) {
+            tracing::debug!(
+                peer=%peer_id,
+                %score,
+                "INVALID: ignoring report of peer with negative score"
+            );
+            return;
+        }
+        let received = self
+            .count_received_invalid_reports
+            .entry(*peer_id)
+            .or_default();
+        if *received >= max_reports {
+            tracing::debug!(peer=%peer_id, "INVALID: report limit reached for peer");
+            return;
+        }
+        *received += 1;
+        if propagation_source == peer_id
+            || self.publish_config.get_own_id() == Some(propagation_source)
+        {
+            tracing::debug!(peer=%peer_id, "INVALID: ignoring report of reporter or ourselves");
+            return;
+        }
+        if !verify_invalid_report(peer_id, &report) {
+            tracing::warn!(peer=%peer_id, "INVALID: ignoring report with invalid signature");
+            return;
+        }
+
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            if peer_score.reported_invalid_message(propagation_source, message_id, topic_hash) {
+                tracing::debug!(
+                    peer=%peer_id,
+                    message=%message_id,
+                    source=%propagation_source,
+                    "INVALID: Penalized reported propagation source"
+                );
+            }
+        }
+    }
+
+    /// Handles a CHOKE or UNCHOKE message of a peer in our mesh.
+    fn handle_choke(&mut self, peer_id: &PeerId, topic_hash: TopicHash, choked: bool) {
+        if self.config.choke_ticks().is_none() {
+            tracing::debug!(peer=%peer_id, "CHOKE: ignoring choking extension message");
+            return;
+        }
+        if !self
+            .mesh
+            .get(&topic_hash)
+            .is_some_and(|peers| peers.contains(peer_id))
+        {
+            tracing::debug!(
+                peer=%peer_id,
+                topic=%topic_hash,
+                "CHOKE: ignoring message of peer not in mesh"
+            );
+            return;
+        }
+        tracing::debug!(peer=%peer_id, topic=%topic_hash, %choked, "Peer updated choking");
+        self.choking.set_choked_by(topic_hash, *peer_id, choked);
+    }
+
+    fn px_connect(&mut self, px: Vec<PeerInfo>) {
+        let mut n = self.config.px_dials_per_prune();
+        if let Some(max) = self.config.max_px_dials_per_heartbeat() {
+            n = n.min(max.saturating_sub(self.px_dials_in_heartbeat));
+        }
+        // Ignore peerInfo with no ID, or with a signed peer record not signed by the peer itself,
+        // to prevent spoofing its addresses.
+        let mut px = px
+            .into_iter()
+            .filter_map(|p| {
+                let peer_id = p.peer_id?;
+                let record = match p.signed_peer_record {
+                    Some(bytes) => match decode_signed_peer_record(&peer_id, &bytes) {
+                        Some(record) => Some(record),
+                        None => {
+                            tracing::debug!(
+                                peer=%peer_id,
+                                "PX: ignoring peer with invalid signed peer record"
+                            );
+                            return None;
+                        }
+                    },
+                    None if self.config.px_require_signed_peer_records() => {
+                        tracing::debug!(peer=%peer_id, "PX: ignoring peer without signed peer record");
+                        return None;
+                    }
+                    None => None,
+                };
+                let addresses = record.as_ref().map_or(&[][..], |r| r.addresses());
+                if !self.config.is_px_dial_allowed(&peer_id, addresses) {
+                    tracing::debug!(peer=%peer_id, "PX: ignoring peer rejected by the dial filter");
+                    return None;
+                }
+                Some((peer_id, record))
+            })
+            .collect::<Vec<_>>();
+        let mut rng = thread_rng();
+        if px.len() > n {
+            // only use at most px_dials_per_prune many random peers
+            px.partial_shuffle(&mut rng, n);
+            px.truncate(n);
+        }
+        self.px_dials_in_heartbeat += px.len();
+
+        let jitter = self.config.px_dial_jitter();
+        for (peer_id, record) in px {
+            // mark as px peer
+            self.px_peers.insert(peer_id);
+
+            // dial peer, on the addresses of its signed peer record if given
+            let opts = match record {
+                Some(record) => {
+                    let opts = DialOpts::peer_id(peer_id)
+                        .addresses(record.addresses().to_vec())
+                        .build();
+                    self.add_signed_peer_record(record);
+                    opts
+                }
+                None => DialOpts::peer_id(peer_id).build(),
+            };
+            if jitter.is_zero() {
+                self.events.push_back(ToSwarm::Dial { opts });
+            } else {
+                let due = Instant::now() + rng.gen_range(Duration::ZERO..=jitter);
+                self.px_dial_queue.push((due, opts));
+                self.px_dial_timer = None;
+            }
+        }
     }
 
-    fn px_connect(&mut self, mut px: Vec<PeerInfo>) {
-        let n = self.config.prune_peers();
-        // Ignore peerInfo with no ID
-        //
-        //TODO: Once signed records are spec'd: Can we use peerInfo without any IDs if they have a
-        // signed peer record?
-        px.retain(|p| p.peer_id.is_some());
-        if px.len() > n {
-            // only use at most prune_peers many random peers
-            let mut rng = thread_rng();
-            px.partial_shuffle(&mut rng, n);
-            px = px.into_iter().take(n).collect();
-        }
+    /// Dials the peers of `px_dial_queue` that are due, and schedules the next wake-up.
+    fn poll_px_dials(&mut self, cx: &mut Context<'_>) {
+        loop {
+            if let Some(timer) = &mut self.px_dial_timer {
+                if timer.poll_unpin(cx).is_pending() {
+                    return;
+                }
+            }
 
-        for p in px {
-            // TODO: Once signed records are spec'd: extract signed peer record if given and handle
-            // it, see https://github.com/libp2p/specs/pull/217
-            if let Some(peer_id) = p.peer_id {
-                // mark as px peer
-                self.px_peers.insert(peer_id);
+            let now = Instant::now();
+            let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.px_dial_queue)
+                .into_iter()
+                .partition(|(at, _)| *at <= now);
+            self.px_dial_queue = pending;
+            self.events
+                .extend(due.into_iter().map(|(_, opts)| ToSwarm::Dial { opts }));
 
-                // dial peer
-                self.events.push_back(ToSwarm::Dial {
-                    opts: DialOpts::peer_id(peer_id).build(),
-                });
+            match self.px_dial_queue.iter().map(|(at, _)| *at).min() {
+                Some(next) => self.px_dial_timer = Some(Delay::new(next - now)),
+                None => {
+                    self.px_dial_timer = None;
+                    return;
+                }
             }
         }
     }
@@ -1690,23 +3443,42 @@ where
             metrics.msg_recvd_unfiltered(&raw_message.topic, raw_message.raw_protobuf_len());
         }
 
+        if raw_message.chunk.is_some() && self.chunk_reassembly.is_none() {
+            tracing::debug!("Dropping chunk, chunking is disabled");
+            return;
+        }
+
         // Try and perform the data transform to the message. If it fails, consider it invalid.
-        let message = match self.data_transform.inbound_transform(raw_message.clone()) {
-            Ok(message) => message,
-            Err(e) => {
-                tracing::debug!("Invalid message. Transform error: {:?}", e);
-                // Reject the message and return
-                self.handle_invalid_message(
-                    propagation_source,
-                    &raw_message,
-                    RejectReason::ValidationError(ValidationError::TransformFailed),
-                );
-                return;
+        // Chunks are only transformed once reassembled.
+        let message = if raw_message.chunk.is_some() {
+            Message {
+                source: raw_message.source,
+                data: raw_message.data.clone(),
+                sequence_number: raw_message.sequence_number,
+                topic: raw_message.topic.clone(),
+            }
+        } else {
+            match self.data_transform.inbound_transform(raw_message.clone()) {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::debug!("Invalid message. Transform error: {:?}", e);
+                    // Reject the message and return
+                    self.handle_invalid_message(
+                        propagation_source,
+                        &raw_message,
+                        RejectReason::ValidationError(ValidationError::TransformFailed),
+                    );
+                    return;
+                }
             }
         };
 
-        // Calculate the message id on the transformed data.
-        let msg_id = self.config.message_id(&message);
+        // Calculate the message id on the transformed data, chunks are identified by their
+        // position instead.
+        let msg_id = match &raw_message.chunk {
+            Some(chunk) => chunk.message_id(),
+            None => self.config.message_id(&message),
+        };
 
         // Check the validity of the message
         // Peers get penalized if this message is invalid. We don't add it to the duplicate cache
@@ -1717,10 +3489,39 @@ where
 
         if !self.duplicate_cache.insert(msg_id.clone()) {
             tracing::debug!(message=%msg_id, "Message already received, ignoring");
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.duplicate_msg_recvd(&message.topic);
+            }
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(TraceEvent::DuplicateMessage {
+                    message_id: msg_id.clone(),
+                    received_from: *propagation_source,
+                    topic: message.topic.clone(),
+                });
+            }
             if let Some((peer_score, ..)) = &mut self.peer_score {
                 peer_score.duplicated_message(propagation_source, &msg_id, &message.topic);
             }
             self.mcache.observe_duplicate(&msg_id, propagation_source);
+            if self.config.stale_mesh_peer_timeout().is_some() {
+                self.topic_last_duplicate
+                    .insert(message.topic.clone(), Instant::now());
+            }
+            if self.config.adaptive_gossip_factor().is_some() {
+                self.topic_deliveries
+                    .entry(message.topic.clone())
+                    .or_default()
+                    .1 += 1;
+            }
+            if self.config.choke_ticks().is_some() {
+                self.choking.delivered(
+                    &message.topic,
+                    propagation_source,
+                    &msg_id,
+                    true,
+                    Instant::now(),
+                );
+            }
             return;
         }
         tracing::debug!(
@@ -1731,6 +3532,12 @@ where
         // Record the received message with the metrics
         if let Some(metrics) = self.metrics.as_mut() {
             metrics.msg_recvd(&message.topic);
+            metrics.iwant_fulfilled(&msg_id, &message.topic);
+        }
+
+        // Ask our mesh peers not to send us large messages we already received.
+        if raw_message.raw_protobuf_len() > self.config.idontwant_message_size_threshold() {
+            self.send_idontwant(&msg_id, &raw_message, propagation_source);
         }
 
         // Tells score that message arrived (but is maybe not fully validated yet).
@@ -1740,28 +3547,90 @@ where
             gossip_promises.message_delivered(&msg_id);
         }
 
+        // Don't propagate messages whose deadline passed, without penalizing the peer.
+        if raw_message.is_expired(SystemTime::now()) {
+            tracing::debug!(message=%msg_id, "Dropping expired message");
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.register_msg_expired(&message.topic);
+            }
+            return;
+        }
+
+        // Record the first delivery to adapt the gossip factor.
+        if self.config.adaptive_gossip_factor().is_some() {
+            self.topic_deliveries
+                .entry(message.topic.clone())
+                .or_default()
+                .0 += 1;
+        }
+
+        // Record the first delivery for stale mesh peer detection.
+        if let Some(last_delivery) = self
+            .mesh_last_delivery
+            .get_mut(&message.topic)
+            .and_then(|peers| peers.get_mut(propagation_source))
+        {
+            *last_delivery = Instant::now();
+        }
+
+        // Record the first delivery to rank the mesh peers for choking.
+        if self.config.choke_ticks().is_some() {
+            self.choking.delivered(
+                &message.topic,
+                propagation_source,
+                &msg_id,
+                false,
+                Instant::now(),
+            );
+        }
+
+        // Messages of topics with a validator are only propagated once validated.
+        let has_validator = self.topic_validators.contains_key(&message.topic);
+        if has_validator {
+            raw_message.validated = false;
+        }
+
         // Add the message to our memcache
         self.mcache.put(&msg_id, raw_message.clone());
 
+        if has_validator && self.mesh.contains_key(&message.topic) && raw_message.chunk.is_none() {
+            tracing::debug!(message=%msg_id, "Validating received message");
+            self.start_validation(msg_id, message, *propagation_source);
+            return;
+        }
+
         // Dispatch the message to the user if we are subscribed to any of the topics
-        if self.mesh.contains_key(&message.topic) {
+        if !self.mesh.contains_key(&message.topic) {
+            tracing::debug!(
+                topic=%message.topic,
+                "Received message on a topic we are not subscribed to"
+            );
+            return;
+        }
+
+        if let Some(chunk) = &raw_message.chunk {
+            // Chunks are delivered once reassembled.
+            self.handle_received_chunk(chunk, &raw_message, propagation_source, has_validator);
+        } else {
             tracing::debug!("Sending received message to user");
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(TraceEvent::DeliverMessage {
+                    message_id: msg_id.clone(),
+                    received_from: *propagation_source,
+                    topic: message.topic.clone(),
+                });
+            }
+            self.record_delivery_latency(&message);
             self.events
                 .push_back(ToSwarm::GenerateEvent(Event::Message {
                     propagation_source: *propagation_source,
                     message_id: msg_id.clone(),
                     message,
                 }));
-        } else {
-            tracing::debug!(
-                topic=%message.topic,
-                "Received message on a topic we are not subscribed to"
-            );
-            return;
         }
 
         // forward the message to mesh peers, if no validation is required
-        if !self.config.validate_messages() {
+        if !self.config.validate_messages() && !has_validator {
             if self
                 .forward_msg(
                     &msg_id,
@@ -1777,6 +3646,101 @@ where
         }
     }
 
+    /// Adds a received chunk to the reassembly of its chunked message, and delivers the message
+    /// once all of its chunks are received.
+    fn handle_received_chunk(
+        &mut self,
+        chunk: &Chunk,
+        raw_message: &RawMessage,
+        propagation_source: &PeerId,
+        has_validator: bool,
+    ) {
+        let Some(reassembly) = self.chunk_reassembly.as_mut() else {
+            return;
+        };
+        let reassembled =
+            match reassembly.insert(chunk, raw_message, *propagation_source, Instant::now()) {
+                Ok(Some(reassembled)) => reassembled,
+                Ok(None) => return,
+                Err(error) => {
+                    tracing::debug!(group=%chunk.group, ?error, "Dropping chunk");
+                    return;
+                }
+            };
+        let group = reassembled.group;
+
+        // The validation result of the message applies to its chunks.
+        let validate = has_validator || self.config.validate_messages();
+        if validate {
+            self.chunk_validations
+                .insert(group.clone(), reassembled.chunks);
+        }
+
+        let message = match self.data_transform.inbound_transform(RawMessage {
+            source: reassembled.source,
+            data: reassembled.data,
+            sequence_number: None,
+            topic: reassembled.topic,
+            signature: None,
+            key: None,
+            validated: false,
+            expires_at: None,
+            chunk: None,
+        }) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::debug!(message=%group, "Invalid chunked message. Transform error: {:?}", e);
+                if validate {
+                    let _ = self.report_message_validation_result(
+                        &group,
+                        propagation_source,
+                        MessageAcceptance::Reject,
+                    );
+                }
+                return;
+            }
+        };
+
+        if has_validator {
+            tracing::debug!(message=%group, "Validating reassembled message");
+            self.start_validation(group, message, *propagation_source);
+            return;
+        }
+
+        tracing::debug!(message=%group, "Sending reassembled message to user");
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(TraceEvent::DeliverMessage {
+                message_id: group.clone(),
+                received_from: *propagation_source,
+                topic: message.topic.clone(),
+            });
+        }
+        self.record_delivery_latency(&message);
+        self.events
+            .push_back(ToSwarm::GenerateEvent(Event::Message {
+                propagation_source: *propagation_source,
+                message_id: group,
+                message,
+            }));
+    }
+
+    /// Records the latency from publishing a message to delivering it to the application, if the
+    /// message carries a timestamp.
+    fn record_delivery_latency(&mut self, message: &Message) {
+        let Some(published) = self.config.delivery_timestamp(message) else {
+            return;
+        };
+        // Timestamps in the future, e.g. due to clock skew, are not measured.
+        let Ok(latency) = SystemTime::now().duration_since(published) else {
+            return;
+        };
+
+        self.delivery_latencies.record(&message.topic, latency);
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.observe_delivery_latency(&message.topic, latency);
+        }
+    }
+
     // Handles invalid messages received.
     fn handle_invalid_message(
         &mut self,
@@ -1784,20 +3748,38 @@ where
         raw_message: &RawMessage,
         reject_reason: RejectReason,
     ) {
+        // Transform the message at most once, as transforms such as decompression may be costly,
+        // and not at all if it is known to fail.
+        let transform = (self.tracer.is_some() || self.peer_score.is_some())
+            && !matches!(
+                reject_reason,
+                RejectReason::ValidationError(ValidationError::TransformFailed)
+            );
+        let message = transform
+            .then(|| {
+                self.data_transform
+                    .inbound_transform(raw_message.clone())
+                    .ok()
+            })
+            .flatten()
+            .map(|message| (self.config.message_id(&message), message.topic));
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(TraceEvent::RejectMessage {
+                message_id: message.as_ref().map(|(message_id, _)| message_id.clone()),
+                received_from: *propagation_source,
+                topic: raw_message.topic.clone(),
+                reason: reject_reason.trace_name().to_owned(),
+            });
+        }
+
         if let Some((peer_score, .., gossip_promises)) = &mut self.peer_score {
             if let Some(metrics) = self.metrics.as_mut() {
                 metrics.register_invalid_message(&raw_message.topic);
             }
 
-            if let Ok(message) = self.data_transform.inbound_transform(raw_message.clone()) {
-                let message_id = self.config.message_id(&message);
-
-                peer_score.reject_message(
-                    propagation_source,
-                    &message_id,
-                    &message.topic,
-                    reject_reason,
-                );
+            if let Some((message_id, topic)) = message {
+                peer_score.reject_message(propagation_source, &message_id, &topic, reject_reason);
 
                 gossip_promises.reject_message(&message_id, &reject_reason);
             } else {
@@ -1852,9 +3834,80 @@ where
             }
         };
 
+        // Remember the subscriptions dropped by the filter, in case it allows them later on.
+        let accepted = filtered_topics
+            .iter()
+            .map(|s| &s.topic_hash)
+            .collect::<HashSet<_>>();
+        for subscription in subscriptions {
+            let topic_hash = &subscription.topic_hash;
+            if accepted.contains(topic_hash)
+                || subscriptions
+                    .iter()
+                    .any(|s| &s.topic_hash == topic_hash && s.action != subscription.action)
+            {
+                continue;
+            }
+            match subscription.action {
+                SubscriptionAction::Subscribe => {
+                    let topics = self
+                        .filtered_subscriptions
+                        .entry(*propagation_source)
+                        .or_default();
+                    if topics.len() < FILTERED_SUBSCRIPTIONS_CAP
+                        && topics.insert(topic_hash.clone())
+                    {
+                        application_event.push(ToSwarm::GenerateEvent(
+                            Event::SubscriptionFiltered {
+                                peer_id: *propagation_source,
+                                topic: topic_hash.clone(),
+                            },
+                        ));
+                    }
+                }
+                SubscriptionAction::Unsubscribe => {
+                    if let Some(topics) = self.filtered_subscriptions.get_mut(propagation_source) {
+                        topics.remove(topic_hash);
+                        if topics.is_empty() {
+                            self.filtered_subscriptions.remove(propagation_source);
+                        }
+                    }
+                }
+            }
+        }
+
         for subscription in filtered_topics {
             // get the peers from the mapping, or insert empty lists if the topic doesn't exist
             let topic_hash = &subscription.topic_hash;
+
+            // ignore the subscriptions of peers not allowed on the topic
+            if !self
+                .config
+                .is_peer_allowed_on_topic(topic_hash, propagation_source)
+            {
+                tracing::debug!(
+                    peer=%propagation_source,
+                    topic=%topic_hash,
+                    "SUBSCRIPTION: Ignoring subscription of peer not allowed on topic"
+                );
+                continue;
+            }
+
+            // remember, but otherwise ignore, subscriptions rejected by the user
+            if let Some(subscribed) = self
+                .rejected_subscriptions
+                .get_mut(propagation_source)
+                .and_then(|topics| topics.get_mut(topic_hash))
+            {
+                tracing::debug!(
+                    peer=%propagation_source,
+                    topic=%topic_hash,
+                    "SUBSCRIPTION: Ignoring rejected subscription of peer"
+                );
+                *subscribed = subscription.action == SubscriptionAction::Subscribe;
+                continue;
+            }
+
             let peer_list = self.topic_peers.entry(topic_hash.clone()).or_default();
 
             match subscription.action {
@@ -1876,7 +3929,9 @@ where
                             self.connected_peers
                                 .get(propagation_source)
                                 .map(|v| &v.kind),
-                            Some(PeerKind::Gossipsubv1_1) | Some(PeerKind::Gossipsub)
+                            Some(PeerKind::Gossipsubv1_2)
+                                | Some(PeerKind::Gossipsubv1_1)
+                                | Some(PeerKind::Gossipsub)
                         )
                         && !Self::score_below_threshold_from_scores(
                             &self.peer_score,
@@ -1887,19 +3942,49 @@ where
                         && !self
                             .backoffs
                             .is_backoff_with_slack(topic_hash, propagation_source)
+                        // relayed peers are left to the heartbeat, unless they are treated like
+                        // any other peer
+                        && (self.config.relayed_mesh_policy() == RelayedMeshPolicy::Allow
+                            || !self
+                                .connected_peers
+                                .get(propagation_source)
+                                .is_some_and(|connections| connections.is_relayed()))
                     {
+                        // Peers that were in the mesh before their connection dropped are added
+                        // back as long as the mesh is not complete.
+                        let reconnected = self
+                            .disconnected_mesh_peers
+                            .get(propagation_source)
+                            .is_some_and(|(_, topics)| topics.contains(topic_hash));
+                        let mesh_params = self.mesh_params.get(topic_hash);
+                        let (mesh_target, reason) = if reconnected {
+                            (mesh_params.mesh_n, MeshJoinReason::Reconnected)
+                        } else {
+                            (mesh_params.mesh_n_low, MeshJoinReason::Subscribed)
+                        };
                         if let Some(peers) = self.mesh.get_mut(topic_hash) {
-                            if peers.len() < self.config.mesh_n_low()
-                                && peers.insert(*propagation_source)
-                            {
+                            if peers.len() < mesh_target && peers.insert(*propagation_source) {
                                 tracing::debug!(
                                     peer=%propagation_source,
                                     topic=%topic_hash,
+                                    ?reason,
                                     "SUBSCRIPTION: Adding peer to the mesh for topic"
                                 );
+                                if let Some((_, topics)) =
+                                    self.disconnected_mesh_peers.get_mut(propagation_source)
+                                {
+                                    topics.remove(topic_hash);
+                                }
                                 if let Some(m) = self.metrics.as_mut() {
                                     m.peers_included(topic_hash, Inclusion::Subscribed, 1)
                                 }
+                                mesh_peers_added(
+                                    &self.config,
+                                    &mut application_event,
+                                    topic_hash,
+                                    [propagation_source],
+                                    reason,
+                                );
                                 // send graft to the peer
                                 tracing::debug!(
                                     peer=%propagation_source,
@@ -1909,6 +3994,12 @@ where
                                 if let Some((peer_score, ..)) = &mut self.peer_score {
                                     peer_score.graft(propagation_source, topic_hash.clone());
                                 }
+                                if let Some(tracer) = &mut self.tracer {
+                                    tracer.trace(TraceEvent::Graft {
+                                        peer_id: *propagation_source,
+                                        topic: topic_hash.clone(),
+                                    });
+                                }
                                 topics_to_graft.push(topic_hash.clone());
                             }
                         }
@@ -2001,6 +4092,7 @@ where
         let start = Instant::now();
 
         self.heartbeat_ticks += 1;
+        self.px_dials_in_heartbeat = 0;
 
         let mut to_graft = HashMap::new();
         let mut to_prune = HashMap::new();
@@ -2009,13 +4101,78 @@ where
         // clean up expired backoffs
         self.backoffs.heartbeat();
 
+        // rotate the shards of sharded topics at the end of their epochs
+        let now = SystemTime::now();
+        for prefix in self.sharded_topics.keys().cloned().collect::<Vec<_>>() {
+            self.rotate_sharded_topics(&prefix, now);
+        }
+
         // clean up ihave counters
         self.count_sent_iwant.clear();
         self.count_received_ihave.clear();
+        self.count_sent_invalid_reports.clear();
+        self.count_received_invalid_reports.clear();
+        self.count_iwant_responses.clear();
+        self.exceeded_budgets.clear();
 
         // apply iwant penalties
         self.apply_iwant_penalties();
 
+        // forget about the activity of topics we are no longer subscribed to
+        if self.config.stale_mesh_peer_timeout().is_some() {
+            let mesh = &self.mesh;
+            self.mesh_last_delivery
+                .retain(|topic_hash, _| mesh.contains_key(topic_hash));
+            self.topic_last_duplicate
+                .retain(|topic_hash, _| mesh.contains_key(topic_hash));
+        }
+
+        if let Some(ticks) = self.config.choke_ticks().filter(|ticks| *ticks > 0) {
+            let window = self.config.heartbeat_interval() * ticks as u32;
+            self.choking.heartbeat(&self.mesh, window, start);
+            if self.heartbeat_ticks % ticks == 0 {
+                self.update_choked_peers();
+            }
+        }
+
+        self.check_saturated_queues(&mut to_prune, &mut no_px);
+        for peer_id in self.outbound_queue_lens.keys().copied().collect::<Vec<_>>() {
+            self.check_queue_watermarks(&peer_id);
+        }
+
+        if let Some(ticks) = self.config.score_report_ticks().filter(|ticks| *ticks > 0) {
+            if self.heartbeat_ticks % ticks == 0 {
+                self.report_scores();
+            }
+        }
+
+        // forget about IDONTWANT message ids that timed out
+        for peer in self.connected_peers.values_mut() {
+            peer.dont_send
+                .retain(|_, received| received.elapsed() < IDONTWANT_TIMEOUT);
+        }
+
+        // forget about messages retained for explicit peers for too long
+        if let Some(window) = self.config.explicit_peer_replay_window() {
+            for backlog in self.explicit_peer_backlogs.values_mut() {
+                while backlog
+                    .messages
+                    .front()
+                    .is_some_and(|(retained, _)| retained.elapsed() > window)
+                {
+                    backlog.messages.pop_front();
+                }
+            }
+        }
+
+        // forget about the meshes of disconnected peers not added back in time
+        if let Some(window) = self.config.mesh_reconnect_window() {
+            self.disconnected_mesh_peers
+                .retain(|_, (disconnected, topics)| {
+                    !topics.is_empty() && disconnected.elapsed() < window
+                });
+        }
+
         // check connections to explicit peers
         if self.heartbeat_ticks % self.config.check_explicit_peers_ticks() == 0 {
             for p in self.explicit_peers.clone() {
@@ -2035,10 +4192,12 @@ where
 
         // maintain the mesh for each topic
         for (topic_hash, peers) in self.mesh.iter_mut() {
+            let mesh_params = self.mesh_params.get(topic_hash);
             let explicit_peers = &self.explicit_peers;
             let backoffs = &self.backoffs;
             let topic_peers = &self.topic_peers;
             let outbound_peers = &self.outbound_peers;
+            let relayed_mesh_policy = self.config.relayed_mesh_policy();
 
             // drop all peers with negative score, without PX
             // if there is at some point a stable retain method for BTreeSet the following can be
@@ -2070,29 +4229,84 @@ where
             if let Some(m) = self.metrics.as_mut() {
                 m.peers_removed(topic_hash, Churn::BadScore, to_remove_peers.len())
             }
+            mesh_peers_removed(
+                &self.config,
+                &mut self.events,
+                topic_hash,
+                &to_remove_peers,
+                MeshLeaveReason::BadScore,
+            );
 
             for peer_id in to_remove_peers {
                 peers.remove(&peer_id);
             }
 
+            // drop all peers that have not delivered any first-seen message for too long despite
+            // activity on the topic, without PX
+            let mut stale_peers = HashSet::new();
+            if let Some(timeout) = self.config.stale_mesh_peer_timeout() {
+                let last_delivery = self
+                    .mesh_last_delivery
+                    .entry(topic_hash.clone())
+                    .or_default();
+                last_delivery.retain(|peer_id, _| peers.contains(peer_id));
+                let last_duplicate = self.topic_last_duplicate.get(topic_hash);
+
+                for peer_id in peers.iter() {
+                    let delivered = *last_delivery.entry(*peer_id).or_insert(start);
+                    if start.duration_since(delivered) >= timeout
+                        && last_duplicate.is_some_and(|t| *t > delivered)
+                    {
+                        tracing::debug!(
+                            peer=%peer_id,
+                            topic=%topic_hash,
+                            "HEARTBEAT: Prune stale peer"
+                        );
+
+                        let current_topic = to_prune.entry(*peer_id).or_insert_with(Vec::new);
+                        current_topic.push(topic_hash.clone());
+                        no_px.insert(*peer_id);
+                        stale_peers.insert(*peer_id);
+                    }
+                }
+
+                if let Some(m) = self.metrics.as_mut() {
+                    m.peers_removed(topic_hash, Churn::Stale, stale_peers.len())
+                }
+                mesh_peers_removed(
+                    &self.config,
+                    &mut self.events,
+                    topic_hash,
+                    &stale_peers,
+                    MeshLeaveReason::Stale,
+                );
+
+                for peer_id in &stale_peers {
+                    peers.remove(peer_id);
+                    last_delivery.remove(peer_id);
+                }
+            }
+
             // too little peers - add some
-            if peers.len() < self.config.mesh_n_low() {
+            if peers.len() < mesh_params.mesh_n_low {
                 tracing::debug!(
                     topic=%topic_hash,
                     "HEARTBEAT: Mesh low. Topic contains: {} needs: {}",
                     peers.len(),
-                    self.config.mesh_n_low()
+                    mesh_params.mesh_n_low
                 );
                 // not enough peers - get mesh_n - current_length more
-                let desired_peers = self.config.mesh_n() - peers.len();
-                let peer_list = get_random_peers(
+                let desired_peers = mesh_params.mesh_n - peers.len();
+                let peer_list = get_random_mesh_peers(
                     topic_peers,
                     &self.connected_peers,
                     topic_hash,
                     desired_peers,
+                    relayed_mesh_policy,
                     |peer| {
                         !peers.contains(peer)
                             && !explicit_peers.contains(peer)
+                            && !stale_peers.contains(peer)
                             && !backoffs.is_backoff_with_slack(topic_hash, peer)
                             && *scores.get(peer).unwrap_or(&0.0) >= 0.0
                     },
@@ -2106,18 +4320,25 @@ where
                 if let Some(m) = self.metrics.as_mut() {
                     m.peers_included(topic_hash, Inclusion::Random, peer_list.len())
                 }
+                mesh_peers_added(
+                    &self.config,
+                    &mut self.events,
+                    topic_hash,
+                    &peer_list,
+                    MeshJoinReason::MeshLow,
+                );
                 peers.extend(peer_list);
             }
 
             // too many peers - remove some
-            if peers.len() > self.config.mesh_n_high() {
+            if peers.len() > mesh_params.mesh_n_high {
                 tracing::debug!(
                     topic=%topic_hash,
                     "HEARTBEAT: Mesh high. Topic contains: {} needs: {}",
                     peers.len(),
-                    self.config.mesh_n_high()
+                    mesh_params.mesh_n_high
                 );
-                let excess_peer_no = peers.len() - self.config.mesh_n();
+                let excess_peer_no = peers.len() - mesh_params.mesh_n;
 
                 // shuffle the peers and then sort by score ascending beginning with the worst
                 let mut rng = thread_rng();
@@ -2130,7 +4351,8 @@ where
                     score_p1.partial_cmp(&score_p2).unwrap_or(Ordering::Equal)
                 });
                 // shuffle everything except the last retain_scores many peers (the best ones)
-                shuffled[..peers.len() - self.config.retain_scores()].shuffle(&mut rng);
+                shuffled[..peers.len().saturating_sub(self.config.retain_scores())]
+                    .shuffle(&mut rng);
 
                 // count total number of outbound peers
                 let mut outbound = {
@@ -2144,12 +4366,13 @@ where
                 // remove the first excess_peer_no allowed (by outbound restrictions) peers adding
                 // them to to_prune
                 let mut removed = 0;
+                let mut excess_peers = Vec::new();
                 for peer in shuffled {
                     if removed == excess_peer_no {
                         break;
                     }
                     if self.outbound_peers.contains(&peer) {
-                        if outbound <= self.config.mesh_outbound_min() {
+                        if outbound <= mesh_params.mesh_outbound_min {
                             // do not remove anymore outbound peers
                             continue;
                         } else {
@@ -2162,45 +4385,79 @@ where
                     peers.remove(&peer);
                     let current_topic = to_prune.entry(peer).or_insert_with(Vec::new);
                     current_topic.push(topic_hash.clone());
+                    excess_peers.push(peer);
                     removed += 1;
                 }
 
                 if let Some(m) = self.metrics.as_mut() {
                     m.peers_removed(topic_hash, Churn::Excess, removed)
                 }
+                mesh_peers_removed(
+                    &self.config,
+                    &mut self.events,
+                    topic_hash,
+                    &excess_peers,
+                    MeshLeaveReason::Excess,
+                );
             }
 
-            // do we have enough outbound peers?
-            if peers.len() >= self.config.mesh_n_low() {
-                // count number of outbound peers we have
-                let outbound = { peers.iter().filter(|p| outbound_peers.contains(*p)).count() };
+            // do we have enough outbound peers? This is also checked if the mesh is low, to report
+            // meshes that cannot meet the quota.
+            let mut outbound = peers.iter().filter(|p| outbound_peers.contains(*p)).count();
 
-                // if we have not enough outbound peers, graft to some new outbound peers
-                if outbound < self.config.mesh_outbound_min() {
-                    let needed = self.config.mesh_outbound_min() - outbound;
-                    let peer_list = get_random_peers(
-                        topic_peers,
-                        &self.connected_peers,
-                        topic_hash,
-                        needed,
-                        |peer| {
-                            !peers.contains(peer)
-                                && !explicit_peers.contains(peer)
-                                && !backoffs.is_backoff_with_slack(topic_hash, peer)
-                                && *scores.get(peer).unwrap_or(&0.0) >= 0.0
-                                && outbound_peers.contains(peer)
-                        },
-                    );
-                    for peer in &peer_list {
-                        let current_topic = to_graft.entry(*peer).or_insert_with(Vec::new);
-                        current_topic.push(topic_hash.clone());
-                    }
-                    // update the mesh
-                    tracing::debug!("Updating mesh, new mesh: {:?}", peer_list);
-                    if let Some(m) = self.metrics.as_mut() {
-                        m.peers_included(topic_hash, Inclusion::Outbound, peer_list.len())
-                    }
-                    peers.extend(peer_list);
+            // if we have not enough outbound peers, graft to some new outbound peers
+            if outbound < mesh_params.mesh_outbound_min {
+                let needed = mesh_params.mesh_outbound_min - outbound;
+                let peer_list = get_random_mesh_peers(
+                    topic_peers,
+                    &self.connected_peers,
+                    topic_hash,
+                    needed,
+                    relayed_mesh_policy,
+                    |peer| {
+                        !peers.contains(peer)
+                            && !explicit_peers.contains(peer)
+                            && !stale_peers.contains(peer)
+                            && !backoffs.is_backoff_with_slack(topic_hash, peer)
+                            && *scores.get(peer).unwrap_or(&0.0) >= 0.0
+                            && outbound_peers.contains(peer)
+                    },
+                );
+                for peer in &peer_list {
+                    let current_topic = to_graft.entry(*peer).or_insert_with(Vec::new);
+                    current_topic.push(topic_hash.clone());
+                }
+                // update the mesh
+                tracing::debug!("Updating mesh, new mesh: {:?}", peer_list);
+                if let Some(m) = self.metrics.as_mut() {
+                    m.peers_included(topic_hash, Inclusion::Outbound, peer_list.len())
+                }
+                mesh_peers_added(
+                    &self.config,
+                    &mut self.events,
+                    topic_hash,
+                    &peer_list,
+                    MeshJoinReason::OutboundQuota,
+                );
+                outbound += peer_list.len();
+                peers.extend(peer_list);
+            }
+
+            if outbound >= mesh_params.mesh_outbound_min {
+                self.outbound_quota_not_met.remove(topic_hash);
+            } else if self.outbound_quota_not_met.insert(topic_hash.clone()) {
+                tracing::debug!(
+                    topic=%topic_hash,
+                    %outbound,
+                    "HEARTBEAT: Mesh does not meet the outbound quota"
+                );
+                if self.config.mesh_events() {
+                    self.events
+                        .push_back(ToSwarm::GenerateEvent(Event::OutboundQuotaNotMet {
+                            topic: topic_hash.clone(),
+                            outbound,
+                            mesh_outbound_min: mesh_params.mesh_outbound_min,
+                        }));
                 }
             }
 
@@ -2245,15 +4502,21 @@ where
 
                     // if the median score is below the threshold, select a better peer (if any) and
                     // GRAFT
-                    if median < thresholds.opportunistic_graft_threshold {
-                        let peer_list = get_random_peers(
+                    let threshold = self
+                        .config
+                        .opportunistic_graft_threshold()
+                        .unwrap_or(thresholds.opportunistic_graft_threshold);
+                    if median < threshold {
+                        let peer_list = get_random_mesh_peers(
                             topic_peers,
                             &self.connected_peers,
                             topic_hash,
-                            self.config.opportunistic_graft_peers(),
+                            mesh_params.opportunistic_graft_peers,
+                            relayed_mesh_policy,
                             |peer_id| {
                                 !peers.contains(peer_id)
                                     && !explicit_peers.contains(peer_id)
+                                    && !stale_peers.contains(peer_id)
                                     && !backoffs.is_backoff_with_slack(topic_hash, peer_id)
                                     && *scores.get(peer_id).unwrap_or(&0.0) > median
                             },
@@ -2271,6 +4534,13 @@ where
                         if let Some(m) = self.metrics.as_mut() {
                             m.peers_included(topic_hash, Inclusion::Random, peer_list.len())
                         }
+                        mesh_peers_added(
+                            &self.config,
+                            &mut self.events,
+                            topic_hash,
+                            &peer_list,
+                            MeshJoinReason::Opportunistic,
+                        );
                         peers.extend(peer_list);
                     }
                 }
@@ -2301,6 +4571,7 @@ where
         // maintain fanout
         // check if our peers are still a part of the topic
         for (topic_hash, peers) in self.fanout.iter_mut() {
+            let mesh_n = self.mesh_params.get(topic_hash).mesh_n;
             let mut to_remove_peers = Vec::new();
             let publish_threshold = match &self.peer_score {
                 Some((_, thresholds, _, _)) => thresholds.publish_threshold,
@@ -2330,13 +4601,13 @@ where
             }
 
             // not enough peers
-            if peers.len() < self.config.mesh_n() {
+            if peers.len() < mesh_n {
                 tracing::debug!(
                     "HEARTBEAT: Fanout low. Contains: {:?} needs: {:?}",
                     peers.len(),
-                    self.config.mesh_n()
+                    mesh_n
                 );
-                let needed_peers = self.config.mesh_n() - peers.len();
+                let needed_peers = mesh_n - peers.len();
                 let explicit_peers = &self.explicit_peers;
                 let new_peers = get_random_peers(
                     &self.topic_peers,
@@ -2380,6 +4651,7 @@ where
             })
         }
 
+        self.update_gossip_factors();
         self.emit_gossip();
 
         // send graft/prunes
@@ -2392,18 +4664,86 @@ where
 
         // shift the memcache
         self.mcache.shift();
+        self.mcache.remove_expired(SystemTime::now());
+
+        if let Some(reassembly) = self.chunk_reassembly.as_mut() {
+            let expired = reassembly.remove_expired(Instant::now());
+            if expired > 0 {
+                tracing::debug!(%expired, "Dropped chunked messages missing chunks");
+            }
+        }
+        // Forget the chunked messages whose chunks left the message cache before validation.
+        let mcache = &self.mcache;
+        self.chunk_validations
+            .retain(|_, chunks| chunks.iter().any(|(id, _)| mcache.contains(id)));
+
+        // Complete the topic teardowns which timed out.
+        let now = Instant::now();
+        let drain_timeout = self.config.unsubscribe_drain_timeout();
+        let events = &mut self.events;
+        self.topic_drains.retain(|topic, (_, started)| {
+            if now.duration_since(*started) < drain_timeout {
+                return true;
+            }
+            tracing::debug!(%topic, "Topic teardown timed out");
+            events.push_back(ToSwarm::GenerateEvent(Event::UnsubscribeCompleted {
+                topic: topic.clone(),
+                timed_out: true,
+            }));
+            false
+        });
+
+        // Report the deliveries which timed out.
+        let events = &mut self.events;
+        self.pending_deliveries.retain(|message_id, delivery| {
+            let Some((outcome, peers)) = delivery.outcome(now) else {
+                return true;
+            };
+            events.push_back(ToSwarm::GenerateEvent(Event::DeliveryReport {
+                message_id: message_id.clone(),
+                outcome,
+                peers,
+            }));
+            false
+        });
 
         tracing::debug!("Completed Heartbeat");
         if let Some(metrics) = self.metrics.as_mut() {
             let duration = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
             metrics.observe_heartbeat_duration(duration);
+            metrics.expire_iwants(self.config.iwant_followup_time());
         }
     }
 
     /// Emits gossip - Send IHAVE messages to a random set of gossip peers. This is applied to mesh
     /// and fanout peers
+    /// Adapts the gossip factor of the topics to the redundancy of the messages received since the
+    /// last heartbeat, if [`Config::adaptive_gossip_factor`] is set.
+    fn update_gossip_factors(&mut self) {
+        let Some(bounds) = self.config.adaptive_gossip_factor() else {
+            return;
+        };
+        for (topic_hash, (messages, duplicates)) in self.topic_deliveries.drain() {
+            if messages == 0 {
+                continue;
+            }
+            let factor = self
+                .gossip_factors
+                .entry(topic_hash)
+                .or_insert(self.config.gossip_factor());
+            let duplicates_per_message = duplicates as f64 / messages as f64;
+            if duplicates_per_message < UNHEALTHY_DUPLICATES_PER_MESSAGE {
+                *factor += GOSSIP_FACTOR_STEP;
+            } else if duplicates_per_message > HEALTHY_DUPLICATES_PER_MESSAGE {
+                *factor -= GOSSIP_FACTOR_STEP;
+            }
+            *factor = factor.max(*bounds.start()).min(*bounds.end());
+        }
+    }
+
     fn emit_gossip(&mut self) {
         let mut rng = thread_rng();
+        let mut count_sent_ihave = HashMap::<PeerId, usize>::new();
         for (topic_hash, peers) in self.mesh.iter().chain(self.fanout.iter()) {
             let mut message_ids = self.mcache.get_gossip_message_ids(topic_hash);
             if message_ids.is_empty() {
@@ -2423,10 +4763,11 @@ where
             }
 
             // dynamic number of peers to gossip based on `gossip_factor` with minimum `gossip_lazy`
+            let gossip_factor = self.gossip_factor(topic_hash);
             let n_map = |m| {
                 max(
-                    self.config.gossip_lazy(),
-                    (self.config.gossip_factor() * m as f64) as usize,
+                    self.mesh_params.get(topic_hash).gossip_lazy,
+                    (gossip_factor * m as f64) as usize,
                 )
             };
             // get gossip_lazy random peers
@@ -2445,6 +4786,16 @@ where
             tracing::debug!("Gossiping IHAVE to {} peers", to_msg_peers.len());
 
             for peer in to_msg_peers {
+                let sent_ihave = count_sent_ihave.entry(peer).or_default();
+                if *sent_ihave >= self.config.max_sent_ihave_messages() {
+                    tracing::debug!(
+                        peer=%peer,
+                        "IHAVE: already advertised the maximum of messages to peer"
+                    );
+                    continue;
+                }
+                *sent_ihave += 1;
+
                 let mut peer_message_ids = message_ids.clone();
 
                 if peer_message_ids.len() > self.config.max_ihave_length() {
@@ -2468,6 +4819,43 @@ where
         }
     }
 
+    /// Chokes mesh peers persistently delivering duplicates and unchokes choked peers announcing
+    /// messages faster than unchoked peers deliver them.
+    fn update_choked_peers(&mut self) {
+        let mut decisions = Vec::new();
+        for (topic_hash, peers) in &self.mesh {
+            let decision = self.choking.evaluate(
+                topic_hash,
+                peers,
+                self.config.choke_duplicate_ratio(),
+                self.config.choke_min_deliveries(),
+                self.config.mesh_unchoked_min(),
+            );
+            decisions.push((topic_hash.clone(), decision));
+        }
+
+        for (topic_hash, decision) in decisions {
+            for peer_id in decision.unchoke {
+                tracing::debug!(peer=%peer_id, topic=%topic_hash, "HEARTBEAT: Unchoke peer");
+                self.send_message(
+                    peer_id,
+                    RpcOut::Control(ControlAction::Unchoke {
+                        topic_hash: topic_hash.clone(),
+                    }),
+                );
+            }
+            for peer_id in decision.choke {
+                tracing::debug!(peer=%peer_id, topic=%topic_hash, "HEARTBEAT: Choke peer");
+                self.send_message(
+                    peer_id,
+                    RpcOut::Control(ControlAction::Choke {
+                        topic_hash: topic_hash.clone(),
+                    }),
+                );
+            }
+        }
+    }
+
     /// Handles multiple GRAFT/PRUNE messages and coalesces them into chunked gossip control
     /// messages.
     fn send_graft_prune(
@@ -2483,6 +4871,12 @@ where
                 if let Some((peer_score, ..)) = &mut self.peer_score {
                     peer_score.graft(&peer, topic.clone());
                 }
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.trace(TraceEvent::Graft {
+                        peer_id: peer,
+                        topic: topic.clone(),
+                    });
+                }
 
                 // inform the handler of the peer being added to the mesh
                 // If the peer did not previously exist in any mesh, inform the handler
@@ -2510,6 +4904,12 @@ where
                 .into_iter()
                 .flatten()
                 .map(|topic_hash| {
+                    if let Some(tracer) = &mut self.tracer {
+                        tracer.trace(TraceEvent::Prune {
+                            peer_id: peer,
+                            topic: topic_hash.clone(),
+                        });
+                    }
                     self.make_prune(
                         &topic_hash,
                         &peer,
@@ -2535,6 +4935,12 @@ where
                     false,
                 );
                 self.send_message(*peer, RpcOut::Control(prune));
+                if let Some(tracer) = &mut self.tracer {
+                    tracer.trace(TraceEvent::Prune {
+                        peer_id: *peer,
+                        topic: topic_hash.clone(),
+                    });
+                }
 
                 // inform the handler
                 peer_removed_from_mesh(
@@ -2568,6 +4974,7 @@ where
         }
 
         tracing::debug!(message=%msg_id, "Forwarding message");
+        self.retain_for_explicit_peers(&message, propagation_source);
         let mut recipient_peers = HashSet::new();
 
         {
@@ -2601,6 +5008,44 @@ where
             }
         }
 
+        // withhold the message from peers that told us they don't want it
+        recipient_peers.retain(|peer_id| {
+            let withheld = self
+                .connected_peers
+                .get(peer_id)
+                .is_some_and(|peer| peer.dont_send.contains_key(msg_id));
+            if withheld {
+                tracing::debug!(peer=%peer_id, message=%msg_id, "Peer doesn't want message");
+                if let Some(metrics) = self.metrics.as_mut() {
+                    metrics.register_msg_withheld(&message.topic);
+                }
+            }
+            !withheld
+        });
+
+        // only announce the message to mesh peers that choked us or can't keep up with their
+        // outbound queue
+        let choked_by = recipient_peers
+            .iter()
+            .filter(|peer_id| {
+                !self.explicit_peers.contains(*peer_id)
+                    && (self.choking.is_choked_by(&message.topic, peer_id)
+                        || self.gossip_only_peers.contains(*peer_id))
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        for peer_id in choked_by {
+            recipient_peers.remove(&peer_id);
+            tracing::debug!(peer=%peer_id, message=%msg_id, "Announcing message to choking peer");
+            self.send_message(
+                peer_id,
+                RpcOut::Control(ControlAction::IHave {
+                    topic_hash: message.topic.clone(),
+                    message_ids: vec![msg_id.clone()],
+                }),
+            );
+        }
+
         // forward the message to peers
         if !recipient_peers.is_empty() {
             let event = RpcOut::Forward(message.clone());
@@ -2621,6 +5066,7 @@ where
         &mut self,
         topic: TopicHash,
         data: Vec<u8>,
+        chunk: Option<Chunk>,
     ) -> Result<RawMessage, PublishError> {
         match &mut self.publish_config {
             PublishConfig::Signing {
@@ -2639,6 +5085,8 @@ where
                         topic: topic.clone().into_string(),
                         signature: None,
                         key: None,
+                        expires_at: None,
+                        chunk: chunk.clone().map(Into::into),
                     };
 
                     let mut buf = Vec::with_capacity(message.get_size());
@@ -2664,6 +5112,8 @@ where
                     signature,
                     key: inline_key.clone(),
                     validated: true, // all published messages are valid
+                    expires_at: None,
+                    chunk,
                 })
             }
             PublishConfig::Author(peer_id) => {
@@ -2677,6 +5127,8 @@ where
                     signature: None,
                     key: None,
                     validated: true, // all published messages are valid
+                    expires_at: None,
+                    chunk,
                 })
             }
             PublishConfig::RandomAuthor => {
@@ -2690,6 +5142,8 @@ where
                     signature: None,
                     key: None,
                     validated: true, // all published messages are valid
+                    expires_at: None,
+                    chunk,
                 })
             }
             PublishConfig::Anonymous => {
@@ -2703,6 +5157,8 @@ where
                     signature: None,
                     key: None,
                     validated: true, // all published messages are valid
+                    expires_at: None,
+                    chunk,
                 })
             }
         }
@@ -2729,6 +5185,164 @@ where
         self.pending_iwant_msgs.clear();
     }
 
+    /// Reports peers whose outbound queue stayed saturated for the configured number of
+    /// heartbeats.
+    fn check_saturated_queues(
+        &mut self,
+        to_prune: &mut HashMap<PeerId, Vec<TopicHash>>,
+        no_px: &mut HashSet<PeerId>,
+    ) {
+        // Forget about peers whose connection was denied after its handler was created.
+        let connected_peers = &self.connected_peers;
+        self.outbound_queue_lens
+            .retain(|peer_id, _| connected_peers.contains_key(peer_id));
+
+        let Some(saturated_len) = self.config.saturated_queue_len() else {
+            return;
+        };
+        let heartbeats = self.config.saturated_queue_heartbeats();
+
+        let mut slow_peers = Vec::new();
+        for (peer_id, queue_len) in &self.outbound_queue_lens {
+            let queue_len = queue_len.load(AtomicOrdering::Relaxed);
+            if queue_len < saturated_len {
+                self.saturated_queues.remove(peer_id);
+                self.gossip_only_peers.remove(peer_id);
+                continue;
+            }
+
+            let count = self.saturated_queues.entry(*peer_id).or_default();
+            *count += 1;
+            if *count >= heartbeats {
+                *count = 0;
+                tracing::debug!(peer=%peer_id, %queue_len, "Outbound queue of peer is saturated");
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::OutboundQueueSaturated {
+                        peer_id: *peer_id,
+                        queue_len,
+                    }));
+                slow_peers.push(*peer_id);
+            }
+        }
+
+        let policy = self.config.slow_peer_policy();
+        for peer_id in slow_peers {
+            if let Some(m) = self.metrics.as_mut() {
+                m.register_slow_peer_action(policy);
+            }
+            match policy {
+                SlowPeerPolicy::Report => {}
+                SlowPeerPolicy::Prune => {
+                    if self.explicit_peers.contains(&peer_id) {
+                        continue;
+                    }
+                    for (topic_hash, peers) in self.mesh.iter_mut() {
+                        if !peers.remove(&peer_id) {
+                            continue;
+                        }
+                        tracing::debug!(
+                            peer=%peer_id,
+                            topic=%topic_hash,
+                            "HEARTBEAT: Prune peer with saturated outbound queue"
+                        );
+                        // Back off right away so that the mesh maintenance doesn't graft the
+                        // peer again.
+                        self.backoffs.update_backoff(
+                            topic_hash,
+                            &peer_id,
+                            self.config.prune_backoff(),
+                        );
+                        to_prune
+                            .entry(peer_id)
+                            .or_default()
+                            .push(topic_hash.clone());
+                        no_px.insert(peer_id);
+                        if let Some(m) = self.metrics.as_mut() {
+                            m.peers_removed(topic_hash, Churn::SlowPeer, 1)
+                        }
+                        mesh_peers_removed(
+                            &self.config,
+                            &mut self.events,
+                            topic_hash,
+                            [&peer_id],
+                            MeshLeaveReason::SlowPeer,
+                        );
+                    }
+                }
+                SlowPeerPolicy::GossipOnly => {
+                    if self.gossip_only_peers.insert(peer_id) {
+                        tracing::debug!(peer=%peer_id, "Only announcing messages to slow peer");
+                    }
+                }
+                SlowPeerPolicy::Disconnect => {
+                    tracing::debug!(peer=%peer_id, "Disconnecting slow peer");
+                    self.events.push_back(ToSwarm::CloseConnection {
+                        peer_id,
+                        connection: CloseConnection::All,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Reports a peer whose outbound queue crossed the high or low watermark.
+    fn check_queue_watermarks(&mut self, peer_id: &PeerId) {
+        let Some(high) = self.config.outbound_queue_high_watermark() else {
+            return;
+        };
+        let Some(queue_len) = self.outbound_queue_len(peer_id) else {
+            return;
+        };
+
+        if queue_len >= high {
+            if self.congested_queues.insert(*peer_id) {
+                tracing::debug!(peer=%peer_id, %queue_len, "Outbound queue of peer is congested");
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::OutboundQueueHigh {
+                        peer_id: *peer_id,
+                        queue_len,
+                    }));
+            }
+        } else if queue_len <= self.config.outbound_queue_low_watermark()
+            && self.congested_queues.remove(peer_id)
+        {
+            tracing::debug!(peer=%peer_id, %queue_len, "Outbound queue of peer drained");
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::OutboundQueueLow {
+                    peer_id: *peer_id,
+                    queue_len,
+                }));
+        }
+    }
+
+    /// Builds the handler of a new connection, accounting its send queue in `queue_len`.
+    fn new_handler(&self, queue_len: Arc<AtomicUsize>) -> Handler {
+        let batch_size = self
+            .config
+            .rpc_batch_size()
+            .map(|size| size.min(self.config.max_transmit_size()));
+        Handler::new(
+            self.config.protocol_config(),
+            queue_len,
+            batch_size,
+            self.config.rpc_batch_delay(),
+        )
+    }
+
+    /// Emits the score of all connected peers as an [`Event::ScoreReport`].
+    fn report_scores(&mut self) {
+        let Some((peer_score, ..)) = &self.peer_score else {
+            return;
+        };
+        let scores = self
+            .connected_peers
+            .keys()
+            .map(|peer_id| (*peer_id, peer_score.score_breakdown(peer_id)))
+            .collect();
+        self.events
+            .push_back(ToSwarm::GenerateEvent(Event::ScoreReport { scores }));
+    }
+
     /// Send a [`RpcOut`] message to a peer. This will wrap the message in an arc if it
     /// is not already an arc.
     fn send_message(&mut self, peer_id: PeerId, rpc: RpcOut) {
@@ -2739,6 +5353,8 @@ where
             }
         }
 
+        self.check_queue_watermarks(&peer_id);
+
         self.events.push_back(ToSwarm::NotifyHandler {
             peer_id,
             event: HandlerIn::Message(rpc),
@@ -2746,6 +5362,61 @@ where
         });
     }
 
+    /// Sends a message we publish to a peer, the handler reporting whether it was written to the
+    /// peer.
+    fn send_reported_message(
+        &mut self,
+        peer_id: PeerId,
+        message_id: MessageId,
+        message: RawMessage,
+    ) {
+        if let Some(m) = self.metrics.as_mut() {
+            m.msg_sent(&message.topic, message.raw_protobuf_len());
+        }
+
+        self.check_queue_watermarks(&peer_id);
+
+        self.events.push_back(ToSwarm::NotifyHandler {
+            peer_id,
+            event: HandlerIn::ReportedMessage {
+                message,
+                message_id,
+            },
+            handler: NotifyHandler::Any,
+        });
+    }
+
+    /// Records that the given messages published via [`Behaviour::publish_with_delivery_report`]
+    /// were written to, or dropped for, the given peer, and reports the deliveries whose outcome
+    /// is known.
+    fn on_messages_delivered(
+        &mut self,
+        peer_id: PeerId,
+        message_ids: impl IntoIterator<Item = MessageId>,
+        sent: bool,
+    ) {
+        let now = Instant::now();
+        for message_id in message_ids {
+            let Some(delivery) = self.pending_deliveries.get_mut(&message_id) else {
+                continue;
+            };
+            if sent {
+                delivery.on_sent(peer_id);
+            } else {
+                delivery.on_dropped(&peer_id);
+            }
+            if let Some((outcome, peers)) = delivery.outcome(now) {
+                self.pending_deliveries.remove(&message_id);
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::DeliveryReport {
+                        message_id,
+                        outcome,
+                        peers,
+                    }));
+            }
+        }
+    }
+
     fn on_connection_established(
         &mut self,
         ConnectionEstablished {
@@ -2783,14 +5454,29 @@ where
         // The protocol negotiation occurs once a message is sent/received. Once this happens we
         // update the type of peer that this is in order to determine which kind of routing should
         // occur.
-        self.connected_peers
+        let connections = self
+            .connected_peers
             .entry(peer_id)
             .or_insert(PeerConnections {
                 kind: PeerKind::Floodsub,
                 connections: vec![],
-            })
-            .connections
-            .push(connection_id);
+                dont_send: HashMap::new(),
+                relayed: vec![],
+                metered: false,
+            });
+        connections.connections.push(connection_id);
+        if endpoint.is_relayed() {
+            connections.relayed.push(connection_id);
+        }
+
+        // Keep the connections to explicit peers alive.
+        if self.explicit_peers.contains(&peer_id) {
+            self.events.push_back(ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Explicit(true),
+                handler: NotifyHandler::One(connection_id),
+            });
+        }
 
         if other_established > 0 {
             return; // Not our first connection to this peer, hence nothing to do.
@@ -2814,6 +5500,27 @@ where
         for topic_hash in self.mesh.clone().into_keys() {
             self.send_message(peer_id, RpcOut::Subscribe(topic_hash));
         }
+
+        // Replay the messages an explicit peer missed while it was disconnected.
+        if let (Some(backlog), Some(window)) = (
+            self.explicit_peer_backlogs.remove(&peer_id),
+            self.config.explicit_peer_replay_window(),
+        ) {
+            let messages = backlog
+                .messages
+                .into_iter()
+                .filter(|(retained, _)| retained.elapsed() <= window)
+                .map(|(_, message)| message)
+                .collect::<Vec<_>>();
+            tracing::debug!(
+                peer=%peer_id,
+                "Replaying {} message(s) to reconnected explicit peer",
+                messages.len()
+            );
+            for message in messages {
+                self.send_message(peer_id, RpcOut::Forward(message));
+            }
+        }
     }
 
     fn on_connection_closed(
@@ -2826,6 +5533,12 @@ where
             ..
         }: ConnectionClosed,
     ) {
+        // The closed connection no longer holds back any topic teardown.
+        let draining = self.topic_drains.keys().cloned().collect::<Vec<_>>();
+        for topic in draining {
+            self.on_topic_drained(&topic, peer_id, connection_id);
+        }
+
         // Remove IP from peer scoring system
         if let Some((peer_score, ..)) = &mut self.peer_score {
             if let Some(ip) = get_ip_addr(endpoint.get_remote_address()) {
@@ -2848,6 +5561,7 @@ where
                     .position(|v| v == &connection_id)
                     .expect("Previously established connection to peer must be present");
                 connections.connections.remove(index);
+                connections.relayed.retain(|c| *c != connection_id);
 
                 // If there are more connections and this peer is in a mesh, inform the first connection
                 // handler.
@@ -2869,6 +5583,35 @@ where
                 }
             }
         } else {
+            self.outbound_queue_lens.remove(&peer_id);
+            self.saturated_queues.remove(&peer_id);
+            self.gossip_only_peers.remove(&peer_id);
+            self.congested_queues.remove(&peer_id);
+            self.choking.remove_peer(&peer_id);
+
+            // Messages still queued for the peer are lost.
+            let undelivered = self
+                .pending_deliveries
+                .iter()
+                .filter(|(_, delivery)| delivery.is_pending_for(&peer_id))
+                .map(|(message_id, _)| message_id.clone())
+                .collect::<Vec<_>>();
+            self.on_messages_delivered(peer_id, undelivered, false);
+
+            // Retain messages for explicit peers until they reconnect.
+            if self.config.explicit_peer_replay_window().is_some()
+                && self.explicit_peers.contains(&peer_id)
+            {
+                let topics = self.peer_topics.get(&peer_id).cloned().unwrap_or_default();
+                self.explicit_peer_backlogs.insert(
+                    peer_id,
+                    ExplicitPeerBacklog {
+                        topics,
+                        messages: VecDeque::new(),
+                    },
+                );
+            }
+
             // remove from mesh, topic_peers, peer_topic and the fanout
             tracing::debug!(peer=%peer_id, "Peer disconnected");
             {
@@ -2881,6 +5624,7 @@ where
                 };
 
                 // remove peer from all mappings
+                let mut mesh_topics = HashSet::new();
                 for topic in topics {
                     // check the mesh for the topic
                     if let Some(mesh_peers) = self.mesh.get_mut(topic) {
@@ -2890,6 +5634,14 @@ where
                                 m.peers_removed(topic, Churn::Dc, 1);
                                 m.set_mesh_peers(topic, mesh_peers.len());
                             }
+                            mesh_peers_removed(
+                                &self.config,
+                                &mut self.events,
+                                topic,
+                                [&peer_id],
+                                MeshLeaveReason::Disconnected,
+                            );
+                            mesh_topics.insert(topic.clone());
                         };
                     }
 
@@ -2918,16 +5670,30 @@ where
                         .get_mut(topic)
                         .map(|peers| peers.remove(&peer_id));
                 }
+
+                // remember the meshes of the peer in case it reconnects
+                if !mesh_topics.is_empty() && self.config.mesh_reconnect_window().is_some() {
+                    self.disconnected_mesh_peers
+                        .insert(peer_id, (Instant::now(), mesh_topics));
+                }
             }
 
             // Forget px and outbound status for this peer
             self.px_peers.remove(&peer_id);
             self.outbound_peers.remove(&peer_id);
+            self.signed_peer_records.remove(&peer_id);
+            self.control_token_buckets.remove(&peer_id);
 
             // Remove peer from peer_topics and connected_peers
             // NOTE: It is possible the peer has already been removed from all mappings if it does not
             // support the protocol.
             self.peer_topics.remove(&peer_id);
+            self.filtered_subscriptions.remove(&peer_id);
+            if let Some(topics) = self.rejected_subscriptions.get_mut(&peer_id) {
+                topics
+                    .values_mut()
+                    .for_each(|subscribed| *subscribed = false);
+            }
 
             // If metrics are enabled, register the disconnection of a peer based on its protocol.
             if let Some(metrics) = self.metrics.as_mut() {
@@ -2947,6 +5713,40 @@ where
         }
     }
 
+    /// Prunes the peer from all meshes before the swarm closes the connections to it, such that
+    /// the peer does not have to wait for the connections to close to look for other mesh peers.
+    fn on_disconnect_requested(
+        &mut self,
+        DisconnectRequested { peer_id, .. }: DisconnectRequested,
+    ) {
+        let mut topics = Vec::new();
+        for (topic_hash, peers) in self.mesh.iter_mut() {
+            if peers.remove(&peer_id) {
+                if let Some(m) = self.metrics.as_mut() {
+                    m.peers_removed(topic_hash, Churn::Dc, 1)
+                }
+                mesh_peers_removed(
+                    &self.config,
+                    &mut self.events,
+                    topic_hash,
+                    [&peer_id],
+                    MeshLeaveReason::Disconnected,
+                );
+                topics.push(topic_hash.clone());
+            }
+        }
+        if topics.is_empty() {
+            return;
+        }
+
+        tracing::debug!(peer=%peer_id, "Pruning peer about to be disconnected");
+        self.send_graft_prune(
+            HashMap::new(),
+            HashMap::from([(peer_id, topics)]),
+            HashSet::new(),
+        );
+    }
+
     fn on_address_change(
         &mut self,
         AddressChange {
@@ -2999,30 +5799,35 @@ where
     fn handle_established_inbound_connection(
         &mut self,
         _: ConnectionId,
-        _: PeerId,
+        peer_id: PeerId,
         _: &Multiaddr,
         _: &Multiaddr,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        Ok(Handler::new(self.config.protocol_config()))
+        let queue_len = self.outbound_queue_lens.entry(peer_id).or_default().clone();
+        Ok(self.new_handler(queue_len))
     }
 
     fn handle_established_outbound_connection(
         &mut self,
         _: ConnectionId,
-        _: PeerId,
+        peer_id: PeerId,
         _: &Multiaddr,
         _: Endpoint,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        Ok(Handler::new(self.config.protocol_config()))
+        let queue_len = self.outbound_queue_lens.entry(peer_id).or_default().clone();
+        Ok(self.new_handler(queue_len))
     }
 
     fn on_connection_handler_event(
         &mut self,
         propagation_source: PeerId,
-        _connection_id: ConnectionId,
+        connection_id: ConnectionId,
         handler_event: THandlerOutEvent<Self>,
     ) {
         match handler_event {
+            HandlerEvent::TopicDrained(topic) => {
+                self.on_topic_drained(&topic, propagation_source, connection_id);
+            }
             HandlerEvent::PeerKind(kind) => {
                 // We have identified the protocol this peer is using
 
@@ -3053,6 +5858,12 @@ where
                     }
                 }
             }
+            HandlerEvent::MessagesSent(message_ids) => {
+                self.on_messages_delivered(propagation_source, message_ids, true);
+            }
+            HandlerEvent::MessagesDropped(message_ids) => {
+                self.on_messages_delivered(propagation_source, message_ids, false);
+            }
             HandlerEvent::Message {
                 rpc,
                 invalid_messages,
@@ -3065,9 +5876,12 @@ where
                     self.handle_received_subscriptions(&rpc.subscriptions, &propagation_source);
                 }
 
-                // Check if peer is graylisted in which case we ignore the event
-                if let (true, _) =
-                    self.score_below_threshold(&propagation_source, |pst| pst.graylist_threshold)
+                // Check if peer is graylisted in which case we ignore the event. Explicit peers are
+                // never graylisted.
+                if !self.explicit_peers.contains(&propagation_source)
+                    && self
+                        .score_below_threshold(&propagation_source, |pst| pst.graylist_threshold)
+                        .0
                 {
                     tracing::debug!(peer=%propagation_source, "RPC Dropped from greylisted peer");
                     return;
@@ -3111,7 +5925,16 @@ where
                 let mut ihave_msgs = vec![];
                 let mut graft_msgs = vec![];
                 let mut prune_msgs = vec![];
+                let now = Instant::now();
                 for control_msg in rpc.control_msgs {
+                    if !self.take_control_token(&propagation_source, now) {
+                        tracing::debug!(
+                            peer=%propagation_source,
+                            "Peer exceeded its control message budget; dropping control messages"
+                        );
+                        self.budget_exceeded(&propagation_source, ControlBudget::ControlMessages);
+                        break;
+                    }
                     match control_msg {
                         ControlAction::IHave {
                             topic_hash,
@@ -3128,6 +5951,18 @@ where
                             peers,
                             backoff,
                         } => prune_msgs.push((topic_hash, peers, backoff)),
+                        ControlAction::IDontWant { message_ids } => {
+                            self.handle_idontwant(&propagation_source, message_ids)
+                        }
+                        ControlAction::Choke { topic_hash } => {
+                            self.handle_choke(&propagation_source, topic_hash, true)
+                        }
+                        ControlAction::Unchoke { topic_hash } => {
+                            self.handle_choke(&propagation_source, topic_hash, false)
+                        }
+                        report @ ControlAction::Invalid { .. } => {
+                            self.handle_invalid_report(&propagation_source, report)
+                        }
                     }
                 }
                 if !ihave_msgs.is_empty() {
@@ -3163,6 +5998,18 @@ where
             self.heartbeat();
         }
 
+        self.poll_px_dials(cx);
+
+        while let Poll::Ready(Some((msg_id, propagation_source, message, acceptance))) =
+            self.pending_validations.poll_next_unpin(cx)
+        {
+            self.on_validation_done(msg_id, propagation_source, message, acceptance);
+        }
+
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(event);
+        }
+
         Poll::Pending
     }
 
@@ -3175,11 +6022,83 @@ where
                 self.on_connection_closed(connection_closed)
             }
             FromSwarm::AddressChange(address_change) => self.on_address_change(address_change),
+            FromSwarm::DisconnectRequested(disconnect_requested) => {
+                self.on_disconnect_requested(disconnect_requested)
+            }
             _ => {}
         }
     }
 }
 
+/// Emits an [`Event::MeshPeerAdded`] for each of the given peers, if enabled by
+/// [`Config::mesh_events`].
+fn mesh_peers_added<'a>(
+    config: &Config,
+    events: &mut impl Extend<ToSwarm<Event, HandlerIn>>,
+    topic_hash: &TopicHash,
+    peers: impl IntoIterator<Item = &'a PeerId>,
+    reason: MeshJoinReason,
+) {
+    if config.mesh_events() {
+        events.extend(peers.into_iter().map(|peer_id| {
+            ToSwarm::GenerateEvent(Event::MeshPeerAdded {
+                peer_id: *peer_id,
+                topic: topic_hash.clone(),
+                reason,
+            })
+        }));
+    }
+}
+
+/// Emits an [`Event::MeshPeerRemoved`] for each of the given peers, if enabled by
+/// [`Config::mesh_events`].
+fn mesh_peers_removed<'a>(
+    config: &Config,
+    events: &mut impl Extend<ToSwarm<Event, HandlerIn>>,
+    topic_hash: &TopicHash,
+    peers: impl IntoIterator<Item = &'a PeerId>,
+    reason: MeshLeaveReason,
+) {
+    if config.mesh_events() {
+        events.extend(peers.into_iter().map(|peer_id| {
+            ToSwarm::GenerateEvent(Event::MeshPeerRemoved {
+                peer_id: *peer_id,
+                topic: topic_hash.clone(),
+                reason,
+            })
+        }));
+    }
+}
+
+/// Decodes a signed peer record received for the given peer, returning `None` if it is invalid or
+/// not signed by that peer.
+fn decode_signed_peer_record(peer_id: &PeerId, bytes: &[u8]) -> Option<PeerRecord> {
+    let envelope = SignedEnvelope::from_protobuf_encoding(bytes).ok()?;
+    let record = PeerRecord::from_signed_envelope(envelope).ok()?;
+    (record.peer_id() == *peer_id).then_some(record)
+}
+
+/// The mesh maintenance parameters of the [`Config`] and those overridden per topic.
+struct MeshParams {
+    default: TopicMeshParams,
+    topics: HashMap<TopicHash, TopicMeshParams>,
+}
+
+impl MeshParams {
+    /// Returns the parameters applying to a topic.
+    fn get(&self, topic_hash: &TopicHash) -> &TopicMeshParams {
+        self.topics.get(topic_hash).unwrap_or(&self.default)
+    }
+}
+
+/// The messages retained for a disconnected explicit peer.
+struct ExplicitPeerBacklog {
+    /// The topics the peer was subscribed to when it disconnected.
+    topics: BTreeSet<TopicHash>,
+    /// The retained messages, together with the time they were retained.
+    messages: VecDeque<(Instant, RawMessage)>,
+}
+
 /// This is called when peers are added to any mesh. It checks if the peer existed
 /// in any other mesh. If this is the first mesh they have joined, it queues a message to notify
 /// the appropriate connection handler to maintain a connection.
@@ -3280,6 +6199,7 @@ fn get_random_peers_dynamic(
                 f(p) && match connected_peers.get(p) {
                     Some(connections) if connections.kind == PeerKind::Gossipsub => true,
                     Some(connections) if connections.kind == PeerKind::Gossipsubv1_1 => true,
+                    Some(connections) if connections.kind == PeerKind::Gossipsubv1_2 => true,
                     _ => false,
                 }
             })
@@ -3315,6 +6235,48 @@ fn get_random_peers(
     get_random_peers_dynamic(topic_peers, connected_peers, topic_hash, |_| n, f)
 }
 
+/// Helper function to get a set of `n` random gossipsub peers to graft to the mesh of a
+/// `topic_hash`, filtered by the function `f` and the [`RelayedMeshPolicy`].
+fn get_random_mesh_peers(
+    topic_peers: &HashMap<TopicHash, BTreeSet<PeerId>>,
+    connected_peers: &HashMap<PeerId, PeerConnections>,
+    topic_hash: &TopicHash,
+    n: usize,
+    policy: RelayedMeshPolicy,
+    mut f: impl FnMut(&PeerId) -> bool,
+) -> BTreeSet<PeerId> {
+    let is_relayed = |peer: &PeerId| {
+        connected_peers
+            .get(peer)
+            .is_some_and(|connections| connections.is_relayed())
+    };
+    match policy {
+        RelayedMeshPolicy::Allow => {
+            get_random_peers(topic_peers, connected_peers, topic_hash, n, f)
+        }
+        RelayedMeshPolicy::Forbid => {
+            get_random_peers(topic_peers, connected_peers, topic_hash, n, |peer| {
+                !is_relayed(peer) && f(peer)
+            })
+        }
+        RelayedMeshPolicy::PreferDirect => {
+            let mut peers = get_random_peers(topic_peers, connected_peers, topic_hash, n, |peer| {
+                !is_relayed(peer) && f(peer)
+            });
+            if peers.len() < n {
+                peers.extend(get_random_peers(
+                    topic_peers,
+                    connected_peers,
+                    topic_hash,
+                    n - peers.len(),
+                    |peer| is_relayed(peer) && f(peer),
+                ));
+            }
+            peers
+        }
+    }
+}
+
 /// Validates the combination of signing, privacy and message validation to ensure the
 /// configuration will not reject published messages.
 fn validate_config(
@@ -3393,6 +6355,8 @@ mod local_test {
             signature: None,
             key: None,
             validated: false,
+            expires_at: None,
+            chunk: None,
         }
     }
 
@@ -21,12 +21,15 @@
 // Collection of tests for the gossipsub network behaviour
 
 use super::*;
-use crate::subscription_filter::WhitelistSubscriptionFilter;
+use crate::subscription_filter::{
+    DynamicSubscriptionFilter, TopicRule, WhitelistSubscriptionFilter,
+};
 use crate::{config::ConfigBuilder, types::Rpc, IdentTopic as Topic};
 use async_std::net::Ipv4Addr;
 use byteorder::{BigEndian, ByteOrder};
 use libp2p_core::ConnectedPoint;
 use rand::Rng;
+use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 
 #[derive(Default, Debug)]
@@ -220,6 +223,7 @@ where
         endpoint: &endpoint,
         failed_addresses: &[],
         other_established: 0, // first connection
+        data: &Default::default(),
     }));
     if let Some(kind) = kind {
         gs.on_connection_handler_event(
@@ -268,6 +272,7 @@ where
                 connection_id,
                 endpoint: &fake_endpoint,
                 remaining_established: active_connections,
+                data: &Default::default(),
             }));
         }
     }
@@ -287,6 +292,8 @@ fn proto_to_message(rpc: &proto::RPC) -> Rpc {
             signature: message.signature, // don't inform the application
             key: None,
             validated: false,
+            expires_at: None,
+            chunk: None,
         });
     }
     let mut control_msgs = Vec::new();
@@ -335,11 +342,10 @@ fn proto_to_message(rpc: &proto::RPC) -> Rpc {
                 .filter_map(|info| {
                     info.peer_id
                         .and_then(|id| PeerId::from_bytes(&id).ok())
-                        .map(|peer_id|
-                            //TODO signedPeerRecord, see https://github.com/libp2p/specs/pull/217
-                            PeerInfo {
-                                peer_id: Some(peer_id),
-                            })
+                        .map(|peer_id| PeerInfo {
+                            peer_id: Some(peer_id),
+                            signed_peer_record: info.signed_peer_record,
+                        })
                 })
                 .collect::<Vec<PeerInfo>>();
 
@@ -355,6 +361,16 @@ fn proto_to_message(rpc: &proto::RPC) -> Rpc {
         control_msgs.extend(iwant_msgs);
         control_msgs.extend(graft_msgs);
         control_msgs.extend(prune_msgs);
+
+        control_msgs.extend(rpc_control.idontwant.into_iter().map(|idontwant| {
+            ControlAction::IDontWant {
+                message_ids: idontwant
+                    .message_ids
+                    .into_iter()
+                    .map(MessageId::from)
+                    .collect::<Vec<_>>(),
+            }
+        }));
     }
 
     Rpc {
@@ -480,6 +496,75 @@ fn test_unsubscribe() {
     }
 }
 
+#[test]
+fn test_unsubscribe_with_prune_wait() {
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(5)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .create_network();
+    let mesh_peers = gs.mesh[&topic_hashes[0]].clone();
+    gs.events.clear();
+
+    assert!(gs
+        .unsubscribe_with_prune_wait(&Topic::new("topic1"))
+        .unwrap());
+
+    // The PRUNEs are sent right away, followed by the drain requests.
+    let pruned = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Message(RpcOut::Control(ControlAction::Prune { .. })),
+                ..
+            } => Some(*peer_id),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    assert_eq!(pruned, mesh_peers);
+    let drained = gs
+        .events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ToSwarm::NotifyHandler {
+                    event: HandlerIn::Drain(_),
+                    ..
+                }
+            )
+        })
+        .count();
+    assert_eq!(drained, peers.len());
+    gs.events.clear();
+
+    // The teardown completes once all connections drained the topic.
+    for peer in &peers {
+        gs.on_connection_handler_event(
+            *peer,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::TopicDrained(topic_hashes[0].clone()),
+        );
+    }
+    let completed = gs
+        .events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ToSwarm::GenerateEvent(Event::UnsubscribeCompleted {
+                    timed_out: false,
+                    ..
+                })
+            )
+        })
+        .count();
+    assert_eq!(completed, 1);
+    assert!(gs.topic_drains.is_empty());
+}
+
 #[test]
 /// Test JOIN(topic) functionality.
 fn test_join() {
@@ -564,6 +649,7 @@ fn test_join() {
             },
             failed_addresses: &[],
             other_established: 0,
+            data: &Default::default(),
         }));
 
         // add the new peer to the fanout
@@ -675,6 +761,92 @@ fn test_publish_without_flood_publishing() {
     );
 }
 
+/// Test that a compressing transform is applied between the application and the wire, with
+/// the message id computed on the uncompressed data on both the publishing and receiving node.
+#[test]
+fn test_compressing_transform() {
+    /// Run-length encodes the data of messages.
+    #[derive(Default, Clone)]
+    struct RunLengthTransform;
+
+    impl DataTransform for RunLengthTransform {
+        fn inbound_transform(&self, raw_message: RawMessage) -> Result<Message, std::io::Error> {
+            if raw_message.data.len() % 2 != 0 {
+                return Err(std::io::ErrorKind::InvalidData.into());
+            }
+            let data = raw_message
+                .data
+                .chunks(2)
+                .flat_map(|run| std::iter::repeat(run[1]).take(run[0].into()))
+                .collect();
+            Ok(Message {
+                source: raw_message.source,
+                data,
+                sequence_number: raw_message.sequence_number,
+                topic: raw_message.topic,
+            })
+        }
+
+        fn outbound_transform(
+            &self,
+            _topic: &TopicHash,
+            data: Vec<u8>,
+        ) -> Result<Vec<u8>, std::io::Error> {
+            let mut compressed: Vec<u8> = Vec::new();
+            for byte in data {
+                match compressed.as_mut_slice() {
+                    [.., count, last] if *last == byte && *count < u8::MAX => *count += 1,
+                    _ => compressed.extend([1, byte]),
+                }
+            }
+            Ok(compressed)
+        }
+    }
+
+    let (mut publisher, _, _) =
+        InjectNodes::<RunLengthTransform, AllowAllSubscriptionFilter>::default()
+            .peer_no(5)
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .create_network();
+    let (mut receiver, peers, _) =
+        InjectNodes::<RunLengthTransform, AllowAllSubscriptionFilter>::default()
+            .peer_no(5)
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .create_network();
+
+    let data = [vec![1; 100], vec![2; 50]].concat();
+    let msg_id = publisher.publish(Topic::new("test"), data.clone()).unwrap();
+    let raw_message = publisher
+        .events
+        .iter()
+        .find_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                event: HandlerIn::Message(RpcOut::Publish(message)),
+                ..
+            } => Some(message.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(raw_message.data, vec![100, 1, 50, 2]);
+
+    receiver.handle_received_message(raw_message, &peers[0]);
+    let received = receiver
+        .events
+        .iter()
+        .find_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::Message {
+                message_id,
+                message,
+                ..
+            }) => Some((message_id.clone(), message.data.clone())),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(received, (msg_id, data));
+}
+
 /// Test local node publish to unsubscribed topic
 #[test]
 fn test_fanout() {
@@ -932,6 +1104,9 @@ fn test_get_random_peers() {
                 PeerConnections {
                     kind: PeerKind::Gossipsubv1_1,
                     connections: vec![ConnectionId::new_unchecked(0)],
+                    dont_send: HashMap::new(),
+                    relayed: vec![],
+                    metered: false,
                 },
             )
         })
@@ -1000,6 +1175,8 @@ fn test_handle_iwant_msg_cached() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     // Transform the inbound message
@@ -1055,6 +1232,8 @@ fn test_handle_iwant_msg_cached_shifted() {
             signature: None,
             key: None,
             validated: true,
+            expires_at: None,
+            chunk: None,
         };
 
         // Transform the inbound message
@@ -1358,6 +1537,68 @@ fn test_explicit_peer_gets_connected() {
     );
 }
 
+#[test]
+fn test_mesh_peer_is_added_back_on_reconnect() {
+    let config = ConfigBuilder::default().mesh_events(true).build().unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(10)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .create_network();
+    gs.mesh.insert(
+        topics[0].clone(),
+        peers[..config.mesh_n()].iter().copied().collect(),
+    );
+    gs.events.clear();
+
+    let peer = peers[0];
+    disconnect_peer(&mut gs, &peer);
+    assert!(!gs.mesh[&topics[0]].contains(&peer));
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::MeshPeerRemoved { peer_id, reason: MeshLeaveReason::Disconnected, .. })
+            if *peer_id == peer
+    )));
+
+    // The mesh is not below `mesh_n_low`, hence only the reconnecting mesh peer is added back.
+    let endpoint = ConnectedPoint::Dialer {
+        address: Multiaddr::empty(),
+        role_override: Endpoint::Dialer,
+    };
+    gs.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: peer,
+        connection_id: ConnectionId::new_unchecked(1),
+        endpoint: &endpoint,
+        failed_addresses: &[],
+        other_established: 0,
+        data: &Default::default(),
+    }));
+    gs.on_connection_handler_event(
+        peer,
+        ConnectionId::new_unchecked(1),
+        HandlerEvent::PeerKind(PeerKind::Gossipsubv1_1),
+    );
+    let subscription = vec![Subscription {
+        action: SubscriptionAction::Subscribe,
+        topic_hash: topics[0].clone(),
+    }];
+    gs.handle_received_subscriptions(&subscription, &peer);
+    let other = add_peer(&mut gs, &topics, false, false);
+
+    assert!(gs.mesh[&topics[0]].contains(&peer));
+    assert!(!gs.mesh[&topics[0]].contains(&other));
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::MeshPeerAdded { peer_id, reason: MeshJoinReason::Reconnected, .. })
+            if *peer_id == peer
+    )));
+    let grafts = count_control_msgs(&gs, |peer_id, m| {
+        peer_id == &peer && matches!(m, ControlAction::Graft { .. })
+    });
+    assert_eq!(grafts, 1);
+}
+
 #[test]
 fn test_explicit_peer_reconnects() {
     let config = ConfigBuilder::default()
@@ -1520,6 +1761,8 @@ fn do_forward_messages_to_explicit_peers() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
     gs.handle_received_message(message.clone(), &local_id);
 
@@ -1657,6 +1900,8 @@ fn no_gossip_gets_sent_to_explicit_peers() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     //forward the message
@@ -1759,6 +2004,7 @@ fn test_connect_to_px_peers_on_handle_prune() {
     for _ in 0..config.prune_peers() + 5 {
         px.push(PeerInfo {
             peer_id: Some(PeerId::random()),
+            signed_peer_record: None,
         });
     }
 
@@ -1797,6 +2043,181 @@ fn test_connect_to_px_peers_on_handle_prune() {
     ));
 }
 
+#[test]
+fn test_px_requires_valid_signed_peer_records() {
+    let config = ConfigBuilder::default()
+        .prune_peers(16)
+        .px_require_signed_peer_records(true)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .create_network();
+
+    let address: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+    let signed_record = |key: &Keypair| {
+        PeerRecord::new(key, vec![address.clone()])
+            .unwrap()
+            .into_signed_envelope()
+            .into_protobuf_encoding()
+    };
+    let valid = Keypair::generate_ed25519();
+    let spoofing = Keypair::generate_ed25519();
+    let px = vec![
+        PeerInfo {
+            peer_id: Some(valid.public().to_peer_id()),
+            signed_peer_record: Some(signed_record(&valid)),
+        },
+        // Record signed by another peer than the exchanged one.
+        PeerInfo {
+            peer_id: Some(PeerId::random()),
+            signed_peer_record: Some(signed_record(&spoofing)),
+        },
+        PeerInfo {
+            peer_id: Some(PeerId::random()),
+            signed_peer_record: None,
+        },
+    ];
+
+    gs.handle_prune(
+        &peers[0],
+        vec![(
+            topics[0].clone(),
+            px,
+            Some(config.prune_backoff().as_secs()),
+        )],
+    );
+
+    // Only the peer with a valid record is dialed, and its record is kept for further PX.
+    let dials: Vec<_> = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::Dial { opts } => opts.get_peer_id(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(dials, vec![valid.public().to_peer_id()]);
+    assert_eq!(
+        gs.signed_peer_records[&valid.public().to_peer_id()].addresses(),
+        &[address]
+    );
+}
+
+#[test]
+fn test_px_dial_policy() {
+    let rejected = PeerId::random();
+    let config = ConfigBuilder::default()
+        .prune_peers(16)
+        .px_dials_per_prune(3)
+        .max_px_dials_per_heartbeat(4)
+        .px_dial_filter(move |peer_id, _| *peer_id != rejected)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .create_network();
+
+    let prune = |gs: &mut Behaviour, px: Vec<PeerId>| {
+        let px = px
+            .into_iter()
+            .map(|peer_id| PeerInfo {
+                peer_id: Some(peer_id),
+                signed_peer_record: None,
+            })
+            .collect();
+        gs.handle_prune(
+            &peers[0],
+            vec![(
+                topics[0].clone(),
+                px,
+                Some(config.prune_backoff().as_secs()),
+            )],
+        );
+    };
+    let dials = |gs: &mut Behaviour| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::Dial { opts } => opts.get_peer_id(),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The filtered peer is never dialed, at most `px_dials_per_prune` of the others are.
+    prune(&mut gs, vec![rejected, PeerId::random()]);
+    assert_eq!(dials(&mut gs).len(), 1);
+    prune(&mut gs, (0..5).map(|_| PeerId::random()).collect());
+    assert_eq!(dials(&mut gs).len(), 3);
+
+    // The heartbeat limit is reached.
+    prune(&mut gs, (0..5).map(|_| PeerId::random()).collect());
+    assert!(dials(&mut gs).is_empty());
+
+    gs.heartbeat();
+    dials(&mut gs);
+    prune(&mut gs, (0..5).map(|_| PeerId::random()).collect());
+    assert_eq!(dials(&mut gs).len(), 3);
+}
+
+#[test]
+fn test_px_dials_are_jittered() {
+    let config = ConfigBuilder::default()
+        .prune_peers(16)
+        .px_dial_jitter(Duration::from_millis(50))
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .create_network();
+    gs.events.clear();
+
+    let px = (0..4)
+        .map(|_| PeerInfo {
+            peer_id: Some(PeerId::random()),
+            signed_peer_record: None,
+        })
+        .collect();
+    gs.handle_prune(
+        &peers[0],
+        vec![(
+            topics[0].clone(),
+            px,
+            Some(config.prune_backoff().as_secs()),
+        )],
+    );
+    assert!(!gs.events.iter().any(|e| matches!(e, ToSwarm::Dial { .. })));
+    assert_eq!(gs.px_dial_queue.len(), 4);
+
+    let mut dials = 0;
+    futures::executor::block_on(futures::future::poll_fn(|cx| {
+        while let Poll::Ready(event) = gs.poll(cx) {
+            if matches!(event, ToSwarm::Dial { .. }) {
+                dials += 1;
+            }
+        }
+        if dials == 4 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+    assert!(gs.px_dial_queue.is_empty());
+}
+
 #[test]
 fn test_send_px_and_backoff_in_prune() {
     let config: Config = Config::default();
@@ -2124,6 +2545,8 @@ fn test_gossip_to_at_least_gossip_lazy_peers() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
     gs.handle_received_message(raw_message.clone(), &PeerId::random());
 
@@ -2169,6 +2592,8 @@ fn test_gossip_to_at_most_gossip_factor_peers() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
     gs.handle_received_message(raw_message.clone(), &PeerId::random());
 
@@ -2356,6 +2781,70 @@ fn test_prune_negative_scored_peers() {
     );
 }
 
+#[test]
+fn test_prune_stale_peers() {
+    let config = ConfigBuilder::default()
+        .stale_mesh_peer_timeout(Some(Duration::from_millis(100)))
+        .build()
+        .unwrap();
+
+    //build mesh with three peers
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .explicit(0)
+        .outbound(0)
+        .create_network();
+
+    //start tracking the mesh peers
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topics[0]].len(), 3);
+
+    sleep(Duration::from_millis(150));
+
+    //the first two peers deliver messages while the third one stays silent
+    let source = PeerId::random();
+    let message = |seq| RawMessage {
+        source: Some(source),
+        data: vec![],
+        sequence_number: Some(seq),
+        topic: topics[0].clone(),
+        signature: None,
+        key: None,
+        validated: true,
+        expires_at: None,
+        chunk: None,
+    };
+    gs.handle_received_message(message(0), &peers[0]);
+    gs.handle_received_message(message(0), &peers[1]);
+    gs.handle_received_message(message(1), &peers[1]);
+    gs.handle_received_message(message(1), &peers[0]);
+
+    gs.heartbeat();
+
+    //only the silent peer should have been removed from the mesh
+    assert!(gs.mesh[&topics[0]].contains(&peers[0]));
+    assert!(gs.mesh[&topics[0]].contains(&peers[1]));
+    assert!(!gs.mesh[&topics[0]].contains(&peers[2]));
+
+    //check prune message
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &peers[2]
+            && match m {
+                ControlAction::Prune {
+                    topic_hash, peers, ..
+                } =>
+                    topic_hash == &topics[0] &&
+                    //no px in this case
+                    peers.is_empty(),
+                _ => false,
+            }),
+        1
+    );
+}
+
 #[test]
 fn test_dont_graft_to_negative_scored_peers() {
     let config = Config::default();
@@ -2416,6 +2905,7 @@ fn test_ignore_px_from_negative_scored_peer() {
     //handle prune from single peer with px peers
     let px = vec![PeerInfo {
         peer_id: Some(PeerId::random()),
+        signed_peer_record: None,
     }];
 
     gs.handle_prune(
@@ -2533,6 +3023,8 @@ fn test_do_not_gossip_to_peers_below_gossip_threshold() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
     gs.handle_received_message(raw_message.clone(), &PeerId::random());
 
@@ -2610,6 +3102,8 @@ fn test_iwant_msg_from_peer_below_gossip_threshold_gets_ignored() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
     gs.handle_received_message(raw_message.clone(), &PeerId::random());
 
@@ -2698,6 +3192,8 @@ fn test_ihave_msg_from_peer_below_gossip_threshold_gets_ignored() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     // Transform the inbound message
@@ -2877,6 +3373,8 @@ fn test_ignore_rpc_from_peers_below_graylist_threshold() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     let raw_message2 = RawMessage {
@@ -2887,6 +3385,8 @@ fn test_ignore_rpc_from_peers_below_graylist_threshold() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     let raw_message3 = RawMessage {
@@ -2897,6 +3397,8 @@ fn test_ignore_rpc_from_peers_below_graylist_threshold() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     let raw_message4 = RawMessage {
@@ -2907,6 +3409,8 @@ fn test_ignore_rpc_from_peers_below_graylist_threshold() {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     // Transform the inbound message
@@ -2998,6 +3502,7 @@ fn test_ignore_px_from_peers_below_accept_px_threshold() {
     // Handle prune from peer peers[0] with px peers
     let px = vec![PeerInfo {
         peer_id: Some(PeerId::random()),
+        signed_peer_record: None,
     }];
     gs.handle_prune(
         &peers[0],
@@ -3020,6 +3525,7 @@ fn test_ignore_px_from_peers_below_accept_px_threshold() {
     //handle prune from peer peers[1] with px peers
     let px = vec![PeerInfo {
         peer_id: Some(PeerId::random()),
+        signed_peer_record: None,
     }];
     gs.handle_prune(
         &peers[1],
@@ -3171,6 +3677,8 @@ fn random_message(seq: &mut u64, topics: &[TopicHash]) -> RawMessage {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     }
 }
 
@@ -4054,6 +4562,7 @@ fn test_scoring_p6() {
             },
             failed_addresses: &[],
             other_established: 0,
+            data: &Default::default(),
         }));
     }
 
@@ -4075,6 +4584,7 @@ fn test_scoring_p6() {
             },
             failed_addresses: &[],
             other_established: 1,
+            data: &Default::default(),
         }));
     }
 
@@ -4105,6 +4615,7 @@ fn test_scoring_p6() {
         },
         failed_addresses: &[],
         other_established: 2,
+        data: &Default::default(),
     }));
 
     //nothing changed
@@ -5008,11 +5519,73 @@ fn test_subscribe_to_invalid_topic() {
 }
 
 #[test]
-fn test_subscribe_and_graft_with_negative_score() {
-    //simulate a communication between two gossipsub instances
-    let (mut gs1, _, topic_hashes) = inject_nodes1()
-        .topics(vec!["test".into()])
-        .scoring(Some((
+fn test_update_subscription_filter() {
+    let t1 = TopicHash::from_raw("t1");
+    let t2 = TopicHash::from_raw("t2");
+    let (mut gs, peers, _) = inject_nodes::<IdentityTransform, _>()
+        .peer_no(2)
+        .topics(vec!["t1".into()])
+        .to_subscribe(true)
+        .subscription_filter(DynamicSubscriptionFilter::new([TopicRule::Exact(
+            t1.clone(),
+        )]))
+        .create_network();
+    assert_eq!(gs.topic_peers[&t1].len(), 2);
+    flush_events(&mut gs);
+
+    // The subscription to the closed topic is filtered and remembered.
+    gs.handle_received_subscriptions(
+        &[Subscription {
+            action: SubscriptionAction::Subscribe,
+            topic_hash: t2.clone(),
+        }],
+        &peers[0],
+    );
+    assert!(!gs
+        .topic_peers
+        .get(&t2)
+        .is_some_and(|p| p.contains(&peers[0])));
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::SubscriptionFiltered { peer_id, topic })
+            if peer_id == &peers[0] && topic == &t2
+    )));
+    gs.events.clear();
+
+    // Closing the first topic removes its subscribers, opening the second one accepts the
+    // remembered subscription.
+    gs.update_subscription_filter(|filter| {
+        filter.remove_rule(&TopicRule::Exact(t1.clone()));
+        filter.add_rule(TopicRule::Prefix("t2".into()));
+    });
+
+    assert!(gs.topic_peers[&t1].is_empty());
+    assert!(gs.mesh[&t1].is_empty());
+    assert!(gs.topic_peers[&t2].contains(&peers[0]));
+    let filtered = gs
+        .events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ToSwarm::GenerateEvent(Event::SubscriptionFiltered { topic, .. }) if topic == &t1
+            )
+        })
+        .count();
+    assert_eq!(filtered, 2);
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::Subscribed { peer_id, topic })
+            if peer_id == &peers[0] && topic == &t2
+    )));
+}
+
+#[test]
+fn test_subscribe_and_graft_with_negative_score() {
+    //simulate a communication between two gossipsub instances
+    let (mut gs1, _, topic_hashes) = inject_nodes1()
+        .topics(vec!["test".into()])
+        .scoring(Some((
             PeerScoreParams::default(),
             PeerScoreThresholds::default(),
         )))
@@ -5108,3 +5681,2183 @@ fn test_graft_without_subscribe() {
     // We unsubscribe from the topic.
     let _ = gs.unsubscribe(&Topic::new(topic));
 }
+
+#[test]
+fn test_saturated_outbound_queue_is_reported() {
+    use libp2p_swarm::ConnectionHandler;
+
+    let config = ConfigBuilder::default()
+        .saturated_queue_len(Some(2))
+        .saturated_queue_heartbeats(2)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(false)
+        .gs_config(config)
+        .create_network();
+
+    let mut handler = gs
+        .handle_established_outbound_connection(
+            ConnectionId::new_unchecked(0),
+            peers[0],
+            &Multiaddr::empty(),
+            Endpoint::Dialer,
+        )
+        .unwrap();
+    assert_eq!(gs.outbound_queue_len(&peers[0]), Some(0));
+
+    // The handler is never polled, hence the messages stay in its queue.
+    for _ in 0..3 {
+        handler.on_behaviour_event(HandlerIn::Message(RpcOut::Subscribe(topics[0].clone())));
+    }
+    assert_eq!(gs.outbound_queue_len(&peers[0]), Some(3));
+
+    let saturated = |gs: &mut Behaviour| {
+        gs.events
+            .drain(..)
+            .filter(|e| {
+                matches!(
+                    e,
+                    ToSwarm::GenerateEvent(Event::OutboundQueueSaturated { peer_id, queue_len: 3 })
+                        if peer_id == &peers[0]
+                )
+            })
+            .count()
+    };
+
+    gs.heartbeat();
+    assert_eq!(saturated(&mut gs), 0);
+    gs.heartbeat();
+    assert_eq!(saturated(&mut gs), 1);
+
+    drop(handler);
+    assert_eq!(gs.outbound_queue_len(&peers[0]), Some(0));
+    gs.heartbeat();
+    gs.heartbeat();
+    assert_eq!(saturated(&mut gs), 0);
+}
+
+#[test]
+fn test_idontwant_is_sent_to_v1_2_mesh_peers_for_large_messages() {
+    let config = ConfigBuilder::default()
+        .idontwant_message_size_threshold(100)
+        .build()
+        .unwrap();
+
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(0)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let add_mesh_peer = |gs: &mut Behaviour, kind| {
+        let peer = add_peer_with_addr_and_kind(
+            gs,
+            &topic_hashes,
+            false,
+            false,
+            Multiaddr::empty(),
+            Some(kind),
+        );
+        gs.handle_graft(&peer, topic_hashes.clone());
+        peer
+    };
+    let source = add_mesh_peer(&mut gs, PeerKind::Gossipsubv1_2);
+    let v1_2_peer = add_mesh_peer(&mut gs, PeerKind::Gossipsubv1_2);
+    let v1_1_peer = add_mesh_peer(&mut gs, PeerKind::Gossipsubv1_1);
+    flush_events(&mut gs);
+
+    let receive = |gs: &mut Behaviour, seq, len| {
+        let raw_message = RawMessage {
+            source: Some(source),
+            data: vec![7; len],
+            sequence_number: Some(seq),
+            topic: topic_hashes[0].clone(),
+            signature: None,
+            key: None,
+            validated: true,
+            expires_at: None,
+            chunk: None,
+        };
+        let message = gs
+            .data_transform
+            .inbound_transform(raw_message.clone())
+            .unwrap();
+        let msg_id = gs.config.message_id(&message);
+        gs.handle_received_message(raw_message, &source);
+        msg_id
+    };
+
+    // Small messages don't trigger an IDONTWANT.
+    receive(&mut gs, 0, 10);
+    assert_eq!(
+        count_control_msgs(&gs, |_, action| matches!(
+            action,
+            ControlAction::IDontWant { .. }
+        )),
+        0
+    );
+
+    let msg_id = receive(&mut gs, 1, 200);
+    let idontwant_to = |peer: PeerId| {
+        count_control_msgs(&gs, |peer_id, action| {
+            peer_id == &peer
+                && matches!(
+                    action,
+                    ControlAction::IDontWant { message_ids } if message_ids == &vec![msg_id.clone()]
+                )
+        })
+    };
+    assert_eq!(idontwant_to(v1_2_peer), 1);
+    assert_eq!(
+        idontwant_to(v1_1_peer),
+        0,
+        "Only v1.2 peers understand IDONTWANT"
+    );
+    assert_eq!(
+        idontwant_to(source),
+        0,
+        "The source has the message already"
+    );
+}
+
+#[test]
+fn test_messages_are_withheld_from_peers_that_sent_idontwant() {
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .create_network();
+
+    for peer in &peers {
+        gs.on_connection_handler_event(
+            *peer,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::PeerKind(PeerKind::Gossipsubv1_2),
+        );
+    }
+    flush_events(&mut gs);
+
+    let raw_message = RawMessage {
+        source: Some(peers[2]),
+        data: vec![1, 2, 3, 4],
+        sequence_number: Some(1u64),
+        topic: topic_hashes[0].clone(),
+        signature: None,
+        key: None,
+        validated: true,
+        expires_at: None,
+        chunk: None,
+    };
+    let message = gs
+        .data_transform
+        .inbound_transform(raw_message.clone())
+        .unwrap();
+    let msg_id = gs.config.message_id(&message);
+
+    gs.on_connection_handler_event(
+        peers[0],
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::Message {
+            rpc: Rpc {
+                messages: vec![],
+                subscriptions: vec![],
+                control_msgs: vec![ControlAction::IDontWant {
+                    message_ids: vec![msg_id.clone()],
+                }],
+            },
+            invalid_messages: vec![],
+        },
+    );
+    gs.handle_received_message(raw_message, &peers[2]);
+
+    let forwarded_to = |peer: PeerId| {
+        gs.events
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    ToSwarm::NotifyHandler {
+                        peer_id,
+                        event: HandlerIn::Message(RpcOut::Forward(_)),
+                        ..
+                    } if peer_id == &peer
+                )
+            })
+            .count()
+    };
+    assert_eq!(forwarded_to(peers[0]), 0);
+    assert_eq!(forwarded_to(peers[1]), 1);
+
+    // The message ids are forgotten after a while.
+    for received in gs
+        .connected_peers
+        .get_mut(&peers[0])
+        .unwrap()
+        .dont_send
+        .values_mut()
+    {
+        *received -= IDONTWANT_TIMEOUT;
+    }
+    gs.heartbeat();
+    assert!(gs.connected_peers[&peers[0]].dont_send.is_empty());
+}
+
+/// Polls the behaviour until it is pending, returning the emitted events.
+fn poll_events<D, F>(gs: &mut Behaviour<D, F>) -> Vec<ToSwarm<Event, HandlerIn>>
+where
+    D: DataTransform + Send + 'static,
+    F: TopicSubscriptionFilter + Send + 'static,
+{
+    let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+    let mut events = Vec::new();
+    while let Poll::Ready(event) = gs.poll(&mut cx) {
+        events.push(event);
+    }
+    events
+}
+
+fn validated_message(source: PeerId, data: Vec<u8>, topic: TopicHash) -> RawMessage {
+    RawMessage {
+        source: Some(source),
+        data,
+        sequence_number: Some(rand::random()),
+        topic,
+        signature: None,
+        key: None,
+        validated: true,
+        expires_at: None,
+        chunk: None,
+    }
+}
+
+#[test]
+fn test_topic_validator_decides_on_delivery_and_forwarding() {
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .create_network();
+    gs.set_topic_validator(&Topic::new("topic"), |message: &Message| {
+        future::ready(if message.data == [1] {
+            MessageAcceptance::Accept
+        } else {
+            MessageAcceptance::Reject
+        })
+    });
+    flush_events(&mut gs);
+
+    let is_message =
+        |e: &ToSwarm<Event, HandlerIn>| matches!(e, ToSwarm::GenerateEvent(Event::Message { .. }));
+    let is_forward = |e: &ToSwarm<Event, HandlerIn>| {
+        matches!(
+            e,
+            ToSwarm::NotifyHandler {
+                event: HandlerIn::Message(RpcOut::Forward(_)),
+                ..
+            }
+        )
+    };
+
+    // Neither delivered nor forwarded before the validation completes.
+    let accepted = validated_message(peers[0], vec![1], topic_hashes[0].clone());
+    gs.handle_received_message(accepted, &peers[0]);
+    assert!(!gs.events.iter().any(|e| is_message(e) || is_forward(e)));
+
+    let events = poll_events(&mut gs);
+    assert_eq!(events.iter().filter(|e| is_message(e)).count(), 1);
+    assert_eq!(events.iter().filter(|e| is_forward(e)).count(), 2);
+
+    let rejected = validated_message(peers[0], vec![2], topic_hashes[0].clone());
+    let msg_id = gs.config.message_id(
+        &gs.data_transform
+            .inbound_transform(rejected.clone())
+            .unwrap(),
+    );
+    gs.handle_received_message(rejected, &peers[0]);
+
+    let events = poll_events(&mut gs);
+    assert!(!events.iter().any(|e| is_message(e) || is_forward(e)));
+    assert!(gs.mcache.get(&msg_id).is_none());
+}
+
+#[test]
+fn test_topic_validations_are_bounded_in_number_and_time() {
+    let config = ConfigBuilder::default()
+        .max_concurrent_validations(1)
+        .validation_timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    gs.set_topic_validator(&Topic::new("topic"), |_: &Message| {
+        future::pending::<MessageAcceptance>()
+    });
+
+    let receive = |gs: &mut Behaviour, data| {
+        let message = validated_message(peers[0], data, topic_hashes[0].clone());
+        let msg_id = gs.config.message_id(
+            &gs.data_transform
+                .inbound_transform(message.clone())
+                .unwrap(),
+        );
+        gs.handle_received_message(message, &peers[0]);
+        msg_id
+    };
+    let first = receive(&mut gs, vec![1]);
+    let second = receive(&mut gs, vec![2]);
+
+    // The second message exceeds the limit of concurrent validations and is ignored.
+    assert_eq!(gs.pending_validations.len(), 1);
+    assert!(gs.mcache.get(&first).is_some());
+    assert!(gs.mcache.get(&second).is_none());
+
+    sleep(Duration::from_millis(100));
+    flush_events(&mut gs);
+    let events = poll_events(&mut gs);
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::Message { .. }))));
+    assert!(gs.pending_validations.is_empty());
+    assert!(gs.mcache.get(&first).is_none());
+}
+
+#[test]
+fn test_messages_are_replayed_to_reconnected_explicit_peer() {
+    let config = ConfigBuilder::default()
+        .explicit_peer_replay_window(Some(Duration::from_secs(60)))
+        .explicit_peer_replay_max_messages(2)
+        .build()
+        .unwrap();
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .explicit(1)
+        .create_network();
+    let explicit_peer = peers[0];
+
+    disconnect_peer(&mut gs, &explicit_peer);
+
+    // The explicit peer is the only subscriber, yet the messages are retained for it.
+    for data in [vec![1], vec![2], vec![3]] {
+        gs.publish(topic_hashes[0].clone(), data).unwrap();
+    }
+    flush_events(&mut gs);
+
+    gs.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: explicit_peer,
+        connection_id: ConnectionId::new_unchecked(1),
+        endpoint: &ConnectedPoint::Dialer {
+            address: Multiaddr::empty(),
+            role_override: Endpoint::Dialer,
+        },
+        failed_addresses: &[],
+        other_established: 0,
+        data: &Default::default(),
+    }));
+
+    // Only the most recent messages within the bound are replayed.
+    let replayed = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Message(RpcOut::Forward(message)),
+                ..
+            } if peer_id == &explicit_peer => Some(message.data.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(replayed, vec![vec![2], vec![3]]);
+    assert!(gs.explicit_peer_backlogs.is_empty());
+}
+
+#[test]
+fn test_score_params_and_thresholds_are_updated_at_runtime() {
+    let topic = Topic::new("test");
+    let mut peer_score_params = PeerScoreParams {
+        app_specific_weight: 1.0,
+        ..Default::default()
+    };
+    peer_score_params
+        .topics
+        .insert(topic.hash(), TopicScoreParams::default());
+    let (mut gs, peers, _) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .scoring(Some((peer_score_params, PeerScoreThresholds::default())))
+        .create_network();
+    gs.set_application_score(&peers[0], -5.0);
+
+    // The score of the peer is above the default gossip threshold.
+    assert!(
+        !gs.score_below_threshold(&peers[0], |t| t.gossip_threshold)
+            .0
+    );
+    gs.update_peer_score_thresholds(PeerScoreThresholds {
+        gossip_threshold: -1.0,
+        publish_threshold: -2.0,
+        graylist_threshold: -3.0,
+        ..Default::default()
+    })
+    .unwrap();
+    assert!(
+        gs.score_below_threshold(&peers[0], |t| t.gossip_threshold)
+            .0
+    );
+    assert!(gs
+        .update_peer_score_thresholds(PeerScoreThresholds {
+            gossip_threshold: 1.0,
+            ..Default::default()
+        })
+        .is_err());
+
+    let topic_params = TopicScoreParams {
+        topic_weight: 0.5,
+        ..Default::default()
+    };
+    gs.update_topic_score_params(&topic, topic_params).unwrap();
+    assert_eq!(gs.get_topic_params(&topic).unwrap().topic_weight, 0.5);
+    assert!(gs
+        .update_topic_score_params(
+            &topic,
+            TopicScoreParams {
+                topic_weight: -1.0,
+                ..Default::default()
+            }
+        )
+        .is_err());
+    assert!(gs.mesh.get(&topic.hash()).unwrap().contains(&peers[0]));
+}
+
+#[test]
+fn test_prune_peer_on_disconnect_request() {
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(5)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .create_network();
+    assert!(gs.mesh[&topics[0]].contains(&peers[0]));
+    flush_events(&mut gs);
+
+    gs.on_swarm_event(FromSwarm::DisconnectRequested(DisconnectRequested {
+        peer_id: peers[0],
+        timeout: Duration::from_secs(1),
+    }));
+
+    assert!(!gs.mesh[&topics[0]].contains(&peers[0]));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &peers[0]
+            && matches!(m, ControlAction::Prune { topic_hash, .. } if topic_hash == &topics[0])),
+        1
+    );
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, _| peer_id != &peers[0]),
+        0
+    );
+}
+
+#[test]
+fn test_score_breakdown_and_report() {
+    let config = ConfigBuilder::default()
+        .score_report_ticks(Some(2))
+        .build()
+        .unwrap();
+    let peer_score_params = PeerScoreParams {
+        behaviour_penalty_weight: -1.0,
+        ..PeerScoreParams::default()
+    };
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .scoring(Some((
+            peer_score_params.clone(),
+            PeerScoreThresholds::default(),
+        )))
+        .create_network();
+
+    gs.set_application_score(&peers[0], 2.0);
+    gs.peer_score.as_mut().unwrap().0.add_penalty(&peers[0], 3);
+
+    let breakdown = gs.peer_score_breakdown(&peers[0]).unwrap();
+    let application_score = 2.0 * peer_score_params.app_specific_weight;
+    let behaviour_penalty = -(3.0 - peer_score_params.behaviour_penalty_threshold).powi(2);
+    assert_eq!(breakdown.application_score, application_score);
+    assert_eq!(breakdown.behaviour_penalty, behaviour_penalty);
+    assert_eq!(breakdown.score, application_score + behaviour_penalty);
+    assert_eq!(gs.peer_score(&peers[0]), Some(breakdown.score));
+    assert_eq!(
+        gs.peer_mesh_topics(&peers[0]).collect::<Vec<_>>(),
+        vec![&topics[0]]
+    );
+
+    // Prune the peer to back it off from the mesh.
+    gs.handle_prune(&peers[1], vec![(topics[0].clone(), Vec::new(), Some(60))]);
+    assert_eq!(gs.peer_mesh_topics(&peers[1]).count(), 0);
+    assert!(gs.peer_backoff(&topics[0], &peers[1]).is_some());
+    assert!(gs.peer_backoff(&topics[0], &peers[0]).is_none());
+
+    flush_events(&mut gs);
+    let reports = |gs: &Behaviour| {
+        gs.events
+            .iter()
+            .filter_map(|e| match e {
+                ToSwarm::GenerateEvent(Event::ScoreReport { scores }) => Some(scores.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    gs.heartbeat();
+    assert!(reports(&gs).is_empty());
+    gs.heartbeat();
+    let reports = reports(&gs);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].len(), 2);
+    assert_eq!(reports[0][&peers[0]].application_score, application_score);
+}
+
+#[test]
+fn test_rejected_peer_subscription() {
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(5)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .create_network();
+    let topic = topics[0].clone();
+    assert!(gs.mesh[&topic].contains(&peers[0]));
+    flush_events(&mut gs);
+
+    assert!(gs.reject_peer_subscription(&peers[0], topic.clone()));
+    assert!(!gs.reject_peer_subscription(&peers[0], topic.clone()));
+    assert!(!gs.mesh[&topic].contains(&peers[0]));
+    assert!(!gs.topic_peers[&topic].contains(&peers[0]));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &peers[0]
+            && matches!(m, ControlAction::Prune { peers, .. } if peers.is_empty())),
+        1
+    );
+    flush_events(&mut gs);
+
+    // Grafts and subscriptions of the peer to the topic are ignored.
+    gs.handle_graft(&peers[0], vec![topic.clone()]);
+    assert!(!gs.mesh[&topic].contains(&peers[0]));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &peers[0]
+            && matches!(m, ControlAction::Prune { .. })),
+        1
+    );
+    let subscription = Subscription {
+        action: SubscriptionAction::Subscribe,
+        topic_hash: topic.clone(),
+    };
+    gs.handle_received_subscriptions(&[subscription], &peers[0]);
+    assert!(!gs.topic_peers[&topic].contains(&peers[0]));
+    assert!(!gs
+        .events
+        .iter()
+        .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::Subscribed { .. }))));
+    gs.heartbeat();
+    assert!(!gs.mesh[&topic].contains(&peers[0]));
+    flush_events(&mut gs);
+
+    // Once allowed again, the current subscription of the peer is accepted.
+    assert!(gs.allow_peer_subscription(&peers[0], &topic));
+    assert!(!gs.allow_peer_subscription(&peers[0], &topic));
+    assert!(gs.topic_peers[&topic].contains(&peers[0]));
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::GenerateEvent(Event::Subscribed { peer_id, .. }) if peer_id == &peers[0]
+    )));
+}
+
+#[test]
+fn test_outbound_queue_watermarks_are_reported() {
+    use libp2p_swarm::ConnectionHandler;
+
+    let config = ConfigBuilder::default()
+        .outbound_queue_high_watermark(Some(3))
+        .outbound_queue_low_watermark(1)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(false)
+        .gs_config(config)
+        .create_network();
+
+    let mut handler = gs
+        .handle_established_outbound_connection(
+            ConnectionId::new_unchecked(0),
+            peers[0],
+            &Multiaddr::empty(),
+            Endpoint::Dialer,
+        )
+        .unwrap();
+
+    let watermarks = |gs: &mut Behaviour| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::GenerateEvent(Event::OutboundQueueHigh { queue_len, .. }) => {
+                    Some((true, queue_len))
+                }
+                ToSwarm::GenerateEvent(Event::OutboundQueueLow { queue_len, .. }) => {
+                    Some((false, queue_len))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The handler is never polled, hence the messages stay in its queue.
+    for _ in 0..3 {
+        handler.on_behaviour_event(HandlerIn::Message(RpcOut::Subscribe(topics[0].clone())));
+    }
+    gs.heartbeat();
+    assert_eq!(watermarks(&mut gs), vec![(true, 3)]);
+    gs.heartbeat();
+    assert_eq!(watermarks(&mut gs), vec![]);
+
+    drop(handler);
+    gs.heartbeat();
+    assert_eq!(watermarks(&mut gs), vec![(false, 0)]);
+    gs.heartbeat();
+    assert_eq!(watermarks(&mut gs), vec![]);
+}
+
+#[test]
+fn test_mesh_peers_delivering_duplicates_are_choked() {
+    let config = ConfigBuilder::default()
+        .choke_ticks(Some(1))
+        .choke_min_deliveries(3)
+        .mesh_unchoked_min(1)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let choking_sent = |gs: &Behaviour, peer: PeerId, choke: bool| {
+        count_control_msgs(gs, |peer_id, action| {
+            peer_id == &peer
+                && match action {
+                    ControlAction::Choke { .. } => choke,
+                    ControlAction::Unchoke { .. } => !choke,
+                    _ => false,
+                }
+        })
+    };
+
+    // peers[0] delivers all messages first, peers[1] only duplicates and peers[2] nothing.
+    let mut seq = 0;
+    for _ in 0..3 {
+        let message = random_message(&mut seq, &topic_hashes);
+        gs.handle_received_message(message.clone(), &peers[0]);
+        gs.handle_received_message(message, &peers[1]);
+    }
+    flush_events(&mut gs);
+    gs.heartbeat();
+
+    assert_eq!(choking_sent(&gs, peers[1], true), 1);
+    assert_eq!(choking_sent(&gs, peers[0], true), 0);
+    assert_eq!(choking_sent(&gs, peers[2], true), 0);
+    assert_eq!(choking_sent(&gs, peers[1], false), 0);
+
+    // The choked peer announces a message no other peer delivered yet.
+    flush_events(&mut gs);
+    let message = random_message(&mut seq, &topic_hashes);
+    let message = gs.data_transform.inbound_transform(message).unwrap();
+    let msg_id = gs.config.message_id(&message);
+    gs.handle_ihave(&peers[1], vec![(topic_hashes[0].clone(), vec![msg_id])]);
+    gs.heartbeat();
+
+    assert_eq!(choking_sent(&gs, peers[1], false), 1);
+    assert_eq!(choking_sent(&gs, peers[1], true), 0);
+}
+
+#[test]
+fn test_messages_are_announced_to_choking_peers() {
+    let config = ConfigBuilder::default()
+        .choke_ticks(Some(1))
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let send_control = |gs: &mut Behaviour, action: ControlAction| {
+        gs.on_connection_handler_event(
+            peers[0],
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::Message {
+                rpc: Rpc {
+                    messages: vec![],
+                    subscriptions: vec![],
+                    control_msgs: vec![action],
+                },
+                invalid_messages: vec![],
+            },
+        );
+    };
+    let forwarded_to = |gs: &Behaviour, peer: PeerId| {
+        gs.events
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    ToSwarm::NotifyHandler {
+                        peer_id,
+                        event: HandlerIn::Message(RpcOut::Forward(_)),
+                        ..
+                    } if peer_id == &peer
+                )
+            })
+            .count()
+    };
+    let announced_to = |gs: &Behaviour, peer: PeerId| {
+        count_control_msgs(gs, |peer_id, action| {
+            peer_id == &peer && matches!(action, ControlAction::IHave { .. })
+        })
+    };
+
+    send_control(
+        &mut gs,
+        ControlAction::Choke {
+            topic_hash: topic_hashes[0].clone(),
+        },
+    );
+    flush_events(&mut gs);
+
+    let mut seq = 0;
+    gs.handle_received_message(random_message(&mut seq, &topic_hashes), &peers[2]);
+    assert_eq!(forwarded_to(&gs, peers[0]), 0);
+    assert_eq!(announced_to(&gs, peers[0]), 1);
+    assert_eq!(forwarded_to(&gs, peers[1]), 1);
+
+    send_control(
+        &mut gs,
+        ControlAction::Unchoke {
+            topic_hash: topic_hashes[0].clone(),
+        },
+    );
+    flush_events(&mut gs);
+
+    gs.handle_received_message(random_message(&mut seq, &topic_hashes), &peers[2]);
+    assert_eq!(forwarded_to(&gs, peers[0]), 1);
+    assert_eq!(announced_to(&gs, peers[0]), 0);
+}
+
+#[test]
+fn test_tracer_records_router_events() {
+    #[derive(Clone, Default)]
+    struct RecordingSink(Arc<Mutex<Vec<TraceEvent>>>);
+
+    impl TraceSink for RecordingSink {
+        fn trace(&mut self, event: TraceEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec!["topic".into()])
+        .to_subscribe(true)
+        .create_network();
+    let sink = RecordingSink::default();
+    gs.set_tracer(sink.clone());
+    let topic = topic_hashes[0].clone();
+    gs.mesh.get_mut(&topic).unwrap().clear();
+
+    gs.handle_graft(&peers[0], vec![topic.clone()]);
+
+    let raw_message = RawMessage {
+        source: Some(PeerId::random()),
+        data: vec![1],
+        sequence_number: Some(0),
+        topic: topic.clone(),
+        signature: None,
+        key: None,
+        validated: true,
+        expires_at: None,
+        chunk: None,
+    };
+    let message_id = gs.config.message_id(
+        &gs.data_transform
+            .inbound_transform(raw_message.clone())
+            .unwrap(),
+    );
+    gs.handle_received_message(raw_message.clone(), &peers[0]);
+    gs.handle_received_message(raw_message, &peers[1]);
+
+    gs.handle_prune(&peers[0], vec![(topic.clone(), vec![], None)]);
+
+    assert_eq!(
+        *sink.0.lock().unwrap(),
+        vec![
+            TraceEvent::Graft {
+                peer_id: peers[0],
+                topic: topic.clone(),
+            },
+            TraceEvent::DeliverMessage {
+                message_id: message_id.clone(),
+                received_from: peers[0],
+                topic: topic.clone(),
+            },
+            TraceEvent::DuplicateMessage {
+                message_id,
+                received_from: peers[1],
+                topic: topic.clone(),
+            },
+            TraceEvent::Prune {
+                peer_id: peers[0],
+                topic,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_add_explicit_peer_at_runtime_prunes_mesh_peer() {
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["topic".into()])
+        .to_subscribe(true)
+        .create_network();
+    let peer = peers[0];
+    let topic = topic_hashes[0].clone();
+    gs.mesh.get_mut(&topic).unwrap().insert(peer);
+    flush_events(&mut gs);
+
+    gs.add_explicit_peer(&peer);
+
+    assert!(!gs.mesh[&topic].contains(&peer));
+    assert_eq!(
+        count_control_msgs(&gs, |p, action| p == &peer
+            && matches!(action, ControlAction::Prune { topic_hash, .. } if topic_hash == &topic)),
+        1
+    );
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::NotifyHandler { peer_id, event: HandlerIn::Explicit(true), .. } if peer_id == &peer
+    )));
+
+    // Grafting the explicit peer is refused.
+    flush_events(&mut gs);
+    gs.handle_graft(&peer, vec![topic.clone()]);
+    assert!(!gs.mesh[&topic].contains(&peer));
+
+    // Once removed, the connections to the peer are no longer kept alive for it.
+    flush_events(&mut gs);
+    gs.remove_explicit_peer(&peer);
+    assert!(gs.events.iter().any(|e| matches!(
+        e,
+        ToSwarm::NotifyHandler { peer_id, event: HandlerIn::Explicit(false), .. } if peer_id == &peer
+    )));
+}
+
+#[test]
+fn test_adaptive_gossip_factor() {
+    let config = ConfigBuilder::default()
+        .adaptive_gossip_factor(Some(0.25..=0.75))
+        .build()
+        .unwrap();
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(4)
+        .topics(vec!["topic".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    let topic = topic_hashes[0].clone();
+    let message = |sequence_number| RawMessage {
+        source: Some(peers[0]),
+        data: vec![1],
+        sequence_number: Some(sequence_number),
+        topic: topic.clone(),
+        signature: None,
+        key: None,
+        validated: true,
+        expires_at: None,
+        chunk: None,
+    };
+
+    assert_eq!(gs.gossip_factor(&topic), 0.25);
+
+    // Messages are only delivered once: the mesh is unhealthy, gossip more.
+    gs.handle_received_message(message(0), &peers[0]);
+    gs.heartbeat();
+    assert!((gs.gossip_factor(&topic) - 0.30).abs() < f64::EPSILON);
+
+    // Every message is received from all mesh peers: the mesh is healthy, gossip less.
+    for peer in &peers {
+        gs.handle_received_message(message(1), peer);
+    }
+    gs.heartbeat();
+    assert!((gs.gossip_factor(&topic) - 0.25).abs() < f64::EPSILON);
+
+    // The factor stays within the configured bounds.
+    for peer in &peers {
+        gs.handle_received_message(message(2), peer);
+    }
+    gs.heartbeat();
+    assert_eq!(gs.gossip_factor(&topic), 0.25);
+}
+
+#[test]
+fn test_publish_to_specific_peers() {
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec!["topic".into()])
+        .to_subscribe(true)
+        .create_network();
+    let recipients = [peers[0], peers[1]];
+
+    let msg_id = gs
+        .publish_to(topic_hashes[0].clone(), vec![1], recipients.iter().copied())
+        .unwrap();
+
+    let publishes = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Message(RpcOut::Publish(_)),
+                ..
+            } => Some(*peer_id),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    assert_eq!(publishes, HashSet::from(recipients));
+    assert!(gs.mcache.get(&msg_id).is_some());
+    assert!(gs.duplicate_cache.contains(&msg_id));
+
+    // Peers which are not connected are skipped.
+    assert!(matches!(
+        gs.publish_to(topic_hashes[0].clone(), vec![2], [PeerId::random()]),
+        Err(PublishError::InsufficientPeers)
+    ));
+}
+
+#[test]
+fn test_custom_duplicate_cache() {
+    #[derive(Clone, Default)]
+    struct SharedCache(Arc<Mutex<HashSet<MessageId>>>);
+
+    impl DuplicateCache for SharedCache {
+        fn insert(&mut self, message_id: MessageId) -> bool {
+            self.0.lock().unwrap().insert(message_id)
+        }
+
+        fn contains(&self, message_id: &MessageId) -> bool {
+            self.0.lock().unwrap().contains(message_id)
+        }
+    }
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec!["topic".into()])
+        .to_subscribe(true)
+        .create_network();
+    let cache = SharedCache::default();
+    gs.set_duplicate_cache(cache.clone());
+
+    let raw_message = RawMessage {
+        source: Some(PeerId::random()),
+        data: vec![1],
+        sequence_number: Some(0),
+        topic: topic_hashes[0].clone(),
+        signature: None,
+        key: None,
+        validated: true,
+        expires_at: None,
+        chunk: None,
+    };
+    let message_id = gs.config.message_id(
+        &gs.data_transform
+            .inbound_transform(raw_message.clone())
+            .unwrap(),
+    );
+    gs.handle_received_message(raw_message.clone(), &peers[0]);
+    gs.handle_received_message(raw_message, &peers[1]);
+
+    assert!(cache.0.lock().unwrap().contains(&message_id));
+    let delivered = gs
+        .events
+        .iter()
+        .filter(|e| matches!(e, ToSwarm::GenerateEvent(Event::Message { .. })))
+        .count();
+    assert_eq!(delivered, 1);
+}
+
+#[test]
+fn test_topic_mesh_params_override_config() {
+    let config = Config::default();
+    let n = config.mesh_n_high() + 10;
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(n)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .outbound(n)
+        .create_network();
+    let topic = Topic::new("test");
+    let params = TopicMeshParams {
+        mesh_n: 3,
+        mesh_n_low: 2,
+        mesh_n_high: 4,
+        mesh_outbound_min: 1,
+        gossip_lazy: 3,
+        opportunistic_graft_peers: 0,
+    };
+
+    assert!(matches!(
+        gs.set_topic_mesh_params(
+            &topic,
+            TopicMeshParams {
+                mesh_n_low: 4,
+                ..params.clone()
+            }
+        ),
+        Err(ConfigBuilderError::MeshParametersInvalid)
+    ));
+    gs.set_topic_mesh_params(&topic, params.clone()).unwrap();
+    assert_eq!(gs.topic_mesh_params(&topics[0]), &params);
+
+    for peer in peers {
+        gs.handle_graft(&peer, topics.clone());
+    }
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topics[0]].len(), 3);
+
+    // The parameters of the config apply again once the override is removed.
+    gs.remove_topic_mesh_params(&topic);
+    assert_eq!(
+        gs.topic_mesh_params(&topics[0]),
+        &TopicMeshParams::from(&config)
+    );
+}
+
+#[test]
+fn test_rejected_messages_are_reported_to_mesh_peers() {
+    let config = ConfigBuilder::default()
+        .validate_messages()
+        .invalid_message_reports(Some(1))
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    let local_id = *gs.publish_config.get_own_id().unwrap();
+
+    let mut seq = 0;
+    let mut reject = |gs: &mut Behaviour| {
+        let message = random_message(&mut seq, &topic_hashes);
+        gs.handle_received_message(message.clone(), &peers[0]);
+        let message = gs.data_transform.inbound_transform(message).unwrap();
+        let msg_id = gs.config.message_id(&message);
+        gs.report_message_validation_result(&msg_id, &peers[0], MessageAcceptance::Reject)
+            .unwrap();
+        msg_id
+    };
+    let reports_sent = |gs: &Behaviour, peer: PeerId, msg_id: &MessageId| {
+        count_control_msgs(gs, |peer_id, action| {
+            peer_id == &peer
+                && match action {
+                    ControlAction::Invalid {
+                        message_id,
+                        propagation_source,
+                        ..
+                    } => {
+                        assert!(verify_invalid_report(&local_id, action));
+                        message_id == msg_id && propagation_source == &peers[0]
+                    }
+                    _ => false,
+                }
+        })
+    };
+
+    // The propagation source is not notified.
+    let msg_id = reject(&mut gs);
+    assert_eq!(reports_sent(&gs, peers[0], &msg_id), 0);
+    assert_eq!(reports_sent(&gs, peers[1], &msg_id), 1);
+    assert_eq!(reports_sent(&gs, peers[2], &msg_id), 1);
+
+    // Only one report is sent per peer and heartbeat.
+    let msg_id = reject(&mut gs);
+    assert_eq!(reports_sent(&gs, peers[1], &msg_id), 0);
+    assert_eq!(reports_sent(&gs, peers[2], &msg_id), 0);
+}
+
+#[test]
+fn test_invalid_reports_penalize_propagation_source() {
+    let config = ConfigBuilder::default()
+        .invalid_message_reports(Some(10))
+        .build()
+        .unwrap();
+    let mut peer_score_params = PeerScoreParams::default();
+    let topic = Topic::new("test");
+    let topic_params = TopicScoreParams {
+        time_in_mesh_weight: 0.0,             //deactivate time in mesh
+        first_message_deliveries_weight: 0.0, //deactivate first time deliveries
+        mesh_message_deliveries_weight: 0.0,  //deactivate message deliveries
+        mesh_failure_penalty_weight: 0.0,     //deactivate mesh failure penalties
+        invalid_message_deliveries_weight: -2.0,
+        invalid_message_deliveries_decay: 0.9,
+        topic_weight: 0.7,
+        ..Default::default()
+    };
+    peer_score_params.topics.insert(topic.hash(), topic_params);
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("test")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .scoring(Some((peer_score_params, PeerScoreThresholds::default())))
+        .create_network();
+
+    // Connect the reporter, which needs a key to sign its reports.
+    let keypair = Keypair::generate_ed25519();
+    let reporter = keypair.public().to_peer_id();
+    gs.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: reporter,
+        connection_id: ConnectionId::new_unchecked(0),
+        endpoint: &ConnectedPoint::Dialer {
+            address: Multiaddr::empty(),
+            role_override: Endpoint::Dialer,
+        },
+        failed_addresses: &[],
+        other_established: 0,
+        data: &Default::default(),
+    }));
+    gs.mesh.get_mut(&topic_hashes[0]).unwrap().insert(reporter);
+
+    let report = |keypair: &Keypair, message_id: MessageId| {
+        let signature = keypair
+            .sign(&invalid_report_signature_bytes(
+                &message_id,
+                &topic_hashes[0],
+                &peers[0],
+            ))
+            .unwrap();
+        ControlAction::Invalid {
+            message_id,
+            topic_hash: topic_hashes[0].clone(),
+            propagation_source: peers[0],
+            signature,
+            key: None,
+        }
+    };
+    let send_report = |gs: &mut Behaviour, report: ControlAction| {
+        gs.on_connection_handler_event(
+            reporter,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::Message {
+                rpc: Rpc {
+                    messages: vec![],
+                    subscriptions: vec![],
+                    control_msgs: vec![report],
+                },
+                invalid_messages: vec![],
+            },
+        );
+    };
+    let score = |gs: &Behaviour| gs.peer_score.as_ref().unwrap().0.score(&peers[0]);
+
+    // A forged report is ignored.
+    send_report(
+        &mut gs,
+        report(&Keypair::generate_ed25519(), MessageId::new(b"forged")),
+    );
+    assert_eq!(score(&gs), 0.0);
+
+    // The propagation source is penalized once per message.
+    let invalid = report(&keypair, MessageId::new(b"invalid"));
+    send_report(&mut gs, invalid.clone());
+    assert_eq!(score(&gs), -2.0 * 0.7);
+    send_report(&mut gs, invalid);
+    assert_eq!(score(&gs), -2.0 * 0.7);
+
+    // Reports of peers not in the mesh are ignored.
+    gs.mesh.get_mut(&topic_hashes[0]).unwrap().remove(&reporter);
+    send_report(&mut gs, report(&keypair, MessageId::new(b"other")));
+    assert_eq!(score(&gs), -2.0 * 0.7);
+}
+
+fn budgets_exceeded<D: DataTransform, F: TopicSubscriptionFilter>(
+    gs: &Behaviour<D, F>,
+) -> Vec<(PeerId, ControlBudget)> {
+    gs.events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::BudgetExceeded { peer_id, budget }) => {
+                Some((*peer_id, *budget))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_ihave_flood_is_penalized() {
+    let config = ConfigBuilder::default()
+        .max_ihave_messages(2)
+        .build()
+        .unwrap();
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .scoring(Some((
+            PeerScoreParams::default(),
+            PeerScoreThresholds::default(),
+        )))
+        .create_network();
+    flush_events(&mut gs);
+
+    for i in 0..4u8 {
+        gs.handle_ihave(
+            &peers[0],
+            vec![(topic_hashes[0].clone(), vec![MessageId::new(&[i])])],
+        );
+    }
+
+    // The budget is exceeded twice, but reported and penalized once.
+    assert_eq!(
+        budgets_exceeded(&gs),
+        vec![(peers[0], ControlBudget::IHave)]
+    );
+    assert!(gs.peer_score.as_ref().unwrap().0.score(&peers[0]) < 0.0);
+
+    // The budget is reset with the heartbeat.
+    gs.heartbeat();
+    flush_events(&mut gs);
+    gs.handle_ihave(
+        &peers[0],
+        vec![(topic_hashes[0].clone(), vec![MessageId::new(&[4])])],
+    );
+    assert!(budgets_exceeded(&gs).is_empty());
+}
+
+#[test]
+fn test_iwant_responses_are_limited() {
+    let config = ConfigBuilder::default()
+        .max_iwant_responses(Some(1))
+        .build()
+        .unwrap();
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    flush_events(&mut gs);
+
+    let mut seq = 0;
+    let msg_ids = (0..2)
+        .map(|_| {
+            let raw_message = random_message(&mut seq, &topic_hashes);
+            let message = gs
+                .data_transform
+                .inbound_transform(raw_message.clone())
+                .unwrap();
+            let msg_id = gs.config.message_id(&message);
+            gs.mcache.put(&msg_id, raw_message);
+            msg_id
+        })
+        .collect::<Vec<_>>();
+
+    gs.handle_iwant(&peers[0], msg_ids);
+
+    let forwarded = gs
+        .events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ToSwarm::NotifyHandler {
+                    event: HandlerIn::Message(RpcOut::Forward(_)),
+                    ..
+                }
+            )
+        })
+        .count();
+    assert_eq!(forwarded, 1);
+    assert_eq!(
+        budgets_exceeded(&gs),
+        vec![(peers[0], ControlBudget::IWant)]
+    );
+}
+
+#[test]
+fn test_control_messages_are_rate_limited() {
+    let config = ConfigBuilder::default()
+        .control_messages_per_second(Some(1))
+        .control_messages_burst(2)
+        .build()
+        .unwrap();
+    let (mut gs, peers, _) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    flush_events(&mut gs);
+
+    let control_msgs = (0..3u8)
+        .map(|i| ControlAction::IDontWant {
+            message_ids: vec![MessageId::new(&[i])],
+        })
+        .collect();
+    gs.on_connection_handler_event(
+        peers[0],
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::Message {
+            rpc: Rpc {
+                messages: vec![],
+                subscriptions: vec![],
+                control_msgs,
+            },
+            invalid_messages: vec![],
+        },
+    );
+
+    // The third control message exceeds the burst and is dropped.
+    assert_eq!(gs.connected_peers[&peers[0]].dont_send.len(), 2);
+    assert_eq!(
+        budgets_exceeded(&gs),
+        vec![(peers[0], ControlBudget::ControlMessages)]
+    );
+}
+
+#[test]
+fn test_ihave_advertisements_per_peer_are_limited() {
+    let config = ConfigBuilder::default()
+        .max_sent_ihave_messages(1)
+        .build()
+        .unwrap();
+    // Only mesh_n_low peers get added to the meshes, the others receive gossip.
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(config.mesh_n_low() + config.gossip_lazy())
+        .topics(vec![String::from("topic1"), String::from("topic2")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let mut seq = 0;
+    for topic_hash in &topic_hashes {
+        let message = random_message(&mut seq, std::slice::from_ref(topic_hash));
+        gs.handle_received_message(message, &PeerId::random());
+    }
+    flush_events(&mut gs);
+    gs.emit_gossip();
+
+    for peer in &peers {
+        let advertised = count_control_msgs(&gs, |peer_id, action| {
+            peer_id == peer && matches!(action, ControlAction::IHave { .. })
+        });
+        assert!(advertised <= 1);
+    }
+    assert!(
+        count_control_msgs(&gs, |_, action| matches!(
+            action,
+            ControlAction::IHave { .. }
+        )) > 0
+    );
+}
+
+#[test]
+fn anonymous_topics_require_anonymous_messages_with_message_id_fn() {
+    let anonymous = Topic::new("anonymous");
+    let config = ConfigBuilder::default()
+        .topic_validation_mode(anonymous.hash(), ValidationMode::Anonymous)
+        .build()
+        .unwrap();
+
+    // Signed messages carry an author.
+    let mut gs: Behaviour = Behaviour::new(
+        MessageAuthenticity::Signed(Keypair::generate_ed25519()),
+        config.clone(),
+    )
+    .unwrap();
+    assert!(matches!(
+        gs.subscribe(&anonymous),
+        Err(SubscriptionError::IncompatibleValidationMode(
+            IncompatibleValidationMode::AuthorPresent
+        ))
+    ));
+    assert!(matches!(
+        gs.publish(anonymous.clone(), vec![1]),
+        Err(PublishError::IncompatibleValidationMode(
+            IncompatibleValidationMode::AuthorPresent
+        ))
+    ));
+    // Other topics are still validated strictly.
+    assert!(gs.subscribe(&Topic::new("strict")).unwrap());
+
+    // Anonymous messages would all get the same default message id.
+    let config = ConfigBuilder::default()
+        .validation_mode(ValidationMode::Permissive)
+        .topic_validation_mode(anonymous.hash(), ValidationMode::Anonymous)
+        .topic_validation_mode(Topic::new("strict").hash(), ValidationMode::Strict)
+        .build()
+        .unwrap();
+    let mut gs: Behaviour = Behaviour::new(MessageAuthenticity::Anonymous, config).unwrap();
+    assert!(matches!(
+        gs.subscribe(&anonymous),
+        Err(SubscriptionError::IncompatibleValidationMode(
+            IncompatibleValidationMode::MessageIdFnRequired
+        ))
+    ));
+    assert!(matches!(
+        gs.subscribe(&Topic::new("strict")),
+        Err(SubscriptionError::IncompatibleValidationMode(
+            IncompatibleValidationMode::SignatureRequired
+        ))
+    ));
+
+    let config = ConfigBuilder::default()
+        .validation_mode(ValidationMode::Permissive)
+        .topic_validation_mode(anonymous.hash(), ValidationMode::Anonymous)
+        .topic_message_id_fn(anonymous.hash(), |message| {
+            MessageId::from(message.data.clone())
+        })
+        .build()
+        .unwrap();
+    let mut gs: Behaviour = Behaviour::new(MessageAuthenticity::Anonymous, config).unwrap();
+    assert!(gs.subscribe(&anonymous).unwrap());
+    assert_eq!(
+        gs.config.message_id(&Message {
+            source: None,
+            data: vec![1, 2],
+            sequence_number: None,
+            topic: anonymous.hash(),
+        }),
+        MessageId::from(vec![1, 2])
+    );
+}
+
+#[test]
+fn mesh_not_meeting_outbound_quota_is_reported_once() {
+    let config = ConfigBuilder::default().mesh_events(true).build().unwrap();
+    let (mut gs, _, topics) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .outbound(0)
+        .create_network();
+    let not_met = |gs: &Behaviour| {
+        gs.events
+            .iter()
+            .filter_map(|e| match e {
+                ToSwarm::GenerateEvent(Event::OutboundQuotaNotMet {
+                    topic,
+                    outbound,
+                    mesh_outbound_min,
+                }) => Some((topic.clone(), *outbound, *mesh_outbound_min)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The mesh is low and only has inbound peers.
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topics[0]].len(), 3);
+    assert_eq!(
+        not_met(&gs),
+        vec![(topics[0].clone(), 0, config.mesh_outbound_min())]
+    );
+    gs.events.clear();
+    gs.heartbeat();
+    assert!(not_met(&gs).is_empty());
+
+    // Outbound peers are grafted to meet the quota again.
+    for _ in 0..config.mesh_outbound_min() {
+        add_peer(&mut gs, &topics, true, false);
+    }
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topics[0]].len(), 3 + config.mesh_outbound_min());
+    assert!(gs.outbound_quota_not_met.is_empty());
+    assert!(not_met(&gs).is_empty());
+}
+
+#[test]
+fn opportunistic_graft_threshold_overrides_score_threshold() {
+    assert!(matches!(
+        ConfigBuilder::default()
+            .opportunistic_graft_threshold(Some(-1.0))
+            .build(),
+        Err(ConfigBuilderError::OpportunisticGraftInvalid)
+    ));
+    assert!(matches!(
+        ConfigBuilder::default()
+            .opportunistic_graft_ticks(0)
+            .build(),
+        Err(ConfigBuilderError::OpportunisticGraftInvalid)
+    ));
+
+    // The mesh peers have a median score of zero, below the score threshold but not below the
+    // configured one.
+    let config = ConfigBuilder::default()
+        .opportunistic_graft_ticks(1)
+        .opportunistic_graft_threshold(Some(0.0))
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(config.mesh_n() + 2)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .scoring(Some((
+            PeerScoreParams::default(),
+            PeerScoreThresholds::default(),
+        )))
+        .create_network();
+    for peer in &peers[..config.mesh_n()] {
+        gs.handle_graft(peer, topics.clone());
+    }
+    for peer in &peers[config.mesh_n()..] {
+        gs.set_application_score(peer, 100.0);
+    }
+
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topics[0]].len(), config.mesh_n());
+}
+
+#[test]
+fn test_slow_peer_is_pruned() {
+    let config = ConfigBuilder::default()
+        .saturated_queue_len(Some(2))
+        .saturated_queue_heartbeats(1)
+        .slow_peer_policy(SlowPeerPolicy::Prune)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(config.mesh_n())
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    assert!(gs.mesh[&topic_hashes[0]].contains(&peers[0]));
+
+    gs.outbound_queue_lens
+        .insert(peers[0], Arc::new(AtomicUsize::new(3)));
+    gs.heartbeat();
+
+    assert!(!gs.mesh[&topic_hashes[0]].contains(&peers[0]));
+    assert!(gs
+        .backoffs
+        .is_backoff_with_slack(&topic_hashes[0], &peers[0]));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, action| {
+            peer_id == &peers[0]
+                && matches!(action, ControlAction::Prune { peers, .. } if peers.is_empty())
+        }),
+        1
+    );
+}
+
+#[test]
+fn test_messages_are_announced_to_slow_peers() {
+    let config = ConfigBuilder::default()
+        .saturated_queue_len(Some(2))
+        .saturated_queue_heartbeats(1)
+        .slow_peer_policy(SlowPeerPolicy::GossipOnly)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let queue_len = Arc::new(AtomicUsize::new(3));
+    gs.outbound_queue_lens.insert(peers[0], queue_len.clone());
+    gs.heartbeat();
+    assert!(gs.mesh[&topic_hashes[0]].contains(&peers[0]));
+    flush_events(&mut gs);
+
+    let forwarded_to = |gs: &Behaviour, peer: PeerId| {
+        gs.events
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    ToSwarm::NotifyHandler {
+                        peer_id,
+                        event: HandlerIn::Message(RpcOut::Forward(_)),
+                        ..
+                    } if peer_id == &peer
+                )
+            })
+            .count()
+    };
+    let announced_to = |gs: &Behaviour, peer: PeerId| {
+        count_control_msgs(gs, |peer_id, action| {
+            peer_id == &peer && matches!(action, ControlAction::IHave { .. })
+        })
+    };
+
+    let mut seq = 0;
+    gs.handle_received_message(random_message(&mut seq, &topic_hashes), &peers[2]);
+    assert_eq!(forwarded_to(&gs, peers[0]), 0);
+    assert_eq!(announced_to(&gs, peers[0]), 1);
+    assert_eq!(forwarded_to(&gs, peers[1]), 1);
+
+    // Messages are forwarded again once the queue drained.
+    queue_len.store(0, AtomicOrdering::Relaxed);
+    gs.heartbeat();
+    flush_events(&mut gs);
+    gs.handle_received_message(random_message(&mut seq, &topic_hashes), &peers[2]);
+    assert_eq!(forwarded_to(&gs, peers[0]), 1);
+}
+
+#[test]
+fn test_slow_peer_is_disconnected() {
+    let config = ConfigBuilder::default()
+        .saturated_queue_len(Some(2))
+        .saturated_queue_heartbeats(1)
+        .slow_peer_policy(SlowPeerPolicy::Disconnect)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, _) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    gs.outbound_queue_lens
+        .insert(peers[0], Arc::new(AtomicUsize::new(3)));
+    gs.heartbeat();
+
+    let disconnected = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::CloseConnection {
+                peer_id,
+                connection: CloseConnection::All,
+            } => Some(*peer_id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(disconnected, vec![peers[0]]);
+}
+
+#[test]
+fn test_sharded_topics_are_rotated() {
+    let (mut gs, _, _) = inject_nodes1()
+        .peer_no(0)
+        .topics(Vec::new())
+        .to_subscribe(false)
+        .create_network();
+
+    let genesis = SystemTime::now();
+    let sharded = ShardedTopics::new("subnet_", 4, Duration::from_secs(100))
+        .with_genesis(genesis)
+        .with_warm_up(Duration::from_secs(10))
+        .with_shard_fn(|key, epoch| key[0] as u64 + epoch);
+    let subnet = |shard| sharded.topic(shard).hash();
+    let subscribed = |gs: &Behaviour| {
+        let mut topics = gs.mesh.keys().map(|t| t.to_string()).collect::<Vec<_>>();
+        topics.sort();
+        topics
+    };
+
+    // The topic of the next epoch is subscribed independently of the sharding.
+    gs.subscribe(&sharded.topic(2)).unwrap();
+    assert_eq!(
+        gs.subscribe_sharded(&sharded, vec![1]).unwrap(),
+        vec![subnet(1)]
+    );
+    assert_eq!(subscribed(&gs), vec!["subnet_1", "subnet_2"]);
+
+    // Warming up for the next epoch.
+    gs.rotate_sharded_topics("subnet_", genesis + Duration::from_secs(95));
+    assert_eq!(subscribed(&gs), vec!["subnet_1", "subnet_2"]);
+    assert_eq!(gs.sharded_topics("subnet_").count(), 1);
+
+    gs.rotate_sharded_topics("subnet_", genesis + Duration::from_secs(100));
+    assert_eq!(subscribed(&gs), vec!["subnet_2"]);
+
+    gs.rotate_sharded_topics("subnet_", genesis + Duration::from_secs(200));
+    assert_eq!(subscribed(&gs), vec!["subnet_2", "subnet_3"]);
+
+    // Topics subscribed independently of the sharding are kept.
+    gs.rotate_sharded_topics("subnet_", genesis + Duration::from_secs(300));
+    assert_eq!(subscribed(&gs), vec!["subnet_0", "subnet_2"]);
+    assert!(gs.unsubscribe_sharded("subnet_", &[1]));
+    assert_eq!(subscribed(&gs), vec!["subnet_2"]);
+    assert_eq!(gs.sharded_topics("subnet_").count(), 0);
+}
+
+#[test]
+fn test_topic_peer_allowlist() {
+    let topic = Topic::new("control");
+    let allowed = Arc::new(Mutex::new(HashSet::new()));
+    let config = ConfigBuilder::default()
+        .topic_peer_allowlist(topic.hash(), {
+            let allowed = allowed.clone();
+            move |peer_id| allowed.lock().unwrap().contains(peer_id)
+        })
+        .build()
+        .unwrap();
+
+    let (mut gs, _, topics) = inject_nodes1()
+        .peer_no(0)
+        .topics(vec!["control".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    let trusted = add_peer(&mut gs, &[], false, false);
+    let untrusted = add_peer(&mut gs, &[], false, false);
+    allowed.lock().unwrap().insert(trusted);
+
+    let subscription = Subscription {
+        action: SubscriptionAction::Subscribe,
+        topic_hash: topics[0].clone(),
+    };
+    gs.handle_received_subscriptions(std::slice::from_ref(&subscription), &trusted);
+    gs.handle_received_subscriptions(&[subscription], &untrusted);
+    assert!(gs.topic_peers[&topics[0]].contains(&trusted));
+    assert!(!gs.topic_peers[&topics[0]].contains(&untrusted));
+    assert!(gs.mesh[&topics[0]].contains(&trusted));
+    flush_events(&mut gs);
+
+    // The graft of the untrusted peer is answered with a prune.
+    gs.handle_graft(&untrusted, vec![topics[0].clone()]);
+    assert!(!gs.mesh[&topics[0]].contains(&untrusted));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &untrusted
+            && matches!(m, ControlAction::Prune { .. })),
+        1
+    );
+    flush_events(&mut gs);
+
+    // Messages are only published to the trusted peer, even when asked for.
+    let msg_id = gs
+        .publish_to(topic.clone(), vec![1], [trusted, untrusted])
+        .unwrap();
+    let published_to = |gs: &Behaviour| {
+        gs.events
+            .iter()
+            .filter_map(|e| match e {
+                ToSwarm::NotifyHandler {
+                    peer_id,
+                    event: HandlerIn::Message(RpcOut::Publish { .. } | RpcOut::Forward(_)),
+                    ..
+                } => Some(*peer_id),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(published_to(&gs), vec![trusted]);
+    flush_events(&mut gs);
+
+    gs.handle_iwant(&untrusted, vec![msg_id]);
+    assert!(published_to(&gs).is_empty());
+}
+
+#[test]
+fn test_relayed_mesh_policy() {
+    let relayed_address: Multiaddr = "/ip4/127.0.0.1/tcp/1234/p2p-circuit".parse().unwrap();
+    let network = |policy| {
+        let config = ConfigBuilder::default()
+            .relayed_mesh_policy(policy)
+            .build()
+            .unwrap();
+        let (mut gs, _, topics) = inject_nodes1()
+            .peer_no(0)
+            .topics(vec!["topic".into()])
+            .gs_config(config)
+            .create_network();
+        let add = |gs: &mut Behaviour, address: &Multiaddr| {
+            add_peer_with_addr_and_kind(
+                gs,
+                &topics,
+                true,
+                false,
+                address.clone(),
+                Some(PeerKind::Gossipsubv1_1),
+            )
+        };
+        let relayed = (0..3)
+            .map(|_| add(&mut gs, &relayed_address))
+            .collect::<BTreeSet<_>>();
+        let direct = add(&mut gs, &Multiaddr::empty());
+        (gs, topics[0].clone(), relayed, direct)
+    };
+
+    // Relayed peers are neither grafted nor accepted in the mesh.
+    let (mut gs, topic, relayed, direct) = network(RelayedMeshPolicy::Forbid);
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topic], BTreeSet::from([direct]));
+    flush_events(&mut gs);
+    let peer = *relayed.first().unwrap();
+    gs.handle_graft(&peer, vec![topic.clone()]);
+    assert!(!gs.mesh[&topic].contains(&peer));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &peer
+            && matches!(m, ControlAction::Prune { .. })),
+        1
+    );
+
+    // Relayed peers fill the mesh if not enough direct peers are available.
+    let (mut gs, topic, relayed, direct) = network(RelayedMeshPolicy::PreferDirect);
+    assert_eq!(gs.mesh[&topic], BTreeSet::from([direct]));
+    gs.heartbeat();
+    assert!(gs.mesh[&topic].is_superset(&relayed));
+
+    // Peers hinted as metered are treated like relayed ones.
+    let (mut gs, topic, _, direct) = network(RelayedMeshPolicy::Forbid);
+    assert!(gs.set_peer_metered(&direct, true));
+    gs.mesh.get_mut(&topic).unwrap().clear();
+    gs.heartbeat();
+    assert!(gs.mesh[&topic].is_empty());
+}
+
+#[test]
+fn test_expired_messages_are_not_propagated() {
+    let topic = Topic::new("realtime");
+    let config = ConfigBuilder::default()
+        .topic_message_ttl(topic.hash(), Duration::from_secs(10))
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(3)
+        .topics(vec![String::from("realtime")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let forwarded = |gs: &Behaviour| {
+        gs.events
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    ToSwarm::NotifyHandler {
+                        event: HandlerIn::Message(RpcOut::Publish(_) | RpcOut::Forward(_)),
+                        ..
+                    }
+                )
+            })
+            .count()
+    };
+
+    // Published messages carry the deadline.
+    gs.publish(topic.clone(), vec![1]).unwrap();
+    let now = unix_millis(SystemTime::now());
+    let published = gs
+        .events
+        .iter()
+        .find_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                event: HandlerIn::Message(RpcOut::Publish(message)),
+                ..
+            } => Some(message.clone()),
+            _ => None,
+        })
+        .unwrap();
+    let expires_at = published.expires_at.unwrap();
+    assert!(expires_at > now && expires_at <= now + 10_000);
+    flush_events(&mut gs);
+
+    // Expired messages are neither forwarded nor delivered.
+    let mut seq = 0;
+    let mut expired = random_message(&mut seq, &topic_hashes);
+    expired.expires_at = Some(now - 1);
+    gs.handle_received_message(expired.clone(), &peers[0]);
+    assert_eq!(forwarded(&gs), 0);
+    assert!(!gs
+        .events
+        .iter()
+        .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::Message { .. }))));
+
+    let mut pending = random_message(&mut seq, &topic_hashes);
+    pending.expires_at = Some(now + 60_000);
+    gs.handle_received_message(pending.clone(), &peers[0]);
+    assert_eq!(forwarded(&gs), 2);
+
+    // Once expired, messages are dropped from the cache.
+    let msg_id = gs.config.message_id(&Message {
+        source: pending.source,
+        data: pending.data.clone(),
+        sequence_number: pending.sequence_number,
+        topic: pending.topic.clone(),
+    });
+    assert!(gs.mcache.get(&msg_id).is_some());
+    gs.mcache
+        .remove_expired(SystemTime::now() + Duration::from_secs(60));
+    assert!(gs.mcache.get(&msg_id).is_none());
+}
+
+#[test]
+fn test_large_messages_are_chunked_and_reassembled() {
+    let config = ConfigBuilder::default()
+        .max_transmit_size(500)
+        .max_chunked_message_size(Some(10_000))
+        .validate_messages()
+        .build()
+        .unwrap();
+    let network = || {
+        inject_nodes1()
+            .peer_no(3)
+            .topics(vec![String::from("large")])
+            .to_subscribe(true)
+            .gs_config(config.clone())
+            .create_network()
+    };
+    let (mut publisher, _, topic_hashes) = network();
+    let (mut receiver, peers, _) = network();
+
+    let data = (0..2000).map(|i| i as u8).collect::<Vec<_>>();
+    let group = publisher
+        .publish(topic_hashes[0].clone(), data.clone())
+        .unwrap();
+    let mut chunks = publisher
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::Message(RpcOut::Publish(message)),
+                ..
+            } if *peer_id == publisher.mesh[&topic_hashes[0]].first().copied().unwrap() => {
+                Some(message.clone())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().enumerate().all(|(i, chunk)| {
+        chunk.raw_protobuf_len() <= 500
+            && chunk.chunk
+                == Some(Chunk {
+                    group: group.clone(),
+                    index: i as u32,
+                    count: chunks.len() as u32,
+                })
+    }));
+
+    // Larger messages are still rejected.
+    assert!(matches!(
+        publisher.publish(topic_hashes[0].clone(), vec![0; 10_001]),
+        Err(PublishError::MessageTooLarge)
+    ));
+
+    // The message is delivered once all chunks are received, in any order.
+    let delivered = |gs: &Behaviour| {
+        gs.events
+            .iter()
+            .filter_map(|e| match e {
+                ToSwarm::GenerateEvent(Event::Message {
+                    message_id,
+                    message,
+                    ..
+                }) => Some((message_id.clone(), message.data.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let last = chunks.remove(0);
+    for chunk in chunks.iter().rev() {
+        receiver.handle_received_message(chunk.clone(), &peers[0]);
+    }
+    assert!(delivered(&receiver).is_empty());
+    receiver.handle_received_message(last, &peers[1]);
+    assert_eq!(delivered(&receiver), vec![(group.clone(), data)]);
+    flush_events(&mut receiver);
+
+    // Validating the message forwards its chunks.
+    assert!(receiver
+        .report_message_validation_result(&group, &peers[1], MessageAcceptance::Accept)
+        .unwrap());
+    let forwarded = receiver
+        .events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ToSwarm::NotifyHandler {
+                    event: HandlerIn::Message(RpcOut::Forward(_)),
+                    ..
+                }
+            )
+        })
+        .count();
+    assert!(forwarded > chunks.len());
+}
+
+#[test]
+fn test_publish_with_delivery_report() {
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(5)
+        .topics(vec!["topic".into()])
+        .to_subscribe(true)
+        .create_network();
+    let topic = topic_hashes[0].clone();
+    let delivery_reports = |gs: &mut Behaviour| {
+        gs.events
+            .drain(..)
+            .filter_map(|e| match e {
+                ToSwarm::GenerateEvent(Event::DeliveryReport {
+                    message_id,
+                    outcome,
+                    peers,
+                }) => Some((message_id, outcome, peers)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let msg_id = gs
+        .publish_with_delivery_report(topic.clone(), vec![1], 2, Duration::from_secs(10))
+        .unwrap();
+    let recipients = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::NotifyHandler {
+                peer_id,
+                event: HandlerIn::ReportedMessage { message_id, .. },
+                ..
+            } if message_id == &msg_id => Some(*peer_id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert!(recipients.len() >= 2);
+    flush_events(&mut gs);
+
+    // The message is delivered once written to two of its recipients.
+    let connection_id = ConnectionId::new_unchecked(0);
+    gs.on_connection_handler_event(
+        recipients[0],
+        connection_id,
+        HandlerEvent::MessagesSent(vec![msg_id.clone()]),
+    );
+    assert!(delivery_reports(&mut gs).is_empty());
+    gs.on_connection_handler_event(
+        recipients[1],
+        connection_id,
+        HandlerEvent::MessagesSent(vec![msg_id.clone()]),
+    );
+    assert_eq!(
+        delivery_reports(&mut gs),
+        vec![(
+            msg_id,
+            DeliveryOutcome::Delivered,
+            vec![recipients[0], recipients[1]]
+        )]
+    );
+
+    // The delivery fails once too few recipients remain.
+    let msg_id = gs
+        .publish_with_delivery_report(
+            topic.clone(),
+            vec![2],
+            recipients.len(),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+    flush_events(&mut gs);
+    gs.on_connection_handler_event(
+        recipients[0],
+        connection_id,
+        HandlerEvent::MessagesDropped(vec![msg_id.clone()]),
+    );
+    assert_eq!(
+        delivery_reports(&mut gs),
+        vec![(msg_id, DeliveryOutcome::Failed, vec![])]
+    );
+
+    // The delivery times out at the next heartbeat.
+    let msg_id = gs
+        .publish_with_delivery_report(topic, vec![3], 1, Duration::ZERO)
+        .unwrap();
+    flush_events(&mut gs);
+    gs.heartbeat();
+    assert_eq!(
+        delivery_reports(&mut gs),
+        vec![(msg_id, DeliveryOutcome::TimedOut, vec![])]
+    );
+}
+
+#[test]
+fn test_delivery_latency_is_measured_per_topic() {
+    let config = ConfigBuilder::default()
+        .delivery_timestamp_fn(|message| {
+            let millis = u64::from_be_bytes(message.data.get(..8)?.try_into().ok()?);
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(millis))
+        })
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("latency")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    let topic = &topic_hashes[0];
+    assert!(gs.delivery_latency(topic).is_none());
+
+    let now = unix_millis(SystemTime::now());
+    let mut seq = 0;
+    for data in [
+        (now - 100).to_be_bytes().to_vec(),
+        (now - 300).to_be_bytes().to_vec(),
+        // Messages without a timestamp or with one in the future are not measured.
+        vec![1],
+        (now + 60_000).to_be_bytes().to_vec(),
+    ] {
+        let mut message = random_message(&mut seq, &topic_hashes);
+        message.data = data;
+        gs.handle_received_message(message, &peers[0]);
+    }
+
+    let latency = gs.delivery_latency(topic).unwrap();
+    assert_eq!(latency.samples, 2);
+    assert!(latency.p50 >= Duration::from_millis(100));
+    assert!(latency.max >= Duration::from_millis(300) && latency.max < Duration::from_secs(10));
+
+    // The latencies are forgotten along with the topic.
+    gs.unsubscribe(&Topic::new("latency")).unwrap();
+    assert!(gs.delivery_latency(topic).is_none());
+}
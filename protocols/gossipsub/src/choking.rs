@@ -0,0 +1,266 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! State of the choking extension, in which mesh peers persistently delivering duplicates are
+//! asked to only announce their messages through `IHAVE`s.
+
+use crate::{MessageId, TopicHash};
+use libp2p_identity::PeerId;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
+use web_time::Instant;
+
+/// The messages delivered or announced by a mesh peer since the last evaluation.
+#[derive(Debug, Default)]
+struct Deliveries {
+    /// The number of messages delivered or announced.
+    messages: usize,
+    /// The number of delivered messages that were already seen.
+    duplicates: usize,
+    /// The total time elapsed between the first delivery of the messages and their delivery or
+    /// announcement by the peer.
+    delay: Duration,
+}
+
+impl Deliveries {
+    /// The average time elapsed between the first delivery of a message and its delivery or
+    /// announcement by the peer, `Duration::MAX` if the peer delivered nothing.
+    fn average_delay(&self) -> Duration {
+        if self.messages == 0 {
+            return Duration::MAX;
+        }
+        self.delay / self.messages as u32
+    }
+}
+
+/// Mesh peers to choke and unchoke in a topic, as decided by [`Choking::evaluate`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ChokeDecision {
+    pub(crate) choke: Vec<PeerId>,
+    pub(crate) unchoke: Vec<PeerId>,
+}
+
+/// Tracks the choked mesh peers in both directions and the deliveries of mesh peers.
+#[derive(Debug, Default)]
+pub(crate) struct Choking {
+    /// The mesh peers we choked, per topic.
+    choked: HashMap<TopicHash, HashSet<PeerId>>,
+    /// The mesh peers that choked us, per topic.
+    choked_by: HashMap<TopicHash, HashSet<PeerId>>,
+    /// The time recently received messages were first delivered.
+    first_seen: HashMap<MessageId, Instant>,
+    /// The deliveries of each mesh peer since the last evaluation, per topic.
+    deliveries: HashMap<TopicHash, HashMap<PeerId, Deliveries>>,
+}
+
+impl Choking {
+    /// Returns whether we choked the peer in the topic.
+    pub(crate) fn is_choked(&self, topic: &TopicHash, peer: &PeerId) -> bool {
+        self.choked
+            .get(topic)
+            .is_some_and(|peers| peers.contains(peer))
+    }
+
+    /// Returns whether the peer choked us in the topic.
+    pub(crate) fn is_choked_by(&self, topic: &TopicHash, peer: &PeerId) -> bool {
+        self.choked_by
+            .get(topic)
+            .is_some_and(|peers| peers.contains(peer))
+    }
+
+    /// Records whether the peer choked us in the topic.
+    pub(crate) fn set_choked_by(&mut self, topic: TopicHash, peer: PeerId, choked: bool) {
+        if choked {
+            self.choked_by.entry(topic).or_default().insert(peer);
+        } else if let Some(peers) = self.choked_by.get_mut(&topic) {
+            peers.remove(&peer);
+        }
+    }
+
+    /// Records the delivery of a message by a peer.
+    pub(crate) fn delivered(
+        &mut self,
+        topic: &TopicHash,
+        peer: &PeerId,
+        message: &MessageId,
+        duplicate: bool,
+        now: Instant,
+    ) {
+        let delay = if duplicate {
+            // The first delivery is no longer tracked.
+            let Some(first_seen) = self.first_seen.get(message) else {
+                return;
+            };
+            now.saturating_duration_since(*first_seen)
+        } else {
+            self.first_seen.insert(message.clone(), now);
+            Duration::ZERO
+        };
+        let deliveries = self
+            .deliveries
+            .entry(topic.clone())
+            .or_default()
+            .entry(*peer)
+            .or_default();
+        deliveries.messages += 1;
+        deliveries.duplicates += usize::from(duplicate);
+        deliveries.delay += delay;
+    }
+
+    /// Records the announcement of messages by a peer we choked. Messages that were not seen
+    /// before count as if the peer delivered them first.
+    pub(crate) fn announced(
+        &mut self,
+        topic: &TopicHash,
+        peer: &PeerId,
+        messages: &[MessageId],
+        now: Instant,
+    ) {
+        if !self.is_choked(topic, peer) {
+            return;
+        }
+        let deliveries = self
+            .deliveries
+            .entry(topic.clone())
+            .or_default()
+            .entry(*peer)
+            .or_default();
+        for message in messages {
+            deliveries.messages += 1;
+            if let Some(first_seen) = self.first_seen.get(message) {
+                deliveries.delay += now.saturating_duration_since(*first_seen);
+            }
+        }
+    }
+
+    /// Decides which peers of the mesh of a topic to choke and unchoke, based on their deliveries
+    /// since the last evaluation, and resets the deliveries of the topic.
+    ///
+    /// At most one choked peer is unchoked per evaluation: the one with the lowest average delay,
+    /// if it announced messages faster than the slowest unchoked peer delivered them, or if less
+    /// than `unchoked_min` peers are unchoked. Unchoked peers of which at least `duplicate_ratio`
+    /// of at least `min_deliveries` messages were duplicates are then choked, slowest first, as
+    /// long as more than `unchoked_min` peers remain unchoked.
+    pub(crate) fn evaluate(
+        &mut self,
+        topic: &TopicHash,
+        mesh: &BTreeSet<PeerId>,
+        duplicate_ratio: f64,
+        min_deliveries: usize,
+        unchoked_min: usize,
+    ) -> ChokeDecision {
+        let deliveries = self.deliveries.remove(topic).unwrap_or_default();
+        let average_delay = |peer: &PeerId| {
+            deliveries
+                .get(peer)
+                .map_or(Duration::MAX, Deliveries::average_delay)
+        };
+        let choked = self.choked.entry(topic.clone()).or_default();
+        choked.retain(|peer| mesh.contains(peer));
+        let mut decision = ChokeDecision::default();
+
+        let mut unchoked = mesh.len() - choked.len();
+        let fastest_choked = choked
+            .iter()
+            .min_by_key(|peer| (average_delay(peer), **peer))
+            .copied();
+        if let Some(peer) = fastest_choked {
+            let slowest_unchoked = mesh
+                .iter()
+                .filter(|peer| !choked.contains(peer))
+                .map(average_delay)
+                .max();
+            if unchoked < unchoked_min
+                || slowest_unchoked.is_some_and(|slowest| average_delay(&peer) < slowest)
+            {
+                choked.remove(&peer);
+                unchoked += 1;
+                decision.unchoke.push(peer);
+            }
+        }
+
+        let mut candidates = mesh
+            .iter()
+            .filter(|peer| !choked.contains(peer) && !decision.unchoke.contains(peer))
+            .filter(|peer| {
+                deliveries.get(peer).is_some_and(|d| {
+                    d.messages >= min_deliveries
+                        && d.duplicates as f64 >= duplicate_ratio * d.messages as f64
+                })
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|peer| std::cmp::Reverse((average_delay(peer), *peer)));
+        for peer in candidates {
+            if unchoked <= unchoked_min {
+                break;
+            }
+            choked.insert(peer);
+            unchoked -= 1;
+            decision.choke.push(peer);
+        }
+
+        decision
+    }
+
+    /// Forgets about the first deliveries of messages older than `max_age` and about the topics
+    /// we are no longer subscribed to.
+    pub(crate) fn heartbeat(
+        &mut self,
+        mesh: &HashMap<TopicHash, BTreeSet<PeerId>>,
+        max_age: Duration,
+        now: Instant,
+    ) {
+        self.first_seen
+            .retain(|_, first_seen| now.saturating_duration_since(*first_seen) < max_age);
+        self.choked.retain(|topic, _| mesh.contains_key(topic));
+        self.deliveries.retain(|topic, _| mesh.contains_key(topic));
+        for (topic, peers) in self.choked_by.iter_mut() {
+            match mesh.get(topic) {
+                Some(mesh_peers) => peers.retain(|peer| mesh_peers.contains(peer)),
+                None => peers.clear(),
+            }
+        }
+        self.choked_by.retain(|_, peers| !peers.is_empty());
+    }
+
+    /// Forgets whether the peer is choked in the topic, in both directions.
+    pub(crate) fn reset(&mut self, topic: &TopicHash, peer: &PeerId) {
+        if let Some(peers) = self.choked.get_mut(topic) {
+            peers.remove(peer);
+        }
+        if let Some(peers) = self.choked_by.get_mut(topic) {
+            peers.remove(peer);
+        }
+    }
+
+    /// Forgets about a disconnected peer.
+    pub(crate) fn remove_peer(&mut self, peer: &PeerId) {
+        for peers in self.choked.values_mut() {
+            peers.remove(peer);
+        }
+        for peers in self.choked_by.values_mut() {
+            peers.remove(peer);
+        }
+        for deliveries in self.deliveries.values_mut() {
+            deliveries.remove(peer);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Messages exceeding the maximum transmission size, split into chunks published as messages of
+//! their own and reassembled by the receivers.
+
+use crate::rpc_proto::proto;
+use crate::types::{MessageId, RawMessage};
+use crate::TopicHash;
+use libp2p_identity::PeerId;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use web_time::Instant;
+
+/// The position of a message in a chunked message, see
+/// [`ConfigBuilder::max_chunked_message_size`](crate::ConfigBuilder::max_chunked_message_size).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Chunk {
+    /// The id of the chunked message, shared by all of its chunks. Starts with the peer id of the
+    /// source of the chunked message.
+    pub group: MessageId,
+    /// The index of the chunk, starting at zero.
+    pub index: u32,
+    /// The number of chunks of the chunked message.
+    pub count: u32,
+}
+
+impl Chunk {
+    /// The message id of the chunk, the group followed by the big-endian index.
+    pub fn message_id(&self) -> MessageId {
+        let mut id = self.group.0.clone();
+        id.extend_from_slice(&self.index.to_be_bytes());
+        MessageId(id)
+    }
+
+    /// Returns whether the chunk lies within its chunked message and the chunked message was
+    /// published by the `source` of the chunk.
+    pub(crate) fn is_valid(&self, source: Option<&[u8]>) -> bool {
+        let is_own_group = source.is_some_and(|source| {
+            self.group.0.len() > source.len() && self.group.0.starts_with(source)
+        });
+
+        is_own_group && self.index < self.count
+    }
+}
+
+impl From<Chunk> for proto::ChunkInfo {
+    fn from(chunk: Chunk) -> Self {
+        proto::ChunkInfo {
+            group: Some(chunk.group.0),
+            index: Some(chunk.index),
+            count: Some(chunk.count),
+        }
+    }
+}
+
+impl From<proto::ChunkInfo> for Chunk {
+    fn from(info: proto::ChunkInfo) -> Self {
+        Chunk {
+            group: MessageId(info.group.unwrap_or_default()),
+            index: info.index.unwrap_or_default(),
+            count: info.count.unwrap_or_default(),
+        }
+    }
+}
+
+/// Why a received chunk was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChunkError {
+    /// The chunked message exceeds the maximum size, it is dropped altogether.
+    TooLarge,
+    /// Too many chunked messages are being reassembled already.
+    TooManyPending,
+    /// The chunk disagrees with the previous chunks of its message on the topic, source or count.
+    Inconsistent,
+}
+
+/// A chunked message all of whose chunks were received.
+#[derive(Debug)]
+pub(crate) struct Reassembled {
+    pub(crate) group: MessageId,
+    pub(crate) source: Option<PeerId>,
+    pub(crate) topic: TopicHash,
+    pub(crate) data: Vec<u8>,
+    /// The ids of the chunks along with the peers they were received from.
+    pub(crate) chunks: Vec<(MessageId, PeerId)>,
+}
+
+#[derive(Debug)]
+struct PendingMessage {
+    source: Option<PeerId>,
+    topic: TopicHash,
+    count: u32,
+    chunks: BTreeMap<u32, (Vec<u8>, PeerId)>,
+    size: usize,
+    started: Instant,
+}
+
+/// Reassembles received chunked messages, within limits on their size, their number and the time
+/// until all of their chunks are received.
+#[derive(Debug)]
+pub(crate) struct Reassembly {
+    pending: HashMap<MessageId, PendingMessage>,
+    max_size: usize,
+    max_pending: usize,
+    timeout: Duration,
+}
+
+impl Reassembly {
+    pub(crate) fn new(max_size: usize, max_pending: usize, timeout: Duration) -> Self {
+        Self {
+            pending: HashMap::new(),
+            max_size,
+            max_pending,
+            timeout,
+        }
+    }
+
+    /// Adds a received chunk, returning the chunked message once all of its chunks are received.
+    /// Duplicate chunks are expected to be filtered beforehand.
+    pub(crate) fn insert(
+        &mut self,
+        chunk: &Chunk,
+        message: &RawMessage,
+        propagation_source: PeerId,
+        now: Instant,
+    ) -> Result<Option<Reassembled>, ChunkError> {
+        // Every chunk carries at least one byte.
+        if chunk.count as usize > self.max_size {
+            return Err(ChunkError::TooLarge);
+        }
+
+        if !self.pending.contains_key(&chunk.group) && self.pending.len() >= self.max_pending {
+            return Err(ChunkError::TooManyPending);
+        }
+        let pending = self
+            .pending
+            .entry(chunk.group.clone())
+            .or_insert_with(|| PendingMessage {
+                source: message.source,
+                topic: message.topic.clone(),
+                count: chunk.count,
+                chunks: BTreeMap::new(),
+                size: 0,
+                started: now,
+            });
+
+        if pending.source != message.source
+            || pending.topic != message.topic
+            || pending.count != chunk.count
+        {
+            return Err(ChunkError::Inconsistent);
+        }
+
+        pending.size += message.data.len();
+        if pending.size > self.max_size {
+            self.pending.remove(&chunk.group);
+            return Err(ChunkError::TooLarge);
+        }
+        pending
+            .chunks
+            .insert(chunk.index, (message.data.clone(), propagation_source));
+
+        if pending.chunks.len() < pending.count as usize {
+            return Ok(None);
+        }
+
+        let pending = self
+            .pending
+            .remove(&chunk.group)
+            .expect("pending message to exist");
+        let mut data = Vec::with_capacity(pending.size);
+        let mut chunks = Vec::with_capacity(pending.chunks.len());
+        for (index, (chunk_data, peer)) in pending.chunks {
+            data.extend_from_slice(&chunk_data);
+            let chunk = Chunk {
+                group: chunk.group.clone(),
+                index,
+                count: pending.count,
+            };
+            chunks.push((chunk.message_id(), peer));
+        }
+
+        Ok(Some(Reassembled {
+            group: chunk.group.clone(),
+            source: pending.source,
+            topic: pending.topic,
+            data,
+            chunks,
+        }))
+    }
+
+    /// Drops the chunked messages whose reassembly timed out, returning their number.
+    pub(crate) fn remove_expired(&mut self, now: Instant) -> usize {
+        let len = self.pending.len();
+        let timeout = self.timeout;
+        self.pending
+            .retain(|_, pending| now.duration_since(pending.started) < timeout);
+        len - self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_message(index: u32, count: u32, data: &[u8]) -> (Chunk, RawMessage) {
+        let chunk = Chunk {
+            group: MessageId::new(b"group"),
+            index,
+            count,
+        };
+        let message = RawMessage {
+            source: None,
+            data: data.to_vec(),
+            sequence_number: None,
+            topic: TopicHash::from_raw("topic"),
+            signature: None,
+            key: None,
+            validated: false,
+            expires_at: None,
+            chunk: Some(chunk.clone()),
+        };
+        (chunk, message)
+    }
+
+    #[test]
+    fn chunks_are_reassembled_in_order() {
+        let mut reassembly = Reassembly::new(100, 1, Duration::from_secs(10));
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        let (chunk, message) = chunk_message(1, 2, b"world");
+        assert!(reassembly
+            .insert(&chunk, &message, peer, now)
+            .unwrap()
+            .is_none());
+        let (chunk, message) = chunk_message(0, 2, b"hello ");
+        let reassembled = reassembly
+            .insert(&chunk, &message, peer, now)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(reassembled.data, b"hello world");
+        assert_eq!(reassembled.group, MessageId::new(b"group"));
+        assert_eq!(
+            reassembled.chunks,
+            vec![
+                (MessageId::new(b"group\0\0\0\0"), peer),
+                (MessageId::new(b"group\0\0\0\x01"), peer)
+            ]
+        );
+        assert!(reassembly.pending.is_empty());
+    }
+
+    #[test]
+    fn chunks_must_belong_to_a_group_of_their_source() {
+        let source = PeerId::random().to_bytes();
+        let mut group = source.clone();
+        group.extend_from_slice(b"group");
+        let chunk = Chunk {
+            group: MessageId(group),
+            index: 0,
+            count: 1,
+        };
+
+        assert!(chunk.is_valid(Some(&source)));
+        assert!(!chunk.is_valid(Some(&PeerId::random().to_bytes())));
+        assert!(!chunk.is_valid(None));
+        assert!(!Chunk { index: 1, ..chunk }.is_valid(Some(&source)));
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let mut reassembly = Reassembly::new(8, 1, Duration::from_secs(10));
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        let (chunk, message) = chunk_message(0, 9, b"a");
+        assert_eq!(
+            reassembly.insert(&chunk, &message, peer, now).unwrap_err(),
+            ChunkError::TooLarge
+        );
+
+        let (chunk, message) = chunk_message(0, 2, b"hello");
+        assert!(reassembly
+            .insert(&chunk, &message, peer, now)
+            .unwrap()
+            .is_none());
+        let (mut chunk, mut message) = chunk_message(0, 2, b"hello");
+        chunk.group = MessageId::new(b"other");
+        message.chunk = Some(chunk.clone());
+        assert_eq!(
+            reassembly.insert(&chunk, &message, peer, now).unwrap_err(),
+            ChunkError::TooManyPending
+        );
+        let (chunk, message) = chunk_message(1, 3, b"!");
+        assert_eq!(
+            reassembly.insert(&chunk, &message, peer, now).unwrap_err(),
+            ChunkError::Inconsistent
+        );
+        let (chunk, message) = chunk_message(1, 2, b"world");
+        assert_eq!(
+            reassembly.insert(&chunk, &message, peer, now).unwrap_err(),
+            ChunkError::TooLarge
+        );
+        assert!(reassembly.pending.is_empty());
+
+        let (chunk, message) = chunk_message(0, 2, b"hello");
+        reassembly.insert(&chunk, &message, peer, now).unwrap();
+        assert_eq!(reassembly.remove_expired(now + Duration::from_secs(5)), 0);
+        assert_eq!(reassembly.remove_expired(now + Duration::from_secs(10)), 1);
+    }
+}
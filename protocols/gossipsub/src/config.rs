@@ -19,15 +19,21 @@
 // DEALINGS IN THE SOFTWARE.
 
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::error::ConfigBuilderError;
 use crate::protocol::{ProtocolConfig, ProtocolId, FLOODSUB_PROTOCOL};
+use crate::topic::TopicHash;
 use crate::types::{Message, MessageId, PeerKind};
 
+use libp2p_core::Multiaddr;
 use libp2p_identity::PeerId;
 use libp2p_swarm::StreamProtocol;
+use prometheus_client::encoding::EncodeLabelValue;
+use web_time::SystemTime;
 
 /// The types of message validation that can be employed by gossipsub.
 #[derive(Debug, Clone)]
@@ -52,11 +58,45 @@ pub enum ValidationMode {
     None,
 }
 
+/// How to treat a peer whose outbound queue stays saturated, see [`Config::slow_peer_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+pub enum SlowPeerPolicy {
+    /// Only report the peer via an
+    /// [`Event::OutboundQueueSaturated`](crate::Event::OutboundQueueSaturated). This is the
+    /// default.
+    Report,
+    /// Prune the peer from all meshes, without peer exchange. The peer may be grafted again once
+    /// the prune backoff expired.
+    Prune,
+    /// Only announce messages to the peer through `IHAVE`s instead of forwarding them, as long as
+    /// its queue stays saturated. The peer stays in the meshes.
+    GossipOnly,
+    /// Disconnect from the peer.
+    Disconnect,
+}
+
+/// Whether peers only connected through relays are grafted to the meshes, see
+/// [`Config::relayed_mesh_policy`].
+///
+/// Relayed mesh links consume the bandwidth of the relay and add latency. Peers whose connection
+/// was hinted as metered via [`Behaviour::set_peer_metered`](crate::Behaviour::set_peer_metered)
+/// are treated the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelayedMeshPolicy {
+    /// Treat relayed peers like any other peer. This is the default.
+    Allow,
+    /// Only graft relayed peers if not enough directly connected peers are available.
+    PreferDirect,
+    /// Never graft relayed peers, and answer their GRAFTs with a PRUNE.
+    Forbid,
+}
+
 /// Selector for custom Protocol Id
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Version {
     V1_0,
     V1_1,
+    V1_2,
 }
 
 /// Configuration parameters that define the performance of the gossipsub network.
@@ -75,26 +115,69 @@ pub struct Config {
     heartbeat_interval: Duration,
     fanout_ttl: Duration,
     check_explicit_peers_ticks: u64,
+    explicit_peer_replay_window: Option<Duration>,
+    explicit_peer_replay_max_messages: usize,
     duplicate_cache_time: Duration,
     validate_messages: bool,
+    max_concurrent_validations: usize,
+    validation_timeout: Duration,
     message_id_fn: Arc<dyn Fn(&Message) -> MessageId + Send + Sync + 'static>,
+    custom_message_id_fn: bool,
+    topic_message_id_fns: HashMap<TopicHash, Arc<dyn Fn(&Message) -> MessageId + Send + Sync>>,
+    topic_peer_allowlists: HashMap<TopicHash, Arc<dyn Fn(&PeerId) -> bool + Send + Sync>>,
+    topic_message_ttls: HashMap<TopicHash, Duration>,
+    max_chunked_message_size: Option<usize>,
+    chunk_reassembly_timeout: Duration,
+    max_pending_chunked_messages: usize,
     allow_self_origin: bool,
     do_px: bool,
     prune_peers: usize,
+    px_require_signed_peer_records: bool,
+    px_dials_per_prune: Option<usize>,
+    px_dial_jitter: Duration,
+    max_px_dials_per_heartbeat: Option<usize>,
+    px_dial_filter: Option<Arc<dyn Fn(&PeerId, &[Multiaddr]) -> bool + Send + Sync>>,
     prune_backoff: Duration,
     unsubscribe_backoff: Duration,
+    unsubscribe_drain_timeout: Duration,
     backoff_slack: u32,
     flood_publish: bool,
     graft_flood_threshold: Duration,
     mesh_outbound_min: usize,
     opportunistic_graft_ticks: u64,
     opportunistic_graft_peers: usize,
+    opportunistic_graft_threshold: Option<f64>,
     gossip_retransimission: u32,
     max_messages_per_rpc: Option<usize>,
+    idontwant_message_size_threshold: usize,
     max_ihave_length: usize,
     max_ihave_messages: usize,
+    max_sent_ihave_messages: usize,
+    max_iwant_responses: Option<usize>,
+    control_messages_per_second: Option<u32>,
+    control_messages_burst: u32,
     iwant_followup_time: Duration,
     published_message_ids_cache_time: Duration,
+    stale_mesh_peer_timeout: Option<Duration>,
+    mesh_reconnect_window: Option<Duration>,
+    mesh_events: bool,
+    saturated_queue_len: Option<usize>,
+    saturated_queue_heartbeats: usize,
+    slow_peer_policy: SlowPeerPolicy,
+    relayed_mesh_policy: RelayedMeshPolicy,
+    score_report_ticks: Option<u64>,
+    rpc_batch_size: Option<usize>,
+    rpc_batch_delay: Duration,
+    outbound_queue_high_watermark: Option<usize>,
+    outbound_queue_low_watermark: usize,
+    choke_ticks: Option<u64>,
+    choke_duplicate_ratio: f64,
+    choke_min_deliveries: usize,
+    mesh_unchoked_min: usize,
+    invalid_message_reports: Option<usize>,
+    adaptive_gossip_factor: Option<RangeInclusive<f64>>,
+    delivery_timestamp_fn: Option<Arc<dyn Fn(&Message) -> Option<SystemTime> + Send + Sync>>,
+    delivery_latency_samples: usize,
 }
 
 impl Config {
@@ -172,6 +255,23 @@ impl Config {
         self.check_explicit_peers_ticks
     }
 
+    /// Time window of messages retained for a disconnected explicit peer. Messages that would have
+    /// been sent to an explicit peer while it was disconnected are replayed to it once it
+    /// reconnects, provided they were published or forwarded less than this duration ago. This
+    /// allows direct peers to catch up on messages after a transient disconnect.
+    ///
+    /// The default is `None`, i.e. no messages are retained for explicit peers.
+    pub fn explicit_peer_replay_window(&self) -> Option<Duration> {
+        self.explicit_peer_replay_window
+    }
+
+    /// The maximum number of messages retained for each disconnected explicit peer, see
+    /// [`Config::explicit_peer_replay_window`]. When exceeded, the oldest messages are dropped.
+    /// The default is 1000.
+    pub fn explicit_peer_replay_max_messages(&self) -> usize {
+        self.explicit_peer_replay_max_messages
+    }
+
     /// The maximum byte size for each gossipsub RPC (default is 65536 bytes).
     ///
     /// This represents the maximum size of the entire protobuf payload. It must be at least
@@ -199,12 +299,62 @@ impl Config {
         self.validate_messages
     }
 
+    /// The maximum number of messages validated concurrently by the validators registered via
+    /// [`Behaviour::set_topic_validator`](crate::Behaviour::set_topic_validator). Messages
+    /// received while the limit is reached are ignored without being validated. The default is
+    /// 1024.
+    pub fn max_concurrent_validations(&self) -> usize {
+        self.max_concurrent_validations
+    }
+
+    /// The time after which a pending validation by a validator registered via
+    /// [`Behaviour::set_topic_validator`](crate::Behaviour::set_topic_validator) is abandoned and
+    /// the message ignored. The default is 10 seconds.
+    pub fn validation_timeout(&self) -> Duration {
+        self.validation_timeout
+    }
+
     /// Determines the level of validation used when receiving messages. See [`ValidationMode`]
     /// for the available types. The default is ValidationMode::Strict.
     pub fn validation_mode(&self) -> &ValidationMode {
         &self.protocol.validation_mode
     }
 
+    /// The level of validation used when receiving messages of the given topic, i.e. the mode set
+    /// via [`ConfigBuilder::topic_validation_mode`] or else [`Config::validation_mode`].
+    ///
+    /// Subscribing and publishing to a topic fails if its mode would reject our own messages.
+    pub fn topic_validation_mode(&self, topic: &TopicHash) -> &ValidationMode {
+        self.protocol
+            .topic_validation_modes
+            .get(topic)
+            .unwrap_or(&self.protocol.validation_mode)
+    }
+
+    /// The time to live of the messages we publish on the given topic, if any, see
+    /// [`ConfigBuilder::topic_message_ttl`].
+    pub fn topic_message_ttl(&self, topic: &TopicHash) -> Option<Duration> {
+        self.topic_message_ttls.get(topic).copied()
+    }
+
+    /// The maximum size of the messages split into chunks, if chunking is enabled, see
+    /// [`ConfigBuilder::max_chunked_message_size`]. The default is `None`.
+    pub fn max_chunked_message_size(&self) -> Option<usize> {
+        self.max_chunked_message_size
+    }
+
+    /// The time after which a chunked message still missing chunks is dropped. The default is 30
+    /// seconds.
+    pub fn chunk_reassembly_timeout(&self) -> Duration {
+        self.chunk_reassembly_timeout
+    }
+
+    /// The maximum number of chunked messages being reassembled at once. The chunks of further
+    /// messages are dropped until a pending message completes or times out. The default is 32.
+    pub fn max_pending_chunked_messages(&self) -> usize {
+        self.max_pending_chunked_messages
+    }
+
     /// A user-defined function allowing the user to specify the message id of a gossipsub message.
     /// The default value is to concatenate the source peer id with a sequence number. Setting this
     /// parameter allows the user to address packets arbitrarily. One example is content based
@@ -214,7 +364,24 @@ impl Config {
     /// The function takes a [`Message`] as input and outputs a String to be interpreted as
     /// the message id.
     pub fn message_id(&self, message: &Message) -> MessageId {
-        (self.message_id_fn)(message)
+        match self.topic_message_id_fns.get(&message.topic) {
+            Some(id_fn) => id_fn(message),
+            None => (self.message_id_fn)(message),
+        }
+    }
+
+    /// Whether the message ids of the given topic are computed by a user-defined function, see
+    /// [`ConfigBuilder::message_id_fn`] and [`ConfigBuilder::topic_message_id_fn`].
+    pub(crate) fn has_message_id_fn(&self, topic: &TopicHash) -> bool {
+        self.custom_message_id_fn || self.topic_message_id_fns.contains_key(topic)
+    }
+
+    /// Whether the given peer may be grafted to the mesh of the given topic and receive its
+    /// messages, see [`ConfigBuilder::topic_peer_allowlist`].
+    pub fn is_peer_allowed_on_topic(&self, topic: &TopicHash, peer_id: &PeerId) -> bool {
+        self.topic_peer_allowlists
+            .get(topic)
+            .map_or(true, |allowed| allowed(peer_id))
     }
 
     /// By default, gossipsub will reject messages that are sent to us that have the same message
@@ -243,6 +410,39 @@ impl Config {
         self.prune_peers
     }
 
+    /// Whether peers received through Peer eXchange are only dialed if they come with a valid
+    /// signed peer record. Otherwise, peers without a record are dialed by their peer id alone,
+    /// relying on the addresses known from other sources. The default is false.
+    pub fn px_require_signed_peer_records(&self) -> bool {
+        self.px_require_signed_peer_records
+    }
+
+    /// The maximum number of peers received through Peer eXchange in a single PRUNE that are
+    /// dialed, picked at random. The default is [`Config::prune_peers`].
+    pub fn px_dials_per_prune(&self) -> usize {
+        self.px_dials_per_prune.unwrap_or(self.prune_peers)
+    }
+
+    /// The maximum random delay of the dials to peers received through Peer eXchange, spreading
+    /// them over time instead of dialing all of them at once. The default is zero.
+    pub fn px_dial_jitter(&self) -> Duration {
+        self.px_dial_jitter
+    }
+
+    /// The maximum number of peers received through Peer eXchange that are dialed per heartbeat,
+    /// across all PRUNEs. Further peers are ignored. The default is unlimited.
+    pub fn max_px_dials_per_heartbeat(&self) -> Option<usize> {
+        self.max_px_dials_per_heartbeat
+    }
+
+    /// Whether the given peer received through Peer eXchange may be dialed on the given
+    /// addresses of its signed peer record, see [`ConfigBuilder::px_dial_filter`].
+    pub fn is_px_dial_allowed(&self, peer_id: &PeerId, addresses: &[Multiaddr]) -> bool {
+        self.px_dial_filter
+            .as_ref()
+            .map_or(true, |allowed| allowed(peer_id, addresses))
+    }
+
     /// Controls the backoff time for pruned peers. This is how long
     /// a peer must wait before attempting to graft into our mesh again after being pruned.
     /// When pruning a peer, we send them our value of `prune_backoff` so they know
@@ -262,6 +462,13 @@ impl Config {
         self.unsubscribe_backoff
     }
 
+    /// The maximum time [`Behaviour::unsubscribe_with_prune_wait`](crate::Behaviour) waits for
+    /// the queued messages of the topic to be written before reporting the teardown as complete
+    /// anyway, checked on every heartbeat. The default is 10 seconds.
+    pub fn unsubscribe_drain_timeout(&self) -> Duration {
+        self.unsubscribe_drain_timeout
+    }
+
     /// Number of heartbeat slots considered as slack for backoffs. This guarantees that we wait
     /// at least backoff_slack heartbeats after a backoff is over before we try to graft. This
     /// solves problems occurring through high latencies. In particular if
@@ -313,12 +520,27 @@ impl Config {
         self.opportunistic_graft_peers
     }
 
+    /// The median score of the mesh peers of a topic below which opportunistic grafting is
+    /// applied, overriding the
+    /// [`PeerScoreThresholds::opportunistic_graft_threshold`](crate::PeerScoreThresholds) of peer
+    /// scoring. The default is None.
+    pub fn opportunistic_graft_threshold(&self) -> Option<f64> {
+        self.opportunistic_graft_threshold
+    }
+
     /// The maximum number of messages we will process in a given RPC. If this is unset, there is
     /// no limit. The default is None.
     pub fn max_messages_per_rpc(&self) -> Option<usize> {
         self.max_messages_per_rpc
     }
 
+    /// The size in bytes a received message must exceed for an IDONTWANT to be sent to the
+    /// gossipsub v1.2 mesh peers of its topic, asking them not to send us the same message.
+    /// The default is 1000 bytes.
+    pub fn idontwant_message_size_threshold(&self) -> usize {
+        self.idontwant_message_size_threshold
+    }
+
     /// The maximum number of messages to include in an IHAVE message.
     /// Also controls the maximum number of IHAVE ids we will accept and request with IWANT from a
     /// peer within a heartbeat, to protect from IHAVE floods. You should adjust this value from the
@@ -329,11 +551,44 @@ impl Config {
     }
 
     /// GossipSubMaxIHaveMessages is the maximum number of IHAVE messages to accept from a peer
-    /// within a heartbeat.
+    /// within a heartbeat. IHAVE messages beyond the budget are ignored, and the peer is penalized
+    /// and reported through an [`Event::BudgetExceeded`](crate::Event::BudgetExceeded).
     pub fn max_ihave_messages(&self) -> usize {
         self.max_ihave_messages
     }
 
+    /// The maximum number of IHAVE messages advertised to a peer within a heartbeat, one per
+    /// topic gossiped to the peer. Peers ignore IHAVE messages beyond their own
+    /// [`Config::max_ihave_messages`], hence the default is 10 as well.
+    pub fn max_sent_ihave_messages(&self) -> usize {
+        self.max_sent_ihave_messages
+    }
+
+    /// The maximum number of messages sent in response to the IWANT requests of a peer within a
+    /// heartbeat. Requests beyond the budget are ignored, and the peer is penalized and reported
+    /// through an [`Event::BudgetExceeded`](crate::Event::BudgetExceeded).
+    ///
+    /// The default is `None`, i.e. only [`Config::gossip_retransimission`] limits the responses.
+    pub fn max_iwant_responses(&self) -> Option<usize> {
+        self.max_iwant_responses
+    }
+
+    /// The number of control messages accepted from a peer per second, enforced by a token bucket
+    /// of [`Config::control_messages_burst`] tokens per peer. Control messages beyond the budget
+    /// are dropped, and the peer is penalized and reported through an
+    /// [`Event::BudgetExceeded`](crate::Event::BudgetExceeded).
+    ///
+    /// The default is `None`, i.e. control messages are not rate limited.
+    pub fn control_messages_per_second(&self) -> Option<u32> {
+        self.control_messages_per_second
+    }
+
+    /// The number of control messages accepted from a peer in a burst, when
+    /// [`Config::control_messages_per_second`] is set. The default is 500.
+    pub fn control_messages_burst(&self) -> u32 {
+        self.control_messages_burst
+    }
+
     /// Time to wait for a message requested through IWANT following an IHAVE advertisement.
     /// If the message is not received within this window, a broken promise is declared and
     /// the router may apply behavioural penalties. The default is 3 seconds.
@@ -350,6 +605,182 @@ impl Config {
     pub fn published_message_ids_cache_time(&self) -> Duration {
         self.published_message_ids_cache_time
     }
+
+    /// Time after which a mesh peer that has not delivered any first-seen message on a topic is
+    /// pruned from the topic's mesh, provided the topic has seen activity in the meantime, i.e.
+    /// other peers have delivered messages that were already seen. Stale peers are pruned without
+    /// peer exchange and the usual mesh failure score penalties apply. Peers that were added to
+    /// the mesh less than this duration ago are never considered stale.
+    ///
+    /// The default is `None`, i.e. mesh peers are never pruned due to inactivity.
+    pub fn stale_mesh_peer_timeout(&self) -> Option<Duration> {
+        self.stale_mesh_peer_timeout
+    }
+
+    /// Time during which a mesh peer whose connection dropped is added back to the mesh as soon
+    /// as it reconnects and subscribes again, as long as the mesh has less than `mesh_n` peers and
+    /// the peer is not backed off. Other subscribing peers are only added to meshes with less than
+    /// `mesh_n_low` peers.
+    ///
+    /// The default is 60 seconds, `None` disables adding reconnecting peers back to the mesh.
+    pub fn mesh_reconnect_window(&self) -> Option<Duration> {
+        self.mesh_reconnect_window
+    }
+
+    /// Whether to emit [`crate::Event::MeshPeerAdded`] and [`crate::Event::MeshPeerRemoved`]
+    /// events, reporting why peers join and leave the meshes. The default is false.
+    pub fn mesh_events(&self) -> bool {
+        self.mesh_events
+    }
+
+    /// Length of the outbound queue of a peer at or beyond which the queue is considered
+    /// saturated. If the queue of a peer stays saturated for
+    /// [`Config::saturated_queue_heartbeats`] heartbeats, an
+    /// [`Event::OutboundQueueSaturated`](crate::Event::OutboundQueueSaturated) is emitted.
+    ///
+    /// The default is `None`, i.e. no events are emitted.
+    pub fn saturated_queue_len(&self) -> Option<usize> {
+        self.saturated_queue_len
+    }
+
+    /// Number of consecutive heartbeats the outbound queue of a peer must be saturated for an
+    /// [`Event::OutboundQueueSaturated`](crate::Event::OutboundQueueSaturated) to be emitted.
+    /// The default is 3.
+    pub fn saturated_queue_heartbeats(&self) -> usize {
+        self.saturated_queue_heartbeats
+    }
+
+    /// How to treat a peer whose outbound queue stayed saturated for
+    /// [`Config::saturated_queue_heartbeats`] heartbeats, applied along with every
+    /// [`Event::OutboundQueueSaturated`](crate::Event::OutboundQueueSaturated). The default is
+    /// [`SlowPeerPolicy::Report`].
+    pub fn slow_peer_policy(&self) -> SlowPeerPolicy {
+        self.slow_peer_policy
+    }
+
+    /// Whether peers only connected through relays, i.e. over `/p2p-circuit` addresses, are
+    /// grafted to the meshes. The default is [`RelayedMeshPolicy::Allow`].
+    pub fn relayed_mesh_policy(&self) -> RelayedMeshPolicy {
+        self.relayed_mesh_policy
+    }
+
+    /// Number of heartbeat ticks between two [`Event::ScoreReport`](crate::Event::ScoreReport)s
+    /// reporting the score of all connected peers. Requires peer scoring to be enabled.
+    ///
+    /// The default is `None`, i.e. no reports are emitted.
+    pub fn score_report_ticks(&self) -> Option<u64> {
+        self.score_report_ticks
+    }
+
+    /// The maximum size in bytes of an RPC coalescing the messages queued for a peer. If set,
+    /// publishes, forwards and control messages queued for the same peer are sent in a single RPC
+    /// up to this size, bounded by [`Config::max_transmit_size`], rather than in an RPC each.
+    ///
+    /// Note that the remote may limit the number of messages it processes per RPC, see
+    /// [`Config::max_messages_per_rpc`].
+    ///
+    /// The default is `None`, i.e. every message is sent in its own RPC.
+    pub fn rpc_batch_size(&self) -> Option<usize> {
+        self.rpc_batch_size
+    }
+
+    /// The time queued messages are held back to be coalesced with messages queued after them,
+    /// unless [`Config::rpc_batch_size`] is reached before. Only applies if
+    /// [`Config::rpc_batch_size`] is set.
+    ///
+    /// The default is 0, i.e. only messages that are already queued are coalesced.
+    pub fn rpc_batch_delay(&self) -> Duration {
+        self.rpc_batch_delay
+    }
+
+    /// Length of the outbound queue of a peer at or beyond which an
+    /// [`Event::OutboundQueueHigh`](crate::Event::OutboundQueueHigh) is emitted, signalling that
+    /// the peer does not keep up with the messages sent to it.
+    ///
+    /// The default is `None`, i.e. no events are emitted.
+    pub fn outbound_queue_high_watermark(&self) -> Option<usize> {
+        self.outbound_queue_high_watermark
+    }
+
+    /// Length of the outbound queue of a peer at or below which an
+    /// [`Event::OutboundQueueLow`](crate::Event::OutboundQueueLow) is emitted, once the queue
+    /// reached the [`Config::outbound_queue_high_watermark`] before. The default is 0.
+    pub fn outbound_queue_low_watermark(&self) -> usize {
+        self.outbound_queue_low_watermark
+    }
+
+    /// Number of heartbeat ticks between two evaluations of the mesh peers to choke or unchoke.
+    /// Enables the choking extension if set: mesh peers persistently delivering duplicates are
+    /// choked, i.e. asked to only announce their messages through `IHAVE`s instead of forwarding
+    /// them, and choked peers announcing messages faster than unchoked peers deliver them are
+    /// unchoked again. Choking requests of peers are only honoured if this is set as well.
+    ///
+    /// The default is `None`, i.e. mesh peers are never choked.
+    pub fn choke_ticks(&self) -> Option<u64> {
+        self.choke_ticks
+    }
+
+    /// Fraction of the messages delivered by a mesh peer since the last evaluation that must
+    /// have been duplicates for the peer to be choked. Only applies if [`Config::choke_ticks`] is
+    /// set. The default is 0.95.
+    pub fn choke_duplicate_ratio(&self) -> f64 {
+        self.choke_duplicate_ratio
+    }
+
+    /// Minimum number of messages a mesh peer must have delivered since the last evaluation to be
+    /// choked. Only applies if [`Config::choke_ticks`] is set. The default is 20.
+    pub fn choke_min_deliveries(&self) -> usize {
+        self.choke_min_deliveries
+    }
+
+    /// Minimum number of unchoked peers in the mesh of a topic. Mesh peers are never choked below
+    /// this number and choked peers are unchoked to reach it. Only applies if
+    /// [`Config::choke_ticks`] is set. The default is 4.
+    pub fn mesh_unchoked_min(&self) -> usize {
+        self.mesh_unchoked_min
+    }
+
+    /// Maximum number of invalid message reports sent to and accepted from each peer per
+    /// heartbeat. Enables the invalid message reports extension if set: when the application
+    /// rejects a message via [`crate::Behaviour::report_message_validation_result`], the mesh
+    /// peers of its topic which did not send us the message are notified through a report signed
+    /// by the local node, so that they can penalize the propagation source before validating the
+    /// message themselves. Reports are only sent with [`crate::MessageAuthenticity::Signed`] and
+    /// only accepted from mesh peers with a non-negative score, and only penalize the reported
+    /// peer if the message has not been validated locally yet.
+    ///
+    /// As misbehaving peers may report honest peers, this should only be enabled in networks
+    /// where mesh peers are trusted to some degree. The default is `None`, i.e. no reports are
+    /// sent or accepted.
+    pub fn invalid_message_reports(&self) -> Option<usize> {
+        self.invalid_message_reports
+    }
+
+    /// Bounds of the gossip factor of each topic when adapting it to the health of the mesh.
+    /// If set, the gossip factor of a topic starts at [`Config::gossip_factor`]. It is increased
+    /// after each heartbeat in which the messages of the topic were rarely received more than
+    /// once, i.e. few mesh peers delivered them, and decreased again once the messages are
+    /// received redundantly.
+    ///
+    /// The default is `None`, i.e. the gossip factor is always [`Config::gossip_factor`].
+    pub fn adaptive_gossip_factor(&self) -> Option<RangeInclusive<f64>> {
+        self.adaptive_gossip_factor.clone()
+    }
+
+    /// The time the given message was published at, as extracted by
+    /// [`ConfigBuilder::delivery_timestamp_fn`], if any.
+    pub fn delivery_timestamp(&self, message: &Message) -> Option<SystemTime> {
+        self.delivery_timestamp_fn
+            .as_ref()
+            .and_then(|timestamp| timestamp(message))
+    }
+
+    /// The number of recent deliveries per topic the delivery latency percentiles are computed
+    /// from, see [`Behaviour::delivery_latency`](crate::Behaviour::delivery_latency). The default
+    /// is 1000.
+    pub fn delivery_latency_samples(&self) -> usize {
+        self.delivery_latency_samples
+    }
 }
 
 impl Default for Config {
@@ -361,6 +792,61 @@ impl Default for Config {
     }
 }
 
+/// Mesh maintenance parameters of a topic, overriding those of the [`Config`] for the topic via
+/// [`Behaviour::set_topic_mesh_params`](crate::Behaviour::set_topic_mesh_params).
+///
+/// This allows e.g. small meshes for control topics and large meshes for heavy data topics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicMeshParams {
+    /// The target number of peers in the mesh of the topic (D), see [`Config::mesh_n`].
+    pub mesh_n: usize,
+    /// The minimum number of peers in the mesh of the topic (D_lo), see [`Config::mesh_n_low`].
+    pub mesh_n_low: usize,
+    /// The maximum number of peers in the mesh of the topic (D_hi), see
+    /// [`Config::mesh_n_high`].
+    pub mesh_n_high: usize,
+    /// The minimum number of outbound peers in the mesh of the topic (D_out), see
+    /// [`Config::mesh_outbound_min`].
+    pub mesh_outbound_min: usize,
+    /// The minimum number of peers to emit gossip to for the topic (D_lazy), see
+    /// [`Config::gossip_lazy`].
+    pub gossip_lazy: usize,
+    /// The number of peers grafted by the opportunistic grafting of the heartbeat for the topic,
+    /// see [`Config::opportunistic_graft_peers`]. Zero disables opportunistic grafting for the
+    /// topic.
+    pub opportunistic_graft_peers: usize,
+}
+
+impl TopicMeshParams {
+    /// Checks that the parameters are consistent, as done by [`ConfigBuilder::build`] for the
+    /// parameters of the [`Config`].
+    pub fn validate(&self) -> Result<(), ConfigBuilderError> {
+        if !(self.mesh_outbound_min <= self.mesh_n_low
+            && self.mesh_n_low <= self.mesh_n
+            && self.mesh_n <= self.mesh_n_high)
+        {
+            return Err(ConfigBuilderError::MeshParametersInvalid);
+        }
+        if self.mesh_outbound_min * 2 > self.mesh_n {
+            return Err(ConfigBuilderError::MeshOutboundInvalid);
+        }
+        Ok(())
+    }
+}
+
+impl From<&Config> for TopicMeshParams {
+    fn from(config: &Config) -> Self {
+        Self {
+            mesh_n: config.mesh_n(),
+            mesh_n_low: config.mesh_n_low(),
+            mesh_n_high: config.mesh_n_high(),
+            mesh_outbound_min: config.mesh_outbound_min(),
+            gossip_lazy: config.gossip_lazy(),
+            opportunistic_graft_peers: config.opportunistic_graft_peers(),
+        }
+    }
+}
+
 /// The builder struct for constructing a gossipsub configuration.
 pub struct ConfigBuilder {
     config: Config,
@@ -384,8 +870,12 @@ impl Default for ConfigBuilder {
                 heartbeat_interval: Duration::from_secs(1),
                 fanout_ttl: Duration::from_secs(60),
                 check_explicit_peers_ticks: 300,
+                explicit_peer_replay_window: None,
+                explicit_peer_replay_max_messages: 1000,
                 duplicate_cache_time: Duration::from_secs(60),
                 validate_messages: false,
+                max_concurrent_validations: 1024,
+                validation_timeout: Duration::from_secs(10),
                 message_id_fn: Arc::new(|message| {
                     // default message id is: source + sequence number
                     // NOTE: If either the peer_id or source is not provided, we set to 0;
@@ -400,23 +890,62 @@ impl Default for ConfigBuilder {
                         .push_str(&message.sequence_number.unwrap_or_default().to_string());
                     MessageId::from(source_string)
                 }),
+                custom_message_id_fn: false,
+                topic_message_id_fns: HashMap::new(),
+                topic_peer_allowlists: HashMap::new(),
+                topic_message_ttls: HashMap::new(),
+                max_chunked_message_size: None,
+                chunk_reassembly_timeout: Duration::from_secs(30),
+                max_pending_chunked_messages: 32,
                 allow_self_origin: false,
                 do_px: false,
                 prune_peers: 0, // NOTE: Increasing this currently has little effect until Signed records are implemented.
+                px_require_signed_peer_records: false,
+                px_dials_per_prune: None,
+                px_dial_jitter: Duration::ZERO,
+                max_px_dials_per_heartbeat: None,
+                px_dial_filter: None,
                 prune_backoff: Duration::from_secs(60),
                 unsubscribe_backoff: Duration::from_secs(10),
+                unsubscribe_drain_timeout: Duration::from_secs(10),
                 backoff_slack: 1,
                 flood_publish: true,
                 graft_flood_threshold: Duration::from_secs(10),
                 mesh_outbound_min: 2,
                 opportunistic_graft_ticks: 60,
                 opportunistic_graft_peers: 2,
+                opportunistic_graft_threshold: None,
                 gossip_retransimission: 3,
                 max_messages_per_rpc: None,
+                idontwant_message_size_threshold: 1000,
                 max_ihave_length: 5000,
                 max_ihave_messages: 10,
+                max_sent_ihave_messages: 10,
+                max_iwant_responses: None,
+                control_messages_per_second: None,
+                control_messages_burst: 500,
                 iwant_followup_time: Duration::from_secs(3),
                 published_message_ids_cache_time: Duration::from_secs(10),
+                stale_mesh_peer_timeout: None,
+                mesh_reconnect_window: Some(Duration::from_secs(60)),
+                mesh_events: false,
+                saturated_queue_len: None,
+                saturated_queue_heartbeats: 3,
+                slow_peer_policy: SlowPeerPolicy::Report,
+                relayed_mesh_policy: RelayedMeshPolicy::Allow,
+                score_report_ticks: None,
+                rpc_batch_size: None,
+                rpc_batch_delay: Duration::ZERO,
+                outbound_queue_high_watermark: None,
+                outbound_queue_low_watermark: 0,
+                choke_ticks: None,
+                choke_duplicate_ratio: 0.95,
+                choke_min_deliveries: 20,
+                mesh_unchoked_min: 4,
+                invalid_message_reports: None,
+                adaptive_gossip_factor: None,
+                delivery_timestamp_fn: None,
+                delivery_latency_samples: 1000,
             },
             invalid_protocol: false,
         }
@@ -433,7 +962,8 @@ impl From<Config> for ConfigBuilder {
 }
 
 impl ConfigBuilder {
-    /// The protocol id prefix to negotiate this protocol (default is `/meshsub/1.1.0` and `/meshsub/1.0.0`).
+    /// The protocol id prefix to negotiate this protocol (default is `/meshsub/1.2.0`,
+    /// `/meshsub/1.1.0` and `/meshsub/1.0.0`).
     pub fn protocol_id_prefix(
         &mut self,
         protocol_id_prefix: impl Into<Cow<'static, str>>,
@@ -441,17 +971,22 @@ impl ConfigBuilder {
         let cow = protocol_id_prefix.into();
 
         match (
+            StreamProtocol::try_from_owned(format!("{}/1.2.0", cow)),
             StreamProtocol::try_from_owned(format!("{}/1.1.0", cow)),
             StreamProtocol::try_from_owned(format!("{}/1.0.0", cow)),
         ) {
-            (Ok(p1), Ok(p2)) => {
+            (Ok(p1), Ok(p2), Ok(p3)) => {
                 self.config.protocol.protocol_ids = vec![
                     ProtocolId {
                         protocol: p1,
-                        kind: PeerKind::Gossipsubv1_1,
+                        kind: PeerKind::Gossipsubv1_2,
                     },
                     ProtocolId {
                         protocol: p2,
+                        kind: PeerKind::Gossipsubv1_1,
+                    },
+                    ProtocolId {
+                        protocol: p3,
                         kind: PeerKind::Gossipsub,
                     },
                 ]
@@ -464,7 +999,8 @@ impl ConfigBuilder {
         self
     }
 
-    /// The full protocol id to negotiate this protocol (does not append `/1.0.0` or `/1.1.0`).
+    /// The full protocol id to negotiate this protocol (does not append `/1.0.0`, `/1.1.0` or
+    /// `/1.2.0`).
     pub fn protocol_id(
         &mut self,
         protocol_id: impl Into<Cow<'static, str>>,
@@ -477,6 +1013,7 @@ impl ConfigBuilder {
                 self.config.protocol.protocol_ids = vec![ProtocolId {
                     protocol,
                     kind: match custom_id_version {
+                        Version::V1_2 => PeerKind::Gossipsubv1_2,
                         Version::V1_1 => PeerKind::Gossipsubv1_1,
                         Version::V1_0 => PeerKind::Gossipsub,
                     },
@@ -565,6 +1102,25 @@ impl ConfigBuilder {
         self
     }
 
+    /// Time window of messages retained for a disconnected explicit peer. Messages that would have
+    /// been sent to an explicit peer while it was disconnected are replayed to it once it
+    /// reconnects, provided they were published or forwarded less than this duration ago. This
+    /// allows direct peers to catch up on messages after a transient disconnect.
+    ///
+    /// The default is `None`, i.e. no messages are retained for explicit peers.
+    pub fn explicit_peer_replay_window(&mut self, window: Option<Duration>) -> &mut Self {
+        self.config.explicit_peer_replay_window = window;
+        self
+    }
+
+    /// The maximum number of messages retained for each disconnected explicit peer, see
+    /// [`Config::explicit_peer_replay_window`]. When exceeded, the oldest messages are dropped.
+    /// The default is 1000.
+    pub fn explicit_peer_replay_max_messages(&mut self, max: usize) -> &mut Self {
+        self.config.explicit_peer_replay_max_messages = max;
+        self
+    }
+
     /// Time to live for fanout peers (default is 60 seconds).
     pub fn fanout_ttl(&mut self, fanout_ttl: Duration) -> &mut Self {
         self.config.fanout_ttl = fanout_ttl;
@@ -595,6 +1151,23 @@ impl ConfigBuilder {
         self
     }
 
+    /// The maximum number of messages validated concurrently by the validators registered via
+    /// [`Behaviour::set_topic_validator`](crate::Behaviour::set_topic_validator). Messages
+    /// received while the limit is reached are ignored without being validated. The default is
+    /// 1024.
+    pub fn max_concurrent_validations(&mut self, max: usize) -> &mut Self {
+        self.config.max_concurrent_validations = max;
+        self
+    }
+
+    /// The time after which a pending validation by a validator registered via
+    /// [`Behaviour::set_topic_validator`](crate::Behaviour::set_topic_validator) is abandoned and
+    /// the message ignored. The default is 10 seconds.
+    pub fn validation_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.config.validation_timeout = timeout;
+        self
+    }
+
     /// Determines the level of validation used when receiving messages. See [`ValidationMode`]
     /// for the available types. The default is ValidationMode::Strict.
     pub fn validation_mode(&mut self, validation_mode: ValidationMode) -> &mut Self {
@@ -602,6 +1175,67 @@ impl ConfigBuilder {
         self
     }
 
+    /// Overrides the level of validation used when receiving messages of the given topic, e.g. to
+    /// require anonymous messages in some topics with [`ValidationMode::Anonymous`].
+    ///
+    /// Subscribing and publishing to a topic fails if its mode would reject our own messages.
+    /// Anonymous topics require a message id function which does not depend on the author and
+    /// sequence number of messages, see [`ConfigBuilder::topic_message_id_fn`].
+    pub fn topic_validation_mode(
+        &mut self,
+        topic: TopicHash,
+        validation_mode: ValidationMode,
+    ) -> &mut Self {
+        Arc::make_mut(&mut self.config.protocol.topic_validation_modes)
+            .insert(topic, validation_mode);
+        self
+    }
+
+    /// Attaches a deadline to the messages we publish on the given topic, the given time to live
+    /// after publishing, e.g. for real-time topics whose messages are useless once stale.
+    ///
+    /// Nodes running this implementation neither forward nor gossip messages once their deadline
+    /// passed, and drop them from their send queues and message cache. The deadline relies on
+    /// loosely synchronized clocks. By default, messages have no deadline.
+    pub fn topic_message_ttl(&mut self, topic: TopicHash, ttl: Duration) -> &mut Self {
+        self.config.topic_message_ttls.insert(topic, ttl);
+        self
+    }
+
+    /// Enables the chunking extension: the messages we publish exceeding
+    /// [`Config::max_transmit_size`] after the outbound transform are split into ordered chunks,
+    /// each published as a message of its own, and reassembled by the receivers. Messages larger
+    /// than the given size are still rejected with [`PublishError::MessageTooLarge`].
+    ///
+    /// The chunks are validated, forwarded and gossiped individually. A reassembled message is
+    /// delivered once, with the id of its chunk group, and its validation result applies to all
+    /// of its chunks. Nodes with chunking disabled drop chunks. The default is `None`, i.e.
+    /// chunking is disabled.
+    ///
+    /// Chunks are identified by their position in the chunked message, whose id starts with the
+    /// peer id of its source. Other peers can only be prevented from injecting forged chunks into
+    /// a chunked message if chunks are signed, thus [`ConfigBuilder::build`] fails if chunking is
+    /// enabled without [`ValidationMode::Strict`] for all topics.
+    ///
+    /// [`PublishError::MessageTooLarge`]: crate::PublishError::MessageTooLarge
+    pub fn max_chunked_message_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.config.max_chunked_message_size = size;
+        self
+    }
+
+    /// The time after which a chunked message still missing chunks is dropped. The default is 30
+    /// seconds.
+    pub fn chunk_reassembly_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.config.chunk_reassembly_timeout = timeout;
+        self
+    }
+
+    /// The maximum number of chunked messages being reassembled at once. The default is 32.
+    pub fn max_pending_chunked_messages(&mut self, max: usize) -> &mut Self {
+        self.config.max_pending_chunked_messages = max;
+        self
+    }
+
     /// A user-defined function allowing the user to specify the message id of a gossipsub message.
     /// The default value is to concatenate the source peer id with a sequence number. Setting this
     /// parameter allows the user to address packets arbitrarily. One example is content based
@@ -615,6 +1249,36 @@ impl ConfigBuilder {
         F: Fn(&Message) -> MessageId + Send + Sync + 'static,
     {
         self.config.message_id_fn = Arc::new(id_fn);
+        self.config.custom_message_id_fn = true;
+        self
+    }
+
+    /// A user-defined function computing the message ids of the given topic instead of
+    /// [`ConfigBuilder::message_id_fn`], e.g. a content based id for a topic of anonymous
+    /// messages, which have no author and sequence number.
+    pub fn topic_message_id_fn<F>(&mut self, topic: TopicHash, id_fn: F) -> &mut Self
+    where
+        F: Fn(&Message) -> MessageId + Send + Sync + 'static,
+    {
+        self.config
+            .topic_message_id_fns
+            .insert(topic, Arc::new(id_fn));
+        self
+    }
+
+    /// Confines the given topic to the peers accepted by the given predicate, e.g. a control
+    /// topic of a closed set of peers within an open network.
+    ///
+    /// The subscriptions of other peers to the topic are ignored, their GRAFTs are answered with
+    /// a PRUNE and no messages of the topic are published, forwarded or gossiped to them. By
+    /// default, all peers are allowed on all topics.
+    pub fn topic_peer_allowlist<F>(&mut self, topic: TopicHash, allowed: F) -> &mut Self
+    where
+        F: Fn(&PeerId) -> bool + Send + Sync + 'static,
+    {
+        self.config
+            .topic_peer_allowlists
+            .insert(topic, Arc::new(allowed));
         self
     }
 
@@ -639,6 +1303,48 @@ impl ConfigBuilder {
         self
     }
 
+    /// Whether peers received through Peer eXchange are only dialed if they come with a valid
+    /// signed peer record. Otherwise, peers without a record are dialed by their peer id alone,
+    /// relying on the addresses known from other sources. The default is false.
+    pub fn px_require_signed_peer_records(&mut self, require: bool) -> &mut Self {
+        self.config.px_require_signed_peer_records = require;
+        self
+    }
+
+    /// The maximum number of peers received through Peer eXchange in a single PRUNE that are
+    /// dialed, picked at random. The default is [`Self::prune_peers`].
+    pub fn px_dials_per_prune(&mut self, px_dials_per_prune: usize) -> &mut Self {
+        self.config.px_dials_per_prune = Some(px_dials_per_prune);
+        self
+    }
+
+    /// Delays each dial to a peer received through Peer eXchange by a random duration up to the
+    /// given one, spreading them over time instead of dialing all of them at once. The default
+    /// is zero.
+    pub fn px_dial_jitter(&mut self, px_dial_jitter: Duration) -> &mut Self {
+        self.config.px_dial_jitter = px_dial_jitter;
+        self
+    }
+
+    /// The maximum number of peers received through Peer eXchange that are dialed per heartbeat,
+    /// across all PRUNEs. Further peers are ignored until the next heartbeat. The default is
+    /// unlimited.
+    pub fn max_px_dials_per_heartbeat(&mut self, max_px_dials_per_heartbeat: usize) -> &mut Self {
+        self.config.max_px_dials_per_heartbeat = Some(max_px_dials_per_heartbeat);
+        self
+    }
+
+    /// Only dials the peers received through Peer eXchange accepted by the given predicate, given
+    /// the peer and the addresses of its signed peer record, if any. E.g. to reject addresses in
+    /// private ranges or of unexpected transports. By default, all peers are dialed.
+    pub fn px_dial_filter<F>(&mut self, allowed: F) -> &mut Self
+    where
+        F: Fn(&PeerId, &[Multiaddr]) -> bool + Send + Sync + 'static,
+    {
+        self.config.px_dial_filter = Some(Arc::new(allowed));
+        self
+    }
+
     /// Controls the backoff time for pruned peers. This is how long
     /// a peer must wait before attempting to graft into our mesh again after being pruned.
     /// When pruning a peer, we send them our value of [`Self::prune_backoff`] so they know
@@ -660,6 +1366,14 @@ impl ConfigBuilder {
         self
     }
 
+    /// The maximum time [`Behaviour::unsubscribe_with_prune_wait`](crate::Behaviour) waits for
+    /// the queued messages of the topic to be written before reporting the teardown as complete
+    /// anyway, checked on every heartbeat. The default is 10 seconds.
+    pub fn unsubscribe_drain_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.config.unsubscribe_drain_timeout = timeout;
+        self
+    }
+
     /// Number of heartbeat slots considered as slack for backoffs. This guarantees that we wait
     /// at least backoff_slack heartbeats after a backoff is over before we try to graft. This
     /// solves problems occurring through high latencies. In particular if
@@ -718,6 +1432,15 @@ impl ConfigBuilder {
         self
     }
 
+    /// The median score of the mesh peers of a topic below which opportunistic grafting is
+    /// applied, overriding the
+    /// [`PeerScoreThresholds::opportunistic_graft_threshold`](crate::PeerScoreThresholds) of peer
+    /// scoring. Must not be negative.
+    pub fn opportunistic_graft_threshold(&mut self, threshold: Option<f64>) -> &mut Self {
+        self.config.opportunistic_graft_threshold = threshold;
+        self
+    }
+
     /// The maximum number of messages we will process in a given RPC. If this is unset, there is
     /// no limit. The default is None.
     pub fn max_messages_per_rpc(&mut self, max: Option<usize>) -> &mut Self {
@@ -725,6 +1448,14 @@ impl ConfigBuilder {
         self
     }
 
+    /// The size in bytes a received message must exceed for an IDONTWANT to be sent to the
+    /// gossipsub v1.2 mesh peers of its topic, asking them not to send us the same message.
+    /// The default is 1000 bytes.
+    pub fn idontwant_message_size_threshold(&mut self, size: usize) -> &mut Self {
+        self.config.idontwant_message_size_threshold = size;
+        self
+    }
+
     /// The maximum number of messages to include in an IHAVE message.
     /// Also controls the maximum number of IHAVE ids we will accept and request with IWANT from a
     /// peer within a heartbeat, to protect from IHAVE floods. You should adjust this value from the
@@ -736,12 +1467,49 @@ impl ConfigBuilder {
     }
 
     /// GossipSubMaxIHaveMessages is the maximum number of IHAVE messages to accept from a peer
-    /// within a heartbeat.
+    /// within a heartbeat. IHAVE messages beyond the budget are ignored, and the peer is penalized
+    /// and reported through an [`Event::BudgetExceeded`](crate::Event::BudgetExceeded).
     pub fn max_ihave_messages(&mut self, max_ihave_messages: usize) -> &mut Self {
         self.config.max_ihave_messages = max_ihave_messages;
         self
     }
 
+    /// The maximum number of IHAVE messages advertised to a peer within a heartbeat, one per
+    /// topic gossiped to the peer. Peers ignore IHAVE messages beyond their own
+    /// [`Config::max_ihave_messages`], hence the default is 10 as well.
+    pub fn max_sent_ihave_messages(&mut self, max_sent_ihave_messages: usize) -> &mut Self {
+        self.config.max_sent_ihave_messages = max_sent_ihave_messages;
+        self
+    }
+
+    /// The maximum number of messages sent in response to the IWANT requests of a peer within a
+    /// heartbeat. Requests beyond the budget are ignored, and the peer is penalized and reported
+    /// through an [`Event::BudgetExceeded`](crate::Event::BudgetExceeded).
+    ///
+    /// The default is `None`, i.e. only [`Config::gossip_retransimission`] limits the responses.
+    pub fn max_iwant_responses(&mut self, max_iwant_responses: Option<usize>) -> &mut Self {
+        self.config.max_iwant_responses = max_iwant_responses;
+        self
+    }
+
+    /// The number of control messages accepted from a peer per second, enforced by a token bucket
+    /// of [`Config::control_messages_burst`] tokens per peer. Control messages beyond the budget
+    /// are dropped, and the peer is penalized and reported through an
+    /// [`Event::BudgetExceeded`](crate::Event::BudgetExceeded).
+    ///
+    /// The default is `None`, i.e. control messages are not rate limited.
+    pub fn control_messages_per_second(&mut self, per_second: Option<u32>) -> &mut Self {
+        self.config.control_messages_per_second = per_second;
+        self
+    }
+
+    /// The number of control messages accepted from a peer in a burst, when
+    /// [`Config::control_messages_per_second`] is set. The default is 500.
+    pub fn control_messages_burst(&mut self, burst: u32) -> &mut Self {
+        self.config.control_messages_burst = burst;
+        self
+    }
+
     /// By default, gossipsub will reject messages that are sent to us that has the same message
     /// source as we have specified locally. Enabling this, allows these messages and prevents
     /// penalizing the peer that sent us the message. Default is false.
@@ -782,6 +1550,206 @@ impl ConfigBuilder {
         self
     }
 
+    /// Time after which a mesh peer that has not delivered any first-seen message on a topic is
+    /// pruned from the topic's mesh, provided the topic has seen activity in the meantime, i.e.
+    /// other peers have delivered messages that were already seen. Stale peers are pruned without
+    /// peer exchange and the usual mesh failure score penalties apply. Peers that were added to
+    /// the mesh less than this duration ago are never considered stale.
+    ///
+    /// The default is `None`, i.e. mesh peers are never pruned due to inactivity.
+    pub fn stale_mesh_peer_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.config.stale_mesh_peer_timeout = timeout;
+        self
+    }
+
+    /// Time during which a mesh peer whose connection dropped is added back to the mesh as soon
+    /// as it reconnects and subscribes again, as long as the mesh has less than `mesh_n` peers and
+    /// the peer is not backed off. Other subscribing peers are only added to meshes with less than
+    /// `mesh_n_low` peers.
+    ///
+    /// The default is 60 seconds, `None` disables adding reconnecting peers back to the mesh.
+    pub fn mesh_reconnect_window(&mut self, window: Option<Duration>) -> &mut Self {
+        self.config.mesh_reconnect_window = window;
+        self
+    }
+
+    /// Whether to emit [`crate::Event::MeshPeerAdded`] and [`crate::Event::MeshPeerRemoved`]
+    /// events, reporting why peers join and leave the meshes. The default is false.
+    pub fn mesh_events(&mut self, enabled: bool) -> &mut Self {
+        self.config.mesh_events = enabled;
+        self
+    }
+
+    /// Length of the outbound queue of a peer at or beyond which the queue is considered
+    /// saturated. If the queue of a peer stays saturated for
+    /// [`Config::saturated_queue_heartbeats`] heartbeats, an
+    /// [`Event::OutboundQueueSaturated`](crate::Event::OutboundQueueSaturated) is emitted.
+    ///
+    /// The default is `None`, i.e. no events are emitted.
+    pub fn saturated_queue_len(&mut self, len: Option<usize>) -> &mut Self {
+        self.config.saturated_queue_len = len;
+        self
+    }
+
+    /// Number of consecutive heartbeats the outbound queue of a peer must be saturated for an
+    /// [`Event::OutboundQueueSaturated`](crate::Event::OutboundQueueSaturated) to be emitted.
+    /// The default is 3.
+    pub fn saturated_queue_heartbeats(&mut self, heartbeats: usize) -> &mut Self {
+        self.config.saturated_queue_heartbeats = heartbeats;
+        self
+    }
+
+    /// How to treat a peer whose outbound queue stayed saturated for
+    /// [`Config::saturated_queue_heartbeats`] heartbeats, e.g. prune it from the meshes instead of
+    /// letting its queue delay the messages. Requires [`ConfigBuilder::saturated_queue_len`].
+    pub fn slow_peer_policy(&mut self, policy: SlowPeerPolicy) -> &mut Self {
+        self.config.slow_peer_policy = policy;
+        self
+    }
+
+    /// Whether peers only connected through relays, i.e. over `/p2p-circuit` addresses, are
+    /// grafted to the meshes, e.g. only if not enough directly connected peers are available.
+    pub fn relayed_mesh_policy(&mut self, policy: RelayedMeshPolicy) -> &mut Self {
+        self.config.relayed_mesh_policy = policy;
+        self
+    }
+
+    /// Number of heartbeat ticks between two [`Event::ScoreReport`](crate::Event::ScoreReport)s
+    /// reporting the score of all connected peers. Requires peer scoring to be enabled.
+    ///
+    /// The default is `None`, i.e. no reports are emitted.
+    pub fn score_report_ticks(&mut self, ticks: Option<u64>) -> &mut Self {
+        self.config.score_report_ticks = ticks;
+        self
+    }
+
+    /// The maximum size in bytes of an RPC coalescing the messages queued for a peer. If set,
+    /// publishes, forwards and control messages queued for the same peer are sent in a single RPC
+    /// up to this size, bounded by [`Config::max_transmit_size`], rather than in an RPC each.
+    ///
+    /// Note that the remote may limit the number of messages it processes per RPC, see
+    /// [`Config::max_messages_per_rpc`].
+    ///
+    /// The default is `None`, i.e. every message is sent in its own RPC.
+    pub fn rpc_batch_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.config.rpc_batch_size = size;
+        self
+    }
+
+    /// The time queued messages are held back to be coalesced with messages queued after them,
+    /// unless [`Config::rpc_batch_size`] is reached before. Only applies if
+    /// [`Config::rpc_batch_size`] is set.
+    ///
+    /// The default is 0, i.e. only messages that are already queued are coalesced.
+    pub fn rpc_batch_delay(&mut self, delay: Duration) -> &mut Self {
+        self.config.rpc_batch_delay = delay;
+        self
+    }
+
+    /// Length of the outbound queue of a peer at or beyond which an
+    /// [`Event::OutboundQueueHigh`](crate::Event::OutboundQueueHigh) is emitted, signalling that
+    /// the peer does not keep up with the messages sent to it.
+    ///
+    /// The default is `None`, i.e. no events are emitted.
+    pub fn outbound_queue_high_watermark(&mut self, len: Option<usize>) -> &mut Self {
+        self.config.outbound_queue_high_watermark = len;
+        self
+    }
+
+    /// Length of the outbound queue of a peer at or below which an
+    /// [`Event::OutboundQueueLow`](crate::Event::OutboundQueueLow) is emitted, once the queue
+    /// reached the [`Config::outbound_queue_high_watermark`] before. The default is 0.
+    pub fn outbound_queue_low_watermark(&mut self, len: usize) -> &mut Self {
+        self.config.outbound_queue_low_watermark = len;
+        self
+    }
+
+    /// Number of heartbeat ticks between two evaluations of the mesh peers to choke or unchoke.
+    /// Enables the choking extension if set: mesh peers persistently delivering duplicates are
+    /// choked, i.e. asked to only announce their messages through `IHAVE`s instead of forwarding
+    /// them, and choked peers announcing messages faster than unchoked peers deliver them are
+    /// unchoked again. Choking requests of peers are only honoured if this is set as well.
+    ///
+    /// The default is `None`, i.e. mesh peers are never choked.
+    pub fn choke_ticks(&mut self, ticks: Option<u64>) -> &mut Self {
+        self.config.choke_ticks = ticks;
+        self
+    }
+
+    /// Fraction of the messages delivered by a mesh peer since the last evaluation that must
+    /// have been duplicates for the peer to be choked. Only applies if [`Config::choke_ticks`] is
+    /// set. The default is 0.95.
+    pub fn choke_duplicate_ratio(&mut self, ratio: f64) -> &mut Self {
+        self.config.choke_duplicate_ratio = ratio;
+        self
+    }
+
+    /// Minimum number of messages a mesh peer must have delivered since the last evaluation to be
+    /// choked. Only applies if [`Config::choke_ticks`] is set. The default is 20.
+    pub fn choke_min_deliveries(&mut self, deliveries: usize) -> &mut Self {
+        self.config.choke_min_deliveries = deliveries;
+        self
+    }
+
+    /// Minimum number of unchoked peers in the mesh of a topic. Mesh peers are never choked below
+    /// this number and choked peers are unchoked to reach it. Only applies if
+    /// [`Config::choke_ticks`] is set. The default is 4.
+    pub fn mesh_unchoked_min(&mut self, n: usize) -> &mut Self {
+        self.config.mesh_unchoked_min = n;
+        self
+    }
+
+    /// Maximum number of invalid message reports sent to and accepted from each peer per
+    /// heartbeat. Enables the invalid message reports extension if set: when the application
+    /// rejects a message via [`crate::Behaviour::report_message_validation_result`], the mesh
+    /// peers of its topic which did not send us the message are notified through a report signed
+    /// by the local node, so that they can penalize the propagation source before validating the
+    /// message themselves. Reports are only sent with [`crate::MessageAuthenticity::Signed`] and
+    /// only accepted from mesh peers with a non-negative score, and only penalize the reported
+    /// peer if the message has not been validated locally yet.
+    ///
+    /// As misbehaving peers may report honest peers, this should only be enabled in networks
+    /// where mesh peers are trusted to some degree. The default is `None`, i.e. no reports are
+    /// sent or accepted.
+    pub fn invalid_message_reports(&mut self, max_per_heartbeat: Option<usize>) -> &mut Self {
+        self.config.invalid_message_reports = max_per_heartbeat;
+        self
+    }
+
+    /// Bounds of the gossip factor of each topic when adapting it to the health of the mesh.
+    /// If set, the gossip factor of a topic starts at [`Config::gossip_factor`]. It is increased
+    /// after each heartbeat in which the messages of the topic were rarely received more than
+    /// once, i.e. few mesh peers delivered them, and decreased again once the messages are
+    /// received redundantly.
+    ///
+    /// The default is `None`, i.e. the gossip factor is always [`Config::gossip_factor`].
+    pub fn adaptive_gossip_factor(&mut self, bounds: Option<RangeInclusive<f64>>) -> &mut Self {
+        self.config.adaptive_gossip_factor = bounds;
+        self
+    }
+
+    /// Extracts the time a message was published at, e.g. from a timestamp in its data, to
+    /// measure the latency until the message is delivered to the application. The latencies are
+    /// recorded per topic in the metrics and in [`Behaviour::delivery_latency`](crate::Behaviour::delivery_latency).
+    ///
+    /// Messages for which the function returns `None`, or a time in the future, are not
+    /// measured. The default is to measure no messages.
+    pub fn delivery_timestamp_fn<F>(&mut self, timestamp_fn: F) -> &mut Self
+    where
+        F: Fn(&Message) -> Option<SystemTime> + Send + Sync + 'static,
+    {
+        self.config.delivery_timestamp_fn = Some(Arc::new(timestamp_fn));
+        self
+    }
+
+    /// The number of recent deliveries per topic the delivery latency percentiles are computed
+    /// from, see [`Behaviour::delivery_latency`](crate::Behaviour::delivery_latency). The default
+    /// is 1000.
+    pub fn delivery_latency_samples(&mut self, samples: usize) -> &mut Self {
+        self.config.delivery_latency_samples = samples;
+        self
+    }
+
     /// Constructs a [`Config`] from the given configuration and validates the settings.
     pub fn build(&self) -> Result<Config, ConfigBuilderError> {
         // check all constraints on config
@@ -809,10 +1777,32 @@ impl ConfigBuilder {
             return Err(ConfigBuilderError::UnsubscribeBackoffIsZero);
         }
 
+        if self.config.opportunistic_graft_ticks == 0
+            || self
+                .config
+                .opportunistic_graft_threshold
+                .is_some_and(|threshold| threshold < 0.0)
+        {
+            return Err(ConfigBuilderError::OpportunisticGraftInvalid);
+        }
+
         if self.invalid_protocol {
             return Err(ConfigBuilderError::InvalidProtocol);
         }
 
+        let is_strict = |mode: &ValidationMode| matches!(mode, ValidationMode::Strict);
+        if self.config.max_chunked_message_size.is_some()
+            && !(is_strict(&self.config.protocol.validation_mode)
+                && self
+                    .config
+                    .protocol
+                    .topic_validation_modes
+                    .values()
+                    .all(is_strict))
+        {
+            return Err(ConfigBuilderError::ChunkingRequiresStrictValidation);
+        }
+
         Ok(self.config.clone())
     }
 }
@@ -833,25 +1823,81 @@ impl std::fmt::Debug for Config {
         let _ = builder.field("heartbeat_interval", &self.heartbeat_interval);
         let _ = builder.field("fanout_ttl", &self.fanout_ttl);
         let _ = builder.field("duplicate_cache_time", &self.duplicate_cache_time);
+        let _ = builder.field(
+            "explicit_peer_replay_window",
+            &self.explicit_peer_replay_window,
+        );
+        let _ = builder.field(
+            "explicit_peer_replay_max_messages",
+            &self.explicit_peer_replay_max_messages,
+        );
         let _ = builder.field("validate_messages", &self.validate_messages);
+        let _ = builder.field(
+            "max_concurrent_validations",
+            &self.max_concurrent_validations,
+        );
+        let _ = builder.field("validation_timeout", &self.validation_timeout);
         let _ = builder.field("allow_self_origin", &self.allow_self_origin);
         let _ = builder.field("do_px", &self.do_px);
         let _ = builder.field("prune_peers", &self.prune_peers);
+        let _ = builder.field(
+            "px_require_signed_peer_records",
+            &self.px_require_signed_peer_records,
+        );
+        let _ = builder.field("px_dials_per_prune", &self.px_dials_per_prune);
+        let _ = builder.field("px_dial_jitter", &self.px_dial_jitter);
+        let _ = builder.field(
+            "max_px_dials_per_heartbeat",
+            &self.max_px_dials_per_heartbeat,
+        );
         let _ = builder.field("prune_backoff", &self.prune_backoff);
+        let _ = builder.field("unsubscribe_drain_timeout", &self.unsubscribe_drain_timeout);
         let _ = builder.field("backoff_slack", &self.backoff_slack);
         let _ = builder.field("flood_publish", &self.flood_publish);
         let _ = builder.field("graft_flood_threshold", &self.graft_flood_threshold);
         let _ = builder.field("mesh_outbound_min", &self.mesh_outbound_min);
         let _ = builder.field("opportunistic_graft_ticks", &self.opportunistic_graft_ticks);
         let _ = builder.field("opportunistic_graft_peers", &self.opportunistic_graft_peers);
+        let _ = builder.field(
+            "opportunistic_graft_threshold",
+            &self.opportunistic_graft_threshold,
+        );
         let _ = builder.field("max_messages_per_rpc", &self.max_messages_per_rpc);
+        let _ = builder.field(
+            "idontwant_message_size_threshold",
+            &self.idontwant_message_size_threshold,
+        );
         let _ = builder.field("max_ihave_length", &self.max_ihave_length);
         let _ = builder.field("max_ihave_messages", &self.max_ihave_messages);
+        let _ = builder.field("max_sent_ihave_messages", &self.max_sent_ihave_messages);
+        let _ = builder.field("max_iwant_responses", &self.max_iwant_responses);
+        let _ = builder.field(
+            "control_messages_per_second",
+            &self.control_messages_per_second,
+        );
+        let _ = builder.field("control_messages_burst", &self.control_messages_burst);
         let _ = builder.field("iwant_followup_time", &self.iwant_followup_time);
         let _ = builder.field(
             "published_message_ids_cache_time",
             &self.published_message_ids_cache_time,
         );
+        let _ = builder.field("stale_mesh_peer_timeout", &self.stale_mesh_peer_timeout);
+        let _ = builder.field("mesh_reconnect_window", &self.mesh_reconnect_window);
+        let _ = builder.field("mesh_events", &self.mesh_events);
+        let _ = builder.field("saturated_queue_len", &self.saturated_queue_len);
+        let _ = builder.field(
+            "saturated_queue_heartbeats",
+            &self.saturated_queue_heartbeats,
+        );
+        let _ = builder.field("slow_peer_policy", &self.slow_peer_policy);
+        let _ = builder.field("relayed_mesh_policy", &self.relayed_mesh_policy);
+        let _ = builder.field("max_chunked_message_size", &self.max_chunked_message_size);
+        let _ = builder.field("chunk_reassembly_timeout", &self.chunk_reassembly_timeout);
+        let _ = builder.field(
+            "max_pending_chunked_messages",
+            &self.max_pending_chunked_messages,
+        );
+        let _ = builder.field("delivery_latency_samples", &self.delivery_latency_samples);
         builder.finish()
     }
 }
@@ -925,19 +1971,25 @@ mod test {
 
         let protocol_ids = protocol_config.protocol_info();
 
-        assert_eq!(protocol_ids.len(), 2);
+        assert_eq!(protocol_ids.len(), 3);
 
         assert_eq!(
             protocol_ids[0].protocol,
-            StreamProtocol::new("/purple/1.1.0")
+            StreamProtocol::new("/purple/1.2.0")
         );
-        assert_eq!(protocol_ids[0].kind, PeerKind::Gossipsubv1_1);
+        assert_eq!(protocol_ids[0].kind, PeerKind::Gossipsubv1_2);
 
         assert_eq!(
             protocol_ids[1].protocol,
+            StreamProtocol::new("/purple/1.1.0")
+        );
+        assert_eq!(protocol_ids[1].kind, PeerKind::Gossipsubv1_1);
+
+        assert_eq!(
+            protocol_ids[2].protocol,
             StreamProtocol::new("/purple/1.0.0")
         );
-        assert_eq!(protocol_ids[1].kind, PeerKind::Gossipsub);
+        assert_eq!(protocol_ids[2].kind, PeerKind::Gossipsub);
     }
 
     #[test]
@@ -956,6 +2008,30 @@ mod test {
         assert_eq!(protocol_ids[0].kind, PeerKind::Gossipsub);
     }
 
+    #[test]
+    fn chunking_requires_strict_validation() {
+        let mut builder = ConfigBuilder::default();
+        builder.max_chunked_message_size(Some(10_000));
+        assert!(builder.build().is_ok());
+
+        builder.topic_validation_mode(
+            Topic::<IdentityHash>::new("test").hash(),
+            ValidationMode::Permissive,
+        );
+        assert!(matches!(
+            builder.build(),
+            Err(ConfigBuilderError::ChunkingRequiresStrictValidation)
+        ));
+
+        assert!(matches!(
+            ConfigBuilder::default()
+                .max_chunked_message_size(Some(10_000))
+                .validation_mode(ValidationMode::Anonymous)
+                .build(),
+            Err(ConfigBuilderError::ChunkingRequiresStrictValidation)
+        ));
+    }
+
     fn get_gossipsub_message() -> Message {
         Message {
             source: None,
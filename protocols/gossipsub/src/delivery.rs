@@ -0,0 +1,136 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Reports on whether the messages we publish were written to enough of their recipients, see
+//! [`Behaviour::publish_with_delivery_report`](crate::Behaviour::publish_with_delivery_report).
+
+use libp2p_identity::PeerId;
+use std::collections::HashSet;
+use web_time::Instant;
+
+/// The outcome of the delivery of a published message, see [`Event::DeliveryReport`].
+///
+/// [`Event::DeliveryReport`]: crate::Event::DeliveryReport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    /// The message was written to the required number of peers.
+    Delivered,
+    /// The message could not be written to enough peers, e.g. because they disconnected.
+    Failed,
+    /// The message was not written to the required number of peers before the timeout.
+    TimedOut,
+}
+
+/// The delivery of a published message to its recipients, until it is reported.
+#[derive(Debug)]
+pub(crate) struct PendingDelivery {
+    /// The recipients the message was neither written to nor dropped for yet.
+    pending: HashSet<PeerId>,
+    /// The peers the message was written to.
+    delivered: Vec<PeerId>,
+    required: usize,
+    deadline: Instant,
+}
+
+impl PendingDelivery {
+    pub(crate) fn new(
+        recipients: impl IntoIterator<Item = PeerId>,
+        required: usize,
+        deadline: Instant,
+    ) -> Self {
+        Self {
+            pending: recipients.into_iter().collect(),
+            delivered: Vec::new(),
+            required,
+            deadline,
+        }
+    }
+
+    /// Returns whether the message is still pending for the given peer.
+    pub(crate) fn is_pending_for(&self, peer: &PeerId) -> bool {
+        self.pending.contains(peer)
+    }
+
+    /// Records that the message was written to the given peer.
+    pub(crate) fn on_sent(&mut self, peer: PeerId) {
+        if self.pending.remove(&peer) {
+            self.delivered.push(peer);
+        }
+    }
+
+    /// Records that the message will not be written to the given peer.
+    pub(crate) fn on_dropped(&mut self, peer: &PeerId) {
+        self.pending.remove(peer);
+    }
+
+    /// Returns the outcome of the delivery once it is known, along with the peers the message
+    /// was written to.
+    pub(crate) fn outcome(&mut self, now: Instant) -> Option<(DeliveryOutcome, Vec<PeerId>)> {
+        let outcome = if self.delivered.len() >= self.required {
+            DeliveryOutcome::Delivered
+        } else if self.delivered.len() + self.pending.len() < self.required {
+            DeliveryOutcome::Failed
+        } else if now >= self.deadline {
+            DeliveryOutcome::TimedOut
+        } else {
+            return None;
+        };
+
+        Some((outcome, std::mem::take(&mut self.delivered)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn outcome_is_known_once_enough_peers_are_reached_or_lost() {
+        let now = Instant::now();
+        let peers = [PeerId::random(), PeerId::random(), PeerId::random()];
+
+        let mut delivery = PendingDelivery::new(peers, 2, now + Duration::from_secs(10));
+        delivery.on_sent(peers[0]);
+        assert_eq!(delivery.outcome(now), None);
+        delivery.on_sent(peers[1]);
+        assert_eq!(
+            delivery.outcome(now),
+            Some((DeliveryOutcome::Delivered, vec![peers[0], peers[1]]))
+        );
+
+        let mut delivery = PendingDelivery::new(peers, 2, now + Duration::from_secs(10));
+        delivery.on_sent(peers[0]);
+        delivery.on_dropped(&peers[1]);
+        assert_eq!(delivery.outcome(now), None);
+        delivery.on_dropped(&peers[2]);
+        assert_eq!(
+            delivery.outcome(now),
+            Some((DeliveryOutcome::Failed, vec![peers[0]]))
+        );
+
+        let mut delivery = PendingDelivery::new(peers, 1, now + Duration::from_secs(10));
+        assert_eq!(delivery.outcome(now + Duration::from_secs(5)), None);
+        assert_eq!(
+            delivery.outcome(now + Duration::from_secs(10)),
+            Some((DeliveryOutcome::TimedOut, vec![]))
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Pluggable storage of the ids of the messages already seen by the router.
+
+use crate::time_cache::TimeDuplicateCache;
+use crate::MessageId;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Stores the ids of the messages seen by the router, such that duplicates are neither propagated
+/// to the application nor forwarded on the network.
+///
+/// By default, the ids are kept in memory for [`Config::duplicate_cache_time`](crate::Config).
+/// Applications expecting replays of messages long after they were first seen can back the cache
+/// with a larger store, see [`Behaviour::set_duplicate_cache`](crate::Behaviour).
+pub trait DuplicateCache: Send + 'static {
+    /// Records the id of a message.
+    ///
+    /// Returns `true` if the id was not seen before, `false` if the message is a duplicate.
+    fn insert(&mut self, message_id: MessageId) -> bool;
+
+    /// Returns whether the id of a message was seen before.
+    fn contains(&self, message_id: &MessageId) -> bool;
+}
+
+impl DuplicateCache for TimeDuplicateCache<MessageId> {
+    fn insert(&mut self, message_id: MessageId) -> bool {
+        TimeDuplicateCache::insert(self, message_id)
+    }
+
+    fn contains(&self, message_id: &MessageId) -> bool {
+        TimeDuplicateCache::contains(self, message_id)
+    }
+}
+
+/// A [`DuplicateCache`] backed by bloom filters, remembering a large number of message ids in
+/// constant memory at the cost of false positives.
+///
+/// A false positive drops a message that was never seen before, so the rate must be chosen
+/// according to the number of messages the application can afford to miss.
+///
+/// The ids are kept in two generations of filters: once the current filter holds `capacity` ids
+/// or is older than `ttl`, it replaces the previous one. Ids are therefore remembered for at least
+/// `ttl`, unless more than `capacity` messages are seen in that time.
+pub struct BloomDuplicateCache {
+    current: Vec<u64>,
+    previous: Vec<u64>,
+    /// The number of bits of each filter.
+    num_bits: u64,
+    /// The number of bits set per id.
+    num_hashes: u32,
+    capacity: usize,
+    /// The number of ids inserted in the current filter.
+    len: usize,
+    ttl: Duration,
+    rotated_at: Instant,
+}
+
+impl BloomDuplicateCache {
+    /// Creates a cache holding up to `capacity` ids per `ttl` with the given false positive rate,
+    /// e.g. `0.001`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or `false_positive_rate` is not in `(0, 1)`.
+    pub fn new(capacity: usize, false_positive_rate: f64, ttl: Duration) -> Self {
+        assert!(capacity > 0, "capacity must be greater than zero");
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate must be between 0 and 1"
+        );
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(capacity as f64) * false_positive_rate.ln() / (ln2 * ln2))
+            .ceil()
+            .max(64.0) as u64;
+        let num_hashes = (-false_positive_rate.log2()).round().max(1.0) as u32;
+        let words = num_bits.div_ceil(64) as usize;
+
+        Self {
+            current: vec![0; words],
+            previous: vec![0; words],
+            num_bits,
+            num_hashes,
+            capacity,
+            len: 0,
+            ttl,
+            rotated_at: Instant::now(),
+        }
+    }
+
+    /// Returns the positions of the bits of an id, using double hashing.
+    fn bits(&self, message_id: &MessageId) -> impl Iterator<Item = u64> {
+        let mut hasher = DefaultHasher::new();
+        message_id.hash(&mut hasher);
+        let h1 = hasher.finish();
+        h1.hash(&mut hasher);
+        let h2 = hasher.finish() | 1;
+        let num_bits = self.num_bits;
+        (0..u64::from(self.num_hashes)).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    fn rotate_if_needed(&mut self) {
+        if self.len < self.capacity && self.rotated_at.elapsed() < self.ttl {
+            return;
+        }
+        std::mem::swap(&mut self.current, &mut self.previous);
+        self.current.iter_mut().for_each(|word| *word = 0);
+        self.len = 0;
+        self.rotated_at = Instant::now();
+    }
+}
+
+fn is_set(filter: &[u64], bit: u64) -> bool {
+    filter[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+}
+
+impl DuplicateCache for BloomDuplicateCache {
+    fn insert(&mut self, message_id: MessageId) -> bool {
+        if self.contains(&message_id) {
+            return false;
+        }
+        self.rotate_if_needed();
+        for bit in self.bits(&message_id).collect::<Vec<_>>() {
+            self.current[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+        true
+    }
+
+    fn contains(&self, message_id: &MessageId) -> bool {
+        [&self.current, &self.previous]
+            .into_iter()
+            .any(|filter| self.bits(message_id).all(|bit| is_set(filter, bit)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bloom_cache_detects_duplicates() {
+        let mut cache = BloomDuplicateCache::new(1000, 0.001, Duration::from_secs(3600));
+
+        for i in 0..1000u32 {
+            assert!(cache.insert(MessageId::new(&i.to_be_bytes())));
+        }
+        for i in 0..1000u32 {
+            assert!(cache.contains(&MessageId::new(&i.to_be_bytes())));
+            assert!(!cache.insert(MessageId::new(&i.to_be_bytes())));
+        }
+
+        let false_positives = (1000..11000u32)
+            .filter(|i| cache.contains(&MessageId::new(&i.to_be_bytes())))
+            .count();
+        assert!(false_positives < 50, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn bloom_cache_forgets_ids_after_two_generations() {
+        let mut cache = BloomDuplicateCache::new(1, 0.01, Duration::from_secs(3600));
+
+        assert!(cache.insert(MessageId::new(b"a")));
+        assert!(cache.insert(MessageId::new(b"b")));
+        // `a` is in the previous generation.
+        assert!(cache.contains(&MessageId::new(b"a")));
+        assert!(cache.insert(MessageId::new(b"c")));
+        assert!(!cache.contains(&MessageId::new(b"a")));
+    }
+}
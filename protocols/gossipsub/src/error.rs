@@ -36,6 +36,9 @@ pub enum PublishError {
     MessageTooLarge,
     /// The compression algorithm failed.
     TransformFailed(std::io::Error),
+    /// The published messages would be rejected by the peers validating the topic, see
+    /// [`Config::topic_validation_mode`](crate::Config::topic_validation_mode).
+    IncompatibleValidationMode(IncompatibleValidationMode),
 }
 
 impl std::fmt::Display for PublishError {
@@ -61,6 +64,9 @@ pub enum SubscriptionError {
     PublishError(PublishError),
     /// We are not allowed to subscribe to this topic by the subscription filter
     NotAllowed,
+    /// Our messages would be rejected by the peers validating the topic, see
+    /// [`Config::topic_validation_mode`](crate::Config::topic_validation_mode).
+    IncompatibleValidationMode(IncompatibleValidationMode),
 }
 
 impl std::fmt::Display for SubscriptionError {
@@ -78,6 +84,35 @@ impl std::error::Error for SubscriptionError {
     }
 }
 
+/// Why the messages published on a topic would be rejected by the peers validating the topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncompatibleValidationMode {
+    /// The topic requires signed messages, but messages are not signed.
+    SignatureRequired,
+    /// The topic requires anonymous messages, but messages carry an author.
+    AuthorPresent,
+    /// The topic requires anonymous messages, but the message ids depend on their author and
+    /// sequence number, so that all messages would get the same id. A message id function is
+    /// required, see [`ConfigBuilder::message_id_fn`](crate::ConfigBuilder::message_id_fn) and
+    /// [`ConfigBuilder::topic_message_id_fn`](crate::ConfigBuilder::topic_message_id_fn).
+    MessageIdFnRequired,
+}
+
+impl std::fmt::Display for IncompatibleValidationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::SignatureRequired => write!(f, "Topic requires signed messages"),
+            Self::AuthorPresent => write!(f, "Topic requires messages without author"),
+            Self::MessageIdFnRequired => {
+                write!(
+                    f,
+                    "Topic requires a message id function for anonymous messages"
+                )
+            }
+        }
+    }
+}
+
 impl From<SigningError> for PublishError {
     fn from(error: SigningError) -> Self {
         PublishError::SigningError(error)
@@ -105,6 +140,9 @@ pub enum ValidationError {
     MessageSourcePresent,
     /// The data transformation failed.
     TransformFailed,
+    /// The message is a chunk outside of its chunked message, or of a chunked message published
+    /// by another peer.
+    InvalidChunk,
 }
 
 impl std::fmt::Display for ValidationError {
@@ -134,8 +172,12 @@ pub enum ConfigBuilderError {
     MeshOutboundInvalid,
     /// unsubscribe_backoff is zero
     UnsubscribeBackoffIsZero,
+    /// opportunistic_graft_ticks is zero or opportunistic_graft_threshold is negative
+    OpportunisticGraftInvalid,
     /// Invalid protocol
     InvalidProtocol,
+    /// Chunking is enabled without the strict validation mode for all topics
+    ChunkingRequiresStrictValidation,
 }
 
 impl std::error::Error for ConfigBuilderError {}
@@ -150,7 +192,9 @@ impl std::fmt::Display for ConfigBuilderError {
             Self::MeshParametersInvalid => write!(f, "The ineauality doesn't hold mesh_outbound_min <= mesh_n_low <= mesh_n <= mesh_n_high"),
             Self::MeshOutboundInvalid => write!(f, "The inequality doesn't hold mesh_outbound_min <= self.config.mesh_n / 2"),
             Self::UnsubscribeBackoffIsZero => write!(f, "unsubscribe_backoff is zero"),
+            Self::OpportunisticGraftInvalid => write!(f, "opportunistic_graft_ticks is zero or opportunistic_graft_threshold is negative"),
             Self::InvalidProtocol => write!(f, "Invalid protocol"),
+            Self::ChunkingRequiresStrictValidation => write!(f, "Chunking requires the strict validation mode for all topics"),
         }
     }
 }
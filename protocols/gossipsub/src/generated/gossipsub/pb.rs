@@ -104,6 +104,8 @@ pub struct Message {
     pub topic: String,
     pub signature: Option<Vec<u8>>,
     pub key: Option<Vec<u8>>,
+    pub expires_at: Option<u64>,
+    pub chunk: Option<gossipsub::pb::ChunkInfo>,
 }
 
 impl<'a> MessageRead<'a> for Message {
@@ -117,6 +119,8 @@ impl<'a> MessageRead<'a> for Message {
                 Ok(34) => msg.topic = r.read_string(bytes)?.to_owned(),
                 Ok(42) => msg.signature = Some(r.read_bytes(bytes)?.to_owned()),
                 Ok(50) => msg.key = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(56) => msg.expires_at = Some(r.read_uint64(bytes)?),
+                Ok(66) => msg.chunk = Some(r.read_message::<gossipsub::pb::ChunkInfo>(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -134,6 +138,8 @@ impl MessageWrite for Message {
         + 1 + sizeof_len((&self.topic).len())
         + self.signature.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
         + self.key.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.expires_at.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.chunk.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -143,6 +149,48 @@ impl MessageWrite for Message {
         w.write_with_tag(34, |w| w.write_string(&**&self.topic))?;
         if let Some(ref s) = self.signature { w.write_with_tag(42, |w| w.write_bytes(&**s))?; }
         if let Some(ref s) = self.key { w.write_with_tag(50, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.expires_at { w.write_with_tag(56, |w| w.write_uint64(*s))?; }
+        if let Some(ref s) = self.chunk { w.write_with_tag(66, |w| w.write_message(s))?; }
+        Ok(())
+    }
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ChunkInfo {
+    pub group: Option<Vec<u8>>,
+    pub index: Option<u32>,
+    pub count: Option<u32>,
+}
+
+impl<'a> MessageRead<'a> for ChunkInfo {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.group = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(16) => msg.index = Some(r.read_uint32(bytes)?),
+                Ok(24) => msg.count = Some(r.read_uint32(bytes)?),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ChunkInfo {
+    fn get_size(&self) -> usize {
+        0
+        + self.group.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.index.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.count.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.group { w.write_with_tag(10, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.index { w.write_with_tag(16, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.count { w.write_with_tag(24, |w| w.write_uint32(*s))?; }
         Ok(())
     }
 }
@@ -154,6 +202,10 @@ pub struct ControlMessage {
     pub iwant: Vec<gossipsub::pb::ControlIWant>,
     pub graft: Vec<gossipsub::pb::ControlGraft>,
     pub prune: Vec<gossipsub::pb::ControlPrune>,
+    pub idontwant: Vec<gossipsub::pb::ControlIDontWant>,
+    pub choke: Vec<gossipsub::pb::ControlChoke>,
+    pub unchoke: Vec<gossipsub::pb::ControlUnChoke>,
+    pub invalid: Vec<gossipsub::pb::ControlInvalid>,
 }
 
 impl<'a> MessageRead<'a> for ControlMessage {
@@ -165,6 +217,10 @@ impl<'a> MessageRead<'a> for ControlMessage {
                 Ok(18) => msg.iwant.push(r.read_message::<gossipsub::pb::ControlIWant>(bytes)?),
                 Ok(26) => msg.graft.push(r.read_message::<gossipsub::pb::ControlGraft>(bytes)?),
                 Ok(34) => msg.prune.push(r.read_message::<gossipsub::pb::ControlPrune>(bytes)?),
+                Ok(42) => msg.idontwant.push(r.read_message::<gossipsub::pb::ControlIDontWant>(bytes)?),
+                Ok(50) => msg.choke.push(r.read_message::<gossipsub::pb::ControlChoke>(bytes)?),
+                Ok(58) => msg.unchoke.push(r.read_message::<gossipsub::pb::ControlUnChoke>(bytes)?),
+                Ok(66) => msg.invalid.push(r.read_message::<gossipsub::pb::ControlInvalid>(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -180,6 +236,10 @@ impl MessageWrite for ControlMessage {
         + self.iwant.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
         + self.graft.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
         + self.prune.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.idontwant.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.choke.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.unchoke.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.invalid.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -187,6 +247,10 @@ impl MessageWrite for ControlMessage {
         for s in &self.iwant { w.write_with_tag(18, |w| w.write_message(s))?; }
         for s in &self.graft { w.write_with_tag(26, |w| w.write_message(s))?; }
         for s in &self.prune { w.write_with_tag(34, |w| w.write_message(s))?; }
+        for s in &self.idontwant { w.write_with_tag(42, |w| w.write_message(s))?; }
+        for s in &self.choke { w.write_with_tag(50, |w| w.write_message(s))?; }
+        for s in &self.unchoke { w.write_with_tag(58, |w| w.write_message(s))?; }
+        for s in &self.invalid { w.write_with_tag(66, |w| w.write_message(s))?; }
         Ok(())
     }
 }
@@ -259,6 +323,38 @@ impl MessageWrite for ControlIWant {
     }
 }
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ControlIDontWant {
+    pub message_ids: Vec<Vec<u8>>,
+}
+
+impl<'a> MessageRead<'a> for ControlIDontWant {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.message_ids.push(r.read_bytes(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ControlIDontWant {
+    fn get_size(&self) -> usize {
+        0
+        + self.message_ids.iter().map(|s| 1 + sizeof_len((s).len())).sum::<usize>()
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        for s in &self.message_ids { w.write_with_tag(10, |w| w.write_bytes(&**s))?; }
+        Ok(())
+    }
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct ControlGraft {
@@ -291,6 +387,118 @@ impl MessageWrite for ControlGraft {
     }
 }
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ControlChoke {
+    pub topic_id: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for ControlChoke {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.topic_id = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ControlChoke {
+    fn get_size(&self) -> usize {
+        0
+        + self.topic_id.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.topic_id { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ControlUnChoke {
+    pub topic_id: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for ControlUnChoke {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.topic_id = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ControlUnChoke {
+    fn get_size(&self) -> usize {
+        0
+        + self.topic_id.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.topic_id { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ControlInvalid {
+    pub message_id: Option<Vec<u8>>,
+    pub topic_id: Option<String>,
+    pub propagation_source: Option<Vec<u8>>,
+    pub signature: Option<Vec<u8>>,
+    pub key: Option<Vec<u8>>,
+}
+
+impl<'a> MessageRead<'a> for ControlInvalid {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.message_id = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(18) => msg.topic_id = Some(r.read_string(bytes)?.to_owned()),
+                Ok(26) => msg.propagation_source = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(34) => msg.signature = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(42) => msg.key = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ControlInvalid {
+    fn get_size(&self) -> usize {
+        0
+        + self.message_id.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.topic_id.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.propagation_source.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.signature.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.key.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.message_id { w.write_with_tag(10, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.topic_id { w.write_with_tag(18, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.propagation_source { w.write_with_tag(26, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.signature { w.write_with_tag(34, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.key { w.write_with_tag(42, |w| w.write_bytes(&**s))?; }
+        Ok(())
+    }
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct ControlPrune {
@@ -1,3 +1,4 @@
 // Automatically generated mod.rs
 pub mod compat;
 pub mod gossipsub;
+pub mod trace;
@@ -20,24 +20,31 @@
 
 use crate::protocol::{GossipsubCodec, ProtocolConfig};
 use crate::rpc_proto::proto;
-use crate::types::{PeerKind, RawMessage, Rpc, RpcOut};
-use crate::ValidationError;
+use crate::types::{unix_millis, MessageId, PeerKind, RawMessage, Rpc, RpcOut};
+use crate::{TopicHash, ValidationError};
 use asynchronous_codec::Framed;
 use futures::future::Either;
 use futures::prelude::*;
 use futures::StreamExt;
+use futures_timer::Delay;
 use libp2p_core::upgrade::DeniedUpgrade;
 use libp2p_swarm::handler::{
     ConnectionEvent, ConnectionHandler, ConnectionHandlerEvent, DialUpgradeError,
     FullyNegotiatedInbound, FullyNegotiatedOutbound, StreamUpgradeError, SubstreamProtocol,
 };
 use libp2p_swarm::Stream;
+use quick_protobuf::MessageWrite;
 use smallvec::SmallVec;
 use std::{
     pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
+    time::Duration,
 };
-use web_time::Instant;
+use web_time::{Instant, SystemTime};
 
 /// The event emitted by the Handler. This informs the behaviour of various events created
 /// by the handler.
@@ -55,6 +62,13 @@ pub enum HandlerEvent {
     /// An inbound or outbound substream has been established with the peer and this informs over
     /// which protocol. This message only occurs once per connection.
     PeerKind(PeerKind),
+    /// Messages sent via [`HandlerIn::ReportedMessage`] were written to the remote.
+    MessagesSent(Vec<MessageId>),
+    /// Messages sent via [`HandlerIn::ReportedMessage`] were dropped before being written to the
+    /// remote, e.g. because the substream failed or their deadline passed.
+    MessagesDropped(Vec<MessageId>),
+    /// No messages of the topic requested via [`HandlerIn::Drain`] are queued anymore.
+    TopicDrained(TopicHash),
 }
 
 /// A message sent from the behaviour to the handler.
@@ -63,10 +77,21 @@ pub enum HandlerEvent {
 pub enum HandlerIn {
     /// A gossipsub message to send.
     Message(RpcOut),
+    /// A message we publish, whose delivery is reported via [`HandlerEvent::MessagesSent`] or
+    /// [`HandlerEvent::MessagesDropped`].
+    ReportedMessage {
+        message: RawMessage,
+        message_id: MessageId,
+    },
     /// The peer has joined the mesh.
     JoinedMesh,
     /// The peer has left the mesh.
     LeftMesh,
+    /// Whether the peer is an explicit peer, whose connection is kept alive.
+    Explicit(bool),
+    /// Report via [`HandlerEvent::TopicDrained`] once all messages of the topic queued so far were
+    /// written or dropped.
+    Drain(TopicHash),
 }
 
 /// The maximum number of inbound or outbound substreams attempts we allow.
@@ -94,8 +119,34 @@ pub struct EnabledHandler {
     /// The single long-lived inbound substream.
     inbound_substream: Option<InboundSubstreamState>,
 
-    /// Queue of values that we want to send to the remote.
-    send_queue: SmallVec<[proto::RPC; 16]>,
+    /// Queue of values that we want to send to the remote, along with the id of the message
+    /// whose delivery is reported, if any.
+    send_queue: SmallVec<[(proto::RPC, Option<MessageId>); 16]>,
+
+    /// Queue of messages forwarded to the remote, only sent once the `send_queue` is empty such
+    /// that control messages and our own publishes are not delayed by forwarded messages.
+    forward_queue: SmallVec<[proto::RPC; 16]>,
+
+    /// The length of the send queues of all connections to the remote, shared with the behaviour.
+    queue_len: Arc<AtomicUsize>,
+
+    /// The maximum size of an RPC coalescing queued messages, if messages are coalesced.
+    batch_size: Option<usize>,
+
+    /// The time queued messages are held back to be coalesced with later ones.
+    batch_delay: Duration,
+
+    /// Fires once the queued messages are no longer held back.
+    batch_deadline: Option<Delay>,
+
+    /// The ids of the reported messages dropped since the behaviour was last informed.
+    dropped_messages: Vec<MessageId>,
+
+    /// The ids of the reported messages carried by the message being sent.
+    in_flight_messages: Vec<MessageId>,
+
+    /// The topics whose queued messages are to be reported as drained, see [`HandlerIn::Drain`].
+    draining: Vec<TopicHash>,
 
     /// Flag indicating that an outbound substream is being established to prevent duplicate
     /// requests.
@@ -120,6 +171,10 @@ pub struct EnabledHandler {
     /// Keeps track of whether this connection is for a peer in the mesh. This is used to make
     /// decisions about the keep alive state for this connection.
     in_mesh: bool,
+
+    /// Keeps track of whether this connection is for an explicit peer, which is kept alive
+    /// regardless of the mesh.
+    explicit: bool,
 }
 
 pub enum DisabledHandler {
@@ -150,7 +205,7 @@ enum OutboundSubstreamState {
     /// Waiting for the user to send a message. The idle state for an outbound substream.
     WaitingOutput(Framed<Stream, GossipsubCodec>),
     /// Waiting to send a message to the remote.
-    PendingSend(Framed<Stream, GossipsubCodec>, proto::RPC),
+    PendingSend(Framed<Stream, GossipsubCodec>, Box<proto::RPC>),
     /// Waiting to flush the substream so that the data arrives to the remote.
     PendingFlush(Framed<Stream, GossipsubCodec>),
     /// An error occurred during processing.
@@ -158,8 +213,16 @@ enum OutboundSubstreamState {
 }
 
 impl Handler {
-    /// Builds a new [`Handler`].
-    pub fn new(protocol_config: ProtocolConfig) -> Self {
+    /// Builds a new [`Handler`], accounting the length of its send queue in `queue_len`.
+    ///
+    /// If `batch_size` is set, queued messages are coalesced into RPCs of up to that size, holding
+    /// them back for up to `batch_delay` to be coalesced with later ones.
+    pub fn new(
+        protocol_config: ProtocolConfig,
+        queue_len: Arc<AtomicUsize>,
+        batch_size: Option<usize>,
+        batch_delay: Duration,
+    ) -> Self {
         Handler::Enabled(EnabledHandler {
             listen_protocol: protocol_config,
             inbound_substream: None,
@@ -168,15 +231,135 @@ impl Handler {
             outbound_substream_attempts: 0,
             inbound_substream_attempts: 0,
             send_queue: SmallVec::new(),
+            forward_queue: SmallVec::new(),
+            queue_len,
+            batch_size,
+            batch_delay,
+            batch_deadline: None,
+            dropped_messages: Vec::new(),
+            in_flight_messages: Vec::new(),
+            draining: Vec::new(),
             peer_kind: None,
             peer_kind_sent: false,
             last_io_activity: Instant::now(),
             in_mesh: false,
+            explicit: false,
         })
     }
 }
 
+impl Drop for EnabledHandler {
+    fn drop(&mut self) {
+        self.queue_len.fetch_sub(
+            self.send_queue.len() + self.forward_queue.len(),
+            Ordering::Relaxed,
+        );
+    }
+}
+
 impl EnabledHandler {
+    /// Returns whether no messages are queued to be sent.
+    fn queue_is_empty(&self) -> bool {
+        self.send_queue.is_empty() && self.forward_queue.is_empty()
+    }
+
+    /// Returns the queued messages in the order they are sent.
+    fn queued(&self) -> impl Iterator<Item = &proto::RPC> {
+        self.send_queue
+            .iter()
+            .rev()
+            .map(|(rpc, _)| rpc)
+            .chain(self.forward_queue.iter().rev())
+    }
+
+    /// Returns whether messages of the given topic are queued or being sent.
+    fn carries_topic(&self, topic: &TopicHash) -> bool {
+        let in_flight = match &self.outbound_substream {
+            Some(OutboundSubstreamState::PendingSend(_, rpc)) => {
+                rpc_carries_topic(rpc, topic.as_str())
+            }
+            Some(OutboundSubstreamState::PendingFlush(..)) => true,
+            _ => false,
+        };
+        in_flight
+            || self
+                .queued()
+                .any(|rpc| rpc_carries_topic(rpc, topic.as_str()))
+    }
+
+    /// Pops the next queued message to send, preferring the `send_queue` over the
+    /// `forward_queue`.
+    ///
+    /// Messages whose deadline passed are dropped.
+    fn pop_queued(&mut self) -> Option<(proto::RPC, Option<MessageId>)> {
+        loop {
+            let (rpc, message_id) = self
+                .send_queue
+                .pop()
+                .or_else(|| self.forward_queue.pop().map(|rpc| (rpc, None)))?;
+            self.queue_len.fetch_sub(1, Ordering::Relaxed);
+            if is_expired(&rpc) {
+                tracing::debug!("Dropping expired message from the send queue");
+                self.dropped_messages.extend(message_id);
+                continue;
+            }
+            return Some((rpc, message_id));
+        }
+    }
+
+    /// Returns whether the queued messages are held back to be coalesced with later ones, i.e.
+    /// neither the batch size nor the batch delay were reached.
+    fn hold_batch(&mut self, cx: &mut Context<'_>) -> bool {
+        let Some(batch_size) = self.batch_size else {
+            return false;
+        };
+        if self.batch_delay.is_zero() || self.queue_is_empty() {
+            return false;
+        }
+
+        let mut size = 0;
+        let full = self.queued().any(|rpc| {
+            size += rpc.get_size();
+            size >= batch_size
+        });
+        let delay = self.batch_delay;
+        let deadline = self.batch_deadline.get_or_insert_with(|| Delay::new(delay));
+        if full || deadline.poll_unpin(cx).is_ready() {
+            self.batch_deadline = None;
+            return false;
+        }
+
+        true
+    }
+
+    /// Pops the next RPC to send from the queue, coalescing it with further queued messages if
+    /// batching is enabled, along with the ids of the reported messages it carries.
+    fn pop_rpc(&mut self) -> Option<(proto::RPC, Vec<MessageId>)> {
+        let (mut rpc, message_id) = self.pop_queued()?;
+        let mut message_ids = Vec::from_iter(message_id);
+
+        if let Some(batch_size) = self.batch_size {
+            // The sum of the sizes of the RPCs is an upper bound of the size of the coalesced RPC.
+            let mut size = rpc.get_size();
+            loop {
+                let Some(next_size) = self.queued().next().map(|next| next.get_size()) else {
+                    break;
+                };
+                size += next_size;
+                if size > batch_size {
+                    break;
+                }
+                let (next, message_id) = self.pop_queued().expect("queue not to be empty");
+                coalesce(&mut rpc, next);
+                message_ids.extend(message_id);
+            }
+        }
+        self.send_queue.shrink_to_fit();
+        self.forward_queue.shrink_to_fit();
+
+        Some((rpc, message_ids))
+    }
+
     fn on_fully_negotiated_inbound(
         &mut self,
         (substream, peer_kind): (Framed<Stream, GossipsubCodec>, PeerKind),
@@ -232,7 +415,7 @@ impl EnabledHandler {
         }
 
         // determine if we need to create the outbound stream
-        if !self.send_queue.is_empty()
+        if !self.queue_is_empty()
             && self.outbound_substream.is_none()
             && !self.outbound_substream_establishing
         {
@@ -250,10 +433,17 @@ impl EnabledHandler {
             ) {
                 // outbound idle state
                 Some(OutboundSubstreamState::WaitingOutput(substream)) => {
-                    if let Some(message) = self.send_queue.pop() {
-                        self.send_queue.shrink_to_fit();
+                    if self.hold_batch(cx) {
                         self.outbound_substream =
-                            Some(OutboundSubstreamState::PendingSend(substream, message));
+                            Some(OutboundSubstreamState::WaitingOutput(substream));
+                        break;
+                    }
+                    if let Some((message, message_ids)) = self.pop_rpc() {
+                        self.in_flight_messages = message_ids;
+                        self.outbound_substream = Some(OutboundSubstreamState::PendingSend(
+                            substream,
+                            Box::new(message),
+                        ));
                         continue;
                     }
 
@@ -264,7 +454,7 @@ impl EnabledHandler {
                 Some(OutboundSubstreamState::PendingSend(mut substream, message)) => {
                     match Sink::poll_ready(Pin::new(&mut substream), cx) {
                         Poll::Ready(Ok(())) => {
-                            match Sink::start_send(Pin::new(&mut substream), message) {
+                            match Sink::start_send(Pin::new(&mut substream), *message) {
                                 Ok(()) => {
                                     self.outbound_substream =
                                         Some(OutboundSubstreamState::PendingFlush(substream))
@@ -274,6 +464,7 @@ impl EnabledHandler {
                                         "Failed to send message on outbound stream: {e}"
                                     );
                                     self.outbound_substream = None;
+                                    self.dropped_messages.append(&mut self.in_flight_messages);
                                     break;
                                 }
                             }
@@ -281,6 +472,7 @@ impl EnabledHandler {
                         Poll::Ready(Err(e)) => {
                             tracing::debug!("Failed to send message on outbound stream: {e}");
                             self.outbound_substream = None;
+                            self.dropped_messages.append(&mut self.in_flight_messages);
                             break;
                         }
                         Poll::Pending => {
@@ -295,11 +487,19 @@ impl EnabledHandler {
                         Poll::Ready(Ok(())) => {
                             self.last_io_activity = Instant::now();
                             self.outbound_substream =
-                                Some(OutboundSubstreamState::WaitingOutput(substream))
+                                Some(OutboundSubstreamState::WaitingOutput(substream));
+                            if !self.in_flight_messages.is_empty() {
+                                return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                                    HandlerEvent::MessagesSent(std::mem::take(
+                                        &mut self.in_flight_messages,
+                                    )),
+                                ));
+                            }
                         }
                         Poll::Ready(Err(e)) => {
                             tracing::debug!("Failed to flush outbound stream: {e}");
                             self.outbound_substream = None;
+                            self.dropped_messages.append(&mut self.in_flight_messages);
                             break;
                         }
                         Poll::Pending => {
@@ -319,6 +519,22 @@ impl EnabledHandler {
             }
         }
 
+        if !self.dropped_messages.is_empty() {
+            return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                HandlerEvent::MessagesDropped(std::mem::take(&mut self.dropped_messages)),
+            ));
+        }
+
+        if let Some(index) = self
+            .draining
+            .iter()
+            .position(|topic| !self.carries_topic(topic))
+        {
+            return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                HandlerEvent::TopicDrained(self.draining.swap_remove(index)),
+            ));
+        }
+
         loop {
             match std::mem::replace(
                 &mut self.inbound_substream,
@@ -409,13 +625,35 @@ impl ConnectionHandler for Handler {
     fn on_behaviour_event(&mut self, message: HandlerIn) {
         match self {
             Handler::Enabled(handler) => match message {
-                HandlerIn::Message(m) => handler.send_queue.push(m.into_protobuf()),
+                HandlerIn::Message(m) => {
+                    if let RpcOut::Forward(_) = m {
+                        handler.forward_queue.push(m.into_protobuf());
+                    } else {
+                        handler.send_queue.push((m.into_protobuf(), None));
+                    }
+                    handler.queue_len.fetch_add(1, Ordering::Relaxed);
+                }
+                HandlerIn::ReportedMessage {
+                    message,
+                    message_id,
+                } => {
+                    handler
+                        .send_queue
+                        .push((RpcOut::Publish(message).into_protobuf(), Some(message_id)));
+                    handler.queue_len.fetch_add(1, Ordering::Relaxed);
+                }
                 HandlerIn::JoinedMesh => {
                     handler.in_mesh = true;
                 }
                 HandlerIn::LeftMesh => {
                     handler.in_mesh = false;
                 }
+                HandlerIn::Explicit(explicit) => {
+                    handler.explicit = explicit;
+                }
+                HandlerIn::Drain(topic) => {
+                    handler.draining.push(topic);
+                }
             },
             Handler::Disabled(_) => {
                 tracing::debug!(?message, "Handler is disabled. Dropping message");
@@ -424,7 +662,7 @@ impl ConnectionHandler for Handler {
     }
 
     fn connection_keep_alive(&self) -> bool {
-        matches!(self, Handler::Enabled(h) if h.in_mesh)
+        matches!(self, Handler::Enabled(h) if h.in_mesh || h.explicit)
     }
 
     #[tracing::instrument(level = "trace", name = "ConnectionHandler::poll", skip(self, cx))]
@@ -499,7 +737,7 @@ impl ConnectionHandler for Handler {
                         handler.on_fully_negotiated_outbound(fully_negotiated_outbound)
                     }
                     ConnectionEvent::DialUpgradeError(DialUpgradeError {
-                        error: StreamUpgradeError::Timeout,
+                        error: StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout,
                         ..
                     }) => {
                         tracing::debug!("Dial upgrade error: Protocol negotiation timeout");
@@ -533,3 +771,247 @@ impl ConnectionHandler for Handler {
         }
     }
 }
+
+/// Returns whether the RPC only carries messages whose deadline passed.
+fn is_expired(rpc: &proto::RPC) -> bool {
+    if rpc.publish.is_empty() || !rpc.subscriptions.is_empty() || rpc.control.is_some() {
+        return false;
+    }
+    let now = unix_millis(SystemTime::now());
+    rpc.publish.iter().all(|message| {
+        message
+            .expires_at
+            .is_some_and(|expires_at| now >= expires_at)
+    })
+}
+
+/// Returns whether the RPC carries messages, subscriptions or PRUNEs of the given topic.
+fn rpc_carries_topic(rpc: &proto::RPC, topic: &str) -> bool {
+    rpc.publish.iter().any(|message| message.topic == topic)
+        || rpc
+            .subscriptions
+            .iter()
+            .any(|sub| sub.topic_id.as_deref() == Some(topic))
+        || rpc.control.as_ref().is_some_and(|control| {
+            control
+                .prune
+                .iter()
+                .any(|prune| prune.topic_id.as_deref() == Some(topic))
+        })
+}
+
+/// Appends the subscriptions, messages and control messages of `other` to `rpc`.
+fn coalesce(rpc: &mut proto::RPC, other: proto::RPC) {
+    rpc.subscriptions.extend(other.subscriptions);
+    rpc.publish.extend(other.publish);
+    if let Some(other) = other.control {
+        let control = rpc.control.get_or_insert_with(Default::default);
+        control.ihave.extend(other.ihave);
+        control.iwant.extend(other.iwant);
+        control.graft.extend(other.graft);
+        control.prune.extend(other.prune);
+        control.idontwant.extend(other.idontwant);
+        control.choke.extend(other.choke);
+        control.unchoke.extend(other.unchoke);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future::poll_fn};
+
+    fn enabled_handler(batch_size: Option<usize>, batch_delay: Duration) -> EnabledHandler {
+        let queue_len = Arc::new(AtomicUsize::new(0));
+        match Handler::new(
+            ProtocolConfig::default(),
+            queue_len,
+            batch_size,
+            batch_delay,
+        ) {
+            Handler::Enabled(handler) => handler,
+            Handler::Disabled(_) => unreachable!(),
+        }
+    }
+
+    fn queue_publish(handler: &mut EnabledHandler, data: Vec<u8>) {
+        handler.send_queue.push((
+            proto::RPC {
+                publish: vec![proto::Message {
+                    data: Some(data),
+                    topic: "topic".into(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            None,
+        ));
+        handler.queue_len.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn queued_messages_are_coalesced_up_to_batch_size() {
+        let mut handler = enabled_handler(Some(100), Duration::ZERO);
+        for i in 0..5 {
+            queue_publish(&mut handler, vec![i; 30]);
+        }
+        handler.send_queue.push((
+            proto::RPC {
+                control: Some(proto::ControlMessage {
+                    graft: vec![proto::ControlGraft {
+                        topic_id: Some("topic".into()),
+                    }],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            None,
+        ));
+        handler.queue_len.fetch_add(1, Ordering::Relaxed);
+
+        let (first, _) = handler.pop_rpc().unwrap();
+        assert!(first.get_size() <= 100);
+        assert_eq!(first.publish.len(), 2);
+        assert_eq!(first.control.unwrap().graft.len(), 1);
+        assert_eq!(handler.pop_rpc().unwrap().0.publish.len(), 2);
+        assert_eq!(handler.pop_rpc().unwrap().0.publish.len(), 1);
+        assert!(handler.pop_rpc().is_none());
+        assert_eq!(handler.queue_len.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn forwarded_messages_are_sent_last() {
+        let mut handler = Handler::new(
+            ProtocolConfig::default(),
+            Arc::new(AtomicUsize::new(0)),
+            None,
+            Duration::ZERO,
+        );
+        let message = RawMessage {
+            source: None,
+            data: vec![1, 2, 3],
+            sequence_number: None,
+            topic: TopicHash::from_raw("topic"),
+            signature: None,
+            key: None,
+            expires_at: None,
+            chunk: None,
+            validated: true,
+        };
+        handler.on_behaviour_event(HandlerIn::Message(RpcOut::Forward(message.clone())));
+        handler.on_behaviour_event(HandlerIn::Message(RpcOut::Publish(message)));
+        handler.on_behaviour_event(HandlerIn::Message(RpcOut::Subscribe(TopicHash::from_raw(
+            "topic",
+        ))));
+        let Handler::Enabled(mut handler) = handler else {
+            unreachable!()
+        };
+
+        assert_eq!(handler.pop_rpc().unwrap().0.subscriptions.len(), 1);
+        assert_eq!(
+            handler.pop_rpc().unwrap().0.publish[0].data,
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(handler.forward_queue.len(), 1);
+        assert!(handler.pop_rpc().is_some());
+        assert!(handler.queue_is_empty());
+    }
+
+    #[test]
+    fn expired_messages_are_dropped_from_the_queue() {
+        let mut handler = enabled_handler(None, Duration::ZERO);
+        let now = unix_millis(SystemTime::now());
+        for expires_at in [now + 60_000, now - 1] {
+            handler.forward_queue.push(proto::RPC {
+                publish: vec![proto::Message {
+                    data: Some(vec![1]),
+                    topic: "topic".into(),
+                    expires_at: Some(expires_at),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            });
+            handler.queue_len.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let (rpc, _) = handler.pop_rpc().unwrap();
+        assert_eq!(rpc.publish[0].expires_at, Some(now + 60_000));
+        assert!(handler.pop_rpc().is_none());
+        assert_eq!(handler.queue_len.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn reported_messages_are_tracked_through_the_queue() {
+        let mut handler = Handler::new(
+            ProtocolConfig::default(),
+            Arc::new(AtomicUsize::new(0)),
+            Some(1024),
+            Duration::ZERO,
+        );
+        let now = unix_millis(SystemTime::now());
+        for (id, expires_at) in [(0u8, now + 60_000), (1, now - 1), (2, now + 60_000)] {
+            let message = RawMessage {
+                source: None,
+                data: vec![id],
+                sequence_number: None,
+                topic: TopicHash::from_raw("topic"),
+                signature: None,
+                key: None,
+                expires_at: Some(expires_at),
+                chunk: None,
+                validated: true,
+            };
+            handler.on_behaviour_event(HandlerIn::ReportedMessage {
+                message,
+                message_id: MessageId::new(&[id]),
+            });
+        }
+        let Handler::Enabled(mut handler) = handler else {
+            unreachable!()
+        };
+
+        let (rpc, message_ids) = handler.pop_rpc().unwrap();
+        assert_eq!(rpc.publish.len(), 2);
+        assert_eq!(
+            message_ids,
+            vec![MessageId::new(&[2]), MessageId::new(&[0])]
+        );
+        assert_eq!(handler.dropped_messages, vec![MessageId::new(&[1])]);
+    }
+
+    #[test]
+    fn topic_is_drained_once_its_messages_are_sent() {
+        let mut handler = enabled_handler(None, Duration::ZERO);
+        queue_publish(&mut handler, vec![1]);
+        handler.forward_queue.push(proto::RPC {
+            publish: vec![proto::Message {
+                data: Some(vec![2]),
+                topic: "other".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        handler.queue_len.fetch_add(1, Ordering::Relaxed);
+        let topic = TopicHash::from_raw("topic");
+
+        assert!(handler.carries_topic(&topic));
+        handler.pop_rpc().unwrap();
+        assert!(!handler.carries_topic(&topic));
+        assert!(handler.carries_topic(&TopicHash::from_raw("other")));
+    }
+
+    #[test]
+    fn messages_are_held_back_until_batch_is_full() {
+        let mut handler = enabled_handler(Some(100), Duration::from_secs(60));
+
+        block_on(poll_fn(|cx| {
+            queue_publish(&mut handler, vec![0; 30]);
+            assert!(handler.hold_batch(cx));
+            queue_publish(&mut handler, vec![1; 30]);
+            assert!(handler.hold_batch(cx));
+            queue_publish(&mut handler, vec![2; 30]);
+            assert!(!handler.hold_batch(cx));
+            Poll::Ready(())
+        }));
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The latency from publishing messages to delivering them to the application, per topic, see
+//! [`ConfigBuilder::delivery_timestamp_fn`](crate::ConfigBuilder::delivery_timestamp_fn).
+
+use crate::TopicHash;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Percentiles of the latency of the recent deliveries on a topic, see
+/// [`Behaviour::delivery_latency`](crate::Behaviour::delivery_latency).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyPercentiles {
+    /// The number of deliveries the percentiles are computed from.
+    pub samples: usize,
+    /// The median latency.
+    pub p50: Duration,
+    /// The 90th percentile of the latency.
+    pub p90: Duration,
+    /// The 99th percentile of the latency.
+    pub p99: Duration,
+    /// The maximum latency.
+    pub max: Duration,
+}
+
+/// The latencies of the recent deliveries per topic, bounded to a number of samples per topic.
+#[derive(Debug)]
+pub(crate) struct DeliveryLatencies {
+    samples: HashMap<TopicHash, VecDeque<Duration>>,
+    max_samples: usize,
+}
+
+impl DeliveryLatencies {
+    pub(crate) fn new(max_samples: usize) -> Self {
+        Self {
+            samples: HashMap::new(),
+            max_samples,
+        }
+    }
+
+    /// Records the latency of a delivery on the given topic, evicting the oldest one if needed.
+    pub(crate) fn record(&mut self, topic: &TopicHash, latency: Duration) {
+        if self.max_samples == 0 {
+            return;
+        }
+
+        let samples = self.samples.entry(topic.clone()).or_default();
+        if samples.len() == self.max_samples {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    /// The percentiles of the recorded latencies on the given topic, if any.
+    pub(crate) fn percentiles(&self, topic: &TopicHash) -> Option<LatencyPercentiles> {
+        let mut samples = self.samples.get(topic)?.iter().copied().collect::<Vec<_>>();
+        samples.sort_unstable();
+        let percentile = |p: usize| samples[(samples.len() * p).div_ceil(100).max(1) - 1];
+
+        Some(LatencyPercentiles {
+            samples: samples.len(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: *samples.last()?,
+        })
+    }
+
+    /// Forgets the latencies on the given topic.
+    pub(crate) fn remove(&mut self, topic: &TopicHash) {
+        self.samples.remove(topic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_of_recent_samples() {
+        let topic = TopicHash::from_raw("topic");
+        let mut latencies = DeliveryLatencies::new(100);
+        assert!(latencies.percentiles(&topic).is_none());
+
+        // The oldest sample is evicted.
+        for millis in 0..=100 {
+            latencies.record(&topic, Duration::from_millis(millis));
+        }
+
+        let percentiles = latencies.percentiles(&topic).unwrap();
+        assert_eq!(percentiles.samples, 100);
+        assert_eq!(percentiles.p50, Duration::from_millis(50));
+        assert_eq!(percentiles.p90, Duration::from_millis(90));
+        assert_eq!(percentiles.p99, Duration::from_millis(99));
+        assert_eq!(percentiles.max, Duration::from_millis(100));
+    }
+}
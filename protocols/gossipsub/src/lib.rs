@@ -95,37 +95,62 @@
 
 mod backoff;
 mod behaviour;
+mod choking;
+mod chunking;
 mod config;
+mod delivery;
+mod duplicate_cache;
 mod error;
 mod gossip_promises;
 mod handler;
+mod latency;
 mod mcache;
 mod metrics;
 mod peer_score;
 mod protocol;
 mod rpc_proto;
+mod sharding;
 mod subscription_filter;
 mod time_cache;
 mod topic;
+mod tracer;
 mod transform;
 mod types;
 
 pub use self::behaviour::{Behaviour, Event, MessageAuthenticity};
-pub use self::config::{Config, ConfigBuilder, ValidationMode, Version};
-pub use self::error::{ConfigBuilderError, PublishError, SubscriptionError, ValidationError};
+pub use self::chunking::Chunk;
+pub use self::config::{
+    Config, ConfigBuilder, RelayedMeshPolicy, SlowPeerPolicy, TopicMeshParams, ValidationMode,
+    Version,
+};
+pub use self::delivery::DeliveryOutcome;
+pub use self::duplicate_cache::{BloomDuplicateCache, DuplicateCache};
+pub use self::error::{
+    ConfigBuilderError, IncompatibleValidationMode, PublishError, SubscriptionError,
+    ValidationError,
+};
+pub use self::latency::LatencyPercentiles;
 pub use self::metrics::Config as MetricsConfig;
 pub use self::peer_score::{
-    score_parameter_decay, score_parameter_decay_with_base, PeerScoreParams, PeerScoreThresholds,
-    TopicScoreParams,
+    score_parameter_decay, score_parameter_decay_with_base, PeerScoreBreakdown, PeerScoreParams,
+    PeerScoreSnapshot, PeerScoreState, PeerScoreThresholds, TopicScoreBreakdown, TopicScoreParams,
+    TopicScoreState,
 };
+pub use self::sharding::ShardedTopics;
 pub use self::subscription_filter::{
     AllowAllSubscriptionFilter, CallbackSubscriptionFilter, CombinedSubscriptionFilters,
-    MaxCountSubscriptionFilter, RegexSubscriptionFilter, TopicSubscriptionFilter,
-    WhitelistSubscriptionFilter,
+    DynamicSubscriptionFilter, MaxCountSubscriptionFilter, RegexSubscriptionFilter, TopicRule,
+    TopicSubscriptionFilter, WhitelistSubscriptionFilter,
 };
 pub use self::topic::{Hasher, Topic, TopicHash};
+pub use self::tracer::{
+    FileTracer, RemoteTraceWriter, RemoteTracer, TraceEvent, TraceSink, REMOTE_TRACER_PROTOCOL,
+};
 pub use self::transform::{DataTransform, IdentityTransform};
-pub use self::types::{Message, MessageAcceptance, MessageId, RawMessage};
+pub use self::types::{
+    ControlBudget, MeshJoinReason, MeshLeaveReason, Message, MessageAcceptance, MessageId,
+    RawMessage,
+};
 
 #[deprecated(note = "Will be removed from the public API.")]
 pub type Rpc = self::types::Rpc;
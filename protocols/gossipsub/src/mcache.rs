@@ -27,6 +27,7 @@ use std::{
     collections::{HashMap, HashSet},
     fmt,
 };
+use web_time::SystemTime;
 
 /// CacheEntry stored in the history.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -105,6 +106,11 @@ impl MessageCache {
         }
     }
 
+    /// Returns whether the message with `message_id` is cached.
+    pub(crate) fn contains(&self, message_id: &MessageId) -> bool {
+        self.msgs.contains_key(message_id)
+    }
+
     /// Get a message with `message_id`
     #[cfg(test)]
     pub(crate) fn get(&self, message_id: &MessageId) -> Option<&RawMessage> {
@@ -205,6 +211,20 @@ impl MessageCache {
         self.history.insert(0, Vec::new());
     }
 
+    /// Removes the messages whose deadline passed at the given time, such that they are no longer
+    /// gossiped nor sent in response to IWANTs.
+    pub(crate) fn remove_expired(&mut self, now: SystemTime) {
+        let iwant_counts = &mut self.iwant_counts;
+        self.msgs.retain(|message_id, (msg, _)| {
+            let expired = msg.is_expired(now);
+            if expired {
+                tracing::trace!(message=%message_id, "Remove expired message from the cache");
+                iwant_counts.remove(message_id);
+            }
+            !expired
+        });
+    }
+
     /// Removes a message from the cache and returns it if existent
     pub(crate) fn remove(
         &mut self,
@@ -243,6 +263,8 @@ mod tests {
             signature: None,
             key: None,
             validated: false,
+            expires_at: None,
+            chunk: None,
         };
 
         let id = default_id(&m);
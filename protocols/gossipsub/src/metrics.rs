@@ -22,16 +22,19 @@
 //! protocol.
 
 use std::collections::HashMap;
+use std::time::Duration;
 
 use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::{Family, MetricConstructor};
 use prometheus_client::metrics::gauge::Gauge;
-use prometheus_client::metrics::histogram::{linear_buckets, Histogram};
+use prometheus_client::metrics::histogram::{exponential_buckets, linear_buckets, Histogram};
 use prometheus_client::registry::Registry;
+use web_time::Instant;
 
+use crate::config::SlowPeerPolicy;
 use crate::topic::TopicHash;
-use crate::types::{MessageAcceptance, PeerKind};
+use crate::types::{MessageAcceptance, MessageId, PeerKind};
 
 // Default value that limits for how many topics do we store metrics.
 const DEFAULT_MAX_TOPICS: usize = 300;
@@ -111,6 +114,8 @@ pub(crate) struct Metrics {
     /* Auxiliary variables */
     /// Information needed to decide if a topic is allowed or not.
     topic_info: HashMap<TopicHash, EverSubscribed>,
+    /// The time at which we requested the messages we are awaiting in response to an IWANT.
+    pending_iwants: HashMap<MessageId, Instant>,
 
     /* Metrics per known topic */
     /// Status of our subscription to this topic. This metric allows analyzing other topic metrics
@@ -151,15 +156,19 @@ pub(crate) struct Metrics {
     topic_msg_recv_counts: Family<TopicHash, Counter>,
     /// Bytes received from gossip messages for each topic.
     topic_msg_recv_bytes: Family<TopicHash, Counter>,
+    /// Number of duplicate messages received on each topic.
+    topic_msg_recv_duplicates: Family<TopicHash, Counter>,
 
     /* Metrics related to scoring */
     /// Histogram of the scores for each mesh topic.
     score_per_mesh: Family<TopicHash, Histogram, HistBuilder>,
     /// A counter of the kind of penalties being applied to peers.
     scoring_penalties: Family<PenaltyLabel, Counter>,
+    /// Counter of the actions taken against peers whose outbound queue stayed saturated.
+    slow_peer_actions: Family<SlowPeerLabel, Counter>,
 
     /* General Metrics */
-    /// Gossipsub supports floodsub, gossipsub v1.0 and gossipsub v1.1. Peers are classified based
+    /// Gossipsub supports floodsub, gossipsub v1.0, v1.1 and v1.2. Peers are classified based
     /// on which protocol they support. This metric keeps track of the number of peers that are
     /// connected of each type.
     peers_per_protocol: Family<ProtocolLabel, Gauge>,
@@ -174,6 +183,22 @@ pub(crate) struct Metrics {
     /// The number of times we have decided that an IWANT control message is required for this
     /// topic. A very high metric might indicate an underperforming network.
     topic_iwant_msgs: Family<TopicHash, Counter>,
+    /// The time it takes for a message we requested with an IWANT to arrive, per topic.
+    topic_iwant_latency: Family<TopicHash, Histogram, HistBuilder>,
+    /// The time from publishing a message to delivering it to the application, per topic, for
+    /// messages carrying a timestamp.
+    topic_delivery_latency: Family<TopicHash, Histogram, HistBuilder>,
+    /// The number of IDONTWANT control messages we have sent for this topic.
+    topic_idontwant_msgs_sent: Family<TopicHash, Counter>,
+    /// The number of forwards of messages on this topic we have withheld because the recipient
+    /// sent us an IDONTWANT for the message.
+    topic_msg_withheld: Family<TopicHash, Counter>,
+    /// The number of received messages on this topic dropped because their deadline passed.
+    topic_msg_expired: Family<TopicHash, Counter>,
+    /// The number of IDONTWANT control messages received.
+    idontwant_msgs: Counter,
+    /// The number of message ids received in IDONTWANT control messages.
+    idontwant_msgs_ids: Counter,
 }
 
 impl Metrics {
@@ -260,6 +285,10 @@ impl Metrics {
             "topic_msg_recv_bytes",
             "Bytes received from gossip messages for each topic"
         );
+        let topic_msg_recv_duplicates = register_family!(
+            "topic_msg_recv_duplicates",
+            "Number of duplicate gossip messages received on each topic"
+        );
 
         let hist_builder = HistBuilder {
             buckets: score_buckets,
@@ -276,6 +305,10 @@ impl Metrics {
             "scoring_penalties",
             "Counter of types of scoring penalties given to peers"
         );
+        let slow_peer_actions = register_family!(
+            "slow_peer_actions",
+            "Counter of the actions taken against peers whose outbound queue stayed saturated"
+        );
         let peers_per_protocol = register_family!(
             "peers_per_protocol",
             "Number of connected peers by protocol type"
@@ -292,6 +325,54 @@ impl Metrics {
             "topic_iwant_msgs",
             "Number of times we have decided an IWANT is required for this topic"
         );
+        let topic_iwant_latency: Family<_, _, HistBuilder> =
+            Family::new_with_constructor(HistBuilder {
+                buckets: exponential_buckets(0.01, 2.0, 10).collect(),
+            });
+        registry.register(
+            "topic_iwant_latency",
+            "Histogram of the seconds until a message requested with an IWANT arrives per topic",
+            topic_iwant_latency.clone(),
+        );
+        let topic_delivery_latency: Family<_, _, HistBuilder> =
+            Family::new_with_constructor(HistBuilder {
+                buckets: exponential_buckets(0.01, 2.0, 12).collect(),
+            });
+        registry.register(
+            "topic_delivery_latency",
+            "Histogram of the seconds from publishing a message to delivering it per topic",
+            topic_delivery_latency.clone(),
+        );
+        let topic_idontwant_msgs_sent = register_family!(
+            "topic_idontwant_msgs_sent",
+            "Number of IDONTWANT control messages sent for each topic"
+        );
+        let topic_msg_withheld = register_family!(
+            "topic_msg_withheld",
+            "Number of message forwards withheld due to an IDONTWANT of the recipient for each topic"
+        );
+        let topic_msg_expired = register_family!(
+            "topic_msg_expired",
+            "Number of received messages dropped because their deadline passed for each topic"
+        );
+        let idontwant_msgs = {
+            let metric = Counter::default();
+            registry.register(
+                "idontwant_msgs",
+                "Number of IDONTWANT control messages received",
+                metric.clone(),
+            );
+            metric
+        };
+        let idontwant_msgs_ids = {
+            let metric = Counter::default();
+            registry.register(
+                "idontwant_msgs_ids",
+                "Number of message ids received in IDONTWANT control messages",
+                metric.clone(),
+            );
+            metric
+        };
         let memcache_misses = {
             let metric = Counter::default();
             registry.register(
@@ -306,6 +387,7 @@ impl Metrics {
             max_topics,
             max_never_subscribed_topics,
             topic_info: HashMap::default(),
+            pending_iwants: HashMap::default(),
             topic_subscription_status,
             topic_peers_count,
             invalid_messages,
@@ -321,12 +403,21 @@ impl Metrics {
             topic_msg_recv_counts_unfiltered,
             topic_msg_recv_counts,
             topic_msg_recv_bytes,
+            topic_msg_recv_duplicates,
             score_per_mesh,
             scoring_penalties,
+            slow_peer_actions,
             peers_per_protocol,
             heartbeat_duration,
             memcache_misses,
             topic_iwant_msgs,
+            topic_iwant_latency,
+            topic_delivery_latency,
+            topic_idontwant_msgs_sent,
+            topic_msg_withheld,
+            topic_msg_expired,
+            idontwant_msgs,
+            idontwant_msgs_ids,
         }
     }
 
@@ -428,6 +519,13 @@ impl Metrics {
         }
     }
 
+    /// Register an action taken against a peer whose outbound queue stayed saturated.
+    pub(crate) fn register_slow_peer_action(&mut self, action: SlowPeerPolicy) {
+        self.slow_peer_actions
+            .get_or_create(&SlowPeerLabel { action })
+            .inc();
+    }
+
     /// Register a score penalty.
     pub(crate) fn register_score_penalty(&mut self, penalty: Penalty) {
         self.scoring_penalties
@@ -471,6 +569,13 @@ impl Metrics {
         }
     }
 
+    /// Register that a duplicate message was received.
+    pub(crate) fn duplicate_msg_recvd(&mut self, topic: &TopicHash) {
+        if self.register_topic(topic).is_ok() {
+            self.topic_msg_recv_duplicates.get_or_create(topic).inc();
+        }
+    }
+
     pub(crate) fn register_msg_validation(
         &mut self,
         topic: &TopicHash,
@@ -497,6 +602,70 @@ impl Metrics {
         }
     }
 
+    /// Register the messages requested in an IWANT control message, to observe how long it takes
+    /// for them to arrive.
+    pub(crate) fn register_iwants_sent(&mut self, message_ids: &[MessageId]) {
+        let now = Instant::now();
+        for message_id in message_ids {
+            self.pending_iwants.entry(message_id.clone()).or_insert(now);
+        }
+    }
+
+    /// Register the arrival of a message, observing the latency if it was requested with an
+    /// IWANT.
+    pub(crate) fn iwant_fulfilled(&mut self, message_id: &MessageId, topic: &TopicHash) {
+        let Some(requested) = self.pending_iwants.remove(message_id) else {
+            return;
+        };
+        if self.register_topic(topic).is_ok() {
+            self.topic_iwant_latency
+                .get_or_create(topic)
+                .observe(requested.elapsed().as_secs_f64());
+        }
+    }
+
+    /// Observe the latency from publishing a message to delivering it to the application.
+    pub(crate) fn observe_delivery_latency(&mut self, topic: &TopicHash, latency: Duration) {
+        if self.register_topic(topic).is_ok() {
+            self.topic_delivery_latency
+                .get_or_create(topic)
+                .observe(latency.as_secs_f64());
+        }
+    }
+
+    /// Forgets the messages requested with an IWANT longer ago than the given timeout.
+    pub(crate) fn expire_iwants(&mut self, timeout: Duration) {
+        self.pending_iwants
+            .retain(|_, requested| requested.elapsed() < timeout);
+    }
+
+    /// Register sending an IDONTWANT msg for this topic.
+    pub(crate) fn register_idontwant_sent(&mut self, topic: &TopicHash) {
+        if self.register_topic(topic).is_ok() {
+            self.topic_idontwant_msgs_sent.get_or_create(topic).inc();
+        }
+    }
+
+    /// Register receiving an IDONTWANT msg with the given number of message ids.
+    pub(crate) fn register_idontwant(&mut self, msg_ids: usize) {
+        self.idontwant_msgs.inc();
+        self.idontwant_msgs_ids.inc_by(msg_ids as u64);
+    }
+
+    /// Register withholding a message on this topic from a peer that sent an IDONTWANT for it.
+    pub(crate) fn register_msg_withheld(&mut self, topic: &TopicHash) {
+        if self.register_topic(topic).is_ok() {
+            self.topic_msg_withheld.get_or_create(topic).inc();
+        }
+    }
+
+    /// Register a received message dropped because its deadline passed.
+    pub(crate) fn register_msg_expired(&mut self, topic: &TopicHash) {
+        if self.register_topic(topic).is_ok() {
+            self.topic_msg_expired.get_or_create(topic).inc();
+        }
+    }
+
     /// Observes a heartbeat duration.
     pub(crate) fn observe_heartbeat_duration(&mut self, millis: u64) {
         self.heartbeat_duration.observe(millis as f64);
@@ -554,6 +723,12 @@ pub(crate) enum Churn {
     Unsub,
     /// Too many peers.
     Excess,
+    /// Peer did not deliver any message for too long.
+    Stale,
+    /// Peer was made an explicit peer.
+    Explicit,
+    /// Peer did not keep up with its outbound queue.
+    SlowPeer,
 }
 
 /// Kinds of reasons a peer's score has been penalized
@@ -567,6 +742,8 @@ pub(crate) enum Penalty {
     MessageDeficit,
     /// Too many peers under one IP address.
     IPColocation,
+    /// A peer exceeded one of its control message budgets.
+    BudgetExceeded,
 }
 
 /// Label for the mesh inclusion event metrics.
@@ -595,6 +772,12 @@ struct PenaltyLabel {
     penalty: Penalty,
 }
 
+/// Label for the slow peer action metrics.
+#[derive(PartialEq, Eq, Hash, EncodeLabelSet, Clone, Debug)]
+struct SlowPeerLabel {
+    action: SlowPeerPolicy,
+}
+
 #[derive(Clone)]
 struct HistBuilder {
     buckets: Vec<f64>,
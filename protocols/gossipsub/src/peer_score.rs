@@ -26,16 +26,21 @@ use crate::time_cache::TimeCache;
 use crate::{MessageId, TopicHash};
 use libp2p_identity::PeerId;
 use std::collections::{hash_map, HashMap, HashSet};
+use std::iter;
 use std::net::IpAddr;
 use std::time::Duration;
 use web_time::Instant;
 
+mod breakdown;
 mod params;
+mod snapshot;
 use crate::ValidationError;
+pub use breakdown::{PeerScoreBreakdown, TopicScoreBreakdown};
 pub use params::{
     score_parameter_decay, score_parameter_decay_with_base, PeerScoreParams, PeerScoreThresholds,
     TopicScoreParams,
 };
+pub use snapshot::{PeerScoreSnapshot, PeerScoreState, TopicScoreState};
 
 #[cfg(test)]
 mod tests;
@@ -168,6 +173,8 @@ struct DeliveryRecord {
     status: DeliveryStatus,
     first_seen: Instant,
     peers: HashSet<PeerId>,
+    /// The peers penalized for the message because of reports of mesh peers.
+    reported: HashSet<PeerId>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -188,6 +195,7 @@ impl Default for DeliveryRecord {
             status: DeliveryStatus::Unknown,
             first_seen: Instant::now(),
             peers: HashSet::new(),
+            reported: HashSet::new(),
         }
     }
 }
@@ -219,7 +227,23 @@ impl PeerScore {
 
     /// Returns the score for a peer, logging metrics. This is called from the heartbeat and
     /// increments the metric counts for penalties.
-    pub(crate) fn metric_score(&self, peer_id: &PeerId, mut metrics: Option<&mut Metrics>) -> f64 {
+    pub(crate) fn metric_score(&self, peer_id: &PeerId, metrics: Option<&mut Metrics>) -> f64 {
+        self.compute_score(peer_id, metrics, None)
+    }
+
+    /// Returns the score for a peer together with the contribution of each of its components.
+    pub(crate) fn score_breakdown(&self, peer_id: &PeerId) -> PeerScoreBreakdown {
+        let mut breakdown = PeerScoreBreakdown::default();
+        breakdown.score = self.compute_score(peer_id, None, Some(&mut breakdown));
+        breakdown
+    }
+
+    fn compute_score(
+        &self,
+        peer_id: &PeerId,
+        mut metrics: Option<&mut Metrics>,
+        mut breakdown: Option<&mut PeerScoreBreakdown>,
+    ) -> f64 {
         let Some(peer_stats) = self.peer_stats.get(peer_id) else {
             return 0.0;
         };
@@ -233,6 +257,7 @@ impl PeerScore {
 
                 // the topic score
                 let mut topic_score = 0.0;
+                let mut topic_breakdown = TopicScoreBreakdown::default();
 
                 // P1: time in mesh
                 if let MeshStatus::Active { mesh_time, .. } = topic_stats.mesh_status {
@@ -245,7 +270,8 @@ impl PeerScore {
                             topic_params.time_in_mesh_cap
                         }
                     };
-                    topic_score += p1 * topic_params.time_in_mesh_weight;
+                    topic_breakdown.time_in_mesh = p1 * topic_params.time_in_mesh_weight;
+                    topic_score += topic_breakdown.time_in_mesh;
                 }
 
                 // P2: first message deliveries
@@ -257,7 +283,9 @@ impl PeerScore {
                         topic_params.first_message_deliveries_cap
                     }
                 };
-                topic_score += p2 * topic_params.first_message_deliveries_weight;
+                topic_breakdown.first_message_deliveries =
+                    p2 * topic_params.first_message_deliveries_weight;
+                topic_score += topic_breakdown.first_message_deliveries;
 
                 // P3: mesh message deliveries
                 if topic_stats.mesh_message_deliveries_active
@@ -267,7 +295,9 @@ impl PeerScore {
                     let deficit = topic_params.mesh_message_deliveries_threshold
                         - topic_stats.mesh_message_deliveries;
                     let p3 = deficit * deficit;
-                    topic_score += p3 * topic_params.mesh_message_deliveries_weight;
+                    topic_breakdown.mesh_message_deliveries =
+                        p3 * topic_params.mesh_message_deliveries_weight;
+                    topic_score += topic_breakdown.mesh_message_deliveries;
                     if let Some(metrics) = metrics.as_mut() {
                         metrics.register_score_penalty(Penalty::MessageDeficit);
                     }
@@ -283,13 +313,22 @@ impl PeerScore {
                 // P3b:
                 // NOTE: the weight of P3b is negative (validated in TopicScoreParams.validate), so this detracts.
                 let p3b = topic_stats.mesh_failure_penalty;
-                topic_score += p3b * topic_params.mesh_failure_penalty_weight;
+                topic_breakdown.mesh_failure_penalty =
+                    p3b * topic_params.mesh_failure_penalty_weight;
+                topic_score += topic_breakdown.mesh_failure_penalty;
 
                 // P4: invalid messages
                 // NOTE: the weight of P4 is negative (validated in TopicScoreParams.validate), so this detracts.
                 let p4 =
                     topic_stats.invalid_message_deliveries * topic_stats.invalid_message_deliveries;
-                topic_score += p4 * topic_params.invalid_message_deliveries_weight;
+                topic_breakdown.invalid_message_deliveries =
+                    p4 * topic_params.invalid_message_deliveries_weight;
+                topic_score += topic_breakdown.invalid_message_deliveries;
+
+                if let Some(breakdown) = breakdown.as_mut() {
+                    topic_breakdown.score = topic_score;
+                    breakdown.topics.insert(topic.clone(), topic_breakdown);
+                }
 
                 // update score, mixing with topic weight
                 score += topic_score * topic_params.topic_weight;
@@ -300,10 +339,16 @@ impl PeerScore {
         if self.params.topic_score_cap > 0f64 && score > self.params.topic_score_cap {
             score = self.params.topic_score_cap;
         }
+        if let Some(breakdown) = breakdown.as_mut() {
+            breakdown.topic_score = score;
+        }
 
         // P5: application-specific score
         let p5 = peer_stats.application_score;
         score += p5 * self.params.app_specific_weight;
+        if let Some(breakdown) = breakdown.as_mut() {
+            breakdown.application_score = p5 * self.params.app_specific_weight;
+        }
 
         // P6: IP collocation factor
         for ip in peer_stats.known_ips.iter() {
@@ -329,6 +374,9 @@ impl PeerScore {
                         "[Penalty] The peer gets penalized because of too many peers with the same ip"
                     );
                     score += p6 * self.params.ip_colocation_factor_weight;
+                    if let Some(breakdown) = breakdown.as_mut() {
+                        breakdown.ip_colocation += p6 * self.params.ip_colocation_factor_weight;
+                    }
                 }
             }
         }
@@ -338,6 +386,9 @@ impl PeerScore {
             let excess = peer_stats.behaviour_penalty - self.params.behaviour_penalty_threshold;
             let p7 = excess * excess;
             score += p7 * self.params.behaviour_penalty_weight;
+            if let Some(breakdown) = breakdown {
+                breakdown.behaviour_penalty = p7 * self.params.behaviour_penalty_weight;
+            }
         }
         score
     }
@@ -643,7 +694,7 @@ impl PeerScore {
             _ => {} // the rest are handled after record creation
         }
 
-        let peers: Vec<_> = {
+        let (peers, reported): (Vec<_>, _) = {
             let record = self.deliveries.entry(msg_id.clone()).or_default();
 
             // Multiple peers can now reject the same message as we track which peers send us the
@@ -663,15 +714,38 @@ impl PeerScore {
             // mark the message as invalid and penalize peers that have already forwarded it.
             record.status = DeliveryStatus::Invalid;
             // release the delivery time tracking map to free some memory early
-            record.peers.drain().collect()
+            (
+                record.peers.drain().collect(),
+                std::mem::take(&mut record.reported),
+            )
         };
 
-        self.mark_invalid_message_delivery(from, topic_hash);
-        for peer_id in peers.iter() {
+        // peers reported by mesh peers have already been penalized for the message
+        for peer_id in iter::once(from)
+            .chain(peers.iter())
+            .filter(|peer_id| !reported.contains(*peer_id))
+        {
             self.mark_invalid_message_delivery(peer_id, topic_hash)
         }
     }
 
+    /// Penalizes a peer reported by a mesh peer to have delivered an invalid message, unless the
+    /// message has already been validated or the peer was already penalized for it. Returns
+    /// whether the peer got penalized.
+    pub(crate) fn reported_invalid_message(
+        &mut self,
+        from: &PeerId,
+        msg_id: &MessageId,
+        topic_hash: &TopicHash,
+    ) -> bool {
+        let record = self.deliveries.entry(msg_id.clone()).or_default();
+        if record.status != DeliveryStatus::Unknown || !record.reported.insert(*from) {
+            return false;
+        }
+        self.mark_invalid_message_delivery(from, topic_hash);
+        true
+    }
+
     pub(crate) fn duplicated_message(
         &mut self,
         from: &PeerId,
@@ -910,3 +984,25 @@ pub(crate) enum RejectReason {
     /// The validation failed.
     ValidationFailed,
 }
+
+impl RejectReason {
+    /// The name of the reason in the traces of go-libp2p-pubsub.
+    pub(crate) fn trace_name(&self) -> &'static str {
+        match self {
+            RejectReason::ValidationError(ValidationError::InvalidSignature) => "invalid signature",
+            RejectReason::ValidationError(ValidationError::SignaturePresent) => {
+                "unexpected signature"
+            }
+            RejectReason::ValidationError(
+                ValidationError::SequenceNumberPresent | ValidationError::MessageSourcePresent,
+            ) => "unexpected auth info",
+            RejectReason::ValidationError(_) | RejectReason::ValidationFailed => {
+                "validation failed"
+            }
+            RejectReason::SelfOrigin => "self originated message",
+            RejectReason::BlackListedPeer => "blacklisted peer",
+            RejectReason::BlackListedSource => "blacklisted source",
+            RejectReason::ValidationIgnored => "validation ignored",
+        }
+    }
+}
@@ -0,0 +1,60 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Breakdown of the score of a peer into its components, e.g. to debug why a peer is pruned.
+
+use crate::TopicHash;
+use std::collections::HashMap;
+
+/// The score of a peer together with the weighted contribution of each of its components.
+///
+/// See [`Behaviour::peer_score_breakdown`](crate::Behaviour::peer_score_breakdown).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerScoreBreakdown {
+    /// The overall score of the peer, i.e. the sum of all components.
+    pub score: f64,
+    /// The score of each scored topic, before applying the topic weight and the topic score cap.
+    pub topics: HashMap<TopicHash, TopicScoreBreakdown>,
+    /// The weighted sum of the topic scores, after applying the topic score cap.
+    pub topic_score: f64,
+    /// P5: the weighted application specific score.
+    pub application_score: f64,
+    /// P6: the weighted IP colocation penalty.
+    pub ip_colocation: f64,
+    /// P7: the weighted behaviour penalty.
+    pub behaviour_penalty: f64,
+}
+
+/// The weighted components of the score of a peer in a single topic.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopicScoreBreakdown {
+    /// The score in the topic, i.e. the sum of all components.
+    pub score: f64,
+    /// P1: the time in the mesh.
+    pub time_in_mesh: f64,
+    /// P2: the first message deliveries.
+    pub first_message_deliveries: f64,
+    /// P3: the mesh message delivery deficit.
+    pub mesh_message_deliveries: f64,
+    /// P3b: the mesh failure penalty.
+    pub mesh_failure_penalty: f64,
+    /// P4: the invalid message deliveries.
+    pub invalid_message_deliveries: f64,
+}
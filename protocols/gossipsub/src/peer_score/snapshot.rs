@@ -0,0 +1,169 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Export and import of the peer score state, e.g. to retain scores across restarts.
+
+use super::{ConnectionStatus, PeerScore, PeerStats, TopicStats};
+use crate::TopicHash;
+use libp2p_identity::PeerId;
+use std::collections::{hash_map, HashMap};
+use web_time::{Instant, SystemTime};
+
+/// A snapshot of the score state of peers.
+///
+/// See [`Behaviour::export_peer_scores`](crate::Behaviour::export_peer_scores) and
+/// [`Behaviour::import_peer_scores`](crate::Behaviour::import_peer_scores).
+#[derive(Debug, Clone)]
+pub struct PeerScoreSnapshot {
+    /// The time the snapshot was taken, used to decay the counters on import.
+    pub taken_at: SystemTime,
+    /// The score state of each peer.
+    pub peers: HashMap<PeerId, PeerScoreState>,
+}
+
+/// The score state of a single peer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerScoreState {
+    /// The P7 behaviour penalty counter.
+    pub behaviour_penalty: f64,
+    /// The application specific score.
+    pub application_score: f64,
+    /// The counters of each scored topic.
+    pub topics: HashMap<TopicHash, TopicScoreState>,
+}
+
+/// The score counters of a peer in a single topic.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopicScoreState {
+    /// The P2 first message deliveries counter.
+    pub first_message_deliveries: f64,
+    /// The P3 mesh message deliveries counter.
+    pub mesh_message_deliveries: f64,
+    /// The P3b mesh failure penalty counter.
+    pub mesh_failure_penalty: f64,
+    /// The P4 invalid message deliveries counter.
+    pub invalid_message_deliveries: f64,
+}
+
+impl PeerScore {
+    /// Exports the score state of all known peers.
+    pub(crate) fn export(&self) -> PeerScoreSnapshot {
+        let peers = self
+            .peer_stats
+            .iter()
+            .map(|(peer_id, stats)| {
+                let topics = stats
+                    .topics
+                    .iter()
+                    .map(|(topic, stats)| {
+                        let state = TopicScoreState {
+                            first_message_deliveries: stats.first_message_deliveries,
+                            mesh_message_deliveries: stats.mesh_message_deliveries,
+                            mesh_failure_penalty: stats.mesh_failure_penalty,
+                            invalid_message_deliveries: stats.invalid_message_deliveries,
+                        };
+                        (topic.clone(), state)
+                    })
+                    .collect();
+                let state = PeerScoreState {
+                    behaviour_penalty: stats.behaviour_penalty,
+                    application_score: stats.application_score,
+                    topics,
+                };
+                (*peer_id, state)
+            })
+            .collect();
+
+        PeerScoreSnapshot {
+            taken_at: SystemTime::now(),
+            peers,
+        }
+    }
+
+    /// Imports the score state of the peers in the snapshot as disconnected peers.
+    ///
+    /// The counters are decayed for every decay interval elapsed since the snapshot was taken.
+    /// Peers that are already known are left untouched. If the score retention period
+    /// elapsed since the snapshot was taken, nothing is imported.
+    pub(crate) fn import(&mut self, snapshot: PeerScoreSnapshot) {
+        let elapsed = SystemTime::now()
+            .duration_since(snapshot.taken_at)
+            .unwrap_or_default();
+        let Some(remaining) = self.params.retain_score.checked_sub(elapsed) else {
+            return;
+        };
+        let expire = Instant::now() + remaining;
+        let decays = (elapsed.as_secs_f64() / self.params.decay_interval.as_secs_f64()) as i32;
+        let decay_to_zero = self.params.decay_to_zero;
+        let decay = |value: f64, factor: f64| {
+            let value = value * factor.powi(decays);
+            if value < decay_to_zero {
+                0.0
+            } else {
+                value
+            }
+        };
+
+        for (peer_id, state) in snapshot.peers {
+            let hash_map::Entry::Vacant(entry) = self.peer_stats.entry(peer_id) else {
+                continue;
+            };
+
+            let mut topics = HashMap::new();
+            for (topic, state) in state.topics {
+                // Counters of topics that are no longer scored are not retained.
+                let Some(params) = self.params.topics.get(&topic) else {
+                    continue;
+                };
+                let stats = TopicStats {
+                    first_message_deliveries: decay(
+                        state.first_message_deliveries,
+                        params.first_message_deliveries_decay,
+                    ),
+                    mesh_message_deliveries: decay(
+                        state.mesh_message_deliveries,
+                        params.mesh_message_deliveries_decay,
+                    ),
+                    mesh_failure_penalty: decay(
+                        state.mesh_failure_penalty,
+                        params.mesh_failure_penalty_decay,
+                    ),
+                    invalid_message_deliveries: decay(
+                        state.invalid_message_deliveries,
+                        params.invalid_message_deliveries_decay,
+                    ),
+                    ..TopicStats::default()
+                };
+                topics.insert(topic, stats);
+            }
+
+            entry.insert(PeerStats {
+                status: ConnectionStatus::Disconnected { expire },
+                topics,
+                behaviour_penalty: decay(
+                    state.behaviour_penalty,
+                    self.params.behaviour_penalty_decay,
+                ),
+                application_score: state.application_score,
+                ..PeerStats::default()
+            });
+        }
+    }
+}
@@ -42,6 +42,8 @@ fn make_test_message(seq: u64) -> (MessageId, RawMessage) {
         signature: None,
         key: None,
         validated: true,
+        expires_at: None,
+        chunk: None,
     };
 
     let message = Message {
@@ -976,3 +978,36 @@ fn test_score_retention() {
         "Score should be the application specific score"
     );
 }
+
+#[test]
+fn test_score_snapshot_import_decays_for_downtime() {
+    let params = PeerScoreParams::default();
+    let decay_interval = params.decay_interval;
+    let retain_score = params.retain_score;
+    let mut peer_score = PeerScore::new(params.clone());
+    let peer_id = PeerId::random();
+    peer_score.add_peer(peer_id);
+    peer_score.add_penalty(&peer_id, 5);
+    assert_eq!(peer_score.score(&peer_id), -250.0);
+
+    // Simulate a restart that took one decay interval.
+    let mut snapshot = peer_score.export();
+    snapshot.taken_at -= decay_interval;
+
+    let mut restarted = PeerScore::new(params.clone());
+    restarted.import(snapshot.clone());
+    // The penalty of 5 decayed to 1 and is squared and weighted.
+    assert!(within_variance(restarted.score(&peer_id), -10.0, 0.0001));
+
+    // Known peers are left untouched.
+    let mut known = PeerScore::new(params.clone());
+    known.add_peer(peer_id);
+    known.import(snapshot.clone());
+    assert_eq!(known.score(&peer_id), 0.0);
+
+    // Snapshots older than the retention period are ignored.
+    snapshot.taken_at -= retain_score;
+    let mut expired = PeerScore::new(params);
+    expired.import(snapshot);
+    assert_eq!(expired.score(&peer_id), 0.0);
+}
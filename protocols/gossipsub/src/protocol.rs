@@ -18,6 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::chunking::Chunk;
 use crate::config::ValidationMode;
 use crate::handler::HandlerEvent;
 use crate::rpc_proto::proto;
@@ -34,11 +35,19 @@ use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_identity::{PeerId, PublicKey};
 use libp2p_swarm::StreamProtocol;
 use quick_protobuf::Writer;
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
 use void::Void;
 
 pub(crate) const SIGNING_PREFIX: &[u8] = b"libp2p-pubsub:";
 
+pub(crate) const INVALID_REPORT_SIGNING_PREFIX: &[u8] = b"libp2p-pubsub-invalid:";
+
+pub(crate) const GOSSIPSUB_1_2_0_PROTOCOL: ProtocolId = ProtocolId {
+    protocol: StreamProtocol::new("/meshsub/1.2.0"),
+    kind: PeerKind::Gossipsubv1_2,
+};
 pub(crate) const GOSSIPSUB_1_1_0_PROTOCOL: ProtocolId = ProtocolId {
     protocol: StreamProtocol::new("/meshsub/1.1.0"),
     kind: PeerKind::Gossipsubv1_1,
@@ -61,6 +70,8 @@ pub struct ProtocolConfig {
     pub(crate) max_transmit_size: usize,
     /// Determines the level of validation to be done on incoming messages.
     pub(crate) validation_mode: ValidationMode,
+    /// Overrides the level of validation for the incoming messages of specific topics.
+    pub(crate) topic_validation_modes: Arc<HashMap<TopicHash, ValidationMode>>,
 }
 
 impl Default for ProtocolConfig {
@@ -68,7 +79,12 @@ impl Default for ProtocolConfig {
         Self {
             max_transmit_size: 65536,
             validation_mode: ValidationMode::Strict,
-            protocol_ids: vec![GOSSIPSUB_1_1_0_PROTOCOL, GOSSIPSUB_1_0_0_PROTOCOL],
+            topic_validation_modes: Arc::default(),
+            protocol_ids: vec![
+                GOSSIPSUB_1_2_0_PROTOCOL,
+                GOSSIPSUB_1_1_0_PROTOCOL,
+                GOSSIPSUB_1_0_0_PROTOCOL,
+            ],
         }
     }
 }
@@ -109,7 +125,8 @@ where
         Box::pin(future::ok((
             Framed::new(
                 socket,
-                GossipsubCodec::new(self.max_transmit_size, self.validation_mode),
+                GossipsubCodec::new(self.max_transmit_size, self.validation_mode)
+                    .with_topic_validation_modes(self.topic_validation_modes),
             ),
             protocol_id.kind,
         )))
@@ -128,7 +145,8 @@ where
         Box::pin(future::ok((
             Framed::new(
                 socket,
-                GossipsubCodec::new(self.max_transmit_size, self.validation_mode),
+                GossipsubCodec::new(self.max_transmit_size, self.validation_mode)
+                    .with_topic_validation_modes(self.topic_validation_modes),
             ),
             protocol_id.kind,
         )))
@@ -140,6 +158,8 @@ where
 pub struct GossipsubCodec {
     /// Determines the level of validation performed on incoming messages.
     validation_mode: ValidationMode,
+    /// Overrides the level of validation for the incoming messages of specific topics.
+    topic_validation_modes: Arc<HashMap<TopicHash, ValidationMode>>,
     /// The codec to handle common encoding/decoding of protobuf messages
     codec: quick_protobuf_codec::Codec<proto::RPC>,
 }
@@ -149,10 +169,20 @@ impl GossipsubCodec {
         let codec = quick_protobuf_codec::Codec::new(max_length);
         GossipsubCodec {
             validation_mode,
+            topic_validation_modes: Arc::default(),
             codec,
         }
     }
 
+    /// Overrides the level of validation for the incoming messages of specific topics.
+    pub(crate) fn with_topic_validation_modes(
+        mut self,
+        topic_validation_modes: Arc<HashMap<TopicHash, ValidationMode>>,
+    ) -> Self {
+        self.topic_validation_modes = topic_validation_modes;
+        self
+    }
+
     /// Verifies a gossipsub message. This returns either a success or failure. All errors
     /// are logged, which prevents error handling in the codec and handler. We simply drop invalid
     /// messages and log warnings, rather than propagating errors through the codec.
@@ -174,31 +204,15 @@ impl GossipsubCodec {
             return false;
         };
 
-        // If there is a key value in the protobuf, use that key otherwise the key must be
-        // obtained from the inlined source peer_id.
-        let public_key = match message.key.as_deref().map(PublicKey::try_decode_protobuf) {
-            Some(Ok(key)) => key,
-            _ => match PublicKey::try_decode_protobuf(&source.to_bytes()[2..]) {
-                Ok(v) => v,
-                Err(_) => {
-                    tracing::warn!("Signature verification failed: No valid public key supplied");
-                    return false;
-                }
-            },
-        };
-
-        // The key must match the peer_id
-        if source != public_key.to_peer_id() {
-            tracing::warn!(
-                "Signature verification failed: Public key doesn't match source peer id"
-            );
+        let Some(public_key) = signing_key(&source, message.key.as_deref()) else {
             return false;
-        }
+        };
 
         // Construct the signature bytes
         let mut message_sig = message.clone();
         message_sig.signature = None;
         message_sig.key = None;
+        message_sig.expires_at = None;
         let mut buf = Vec::with_capacity(message_sig.get_size());
         let mut writer = Writer::new(&mut buf);
         message_sig
@@ -210,6 +224,77 @@ impl GossipsubCodec {
     }
 }
 
+/// Returns the public key of the signer of a message or report, checking that it matches the
+/// peer id of the signer.
+fn signing_key(signer: &PeerId, key: Option<&[u8]>) -> Option<PublicKey> {
+    // If there is a key value in the protobuf, use that key otherwise the key must be
+    // obtained from the inlined source peer_id.
+    let public_key = match key.map(PublicKey::try_decode_protobuf) {
+        Some(Ok(key)) => key,
+        _ => match PublicKey::try_decode_protobuf(&signer.to_bytes()[2..]) {
+            Ok(v) => v,
+            Err(_) => {
+                tracing::warn!("Signature verification failed: No valid public key supplied");
+                return None;
+            }
+        },
+    };
+
+    // The key must match the peer_id
+    if *signer != public_key.to_peer_id() {
+        tracing::warn!("Signature verification failed: Public key doesn't match source peer id");
+        return None;
+    }
+    Some(public_key)
+}
+
+/// Returns the bytes signed by the reporter of an invalid message, i.e. the encoded report
+/// without signature and key.
+pub(crate) fn invalid_report_signature_bytes(
+    message_id: &MessageId,
+    topic_hash: &TopicHash,
+    propagation_source: &PeerId,
+) -> Vec<u8> {
+    use quick_protobuf::MessageWrite;
+
+    let report = proto::ControlInvalid {
+        message_id: Some(message_id.0.clone()),
+        topic_id: Some(topic_hash.as_str().to_owned()),
+        propagation_source: Some(propagation_source.to_bytes()),
+        signature: None,
+        key: None,
+    };
+    let mut buf = Vec::with_capacity(report.get_size());
+    let mut writer = Writer::new(&mut buf);
+    report
+        .write_message(&mut writer)
+        .expect("Encoding to succeed");
+    let mut signature_bytes = INVALID_REPORT_SIGNING_PREFIX.to_vec();
+    signature_bytes.extend_from_slice(&buf);
+    signature_bytes
+}
+
+/// Verifies the signature of a [`ControlAction::Invalid`] report by the given reporter.
+pub(crate) fn verify_invalid_report(reporter: &PeerId, report: &ControlAction) -> bool {
+    let ControlAction::Invalid {
+        message_id,
+        topic_hash,
+        propagation_source,
+        signature,
+        key,
+    } = report
+    else {
+        return false;
+    };
+    let Some(public_key) = signing_key(reporter, key.as_deref()) else {
+        return false;
+    };
+    public_key.verify(
+        &invalid_report_signature_bytes(message_id, topic_hash, propagation_source),
+        signature,
+    )
+}
+
 impl Encoder for GossipsubCodec {
     type Item<'a> = proto::RPC;
     type Error = quick_protobuf_codec::Error;
@@ -239,7 +324,14 @@ impl Decoder for GossipsubCodec {
             let mut verify_sequence_no = false;
             let mut verify_source = false;
 
-            match self.validation_mode {
+            let validation_mode = if self.topic_validation_modes.is_empty() {
+                &self.validation_mode
+            } else {
+                self.topic_validation_modes
+                    .get(&TopicHash::from_raw(message.topic.clone()))
+                    .unwrap_or(&self.validation_mode)
+            };
+            match validation_mode {
                 ValidationMode::Strict => {
                     // Validate everything
                     verify_signature = true;
@@ -277,6 +369,17 @@ impl Decoder for GossipsubCodec {
                 ValidationMode::None => {}
             }
 
+            // A chunk must lie within a chunked message of its source.
+            if invalid_kind.is_none()
+                && message
+                    .chunk
+                    .clone()
+                    .is_some_and(|chunk| !Chunk::from(chunk).is_valid(message.from.as_deref()))
+            {
+                tracing::debug!("Message dropped. Invalid chunk position");
+                invalid_kind = Some(ValidationError::InvalidChunk);
+            }
+
             // If the initial validation logic failed, add the message to invalid messages and
             // continue processing the others.
             if let Some(validation_error) = invalid_kind.take() {
@@ -288,6 +391,8 @@ impl Decoder for GossipsubCodec {
                     signature: None, // don't inform the application
                     key: message.key,
                     validated: false,
+                    expires_at: None,
+                    chunk: None,
                 };
                 invalid_messages.push((message, validation_error));
                 // proceed to the next message
@@ -308,6 +413,8 @@ impl Decoder for GossipsubCodec {
                     signature: None, // don't inform the application
                     key: message.key,
                     validated: false,
+                    expires_at: None,
+                    chunk: None,
                 };
                 invalid_messages.push((message, ValidationError::InvalidSignature));
                 // proceed to the next message
@@ -333,6 +440,8 @@ impl Decoder for GossipsubCodec {
                             signature: message.signature, // don't inform the application
                             key: message.key,
                             validated: false,
+                            expires_at: None,
+                            chunk: None,
                         };
                         invalid_messages.push((message, ValidationError::InvalidSequenceNumber));
                         // proceed to the next message
@@ -352,6 +461,8 @@ impl Decoder for GossipsubCodec {
                         signature: message.signature, // don't inform the application
                         key: message.key,
                         validated: false,
+                        expires_at: None,
+                        chunk: None,
                     };
                     invalid_messages.push((message, ValidationError::EmptySequenceNumber));
                     continue;
@@ -378,6 +489,8 @@ impl Decoder for GossipsubCodec {
                                     signature: message.signature, // don't inform the application
                                     key: message.key,
                                     validated: false,
+                                    expires_at: None,
+                                    chunk: None,
                                 };
                                 invalid_messages.push((message, ValidationError::InvalidPeerId));
                                 continue;
@@ -402,6 +515,8 @@ impl Decoder for GossipsubCodec {
                 signature: message.signature,
                 key: message.key,
                 validated: false,
+                expires_at: message.expires_at,
+                chunk: message.chunk.map(Into::into),
             });
         }
 
@@ -453,11 +568,10 @@ impl Decoder for GossipsubCodec {
                         info.peer_id
                             .as_ref()
                             .and_then(|id| PeerId::from_bytes(id).ok())
-                            .map(|peer_id|
-                                    //TODO signedPeerRecord, see https://github.com/libp2p/specs/pull/217
-                                    PeerInfo {
-                                        peer_id: Some(peer_id),
-                                    })
+                            .map(|peer_id| PeerInfo {
+                                peer_id: Some(peer_id),
+                                signed_peer_record: info.signed_peer_record,
+                            })
                     })
                     .collect::<Vec<PeerInfo>>();
 
@@ -469,10 +583,52 @@ impl Decoder for GossipsubCodec {
                 });
             }
 
+            let idontwant_msgs: Vec<ControlAction> = rpc_control
+                .idontwant
+                .into_iter()
+                .map(|idontwant| ControlAction::IDontWant {
+                    message_ids: idontwant
+                        .message_ids
+                        .into_iter()
+                        .map(MessageId::from)
+                        .collect::<Vec<_>>(),
+                })
+                .collect();
+
+            let choke_msgs = rpc_control
+                .choke
+                .into_iter()
+                .map(|choke| ControlAction::Choke {
+                    topic_hash: TopicHash::from_raw(choke.topic_id.unwrap_or_default()),
+                });
+
+            let unchoke_msgs =
+                rpc_control
+                    .unchoke
+                    .into_iter()
+                    .map(|unchoke| ControlAction::Unchoke {
+                        topic_hash: TopicHash::from_raw(unchoke.topic_id.unwrap_or_default()),
+                    });
+
             control_msgs.extend(ihave_msgs);
             control_msgs.extend(iwant_msgs);
             control_msgs.extend(graft_msgs);
             control_msgs.extend(prune_msgs);
+            control_msgs.extend(idontwant_msgs);
+            // Filter out reports with invalid peer ids or without signature.
+            let invalid_msgs = rpc_control.invalid.into_iter().filter_map(|invalid| {
+                Some(ControlAction::Invalid {
+                    message_id: MessageId::from(invalid.message_id?),
+                    topic_hash: TopicHash::from_raw(invalid.topic_id.unwrap_or_default()),
+                    propagation_source: PeerId::from_bytes(&invalid.propagation_source?).ok()?,
+                    signature: invalid.signature?,
+                    key: invalid.key,
+                })
+            });
+
+            control_msgs.extend(choke_msgs);
+            control_msgs.extend(unchoke_msgs);
+            control_msgs.extend(invalid_msgs);
         }
 
         Ok(Some(HandlerEvent::Message {
@@ -521,7 +677,7 @@ mod tests {
                 .map(|_| u8::arbitrary(g))
                 .collect::<Vec<_>>();
             let topic_id = TopicId::arbitrary(g).0;
-            Message(gs.build_raw_message(topic_id, data).unwrap())
+            Message(gs.build_raw_message(topic_id, data, None).unwrap())
         }
     }
 
@@ -585,6 +741,82 @@ mod tests {
         QuickCheck::new().quickcheck(prop as fn(_) -> _)
     }
 
+    #[test]
+    fn deadline_is_not_signed() {
+        let mut gs: Behaviour = Behaviour::new(
+            crate::MessageAuthenticity::Signed(Keypair::generate_ed25519()),
+            Config::default(),
+        )
+        .unwrap();
+        let mut message = gs
+            .build_raw_message(Topic::new("topic").hash(), vec![1, 2, 3], None)
+            .unwrap();
+        message.expires_at = Some(42);
+
+        // The deadline is kept, and nodes not supporting deadlines still verify the signature.
+        for expires_at in [Some(42), None] {
+            message.expires_at = expires_at;
+            let rpc = Rpc {
+                messages: vec![message.clone()],
+                subscriptions: vec![],
+                control_msgs: vec![],
+            };
+            let mut codec = GossipsubCodec::new(u32::MAX as usize, ValidationMode::Strict);
+            let mut buf = BytesMut::new();
+            codec.encode(rpc.into_protobuf(), &mut buf).unwrap();
+            match codec.decode(&mut buf).unwrap().unwrap() {
+                HandlerEvent::Message {
+                    rpc,
+                    invalid_messages,
+                } => {
+                    assert!(invalid_messages.is_empty());
+                    assert_eq!(rpc.messages[0].expires_at, expires_at);
+                }
+                _ => panic!("Must decode a message"),
+            }
+        }
+    }
+
+    #[test]
+    fn topic_validation_mode_overrides_validation_mode() {
+        let anonymous = Topic::new("anonymous").hash();
+        let message = |topic: &TopicHash| RawMessage {
+            source: None,
+            data: vec![1, 2, 3],
+            sequence_number: None,
+            topic: topic.clone(),
+            signature: None,
+            key: None,
+            validated: false,
+            expires_at: None,
+            chunk: None,
+        };
+        let rpc = Rpc {
+            messages: vec![message(&anonymous), message(&Topic::new("strict").hash())],
+            subscriptions: vec![],
+            control_msgs: vec![],
+        };
+
+        let mut codec = GossipsubCodec::new(u32::MAX as usize, ValidationMode::Strict)
+            .with_topic_validation_modes(Arc::new(HashMap::from([(
+                anonymous.clone(),
+                ValidationMode::Anonymous,
+            )])));
+        let mut buf = BytesMut::new();
+        codec.encode(rpc.into_protobuf(), &mut buf).unwrap();
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            HandlerEvent::Message {
+                rpc,
+                invalid_messages,
+            } => {
+                assert_eq!(rpc.messages, vec![message(&anonymous)]);
+                assert_eq!(invalid_messages.len(), 1);
+                assert_eq!(invalid_messages[0].0.topic, Topic::new("strict").hash());
+            }
+            _ => panic!("Must decode a message"),
+        }
+    }
+
     #[test]
     fn support_floodsub_with_custom_protocol() {
         let protocol_config = ConfigBuilder::default()
@@ -22,6 +22,7 @@ pub(crate) mod proto {
     #![allow(unreachable_pub)]
     include!("generated/mod.rs");
     pub use self::gossipsub::pb::{mod_RPC::SubOpts, *};
+    pub use self::trace::pb::{mod_TraceEvent, TraceEvent, TraceEventBatch};
 }
 
 #[cfg(test)]
@@ -44,6 +45,8 @@ mod test {
             topic: topic1.clone().into_string(),
             signature: Some(rand::thread_rng().gen::<[u8; 32]>().to_vec()),
             key: Some(rand::thread_rng().gen::<[u8; 32]>().to_vec()),
+            expires_at: None,
+            chunk: None,
         };
         let old_message1 = compat::pb::Message {
             from: Some(PeerId::random().to_bytes()),
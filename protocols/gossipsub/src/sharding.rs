@@ -0,0 +1,230 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Families of sharded topics, in which a key is subscribed to a few shards computed per epoch,
+//! e.g. the attestation subnets of a consensus client.
+
+use crate::{IdentTopic, TopicHash};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use web_time::SystemTime;
+
+/// Computes the shard of a key in an epoch, reduced modulo the number of shards.
+type ShardFn = Arc<dyn Fn(&[u8], u64) -> u64 + Send + Sync>;
+
+/// A family of topics `<prefix><shard>`, e.g. `beacon_attestation_0` to `beacon_attestation_63`,
+/// see [`Behaviour::subscribe_sharded`](crate::Behaviour::subscribe_sharded).
+///
+/// The shards a key is subscribed to are computed per epoch, and rotated automatically once an
+/// epoch ends. The shards of the next epoch are subscribed [`ShardedTopics::with_warm_up`] ahead
+/// of the rotation, so that their meshes are established by then.
+#[derive(Clone)]
+pub struct ShardedTopics {
+    prefix: String,
+    shard_count: u64,
+    shards_per_key: u64,
+    epoch_duration: Duration,
+    genesis: SystemTime,
+    warm_up: Duration,
+    shard_fn: ShardFn,
+}
+
+impl ShardedTopics {
+    /// Creates a family of `shard_count` topics named `<prefix><shard>`, rotated every
+    /// `epoch_duration`.
+    ///
+    /// # Panics
+    ///
+    /// If `shard_count` or `epoch_duration` is zero.
+    pub fn new(prefix: impl Into<String>, shard_count: u64, epoch_duration: Duration) -> Self {
+        assert!(shard_count > 0, "At least one shard is required");
+        assert!(!epoch_duration.is_zero(), "Epochs must not be empty");
+
+        Self {
+            prefix: prefix.into(),
+            shard_count,
+            shards_per_key: 1,
+            epoch_duration,
+            genesis: SystemTime::UNIX_EPOCH,
+            warm_up: Duration::ZERO,
+            shard_fn: Arc::new(default_shard),
+        }
+    }
+
+    /// Sets the start of the first epoch. The default is the unix epoch.
+    pub fn with_genesis(mut self, genesis: SystemTime) -> Self {
+        self.genesis = genesis;
+        self
+    }
+
+    /// Subscribes the shards of the next epoch the given duration ahead of the rotation. The
+    /// default is to subscribe them only once the epoch started.
+    pub fn with_warm_up(mut self, warm_up: Duration) -> Self {
+        self.warm_up = warm_up;
+        self
+    }
+
+    /// Subscribes each key to the given number of consecutive shards. The default is one.
+    pub fn with_shards_per_key(mut self, shards_per_key: u64) -> Self {
+        self.shards_per_key = shards_per_key.clamp(1, self.shard_count);
+        self
+    }
+
+    /// Computes the first shard of a key in an epoch with the given function instead of hashing
+    /// the key and the epoch with SHA-256. The result is reduced modulo the number of shards.
+    pub fn with_shard_fn<F>(mut self, shard_fn: F) -> Self
+    where
+        F: Fn(&[u8], u64) -> u64 + Send + Sync + 'static,
+    {
+        self.shard_fn = Arc::new(shard_fn);
+        self
+    }
+
+    /// The prefix of the topics, identifying the family.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The topic of the given shard.
+    pub fn topic(&self, shard: u64) -> IdentTopic {
+        IdentTopic::new(format!("{}{}", self.prefix, shard % self.shard_count))
+    }
+
+    /// The epoch at the given time, `0` before the genesis.
+    pub fn epoch_at(&self, time: SystemTime) -> u64 {
+        let elapsed = time.duration_since(self.genesis).unwrap_or_default();
+        (elapsed.as_nanos() / self.epoch_duration.as_nanos()) as u64
+    }
+
+    /// The shards of the given key in the given epoch.
+    pub fn shards(&self, key: &[u8], epoch: u64) -> Vec<u64> {
+        let first = (self.shard_fn)(key, epoch) % self.shard_count;
+        (0..self.shards_per_key)
+            .map(|i| (first + i) % self.shard_count)
+            .collect()
+    }
+
+    /// The topics of the given keys to be subscribed at the given time, including the topics of
+    /// the next epoch while warming up.
+    pub(crate) fn topics_at<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a Vec<u8>>,
+        time: SystemTime,
+    ) -> HashSet<TopicHash> {
+        let epoch = self.epoch_at(time);
+        let warming_up = self.epoch_at(time + self.warm_up) > epoch;
+
+        let mut topics = HashSet::new();
+        for key in keys {
+            topics.extend(
+                self.shards(key, epoch)
+                    .into_iter()
+                    .map(|s| self.topic(s).hash()),
+            );
+            if warming_up {
+                topics.extend(
+                    self.shards(key, epoch + 1)
+                        .into_iter()
+                        .map(|s| self.topic(s).hash()),
+                );
+            }
+        }
+        topics
+    }
+}
+
+impl fmt::Debug for ShardedTopics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedTopics")
+            .field("prefix", &self.prefix)
+            .field("shard_count", &self.shard_count)
+            .field("shards_per_key", &self.shards_per_key)
+            .field("epoch_duration", &self.epoch_duration)
+            .field("genesis", &self.genesis)
+            .field("warm_up", &self.warm_up)
+            .finish()
+    }
+}
+
+/// Hashes the key and the big-endian epoch with SHA-256.
+fn default_shard(key: &[u8], epoch: u64) -> u64 {
+    let digest = Sha256::new()
+        .chain_update(key)
+        .chain_update(epoch.to_be_bytes())
+        .finalize();
+    u64::from_be_bytes(digest[..8].try_into().expect("digest has 32 bytes"))
+}
+
+/// The keys subscribed to a family of sharded topics.
+#[derive(Debug)]
+pub(crate) struct ShardSubscriptions {
+    pub(crate) topics: ShardedTopics,
+    pub(crate) keys: HashSet<Vec<u8>>,
+    /// The topics subscribed on behalf of the keys, excluding those subscribed otherwise.
+    pub(crate) subscribed: HashSet<TopicHash>,
+}
+
+impl ShardSubscriptions {
+    pub(crate) fn new(topics: ShardedTopics) -> Self {
+        Self {
+            topics,
+            keys: HashSet::new(),
+            subscribed: HashSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shards_wrap_around() {
+        let topics = ShardedTopics::new("subnet_", 4, Duration::from_secs(10))
+            .with_shards_per_key(2)
+            .with_shard_fn(|key, epoch| key[0] as u64 + epoch);
+
+        assert_eq!(topics.shards(&[3], 0), vec![3, 0]);
+        assert_eq!(topics.shards(&[3], 1), vec![0, 1]);
+        assert_eq!(topics.topic(5).hash().as_str(), "subnet_1");
+    }
+
+    #[test]
+    fn next_epoch_is_subscribed_during_warm_up() {
+        let topics = ShardedTopics::new("subnet_", 4, Duration::from_secs(10))
+            .with_warm_up(Duration::from_secs(2))
+            .with_shard_fn(|key, epoch| key[0] as u64 + epoch);
+        let keys = [vec![0]];
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let hashes = |shards: &[u64]| {
+            shards
+                .iter()
+                .map(|s| topics.topic(*s).hash())
+                .collect::<HashSet<_>>()
+        };
+
+        assert_eq!(topics.topics_at(&keys, at(5)), hashes(&[0]));
+        assert_eq!(topics.topics_at(&keys, at(9)), hashes(&[0, 1]));
+        assert_eq!(topics.topics_at(&keys, at(10)), hashes(&[1]));
+    }
+}
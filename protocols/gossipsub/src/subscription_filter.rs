@@ -208,6 +208,92 @@ impl TopicSubscriptionFilter for RegexSubscriptionFilter {
     }
 }
 
+/// A rule of a [`DynamicSubscriptionFilter`], matching the hashes of topics.
+#[derive(Debug, Clone)]
+pub enum TopicRule {
+    /// Matches the topic with exactly this hash.
+    Exact(TopicHash),
+    /// Matches all topics whose hash starts with the prefix.
+    Prefix(String),
+    /// Matches all topics whose hash matches the regular expression.
+    Regex(regex::Regex),
+}
+
+impl TopicRule {
+    /// Returns true iff the rule matches the topic.
+    pub fn matches(&self, topic_hash: &TopicHash) -> bool {
+        match self {
+            TopicRule::Exact(hash) => hash == topic_hash,
+            TopicRule::Prefix(prefix) => topic_hash.as_str().starts_with(prefix.as_str()),
+            TopicRule::Regex(regex) => regex.is_match(topic_hash.as_str()),
+        }
+    }
+}
+
+impl PartialEq for TopicRule {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TopicRule::Exact(a), TopicRule::Exact(b)) => a == b,
+            (TopicRule::Prefix(a), TopicRule::Prefix(b)) => a == b,
+            (TopicRule::Regex(a), TopicRule::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+/// Allows subscriptions to the topics matching any of its rules.
+///
+/// Unlike the other filters, the rules can be changed at runtime via
+/// [`Behaviour::update_subscription_filter`](crate::Behaviour::update_subscription_filter),
+/// e.g. to open and close topics without restarting the node.
+#[derive(Debug, Default, Clone)]
+pub struct DynamicSubscriptionFilter {
+    rules: Vec<TopicRule>,
+}
+
+impl DynamicSubscriptionFilter {
+    /// Creates a filter allowing the topics matching any of the given rules.
+    pub fn new(rules: impl IntoIterator<Item = TopicRule>) -> Self {
+        let mut filter = Self::default();
+        for rule in rules {
+            filter.add_rule(rule);
+        }
+        filter
+    }
+
+    /// Adds a rule. Returns `false` if the filter already contains the rule.
+    pub fn add_rule(&mut self, rule: TopicRule) -> bool {
+        if self.rules.contains(&rule) {
+            return false;
+        }
+        self.rules.push(rule);
+        true
+    }
+
+    /// Removes a rule. Returns `false` if the filter did not contain the rule.
+    pub fn remove_rule(&mut self, rule: &TopicRule) -> bool {
+        let len = self.rules.len();
+        self.rules.retain(|r| r != rule);
+        self.rules.len() != len
+    }
+
+    /// Removes all rules, rejecting all subscriptions.
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    /// Returns the rules of the filter.
+    pub fn rules(&self) -> impl Iterator<Item = &TopicRule> {
+        self.rules.iter()
+    }
+}
+
+impl TopicSubscriptionFilter for DynamicSubscriptionFilter {
+    fn can_subscribe(&mut self, topic_hash: &TopicHash) -> bool {
+        self.rules.iter().any(|rule| rule.matches(topic_hash))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -432,4 +518,26 @@ mod test {
             .unwrap();
         assert_eq!(result, subscriptions[..2].iter().collect());
     }
+
+    #[test]
+    fn test_dynamic_subscription_filter() {
+        let mut filter = DynamicSubscriptionFilter::new([
+            TopicRule::Exact(TopicHash::from_raw("t1")),
+            TopicRule::Prefix("blocks/".into()),
+            TopicRule::Regex(regex::Regex::new("^tx-[0-9]+$").unwrap()),
+        ]);
+
+        assert!(filter.can_subscribe(&TopicHash::from_raw("t1")));
+        assert!(!filter.can_subscribe(&TopicHash::from_raw("t2")));
+        assert!(filter.can_subscribe(&TopicHash::from_raw("blocks/1")));
+        assert!(filter.can_subscribe(&TopicHash::from_raw("tx-42")));
+        assert!(!filter.can_subscribe(&TopicHash::from_raw("tx-a")));
+
+        assert!(!filter.add_rule(TopicRule::Prefix("blocks/".into())));
+        assert!(filter.remove_rule(&TopicRule::Prefix("blocks/".into())));
+        assert!(!filter.can_subscribe(&TopicHash::from_raw("blocks/1")));
+
+        assert!(filter.add_rule(TopicRule::Exact(TopicHash::from_raw("t2"))));
+        assert!(filter.can_subscribe(&TopicHash::from_raw("t2")));
+    }
 }
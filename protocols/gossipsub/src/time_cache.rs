@@ -155,9 +155,9 @@ where
     }
 }
 
-pub(crate) struct DuplicateCache<Key>(TimeCache<Key, ()>);
+pub(crate) struct TimeDuplicateCache<Key>(TimeCache<Key, ()>);
 
-impl<Key> DuplicateCache<Key>
+impl<Key> TimeDuplicateCache<Key>
 where
     Key: Eq + std::hash::Hash + Clone,
 {
@@ -189,7 +189,7 @@ mod test {
 
     #[test]
     fn cache_added_entries_exist() {
-        let mut cache = DuplicateCache::new(Duration::from_secs(10));
+        let mut cache = TimeDuplicateCache::new(Duration::from_secs(10));
 
         cache.insert("t");
         cache.insert("e");
@@ -201,7 +201,7 @@ mod test {
 
     #[test]
     fn cache_entries_expire() {
-        let mut cache = DuplicateCache::new(Duration::from_millis(100));
+        let mut cache = TimeDuplicateCache::new(Duration::from_millis(100));
 
         cache.insert("t");
         assert!(!cache.insert("t"));
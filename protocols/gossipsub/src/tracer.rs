@@ -0,0 +1,456 @@
+// Copyright 2024 Sigma Prime Pty Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Tracing of the router events in the protobuf format of
+//! [go-libp2p-pubsub-tracer](https://github.com/libp2p/go-libp2p-pubsub-tracer), so that the
+//! existing tooling analysing traces of go nodes can be used with rust nodes.
+
+use crate::rpc_proto::proto;
+use crate::{MessageId, TopicHash};
+use futures::channel::mpsc;
+use futures::{AsyncWrite, AsyncWriteExt, StreamExt};
+use libp2p_identity::PeerId;
+use libp2p_swarm::StreamProtocol;
+use miniz_oxide::deflate::core::{
+    compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+use quick_protobuf::Writer;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// The protocol of the streams over which a [`RemoteTraceWriter`] sends traces to a collector.
+pub const REMOTE_TRACER_PROTOCOL: StreamProtocol =
+    StreamProtocol::new("/libp2p/pubsub/tracer/1.0.0");
+
+/// The maximum number of events sent to a remote collector in a single batch.
+const MAX_BATCH_SIZE: usize = 512;
+
+/// The compression level of the gzip stream sent to a remote collector.
+const COMPRESSION_LEVEL: i32 = 6;
+
+/// An event of the router that is recorded by a [`TraceSink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// A message has been published by the local node.
+    PublishMessage {
+        message_id: MessageId,
+        topic: TopicHash,
+    },
+    /// A received message has been rejected.
+    RejectMessage {
+        /// The id of the message, unknown if the message could not be decoded.
+        message_id: Option<MessageId>,
+        received_from: PeerId,
+        topic: TopicHash,
+        /// The reason of the rejection, as named by go-libp2p-pubsub.
+        reason: String,
+    },
+    /// A message has been received again.
+    DuplicateMessage {
+        message_id: MessageId,
+        received_from: PeerId,
+        topic: TopicHash,
+    },
+    /// A received message has been delivered to the application.
+    DeliverMessage {
+        message_id: MessageId,
+        received_from: PeerId,
+        topic: TopicHash,
+    },
+    /// A peer has been added to the mesh of a topic.
+    Graft { peer_id: PeerId, topic: TopicHash },
+    /// A peer has been removed from the mesh of a topic.
+    Prune { peer_id: PeerId, topic: TopicHash },
+}
+
+impl TraceEvent {
+    /// Converts the event into its protobuf representation, as recorded by the local node at the
+    /// given time.
+    fn into_proto(self, local_peer_id: &PeerId, timestamp: SystemTime) -> proto::TraceEvent {
+        use proto::mod_TraceEvent::{self as pb, Type};
+
+        let timestamp = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| i64::try_from(d.as_nanos()).unwrap_or(i64::MAX));
+        let mut event = proto::TraceEvent {
+            peerID: Some(local_peer_id.to_bytes()),
+            timestamp: Some(timestamp),
+            ..Default::default()
+        };
+        match self {
+            TraceEvent::PublishMessage { message_id, topic } => {
+                event.type_pb = Some(Type::PUBLISH_MESSAGE);
+                event.publishMessage = Some(pb::PublishMessage {
+                    messageID: Some(message_id.0),
+                    topic: Some(topic.into_string()),
+                });
+            }
+            TraceEvent::RejectMessage {
+                message_id,
+                received_from,
+                topic,
+                reason,
+            } => {
+                event.type_pb = Some(Type::REJECT_MESSAGE);
+                event.rejectMessage = Some(pb::RejectMessage {
+                    messageID: message_id.map(|id| id.0),
+                    receivedFrom: Some(received_from.to_bytes()),
+                    reason: Some(reason),
+                    topic: Some(topic.into_string()),
+                });
+            }
+            TraceEvent::DuplicateMessage {
+                message_id,
+                received_from,
+                topic,
+            } => {
+                event.type_pb = Some(Type::DUPLICATE_MESSAGE);
+                event.duplicateMessage = Some(pb::DuplicateMessage {
+                    messageID: Some(message_id.0),
+                    receivedFrom: Some(received_from.to_bytes()),
+                    topic: Some(topic.into_string()),
+                });
+            }
+            TraceEvent::DeliverMessage {
+                message_id,
+                received_from,
+                topic,
+            } => {
+                event.type_pb = Some(Type::DELIVER_MESSAGE);
+                event.deliverMessage = Some(pb::DeliverMessage {
+                    messageID: Some(message_id.0),
+                    topic: Some(topic.into_string()),
+                    receivedFrom: Some(received_from.to_bytes()),
+                });
+            }
+            TraceEvent::Graft { peer_id, topic } => {
+                event.type_pb = Some(Type::GRAFT);
+                event.graft = Some(pb::Graft {
+                    peerID: Some(peer_id.to_bytes()),
+                    topic: Some(topic.into_string()),
+                });
+            }
+            TraceEvent::Prune { peer_id, topic } => {
+                event.type_pb = Some(Type::PRUNE);
+                event.prune = Some(pb::Prune {
+                    peerID: Some(peer_id.to_bytes()),
+                    topic: Some(topic.into_string()),
+                });
+            }
+        }
+        event
+    }
+}
+
+/// A sink recording the events of the router, set with
+/// [`Behaviour::set_tracer`](crate::Behaviour::set_tracer).
+pub trait TraceSink: Send + 'static {
+    /// Records an event of the router.
+    ///
+    /// Called synchronously by the router, hence must not block.
+    fn trace(&mut self, event: TraceEvent);
+}
+
+/// A [`TraceSink`] writing the events as varint length-delimited protobuf `TraceEvent`s, the
+/// format written by the `PBTracer` of go-libp2p-pubsub.
+pub struct FileTracer<W = BufWriter<File>> {
+    local_peer_id: PeerId,
+    writer: W,
+}
+
+impl FileTracer {
+    /// Creates a tracer writing to the file at the given path, truncating any existing file.
+    pub fn create(local_peer_id: PeerId, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(
+            local_peer_id,
+            BufWriter::new(File::create(path)?),
+        ))
+    }
+}
+
+impl<W: Write> FileTracer<W> {
+    /// Creates a tracer writing to the given writer.
+    pub fn new(local_peer_id: PeerId, writer: W) -> Self {
+        Self {
+            local_peer_id,
+            writer,
+        }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send + 'static> TraceSink for FileTracer<W> {
+    fn trace(&mut self, event: TraceEvent) {
+        let event = event.into_proto(&self.local_peer_id, SystemTime::now());
+        let mut buf = Vec::new();
+        Writer::new(&mut buf)
+            .write_message(&event)
+            .expect("Encoding to succeed");
+        if let Err(e) = self.writer.write_all(&buf) {
+            tracing::warn!("Failed to write trace event: {e}");
+        }
+    }
+}
+
+/// A [`TraceSink`] forwarding the events to a [`RemoteTraceWriter`], which sends them to a remote
+/// collector such as go-libp2p-pubsub-tracer.
+///
+/// Events are dropped if more than the configured number of events are waiting to be sent.
+pub struct RemoteTracer {
+    local_peer_id: PeerId,
+    sender: mpsc::Sender<proto::TraceEvent>,
+}
+
+impl RemoteTracer {
+    /// Creates a tracer buffering up to `buffer` events, and the writer sending them.
+    pub fn new(local_peer_id: PeerId, buffer: usize) -> (Self, RemoteTraceWriter) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        (
+            Self {
+                local_peer_id,
+                sender,
+            },
+            RemoteTraceWriter { receiver },
+        )
+    }
+}
+
+impl TraceSink for RemoteTracer {
+    fn trace(&mut self, event: TraceEvent) {
+        let event = event.into_proto(&self.local_peer_id, SystemTime::now());
+        if let Err(e) = self.sender.try_send(event) {
+            if e.is_full() {
+                tracing::debug!("Trace buffer full, dropping trace event");
+            }
+        }
+    }
+}
+
+/// Sends the events of a [`RemoteTracer`] to a remote collector.
+pub struct RemoteTraceWriter {
+    receiver: mpsc::Receiver<proto::TraceEvent>,
+}
+
+impl RemoteTraceWriter {
+    /// Sends the events over a stream of the [`REMOTE_TRACER_PROTOCOL`] to a collector, until the
+    /// [`RemoteTracer`] is dropped.
+    ///
+    /// The events are sent in batches of varint length-delimited protobuf `TraceEventBatch`es
+    /// over a gzip stream. If the stream fails, the events not sent yet remain buffered and this
+    /// can be called again with a new stream.
+    pub async fn run<S: AsyncWrite + Unpin>(&mut self, mut stream: S) -> io::Result<()> {
+        let mut encoder = GzipEncoder::new();
+        while let Some(event) = self.receiver.next().await {
+            let mut batch = proto::TraceEventBatch { batch: vec![event] };
+            while batch.batch.len() < MAX_BATCH_SIZE {
+                match self.receiver.try_next() {
+                    Ok(Some(event)) => batch.batch.push(event),
+                    _ => break,
+                }
+            }
+
+            let mut buf = Vec::new();
+            Writer::new(&mut buf)
+                .write_message(&batch)
+                .expect("Encoding to succeed");
+            stream.write_all(&encoder.write(&buf)).await?;
+            stream.flush().await?;
+        }
+        stream.write_all(&encoder.finish()).await?;
+        stream.close().await
+    }
+}
+
+/// A gzip encoder flushing the compressed data on every write, as done by the remote tracer of
+/// go-libp2p-pubsub.
+struct GzipEncoder {
+    compressor: CompressorOxide,
+    header_written: bool,
+    crc: u32,
+    size: u32,
+}
+
+impl GzipEncoder {
+    /// The gzip header without modification time, extra fields nor name, from an unknown OS.
+    const HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+    fn new() -> Self {
+        Self {
+            // Negative window bits select a raw deflate stream.
+            compressor: CompressorOxide::new(create_comp_flags_from_zip_params(
+                COMPRESSION_LEVEL,
+                -15,
+                0,
+            )),
+            header_written: false,
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    /// Compresses the data, returning all the compressed bytes so far.
+    fn write(&mut self, data: &[u8]) -> Vec<u8> {
+        self.crc = crc32(self.crc, data);
+        self.size = self.size.wrapping_add(data.len() as u32);
+        self.compress(data, TDEFLFlush::Sync)
+    }
+
+    /// Terminates the stream, returning the remaining compressed bytes and the trailer.
+    fn finish(&mut self) -> Vec<u8> {
+        let mut out = self.compress(&[], TDEFLFlush::Finish);
+        out.extend_from_slice(&self.crc.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out
+    }
+
+    fn compress(&mut self, data: &[u8], flush: TDEFLFlush) -> Vec<u8> {
+        let mut out = Vec::new();
+        if !self.header_written {
+            out.extend_from_slice(&Self::HEADER);
+            self.header_written = true;
+        }
+        let (status, _) = compress_to_output(&mut self.compressor, data, flush, |chunk| {
+            out.extend_from_slice(chunk);
+            true
+        });
+        debug_assert!(matches!(status, TDEFLStatus::Okay | TDEFLStatus::Done));
+        out
+    }
+}
+
+/// The lookup table of the CRC-32 checksum of gzip.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Updates the CRC-32 checksum `crc` with the data.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let crc = data.iter().fold(!crc, |crc, byte| {
+        CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_protobuf::BytesReader;
+
+    fn decode_events(bytes: &[u8]) -> Vec<proto::TraceEvent> {
+        let mut reader = BytesReader::from_bytes(bytes);
+        let mut events = Vec::new();
+        while !reader.is_eof() {
+            events.push(reader.read_message::<proto::TraceEvent>(bytes).unwrap());
+        }
+        events
+    }
+
+    #[test]
+    fn file_tracer_writes_delimited_events() {
+        let local_peer_id = PeerId::random();
+        let peer_id = PeerId::random();
+        let topic = TopicHash::from_raw("topic");
+        let mut tracer = FileTracer::new(local_peer_id, Vec::new());
+        tracer.trace(TraceEvent::Graft {
+            peer_id,
+            topic: topic.clone(),
+        });
+        tracer.trace(TraceEvent::DeliverMessage {
+            message_id: MessageId::new(b"id"),
+            received_from: peer_id,
+            topic: topic.clone(),
+        });
+
+        let events = decode_events(&tracer.into_inner());
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|e| e.peerID.as_deref() == Some(&local_peer_id.to_bytes()[..])));
+        assert_eq!(events[0].type_pb, Some(proto::mod_TraceEvent::Type::GRAFT));
+        assert_eq!(
+            events[0].graft.as_ref().unwrap().peerID,
+            Some(peer_id.to_bytes())
+        );
+        let deliver = events[1].deliverMessage.as_ref().unwrap();
+        assert_eq!(deliver.messageID.as_deref(), Some(&b"id"[..]));
+        assert_eq!(deliver.topic.as_deref(), Some("topic"));
+    }
+
+    #[test]
+    fn remote_trace_writer_sends_gzipped_batches() {
+        let local_peer_id = PeerId::random();
+        let (mut tracer, mut writer) = RemoteTracer::new(local_peer_id, 16);
+        for i in 0..3 {
+            tracer.trace(TraceEvent::PublishMessage {
+                message_id: MessageId::new(&[i]),
+                topic: TopicHash::from_raw("topic"),
+            });
+        }
+        drop(tracer);
+
+        let mut stream = futures::io::Cursor::new(Vec::new());
+        futures::executor::block_on(writer.run(&mut stream)).unwrap();
+        let bytes = stream.into_inner();
+
+        assert_eq!(bytes[..10], GzipEncoder::HEADER);
+        let (deflated, trailer) = bytes[10..].split_at(bytes.len() - 18);
+        let inflated = miniz_oxide::inflate::decompress_to_vec(deflated).unwrap();
+        assert_eq!(trailer[..4], crc32(0, &inflated).to_le_bytes());
+        assert_eq!(trailer[4..], (inflated.len() as u32).to_le_bytes());
+
+        let mut reader = BytesReader::from_bytes(&inflated);
+        let batch = reader
+            .read_message::<proto::TraceEventBatch>(&inflated)
+            .unwrap();
+        assert!(reader.is_eof());
+        assert_eq!(batch.batch.len(), 3);
+        assert_eq!(
+            batch.batch[2].publishMessage.as_ref().unwrap().messageID,
+            Some(vec![2])
+        );
+    }
+
+    #[test]
+    fn crc32_matches_check_value() {
+        assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+    }
+}
@@ -19,19 +19,22 @@
 // DEALINGS IN THE SOFTWARE.
 
 //! A collection of types using the Gossipsub system.
+use crate::chunking::Chunk;
 use crate::TopicHash;
 use libp2p_identity::PeerId;
 use libp2p_swarm::ConnectionId;
 use prometheus_client::encoding::EncodeLabelValue;
 use quick_protobuf::MessageWrite;
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Debug;
+use web_time::{Instant, SystemTime};
 
 use crate::rpc_proto::proto;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 /// Validation kinds from the application for received messages.
 pub enum MessageAcceptance {
     /// The message is considered valid, and it should be delivered and forwarded to the network.
@@ -77,11 +80,121 @@ pub(crate) struct PeerConnections {
     pub(crate) kind: PeerKind,
     /// Its current connections.
     pub(crate) connections: Vec<ConnectionId>,
+    /// Message ids the peer asked us not to send via IDONTWANT, with the time of the request.
+    pub(crate) dont_send: HashMap<MessageId, Instant>,
+    /// Those of its connections that are relayed.
+    pub(crate) relayed: Vec<ConnectionId>,
+    /// Whether its connection was hinted as metered, see
+    /// [`crate::Behaviour::set_peer_metered`].
+    pub(crate) metered: bool,
+}
+
+impl PeerConnections {
+    /// Returns whether the peer is only connected through relays or its connection was hinted
+    /// as metered, see [`crate::RelayedMeshPolicy`].
+    pub(crate) fn is_relayed(&self) -> bool {
+        self.metered || self.connections.iter().all(|c| self.relayed.contains(c))
+    }
+}
+
+/// A budget limiting the control messages of a peer, see [`crate::Event::BudgetExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControlBudget {
+    /// The IHAVE messages accepted from a peer within a heartbeat, see
+    /// [`crate::Config::max_ihave_messages`].
+    IHave,
+    /// The messages sent in response to the IWANT requests of a peer within a heartbeat, see
+    /// [`crate::Config::max_iwant_responses`].
+    IWant,
+    /// The control messages accepted from a peer, see
+    /// [`crate::Config::control_messages_per_second`].
+    ControlMessages,
+}
+
+/// Token bucket limiting the control messages accepted from a peer.
+#[derive(Debug)]
+pub(crate) struct ControlTokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl ControlTokenBucket {
+    /// Creates a full bucket.
+    pub(crate) fn new(burst: u32, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(burst),
+            last_refill: now,
+        }
+    }
+
+    /// Refills the bucket at the given rate and takes a token from it, returning whether a token
+    /// was available.
+    pub(crate) fn try_take(&mut self, per_second: u32, burst: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+        self.tokens = f64::min(
+            f64::from(burst),
+            self.tokens + elapsed.as_secs_f64() * f64::from(per_second),
+        );
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Why a peer was added to the mesh of a topic, see [`crate::Event::MeshPeerAdded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshJoinReason {
+    /// We joined the topic and the peer was in its fanout.
+    Fanout,
+    /// We joined the topic and selected the peer at random.
+    Joined,
+    /// The peer grafted us.
+    Grafted,
+    /// The peer subscribed to the topic while the mesh had too few peers.
+    Subscribed,
+    /// The peer was in the mesh before its connection dropped, and subscribed again after
+    /// reconnecting.
+    Reconnected,
+    /// The mesh had too few peers during the heartbeat.
+    MeshLow,
+    /// The mesh had too few outbound peers during the heartbeat.
+    OutboundQuota,
+    /// The peer was opportunistically grafted to improve a mesh of low scoring peers.
+    Opportunistic,
+}
+
+/// Why a peer was removed from the mesh of a topic, see [`crate::Event::MeshPeerRemoved`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshLeaveReason {
+    /// The peer disconnected.
+    Disconnected,
+    /// The peer had a negative score.
+    BadScore,
+    /// The peer pruned us.
+    Pruned,
+    /// The peer unsubscribed from the topic.
+    Unsubscribed,
+    /// We left the topic.
+    Left,
+    /// The mesh had too many peers.
+    Excess,
+    /// The peer did not deliver any message for too long.
+    Stale,
+    /// The peer was made an explicit peer.
+    Explicit,
+    /// The outbound queue of the peer stayed saturated, see
+    /// [`Config::slow_peer_policy`](crate::Config::slow_peer_policy).
+    SlowPeer,
 }
 
 /// Describes the types of peers that can exist in the gossipsub context.
 #[derive(Debug, Clone, PartialEq, Hash, EncodeLabelValue, Eq)]
 pub enum PeerKind {
+    /// A gossipsub 1.2 peer.
+    Gossipsubv1_2,
     /// A gossipsub 1.1 peer.
     Gossipsubv1_1,
     /// A gossipsub 1.0 peer.
@@ -115,6 +228,17 @@ pub struct RawMessage {
 
     /// Flag indicating if this message has been validated by the application or not.
     pub validated: bool,
+
+    /// The unix timestamp in milliseconds after which the message is no longer propagated, see
+    /// [`ConfigBuilder::topic_message_ttl`](crate::ConfigBuilder::topic_message_ttl).
+    ///
+    /// The deadline is not covered by the signature, such that peers not supporting deadlines
+    /// still accept the message.
+    pub expires_at: Option<u64>,
+
+    /// The position of the message in a chunked message, if it is a chunk, see
+    /// [`ConfigBuilder::max_chunked_message_size`](crate::ConfigBuilder::max_chunked_message_size).
+    pub chunk: Option<Chunk>,
 }
 
 impl RawMessage {
@@ -127,9 +251,24 @@ impl RawMessage {
             topic: TopicHash::into_string(self.topic.clone()),
             signature: self.signature.clone(),
             key: self.key.clone(),
+            expires_at: self.expires_at,
+            chunk: self.chunk.clone().map(Into::into),
         };
         message.get_size()
     }
+
+    /// Returns whether the deadline of the message passed at the given time.
+    pub(crate) fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| unix_millis(now) >= expires_at)
+    }
+}
+
+/// Returns the milliseconds elapsed between the unix epoch and the given time.
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
 }
 
 impl From<RawMessage> for proto::Message {
@@ -141,6 +280,8 @@ impl From<RawMessage> for proto::Message {
             topic: TopicHash::into_string(raw.topic),
             signature: raw.signature,
             key: raw.key,
+            expires_at: raw.expires_at,
+            chunk: raw.chunk.map(Into::into),
         }
     }
 }
@@ -197,9 +338,9 @@ pub enum SubscriptionAction {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PeerInfo {
     pub peer_id: Option<PeerId>,
-    //TODO add this when RFC: Signed Address Records got added to the spec (see pull request
-    // https://github.com/libp2p/specs/pull/217)
-    //pub signed_peer_record: ?,
+    /// The protobuf encoded signed envelope of the peer record of the peer, see
+    /// [`libp2p_core::PeerRecord`].
+    pub signed_peer_record: Option<Vec<u8>>,
 }
 
 /// A Control message received by the gossipsub system.
@@ -231,6 +372,35 @@ pub enum ControlAction {
         /// The backoff time in seconds before we allow to reconnect
         backoff: Option<u64>,
     },
+    /// The node does not want to receive the given messages - IDontWant control message.
+    IDontWant {
+        /// A list of message ids the node already received.
+        message_ids: Vec<MessageId>,
+    },
+    /// The node asks to only receive `IHAVE`s for the messages of a mesh topic - Choke control
+    /// message.
+    Choke {
+        /// The mesh topic of which messages should no longer be forwarded.
+        topic_hash: TopicHash,
+    },
+    /// The node asks to receive the messages of a mesh topic again - Unchoke control message.
+    Unchoke {
+        /// The mesh topic of which messages should be forwarded again.
+        topic_hash: TopicHash,
+    },
+    /// The node rejected a message as invalid - Invalid control message, signed by the node.
+    Invalid {
+        /// The id of the rejected message.
+        message_id: MessageId,
+        /// The topic of the rejected message.
+        topic_hash: TopicHash,
+        /// The peer the node received the rejected message from.
+        propagation_source: PeerId,
+        /// The signature of the report by the node.
+        signature: Vec<u8>,
+        /// The protobuf encoded public key of the node, if it is not inlined in its peer id.
+        key: Option<Vec<u8>>,
+    },
 }
 
 /// A Gossipsub RPC message sent.
@@ -300,6 +470,10 @@ impl From<RpcOut> for proto::RPC {
                     iwant: vec![],
                     graft: vec![],
                     prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
+                    invalid: vec![],
                 }),
             },
             RpcOut::Control(ControlAction::IWant { message_ids }) => proto::RPC {
@@ -312,6 +486,10 @@ impl From<RpcOut> for proto::RPC {
                     }],
                     graft: vec![],
                     prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
+                    invalid: vec![],
                 }),
             },
             RpcOut::Control(ControlAction::Graft { topic_hash }) => proto::RPC {
@@ -324,35 +502,114 @@ impl From<RpcOut> for proto::RPC {
                         topic_id: Some(topic_hash.into_string()),
                     }],
                     prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
+                    invalid: vec![],
                 }),
             },
             RpcOut::Control(ControlAction::Prune {
                 topic_hash,
                 peers,
                 backoff,
-            }) => {
-                proto::RPC {
-                    publish: Vec::new(),
-                    subscriptions: vec![],
-                    control: Some(proto::ControlMessage {
-                        ihave: vec![],
-                        iwant: vec![],
-                        graft: vec![],
-                        prune: vec![proto::ControlPrune {
-                            topic_id: Some(topic_hash.into_string()),
-                            peers: peers
-                                .into_iter()
-                                .map(|info| proto::PeerInfo {
-                                    peer_id: info.peer_id.map(|id| id.to_bytes()),
-                                    // TODO, see https://github.com/libp2p/specs/pull/217
-                                    signed_peer_record: None,
-                                })
-                                .collect(),
-                            backoff,
-                        }],
-                    }),
-                }
-            }
+            }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: vec![],
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![proto::ControlPrune {
+                        topic_id: Some(topic_hash.into_string()),
+                        peers: peers
+                            .into_iter()
+                            .map(|info| proto::PeerInfo {
+                                peer_id: info.peer_id.map(|id| id.to_bytes()),
+                                signed_peer_record: info.signed_peer_record,
+                            })
+                            .collect(),
+                        backoff,
+                    }],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
+                    invalid: vec![],
+                }),
+            },
+            RpcOut::Control(ControlAction::IDontWant { message_ids }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: Vec::new(),
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![],
+                    idontwant: vec![proto::ControlIDontWant {
+                        message_ids: message_ids.into_iter().map(|msg_id| msg_id.0).collect(),
+                    }],
+                    choke: vec![],
+                    unchoke: vec![],
+                    invalid: vec![],
+                }),
+            },
+            RpcOut::Control(ControlAction::Choke { topic_hash }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: Vec::new(),
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![proto::ControlChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    }],
+                    unchoke: vec![],
+                    invalid: vec![],
+                }),
+            },
+            RpcOut::Control(ControlAction::Unchoke { topic_hash }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: Vec::new(),
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![proto::ControlUnChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    }],
+                    invalid: vec![],
+                }),
+            },
+            RpcOut::Control(ControlAction::Invalid {
+                message_id,
+                topic_hash,
+                propagation_source,
+                signature,
+                key,
+            }) => proto::RPC {
+                publish: Vec::new(),
+                subscriptions: Vec::new(),
+                control: Some(proto::ControlMessage {
+                    ihave: vec![],
+                    iwant: vec![],
+                    graft: vec![],
+                    prune: vec![],
+                    idontwant: vec![],
+                    choke: vec![],
+                    unchoke: vec![],
+                    invalid: vec![proto::ControlInvalid {
+                        message_id: Some(message_id.0),
+                        topic_id: Some(topic_hash.into_string()),
+                        propagation_source: Some(propagation_source.to_bytes()),
+                        signature: Some(signature),
+                        key,
+                    }],
+                }),
+            },
         }
     }
 }
@@ -390,6 +647,8 @@ impl From<Rpc> for proto::RPC {
                 topic: TopicHash::into_string(message.topic),
                 signature: message.signature,
                 key: message.key,
+                expires_at: message.expires_at,
+                chunk: message.chunk.map(Into::into),
             };
 
             publish.push(message);
@@ -411,6 +670,10 @@ impl From<Rpc> for proto::RPC {
             iwant: Vec::new(),
             graft: Vec::new(),
             prune: Vec::new(),
+            idontwant: Vec::new(),
+            choke: Vec::new(),
+            unchoke: Vec::new(),
+            invalid: Vec::new(),
         };
 
         let empty_control_msg = rpc.control_msgs.is_empty();
@@ -451,14 +714,47 @@ impl From<Rpc> for proto::RPC {
                             .into_iter()
                             .map(|info| proto::PeerInfo {
                                 peer_id: info.peer_id.map(|id| id.to_bytes()),
-                                // TODO, see https://github.com/libp2p/specs/pull/217
-                                signed_peer_record: None,
+                                signed_peer_record: info.signed_peer_record,
                             })
                             .collect(),
                         backoff,
                     };
                     control.prune.push(rpc_prune);
                 }
+                ControlAction::IDontWant { message_ids } => {
+                    let rpc_idontwant = proto::ControlIDontWant {
+                        message_ids: message_ids.into_iter().map(|msg_id| msg_id.0).collect(),
+                    };
+                    control.idontwant.push(rpc_idontwant);
+                }
+                ControlAction::Choke { topic_hash } => {
+                    let rpc_choke = proto::ControlChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    };
+                    control.choke.push(rpc_choke);
+                }
+                ControlAction::Unchoke { topic_hash } => {
+                    let rpc_unchoke = proto::ControlUnChoke {
+                        topic_id: Some(topic_hash.into_string()),
+                    };
+                    control.unchoke.push(rpc_unchoke);
+                }
+                ControlAction::Invalid {
+                    message_id,
+                    topic_hash,
+                    propagation_source,
+                    signature,
+                    key,
+                } => {
+                    let rpc_invalid = proto::ControlInvalid {
+                        message_id: Some(message_id.0),
+                        topic_id: Some(topic_hash.into_string()),
+                        propagation_source: Some(propagation_source.to_bytes()),
+                        signature: Some(signature),
+                        key,
+                    };
+                    control.invalid.push(rpc_invalid);
+                }
             }
         }
 
@@ -497,6 +793,7 @@ impl PeerKind {
             Self::Floodsub => "Floodsub",
             Self::Gossipsub => "Gossipsub v1.0",
             Self::Gossipsubv1_1 => "Gossipsub v1.1",
+            Self::Gossipsubv1_2 => "Gossipsub v1.2",
         }
     }
 }
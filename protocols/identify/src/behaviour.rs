@@ -19,25 +19,33 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::handler::{self, Handler, InEvent};
+use crate::info_cache::InfoCache;
 use crate::protocol::{Info, UpgradeError};
+use futures::FutureExt;
+use futures_timer::Delay;
 use libp2p_core::{multiaddr, ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_identity::PublicKey;
 use libp2p_swarm::behaviour::{ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm};
 use libp2p_swarm::{
-    ConnectionDenied, DialError, ExternalAddresses, ListenAddresses, NetworkBehaviour,
-    NotifyHandler, PeerAddresses, StreamUpgradeError, THandlerInEvent, ToSwarm,
+    CloseConnection, ConnectionDenied, DialError, ExternalAddresses, ListenAddresses,
+    NetworkBehaviour, NotifyHandler, PeerAddresses, StreamProtocol, StreamUpgradeError,
+    THandlerInEvent, ToSwarm,
 };
 use libp2p_swarm::{ConnectionId, THandler, THandlerOutEvent};
 
 use std::collections::hash_map::Entry;
+use std::net::IpAddr;
 use std::num::NonZeroUsize;
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    sync::Arc,
     task::Context,
     task::Poll,
     time::Duration,
 };
+use web_time::Instant;
 
 /// Network behaviour that automatically identifies nodes periodically, returns information
 /// about them, and answers identify queries from other nodes.
@@ -56,9 +64,32 @@ pub struct Behaviour {
     events: VecDeque<ToSwarm<Event, InEvent>>,
     /// The addresses of all peers that we have discovered.
     discovered_peers: PeerCache,
+    /// The latest information of the identified peers, see [`Config::with_info_cache_ttl`].
+    identified_peers: Option<InfoCache>,
 
     listen_addresses: ListenAddresses,
     external_addresses: ExternalAddresses,
+
+    /// The peers that reported each of our observed addresses, see
+    /// [`Config::with_observed_addr_confirmations`].
+    observed_addr_reports: ObservedAddrReports,
+
+    /// Fires once the batched changes of the listen addresses are to be pushed, see
+    /// [`Config::push_interval`].
+    scheduled_push: Option<Delay>,
+
+    /// The id of the next request of [`Behaviour::request_identify`].
+    next_request_id: u64,
+}
+
+/// The id of a request of [`Behaviour::request_identify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 /// Configuration for the [`identify::Behaviour`](Behaviour).
@@ -92,11 +123,111 @@ pub struct Config {
     /// Disabled by default.
     pub push_listen_addr_updates: bool,
 
+    /// The interval over which changes of the listen addresses are batched into a single push,
+    /// see [`Config::push_listen_addr_updates`]. The first change schedules a push after the
+    /// interval, which includes all changes until then, bounding the rate of pushes while the
+    /// addresses change rapidly, e.g. during a cellular handover.
+    ///
+    /// Defaults to zero, i.e. every change is pushed right away.
+    pub push_interval: Duration,
+
+    /// The maximum number of pushes accepted from a peer on a connection within a window. The
+    /// pushes exceeding it are dropped and reported via [`Event::PushRateLimited`].
+    ///
+    /// Disabled by default.
+    pub push_rate_limit: Option<(u32, Duration)>,
+
+    /// What to do about peers exceeding the [`Config::push_rate_limit`].
+    ///
+    /// Defaults to [`PushRateLimitPolicy::Ignore`].
+    pub push_rate_limit_policy: PushRateLimitPolicy,
+
     /// How many entries of discovered peers to keep before we discard
     /// the least-recently used one.
     ///
     /// Disabled by default.
     pub cache_size: usize,
+
+    /// Application-defined key-value pairs sent to peers along with the identify message, and
+    /// reported in [`Info::metadata`] by peers supporting them.
+    ///
+    /// They are carried in the extension space of the identify message, which is skipped by
+    /// implementations not knowing about it. Note that they count towards the maximum size of the
+    /// message of 4 KiB.
+    ///
+    /// Empty by default.
+    pub metadata: BTreeMap<String, Vec<u8>>,
+
+    /// Decides which of the local listen and external addresses are disclosed to a remote, see
+    /// [`Config::with_address_filter`].
+    address_filter: Option<AddressFilter>,
+
+    /// The number of distinct peers on distinct IP addresses that must report the same observed
+    /// address within [`Config::observed_addr_window`] before it is reported via
+    /// [`ToSwarm::NewExternalAddrCandidate`].
+    ///
+    /// Only the latest address observed on each connection is counted, and the reports of at most
+    /// 1024 connections are kept, dropping the oldest ones first.
+    ///
+    /// Defaults to 1, i.e. every observed address is reported right away.
+    pub observed_addr_confirmations: usize,
+
+    /// The window within which the reports of an observed address are counted towards
+    /// [`Config::observed_addr_confirmations`].
+    ///
+    /// Defaults to 10 minutes.
+    pub observed_addr_window: Duration,
+
+    /// How long the information of an identified peer is cached. A peer reconnecting within the
+    /// TTL is not identified right away, but only after [`Config::interval`], starting from the
+    /// cached information instead. The cache holds up to [`Config::cache_size`] peers.
+    ///
+    /// Disabled by default.
+    pub info_cache_ttl: Option<Duration>,
+
+    /// How identify runs on connections relayed via `/p2p-circuit`.
+    ///
+    /// Defaults to [`RelayedConnectionPolicy::Identify`].
+    pub relayed_connection_policy: RelayedConnectionPolicy,
+}
+
+/// How identify runs on connections relayed via `/p2p-circuit`, see
+/// [`Config::relayed_connection_policy`].
+///
+/// The address a remote observes on a relayed connection is the address of the relay circuit
+/// rather than one of the local node, so it is no useful external address candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RelayedConnectionPolicy {
+    /// Identify like on any other connection.
+    #[default]
+    Identify,
+    /// Identify, but ignore the address the remote observed for external address candidacy.
+    IgnoreObservedAddr,
+    /// Neither identify the remote nor answer its identify requests and pushes, e.g. to save the
+    /// limited data of transient relayed connections.
+    Disabled,
+}
+
+/// What to do about peers exceeding the [`Config::push_rate_limit`], on top of dropping their
+/// excess pushes and reporting them via [`Event::PushRateLimited`], e.g. to score them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PushRateLimitPolicy {
+    /// Keep the connection.
+    #[default]
+    Ignore,
+    /// Close the connection the excess push was received on.
+    Disconnect,
+}
+
+/// Returns whether the local address is disclosed to the remote peer, connected via the remote
+/// address.
+#[derive(Clone)]
+struct AddressFilter(Arc<dyn Fn(&PeerId, &Multiaddr, &Multiaddr) -> bool + Send + Sync>);
+
+impl fmt::Debug for AddressFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AddressFilter")
+    }
 }
 
 impl Config {
@@ -109,7 +240,16 @@ impl Config {
             local_public_key,
             interval: Duration::from_secs(5 * 60),
             push_listen_addr_updates: false,
+            push_interval: Duration::ZERO,
+            push_rate_limit: None,
+            push_rate_limit_policy: PushRateLimitPolicy::Ignore,
             cache_size: 100,
+            metadata: BTreeMap::new(),
+            address_filter: None,
+            observed_addr_confirmations: 1,
+            observed_addr_window: Duration::from_secs(10 * 60),
+            info_cache_ttl: None,
+            relayed_connection_policy: RelayedConnectionPolicy::Identify,
         }
     }
 
@@ -134,11 +274,83 @@ impl Config {
         self
     }
 
+    /// Configures the interval over which changes of the listen addresses are batched into a
+    /// single push.
+    pub fn with_push_interval(mut self, d: Duration) -> Self {
+        self.push_interval = d;
+        self
+    }
+
+    /// Configures the maximum number of pushes accepted from a peer on a connection within the
+    /// given window, and what to do about peers exceeding it.
+    pub fn with_push_rate_limit(
+        mut self,
+        max_pushes: u32,
+        window: Duration,
+        policy: PushRateLimitPolicy,
+    ) -> Self {
+        self.push_rate_limit = Some((max_pushes, window));
+        self.push_rate_limit_policy = policy;
+        self
+    }
+
     /// Configures the size of the LRU cache, caching addresses of discovered peers.
     pub fn with_cache_size(mut self, cache_size: usize) -> Self {
         self.cache_size = cache_size;
         self
     }
+
+    /// Configures a key-value pair sent to peers along with the identify message.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Configures which of the local listen and external addresses are disclosed to remotes.
+    ///
+    /// The filter is called with the remote peer, the address of the remote on the connection the
+    /// identify message is sent on, and a local address, returning whether the local address is
+    /// disclosed. E.g. private addresses can be withheld from remotes connected via public
+    /// addresses, or all addresses from remotes not in an allowlist.
+    ///
+    /// By default, all addresses are disclosed.
+    pub fn with_address_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&PeerId, &Multiaddr, &Multiaddr) -> bool + Send + Sync + 'static,
+    {
+        self.address_filter = Some(AddressFilter(Arc::new(filter)));
+        self
+    }
+
+    /// Configures the number of distinct peers on distinct IP addresses that must report the same
+    /// observed address within the given window before it is reported as external address
+    /// candidate, reducing the flapping of addresses behind NATs with multiple egress addresses.
+    ///
+    /// Once confirmed, an address is also reported via [`Event::ObservedAddrConfirmed`].
+    pub fn with_observed_addr_confirmations(
+        mut self,
+        confirmations: usize,
+        window: Duration,
+    ) -> Self {
+        self.observed_addr_confirmations = confirmations;
+        self.observed_addr_window = window;
+        self
+    }
+
+    /// Configures how long the information of an identified peer is cached, skipping the
+    /// identification of peers reconnecting within the TTL.
+    ///
+    /// The cached information can be queried via [`Behaviour::cached_info`].
+    pub fn with_info_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.info_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Configures how identify runs on connections relayed via `/p2p-circuit`.
+    pub fn with_relayed_connection_policy(mut self, policy: RelayedConnectionPolicy) -> Self {
+        self.relayed_connection_policy = policy;
+        self
+    }
 }
 
 impl Behaviour {
@@ -148,6 +360,12 @@ impl Behaviour {
             None => PeerCache::disabled(),
             Some(size) => PeerCache::enabled(size),
         };
+        let identified_peers = NonZeroUsize::new(config.cache_size)
+            .zip(config.info_cache_ttl)
+            .map(|(size, ttl)| InfoCache::new(size, ttl));
+
+        let observed_addr_reports =
+            ObservedAddrReports::new(config.observed_addr_window, MAX_OBSERVED_ADDR_REPORTS);
 
         Self {
             config,
@@ -155,11 +373,51 @@ impl Behaviour {
             our_observed_addresses: Default::default(),
             events: VecDeque::new(),
             discovered_peers,
+            identified_peers,
             listen_addresses: Default::default(),
             external_addresses: Default::default(),
+            observed_addr_reports,
+            scheduled_push: None,
+            next_request_id: 0,
         }
     }
 
+    /// Identifies the given peer again on one of the existing connections, without waiting for
+    /// the next periodic identification or a push of the peer.
+    ///
+    /// The information of the peer is reported via an [`Event::Received`] carrying the returned
+    /// id. If the identification fails, an [`Event::Error`] is reported instead.
+    ///
+    /// Returns `None` if we are not connected to the peer.
+    pub fn request_identify(&mut self, peer_id: &PeerId) -> Option<RequestId> {
+        if !self.connected.contains_key(peer_id) {
+            tracing::debug!(peer=%peer_id, "Not identifying peer because we are not connected");
+            return None;
+        }
+
+        let request_id = RequestId(self.next_request_id);
+        self.next_request_id += 1;
+        self.events.push_back(ToSwarm::NotifyHandler {
+            peer_id: *peer_id,
+            handler: self.any_connection(peer_id),
+            event: InEvent::Identify(request_id),
+        });
+
+        Some(request_id)
+    }
+
+    /// The latest information of the given peer, if it was identified within
+    /// [`Config::info_cache_ttl`].
+    pub fn cached_info(&self, peer_id: &PeerId) -> Option<&Info> {
+        self.identified_peers.as_ref()?.get(peer_id, Instant::now())
+    }
+
+    /// Forgets the cached information of the given peer, e.g. to identify it right away once it
+    /// reconnects.
+    pub fn remove_cached_info(&mut self, peer_id: &PeerId) -> Option<Info> {
+        self.identified_peers.as_mut()?.remove(peer_id)
+    }
+
     /// Initiates an active push of the local peer information to the given peers.
     pub fn push<I>(&mut self, peers: I)
     where
@@ -173,12 +431,67 @@ impl Behaviour {
 
             self.events.push_back(ToSwarm::NotifyHandler {
                 peer_id: p,
-                handler: NotifyHandler::Any,
+                handler: self.any_connection(&p),
                 event: InEvent::Push,
             });
         }
     }
 
+    /// Any connection to the given peer identify runs on, i.e. excluding the relayed connections
+    /// if identify is [`RelayedConnectionPolicy::Disabled`] on them.
+    fn any_connection(&self, peer_id: &PeerId) -> NotifyHandler {
+        self.connected
+            .get(peer_id)
+            .and_then(|connections| connections.keys().next())
+            .map_or(NotifyHandler::Any, |connection_id| {
+                NotifyHandler::One(*connection_id)
+            })
+    }
+
+    /// Returns whether identify runs on a connection via the given remote address.
+    fn is_identified(&self, remote_addr: &Multiaddr) -> bool {
+        self.config.relayed_connection_policy != RelayedConnectionPolicy::Disabled
+            || !is_relayed(remote_addr)
+    }
+
+    /// Sets a key-value pair sent to peers along with the identify message, pushing it to all
+    /// connected peers.
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: impl Into<Vec<u8>>) {
+        let key = key.into();
+        let value = value.into();
+        if self.config.metadata.get(&key) == Some(&value) {
+            return;
+        }
+
+        self.config.metadata.insert(key, value);
+        self.on_metadata_changed();
+    }
+
+    /// Removes a key-value pair sent to peers along with the identify message, pushing the
+    /// remaining ones to all connected peers.
+    ///
+    /// Peers only learn about the removal if at least one key-value pair remains, as they keep the
+    /// previous ones if a push carries none.
+    pub fn remove_metadata(&mut self, key: &str) -> Option<Vec<u8>> {
+        let value = self.config.metadata.remove(key)?;
+        self.on_metadata_changed();
+
+        Some(value)
+    }
+
+    fn on_metadata_changed(&mut self) {
+        for (peer_id, connections) in &self.connected {
+            for connection_id in connections.keys() {
+                self.events.push_back(ToSwarm::NotifyHandler {
+                    peer_id: *peer_id,
+                    handler: NotifyHandler::One(*connection_id),
+                    event: InEvent::MetadataChanged(self.config.metadata.clone()),
+                });
+            }
+        }
+        self.push(self.connected.keys().copied().collect::<Vec<_>>());
+    }
+
     fn on_connection_established(
         &mut self,
         ConnectionEstablished {
@@ -194,6 +507,11 @@ impl Behaviour {
             ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr.clone(),
         };
 
+        if !self.is_identified(&addr) {
+            tracing::debug!(peer=%peer_id, "Not identifying peer on relayed connection {conn}");
+            return;
+        }
+
         self.connected
             .entry(peer_id)
             .or_default()
@@ -206,10 +524,82 @@ impl Behaviour {
         }
     }
 
-    fn all_addresses(&self) -> HashSet<Multiaddr> {
+    /// Reports the address observed by the given peer as external address candidate once it is
+    /// confirmed by enough peers, see [`Config::with_observed_addr_confirmations`].
+    fn on_observed_addr(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        observed: Multiaddr,
+    ) {
+        let relayed = self
+            .connected
+            .get(&peer_id)
+            .and_then(|connections| connections.get(&connection_id))
+            .is_some_and(is_relayed);
+        if relayed
+            && self.config.relayed_connection_policy == RelayedConnectionPolicy::IgnoreObservedAddr
+        {
+            tracing::debug!(
+                address=%observed,
+                "Ignoring address observed on relayed connection {connection_id}"
+            );
+            return;
+        }
+
+        let required = self.config.observed_addr_confirmations;
+        if required <= 1 {
+            self.events
+                .push_back(ToSwarm::NewExternalAddrCandidate(observed));
+            return;
+        }
+
+        let remote_ip = self
+            .connected
+            .get(&peer_id)
+            .and_then(|connections| connections.get(&connection_id))
+            .and_then(ip_of);
+        let now = Instant::now();
+        let before = self.observed_addr_reports.confirmations(&observed, now);
+        let confirmations = self.observed_addr_reports.insert(
+            peer_id,
+            connection_id,
+            observed.clone(),
+            remote_ip,
+            now,
+        );
+
+        if confirmations < required {
+            tracing::debug!(
+                address=%observed,
+                %confirmations,
+                "Not yet reporting observed address as external address candidate"
+            );
+            return;
+        }
+        if before < required {
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::ObservedAddrConfirmed {
+                    connection_id,
+                    address: observed.clone(),
+                    confirmations,
+                }));
+        }
+        self.events
+            .push_back(ToSwarm::NewExternalAddrCandidate(observed));
+    }
+
+    /// The local addresses disclosed to the given peer, connected via the given remote address.
+    fn addresses_for(&self, peer_id: &PeerId, remote_addr: &Multiaddr) -> HashSet<Multiaddr> {
         self.listen_addresses
             .iter()
             .chain(self.external_addresses.iter())
+            .filter(|addr| {
+                self.config
+                    .address_filter
+                    .as_ref()
+                    .map_or(true, |filter| (filter.0)(peer_id, remote_addr, addr))
+            })
             .cloned()
             .collect()
     }
@@ -233,8 +623,12 @@ impl NetworkBehaviour for Behaviour {
             self.config.protocol_version.clone(),
             self.config.agent_version.clone(),
             remote_addr.clone(),
-            self.all_addresses(),
-        ))
+            self.addresses_for(&peer, remote_addr),
+        )
+        .with_metadata(self.config.metadata.clone())
+        .with_push_rate_limit(self.config.push_rate_limit)
+        .with_cached_info(self.cached_info(&peer).cloned())
+        .with_active(self.is_identified(remote_addr)))
     }
 
     fn handle_established_outbound_connection(
@@ -251,8 +645,12 @@ impl NetworkBehaviour for Behaviour {
             self.config.protocol_version.clone(),
             self.config.agent_version.clone(),
             addr.clone(), // TODO: This is weird? That is the public address we dialed, shouldn't need to tell the other party?
-            self.all_addresses(),
-        ))
+            self.addresses_for(&peer, addr),
+        )
+        .with_metadata(self.config.metadata.clone())
+        .with_push_rate_limit(self.config.push_rate_limit)
+        .with_cached_info(self.cached_info(&peer).cloned())
+        .with_active(self.is_identified(addr)))
     }
 
     fn on_connection_handler_event(
@@ -262,17 +660,21 @@ impl NetworkBehaviour for Behaviour {
         event: THandlerOutEvent<Self>,
     ) {
         match event {
-            handler::Event::Identified(mut info) => {
+            handler::Event::Identified(mut info, request_id) => {
                 // Remove invalid multiaddrs.
                 info.listen_addrs
                     .retain(|addr| multiaddr_matches_peer_id(addr, &peer_id));
 
                 let observed = info.observed_addr.clone();
+                if let Some(cache) = self.identified_peers.as_mut() {
+                    cache.insert(peer_id, info.clone(), Instant::now());
+                }
                 self.events
                     .push_back(ToSwarm::GenerateEvent(Event::Received {
                         connection_id,
                         peer_id,
                         info: info.clone(),
+                        request_id,
                     }));
 
                 if let Some(ref mut discovered_peers) = self.discovered_peers.0 {
@@ -289,8 +691,7 @@ impl NetworkBehaviour for Behaviour {
                 match self.our_observed_addresses.entry(connection_id) {
                     Entry::Vacant(not_yet_observed) => {
                         not_yet_observed.insert(observed.clone());
-                        self.events
-                            .push_back(ToSwarm::NewExternalAddrCandidate(observed));
+                        self.on_observed_addr(peer_id, connection_id, observed);
                     }
                     Entry::Occupied(already_observed) if already_observed.get() == &observed => {
                         // No-op, we already observed this address.
@@ -303,8 +704,7 @@ impl NetworkBehaviour for Behaviour {
                         );
 
                         *already_observed.get_mut() = observed.clone();
-                        self.events
-                            .push_back(ToSwarm::NewExternalAddrCandidate(observed));
+                        self.on_observed_addr(peer_id, connection_id, observed);
                     }
                 }
             }
@@ -328,15 +728,48 @@ impl NetworkBehaviour for Behaviour {
                     error,
                 }));
             }
+            handler::Event::ProtocolsChanged { added, removed } => {
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::ProtocolsChanged {
+                        connection_id,
+                        peer_id,
+                        added,
+                        removed,
+                    }));
+            }
+            handler::Event::PushRateLimited => {
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::PushRateLimited {
+                        connection_id,
+                        peer_id,
+                    }));
+                if self.config.push_rate_limit_policy == PushRateLimitPolicy::Disconnect {
+                    self.events.push_back(ToSwarm::CloseConnection {
+                        peer_id,
+                        connection: CloseConnection::One(connection_id),
+                    });
+                }
+            }
         }
     }
 
-    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self))]
-    fn poll(&mut self, _: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+    #[tracing::instrument(level = "trace", name = "NetworkBehaviour::poll", skip(self, cx))]
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
         if let Some(event) = self.events.pop_front() {
             return Poll::Ready(event);
         }
 
+        if let Some(Poll::Ready(())) = self.scheduled_push.as_mut().map(|d| d.poll_unpin(cx)) {
+            self.scheduled_push = None;
+            self.push(self.connected.keys().copied().collect::<Vec<_>>());
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(event);
+            }
+        }
+
         Poll::Pending
     }
 
@@ -364,26 +797,30 @@ impl NetworkBehaviour for Behaviour {
             let change_events = self
                 .connected
                 .iter()
-                .flat_map(|(peer, map)| map.keys().map(|id| (*peer, id)))
-                .map(|(peer_id, connection_id)| ToSwarm::NotifyHandler {
-                    peer_id,
-                    handler: NotifyHandler::One(*connection_id),
-                    event: InEvent::AddressesChanged(self.all_addresses()),
-                })
+                .flat_map(|(peer, map)| map.iter().map(|(id, addr)| (*peer, id, addr)))
+                .map(
+                    |(peer_id, connection_id, remote_addr)| ToSwarm::NotifyHandler {
+                        peer_id,
+                        handler: NotifyHandler::One(*connection_id),
+                        event: InEvent::AddressesChanged(self.addresses_for(&peer_id, remote_addr)),
+                    },
+                )
                 .collect::<Vec<_>>();
 
             self.events.extend(change_events)
         }
 
-        if listen_addr_changed && self.config.push_listen_addr_updates {
+        if listen_addr_changed
+            && self.config.push_listen_addr_updates
+            && !self.config.push_interval.is_zero()
+        {
+            // batch the changes into a single push, scheduled unless already
+            if self.scheduled_push.is_none() {
+                self.scheduled_push = Some(Delay::new(self.config.push_interval));
+            }
+        } else if listen_addr_changed && self.config.push_listen_addr_updates {
             // trigger an identify push for all connected peers
-            let push_events = self.connected.keys().map(|peer| ToSwarm::NotifyHandler {
-                peer_id: *peer,
-                handler: NotifyHandler::Any,
-                event: InEvent::Push,
-            });
-
-            self.events.extend(push_events);
+            self.push(self.connected.keys().copied().collect::<Vec<_>>());
         }
 
         match event {
@@ -393,13 +830,13 @@ impl NetworkBehaviour for Behaviour {
             FromSwarm::ConnectionClosed(ConnectionClosed {
                 peer_id,
                 connection_id,
-                remaining_established,
                 ..
             }) => {
-                if remaining_established == 0 {
-                    self.connected.remove(&peer_id);
-                } else if let Some(addrs) = self.connected.get_mut(&peer_id) {
-                    addrs.remove(&connection_id);
+                if let Entry::Occupied(mut addrs) = self.connected.entry(peer_id) {
+                    addrs.get_mut().remove(&connection_id);
+                    if addrs.get().is_empty() {
+                        addrs.remove();
+                    }
                 }
 
                 self.our_observed_addresses.remove(&connection_id);
@@ -430,6 +867,8 @@ pub enum Event {
         peer_id: PeerId,
         /// The information provided by the peer.
         info: Info,
+        /// The request of [`Behaviour::request_identify`] answered by the information, if any.
+        request_id: Option<RequestId>,
     },
     /// Identification information of the local node has been sent to a peer in
     /// response to an identification request.
@@ -459,6 +898,36 @@ pub enum Event {
         /// The error that occurred.
         error: StreamUpgradeError<UpgradeError>,
     },
+    /// The protocols supported by a peer changed since it was last identified, e.g. as it pushed
+    /// its updated information. Reported after the [`Event::Received`] carrying the updated
+    /// information.
+    ProtocolsChanged {
+        /// Identifier of the connection.
+        connection_id: ConnectionId,
+        /// The peer whose protocols changed.
+        peer_id: PeerId,
+        /// The protocols newly supported by the peer.
+        added: HashSet<StreamProtocol>,
+        /// The protocols no longer supported by the peer.
+        removed: HashSet<StreamProtocol>,
+    },
+    /// A push of a peer exceeding the [`Config::push_rate_limit`] has been dropped.
+    PushRateLimited {
+        /// Identifier of the connection.
+        connection_id: ConnectionId,
+        /// The peer that pushed.
+        peer_id: PeerId,
+    },
+    /// An observed address of the local node has been confirmed by enough peers and is now
+    /// reported as external address candidate, see [`Config::with_observed_addr_confirmations`].
+    ObservedAddrConfirmed {
+        /// Identifier of the connection the confirming report was received on.
+        connection_id: ConnectionId,
+        /// The observed address.
+        address: Multiaddr,
+        /// The number of distinct peers on distinct IP addresses that reported the address.
+        confirmations: usize,
+    },
 }
 
 impl Event {
@@ -467,7 +936,10 @@ impl Event {
             Event::Received { connection_id, .. }
             | Event::Sent { connection_id, .. }
             | Event::Pushed { connection_id, .. }
-            | Event::Error { connection_id, .. } => *connection_id,
+            | Event::Error { connection_id, .. }
+            | Event::ProtocolsChanged { connection_id, .. }
+            | Event::PushRateLimited { connection_id, .. }
+            | Event::ObservedAddrConfirmed { connection_id, .. } => *connection_id,
         }
     }
 }
@@ -482,6 +954,142 @@ fn multiaddr_matches_peer_id(addr: &Multiaddr, peer_id: &PeerId) -> bool {
     true
 }
 
+/// Returns whether the given address is relayed via a relay circuit.
+fn is_relayed(addr: &Multiaddr) -> bool {
+    addr.iter().any(|p| p == multiaddr::Protocol::P2pCircuit)
+}
+
+/// The IP address of the given address, if any.
+fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    addr.iter().find_map(|p| match p {
+        multiaddr::Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        multiaddr::Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    })
+}
+
+/// The maximum number of connections whose latest observed address report is kept.
+const MAX_OBSERVED_ADDR_REPORTS: usize = 1024;
+
+/// The latest report of our observed address on a connection.
+#[derive(Debug)]
+struct ObservedAddrReport {
+    address: Multiaddr,
+    remote_ip: Option<IpAddr>,
+    reported: Instant,
+}
+
+/// The latest observed address reported on each connection, indexed by address.
+///
+/// A remote reporting another address on the same connection replaces its previous report, and
+/// at most `max_len` reports are kept, such that the memory used does not depend on the number of
+/// reports.
+#[derive(Debug)]
+struct ObservedAddrReports {
+    reports: HashMap<(PeerId, ConnectionId), ObservedAddrReport>,
+    by_address: HashMap<Multiaddr, HashSet<(PeerId, ConnectionId)>>,
+    window: Duration,
+    max_len: usize,
+}
+
+impl ObservedAddrReports {
+    fn new(window: Duration, max_len: usize) -> Self {
+        Self {
+            reports: HashMap::new(),
+            by_address: HashMap::new(),
+            window,
+            max_len,
+        }
+    }
+
+    /// Records the report of the given address on the given connection, returning the number of
+    /// confirmations of the address.
+    fn insert(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        address: Multiaddr,
+        remote_ip: Option<IpAddr>,
+        now: Instant,
+    ) -> usize {
+        let key = (peer_id, connection_id);
+        self.remove(&key);
+        if self.reports.len() >= self.max_len {
+            self.remove_expired(now);
+        }
+        if self.reports.len() >= self.max_len {
+            let oldest = self
+                .reports
+                .iter()
+                .min_by_key(|(_, report)| report.reported)
+                .map(|(key, _)| *key)
+                .expect("reports to be non-empty");
+            self.remove(&oldest);
+        }
+
+        self.by_address
+            .entry(address.clone())
+            .or_default()
+            .insert(key);
+        self.reports.insert(
+            key,
+            ObservedAddrReport {
+                address: address.clone(),
+                remote_ip,
+                reported: now,
+            },
+        );
+
+        self.confirmations(&address, now)
+    }
+
+    /// The number of distinct peers on distinct IP addresses that reported the given address
+    /// within the window. Peers without IP address, e.g. on in-memory connections, are counted
+    /// individually.
+    fn confirmations(&self, address: &Multiaddr, now: Instant) -> usize {
+        let Some(keys) = self.by_address.get(address) else {
+            return 0;
+        };
+        let ip_by_peer = keys
+            .iter()
+            .filter_map(|key| Some((key.0, self.reports.get(key)?)))
+            .filter(|(_, report)| now.duration_since(report.reported) < self.window)
+            .map(|(peer_id, report)| (peer_id, report.remote_ip))
+            .collect::<HashMap<_, _>>();
+        let ips = ip_by_peer
+            .values()
+            .filter_map(|ip| *ip)
+            .collect::<HashSet<_>>();
+
+        ips.len() + ip_by_peer.values().filter(|ip| ip.is_none()).count()
+    }
+
+    fn remove(&mut self, key: &(PeerId, ConnectionId)) {
+        let Some(report) = self.reports.remove(key) else {
+            return;
+        };
+        if let Entry::Occupied(mut keys) = self.by_address.entry(report.address) {
+            keys.get_mut().remove(key);
+            if keys.get().is_empty() {
+                keys.remove();
+            }
+        }
+    }
+
+    /// Removes the reports older than the window.
+    fn remove_expired(&mut self, now: Instant) {
+        let expired = self
+            .reports
+            .iter()
+            .filter(|(_, report)| now.duration_since(report.reported) >= self.window)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in expired {
+            self.remove(&key);
+        }
+    }
+}
+
 struct PeerCache(Option<PeerAddresses>);
 
 impl PeerCache {
@@ -527,4 +1135,151 @@ mod tests {
         ));
         assert!(multiaddr_matches_peer_id(&addr_without_peer_id, &peer_id));
     }
+
+    #[test]
+    fn observed_addr_is_confirmed_by_distinct_peers_on_distinct_ips() {
+        let observed: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let ip = |i: u8| Some(IpAddr::from([10, 0, 0, i]));
+        let now = Instant::now();
+        let window = Duration::from_secs(60);
+        let mut reports = ObservedAddrReports::new(window, 10);
+        let mut insert =
+            |peer, connection, ip, now| reports.insert(peer, connection, observed.clone(), ip, now);
+
+        assert_eq!(
+            insert(PeerId::random(), ConnectionId::new_unchecked(0), ip(1), now),
+            1
+        );
+        // Peers behind the same IP address count once.
+        assert_eq!(
+            insert(PeerId::random(), ConnectionId::new_unchecked(1), ip(1), now),
+            1
+        );
+        let peer = PeerId::random();
+        assert_eq!(insert(peer, ConnectionId::new_unchecked(2), ip(2), now), 2);
+        // Repeated reports of a peer count once, also on another connection.
+        assert_eq!(
+            insert(
+                peer,
+                ConnectionId::new_unchecked(2),
+                ip(2),
+                now + window / 2
+            ),
+            2
+        );
+        assert_eq!(
+            insert(
+                peer,
+                ConnectionId::new_unchecked(3),
+                ip(2),
+                now + window / 2
+            ),
+            2
+        );
+
+        assert_eq!(reports.confirmations(&observed, now + window), 1);
+        assert_eq!(reports.confirmations(&observed, now + window * 2), 0);
+        reports.remove_expired(now + window * 2);
+        assert!(reports.reports.is_empty());
+        assert!(reports.by_address.is_empty());
+    }
+
+    #[test]
+    fn observed_addr_reports_are_bounded() {
+        let now = Instant::now();
+        let mut reports = ObservedAddrReports::new(Duration::from_secs(60), 2);
+        let address =
+            |i: u8| Multiaddr::empty().with(multiaddr::Protocol::Ip4([1, 2, 3, i].into()));
+        let peer = PeerId::random();
+        let connection = ConnectionId::new_unchecked(0);
+
+        // A connection reporting ever new addresses only keeps its latest report.
+        for i in 0..10 {
+            reports.insert(peer, connection, address(i), None, now);
+        }
+        assert_eq!(reports.reports.len(), 1);
+        assert_eq!(reports.by_address.len(), 1);
+        assert_eq!(reports.confirmations(&address(9), now), 1);
+        assert_eq!(reports.confirmations(&address(0), now), 0);
+
+        // The oldest report is dropped once the limit is reached.
+        let other = PeerId::random();
+        reports.insert(
+            other,
+            connection,
+            address(9),
+            None,
+            now + Duration::from_secs(1),
+        );
+        reports.insert(
+            PeerId::random(),
+            connection,
+            address(1),
+            None,
+            now + Duration::from_secs(2),
+        );
+        assert_eq!(reports.reports.len(), 2);
+        assert_eq!(
+            reports.confirmations(&address(9), now + Duration::from_secs(2)),
+            1
+        );
+        assert!(!reports.reports.contains_key(&(peer, connection)));
+    }
+
+    #[test]
+    fn observed_addr_on_relayed_connection_is_ignored() {
+        let keypair = libp2p_identity::Keypair::generate_ed25519();
+        let mut behaviour = Behaviour::new(
+            Config::new("a".to_string(), keypair.public())
+                .with_relayed_connection_policy(RelayedConnectionPolicy::IgnoreObservedAddr),
+        );
+        let peer_id = PeerId::random();
+        let relayed: Multiaddr =
+            format!("/ip4/1.2.3.4/tcp/4001/p2p/{}/p2p-circuit", PeerId::random())
+                .parse()
+                .unwrap();
+        let direct: Multiaddr = "/ip4/5.6.7.8/tcp/4001".parse().unwrap();
+
+        let observe = |behaviour: &mut Behaviour, connection_id, address: &Multiaddr| {
+            let endpoint = ConnectedPoint::Dialer {
+                address: address.clone(),
+                role_override: Endpoint::Dialer,
+            };
+            behaviour.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint: &endpoint,
+                failed_addresses: &[],
+                other_established: 0,
+                data: &Default::default(),
+            }));
+            let info = Info {
+                public_key: keypair.public(),
+                protocol_version: "a".to_string(),
+                agent_version: "b".to_string(),
+                listen_addrs: Vec::new(),
+                protocols: Vec::new(),
+                observed_addr: "/ip4/9.9.9.9/tcp/1".parse().unwrap(),
+                metadata: Default::default(),
+            };
+            behaviour.on_connection_handler_event(
+                peer_id,
+                connection_id,
+                handler::Event::Identified(info, None),
+            );
+            std::mem::take(&mut behaviour.events)
+                .into_iter()
+                .filter(|event| matches!(event, ToSwarm::NewExternalAddrCandidate(_)))
+                .count()
+        };
+
+        assert_eq!(
+            observe(&mut behaviour, ConnectionId::new_unchecked(0), &relayed),
+            0
+        );
+        assert_eq!(
+            observe(&mut behaviour, ConnectionId::new_unchecked(1), &direct),
+            1
+        );
+    }
 }
@@ -18,6 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::behaviour::RequestId;
 use crate::protocol::{Info, PushInfo, UpgradeError};
 use crate::{protocol, PROTOCOL_NAME, PUSH_PROTOCOL_NAME};
 use either::Either;
@@ -37,9 +38,11 @@ use libp2p_swarm::{
     SubstreamProtocol, SupportedProtocols,
 };
 use smallvec::SmallVec;
-use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet};
 use std::{task::Context, task::Poll, time::Duration};
 use tracing::Level;
+use web_time::Instant;
 
 const STREAM_TIMEOUT: Duration = Duration::from_secs(60);
 const MAX_CONCURRENT_STREAMS_PER_CONNECTION: usize = 10;
@@ -55,7 +58,7 @@ pub struct Handler {
     events: SmallVec<
         [ConnectionHandlerEvent<
             Either<ReadyUpgrade<StreamProtocol>, ReadyUpgrade<StreamProtocol>>,
-            (),
+            Option<RequestId>,
             Event,
         >; 4],
     >,
@@ -85,9 +88,20 @@ pub struct Handler {
     /// Address observed by or for the remote.
     observed_addr: Multiaddr,
 
+    /// Application-defined key-value pairs sent to the remote.
+    metadata: BTreeMap<String, Vec<u8>>,
+
+    /// The maximum number of pushes accepted from the remote within a window.
+    push_rate_limit: Option<(u32, Duration)>,
+    /// The times of the pushes accepted from the remote within the current window.
+    received_pushes: VecDeque<Instant>,
+
     /// Identify information about the remote peer.
     remote_info: Option<Info>,
 
+    /// Whether identify runs on the connection at all.
+    active: bool,
+
     local_supported_protocols: SupportedProtocols,
     remote_supported_protocols: HashSet<StreamProtocol>,
     external_addresses: HashSet<Multiaddr>,
@@ -97,21 +111,32 @@ pub struct Handler {
 #[derive(Debug)]
 pub enum InEvent {
     AddressesChanged(HashSet<Multiaddr>),
+    MetadataChanged(BTreeMap<String, Vec<u8>>),
     Push,
+    /// Identify the remote now, on behalf of the request with the given id.
+    Identify(RequestId),
 }
 
 /// Event produced by the `Handler`.
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum Event {
-    /// We obtained identification information from the remote.
-    Identified(Info),
+    /// We obtained identification information from the remote, in response to the given request
+    /// if it was explicitly requested.
+    Identified(Info, Option<RequestId>),
     /// We replied to an identification request from the remote.
     Identification,
     /// We actively pushed our identification information to the remote.
     IdentificationPushed(Info),
     /// Failed to identify the remote, or to reply to an identification request.
     IdentificationError(StreamUpgradeError<UpgradeError>),
+    /// Dropped a push of the remote exceeding the rate limit.
+    PushRateLimited,
+    /// The protocols supported by the remote changed since it was last identified.
+    ProtocolsChanged {
+        added: HashSet<StreamProtocol>,
+        removed: HashSet<StreamProtocol>,
+    },
 }
 
 impl Handler {
@@ -139,13 +164,68 @@ impl Handler {
             protocol_version,
             agent_version,
             observed_addr,
+            metadata: BTreeMap::new(),
+            push_rate_limit: None,
+            received_pushes: VecDeque::new(),
             local_supported_protocols: SupportedProtocols::default(),
             remote_supported_protocols: HashSet::default(),
             remote_info: Default::default(),
+            active: true,
             external_addresses,
         }
     }
 
+    /// Sends the given application-defined key-value pairs to the remote.
+    pub(crate) fn with_metadata(mut self, metadata: BTreeMap<String, Vec<u8>>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Accepts at most the given number of pushes from the remote within the given window.
+    pub(crate) fn with_push_rate_limit(mut self, push_rate_limit: Option<(u32, Duration)>) -> Self {
+        self.push_rate_limit = push_rate_limit;
+        self
+    }
+
+    /// Starts from the given information of the remote, identified recently on another
+    /// connection, instead of identifying it right away. The remote is identified again after
+    /// the interval.
+    pub(crate) fn with_cached_info(mut self, info: Option<Info>) -> Self {
+        if let Some(info) = info {
+            self.trigger_next_identify = Delay::new(self.interval);
+            self.handle_incoming_info(&info);
+        }
+        self
+    }
+
+    /// Neither identifies the remote nor answers its requests and pushes unless active.
+    pub(crate) fn with_active(mut self, active: bool) -> Self {
+        self.active = active;
+        self
+    }
+
+    /// Records a push of the remote, returning whether it is within the rate limit.
+    fn accept_push(&mut self) -> bool {
+        let Some((max_pushes, window)) = self.push_rate_limit else {
+            return true;
+        };
+
+        let now = Instant::now();
+        while self
+            .received_pushes
+            .front()
+            .is_some_and(|received| now.duration_since(*received) >= window)
+        {
+            self.received_pushes.pop_front();
+        }
+        if self.received_pushes.len() >= max_pushes as usize {
+            return false;
+        }
+        self.received_pushes.push_back(now);
+
+        true
+    }
+
     fn on_fully_negotiated_inbound(
         &mut self,
         FullyNegotiatedInbound {
@@ -155,6 +235,14 @@ impl Handler {
             <Self as ConnectionHandler>::InboundOpenInfo,
         >,
     ) {
+        if !self.active {
+            tracing::debug!(
+                peer=%self.remote_peer_id,
+                "Dropping inbound identify stream on inactive connection"
+            );
+            return;
+        }
+
         match output {
             future::Either::Left(stream) => {
                 let info = self.build_info();
@@ -172,6 +260,17 @@ impl Handler {
                 }
             }
             future::Either::Right(stream) => {
+                if !self.accept_push() {
+                    tracing::debug!(
+                        peer=%self.remote_peer_id,
+                        "Dropping inbound identify push stream exceeding the rate limit"
+                    );
+                    self.events.push(ConnectionHandlerEvent::NotifyBehaviour(
+                        Event::PushRateLimited,
+                    ));
+                    return;
+                }
+
                 if self
                     .active_streams
                     .try_push(protocol::recv_push(stream).map_ok(Success::ReceivedIdentifyPush))
@@ -188,7 +287,8 @@ impl Handler {
     fn on_fully_negotiated_outbound(
         &mut self,
         FullyNegotiatedOutbound {
-            protocol: output, ..
+            protocol: output,
+            info: request_id,
         }: FullyNegotiatedOutbound<
             <Self as ConnectionHandler>::OutboundProtocol,
             <Self as ConnectionHandler>::OutboundOpenInfo,
@@ -198,7 +298,10 @@ impl Handler {
             future::Either::Left(stream) => {
                 if self
                     .active_streams
-                    .try_push(protocol::recv_identify(stream).map_ok(Success::ReceivedIdentify))
+                    .try_push(
+                        protocol::recv_identify(stream)
+                            .map_ok(move |info| Success::ReceivedIdentify(info, request_id)),
+                    )
                     .is_err()
                 {
                     tracing::warn!("Dropping outbound identify stream because we are at capacity");
@@ -230,16 +333,27 @@ impl Handler {
             listen_addrs: Vec::from_iter(self.external_addresses.iter().cloned()),
             protocols: Vec::from_iter(self.local_supported_protocols.iter().cloned()),
             observed_addr: self.observed_addr.clone(),
+            metadata: self.metadata.clone(),
         }
     }
 
     fn handle_incoming_info(&mut self, info: &Info) {
-        self.remote_info.replace(info.clone());
+        let previously_identified = self.remote_info.replace(info.clone()).is_some();
 
-        self.update_supported_protocols_for_remote(info);
+        let (added, removed) = self.update_supported_protocols_for_remote(info);
+        if previously_identified && (!added.is_empty() || !removed.is_empty()) {
+            self.events.push(ConnectionHandlerEvent::NotifyBehaviour(
+                Event::ProtocolsChanged { added, removed },
+            ));
+        }
     }
 
-    fn update_supported_protocols_for_remote(&mut self, remote_info: &Info) {
+    /// Reports the changes of the protocols supported by the remote to the connection, returning
+    /// the added and the removed protocols.
+    fn update_supported_protocols_for_remote(
+        &mut self,
+        remote_info: &Info,
+    ) -> (HashSet<StreamProtocol>, HashSet<StreamProtocol>) {
         let new_remote_protocols = HashSet::from_iter(remote_info.protocols.clone());
 
         let remote_added_protocols = new_remote_protocols
@@ -255,18 +369,20 @@ impl Handler {
         if !remote_added_protocols.is_empty() {
             self.events
                 .push(ConnectionHandlerEvent::ReportRemoteProtocols(
-                    ProtocolSupport::Added(remote_added_protocols),
+                    ProtocolSupport::Added(remote_added_protocols.clone()),
                 ));
         }
 
         if !remote_removed_protocols.is_empty() {
             self.events
                 .push(ConnectionHandlerEvent::ReportRemoteProtocols(
-                    ProtocolSupport::Removed(remote_removed_protocols),
+                    ProtocolSupport::Removed(remote_removed_protocols.clone()),
                 ));
         }
 
         self.remote_supported_protocols = new_remote_protocols;
+
+        (remote_added_protocols, remote_removed_protocols)
     }
 
     fn local_protocols_to_string(&mut self) -> String {
@@ -284,7 +400,7 @@ impl ConnectionHandler for Handler {
     type InboundProtocol =
         SelectUpgrade<ReadyUpgrade<StreamProtocol>, ReadyUpgrade<StreamProtocol>>;
     type OutboundProtocol = Either<ReadyUpgrade<StreamProtocol>, ReadyUpgrade<StreamProtocol>>;
-    type OutboundOpenInfo = ();
+    type OutboundOpenInfo = Option<RequestId>;
     type InboundOpenInfo = ();
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
@@ -302,12 +418,24 @@ impl ConnectionHandler for Handler {
             InEvent::AddressesChanged(addresses) => {
                 self.external_addresses = addresses;
             }
+            InEvent::MetadataChanged(metadata) => {
+                self.metadata = metadata;
+            }
             InEvent::Push => {
                 self.events
                     .push(ConnectionHandlerEvent::OutboundSubstreamRequest {
                         protocol: SubstreamProtocol::new(
                             Either::Right(ReadyUpgrade::new(PUSH_PROTOCOL_NAME)),
-                            (),
+                            None,
+                        ),
+                    });
+            }
+            InEvent::Identify(request_id) => {
+                self.events
+                    .push(ConnectionHandlerEvent::OutboundSubstreamRequest {
+                        protocol: SubstreamProtocol::new(
+                            Either::Left(ReadyUpgrade::new(PROTOCOL_NAME)),
+                            Some(request_id),
                         ),
                     });
             }
@@ -324,23 +452,27 @@ impl ConnectionHandler for Handler {
         }
 
         // Poll the future that fires when we need to identify the node again.
+        if !self.active {
+            return Poll::Pending;
+        }
         if let Poll::Ready(()) = self.trigger_next_identify.poll_unpin(cx) {
             self.trigger_next_identify.reset(self.interval);
             let event = ConnectionHandlerEvent::OutboundSubstreamRequest {
                 protocol: SubstreamProtocol::new(
                     Either::Left(ReadyUpgrade::new(PROTOCOL_NAME)),
-                    (),
+                    None,
                 ),
             };
             return Poll::Ready(event);
         }
 
         match self.active_streams.poll_unpin(cx) {
-            Poll::Ready(Ok(Ok(Success::ReceivedIdentify(remote_info)))) => {
+            Poll::Ready(Ok(Ok(Success::ReceivedIdentify(remote_info, request_id)))) => {
                 self.handle_incoming_info(&remote_info);
 
                 return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(Event::Identified(
                     remote_info,
+                    request_id,
                 )));
             }
             Poll::Ready(Ok(Ok(Success::SentIdentifyPush(info)))) => {
@@ -359,7 +491,7 @@ impl ConnectionHandler for Handler {
                     self.handle_incoming_info(&info);
 
                     return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
-                        Event::Identified(info),
+                        Event::Identified(info, None),
                     ));
                 };
             }
@@ -424,7 +556,7 @@ impl ConnectionHandler for Handler {
                         .push(ConnectionHandlerEvent::OutboundSubstreamRequest {
                             protocol: SubstreamProtocol::new(
                                 Either::Right(ReadyUpgrade::new(PUSH_PROTOCOL_NAME)),
-                                (),
+                                None,
                             ),
                         });
                 }
@@ -436,7 +568,67 @@ impl ConnectionHandler for Handler {
 
 enum Success {
     SentIdentify,
-    ReceivedIdentify(Info),
+    ReceivedIdentify(Info, Option<RequestId>),
     SentIdentifyPush(Info),
     ReceivedIdentifyPush(PushInfo),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_identity::Keypair;
+
+    #[test]
+    fn reports_protocol_changes_of_identified_remote() {
+        let keypair = Keypair::generate_ed25519();
+        let mut handler = Handler::new(
+            Duration::from_secs(60),
+            keypair.public().to_peer_id(),
+            keypair.public(),
+            "a".to_string(),
+            "b".to_string(),
+            "/memory/1".parse().unwrap(),
+            HashSet::new(),
+        );
+        let info = |protocols: &[&'static str]| Info {
+            public_key: keypair.public(),
+            protocol_version: "a".to_string(),
+            agent_version: "b".to_string(),
+            listen_addrs: Vec::new(),
+            protocols: protocols.iter().map(|p| StreamProtocol::new(p)).collect(),
+            observed_addr: "/memory/2".parse().unwrap(),
+            metadata: Default::default(),
+        };
+        let protocols_changed = |handler: &Handler| {
+            handler
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    ConnectionHandlerEvent::NotifyBehaviour(Event::ProtocolsChanged {
+                        added,
+                        removed,
+                    }) => Some((added.clone(), removed.clone())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The first identification is no change.
+        handler.handle_incoming_info(&info(&["/a", "/b"]));
+        assert!(protocols_changed(&handler).is_empty());
+
+        handler.events.clear();
+        handler.handle_incoming_info(&info(&["/b", "/c"]));
+        assert_eq!(
+            protocols_changed(&handler),
+            vec![(
+                HashSet::from([StreamProtocol::new("/c")]),
+                HashSet::from([StreamProtocol::new("/a")])
+            )]
+        );
+
+        handler.events.clear();
+        handler.handle_incoming_info(&info(&["/b", "/c"]));
+        assert!(protocols_changed(&handler).is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::Info;
+use libp2p_identity::PeerId;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use web_time::Instant;
+
+/// The latest information of the identified peers, expiring after a TTL, see
+/// [`Config::with_info_cache_ttl`](crate::Config::with_info_cache_ttl).
+#[derive(Debug)]
+pub(crate) struct InfoCache {
+    entries: LruCache<PeerId, (Info, Instant)>,
+    ttl: Duration,
+}
+
+impl InfoCache {
+    pub(crate) fn new(size: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            entries: LruCache::new(size),
+            ttl,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, peer_id: PeerId, info: Info, now: Instant) {
+        self.entries.put(peer_id, (info, now));
+    }
+
+    /// The information of the given peer, unless it expired.
+    pub(crate) fn get(&self, peer_id: &PeerId, now: Instant) -> Option<&Info> {
+        let (info, identified) = self.entries.peek(peer_id)?;
+
+        (now.duration_since(*identified) < self.ttl).then_some(info)
+    }
+
+    pub(crate) fn remove(&mut self, peer_id: &PeerId) -> Option<Info> {
+        self.entries.pop(peer_id).map(|(info, _)| info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_identity::Keypair;
+
+    #[test]
+    fn info_expires_after_ttl() {
+        let keypair = Keypair::generate_ed25519();
+        let info = Info {
+            public_key: keypair.public(),
+            protocol_version: "a".to_string(),
+            agent_version: "b".to_string(),
+            listen_addrs: Vec::new(),
+            protocols: Vec::new(),
+            observed_addr: "/memory/1".parse().unwrap(),
+            metadata: Default::default(),
+        };
+        let ttl = Duration::from_secs(60);
+        let mut cache = InfoCache::new(NonZeroUsize::new(1).unwrap(), ttl);
+        let now = Instant::now();
+
+        let peer = keypair.public().to_peer_id();
+        cache.insert(peer, info.clone(), now);
+        assert!(cache.get(&peer, now + ttl / 2).is_some());
+        assert!(cache.get(&peer, now + ttl).is_none());
+
+        // The least recently identified peer is evicted.
+        cache.insert(PeerId::random(), info, now);
+        assert!(cache.get(&peer, now).is_none());
+    }
+}
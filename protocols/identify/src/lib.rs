@@ -41,15 +41,22 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
-pub use self::behaviour::{Behaviour, Config, Event};
+pub use self::behaviour::{
+    Behaviour, Config, Event, PushRateLimitPolicy, RelayedConnectionPolicy, RequestId,
+};
+#[cfg(feature = "peer-store")]
+pub use self::peer_store::update_peer_store;
 pub use self::protocol::{Info, UpgradeError, PROTOCOL_NAME, PUSH_PROTOCOL_NAME};
 
 mod behaviour;
 mod handler;
+mod info_cache;
+#[cfg(feature = "peer-store")]
+mod peer_store;
 mod protocol;
 
 mod proto {
     #![allow(unreachable_pub)]
     include!("generated/mod.rs");
-    pub(crate) use self::structs::Identify;
+    pub(crate) use self::structs::{Identify, Metadata};
 }
@@ -0,0 +1,73 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::Info;
+use libp2p_identity::PeerId;
+use libp2p_peer_store::Behaviour as PeerStore;
+
+/// Records the listen addresses and protocols of an identified peer in a
+/// [`libp2p_peer_store::Behaviour`], to be called with the information of every
+/// [`Event::Received`](crate::Event::Received).
+///
+/// The listen addresses of the peer are refreshed in the store, its protocols replace the ones
+/// previously recorded.
+pub fn update_peer_store(store: &mut PeerStore, peer_id: PeerId, info: &Info) {
+    for address in &info.listen_addrs {
+        store.add_address(peer_id, address.clone());
+    }
+    store.set_protocols(peer_id, info.protocols.iter().cloned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_identity::Keypair;
+    use libp2p_peer_store::Config;
+    use libp2p_swarm::StreamProtocol;
+
+    #[test]
+    fn identified_addresses_and_protocols_are_recorded() {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+        let address: libp2p_core::Multiaddr = "/memory/1".parse().unwrap();
+        let protocol = StreamProtocol::new("/ipfs/ping/1.0.0");
+        let info = Info {
+            public_key: keypair.public(),
+            protocol_version: "a".to_string(),
+            agent_version: "b".to_string(),
+            listen_addrs: vec![address.clone()],
+            protocols: vec![protocol.clone()],
+            observed_addr: "/memory/2".parse().unwrap(),
+            metadata: Default::default(),
+        };
+        let mut store = PeerStore::new(Config::default());
+
+        update_peer_store(&mut store, peer_id, &info);
+
+        assert_eq!(
+            store.addresses_of_peer(&peer_id).collect::<Vec<_>>(),
+            [&address]
+        );
+        assert_eq!(
+            store.protocols_of_peer(&peer_id).collect::<Vec<_>>(),
+            [&protocol]
+        );
+    }
+}
@@ -25,6 +25,7 @@ use libp2p_core::{multiaddr, Multiaddr};
 use libp2p_identity as identity;
 use libp2p_identity::PublicKey;
 use libp2p_swarm::StreamProtocol;
+use std::collections::BTreeMap;
 use std::io;
 use thiserror::Error;
 
@@ -51,6 +52,10 @@ pub struct Info {
     pub protocols: Vec<StreamProtocol>,
     /// Address observed by or for the remote.
     pub observed_addr: Multiaddr,
+    /// Application-defined key-value pairs, see [`Config::with_metadata`](crate::Config::with_metadata).
+    ///
+    /// Empty for peers not sending any.
+    pub metadata: BTreeMap<String, Vec<u8>>,
 }
 
 impl Info {
@@ -73,6 +78,9 @@ impl Info {
         if let Some(observed_addr) = info.observed_addr {
             self.observed_addr = observed_addr;
         }
+        if !info.metadata.is_empty() {
+            self.metadata = info.metadata;
+        }
     }
 }
 
@@ -86,6 +94,7 @@ pub struct PushInfo {
     pub listen_addrs: Vec<Multiaddr>,
     pub protocols: Vec<StreamProtocol>,
     pub observed_addr: Option<Multiaddr>,
+    pub metadata: BTreeMap<String, Vec<u8>>,
 }
 
 pub(crate) async fn send_identify<T>(io: T, info: Info) -> Result<Info, UpgradeError>
@@ -105,6 +114,14 @@ where
         listenAddrs: listen_addrs,
         observedAddr: Some(info.observed_addr.to_vec()),
         protocols: info.protocols.iter().map(|p| p.to_string()).collect(),
+        metadata: info
+            .metadata
+            .iter()
+            .map(|(key, value)| proto::Metadata {
+                key: Some(key.clone()),
+                value: Some(value.clone()),
+            })
+            .collect(),
     };
 
     let mut framed_io = FramedWrite::new(
@@ -186,6 +203,19 @@ fn parse_protocols(protocols: Vec<String>) -> Vec<StreamProtocol> {
         .collect()
 }
 
+fn parse_metadata(metadata: Vec<proto::Metadata>) -> BTreeMap<String, Vec<u8>> {
+    metadata
+        .into_iter()
+        .filter_map(|m| match m.key {
+            Some(key) => Some((key, m.value.unwrap_or_default())),
+            None => {
+                tracing::debug!("Received metadata without key from peer");
+                None
+            }
+        })
+        .collect()
+}
+
 fn parse_public_key(public_key: Option<Vec<u8>>) -> Option<PublicKey> {
     public_key.and_then(|key| match PublicKey::try_decode_protobuf(&key) {
         Ok(k) => Some(k),
@@ -225,6 +255,7 @@ impl TryFrom<proto::Identify> for Info {
             listen_addrs: parse_listen_addrs(msg.listenAddrs),
             protocols: parse_protocols(msg.protocols),
             observed_addr: parse_observed_addr(msg.observedAddr).unwrap_or(Multiaddr::empty()),
+            metadata: parse_metadata(msg.metadata),
         };
 
         Ok(info)
@@ -242,6 +273,7 @@ impl TryFrom<proto::Identify> for PushInfo {
             listen_addrs: parse_listen_addrs(msg.listenAddrs),
             protocols: parse_protocols(msg.protocols),
             observed_addr: parse_observed_addr(msg.observedAddr),
+            metadata: parse_metadata(msg.metadata),
         };
 
         Ok(info)
@@ -284,6 +316,7 @@ mod tests {
             observedAddr: None,
             protocolVersion: None,
             protocols: vec![],
+            metadata: vec![],
             publicKey: Some(
                 identity::Keypair::generate_ed25519()
                     .public()
@@ -228,6 +228,46 @@ async fn emits_unique_listen_addresses() {
     assert!(reported_addrs.contains(&(swarm2_peer_id, swarm2_tcp_listen_addr)));
 }
 
+#[async_std::test]
+async fn only_discloses_filtered_addresses() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public()))
+    });
+    let swarm1_peer_id = *swarm1.local_peer_id();
+    let mut swarm2 = Swarm::new_ephemeral(move |identity| {
+        identify::Behaviour::new(
+            identify::Config::new("a".to_string(), identity.public()).with_address_filter(
+                move |peer_id, _, addr| {
+                    *peer_id == swarm1_peer_id
+                        && addr.iter().any(|p| matches!(p, Protocol::Memory(_)))
+                },
+            ),
+        )
+    });
+
+    let (swarm2_mem_listen_addr, _) = swarm2.listen().with_memory_addr_external().await;
+    swarm1.connect(&mut swarm2).await;
+
+    let ([e1, e2], [_, _]): ([identify::Event; 2], [identify::Event; 2]) =
+        libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await;
+    let swarm1_received_info = [e1, e2]
+        .into_iter()
+        .find_map(|e| match e {
+            identify::Event::Received { info, .. } => Some(info),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(
+        swarm1_received_info.listen_addrs,
+        vec![swarm2_mem_listen_addr]
+    );
+}
+
 #[async_std::test]
 async fn identify_push() {
     let _ = tracing_subscriber::fmt()
@@ -280,6 +320,265 @@ async fn identify_push() {
     assert!(swarm1_received_info.listen_addrs.is_empty());
 }
 
+#[async_std::test]
+async fn pushes_exceeding_rate_limit_are_dropped() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(
+            identify::Config::new("a".to_string(), identity.public()).with_push_rate_limit(
+                1,
+                Duration::from_secs(60),
+                identify::PushRateLimitPolicy::Disconnect,
+            ),
+        )
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public()))
+    });
+    let swarm2_peer_id = *swarm2.local_peer_id();
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let ([_, _], [_, _]): ([identify::Event; 2], [identify::Event; 2]) =
+        libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await;
+
+    swarm2
+        .behaviour_mut()
+        .push(iter::once(*swarm1.local_peer_id()));
+    match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+        ([identify::Event::Received { .. }], [identify::Event::Pushed { .. }]) => {}
+        other => panic!("Unexpected events: {other:?}"),
+    };
+
+    // The second push within the window exceeds the rate limit.
+    swarm2
+        .behaviour_mut()
+        .push(iter::once(*swarm1.local_peer_id()));
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    let mut rate_limited = false;
+    loop {
+        match swarm1.next_swarm_event().await {
+            SwarmEvent::Behaviour(identify::Event::Received { .. }) => {
+                panic!("Push exceeding the rate limit was received")
+            }
+            SwarmEvent::Behaviour(identify::Event::PushRateLimited { peer_id, .. }) => {
+                assert_eq!(peer_id, swarm2_peer_id);
+                rate_limited = true;
+            }
+            SwarmEvent::ConnectionClosed { peer_id, .. } => {
+                assert_eq!(peer_id, swarm2_peer_id);
+                break;
+            }
+            _ => {}
+        }
+    }
+    assert!(rate_limited);
+}
+
+#[async_std::test]
+async fn listen_addr_changes_are_batched_into_one_push() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public()))
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(
+            identify::Config::new("a".to_string(), identity.public())
+                .with_push_listen_addr_updates(true)
+                .with_push_interval(Duration::from_millis(500)),
+        )
+    });
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let ([_, _], [_, _]): ([identify::Event; 2], [identify::Event; 2]) =
+        libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await;
+
+    for _ in 0..2 {
+        swarm2.listen_on(Protocol::Memory(0).into()).unwrap();
+    }
+
+    let swarm1_received_info = match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+        ([identify::Event::Received { info, .. }], [identify::Event::Pushed { .. }]) => info,
+        other => panic!("Unexpected events: {other:?}"),
+    };
+    assert_eq!(swarm1_received_info.listen_addrs.len(), 2);
+}
+
+#[async_std::test]
+async fn request_identify() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(
+            identify::Config::new("a".to_string(), identity.public())
+                .with_agent_version("b".to_string()),
+        )
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public()))
+    });
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    // First, let the periodic identify do its thing.
+    let ([_, _], [_, _]): ([identify::Event; 2], [identify::Event; 2]) =
+        libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await;
+
+    assert!(swarm2
+        .behaviour_mut()
+        .request_identify(&libp2p_identity::PeerId::random())
+        .is_none());
+
+    // Second, explicitly identify again.
+    let request_id = swarm2
+        .behaviour_mut()
+        .request_identify(swarm1.local_peer_id())
+        .unwrap();
+
+    let (received_id, info) = match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+        (
+            [identify::Event::Sent { .. }],
+            [identify::Event::Received {
+                request_id, info, ..
+            }],
+        ) => (request_id, info),
+        other => panic!("Unexpected events: {other:?}"),
+    };
+
+    assert_eq!(received_id, Some(request_id));
+    assert_eq!(info.agent_version, "b");
+}
+
+#[async_std::test]
+async fn reconnecting_peers_are_not_identified_within_cache_ttl() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(
+            identify::Config::new("a".to_string(), identity.public())
+                .with_agent_version("b".to_string())
+                .with_info_cache_ttl(Duration::from_secs(60)),
+        )
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(
+            identify::Config::new("a".to_string(), identity.public())
+                .with_info_cache_ttl(Duration::from_secs(60)),
+        )
+    });
+    let swarm1_peer_id = *swarm1.local_peer_id();
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let ([_, _], [_, _]): ([identify::Event; 2], [identify::Event; 2]) =
+        libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await;
+
+    swarm2.disconnect_peer_id(swarm1_peer_id).unwrap();
+    swarm2
+        .wait(|event| matches!(event, SwarmEvent::ConnectionClosed { .. }).then_some(()))
+        .await;
+    swarm1
+        .wait(|event| matches!(event, SwarmEvent::ConnectionClosed { .. }).then_some(()))
+        .await;
+    assert_eq!(
+        swarm2
+            .behaviour()
+            .cached_info(&swarm1_peer_id)
+            .map(|info| info.agent_version.as_str()),
+        Some("b")
+    );
+
+    // Neither peer identifies the other on reconnecting, so the first identification is the
+    // explicitly requested one.
+    swarm2.connect(&mut swarm1).await;
+    let request_id = swarm2
+        .behaviour_mut()
+        .request_identify(&swarm1_peer_id)
+        .unwrap();
+
+    match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+        (
+            [identify::Event::Sent { .. }],
+            [identify::Event::Received {
+                request_id: received_id,
+                ..
+            }],
+        ) => assert_eq!(received_id, Some(request_id)),
+        other => panic!("Unexpected events: {other:?}"),
+    }
+
+    assert!(swarm2
+        .behaviour_mut()
+        .remove_cached_info(&swarm1_peer_id)
+        .is_some());
+}
+
+#[async_std::test]
+async fn exchange_metadata() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut swarm1 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(identify::Config::new("a".to_string(), identity.public()))
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|identity| {
+        identify::Behaviour::new(
+            identify::Config::new("a".to_string(), identity.public())
+                .with_metadata("role", "relay"),
+        )
+    });
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let ([e1, e2], [_, _]): ([identify::Event; 2], [identify::Event; 2]) =
+        libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await;
+    let swarm1_received_info = [e1, e2]
+        .into_iter()
+        .find_map(|e| match e {
+            identify::Event::Received { info, .. } => Some(info),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        swarm1_received_info.metadata.get("role").map(Vec::as_slice),
+        Some(b"relay".as_slice())
+    );
+
+    // Changed metadata is pushed to connected peers.
+    swarm2.behaviour_mut().set_metadata("region", "eu");
+
+    let swarm1_received_info = match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+        ([identify::Event::Received { info, .. }], [identify::Event::Pushed { .. }]) => info,
+        other => panic!("Unexpected events: {other:?}"),
+    };
+    assert_eq!(swarm1_received_info.metadata.len(), 2);
+    assert_eq!(
+        swarm1_received_info
+            .metadata
+            .get("region")
+            .map(Vec::as_slice),
+        Some(b"eu".as_slice())
+    );
+}
+
 #[async_std::test]
 async fn discover_peer_after_disconnect() {
     let _ = tracing_subscriber::fmt()
@@ -19,14 +19,19 @@
 // DEALINGS IN THE SOFTWARE.
 
 use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
 use smallvec::SmallVec;
 use std::fmt;
+use std::time::Duration;
+use web_time::Instant;
 
 /// A non-empty list of (unique) addresses of a peer in the routing table.
 /// Every address must be a fully-qualified /p2p address.
 #[derive(Clone)]
 pub struct Addresses {
     addrs: SmallVec<[Multiaddr; 6]>,
+    /// The time each address was last confirmed, in the order of `addrs`.
+    confirmed: SmallVec<[Option<Instant>; 6]>,
 }
 
 #[allow(clippy::len_without_is_empty)]
@@ -35,7 +40,9 @@ impl Addresses {
     pub fn new(addr: Multiaddr) -> Addresses {
         let mut addrs = SmallVec::new();
         addrs.push(addr);
-        Addresses { addrs }
+        let mut confirmed = SmallVec::new();
+        confirmed.push(None);
+        Addresses { addrs, confirmed }
     }
 
     /// Gets a reference to the first address in the list.
@@ -53,6 +60,33 @@ impl Addresses {
         self.addrs.len()
     }
 
+    /// Returns the time the given address was last confirmed, i.e. the last time a
+    /// connection to the peer was established via the address.
+    ///
+    /// Returns `None` if the address was never confirmed or is not in the list.
+    pub fn last_confirmed(&self, addr: &Multiaddr) -> Option<Instant> {
+        let pos = self.addrs.iter().position(|a| a == addr)?;
+        self.confirmed[pos]
+    }
+
+    /// Returns an iterator over the addresses together with the time they were last confirmed.
+    pub fn iter_with_last_confirmed(&self) -> impl Iterator<Item = (&Multiaddr, Option<Instant>)> {
+        self.addrs.iter().zip(self.confirmed.iter().copied())
+    }
+
+    /// Records that a connection to the peer was established via the given address.
+    ///
+    /// Returns false if the address is not in the list.
+    pub(crate) fn confirm(&mut self, addr: &Multiaddr, now: Instant) -> bool {
+        match self.addrs.iter().position(|a| a == addr) {
+            Some(pos) => {
+                self.confirmed[pos] = Some(now);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Converts the addresses into a `Vec`.
     pub fn into_vec(self) -> Vec<Multiaddr> {
         self.addrs.into_vec()
@@ -74,8 +108,10 @@ impl Addresses {
 
         if let Some(pos) = self.addrs.iter().position(|a| a == addr) {
             self.addrs.remove(pos);
+            self.confirmed.remove(pos);
             if self.addrs.len() <= self.addrs.inline_size() {
                 self.addrs.shrink_to_fit();
+                self.confirmed.shrink_to_fit();
             }
         }
 
@@ -89,6 +125,7 @@ impl Addresses {
     pub fn insert(&mut self, addr: Multiaddr) -> bool {
         if self.addrs.iter().all(|a| *a != addr) {
             self.addrs.push(addr);
+            self.confirmed.push(None);
             true
         } else {
             false
@@ -100,8 +137,9 @@ impl Addresses {
     /// Returns true if the previous address was found and replaced with a clone
     /// of the new address, returns false otherwise.
     pub fn replace(&mut self, old: &Multiaddr, new: &Multiaddr) -> bool {
-        if let Some(a) = self.addrs.iter_mut().find(|a| *a == old) {
-            *a = new.clone();
+        if let Some(pos) = self.addrs.iter().position(|a| a == old) {
+            self.addrs[pos] = new.clone();
+            self.confirmed[pos] = None;
             return true;
         }
 
@@ -109,6 +147,42 @@ impl Addresses {
     }
 }
 
+/// An address of a peer in the routing table that was not confirmed recently.
+///
+/// See [`Behaviour::stale_addresses`](crate::Behaviour::stale_addresses).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleAddress {
+    pub peer: PeerId,
+    pub address: Multiaddr,
+    /// The time the address was last confirmed, `None` if it never was.
+    pub last_confirmed: Option<Instant>,
+}
+
+/// The distribution of the time since the addresses in the routing table were last confirmed.
+///
+/// See [`Behaviour::address_staleness`](crate::Behaviour::address_staleness).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressStaleness {
+    /// The number of addresses in the routing table.
+    pub num_addresses: usize,
+    /// The number of addresses that were never confirmed.
+    pub num_unconfirmed: usize,
+    /// The time since each confirmed address was last confirmed, in ascending order.
+    pub ages: Vec<Duration>,
+}
+
+impl AddressStaleness {
+    /// Returns the age below which the given fraction `q` (between 0 and 1) of the confirmed
+    /// addresses fall, e.g. the median age for `q = 0.5`.
+    ///
+    /// Returns `None` if no address was confirmed.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let last = self.ages.len().checked_sub(1)?;
+        let index = (q.clamp(0.0, 1.0) * last as f64).round() as usize;
+        Some(self.ages[index])
+    }
+}
+
 impl fmt::Debug for Addresses {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_list().entries(self.addrs.iter()).finish()
@@ -175,11 +249,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn confirmation_follows_its_address() {
+        let mut addresses = make_addresses([tcp_addr(1234), tcp_addr(4321), tcp_addr(5678)]);
+        let now = Instant::now();
+
+        assert!(addresses.confirm(&tcp_addr(4321), now));
+        assert!(addresses.confirm(&tcp_addr(5678), now + Duration::from_secs(1)));
+        assert!(!addresses.confirm(&tcp_addr(1111), now));
+        addresses.remove(&tcp_addr(1234)).unwrap();
+
+        assert_eq!(addresses.last_confirmed(&tcp_addr(4321)), Some(now));
+        assert_eq!(
+            addresses.last_confirmed(&tcp_addr(5678)),
+            Some(now + Duration::from_secs(1))
+        );
+
+        assert!(addresses.replace(&tcp_addr(4321), &tcp_addr(8765)));
+        assert_eq!(addresses.last_confirmed(&tcp_addr(8765)), None);
+    }
+
     /// Helper function to easily initialize Addresses struct with multiple addresses.
     fn make_addresses(addresses: impl IntoIterator<Item = Multiaddr>) -> Addresses {
-        Addresses {
-            addrs: SmallVec::from_iter(addresses),
-        }
+        let addrs = SmallVec::from_iter(addresses);
+        let confirmed = addrs.iter().map(|_| None).collect();
+        Addresses { addrs, confirmed }
     }
 
     /// Helper function to create a tcp Multiaddr with a specific port
@@ -22,24 +22,33 @@
 
 mod test;
 
-use crate::addresses::Addresses;
+use crate::addresses::{AddressStaleness, Addresses, StaleAddress};
 use crate::bootstrap;
 use crate::handler::{Handler, HandlerEvent, HandlerIn, RequestId};
 use crate::kbucket::{self, Distance, KBucketsTable, NodeStatus};
 use crate::protocol::{ConnectionType, KadPeer, ProtocolConfig};
-use crate::query::{Query, QueryConfig, QueryId, QueryPool, QueryPoolState};
+use crate::query::{
+    PeerLatencyFn, Query, QueryConfig, QueryId, QueryPool, QueryPoolState, QueryTracer,
+};
 use crate::record::{
     self,
     store::{self, RecordStore},
     ProviderRecord, Record,
 };
+use crate::reprovide::ReprovideStrategy;
+use crate::response_cache::FindNodeCache;
+use crate::routing_log::{RoutingChange, RoutingLog, RoutingTableStore};
+use crate::scorer::{PeerObservation, PeerScorer};
 use crate::K_VALUE;
 use crate::{jobs::*, protocol};
 use fnv::{FnvHashMap, FnvHashSet};
+use futures::FutureExt;
+use futures_timer::Delay;
 use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr, PeerInfo};
 use libp2p_identity::PeerId;
 use libp2p_swarm::behaviour::{
-    AddressChange, ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm,
+    AddressChange, ConnectionClosed, ConnectionEstablished, DialFailure, ExternalAddrConfirmed,
+    ExternalAddrExpired, FromSwarm,
 };
 use libp2p_swarm::{
     dial_opts::{self, DialOpts},
@@ -50,7 +59,9 @@ use libp2p_swarm::{
 use smallvec::SmallVec;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::io;
 use std::num::NonZeroUsize;
+use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
 use std::time::Duration;
 use std::vec;
@@ -75,6 +86,9 @@ pub struct Behaviour<TStore> {
     /// Configuration of [`RecordStore`] filtering.
     record_filtering: StoreInserts,
 
+    /// The peers records are placed at by [`Behaviour::put_record`].
+    record_placement: RecordPlacement,
+
     /// The currently active (i.e. in-progress) queries.
     queries: QueryPool<QueryInner>,
 
@@ -120,6 +134,57 @@ pub struct Behaviour<TStore> {
 
     /// Tracks the status of the current bootstrap.
     bootstrap_status: bootstrap::Status,
+
+    /// See [`Config::set_bootstrap_criteria`].
+    bootstrap_criteria: BootstrapCriteria,
+
+    /// The accumulated statistics of the steps of running bootstraps.
+    bootstrap_stats: FnvHashMap<QueryId, QueryStats>,
+
+    /// See [`Config::set_address_resolver`].
+    address_resolver: Option<AddressResolver>,
+
+    /// See [`Config::set_address_filter`].
+    address_filter: Option<AddressFilter>,
+
+    /// See [`Config::set_remember_resolved_addresses`].
+    remember_resolved_addresses: bool,
+
+    /// See [`Config::set_peer_scorer`].
+    peer_scorer: Option<Arc<dyn PeerScorer>>,
+
+    /// The observations of the connected peers, consulted by the [`PeerScorer`].
+    observed_peers: FnvHashMap<PeerId, PeerObservation>,
+
+    /// The addresses of connected peers whose insertion into the routing table
+    /// was rejected by the [`PeerScorer`], to be reconsidered on their next response.
+    deferred_inserts: FnvHashMap<PeerId, Multiaddr>,
+
+    /// See [`Config::set_bucket_refresh_budget`].
+    bucket_refresh_budget: Option<NonZeroUsize>,
+
+    /// See [`Config::set_max_pending_rpcs`].
+    max_pending_rpcs: Option<NonZeroUsize>,
+
+    /// See [`Config::set_num_closer_peers`].
+    num_closer_peers: Option<NonZeroUsize>,
+
+    /// See [`Config::set_adaptive_closer_peers`].
+    adaptive_closer_peers: bool,
+
+    /// See [`Config::set_find_node_cache_ttl`].
+    find_node_cache: FindNodeCache,
+
+    /// The interval and the delay of the current period of routing table liveness checks,
+    /// see [`Config::set_liveness_check_interval`].
+    liveness_check: Option<(Duration, Delay)>,
+
+    /// Whether the background activity is paused, see [`Behaviour::pause_background`].
+    background_paused: bool,
+
+    /// The log the changes of the routing table are persisted in, see
+    /// [`Behaviour::set_routing_table_store`].
+    routing_log: Option<RoutingLog>,
 }
 
 /// The configurable strategies for the insertion of peers
@@ -169,10 +234,34 @@ pub enum StoreInserts {
     FilterBoth,
 }
 
+/// The peers a record is stored at by [`Behaviour::put_record`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecordPlacement {
+    /// The record is stored at the closest peers to its key.
+    Closest,
+    /// The record is stored at the closest peers to its key and, in addition,
+    /// at all providers of the same key that are found during the lookup.
+    ///
+    /// The lookup for the closest peers is performed with `GET_PROVIDERS`
+    /// instead of `FIND_NODE` requests. This supports patterns where the
+    /// providers of a key act as authoritative replicas of its record.
+    ClosestAndProviders,
+}
+
+/// A function that resolves additional addresses of a peer.
+///
+/// See [`Config::set_address_resolver`].
+type AddressResolver = Arc<dyn Fn(&PeerId) -> Vec<Multiaddr> + Send + Sync + 'static>;
+
+/// A function that decides whether an address learned during a query may be dialed.
+///
+/// See [`Config::set_address_filter`].
+type AddressFilter = Arc<dyn Fn(&Multiaddr) -> bool + Send + Sync + 'static>;
+
 /// The configuration for the `Kademlia` behaviour.
 ///
 /// The configuration is consumed by [`Behaviour::new`].
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Config {
     kbucket_pending_timeout: Duration,
     query_config: QueryConfig,
@@ -181,12 +270,74 @@ pub struct Config {
     record_replication_interval: Option<Duration>,
     record_publication_interval: Option<Duration>,
     record_filtering: StoreInserts,
+    record_placement: RecordPlacement,
     provider_record_ttl: Option<Duration>,
     provider_publication_interval: Option<Duration>,
     kbucket_inserts: BucketInserts,
     caching: Caching,
     periodic_bootstrap_interval: Option<Duration>,
     automatic_bootstrap_throttle: Option<Duration>,
+    bootstrap_criteria: BootstrapCriteria,
+    address_resolver: Option<AddressResolver>,
+    address_filter: Option<AddressFilter>,
+    remember_resolved_addresses: bool,
+    peer_scorer: Option<Arc<dyn PeerScorer>>,
+    bucket_refresh_budget: Option<NonZeroUsize>,
+    max_pending_rpcs: Option<NonZeroUsize>,
+    num_closer_peers: Option<NonZeroUsize>,
+    adaptive_closer_peers: bool,
+    find_node_cache_ttl: Option<Duration>,
+    liveness_check_interval: Option<Duration>,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("kbucket_pending_timeout", &self.kbucket_pending_timeout)
+            .field("query_config", &self.query_config)
+            .field("protocol_config", &self.protocol_config)
+            .field("record_ttl", &self.record_ttl)
+            .field(
+                "record_replication_interval",
+                &self.record_replication_interval,
+            )
+            .field(
+                "record_publication_interval",
+                &self.record_publication_interval,
+            )
+            .field("record_filtering", &self.record_filtering)
+            .field("record_placement", &self.record_placement)
+            .field("provider_record_ttl", &self.provider_record_ttl)
+            .field(
+                "provider_publication_interval",
+                &self.provider_publication_interval,
+            )
+            .field("kbucket_inserts", &self.kbucket_inserts)
+            .field("caching", &self.caching)
+            .field(
+                "periodic_bootstrap_interval",
+                &self.periodic_bootstrap_interval,
+            )
+            .field(
+                "automatic_bootstrap_throttle",
+                &self.automatic_bootstrap_throttle,
+            )
+            .field("bootstrap_criteria", &self.bootstrap_criteria)
+            .field("address_resolver", &self.address_resolver.is_some())
+            .field("address_filter", &self.address_filter.is_some())
+            .field(
+                "remember_resolved_addresses",
+                &self.remember_resolved_addresses,
+            )
+            .field("peer_scorer", &self.peer_scorer.is_some())
+            .field("bucket_refresh_budget", &self.bucket_refresh_budget)
+            .field("max_pending_rpcs", &self.max_pending_rpcs)
+            .field("num_closer_peers", &self.num_closer_peers)
+            .field("adaptive_closer_peers", &self.adaptive_closer_peers)
+            .field("find_node_cache_ttl", &self.find_node_cache_ttl)
+            .field("liveness_check_interval", &self.liveness_check_interval)
+            .finish()
+    }
 }
 
 impl Default for Config {
@@ -224,12 +375,24 @@ impl Config {
             record_replication_interval: Some(Duration::from_secs(60 * 60)),
             record_publication_interval: Some(Duration::from_secs(22 * 60 * 60)),
             record_filtering: StoreInserts::Unfiltered,
+            record_placement: RecordPlacement::Closest,
             provider_publication_interval: Some(Duration::from_secs(12 * 60 * 60)),
             provider_record_ttl: Some(Duration::from_secs(48 * 60 * 60)),
             kbucket_inserts: BucketInserts::OnConnected,
             caching: Caching::Enabled { max_peers: 1 },
             periodic_bootstrap_interval: Some(Duration::from_secs(5 * 60)),
             automatic_bootstrap_throttle: Some(bootstrap::DEFAULT_AUTOMATIC_THROTTLE),
+            bootstrap_criteria: BootstrapCriteria::default(),
+            address_resolver: None,
+            address_filter: None,
+            remember_resolved_addresses: true,
+            peer_scorer: None,
+            bucket_refresh_budget: None,
+            max_pending_rpcs: None,
+            num_closer_peers: None,
+            adaptive_closer_peers: false,
+            find_node_cache_ttl: None,
+            liveness_check_interval: None,
         }
     }
 
@@ -256,6 +419,16 @@ impl Config {
         self
     }
 
+    /// Returns a copy of this configuration that only speaks the given protocol name.
+    #[allow(deprecated)]
+    pub(crate) fn with_protocol_name(&self, protocol_name: StreamProtocol) -> Self {
+        let mut config = self.clone();
+        config
+            .protocol_config
+            .set_protocol_names(vec![protocol_name]);
+        config
+    }
+
     /// Sets the timeout for a single query.
     ///
     /// > **Note**: A single query usually comprises at least as many requests
@@ -314,6 +487,9 @@ impl Config {
     ///
     /// `None` means records never expire.
     ///
+    /// Individual records may override the TTL with an earlier expiration,
+    /// see [`Record::with_ttl`].
+    ///
     /// Does not apply to provider records.
     pub fn set_record_ttl(&mut self, record_ttl: Option<Duration>) -> &mut Self {
         self.record_ttl = record_ttl;
@@ -329,6 +505,15 @@ impl Config {
         self
     }
 
+    /// Sets the peers records are placed at when published or republished.
+    ///
+    /// See [`RecordPlacement`] for the different values.
+    /// Defaults to [`RecordPlacement::Closest`].
+    pub fn set_record_placement(&mut self, placement: RecordPlacement) -> &mut Self {
+        self.record_placement = placement;
+        self
+    }
+
     /// Sets the (re-)replication interval for stored records.
     ///
     /// Periodic replication of stored records ensures that the records
@@ -383,7 +568,8 @@ impl Config {
     /// by the local node are re-published.
     ///
     /// `None` means that stored provider records are never automatically
-    /// re-published.
+    /// re-published, unless a strategy is set with
+    /// [`Behaviour::set_reprovide_strategy`].
     ///
     /// Must be significantly less than the provider record TTL.
     pub fn set_provider_publication_interval(&mut self, interval: Option<Duration>) -> &mut Self {
@@ -400,6 +586,31 @@ impl Config {
         self
     }
 
+    /// Sets the maximum number of concurrent inbound substreams per connection.
+    ///
+    /// When the limit is reached, an idle substream waiting to be reused by the
+    /// remote is closed in favour of the new one. If there is none, the new
+    /// substream is dropped.
+    ///
+    /// * Default to `32`.
+    pub fn set_max_inbound_streams(&mut self, num: usize) -> &mut Self {
+        self.protocol_config.set_max_inbound_streams(num);
+        self
+    }
+
+    /// Sets whether an outbound substream is kept open after a request
+    /// completed, to be reused for the next request to the same peer.
+    ///
+    /// This avoids the overhead of setting up a new substream for each of
+    /// several sequential requests to a peer. Idle substreams are closed
+    /// after 10 seconds.
+    ///
+    /// * Default to `false`.
+    pub fn set_reuse_outbound_streams(&mut self, reuse: bool) -> &mut Self {
+        self.protocol_config.set_reuse_outbound_streams(reuse);
+        self
+    }
+
     /// Sets the k-bucket insertion strategy for the Kademlia routing table.
     pub fn set_kbucket_inserts(&mut self, inserts: BucketInserts) -> &mut Self {
         self.kbucket_inserts = inserts;
@@ -426,6 +637,254 @@ impl Config {
         self
     }
 
+    /// Sets the criteria the routing table must meet at the end of a bootstrap
+    /// for the bootstrap to be considered successful.
+    ///
+    /// See [`Event::BootstrapFinished`].
+    ///
+    /// * Default to at least `1` peer in at least `1` bucket.
+    pub fn set_bootstrap_criteria(&mut self, criteria: BootstrapCriteria) -> &mut Self {
+        self.bootstrap_criteria = criteria;
+        self
+    }
+
+    /// Sets the maximum number of buckets refreshed by a single bootstrap.
+    ///
+    /// After the lookup of the local key, a bootstrap refreshes the buckets farther away
+    /// than the bucket of the closest neighbour, starting with the buckets whose last
+    /// successful lookup lies furthest in the past (or which have never been looked up)
+    /// and, among those, the ones with the fewest entries. With a budget, only the given
+    /// number of stalest buckets is refreshed, spreading the cost of keeping the routing
+    /// table healthy over multiple bootstrap intervals.
+    ///
+    /// * Default to `None`, i.e. all eligible buckets are refreshed.
+    pub fn set_bucket_refresh_budget(&mut self, budget: Option<NonZeroUsize>) -> &mut Self {
+        self.bucket_refresh_budget = budget;
+        self
+    }
+
+    /// Sets the maximum number of requests of a query that wait for a connection
+    /// to their peer at the same time.
+    ///
+    /// Requests to peers that are not connected are sent once the connection to the peer
+    /// is established. When many dials are in flight, e.g. because of unreachable peers,
+    /// the requests to the peers furthest from the target of the query are dropped in
+    /// favour of closer ones and count as failed, letting the query move on to other peers.
+    /// The time requests spend waiting for a connection is reported in the [`QueryStats`].
+    ///
+    /// * Default to `None`, i.e. the number of waiting requests is unbounded.
+    pub fn set_max_pending_rpcs(&mut self, max: Option<NonZeroUsize>) -> &mut Self {
+        self.max_pending_rpcs = max;
+        self
+    }
+
+    /// Sets the number of closer peers returned in responses to inbound
+    /// `FIND_NODE`, `GET_PROVIDERS` and `GET_VALUE` requests.
+    ///
+    /// * Default to `None`, i.e. the replication factor.
+    pub fn set_num_closer_peers(&mut self, num: Option<NonZeroUsize>) -> &mut Self {
+        self.num_closer_peers = num;
+        self
+    }
+
+    /// Sets whether the closer peers returned in responses to inbound requests
+    /// adapt to the distance of the requester to the key.
+    ///
+    /// If enabled, only peers closer to the key than the requester itself are
+    /// returned, since the requester is expected to know its own neighbourhood.
+    /// Lookups of the requester's own key are answered in full.
+    ///
+    /// * Default to `false`.
+    pub fn set_adaptive_closer_peers(&mut self, adaptive: bool) -> &mut Self {
+        self.adaptive_closer_peers = adaptive;
+        self
+    }
+
+    /// Sets the time for which the closer peers returned for inbound `FIND_NODE`
+    /// requests are cached.
+    ///
+    /// Nodes receiving many identical requests, e.g. for their own key when used for
+    /// bootstrapping, then serve repeated lookups of a key from the cache instead of
+    /// the routing table. The cache is cleared whenever the routing table changes, so
+    /// a short TTL of about a second suffices.
+    ///
+    /// * Default to `None`, i.e. responses are not cached.
+    pub fn set_find_node_cache_ttl(&mut self, ttl: Option<Duration>) -> &mut Self {
+        self.find_node_cache_ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum encoded size of responses to inbound requests.
+    ///
+    /// Responses exceeding the size are truncated, removing the closer peers
+    /// furthest from the key first, followed by provider peers. Records are
+    /// never truncated.
+    ///
+    /// * Default to `None`, i.e. responses are not truncated.
+    pub fn set_max_response_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.protocol_config.set_max_response_size(size);
+        self
+    }
+
+    /// Sets the encoded size of messages beyond which they are compressed.
+    ///
+    /// When set, a compressed variant of each protocol name, suffixed with
+    /// `/deflate`, is negotiated with peers supporting it. On such streams,
+    /// messages exceeding the threshold, e.g. carrying large records or
+    /// provider lists, are deflate-compressed. Peers only speaking the plain
+    /// protocol are still served uncompressed.
+    ///
+    /// * Default to `None`, i.e. messages are never compressed.
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) -> &mut Self {
+        self.protocol_config.set_compression_threshold(threshold);
+        self
+    }
+
+    /// Sets the interval on which the liveness of peers in the routing table is checked.
+    ///
+    /// On every check, the least-recently connected peer of each bucket is sent a
+    /// `FIND_NODE` request, unless it is currently connected. Peers that cannot be
+    /// dialed or fail to respond within the query timeout are removed from the
+    /// routing table, keeping it fresh on networks where peers leave silently.
+    ///
+    /// * Default to `None`, i.e. liveness checks are disabled.
+    pub fn set_liveness_check_interval(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.liveness_check_interval = interval;
+        self
+    }
+
+    /// Sets a function that supplies additional addresses of a peer on demand.
+    ///
+    /// The function is invoked whenever an ongoing query needs to dial a peer, e.g. a peer
+    /// that was reported by another node without (reachable) addresses. The returned
+    /// addresses are tried in addition to the ones found in the routing table and the ones
+    /// learned during queries, and are remembered by the query for the remainder of its
+    /// lifetime, unless disabled via [`Config::set_remember_resolved_addresses`]. This allows
+    /// to integrate external sources of addresses such as a private database or DNS.
+    ///
+    /// Note that the addresses of a peer store composed into the same
+    /// [`Swarm`](libp2p_swarm::Swarm), e.g. `libp2p-peer-store`, are already added to the dials
+    /// of queries by the [`Swarm`](libp2p_swarm::Swarm), which removes duplicate addresses.
+    ///
+    /// The function is called from within the [`NetworkBehaviour`] and must therefore not block.
+    ///
+    /// Defaults to `None`, i.e. only addresses known to the behaviour are used.
+    pub fn set_address_resolver<F>(&mut self, resolver: F) -> &mut Self
+    where
+        F: Fn(&PeerId) -> Vec<Multiaddr> + Send + Sync + 'static,
+    {
+        self.address_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Sets a function that decides whether an address learned during a query may be dialed.
+    ///
+    /// The function is applied to the addresses of peers reported by other nodes in
+    /// responses to queries as well as to the addresses supplied by the function set via
+    /// [`Config::set_address_resolver`]. Addresses for which it returns `false` are
+    /// discarded before they are handed to the [`Swarm`](libp2p_swarm::Swarm) for dialing.
+    /// This allows to e.g. drop private IP ranges, relayed addresses or addresses of
+    /// unsupported transports, avoiding dials to addresses that are not routable anyway.
+    ///
+    /// Addresses of peers in the routing table are not subject to the filter.
+    ///
+    /// Defaults to `None`, i.e. all addresses are accepted.
+    pub fn set_address_filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&Multiaddr) -> bool + Send + Sync + 'static,
+    {
+        self.address_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sets whether the addresses supplied by the function set via
+    /// [`Config::set_address_resolver`] are remembered by the queries waiting for the peer.
+    ///
+    /// If disabled, the resolver is consulted again on every dial instead, which suits
+    /// resolvers whose addresses change during a query, e.g. ones reading from a cache.
+    ///
+    /// Defaults to `true`.
+    pub fn set_remember_resolved_addresses(&mut self, remember: bool) -> &mut Self {
+        self.remember_resolved_addresses = remember;
+        self
+    }
+
+    /// Sets a [`PeerScorer`] that is consulted before a newly connected peer is inserted
+    /// into the routing table.
+    ///
+    /// This makes it harder for an attacker to fill the buckets of the local node with
+    /// its own nodes by advertising them in responses to queries, e.g. by requiring peers
+    /// to have responded to a request ([`RespondedToQuery`](crate::RespondedToQuery)) or
+    /// to have been connected for some time ([`MinimumUptime`](crate::MinimumUptime)).
+    /// Rejected peers are reconsidered whenever they respond to a request.
+    ///
+    /// Only applies to [`BucketInserts::OnConnected`].
+    ///
+    /// Defaults to `None`, i.e. all peers are inserted.
+    pub fn set_peer_scorer<S>(&mut self, scorer: S) -> &mut Self
+    where
+        S: PeerScorer,
+    {
+        self.peer_scorer = Some(Arc::new(scorer));
+        self
+    }
+
+    /// Sets a function that supplies the observed latency of a peer, if known.
+    ///
+    /// Iterative queries use the returned latencies, e.g. round-trip times measured by
+    /// `libp2p-ping` or response times of past queries, to prefer faster peers among the
+    /// not yet contacted peers that are equally close to the target, i.e. that fall into
+    /// the same bucket w.r.t. the target. Peers with an unknown latency are only
+    /// contacted first if no latency is known for any equally close peer.
+    ///
+    /// The function is called from within the [`NetworkBehaviour`] and must therefore not block.
+    ///
+    /// Defaults to `None`, i.e. peers are always contacted in order of their distance.
+    pub fn set_peer_latency_fn<F>(&mut self, latency_fn: F) -> &mut Self
+    where
+        F: Fn(&PeerId) -> Option<Duration> + Send + Sync + 'static,
+    {
+        self.query_config.latency_fn = Some(PeerLatencyFn::new(latency_fn));
+        self
+    }
+
+    /// Sets the number of times a failed request to a peer is retried within a query
+    /// before the peer is considered failed for that query.
+    ///
+    /// A request is only retried if it failed on an established connection, e.g.
+    /// because the stream was dropped, which is common on lossy networks.
+    /// Peers that cannot be dialed or disconnect are never retried.
+    ///
+    /// * Default to `0`, i.e. failed requests are never retried.
+    pub fn set_max_rpc_retries(&mut self, retries: usize) -> &mut Self {
+        self.query_config.max_rpc_retries = retries;
+        self
+    }
+
+    /// Sets the delay before retrying a failed request to a peer,
+    /// see [`Config::set_max_rpc_retries`].
+    ///
+    /// The delay grows linearly with the number of retries to the same peer.
+    ///
+    /// * Default to `500` ms.
+    pub fn set_rpc_retry_backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.query_config.rpc_retry_backoff = backoff;
+        self
+    }
+
+    /// Sets a [`QueryTracer`] that is informed about every state transition of every query,
+    /// e.g. whenever a peer is contacted, responds or fails, allowing to reconstruct full
+    /// lookup traces.
+    ///
+    /// Defaults to `None`.
+    pub fn set_query_tracer<T>(&mut self, tracer: T) -> &mut Self
+    where
+        T: QueryTracer,
+    {
+        self.query_config.tracer = Some(Arc::new(tracer));
+        self
+    }
+
     /// Sets the time to wait before calling [`Behaviour::bootstrap`] after a new peer is inserted in the routing table.
     /// This prevent cascading bootstrap requests when multiple peers are inserted into the routing table "at the same time".
     /// This also allows to wait a little bit for other potential peers to be inserted into the routing table before
@@ -487,6 +946,7 @@ where
             kbucket_inserts: config.kbucket_inserts,
             protocol_config: config.protocol_config,
             record_filtering: config.record_filtering,
+            record_placement: config.record_placement,
             queued_events: VecDeque::with_capacity(config.query_config.replication_factor.get()),
             listen_addresses: Default::default(),
             queries: QueryPool::new(config.query_config),
@@ -505,6 +965,24 @@ where
                 config.periodic_bootstrap_interval,
                 config.automatic_bootstrap_throttle,
             ),
+            bootstrap_criteria: config.bootstrap_criteria,
+            bootstrap_stats: Default::default(),
+            address_resolver: config.address_resolver,
+            address_filter: config.address_filter,
+            remember_resolved_addresses: config.remember_resolved_addresses,
+            peer_scorer: config.peer_scorer,
+            observed_peers: Default::default(),
+            deferred_inserts: Default::default(),
+            bucket_refresh_budget: config.bucket_refresh_budget,
+            max_pending_rpcs: config.max_pending_rpcs,
+            num_closer_peers: config.num_closer_peers,
+            adaptive_closer_peers: config.adaptive_closer_peers,
+            find_node_cache: FindNodeCache::new(config.find_node_cache_ttl),
+            liveness_check: config
+                .liveness_check_interval
+                .map(|interval| (interval, Delay::new(interval))),
+            background_paused: false,
+            routing_log: None,
         }
     }
 
@@ -541,6 +1019,47 @@ where
         })
     }
 
+    /// Injects peers learned outside of the DHT, e.g. via rendezvous, into a running query.
+    ///
+    /// The peers are considered by the query as if they had been reported by a
+    /// contacted peer and their addresses are used to dial them, allowing the
+    /// query to take shortcuts. The peers are not added to the routing table.
+    ///
+    /// Queries sending requests to a fixed set of peers, e.g. in the phase of
+    /// [`Behaviour::put_record`] storing the record, ignore injected peers.
+    ///
+    /// Returns `true` if any of the given peers was not yet known to the query,
+    /// `false` otherwise or if the query is not running.
+    pub fn inject_peers_into_query<I>(&mut self, id: QueryId, peers: I) -> bool
+    where
+        I: IntoIterator<Item = PeerInfo>,
+    {
+        let local_id = *self.kbuckets.local_key().preimage();
+        let address_filter = self.address_filter.as_ref();
+        let Some(query) = self.queries.get_mut(&id) else {
+            return false;
+        };
+        if query.is_finished() {
+            return false;
+        }
+
+        let mut peer_ids = Vec::new();
+        for PeerInfo { peer_id, addrs } in peers {
+            if peer_id == local_id {
+                continue;
+            }
+            let addresses = query.inner.addresses.entry(peer_id).or_default();
+            for addr in addrs {
+                if address_filter.map_or(true, |f| f(&addr)) && !addresses.contains(&addr) {
+                    addresses.push(addr);
+                }
+            }
+            peer_ids.push(peer_id);
+        }
+
+        query.inject_peers(peer_ids)
+    }
+
     /// Gets a mutable reference to a running query, if it exists.
     pub fn query_mut<'a>(&'a mut self, id: &QueryId) -> Option<QueryMut<'a>> {
         self.queries.get_mut(id).and_then(|query| {
@@ -578,11 +1097,14 @@ where
         match self.kbuckets.entry(&key) {
             Some(kbucket::Entry::Present(mut entry, _)) => {
                 if entry.value().insert(address) {
+                    let addresses = entry.value().clone();
+                    self.find_node_cache.clear();
+                    self.log_routing_change(RoutingChange::upserted(*peer, &addresses));
                     self.queued_events
                         .push_back(ToSwarm::GenerateEvent(Event::RoutingUpdated {
                             peer: *peer,
                             is_new_peer: false,
-                            addresses: entry.value().clone(),
+                            addresses,
                             old_peer: None,
                             bucket_range: self
                                 .kbuckets
@@ -607,6 +1129,8 @@ where
                 match entry.insert(addresses.clone(), status) {
                     kbucket::InsertResult::Inserted => {
                         self.bootstrap_status.on_new_peer_in_routing_table();
+                        self.find_node_cache.clear();
+                        self.log_routing_change(RoutingChange::upserted(*peer, &addresses));
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
                             Event::RoutingUpdated {
                                 peer: *peer,
@@ -657,9 +1181,14 @@ where
         let key = kbucket::Key::from(*peer);
         match self.kbuckets.entry(&key)? {
             kbucket::Entry::Present(mut entry, _) => {
+                self.find_node_cache.clear();
                 if entry.value().remove(address).is_err() {
-                    Some(entry.remove()) // it is the last address, thus remove the peer.
+                    let removed = entry.remove(); // it is the last address, thus remove the peer.
+                    self.log_routing_change(RoutingChange::Removed { peer: *peer });
+                    Some(removed)
                 } else {
+                    let addresses = entry.value().clone();
+                    self.log_routing_change(RoutingChange::upserted(*peer, &addresses));
                     None
                 }
             }
@@ -684,7 +1213,12 @@ where
     ) -> Option<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> {
         let key = kbucket::Key::from(*peer);
         match self.kbuckets.entry(&key)? {
-            kbucket::Entry::Present(entry, _) => Some(entry.remove()),
+            kbucket::Entry::Present(entry, _) => {
+                self.find_node_cache.clear();
+                let removed = entry.remove();
+                self.log_routing_change(RoutingChange::Removed { peer: *peer });
+                Some(removed)
+            }
             kbucket::Entry::Pending(entry, _) => Some(entry.remove()),
             kbucket::Entry::Absent(..) => None,
         }
@@ -697,6 +1231,53 @@ where
         self.kbuckets.iter().filter(|b| !b.is_empty())
     }
 
+    /// Returns the addresses of the peers in the routing table that were not confirmed within
+    /// `max_age`, including addresses that were never confirmed.
+    ///
+    /// An address is confirmed whenever a connection to the peer is established via the address,
+    /// i.e. on a successful dial or an inbound connection from the address.
+    pub fn stale_addresses(&mut self, max_age: Duration) -> Vec<StaleAddress> {
+        let now = Instant::now();
+        let mut stale = Vec::new();
+        for bucket in self.kbuckets.iter() {
+            for entry in bucket.iter() {
+                for (address, last_confirmed) in entry.node.value.iter_with_last_confirmed() {
+                    if last_confirmed.is_some_and(|t| now.duration_since(t) <= max_age) {
+                        continue;
+                    }
+                    stale.push(StaleAddress {
+                        peer: *entry.node.key.preimage(),
+                        address: address.clone(),
+                        last_confirmed,
+                    });
+                }
+            }
+        }
+        stale
+    }
+
+    /// Returns the distribution of the time since the addresses of the peers in the routing
+    /// table were last confirmed.
+    ///
+    /// See [`Behaviour::stale_addresses`] for when an address is confirmed.
+    pub fn address_staleness(&mut self) -> AddressStaleness {
+        let now = Instant::now();
+        let mut staleness = AddressStaleness::default();
+        for bucket in self.kbuckets.iter() {
+            for entry in bucket.iter() {
+                for (_, last_confirmed) in entry.node.value.iter_with_last_confirmed() {
+                    staleness.num_addresses += 1;
+                    match last_confirmed {
+                        Some(t) => staleness.ages.push(now.duration_since(t)),
+                        None => staleness.num_unconfirmed += 1,
+                    }
+                }
+            }
+        }
+        staleness.ages.sort_unstable();
+        staleness
+    }
+
     /// Returns the k-bucket for the distance to the given key.
     ///
     /// Returns `None` if the given key refers to the local key.
@@ -742,19 +1323,7 @@ where
     /// The result of this operation is delivered in a
     /// [`Event::OutboundQueryProgressed{QueryResult::GetRecord}`].
     pub fn get_record(&mut self, key: record::Key) -> QueryId {
-        let record = if let Some(record) = self.store.get(&key) {
-            if record.is_expired(Instant::now()) {
-                self.store.remove(&key);
-                None
-            } else {
-                Some(PeerRecord {
-                    peer: None,
-                    record: record.into_owned(),
-                })
-            }
-        } else {
-            None
-        };
+        let record = self.local_record(&key);
 
         let step = ProgressStep::first();
 
@@ -794,6 +1363,108 @@ where
         id
     }
 
+    /// Performs lookups for the records of all of the given keys.
+    ///
+    /// The keys are looked up one after another, ordered such that consecutive keys
+    /// are close to each other in the XOR space. The lookup of a key starts from the
+    /// closest peers found by the lookup of the previous key, so related keys, e.g.
+    /// the entries of a directory, are resolved with far fewer requests than
+    /// separate lookups would need. The lookup of a key finishes as soon as
+    /// `quorum` records are found, counting a record in the local store.
+    ///
+    /// Returns `None` if no key is given. Otherwise, the results are reported via
+    /// one [`Event::OutboundQueryProgressed{QueryResult::GetRecords}`] per key.
+    pub fn get_records(&mut self, keys: Vec<record::Key>, quorum: Quorum) -> Option<QueryId> {
+        let mut keys = keys.into_iter().map(kbucket::Key::new).collect::<Vec<_>>();
+        keys.sort_by(|a, b| a.hashed_bytes().cmp(b.hashed_bytes()));
+        keys.dedup();
+        let mut remaining = keys
+            .into_iter()
+            .map(kbucket::Key::into_preimage)
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        let key = remaining.next()?;
+        let quorum = quorum.eval(self.queries.config().replication_factor);
+        let records = self.local_record(&key).into_iter().collect::<Vec<_>>();
+        let satisfied = records.len() >= quorum.get();
+        let target = kbucket::Key::new(key.clone());
+        let peers = self.kbuckets.closest_keys(&target).collect::<Vec<_>>();
+        let info = QueryInfo::GetRecords {
+            key,
+            remaining,
+            quorum,
+            records,
+            step: ProgressStep::first(),
+        };
+        let id = self
+            .queries
+            .add_iter_closest(target, peers, QueryInner::new(info));
+        if satisfied {
+            if let Some(query) = self.queries.get_mut(&id) {
+                query.finish();
+            }
+        }
+
+        Some(id)
+    }
+
+    /// Starts the lookup of the next key of a [`QueryInfo::GetRecords`] query, from the
+    /// closest peers found by the lookup of the previous key.
+    ///
+    /// Returns `false` if there are no keys left to look up.
+    fn continue_get_records(
+        &mut self,
+        query_id: QueryId,
+        mut remaining: vec::IntoIter<record::Key>,
+        quorum: NonZeroUsize,
+        step: ProgressStep,
+        closest_peers: &[PeerId],
+    ) -> bool {
+        let Some(key) = remaining.next() else {
+            return false;
+        };
+        let target = kbucket::Key::new(key.clone());
+        let mut peers = closest_peers
+            .iter()
+            .map(|peer| kbucket::Key::from(*peer))
+            .chain(self.kbuckets.closest_keys(&target))
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|peer| peer.distance(&target));
+        peers.dedup();
+
+        let records = self.local_record(&key).into_iter().collect::<Vec<_>>();
+        let satisfied = records.len() >= quorum.get();
+        let info = QueryInfo::GetRecords {
+            key,
+            remaining,
+            quorum,
+            records,
+            step,
+        };
+        self.queries
+            .continue_iter_closest(query_id, target, peers, QueryInner::new(info));
+        if satisfied {
+            if let Some(query) = self.queries.get_mut(&query_id) {
+                query.finish();
+            }
+        }
+        true
+    }
+
+    /// Returns the unexpired record of the given key in the local store, if any.
+    fn local_record(&mut self, key: &record::Key) -> Option<PeerRecord> {
+        let record = self.store.get(key)?;
+        if record.is_expired(Instant::now()) {
+            self.store.remove(key);
+            return None;
+        }
+        Some(PeerRecord {
+            peer: None,
+            record: record.into_owned(),
+        })
+    }
+
     /// Stores a record in the DHT, locally as well as at the nodes
     /// closest to the key as per the xor distance metric.
     ///
@@ -823,6 +1494,7 @@ where
             .expires
             .or_else(|| self.record_ttl.map(|ttl| Instant::now() + ttl));
         let quorum = quorum.eval(self.queries.config().replication_factor);
+        let phase = self.put_record_lookup_phase();
         let target = kbucket::Key::new(record.key.clone());
         let peers = self.kbuckets.closest_keys(&target);
         let context = PutRecordContext::Publish;
@@ -830,7 +1502,7 @@ where
             context,
             record,
             quorum,
-            phase: PutRecordPhase::GetClosestPeers,
+            phase,
         };
         let inner = QueryInner::new(info);
         Ok(self.queries.add_iter_closest(target.clone(), peers, inner))
@@ -904,6 +1576,15 @@ where
         &mut self.store
     }
 
+    /// Sets the strategy deciding when the provider records of the local node
+    /// are re-published, replacing the [`PeriodicSweep`](crate::PeriodicSweep) using the
+    /// [provider publication interval](Config::set_provider_publication_interval).
+    ///
+    /// A re-publication currently in progress is aborted.
+    pub fn set_reprovide_strategy(&mut self, strategy: impl ReprovideStrategy) {
+        self.add_provider_job = Some(AddProviderJob::with_strategy(strategy));
+    }
+
     /// Bootstraps the local node to join the DHT.
     ///
     /// Bootstrapping is a multi-step operation that starts with a lookup of the local node's
@@ -994,6 +1675,41 @@ where
         Ok(id)
     }
 
+    /// Establishes the local node as a provider of values for all of the given keys.
+    ///
+    /// This is equivalent to calling [`Behaviour::start_providing`] for every key, except
+    /// that keys with the same closest peers in the local routing table share a single
+    /// lookup and that all announcements destined for the same peer are sent together
+    /// on a single stream. This drastically reduces the number of queries needed to
+    /// announce a large number of keys.
+    ///
+    /// The lookup of a batch targets one of its keys and yields twice the replication
+    /// factor of peers, among which the closest peers to each key are announced to.
+    /// Thus the provider records of a batch may end up on peers slightly further away
+    /// from a key than a dedicated lookup would have found.
+    ///
+    /// Returns the `QueryId`s of the started queries if all provider records have been
+    /// stored locally. Each query reports one
+    /// [`Event::OutboundQueryProgressed{QueryResult::StartProviding}`] per key of its batch.
+    /// If storing a provider record fails, no query is started.
+    pub fn start_providing_many<I>(&mut self, keys: I) -> Result<Vec<QueryId>, store::Error>
+    where
+        I: IntoIterator<Item = record::Key>,
+    {
+        let local_id = *self.kbuckets.local_key().preimage();
+        let mut batches = HashMap::<Vec<PeerId>, Vec<record::Key>>::new();
+        for key in keys {
+            self.store
+                .add_provider(ProviderRecord::new(key.clone(), local_id, Vec::new()))?;
+            batches
+                .entry(self.provider_batch(&key))
+                .or_default()
+                .push(key);
+        }
+
+        Ok(self.start_add_providers(batches, AddProviderContext::Publish))
+    }
+
     /// Stops the local node from announcing that it is a provider for the given key.
     ///
     /// This is a local operation. The local node will still be considered as a
@@ -1066,7 +1782,7 @@ where
             }
             None => {
                 self.auto_mode = true;
-                self.determine_mode_from_external_addresses();
+                self.determine_mode_from_external_addresses(ModeChangeReason::AutoModeEnabled);
             }
         }
 
@@ -1075,6 +1791,119 @@ where
         }
     }
 
+    /// Returns the [`Mode`] in which we currently operate, either set via [`Behaviour::set_mode`]
+    /// or determined automatically, see [`Event::ModeChanged`].
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Pauses the background activity, i.e. the periodic republication and replication of
+    /// records, the periodic and automatic bootstraps including their bucket refreshes, and the
+    /// liveness checks of the routing table, e.g. while a mobile application is in the
+    /// background.
+    ///
+    /// Inbound requests are still answered, and queries started explicitly are still run, as are
+    /// the background queries already running. The activity that became due while paused runs
+    /// once [`Behaviour::resume`] is called.
+    pub fn pause_background(&mut self) {
+        self.background_paused = true;
+    }
+
+    /// Resumes the background activity paused via [`Behaviour::pause_background`].
+    pub fn resume(&mut self) {
+        if !std::mem::replace(&mut self.background_paused, false) {
+            return;
+        }
+
+        if let Some(waker) = self.no_events_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Returns whether the background activity is paused, see [`Behaviour::pause_background`].
+    pub fn is_background_paused(&self) -> bool {
+        self.background_paused
+    }
+
+    /// Persists the routing table in the given store, as a log of its changes appended as they
+    /// happen, so that the routing table can be restored after a restart, even after a crash.
+    /// The log is compacted into a snapshot of the routing table once it grows large.
+    ///
+    /// The routing table is first restored from the log of the store via
+    /// [`Behaviour::add_address`], skipping the damaged entries of the log, e.g. an entry torn by
+    /// a crash. Returns the number of peers of the log that were added to the routing table.
+    pub fn set_routing_table_store<S>(&mut self, store: S) -> io::Result<usize>
+    where
+        S: RoutingTableStore,
+    {
+        let mut log = RoutingLog::new(Box::new(store));
+        let replayed = log.replay()?;
+        if replayed.skipped > 0 || replayed.truncated {
+            tracing::warn!(
+                skipped=%replayed.skipped,
+                truncated=%replayed.truncated,
+                "Skipped damaged entries of the routing table log"
+            );
+        }
+
+        // The restored peers are part of the compacted log.
+        self.routing_log = None;
+        let mut restored = 0;
+        for (peer, addresses) in replayed.peers {
+            let mut added = false;
+            for address in addresses {
+                added |= self.add_address(&peer, address) == RoutingUpdate::Success;
+            }
+            restored += usize::from(added);
+        }
+        self.routing_log = Some(log);
+        self.compact_routing_log()?;
+
+        Ok(restored)
+    }
+
+    /// Appends the given change of the routing table to the log, compacting it if needed.
+    fn log_routing_change(&mut self, change: RoutingChange) {
+        let Some(log) = self.routing_log.as_mut() else {
+            return;
+        };
+        if let Err(e) = log.append(&change) {
+            tracing::warn!("Failed to persist a change of the routing table: {e}");
+            return;
+        }
+
+        let num_peers = self.kbuckets.iter().map(|b| b.num_entries()).sum();
+        if log.needs_compaction(num_peers) {
+            if let Err(e) = self.compact_routing_log() {
+                tracing::warn!("Failed to compact the routing table log: {e}");
+            }
+        }
+    }
+
+    /// Replaces the routing table log with a snapshot of the routing table.
+    fn compact_routing_log(&mut self) -> io::Result<()> {
+        let peers = self
+            .kbuckets
+            .iter()
+            .flat_map(|bucket| {
+                bucket
+                    .iter()
+                    .map(|entry| {
+                        (
+                            *entry.node.key.preimage(),
+                            entry.node.value.clone().into_vec(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        match self.routing_log.as_mut() {
+            Some(log) => log.compact(peers),
+            None => Ok(()),
+        }
+    }
+
     fn reconfigure_mode(&mut self) {
         if self.connections.is_empty() {
             return;
@@ -1102,7 +1931,7 @@ where
             );
     }
 
-    fn determine_mode_from_external_addresses(&mut self) {
+    fn determine_mode_from_external_addresses(&mut self, reason: ModeChangeReason) {
         let old_mode = self.mode;
 
         self.mode = match (self.external_addresses.as_slice(), self.mode) {
@@ -1144,6 +1973,7 @@ where
             self.queued_events
                 .push_back(ToSwarm::GenerateEvent(Event::ModeChanged {
                     new_mode: self.mode,
+                    reason,
                 }));
         }
     }
@@ -1155,6 +1985,7 @@ where
     {
         let local_id = self.kbuckets.local_key().preimage();
         let others_iter = peers.filter(|p| &p.node_id != local_id);
+        let address_filter = self.address_filter.as_ref();
         if let Some(query) = self.queries.get_mut(query_id) {
             tracing::trace!(peer=%source, query=?query_id, "Request to peer in query succeeded");
             for peer in others_iter.clone() {
@@ -1164,11 +1995,28 @@ where
                     query=?query_id,
                     "Peer reported by source in query"
                 );
-                let addrs = peer.multiaddrs.iter().cloned().collect();
+                let addrs = peer
+                    .multiaddrs
+                    .iter()
+                    .filter(|a| address_filter.map_or(true, |f| f(a)))
+                    .cloned()
+                    .collect();
                 query.inner.addresses.insert(peer.node_id, addrs);
             }
             query.on_success(source, others_iter.cloned().map(|kp| kp.node_id))
         }
+        self.response_received(*source);
+    }
+
+    /// Records a successful response of a connected peer and reconsiders
+    /// its insertion into the routing table, if it was rejected by the [`PeerScorer`].
+    fn response_received(&mut self, peer: PeerId) {
+        if let Some(observation) = self.observed_peers.get_mut(&peer) {
+            observation.num_responses += 1;
+        }
+        if let Some(address) = self.deferred_inserts.remove(&peer) {
+            self.connection_updated(peer, Some(address), NodeStatus::Connected);
+        }
     }
 
     /// Finds the closest peers to a `target` in the context of a request by
@@ -1179,14 +2027,80 @@ where
         target: &kbucket::Key<T>,
         source: &PeerId,
     ) -> Vec<KadPeer> {
+        let (num_results, max_distance) = self.closer_peers_limits(target, source);
+
         self.kbuckets
             .closest(target)
             .filter(|e| e.node.key.preimage() != source)
-            .take(self.queries.config().replication_factor.get())
+            .take_while(|e| max_distance.map_or(true, |d| target.distance(&e.node.key) < d))
+            .take(num_results)
             .map(KadPeer::from)
             .collect()
     }
 
+    /// Returns the number of closer peers to return for a request of `source` and,
+    /// if the closer peers adapt to the requester, the distance they must be closer than.
+    fn closer_peers_limits<T>(
+        &self,
+        target: &kbucket::Key<T>,
+        source: &PeerId,
+    ) -> (usize, Option<Distance>) {
+        let num_results = self
+            .num_closer_peers
+            .unwrap_or(self.queries.config().replication_factor)
+            .get();
+        let max_distance = if self.adaptive_closer_peers {
+            Some(target.distance(&kbucket::Key::from(*source)))
+                .filter(|distance| distance.ilog2().is_some())
+        } else {
+            None
+        };
+
+        (num_results, max_distance)
+    }
+
+    /// Finds the closer peers of an inbound `FIND_NODE` request, using the
+    /// cache of recent responses if enabled.
+    fn find_closest_cached(&mut self, key: Vec<u8>, source: &PeerId) -> Vec<KadPeer> {
+        let target = kbucket::Key::new(key);
+        if !self.find_node_cache.is_enabled() {
+            return self.find_closest(&target, source);
+        }
+
+        let (num_results, max_distance) = self.closer_peers_limits(&target, source);
+        let now = Instant::now();
+        let cached = self
+            .find_node_cache
+            .get(target.preimage(), now)
+            .map(<[KadPeer]>::to_vec);
+        let candidates = match cached {
+            Some(candidates) => candidates,
+            None => {
+                // One peer more than returned, in case the requester is among them.
+                let candidates = self
+                    .kbuckets
+                    .closest(&target)
+                    .take(num_results + 1)
+                    .map(KadPeer::from)
+                    .collect::<Vec<_>>();
+                self.find_node_cache
+                    .insert(target.preimage().clone(), candidates.clone(), now);
+                candidates
+            }
+        };
+
+        candidates
+            .into_iter()
+            .filter(|peer| &peer.node_id != source)
+            .take_while(|peer| {
+                max_distance.map_or(true, |d| {
+                    target.distance(&kbucket::Key::from(peer.node_id)) < d
+                })
+            })
+            .take(num_results)
+            .collect()
+    }
+
     /// Collects all peers who are known to be providers of the value for a given `Multihash`.
     fn provider_peers(&mut self, key: &record::Key, source: &PeerId) -> Vec<KadPeer> {
         let kbuckets = &mut self.kbuckets;
@@ -1245,34 +2159,78 @@ where
             .collect()
     }
 
-    /// Starts an iterative `ADD_PROVIDER` query for the given key.
-    fn start_add_provider(&mut self, key: record::Key, context: AddProviderContext) {
-        let info = QueryInfo::AddProvider {
-            context,
-            key: key.clone(),
-            phase: AddProviderPhase::GetClosestPeers,
-        };
-        let target = kbucket::Key::new(key);
-        let peers = self.kbuckets.closest_keys(&target);
-        let inner = QueryInner::new(info);
-        self.queries.add_iter_closest(target.clone(), peers, inner);
+    /// Returns the closest peers to the given key in the local routing table, in a
+    /// canonical order, by which provider announcements are batched.
+    fn provider_batch(&mut self, key: &record::Key) -> Vec<PeerId> {
+        let target = kbucket::Key::new(key.clone());
+        let mut closest = self
+            .kbuckets
+            .closest_keys(&target)
+            .take(self.queries.config().replication_factor.get())
+            .map(|k| k.into_preimage())
+            .collect::<Vec<_>>();
+        closest.sort();
+        closest
+    }
+
+    /// Starts an iterative `ADD_PROVIDER` query for each batch of keys, announcing
+    /// all keys of a batch destined for the same peer on a single stream.
+    fn start_add_providers(
+        &mut self,
+        batches: HashMap<Vec<PeerId>, Vec<record::Key>>,
+        context: AddProviderContext,
+    ) -> Vec<QueryId> {
+        let num_results = self
+            .queries
+            .config()
+            .replication_factor
+            .saturating_mul(NonZeroUsize::new(2).expect("2 > 0"));
+        batches
+            .into_values()
+            .map(|keys| {
+                let target = kbucket::Key::new(keys[0].clone());
+                let peers = self.kbuckets.closest_keys(&target).collect::<Vec<_>>();
+                let inner = QueryInner::new(QueryInfo::AddProviders {
+                    keys,
+                    phase: AddProvidersPhase::GetClosestPeers,
+                    context,
+                });
+                self.queries
+                    .add_iter_closest_n(target, peers, inner, num_results)
+            })
+            .collect()
     }
 
     /// Starts an iterative `PUT_VALUE` query for the given record.
     fn start_put_record(&mut self, record: Record, quorum: Quorum, context: PutRecordContext) {
         let quorum = quorum.eval(self.queries.config().replication_factor);
+        let phase = match context {
+            PutRecordContext::Replicate => PutRecordPhase::GetClosestPeers,
+            _ => self.put_record_lookup_phase(),
+        };
         let target = kbucket::Key::new(record.key.clone());
         let peers = self.kbuckets.closest_keys(&target);
         let info = QueryInfo::PutRecord {
             record,
             quorum,
             context,
-            phase: PutRecordPhase::GetClosestPeers,
+            phase,
         };
         let inner = QueryInner::new(info);
         self.queries.add_iter_closest(target.clone(), peers, inner);
     }
 
+    /// Returns the initial phase of a query publishing a record, according to
+    /// the configured [`RecordPlacement`].
+    fn put_record_lookup_phase(&self) -> PutRecordPhase {
+        match self.record_placement {
+            RecordPlacement::Closest => PutRecordPhase::GetClosestPeers,
+            RecordPlacement::ClosestAndProviders => PutRecordPhase::GetProviders {
+                providers: Vec::new(),
+            },
+        }
+    }
+
     /// Updates the routing table with a new connection status and address of a peer.
     fn connection_updated(
         &mut self,
@@ -1281,18 +2239,30 @@ where
         new_status: NodeStatus,
     ) {
         let key = kbucket::Key::from(peer);
+        // The address of a connected peer is one the peer was just dialed on.
+        let confirmed = address
+            .clone()
+            .filter(|_| new_status == NodeStatus::Connected);
         match self.kbuckets.entry(&key) {
             Some(kbucket::Entry::Present(mut entry, old_status)) => {
                 if old_status != new_status {
-                    entry.update(new_status)
+                    entry.update(new_status);
+                    self.find_node_cache.clear();
                 }
                 if let Some(address) = address {
-                    if entry.value().insert(address) {
+                    let inserted = entry.value().insert(address);
+                    if let Some(confirmed) = &confirmed {
+                        entry.value().confirm(confirmed, Instant::now());
+                    }
+                    if inserted {
+                        let addresses = entry.value().clone();
+                        self.find_node_cache.clear();
+                        self.log_routing_change(RoutingChange::upserted(peer, &addresses));
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
                             Event::RoutingUpdated {
                                 peer,
                                 is_new_peer: false,
-                                addresses: entry.value().clone(),
+                                addresses,
                                 old_peer: None,
                                 bucket_range: self
                                     .kbuckets
@@ -1309,6 +2279,9 @@ where
                 if let Some(address) = address {
                     entry.value().insert(address);
                 }
+                if let Some(confirmed) = &confirmed {
+                    entry.value().confirm(confirmed, Instant::now());
+                }
                 if old_status != new_status {
                     entry.update(new_status);
                 }
@@ -1332,10 +2305,30 @@ where
                             }));
                     }
                     (Some(a), BucketInserts::OnConnected) => {
-                        let addresses = Addresses::new(a);
+                        let allowed = match (&self.peer_scorer, self.observed_peers.get(&peer)) {
+                            (Some(scorer), Some(observation)) => {
+                                scorer.allow_insert(&peer, observation)
+                            }
+                            (Some(_), None) => false,
+                            (None, _) => true,
+                        };
+                        if !allowed {
+                            tracing::debug!(
+                                %peer,
+                                "Peer rejected by scorer. Peer not added to routing table"
+                            );
+                            self.deferred_inserts.insert(peer, a);
+                            return;
+                        }
+                        let mut addresses = Addresses::new(a);
+                        if let Some(confirmed) = &confirmed {
+                            addresses.confirm(confirmed, Instant::now());
+                        }
                         match entry.insert(addresses.clone(), new_status) {
                             kbucket::InsertResult::Inserted => {
                                 self.bootstrap_status.on_new_peer_in_routing_table();
+                                self.find_node_cache.clear();
+                                self.log_routing_change(RoutingChange::upserted(peer, &addresses));
                                 let event = Event::RoutingUpdated {
                                     peer,
                                     is_new_peer: true,
@@ -1386,6 +2379,64 @@ where
         }
     }
 
+    /// Probes the least-recently connected peer of every bucket, if it is not connected.
+    ///
+    /// See [`Config::set_liveness_check_interval`].
+    fn check_liveness(&mut self) {
+        let probing = self
+            .queries
+            .iter()
+            .filter_map(|q| match q.inner.info {
+                QueryInfo::LivenessCheck { peer } => Some(peer),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let candidates = self
+            .kbuckets
+            .iter()
+            .filter_map(|b| {
+                b.iter()
+                    .next()
+                    .filter(|e| e.status == NodeStatus::Disconnected)
+                    .map(|e| *e.node.key.preimage())
+            })
+            .filter(|peer| !probing.contains(peer))
+            .collect::<Vec<_>>();
+
+        for peer in candidates {
+            tracing::debug!(%peer, "Checking liveness of peer in routing table");
+            let inner = QueryInner::new(QueryInfo::LivenessCheck { peer });
+            self.queries.add_fixed(std::iter::once(peer), inner);
+        }
+    }
+
+    /// Removes a peer from the routing table that failed a liveness check.
+    fn on_liveness_check_failed(&mut self, peer: PeerId) {
+        if self.remove_peer(&peer).is_some() {
+            tracing::debug!(%peer, "Removed unresponsive peer from routing table");
+        }
+    }
+
+    /// Selects the buckets to refresh after the self-lookup of a bootstrap.
+    ///
+    /// These are the buckets farther away than the bucket of the closest neighbour, ordered
+    /// by the time of their last successful lookup (never looked up first) and then by
+    /// their number of entries, limited to [`Config::set_bucket_refresh_budget`].
+    fn buckets_to_refresh(
+        &mut self,
+    ) -> Vec<kbucket::KBucketRef<'_, kbucket::Key<PeerId>, Addresses>> {
+        let budget = self.bucket_refresh_budget.map_or(usize::MAX, |b| b.get());
+        let mut buckets = self
+            .kbuckets
+            .iter()
+            .skip_while(|b| b.is_empty())
+            .skip(1) // Skip the bucket with the closest neighbour.
+            .collect::<Vec<_>>();
+        buckets.sort_by_key(|b| (b.last_lookup(), b.num_entries()));
+        buckets.truncate(budget);
+        buckets
+    }
+
     /// Handles a finished (i.e. successful) query.
     fn query_finished(&mut self, q: Query<QueryInner>) -> Option<Event> {
         let query_id = q.id();
@@ -1403,11 +2454,9 @@ where
                     // The lookup for the local key finished. To complete the bootstrap process,
                     // a bucket refresh should be performed for every bucket farther away than
                     // the first non-empty bucket (which are most likely no more than the last
-                    // few, i.e. farthest, buckets).
-                    self.kbuckets
-                        .iter()
-                        .skip_while(|b| b.is_empty())
-                        .skip(1) // Skip the bucket with the closest neighbour.
+                    // few, i.e. farthest, buckets), stalest first and up to the configured budget.
+                    self.buckets_to_refresh()
+                        .into_iter()
                         .map(|b| {
                             // Try to find a key that falls into the bucket. While such keys can
                             // be generated fully deterministically, the current libp2p kademlia
@@ -1452,6 +2501,7 @@ where
                     step.last = true;
                     self.bootstrap_status.on_finish();
                 };
+                self.bootstrap_progressed(query_id, &result.stats, step.last);
 
                 Some(Event::OutboundQueryProgressed {
                     id: query_id,
@@ -1515,7 +2565,7 @@ where
                     phase: AddProviderPhase::AddProvider {
                         provider_id,
                         external_addresses,
-                        get_closest_peers_stats: result.stats,
+                        get_closest_peers_stats: Box::new(result.stats),
                     },
                 });
                 self.queries.continue_fixed(query_id, result.peers, inner);
@@ -1533,18 +2583,111 @@ where
             } => match context {
                 AddProviderContext::Publish => Some(Event::OutboundQueryProgressed {
                     id: query_id,
-                    stats: get_closest_peers_stats.merge(result.stats),
+                    stats: (*get_closest_peers_stats).merge(result.stats),
                     result: QueryResult::StartProviding(Ok(AddProviderOk { key })),
                     step: ProgressStep::first_and_last(),
                 }),
                 AddProviderContext::Republish => Some(Event::OutboundQueryProgressed {
                     id: query_id,
-                    stats: get_closest_peers_stats.merge(result.stats),
+                    stats: (*get_closest_peers_stats).merge(result.stats),
                     result: QueryResult::RepublishProvider(Ok(AddProviderOk { key })),
                     step: ProgressStep::first_and_last(),
                 }),
             },
 
+            QueryInfo::AddProviders {
+                keys,
+                phase: AddProvidersPhase::GetClosestPeers,
+                context,
+            } => {
+                let peers = result.peers.map(kbucket::Key::from).collect::<Vec<_>>();
+                let num_closest = self.queries.config().replication_factor.get();
+                let mut keys_per_peer = HashMap::<PeerId, Vec<record::Key>>::new();
+                for key in &keys {
+                    let target = kbucket::Key::new(key.clone());
+                    let mut closest = peers.clone();
+                    closest.sort_by_key(|peer| target.distance(peer));
+                    for peer in closest.into_iter().take(num_closest) {
+                        keys_per_peer
+                            .entry(peer.into_preimage())
+                            .or_default()
+                            .push(key.clone());
+                    }
+                }
+                let provider_id = self.local_peer_id;
+                let external_addresses = self.external_addresses.iter().cloned().collect();
+                let peers = keys_per_peer.keys().copied().collect::<Vec<_>>();
+                let inner = QueryInner::new(QueryInfo::AddProviders {
+                    keys,
+                    phase: AddProvidersPhase::AddProvider {
+                        provider_id,
+                        external_addresses,
+                        keys_per_peer,
+                        get_closest_peers_stats: Box::new(result.stats),
+                    },
+                    context,
+                });
+                self.queries.continue_fixed(query_id, peers, inner);
+                None
+            }
+
+            QueryInfo::AddProviders {
+                keys,
+                phase:
+                    AddProvidersPhase::AddProvider {
+                        get_closest_peers_stats,
+                        ..
+                    },
+                context,
+            } => {
+                let stats = (*get_closest_peers_stats).merge(result.stats);
+                self.batch_progressed(
+                    query_id,
+                    stats,
+                    context,
+                    keys.into_iter().map(|key| Ok(AddProviderOk { key })),
+                );
+                None
+            }
+
+            QueryInfo::GetRecords {
+                key,
+                remaining,
+                quorum,
+                records,
+                mut step,
+            } => {
+                let closest_peers = result.peers.collect::<Vec<_>>();
+                let next_step = step.next();
+                if !self.continue_get_records(
+                    query_id,
+                    remaining,
+                    quorum,
+                    next_step,
+                    &closest_peers,
+                ) {
+                    step.last = true;
+                }
+
+                let lookup = if records.len() >= quorum.get() {
+                    Ok(GetRecordsOk { key, records })
+                } else if records.is_empty() {
+                    Err(GetRecordError::NotFound { key, closest_peers })
+                } else {
+                    Err(GetRecordError::QuorumFailed {
+                        key,
+                        records,
+                        quorum,
+                    })
+                };
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::GetRecords(lookup),
+                    step,
+                })
+            }
+
             QueryInfo::GetRecord {
                 key,
                 mut step,
@@ -1573,7 +2716,27 @@ where
                 context,
                 record,
                 quorum,
-                phase: PutRecordPhase::GetClosestPeers,
+                phase: PutRecordPhase::GetClosestPeers,
+            } => {
+                let info = QueryInfo::PutRecord {
+                    context,
+                    record,
+                    quorum,
+                    phase: PutRecordPhase::PutRecord {
+                        success: vec![],
+                        get_closest_peers_stats: result.stats,
+                    },
+                };
+                let inner = QueryInner::new(info);
+                self.queries.continue_fixed(query_id, result.peers, inner);
+                None
+            }
+
+            QueryInfo::PutRecord {
+                context,
+                record,
+                quorum,
+                phase: PutRecordPhase::GetProviders { providers },
             } => {
                 let info = QueryInfo::PutRecord {
                     context,
@@ -1584,8 +2747,14 @@ where
                         get_closest_peers_stats: result.stats,
                     },
                 };
+                let mut peers = result.peers.collect::<Vec<_>>();
+                for provider in providers {
+                    if !peers.contains(&provider) {
+                        peers.push(provider);
+                    }
+                }
                 let inner = QueryInner::new(info);
-                self.queries.continue_fixed(query_id, result.peers, inner);
+                self.queries.continue_fixed(query_id, peers, inner);
                 None
             }
 
@@ -1631,7 +2800,79 @@ where
                     }
                 }
             }
+
+            QueryInfo::LivenessCheck { peer } => {
+                if !result.peers.any(|p| p == peer) {
+                    self.on_liveness_check_failed(peer);
+                }
+                None
+            }
+        }
+    }
+
+    /// Queues the results of a [`QueryInfo::AddProviders`] query, one event per key.
+    fn batch_progressed<I>(
+        &mut self,
+        id: QueryId,
+        stats: QueryStats,
+        context: AddProviderContext,
+        results: I,
+    ) where
+        I: IntoIterator<Item = AddProviderResult>,
+    {
+        let mut results = results.into_iter().peekable();
+        let mut step = ProgressStep::first();
+        while let Some(result) = results.next() {
+            step.last = results.peek().is_none();
+            let result = match context {
+                AddProviderContext::Publish => QueryResult::StartProviding(result),
+                AddProviderContext::Republish => QueryResult::RepublishProvider(result),
+            };
+            self.queued_events
+                .push_back(ToSwarm::GenerateEvent(Event::OutboundQueryProgressed {
+                    id,
+                    result,
+                    stats: stats.clone(),
+                    step: step.clone(),
+                }));
+            if !step.last {
+                step = step.next();
+            }
+        }
+    }
+
+    /// Accumulates the statistics of a finished step of a bootstrap, reporting the
+    /// state of the routing table via [`Event::BootstrapFinished`] after the last step.
+    fn bootstrap_progressed(&mut self, id: QueryId, stats: &QueryStats, last: bool) {
+        let stats = match self.bootstrap_stats.remove(&id) {
+            Some(previous) => previous.merge(stats.clone()),
+            None => stats.clone(),
+        };
+        if !last {
+            self.bootstrap_stats.insert(id, stats);
+            return;
         }
+
+        let bucket_fill = self
+            .kbuckets
+            .iter()
+            .filter(|b| !b.is_empty())
+            .filter_map(|b| Some((b.range().0.ilog2()?, b.num_entries())))
+            .collect::<Vec<_>>();
+        let num_peers = bucket_fill.iter().map(|(_, n)| n).sum::<usize>();
+        let criteria = self.bootstrap_criteria;
+        let report = BootstrapReport {
+            success: num_peers >= criteria.min_peers && bucket_fill.len() >= criteria.min_buckets,
+            num_peers,
+            bucket_fill,
+            num_failures: stats.num_failures(),
+            duration: stats.duration().unwrap_or_default(),
+        };
+        self.queued_events
+            .push_back(ToSwarm::GenerateEvent(Event::BootstrapFinished {
+                id,
+                report,
+            }));
     }
 
     /// Handles a query that timed out.
@@ -1664,6 +2905,7 @@ where
                     step.last = true;
                     self.bootstrap_status.on_finish();
                 }
+                self.bootstrap_progressed(query_id, &result.stats, step.last);
 
                 Some(Event::OutboundQueryProgressed {
                     id: query_id,
@@ -1691,6 +2933,17 @@ where
                 },
             }),
 
+            QueryInfo::AddProviders { keys, context, .. } => {
+                self.batch_progressed(
+                    query_id,
+                    result.stats,
+                    context,
+                    keys.into_iter()
+                        .map(|key| Err(AddProviderError::Timeout { key })),
+                );
+                None
+            }
+
             QueryInfo::GetClosestPeers { key, mut step } => {
                 step.last = true;
                 let peers = result
@@ -1727,7 +2980,9 @@ where
                     key: record.key,
                     quorum,
                     success: match phase {
-                        PutRecordPhase::GetClosestPeers => vec![],
+                        PutRecordPhase::GetClosestPeers | PutRecordPhase::GetProviders { .. } => {
+                            vec![]
+                        }
                         PutRecordPhase::PutRecord { ref success, .. } => success.clone(),
                     },
                 });
@@ -1747,7 +3002,7 @@ where
                         step: ProgressStep::first_and_last(),
                     }),
                     PutRecordContext::Replicate => match phase {
-                        PutRecordPhase::GetClosestPeers => {
+                        PutRecordPhase::GetClosestPeers | PutRecordPhase::GetProviders { .. } => {
                             tracing::warn!(
                                 "Locating closest peers for replication failed: {:?}",
                                 err
@@ -1773,6 +3028,33 @@ where
                 })
             }
 
+            QueryInfo::GetRecords {
+                key,
+                remaining,
+                quorum,
+                mut step,
+                ..
+            } => {
+                let closest_peers = result.peers.collect::<Vec<_>>();
+                let next_step = step.next();
+                if !self.continue_get_records(
+                    query_id,
+                    remaining,
+                    quorum,
+                    next_step,
+                    &closest_peers,
+                ) {
+                    step.last = true;
+                }
+
+                Some(Event::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: QueryResult::GetRecords(Err(GetRecordError::Timeout { key })),
+                    step,
+                })
+            }
+
             QueryInfo::GetProviders { key, mut step, .. } => {
                 step.last = true;
 
@@ -1786,6 +3068,11 @@ where
                     step,
                 })
             }
+
+            QueryInfo::LivenessCheck { peer } => {
+                self.on_liveness_check_failed(peer);
+                None
+            }
         }
     }
 
@@ -1807,6 +3094,7 @@ where
                 event: HandlerIn::PutRecordRes {
                     key: record.key,
                     value: record.value,
+                    error: None,
                     request_id,
                 },
             });
@@ -1828,7 +3116,10 @@ where
             .map(|ttl| now + exp_decrease(ttl, num_beyond_k));
         // The smaller TTL prevails. Only if neither TTL is set is the record
         // stored "forever".
-        record.expires = record.expires.or(expiration).min(expiration);
+        record.expires = match (record.expires, expiration) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
 
         if let Some(job) = self.put_record_job.as_mut() {
             // Ignore the record in the next run of the replication
@@ -1872,10 +3163,24 @@ where
                     }
                     Err(e) => {
                         tracing::info!("Record not stored: {:?}", e);
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(
+                            Event::InboundStoreFailed {
+                                source,
+                                key: record.key.clone(),
+                                error: e,
+                            },
+                        ));
+                        // The value is not sent back, such that remotes that do not
+                        // understand the error still consider the request failed.
                         self.queued_events.push_back(ToSwarm::NotifyHandler {
                             peer_id: source,
                             handler: NotifyHandler::One(connection),
-                            event: HandlerIn::Reset(request_id),
+                            event: HandlerIn::PutRecordRes {
+                                key: record.key,
+                                value: Vec::new(),
+                                error: Some(e),
+                                request_id,
+                            },
                         });
 
                         return;
@@ -1907,6 +3212,7 @@ where
             event: HandlerIn::PutRecordRes {
                 key: record.key,
                 value: record.value,
+                error: None,
                 request_id,
             },
         })
@@ -1923,8 +3229,16 @@ where
             };
             match self.record_filtering {
                 StoreInserts::Unfiltered => {
+                    let key = record.key.clone();
                     if let Err(e) = self.store.add_provider(record) {
                         tracing::info!("Provider record not stored: {:?}", e);
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(
+                            Event::InboundStoreFailed {
+                                source: provider.node_id,
+                                key,
+                                error: e,
+                            },
+                        ));
                         return;
                     }
 
@@ -1955,11 +3269,16 @@ where
             // of the error is not possible (and also not truly desirable or ergonomic).
             // The error passed in should rather be a dedicated enum.
             if addrs.remove(address).is_ok() {
+                self.find_node_cache.clear();
                 tracing::debug!(
                     peer=%peer_id,
                     %address,
                     "Address removed from peer due to error."
                 );
+                if let Some(kbucket::Entry::Present(mut entry, _)) = self.kbuckets.entry(&key) {
+                    let addresses = entry.value().clone();
+                    self.log_routing_change(RoutingChange::upserted(peer_id, &addresses));
+                }
             } else {
                 // Despite apparently having no reachable address (any longer),
                 // the peer is kept in the routing table with the last address to avoid
@@ -1989,6 +3308,7 @@ where
         &mut self,
         ConnectionEstablished {
             peer_id,
+            endpoint,
             failed_addresses,
             other_established,
             ..
@@ -1998,9 +3318,26 @@ where
             self.address_failed(peer_id, addr);
         }
 
+        // Confirm the address of the peer the connection was established on, if known.
+        let address = endpoint.get_remote_address();
+        if let Some(addrs) = self
+            .kbuckets
+            .entry(&kbucket::Key::from(peer_id))
+            .as_mut()
+            .and_then(|e| e.value())
+        {
+            let now = Instant::now();
+            if !addrs.confirm(address, now) {
+                if let Ok(address) = address.clone().with_p2p(peer_id) {
+                    addrs.confirm(&address, now);
+                }
+            }
+        }
+
         // Peer's first connection.
         if other_established == 0 {
             self.connected_peers.insert(peer_id);
+            self.observed_peers.insert(peer_id, PeerObservation::new());
         }
     }
 
@@ -2123,6 +3460,8 @@ where
             }
             self.connection_updated(peer_id, None, NodeStatus::Disconnected);
             self.connected_peers.remove(&peer_id);
+            self.observed_peers.remove(&peer_id);
+            self.deferred_inserts.remove(&peer_id);
         }
     }
 
@@ -2136,14 +3475,12 @@ where
         self.connections.insert(connection_id, peer);
         // Queue events for sending pending RPCs to the connected peer.
         // There can be only one pending RPC for a particular peer and query per definition.
-        for (_peer_id, event) in self.queries.iter_mut().filter_map(|q| {
-            q.inner
-                .pending_rpcs
-                .iter()
-                .position(|(p, _)| p == &peer)
-                .map(|p| q.inner.pending_rpcs.remove(p))
-        }) {
-            handler.on_behaviour_event(event)
+        for query in self.queries.iter_mut() {
+            if let Some(pos) = query.inner.pending_rpcs.iter().position(|r| r.peer == peer) {
+                let rpc = query.inner.pending_rpcs.remove(pos);
+                query.on_connection_wait(rpc.since.elapsed());
+                handler.on_behaviour_event(rpc.request)
+            }
         }
     }
 }
@@ -2153,6 +3490,14 @@ fn exp_decrease(ttl: Duration, exp: u32) -> Duration {
     Duration::from_secs(ttl.as_secs().checked_shr(exp).unwrap_or(0))
 }
 
+/// Strips a trailing `/p2p` component from the address.
+fn without_p2p(mut addr: Multiaddr) -> Multiaddr {
+    if let Some(libp2p_core::multiaddr::Protocol::P2p(_)) = addr.iter().last() {
+        addr.pop();
+    }
+    addr
+}
+
 impl<TStore> NetworkBehaviour for Behaviour<TStore>
 where
     TStore: RecordStore + Send + 'static,
@@ -2237,6 +3582,49 @@ where
             }
         }
 
+        // Finally, ask the external resolver, if any, on behalf of the queries waiting for the peer.
+        if let Some(resolver) = self.address_resolver.as_ref() {
+            let mut waiting_queries = self
+                .queries
+                .iter_mut()
+                .filter(|q| q.inner.pending_rpcs.iter().any(|r| r.peer == peer_id))
+                .peekable();
+
+            if waiting_queries.peek().is_some() {
+                let address_filter = self.address_filter.as_ref();
+                let resolved = resolver(&peer_id)
+                    .into_iter()
+                    .filter(|a| address_filter.map_or(true, |f| f(a)))
+                    .filter_map(|a| a.with_p2p(peer_id).ok())
+                    .filter(|a| !peer_addrs.contains(a))
+                    .collect::<Vec<_>>();
+
+                if !resolved.is_empty() {
+                    tracing::debug!(
+                        peer=%peer_id,
+                        "Resolved {} additional address(es) for peer",
+                        resolved.len()
+                    );
+
+                    if self.remember_resolved_addresses {
+                        for query in waiting_queries {
+                            let addrs = query.inner.addresses.entry(peer_id).or_default();
+                            for addr in &resolved {
+                                if !addrs.contains(addr) {
+                                    addrs.push(addr.clone());
+                                }
+                            }
+                        }
+                    }
+                    peer_addrs.extend(resolved);
+                }
+            }
+        }
+
+        // Remove duplicates, keeping the first and thus most preferred occurrence.
+        let mut seen = HashSet::new();
+        peer_addrs.retain(|addr| seen.insert(without_p2p(addr.clone())));
+
         Ok(peer_addrs)
     }
 
@@ -2270,7 +3658,7 @@ where
             }
 
             HandlerEvent::FindNodeReq { key, request_id } => {
-                let closer_peers = self.find_closest(&kbucket::Key::new(key), &source);
+                let closer_peers = self.find_closest_cached(key, &source);
 
                 self.queued_events
                     .push_back(ToSwarm::GenerateEvent(Event::InboundRequest {
@@ -2328,6 +3716,7 @@ where
                 self.discovered(&query_id, &source, peers);
                 if let Some(query) = self.queries.get_mut(&query_id) {
                     let stats = query.stats().clone();
+                    let path = query.path_of(&source);
                     if let QueryInfo::GetProviders {
                         ref key,
                         ref mut providers_found,
@@ -2347,11 +3736,21 @@ where
                                         providers,
                                     },
                                 )),
-                                step: step.clone(),
+                                step: step.with_path(path),
                                 stats,
                             },
                         ));
                         *step = step.next();
+                    } else if let QueryInfo::PutRecord {
+                        phase: PutRecordPhase::GetProviders { ref mut providers },
+                        ..
+                    } = query.inner.info
+                    {
+                        for peer in provider_peers {
+                            if !providers.contains(&peer.node_id) {
+                                providers.push(peer.node_id);
+                            }
+                        }
                     }
                 }
             }
@@ -2363,9 +3762,11 @@ where
                     error
                 );
                 // If the query to which the error relates is still active,
-                // signal the failure w.r.t. `source`.
+                // retry the request or signal the failure w.r.t. `source`.
                 if let Some(query) = self.queries.get_mut(&query_id) {
-                    query.on_failure(&source)
+                    if !query.try_retry(&source, Instant::now()) {
+                        query.on_failure(&source)
+                    }
                 }
             }
 
@@ -2420,6 +3821,7 @@ where
             } => {
                 if let Some(query) = self.queries.get_mut(&query_id) {
                     let stats = query.stats().clone();
+                    let path = query.path_of(&source);
                     if let QueryInfo::GetRecord {
                         key,
                         ref mut step,
@@ -2440,7 +3842,7 @@ where
                                     result: QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(
                                         record,
                                     ))),
-                                    step: step.clone(),
+                                    step: step.with_path(path),
                                     stats,
                                 },
                             ));
@@ -2462,6 +3864,21 @@ where
                                 }
                             }
                         }
+                    } else if let QueryInfo::GetRecords {
+                        records: found,
+                        quorum,
+                        ..
+                    } = &mut query.inner.info
+                    {
+                        if let Some(record) = record {
+                            found.push(PeerRecord {
+                                peer: Some(source),
+                                record,
+                            });
+                            if found.len() >= quorum.get() {
+                                query.finish();
+                            }
+                        }
                     }
                 }
 
@@ -2472,7 +3889,28 @@ where
                 self.record_received(source, connection, request_id, record);
             }
 
+            HandlerEvent::PutRecordRes {
+                key,
+                error: Some(error),
+                query_id,
+                ..
+            } => {
+                self.response_received(source);
+                tracing::debug!(peer=%source, record=?key, "Record rejected by peer: {error}");
+                if let Some(query) = self.queries.get_mut(&query_id) {
+                    query.on_failure(&source);
+                }
+                self.queued_events
+                    .push_back(ToSwarm::GenerateEvent(Event::RecordRejected {
+                        id: query_id,
+                        peer: source,
+                        key,
+                        error,
+                    }));
+            }
+
             HandlerEvent::PutRecordRes { query_id, .. } => {
+                self.response_received(source);
                 if let Some(query) = self.queries.get_mut(&query_id) {
                     query.on_success(&source, vec![]);
                     if let QueryInfo::PutRecord {
@@ -2514,43 +3952,66 @@ where
         // Calculate the available capacity for queries triggered by background jobs.
         let mut jobs_query_capacity = JOBS_MAX_QUERIES.saturating_sub(self.queries.size());
 
-        // Run the periodic provider announcement job.
-        if let Some(mut job) = self.add_provider_job.take() {
-            let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
-            for i in 0..num {
-                if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
-                    self.start_add_provider(r.key, AddProviderContext::Republish)
-                } else {
-                    jobs_query_capacity -= i;
-                    break;
+        // Run the background activity, unless paused.
+        if !self.background_paused {
+            // Run the periodic provider announcement job.
+            if let Some(mut job) = self.add_provider_job.take() {
+                // Provider records sharing the same closest peers are republished by a
+                // single query, so the number of batches rather than records is limited.
+                let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
+                let mut batches = HashMap::<Vec<PeerId>, Vec<record::Key>>::new();
+                let mut num_keys = 0;
+                while batches.len() < num && num_keys < num * JOBS_MAX_PROVIDER_BATCH_SIZE {
+                    let Poll::Ready(r) = job.poll(cx, &mut self.store, now) else {
+                        break;
+                    };
+                    batches
+                        .entry(self.provider_batch(&r.key))
+                        .or_default()
+                        .push(r.key);
+                    num_keys += 1;
                 }
+                jobs_query_capacity -= batches.len();
+                self.start_add_providers(batches, AddProviderContext::Republish);
+                self.add_provider_job = Some(job);
             }
-            self.add_provider_job = Some(job);
-        }
 
-        // Run the periodic record replication / publication job.
-        if let Some(mut job) = self.put_record_job.take() {
-            let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
-            for _ in 0..num {
-                if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
-                    let context =
-                        if r.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
-                            PutRecordContext::Republish
-                        } else {
-                            PutRecordContext::Replicate
-                        };
-                    self.start_put_record(r, Quorum::All, context)
-                } else {
-                    break;
+            // Run the periodic record replication / publication job.
+            if let Some(mut job) = self.put_record_job.take() {
+                let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
+                for _ in 0..num {
+                    if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
+                        let context =
+                            if r.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
+                                PutRecordContext::Republish
+                            } else {
+                                PutRecordContext::Replicate
+                            };
+                        self.start_put_record(r, Quorum::All, context)
+                    } else {
+                        break;
+                    }
+                }
+                self.put_record_job = Some(job);
+            }
+
+            // Check the liveness of peers in the routing table periodically.
+            if let Some((interval, delay)) = self.liveness_check.as_mut() {
+                let mut check = false;
+                while delay.poll_unpin(cx).is_ready() {
+                    delay.reset(*interval);
+                    check = true;
+                }
+                if check {
+                    self.check_liveness();
                 }
             }
-            self.put_record_job = Some(job);
-        }
 
-        // Poll bootstrap periodically and automatically.
-        if let Poll::Ready(()) = self.bootstrap_status.poll_next_bootstrap(cx) {
-            if let Err(e) = self.bootstrap() {
-                tracing::warn!("Failed to trigger bootstrap: {e}");
+            // Poll bootstrap periodically and automatically.
+            if let Poll::Ready(()) = self.bootstrap_status.poll_next_bootstrap(cx) {
+                if let Err(e) = self.bootstrap() {
+                    tracing::warn!("Failed to trigger bootstrap: {e}");
+                }
             }
         }
 
@@ -2562,6 +4023,15 @@ where
 
             // Drain applied pending entries from the routing table.
             if let Some(entry) = self.kbuckets.take_applied_pending() {
+                self.find_node_cache.clear();
+                if let Some(evicted) = &entry.evicted {
+                    let peer = *evicted.key.preimage();
+                    self.log_routing_change(RoutingChange::Removed { peer });
+                }
+                self.log_routing_change(RoutingChange::upserted(
+                    *entry.inserted.key.preimage(),
+                    &entry.inserted.value,
+                ));
                 let kbucket::Node { key, value } = entry.inserted;
                 let event = Event::RoutingUpdated {
                     bucket_range: self
@@ -2581,6 +4051,9 @@ where
             loop {
                 match self.queries.poll(now) {
                     QueryPoolState::Finished(q) => {
+                        if let Some(target) = q.target() {
+                            self.kbuckets.on_lookup_finished(target, now);
+                        }
                         if let Some(event) = self.query_finished(q) {
                             return Poll::Ready(ToSwarm::GenerateEvent(event));
                         }
@@ -2591,7 +4064,7 @@ where
                         }
                     }
                     QueryPoolState::Waiting(Some((query, peer_id))) => {
-                        let event = query.inner.info.to_request(query.id());
+                        let event = query.inner.info.to_request(query.id(), &peer_id);
                         // TODO: AddProvider requests yield no response, so the query completes
                         // as soon as all requests have been sent. However, the handler should
                         // better emit an event when the request has been sent (and report
@@ -2600,6 +4073,10 @@ where
                         if let QueryInfo::AddProvider {
                             phase: AddProviderPhase::AddProvider { .. },
                             ..
+                        }
+                        | QueryInfo::AddProviders {
+                            phase: AddProvidersPhase::AddProvider { .. },
+                            ..
                         } = &query.inner.info
                         {
                             query.on_success(&peer_id, vec![])
@@ -2612,10 +4089,41 @@ where
                                 handler: NotifyHandler::Any,
                             });
                         } else if &peer_id != self.kbuckets.local_key().preimage() {
-                            query.inner.pending_rpcs.push((peer_id, event));
-                            self.queued_events.push_back(ToSwarm::Dial {
-                                opts: DialOpts::peer_id(peer_id).build(),
-                            });
+                            let distance = query
+                                .target()
+                                .map(|t| t.distance(&kbucket::Key::from(peer_id)));
+                            let pending = &mut query.inner.pending_rpcs;
+                            // Keep the pending RPCs ordered by increasing distance to the target.
+                            let pos = pending
+                                .iter()
+                                .position(|r| r.distance > distance)
+                                .unwrap_or(pending.len());
+                            pending.insert(
+                                pos,
+                                PendingRpc {
+                                    peer: peer_id,
+                                    request: event,
+                                    distance,
+                                    since: now,
+                                },
+                            );
+                            let evicted = self
+                                .max_pending_rpcs
+                                .filter(|max| pending.len() > max.get())
+                                .and_then(|_| pending.pop());
+                            if let Some(evicted) = &evicted {
+                                tracing::debug!(
+                                    peer=%evicted.peer,
+                                    query=?query.id(),
+                                    "Dropping RPC waiting for a connection in favour of closer peers"
+                                );
+                                query.on_evicted(&evicted.peer);
+                            }
+                            if evicted.map_or(true, |e| e.peer != peer_id) {
+                                self.queued_events.push_back(ToSwarm::Dial {
+                                    opts: DialOpts::peer_id(peer_id).build(),
+                                });
+                            }
                         }
                     }
                     QueryPoolState::Waiting(None) | QueryPoolState::Idle => break,
@@ -2638,7 +4146,16 @@ where
         let external_addresses_changed = self.external_addresses.on_swarm_event(&event);
 
         if self.auto_mode && external_addresses_changed {
-            self.determine_mode_from_external_addresses();
+            let reason = match event {
+                FromSwarm::ExternalAddrConfirmed(ExternalAddrConfirmed { addr }) => {
+                    ModeChangeReason::ExternalAddrConfirmed(addr.clone())
+                }
+                FromSwarm::ExternalAddrExpired(ExternalAddrExpired { addr }) => {
+                    ModeChangeReason::ExternalAddrExpired(addr.clone())
+                }
+                _ => unreachable!("Only changes to external addresses change them"),
+            };
+            self.determine_mode_from_external_addresses(reason);
         }
 
         match event {
@@ -2770,7 +4287,42 @@ pub enum Event {
     ///
     /// This happens in response to an external
     /// address being added or removed.
-    ModeChanged { new_mode: Mode },
+    ModeChanged {
+        new_mode: Mode,
+        /// Why the mode changed.
+        reason: ModeChangeReason,
+    },
+
+    /// A record or provider record sent by a remote peer could not be stored
+    /// in the local [`RecordStore`].
+    ///
+    /// For a record, the reason is reported back to the remote.
+    InboundStoreFailed {
+        source: PeerId,
+        key: record::Key,
+        error: store::Error,
+    },
+
+    /// A bootstrap finished, i.e. its last step was reported via
+    /// [`Event::OutboundQueryProgressed`].
+    ///
+    /// The report describes the state of the routing table after the bootstrap and whether
+    /// it meets the configured [`BootstrapCriteria`], allowing to decide whether to retry.
+    BootstrapFinished {
+        /// The ID of the bootstrap query.
+        id: QueryId,
+        report: BootstrapReport,
+    },
+
+    /// A remote peer did not store the record of an outbound [`QueryResult::PutRecord`]
+    /// query and reported the reason. The peer is not counted towards the quorum.
+    RecordRejected {
+        /// The ID of the query.
+        id: QueryId,
+        peer: PeerId,
+        key: record::Key,
+        error: store::Error,
+    },
 }
 
 /// Information about progress events.
@@ -2780,6 +4332,12 @@ pub struct ProgressStep {
     pub count: NonZeroUsize,
     /// Is this the final event?
     pub last: bool,
+    /// The index of the disjoint path whose peer produced the result of this event.
+    ///
+    /// Only set for results received from a single peer of a query using disjoint
+    /// paths, see [`Config::disjoint_query_paths`]. Comparing the results of the
+    /// different paths allows e.g. to detect peers returning conflicting answers.
+    pub path: Option<usize>,
 }
 
 impl ProgressStep {
@@ -2787,6 +4345,7 @@ impl ProgressStep {
         Self {
             count: NonZeroUsize::new(1).expect("1 to be greater than 0."),
             last: false,
+            path: None,
         }
     }
 
@@ -2800,7 +4359,19 @@ impl ProgressStep {
         assert!(!self.last);
         let count = NonZeroUsize::new(self.count.get() + 1).expect("Adding 1 not to result in 0.");
 
-        Self { count, last: false }
+        Self {
+            count,
+            last: false,
+            path: None,
+        }
+    }
+
+    /// Attributes the event to the given disjoint path.
+    fn with_path(&self, path: Option<usize>) -> Self {
+        Self {
+            path,
+            ..self.clone()
+        }
     }
 }
 
@@ -2858,6 +4429,9 @@ pub enum QueryResult {
     /// The result of [`Behaviour::get_record`].
     GetRecord(GetRecordResult),
 
+    /// The result of the lookup of a key by [`Behaviour::get_records`].
+    GetRecords(GetRecordsResult),
+
     /// The result of [`Behaviour::put_record`].
     PutRecord(PutRecordResult),
 
@@ -2925,6 +4499,18 @@ impl GetRecordError {
     }
 }
 
+/// The result of the lookup of a key by [`Behaviour::get_records`].
+pub type GetRecordsResult = Result<GetRecordsOk, GetRecordError>;
+
+/// The successful lookup of a key by [`Behaviour::get_records`].
+#[derive(Debug, Clone)]
+pub struct GetRecordsOk {
+    /// The key looked up.
+    pub key: record::Key,
+    /// The records found, at least as many as the requested quorum.
+    pub records: Vec<PeerRecord>,
+}
+
 /// The result of [`Behaviour::put_record`].
 pub type PutRecordResult = Result<PutRecordOk, PutRecordError>;
 
@@ -2992,6 +4578,42 @@ pub enum BootstrapError {
     },
 }
 
+/// The criteria the routing table must meet for a bootstrap to be considered successful.
+///
+/// See [`Config::set_bootstrap_criteria`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootstrapCriteria {
+    /// The minimum number of peers in the routing table.
+    pub min_peers: usize,
+    /// The minimum number of non-empty buckets the peers are spread across.
+    pub min_buckets: usize,
+}
+
+impl Default for BootstrapCriteria {
+    fn default() -> Self {
+        BootstrapCriteria {
+            min_peers: 1,
+            min_buckets: 1,
+        }
+    }
+}
+
+/// The outcome of a finished bootstrap, see [`Event::BootstrapFinished`].
+#[derive(Debug, Clone)]
+pub struct BootstrapReport {
+    /// Whether the routing table meets the configured [`BootstrapCriteria`].
+    pub success: bool,
+    /// The number of peers in the routing table.
+    pub num_peers: usize,
+    /// The number of entries of each non-empty bucket, by bucket index, i.e.
+    /// the base 2 logarithm of the distances covered by the bucket.
+    pub bucket_fill: Vec<(u32, usize)>,
+    /// The number of failed requests, e.g. due to failed dials, across all steps.
+    pub num_failures: u32,
+    /// The time elapsed from the start of the bootstrap until its last step finished.
+    pub duration: Duration,
+}
+
 /// The result of [`Behaviour::get_closest_peers`].
 pub type GetClosestPeersResult = Result<GetClosestPeersOk, GetClosestPeersError>;
 
@@ -3122,11 +4744,21 @@ struct QueryInner {
     info: QueryInfo,
     /// Addresses of peers discovered during a query.
     addresses: FnvHashMap<PeerId, SmallVec<[Multiaddr; 8]>>,
-    /// A map of pending requests to peers.
+    /// The pending requests to peers, ordered by increasing distance to the target.
     ///
     /// A request is pending if the targeted peer is not currently connected
     /// and these requests are sent as soon as a connection to the peer is established.
-    pending_rpcs: SmallVec<[(PeerId, HandlerIn); K_VALUE.get()]>,
+    pending_rpcs: SmallVec<[PendingRpc; K_VALUE.get()]>,
+}
+
+/// A request of a query waiting for a connection to its peer.
+struct PendingRpc {
+    peer: PeerId,
+    request: HandlerIn,
+    /// The distance of the peer to the target of the query, if the query has a target.
+    distance: Option<Distance>,
+    /// When the request started waiting.
+    since: Instant,
 }
 
 impl QueryInner {
@@ -3139,7 +4771,7 @@ impl QueryInner {
     }
 }
 
-/// The context of a [`QueryInfo::AddProvider`] query.
+/// The context of a [`QueryInfo::AddProvider`] or [`QueryInfo::AddProviders`] query.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AddProviderContext {
     /// The context is a [`Behaviour::start_providing`] operation.
@@ -3210,6 +4842,17 @@ pub enum QueryInfo {
         context: AddProviderContext,
     },
 
+    /// A batch of provider announcements initiated by [`Behaviour::start_providing_many`]
+    /// or by the periodic republishing of provider records.
+    AddProviders {
+        /// The record keys of the batch.
+        keys: Vec<record::Key>,
+        /// The current phase of the query.
+        phase: AddProvidersPhase,
+        /// The execution context of the query.
+        context: AddProviderContext,
+    },
+
     /// A (repeated) query initiated by [`Behaviour::put_record`].
     PutRecord {
         record: Record,
@@ -3233,12 +4876,32 @@ pub enum QueryInfo {
         /// i.e. the peers that are candidates for caching the record.
         cache_candidates: BTreeMap<kbucket::Distance, PeerId>,
     },
+
+    /// A batch of record lookups initiated by [`Behaviour::get_records`].
+    GetRecords {
+        /// The key currently looked up.
+        key: record::Key,
+        /// The keys remaining to be looked up, in order.
+        remaining: vec::IntoIter<record::Key>,
+        /// The number of records after which the lookup of a key finishes.
+        quorum: NonZeroUsize,
+        /// The records found so far for the current key.
+        records: Vec<PeerRecord>,
+        /// Current index of events.
+        step: ProgressStep,
+    },
+
+    /// A liveness check of a peer in the routing table, see [`Config::set_liveness_check_interval`].
+    LivenessCheck {
+        /// The peer whose liveness is checked.
+        peer: PeerId,
+    },
 }
 
 impl QueryInfo {
     /// Creates an event for a handler to issue an outgoing request in the
     /// context of a query.
-    fn to_request(&self, query_id: QueryId) -> HandlerIn {
+    fn to_request(&self, query_id: QueryId, peer: &PeerId) -> HandlerIn {
         match &self {
             QueryInfo::Bootstrap { peer, .. } => HandlerIn::FindNodeReq {
                 key: peer.to_bytes(),
@@ -3248,6 +4911,10 @@ impl QueryInfo {
                 key: key.clone(),
                 query_id,
             },
+            QueryInfo::LivenessCheck { peer } => HandlerIn::FindNodeReq {
+                key: peer.to_bytes(),
+                query_id,
+            },
             QueryInfo::GetProviders { key, .. } => HandlerIn::GetProvidersReq {
                 key: key.clone(),
                 query_id,
@@ -3271,15 +4938,41 @@ impl QueryInfo {
                     query_id,
                 },
             },
-            QueryInfo::GetRecord { key, .. } => HandlerIn::GetRecord {
-                key: key.clone(),
-                query_id,
+            QueryInfo::AddProviders { keys, phase, .. } => match phase {
+                AddProvidersPhase::GetClosestPeers => HandlerIn::FindNodeReq {
+                    key: keys[0].to_vec(),
+                    query_id,
+                },
+                AddProvidersPhase::AddProvider {
+                    provider_id,
+                    external_addresses,
+                    keys_per_peer,
+                    ..
+                } => HandlerIn::AddProviders {
+                    keys: keys_per_peer.get(peer).cloned().unwrap_or_default(),
+                    provider: crate::protocol::KadPeer {
+                        node_id: *provider_id,
+                        multiaddrs: external_addresses.clone(),
+                        connection_ty: crate::protocol::ConnectionType::Connected,
+                    },
+                    query_id,
+                },
             },
+            QueryInfo::GetRecord { key, .. } | QueryInfo::GetRecords { key, .. } => {
+                HandlerIn::GetRecord {
+                    key: key.clone(),
+                    query_id,
+                }
+            }
             QueryInfo::PutRecord { record, phase, .. } => match phase {
                 PutRecordPhase::GetClosestPeers => HandlerIn::FindNodeReq {
                     key: record.key.to_vec(),
                     query_id,
                 },
+                PutRecordPhase::GetProviders { .. } => HandlerIn::GetProvidersReq {
+                    key: record.key.clone(),
+                    query_id,
+                },
                 PutRecordPhase::PutRecord { .. } => HandlerIn::PutRecord {
                     record: record.clone(),
                     query_id,
@@ -3303,7 +4996,27 @@ pub enum AddProviderPhase {
         /// The external addresses of the provider being advertised.
         external_addresses: Vec<Multiaddr>,
         /// Query statistics from the finished `GetClosestPeers` phase.
-        get_closest_peers_stats: QueryStats,
+        get_closest_peers_stats: Box<QueryStats>,
+    },
+}
+
+/// The phases of a [`QueryInfo::AddProviders`] query.
+#[derive(Debug, Clone)]
+pub enum AddProvidersPhase {
+    /// The query is searching for the closest nodes to the keys of the batch.
+    GetClosestPeers,
+
+    /// The query advertises the local node as a provider for the keys of the batch
+    /// to the closest nodes to each key.
+    AddProvider {
+        /// The local peer ID that is advertised as a provider.
+        provider_id: PeerId,
+        /// The external addresses of the provider being advertised.
+        external_addresses: Vec<Multiaddr>,
+        /// The keys announced to each of the closest nodes.
+        keys_per_peer: HashMap<PeerId, Vec<record::Key>>,
+        /// Query statistics from the finished `GetClosestPeers` phase.
+        get_closest_peers_stats: Box<QueryStats>,
     },
 }
 
@@ -3313,6 +5026,13 @@ pub enum PutRecordPhase {
     /// The query is searching for the closest nodes to the record key.
     GetClosestPeers,
 
+    /// The query is searching for the closest nodes to the record key and
+    /// for providers of the key, see [`RecordPlacement::ClosestAndProviders`].
+    GetProviders {
+        /// The providers of the key found so far.
+        providers: Vec<PeerId>,
+    },
+
     /// The query is replicating the record to the closest nodes to the key.
     PutRecord {
         /// A list of peers the given record has been successfully replicated to.
@@ -3423,6 +5143,20 @@ impl fmt::Display for Mode {
     }
 }
 
+/// Why the [`Mode`] changed automatically, see [`Event::ModeChanged`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModeChangeReason {
+    /// The given external address was confirmed, e.g. by AutoNAT, hence we assume to be
+    /// reachable by other peers and switched to [`Mode::Server`].
+    ExternalAddrConfirmed(Multiaddr),
+    /// The given external address expired and was the last confirmed one, hence we switched to
+    /// [`Mode::Client`].
+    ExternalAddrExpired(Multiaddr),
+    /// The automatic mode was re-enabled via [`Behaviour::set_mode`] and the mode was
+    /// determined from the confirmed external addresses.
+    AutoModeEnabled,
+}
+
 fn to_comma_separated_list<T>(confirmed_external_addresses: &[T]) -> String
 where
     T: ToString,
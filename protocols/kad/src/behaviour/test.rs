@@ -23,7 +23,7 @@
 use super::*;
 
 use crate::record::{store::MemoryStore, Key};
-use crate::{PROTOCOL_NAME, SHA_256_MH};
+use crate::{MemoryRoutingTableStore, PROTOCOL_NAME, SHA_256_MH};
 use futures::{executor::block_on, future::poll_fn, prelude::*};
 use futures_timer::Delay;
 use libp2p_core::{
@@ -233,6 +233,60 @@ fn bootstrap() {
     QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _)
 }
 
+#[test]
+fn bootstrap_reports_routing_table_against_criteria() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(None);
+    cfg.set_bootstrap_criteria(BootstrapCriteria {
+        min_peers: 4,
+        min_buckets: 1,
+    });
+
+    let mut swarms = build_connected_nodes_with_config(4, 4, cfg)
+        .into_iter()
+        .map(|(_a, s)| s)
+        .collect::<Vec<_>>();
+    let qid = swarms[0].behaviour_mut().bootstrap().unwrap();
+    let mut last_step = false;
+
+    block_on(poll_fn(move |ctx| {
+        for (i, swarm) in swarms.iter_mut().enumerate() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::Bootstrap(_),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!((i, id), (0, qid));
+                        last_step = step.last;
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::BootstrapFinished {
+                        id,
+                        report,
+                    }))) => {
+                        assert_eq!((i, id), (0, qid));
+                        assert!(last_step, "Report must follow the last step.");
+                        // Only the three peers known from the start are in the routing table.
+                        assert_eq!(report.num_peers, 3);
+                        assert!(!report.success);
+                        assert_eq!(report.bucket_fill.iter().map(|(_, n)| n).sum::<usize>(), 3);
+                        assert_eq!(report.num_failures, 0);
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }))
+}
+
 #[test]
 fn query_iter() {
     fn distances<K>(key: &kbucket::Key<K>, peers: Vec<PeerId>) -> Vec<Distance> {
@@ -771,7 +825,7 @@ fn get_record() {
                     Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
                         id,
                         result: QueryResult::GetRecord(Ok(r)),
-                        step: ProgressStep { count, last },
+                        step: ProgressStep { count, last, .. },
                         ..
                     }))) => {
                         assert_eq!(id, qid);
@@ -802,6 +856,153 @@ fn get_record() {
     }))
 }
 
+#[test]
+fn get_records() {
+    let mut swarms = build_nodes(3);
+
+    // Let first peer know of second peer and second peer know of third peer.
+    for i in 0..2 {
+        let (peer_id, address) = (
+            *Swarm::local_peer_id(&swarms[i + 1].1),
+            swarms[i + 1].0.clone(),
+        );
+        swarms[i].1.behaviour_mut().add_address(&peer_id, address);
+    }
+
+    // Drop the swarm addresses.
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let records = [
+        Record::new(random_multihash(), vec![1]),
+        Record::new(random_multihash(), vec![2]),
+    ];
+    let missing = record::Key::from(random_multihash());
+    for record in &records {
+        swarms[2].behaviour_mut().store.put(record.clone()).unwrap();
+    }
+    let keys = vec![
+        records[0].key.clone(),
+        missing.clone(),
+        records[1].key.clone(),
+    ];
+    let qid = swarms[0]
+        .behaviour_mut()
+        .get_records(keys, Quorum::One)
+        .unwrap();
+    let mut found = HashMap::new();
+    let mut not_found = Vec::new();
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetRecords(result),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        match result {
+                            Ok(GetRecordsOk { key, records }) => {
+                                found.insert(key, records);
+                            }
+                            Err(GetRecordError::NotFound { key, .. }) => not_found.push(key),
+                            Err(e) => panic!("Unexpected error: {e:?}"),
+                        }
+                        if step.last {
+                            assert_eq!(usize::from(step.count), 3);
+                            assert_eq!(not_found, vec![missing.clone()]);
+                            for record in &records {
+                                let peer_records = &found[&record.key];
+                                assert_eq!(peer_records.len(), 1);
+                                assert_eq!(peer_records[0].record, *record);
+                            }
+                            return Poll::Ready(());
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn large_records_are_exchanged_with_and_without_compression() {
+    let mut compressing = Config::new(PROTOCOL_NAME);
+    compressing.set_compression_threshold(Some(1024));
+
+    // Only the first and the last peer support compression.
+    let mut swarms = vec![
+        build_node_with_config(compressing.clone()),
+        build_node(),
+        build_node_with_config(compressing),
+    ];
+
+    // Let first peer know of second peer and second peer know of third peer.
+    for i in 0..2 {
+        let (peer_id, address) = (
+            *Swarm::local_peer_id(&swarms[i + 1].1),
+            swarms[i + 1].0.clone(),
+        );
+        swarms[i].1.behaviour_mut().add_address(&peer_id, address);
+    }
+
+    // Drop the swarm addresses.
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let record = Record::new(random_multihash(), vec![7; 8 * 1024]);
+
+    swarms[1].behaviour_mut().store.put(record.clone()).unwrap();
+    swarms[2].behaviour_mut().store.put(record.clone()).unwrap();
+    let qid = swarms[0].behaviour_mut().get_record(record.key.clone());
+    let mut found = 0;
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetRecord(Ok(r)),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        match r {
+                            GetRecordOk::FoundRecord(r) => {
+                                assert_eq!(r.record, record);
+                                found += 1;
+                            }
+                            GetRecordOk::FinishedWithNoAdditionalRecord { .. } => {
+                                assert_eq!(found, 2);
+                                return Poll::Ready(());
+                            }
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
 #[test]
 fn get_record_many() {
     // TODO: Randomise
@@ -833,7 +1034,7 @@ fn get_record_many() {
                     Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
                         id,
                         result: QueryResult::GetRecord(Ok(r)),
-                        step: ProgressStep { count: _, last },
+                        step: ProgressStep { count: _, last, .. },
                         ..
                     }))) => {
                         assert_eq!(id, qid);
@@ -1242,6 +1443,67 @@ fn disjoint_query_does_not_finish_before_all_paths_did() {
     }));
 }
 
+#[test]
+fn disjoint_query_results_are_attributed_to_paths() {
+    let mut config = Config::new(PROTOCOL_NAME);
+    config.disjoint_query_paths(true);
+    config.set_parallelism(NonZeroUsize::new(2).unwrap());
+    config.set_periodic_bootstrap_interval(None);
+    config.set_automatic_bootstrap_throttle(None);
+    let (_, mut alice) = build_node_with_config(config);
+    let key = Key::from(random_multihash());
+
+    let mut others = build_nodes(2);
+    for (addr, swarm) in &mut others {
+        let record = Record::new(key.clone(), swarm.local_peer_id().to_bytes());
+        swarm.behaviour_mut().store.put(record).unwrap();
+        alice
+            .behaviour_mut()
+            .add_address(swarm.local_peer_id(), addr.clone());
+    }
+    let query_id = alice.behaviour_mut().get_record(key);
+
+    let mut paths = HashMap::new();
+    let stats = block_on(poll_fn(|ctx| {
+        for (_, swarm) in &mut others {
+            while let Poll::Ready(Some(_)) = swarm.poll_next_unpin(ctx) {}
+        }
+        loop {
+            match alice.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetRecord(result),
+                    step,
+                    stats,
+                }))) if id == query_id => {
+                    if let Ok(GetRecordOk::FoundRecord(record)) = result {
+                        let path = step.path.expect("record to be attributed to a path");
+                        paths.insert(record.peer.unwrap(), path);
+                    }
+                    if step.last {
+                        return Poll::Ready(stats);
+                    }
+                }
+                // Ignore any other event.
+                Poll::Ready(Some(_)) => (),
+                e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                Poll::Pending => break,
+            }
+        }
+
+        Poll::Pending
+    }));
+
+    // Each path contacted one of the peers holding the record.
+    assert_eq!(paths.len(), 2);
+    assert_ne!(paths.values().next(), paths.values().nth(1));
+    assert_eq!(stats.paths().len(), 2);
+    for path in stats.paths() {
+        assert_eq!(path.num_requests(), 1);
+        assert_eq!(path.num_successes(), 1);
+    }
+}
+
 /// Tests that peers are not automatically inserted into
 /// the routing table with `BucketInserts::Manual`.
 #[test]
@@ -1319,6 +1581,7 @@ fn network_behaviour_on_address_change() {
         endpoint: &endpoint,
         failed_addresses: &[],
         other_established: 0,
+        data: &Default::default(),
     }));
 
     // At this point the remote is not yet known to support the
@@ -1535,3 +1798,1148 @@ fn get_providers_limit_n_2() {
 fn get_providers_limit_n_5() {
     get_providers_limit::<5>();
 }
+
+#[test]
+fn address_resolver_supplies_addresses_of_queried_peers() {
+    let (addr_b, swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_address_resolver(move |peer| {
+        if peer == &peer_b {
+            vec![addr_b.clone()]
+        } else {
+            vec![]
+        }
+    });
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+
+    // Peer B is neither in the routing table of A nor did A learn about it in a query.
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+    let qid =
+        swarm_a
+            .behaviour_mut()
+            .put_record_to(record.clone(), std::iter::once(peer_b), Quorum::One);
+
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::PutRecord(res),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert_eq!(res.unwrap().key, record.key);
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn resolved_addresses_are_not_remembered_if_disabled() {
+    let (addr_b, swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+    let addr_c: Multiaddr = Protocol::Memory(rand::random::<u64>()).into();
+
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    let resolved = vec![addr_b.clone().with_p2p(peer_b).unwrap(), addr_c.clone()];
+    cfg.set_address_resolver(move |peer| {
+        if peer == &peer_b {
+            resolved.clone()
+        } else {
+            vec![]
+        }
+    })
+    .set_remember_resolved_addresses(false);
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+
+    // Peer B is neither in the routing table of A nor did A learn about it in a query.
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+    let qid =
+        swarm_a
+            .behaviour_mut()
+            .put_record_to(record.clone(), std::iter::once(peer_b), Quorum::One);
+
+    // Once the query waits for peer B, the resolved addresses are used, with the address of B
+    // de-duplicated, but not added to the addresses of the query.
+    block_on(poll_fn(|ctx| {
+        while swarm_a.poll_next_unpin(ctx).is_ready() {}
+        Poll::Ready(())
+    }));
+    swarm_a
+        .behaviour_mut()
+        .queries
+        .get_mut(&qid)
+        .unwrap()
+        .inner
+        .addresses
+        .insert(peer_b, smallvec::smallvec![addr_b.clone(), addr_c.clone()]);
+    let addrs = swarm_a
+        .behaviour_mut()
+        .handle_pending_outbound_connection(
+            ConnectionId::new_unchecked(0),
+            Some(peer_b),
+            &[],
+            Endpoint::Dialer,
+        )
+        .unwrap();
+    assert_eq!(addrs, vec![addr_b.clone(), addr_c.clone()]);
+    assert_eq!(
+        swarm_a
+            .behaviour()
+            .queries
+            .get(&qid)
+            .unwrap()
+            .inner
+            .addresses[&peer_b],
+        smallvec::SmallVec::<[Multiaddr; 8]>::from_vec(vec![addr_b, addr_c])
+    );
+
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::PutRecord(res),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert_eq!(res.unwrap().key, record.key);
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn address_filter_discards_resolved_addresses() {
+    let (addr_b, swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    let resolved_addr = addr_b.clone();
+    cfg.set_address_resolver(move |_| vec![resolved_addr.clone()]);
+    cfg.set_address_filter(move |addr| addr != &addr_b);
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+
+    // The only known address of peer B is rejected by the filter, hence it can't be reached.
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+    let qid =
+        swarm_a
+            .behaviour_mut()
+            .put_record_to(record.clone(), std::iter::once(peer_b), Quorum::One);
+
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::PutRecord(res),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        match res {
+                            Err(PutRecordError::QuorumFailed { key, success, .. }) => {
+                                assert_eq!(key, record.key);
+                                assert!(success.is_empty());
+                            }
+                            res => panic!("Unexpected result: {res:?}"),
+                        }
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn record_ttl_override_is_respected_by_remote() {
+    let (_, mut swarm_a) = build_node();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_record_ttl(None);
+    let (addr_b, swarm_b) = build_node_with_config(cfg);
+    let peer_b = *swarm_b.local_peer_id();
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+
+    // Peer B does not expire records on its own, but must respect the record's expiration.
+    let record = Record::new(random_multihash(), vec![4, 5, 6]).with_ttl(Duration::from_secs(60));
+    swarm_a
+        .behaviour_mut()
+        .put_record_to(record.clone(), std::iter::once(peer_b), Quorum::One);
+
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        result: QueryResult::PutRecord(res),
+                        ..
+                    }))) => {
+                        assert_eq!(res.unwrap().key, record.key);
+                        let stored = swarms[1]
+                            .behaviour_mut()
+                            .store_mut()
+                            .get(&record.key)
+                            .expect("record to be stored")
+                            .into_owned();
+                        let expires = stored.expires.expect("record to expire");
+                        assert!(expires <= record.expires.unwrap() + Duration::from_secs(1));
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn query_tracer_observes_state_transitions() {
+    #[derive(Default)]
+    struct Trace {
+        contacted: Vec<PeerId>,
+        responses: Vec<PeerId>,
+        failures: Vec<PeerId>,
+        finished: Vec<QueryId>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Tracer(Arc<std::sync::Mutex<Trace>>);
+
+    impl QueryTracer for Tracer {
+        fn on_peer_contacted(&self, _: QueryId, peer: &PeerId) {
+            self.0.lock().unwrap().contacted.push(*peer);
+        }
+
+        fn on_response(&self, _: QueryId, peer: &PeerId, _: usize) {
+            self.0.lock().unwrap().responses.push(*peer);
+        }
+
+        fn on_failure(&self, _: QueryId, peer: &PeerId) {
+            self.0.lock().unwrap().failures.push(*peer);
+        }
+
+        fn on_finished(&self, query: QueryId, _: &QueryStats) {
+            self.0.lock().unwrap().finished.push(query);
+        }
+    }
+
+    let tracer = Tracer::default();
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_query_tracer(tracer.clone());
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+    let (addr_b, swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+
+    // Peer A knows about peer B and a few unreachable peers.
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+    let fake_peers = (0..3).map(|_| PeerId::random()).collect::<Vec<_>>();
+    for peer in &fake_peers {
+        swarm_a
+            .behaviour_mut()
+            .add_address(peer, Protocol::Udp(10u16).into());
+    }
+
+    let qid = swarm_a.behaviour_mut().get_closest_peers(PeerId::random());
+
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetClosestPeers(Ok(_)),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }));
+
+    let trace = tracer.0.lock().unwrap();
+    assert_eq!(trace.contacted.len(), 4);
+    assert_eq!(trace.responses, vec![peer_b]);
+    let mut failures = trace.failures.clone();
+    failures.sort();
+    let mut expected = fake_peers.clone();
+    expected.sort();
+    assert_eq!(failures, expected);
+    assert_eq!(trace.finished, vec![qid]);
+}
+
+#[test]
+fn bucket_refresh_prefers_stale_buckets_within_budget() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_bucket_refresh_budget(Some(NonZeroUsize::new(2).unwrap()));
+    let (_, mut swarm) = build_node_with_config(cfg);
+    let kad = swarm.behaviour_mut();
+    let local_key = *kad.kbuckets.local_key();
+
+    // Populate buckets 252 to 255 (and nothing closer), remembering one key per bucket.
+    let mut keys = HashMap::new();
+    while keys.len() < 4 {
+        let peer = PeerId::random();
+        let key = kbucket::Key::from(peer);
+        let Some(index) = local_key.distance(&key).ilog2() else {
+            continue;
+        };
+        if index >= 252 {
+            kad.add_address(&peer, multiaddr![Memory(1u64)]);
+            keys.entry(index).or_insert(key);
+        }
+    }
+
+    // Bucket 252 holds the closest neighbour and is never refreshed. Bucket 253 has never
+    // been looked up, bucket 254 was looked up before bucket 255.
+    let now = Instant::now();
+    kad.kbuckets.on_lookup_finished(&keys[&254], now);
+    kad.kbuckets
+        .on_lookup_finished(&keys[&255], now + Duration::from_secs(1));
+
+    let refreshed = kad
+        .buckets_to_refresh()
+        .iter()
+        .map(|b| b.range().0.ilog2().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(refreshed, vec![253, 254]);
+}
+
+#[test]
+fn liveness_check_evicts_unresponsive_peers() {
+    let (addr_b, swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(None);
+    cfg.set_liveness_check_interval(Some(Duration::from_millis(100)));
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+
+    // Nobody listens on the address of the fake peer, hence it can never be reached.
+    let fake_peer = PeerId::random();
+    swarm_a
+        .behaviour_mut()
+        .add_address(&fake_peer, Protocol::Memory(random::<u64>()).into());
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    // Ignore any event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        let kad = swarms[0].behaviour_mut();
+        let known = kad
+            .kbuckets
+            .iter()
+            .flat_map(|b| {
+                b.iter()
+                    .map(|e| (*e.node.key.preimage(), e.status))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<HashMap<_, _>>();
+        // The responsive peer is never evicted.
+        assert!(known.contains_key(&peer_b));
+        if !known.contains_key(&fake_peer) && known[&peer_b] == NodeStatus::Connected {
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn paused_background_activity_runs_on_resume() {
+    let (addr_b, mut swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+    swarm_b.behaviour_mut().pause_background();
+
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(Some(Duration::from_millis(10)));
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+    swarm_a.behaviour_mut().pause_background();
+    // Schedules an automatic bootstrap.
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+
+    let mut delay = Delay::new(Duration::from_millis(200));
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        result: QueryResult::Bootstrap(res),
+                        ..
+                    }))) => {
+                        assert!(
+                            !swarm.behaviour().is_background_paused(),
+                            "Bootstrap while paused"
+                        );
+                        // The paused peer still answers the requests.
+                        res.expect("bootstrap to succeed");
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        if swarms[0].behaviour().is_background_paused() && delay.poll_unpin(ctx).is_ready() {
+            assert_eq!(swarms[0].behaviour_mut().queries.size(), 0);
+            swarms[0].behaviour_mut().resume();
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn sequential_requests_reuse_outbound_stream() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_reuse_outbound_streams(true);
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_inbound_streams(1);
+    let (addr_b, swarm_b) = build_node_with_config(cfg);
+    let peer_b = *swarm_b.local_peer_id();
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+    swarm_a.behaviour_mut().get_closest_peers(PeerId::random());
+
+    let mut remaining = 3;
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(move |ctx| {
+        for i in 0..swarms.len() {
+            loop {
+                match swarms[i].poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        result: QueryResult::GetClosestPeers(res),
+                        stats,
+                        ..
+                    }))) => {
+                        res.expect("query to succeed");
+                        assert_eq!(stats.num_failures(), 0);
+                        remaining -= 1;
+                        if remaining == 0 {
+                            return Poll::Ready(());
+                        }
+                        // The next request to B reuses the idle outbound stream.
+                        swarms[0]
+                            .behaviour_mut()
+                            .get_closest_peers(PeerId::random());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn peer_scorer_defers_insertion_until_peer_responded() {
+    let observed = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_peer_scorer({
+        let observed = observed.clone();
+        move |peer: &PeerId, observation: &PeerObservation| {
+            observed
+                .lock()
+                .unwrap()
+                .push((*peer, observation.num_responses()));
+            crate::RespondedToQuery.allow_insert(peer, observation)
+        }
+    });
+    let (_, mut swarm_a) = build_node_with_config(cfg);
+    let (addr_b, mut swarm_b) = build_node();
+    let (addr_c, swarm_c) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+    let peer_c = *swarm_c.local_peer_id();
+
+    // Peer A only learns about peer C from the response of peer B.
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+    swarm_b.behaviour_mut().add_address(&peer_c, addr_c);
+
+    let qid = swarm_a.behaviour_mut().get_closest_peers(PeerId::random());
+
+    let mut swarms = [swarm_a, swarm_b, swarm_c];
+    block_on(poll_fn(|ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetClosestPeers(Ok(_)),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }));
+
+    // Peer C was rejected when the connection was established and
+    // only inserted once it responded to the request of peer A.
+    let observed = observed.lock().unwrap();
+    let observed_c = observed
+        .iter()
+        .filter(|(peer, _)| *peer == peer_c)
+        .map(|(_, responses)| *responses)
+        .collect::<Vec<_>>();
+    assert_eq!(observed_c, vec![0, 1]);
+    let bucket = swarms[0].behaviour_mut().kbucket(peer_c).unwrap();
+    assert!(bucket.iter().any(|e| *e.node.key.preimage() == peer_c));
+}
+
+#[test]
+fn start_providing_many_shares_queries_and_batches_announcements() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(None);
+    let mut swarms = build_fully_connected_nodes_with_config(4, cfg)
+        .into_iter()
+        .map(|(_, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let keys = (0..5)
+        .map(|_| record::Key::from(random_multihash()))
+        .collect::<Vec<_>>();
+    // All keys share the same closest peers, thus a single query.
+    let qids = swarms[0]
+        .behaviour_mut()
+        .start_providing_many(keys.clone())
+        .unwrap();
+    assert_eq!(qids.len(), 1);
+
+    let mut announced = Vec::new();
+    block_on(poll_fn(|ctx| {
+        for swarm in swarms.iter_mut() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::StartProviding(Ok(AddProviderOk { key })),
+                        step,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qids[0]);
+                        announced.push(key);
+                        assert_eq!(step.count.get(), announced.len());
+                        assert_eq!(step.last, announced.len() == keys.len());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        let provider = *swarms[0].local_peer_id();
+        let stored = swarms.iter_mut().skip(1).all(|swarm| {
+            keys.iter().all(|key| {
+                swarm
+                    .behaviour_mut()
+                    .store_mut()
+                    .providers(key)
+                    .iter()
+                    .any(|r| r.provider == provider)
+            })
+        });
+        if announced.len() == keys.len() && stored {
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }));
+
+    assert_eq!(
+        announced.into_iter().collect::<HashSet<_>>(),
+        keys.into_iter().collect::<HashSet<_>>()
+    );
+}
+
+#[test]
+fn republishing_providers_batches_announcements() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_periodic_bootstrap_interval(None);
+    cfg.set_automatic_bootstrap_throttle(None);
+    let mut swarms = build_fully_connected_nodes_with_config(4, cfg)
+        .into_iter()
+        .map(|(_, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let keys = (0..5)
+        .map(|_| record::Key::from(random_multihash()))
+        .collect::<Vec<_>>();
+    let local_id = *swarms[0].local_peer_id();
+    for key in &keys {
+        swarms[0]
+            .behaviour_mut()
+            .store_mut()
+            .add_provider(ProviderRecord::new(key.clone(), local_id, Vec::new()))
+            .unwrap();
+    }
+    swarms[0]
+        .behaviour_mut()
+        .add_provider_job
+        .as_mut()
+        .unwrap()
+        .asap();
+
+    let mut qids = HashSet::new();
+    let mut republished = Vec::new();
+    block_on(poll_fn(|ctx| {
+        for swarm in swarms.iter_mut() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::RepublishProvider(Ok(AddProviderOk { key })),
+                        ..
+                    }))) => {
+                        qids.insert(id);
+                        republished.push(key);
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        if republished.len() == keys.len() {
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }));
+
+    // All keys share the same closest peers, thus a single query.
+    assert_eq!(qids.len(), 1);
+    assert_eq!(
+        republished.into_iter().collect::<HashSet<_>>(),
+        keys.into_iter().collect::<HashSet<_>>()
+    );
+}
+
+#[test]
+fn store_errors_are_reported_to_publisher() {
+    let (_, mut swarm_a) = build_node();
+    let (addr_b, mut swarm_b) = build_node();
+    let peer_a = *swarm_a.local_peer_id();
+    let peer_b = *swarm_b.local_peer_id();
+
+    // Fill the store of peer B to capacity.
+    for _ in 0..store::MemoryStoreConfig::default().max_records {
+        let record = Record::new(random_multihash(), vec![1]);
+        swarm_b.behaviour_mut().store_mut().put(record).unwrap();
+    }
+
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+    let record = Record::new(random_multihash(), vec![2]);
+    let qid = swarm_a
+        .behaviour_mut()
+        .put_record(record.clone(), Quorum::One)
+        .unwrap();
+
+    let mut rejected = false;
+    let mut failed = false;
+    let mut inbound_failed = false;
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(|ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::RecordRejected {
+                        id,
+                        peer,
+                        key,
+                        error,
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert_eq!(peer, peer_b);
+                        assert_eq!(key, record.key);
+                        assert_eq!(error, store::Error::MaxRecords);
+                        rejected = true;
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::PutRecord(result),
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        assert!(matches!(
+                            result,
+                            Err(PutRecordError::QuorumFailed { success, .. }) if success.is_empty()
+                        ));
+                        failed = true;
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::InboundStoreFailed {
+                        source,
+                        key,
+                        error,
+                    }))) => {
+                        assert_eq!(source, peer_a);
+                        assert_eq!(key, record.key);
+                        assert_eq!(error, store::Error::MaxRecords);
+                        inbound_failed = true;
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        if rejected && failed && inbound_failed {
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }));
+}
+
+#[test]
+fn pending_rpcs_beyond_limit_are_evicted() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_max_pending_rpcs(NonZeroUsize::new(1));
+    let (_, mut swarm) = build_node_with_config(cfg);
+
+    // All peers are unreachable, thus their requests wait for a connection.
+    for _ in 0..3 {
+        swarm
+            .behaviour_mut()
+            .add_address(&PeerId::random(), Protocol::Udp(10u16).into());
+    }
+    let qid = swarm.behaviour_mut().get_closest_peers(PeerId::random());
+
+    let stats = block_on(poll_fn(|ctx| loop {
+        match swarm.poll_next_unpin(ctx) {
+            Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                id,
+                result: QueryResult::GetClosestPeers(_),
+                stats,
+                ..
+            }))) => {
+                assert_eq!(id, qid);
+                return Poll::Ready(stats);
+            }
+            // Ignore any other event.
+            Poll::Ready(Some(_)) => (),
+            e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+            Poll::Pending => return Poll::Pending,
+        }
+    }));
+
+    assert_eq!(stats.num_evicted(), 2);
+    assert_eq!(stats.num_failures(), 3);
+}
+
+#[test]
+fn connection_wait_of_pending_rpcs_is_measured() {
+    let (_, mut swarm_a) = build_node();
+    let (addr_b, swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b);
+    let qid = swarm_a.behaviour_mut().get_closest_peers(PeerId::random());
+
+    let mut swarms = [swarm_a, swarm_b];
+    let stats = block_on(poll_fn(|ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetClosestPeers(Ok(_)),
+                        stats,
+                        ..
+                    }))) => {
+                        assert_eq!(id, qid);
+                        return Poll::Ready(stats);
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }));
+
+    assert_eq!(stats.num_evicted(), 0);
+    assert!(stats.connection_wait() > Duration::ZERO);
+    assert_eq!(stats.max_connection_wait(), stats.connection_wait());
+}
+
+#[test]
+fn closer_peers_in_responses_are_limited_and_adapt_to_requester() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_num_closer_peers(NonZeroUsize::new(3));
+    cfg.set_adaptive_closer_peers(true);
+    let (addr_a, mut swarm_a) = build_node_with_config(cfg);
+    let (_, mut swarm_b) = build_node();
+    let peer_a = *swarm_a.local_peer_id();
+    let peer_b = *swarm_b.local_peer_id();
+
+    let peers = (0..10).map(|_| PeerId::random()).collect::<Vec<_>>();
+    for peer in &peers {
+        swarm_a
+            .behaviour_mut()
+            .add_address(peer, Protocol::Udp(10u16).into());
+    }
+    swarm_b.behaviour_mut().add_address(&peer_a, addr_a);
+
+    // A lookup of the requester's own key is answered in full.
+    swarm_b.behaviour_mut().get_closest_peers(peer_b);
+
+    // Other lookups are answered with the peers closer to the key than the requester.
+    let target = kbucket::Key::from(PeerId::random());
+    let requester_distance = target.distance(&kbucket::Key::from(peer_b));
+    let num_closer = peers
+        .iter()
+        .filter(|p| target.distance(&kbucket::Key::from(**p)) < requester_distance)
+        .count();
+    let mut expected = vec![3, num_closer.min(3)].into_iter();
+    let mut next_expected = expected.next();
+
+    block_on(poll_fn(move |ctx| {
+        loop {
+            match swarm_a.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(Event::InboundRequest {
+                    request: InboundRequest::FindNode { num_closer_peers },
+                }))) => {
+                    assert_eq!(Some(num_closer_peers), next_expected);
+                    next_expected = expected.next();
+                    match next_expected {
+                        Some(_) => {
+                            swarm_b
+                                .behaviour_mut()
+                                .get_closest_peers(*target.preimage());
+                        }
+                        None => return Poll::Ready(()),
+                    }
+                }
+                // Ignore any other event.
+                Poll::Ready(Some(_)) => (),
+                e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                Poll::Pending => break,
+            }
+        }
+        while let Poll::Ready(Some(_)) = swarm_b.poll_next_unpin(ctx) {}
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn find_node_responses_are_cached_until_routing_table_changes() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_find_node_cache_ttl(Some(Duration::from_secs(60)));
+    let (_, mut swarm) = build_node_with_config(cfg);
+    let kad = swarm.behaviour_mut();
+
+    let peers = (0..5).map(|_| PeerId::random()).collect::<Vec<_>>();
+    for peer in &peers {
+        kad.add_address(peer, Protocol::Udp(10u16).into());
+    }
+    let target = PeerId::random().to_bytes();
+
+    let closer_peers = kad.find_closest_cached(target.clone(), &PeerId::random());
+    assert_eq!(closer_peers.len(), 5);
+    assert!(kad.find_node_cache.get(&target, Instant::now()).is_some());
+
+    // Cached responses still exclude the requester.
+    let closer_peers = kad.find_closest_cached(target.clone(), &peers[0]);
+    assert_eq!(closer_peers.len(), 4);
+    assert!(closer_peers.iter().all(|p| p.node_id != peers[0]));
+
+    kad.add_address(&PeerId::random(), Protocol::Udp(10u16).into());
+    assert!(kad.find_node_cache.get(&target, Instant::now()).is_none());
+
+    let closer_peers = kad.find_closest_cached(target, &PeerId::random());
+    assert_eq!(closer_peers.len(), 6);
+}
+
+#[test]
+fn put_record_is_placed_at_providers_of_the_key() {
+    let mut cfg = Config::new(PROTOCOL_NAME);
+    cfg.set_record_placement(RecordPlacement::ClosestAndProviders);
+    let (_, mut publisher) = build_node_with_config(cfg);
+    let (addr_closest, mut closest) = build_node();
+    let (addr_provider, mut provider) = build_node();
+    let provider_id = *provider.local_peer_id();
+
+    // The publisher only learns about the provider from the provider record
+    // held by the closest peer.
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+    closest
+        .behaviour_mut()
+        .store
+        .add_provider(ProviderRecord::new(
+            record.key.clone(),
+            provider_id,
+            vec![addr_provider],
+        ))
+        .unwrap();
+    publisher
+        .behaviour_mut()
+        .add_address(closest.local_peer_id(), addr_closest);
+    let query_id = publisher
+        .behaviour_mut()
+        .put_record(record.clone(), Quorum::N(NonZeroUsize::new(2).unwrap()))
+        .unwrap();
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in [&mut closest, &mut provider] {
+            while let Poll::Ready(Some(_)) = swarm.poll_next_unpin(ctx) {}
+        }
+        loop {
+            match publisher.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::PutRecord(res),
+                    ..
+                }))) if id == query_id => {
+                    res.expect("record to be stored at the closest peer and the provider");
+                    let stored = provider.behaviour_mut().store.get(&record.key);
+                    assert_eq!(stored.map(|r| r.value.clone()), Some(record.value.clone()));
+                    return Poll::Ready(());
+                }
+                // Ignore any other event.
+                Poll::Ready(Some(_)) => (),
+                e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                Poll::Pending => break,
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn injected_peers_are_contacted_by_running_query() {
+    let (_, mut swarm) = build_node();
+    let (addr_other, mut other) = build_node();
+    let other_id = *other.local_peer_id();
+
+    // The only peer in the routing table is unreachable.
+    swarm
+        .behaviour_mut()
+        .add_address(&PeerId::random(), Protocol::Udp(10u16).into());
+    let query_id = swarm.behaviour_mut().get_closest_peers(PeerId::random());
+    assert!(swarm.behaviour_mut().inject_peers_into_query(
+        query_id,
+        [PeerInfo {
+            peer_id: other_id,
+            addrs: vec![addr_other],
+        }]
+    ));
+
+    block_on(poll_fn(move |ctx| {
+        while let Poll::Ready(Some(_)) = other.poll_next_unpin(ctx) {}
+        loop {
+            match swarm.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetClosestPeers(Ok(ok)),
+                    ..
+                }))) if id == query_id => {
+                    assert!(ok.peers.iter().any(|p| p.peer_id == other_id));
+                    return Poll::Ready(());
+                }
+                // Ignore any other event.
+                Poll::Ready(Some(_)) => (),
+                e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                Poll::Pending => break,
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn addresses_are_confirmed_on_connection() {
+    let (addr_b, swarm_b) = build_node();
+    let peer_b = *swarm_b.local_peer_id();
+    let addr_c: Multiaddr = Protocol::Memory(rand::random::<u64>()).into();
+    let (_, mut swarm_a) = build_node();
+
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_b.clone());
+    swarm_a.behaviour_mut().add_address(&peer_b, addr_c.clone());
+
+    let staleness = swarm_a.behaviour_mut().address_staleness();
+    assert_eq!(staleness.num_addresses, 2);
+    assert_eq!(staleness.num_unconfirmed, 2);
+    assert_eq!(staleness.quantile(0.5), None);
+
+    swarm_a.dial(addr_b.clone()).unwrap();
+    let mut swarms = [swarm_a, swarm_b];
+    block_on(poll_fn(|ctx| {
+        for swarm in &mut swarms {
+            while let Poll::Ready(Some(event)) = swarm.poll_next_unpin(ctx) {
+                if let SwarmEvent::ConnectionEstablished { peer_id, .. } = event {
+                    if peer_id == peer_b {
+                        return Poll::Ready(());
+                    }
+                }
+            }
+        }
+        Poll::Pending
+    }));
+
+    let [mut swarm_a, _] = swarms;
+    let staleness = swarm_a.behaviour_mut().address_staleness();
+    assert_eq!(staleness.num_addresses, 2);
+    assert_eq!(staleness.num_unconfirmed, 1);
+    assert!(staleness.quantile(0.5).unwrap() < Duration::from_secs(60));
+
+    let stale = swarm_a
+        .behaviour_mut()
+        .stale_addresses(Duration::from_secs(60));
+    assert_eq!(
+        stale,
+        vec![StaleAddress {
+            peer: peer_b,
+            address: addr_c.with_p2p(peer_b).unwrap(),
+            last_confirmed: None,
+        }]
+    );
+}
+
+#[test]
+fn routing_table_is_restored_from_store() {
+    #[derive(Clone, Default)]
+    struct SharedStore(Arc<std::sync::Mutex<MemoryRoutingTableStore>>);
+
+    impl RoutingTableStore for SharedStore {
+        fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+            self.0.lock().unwrap().append(bytes)
+        }
+
+        fn read(&mut self) -> io::Result<Vec<u8>> {
+            self.0.lock().unwrap().read()
+        }
+
+        fn replace(&mut self, bytes: &[u8]) -> io::Result<()> {
+            self.0.lock().unwrap().replace(bytes)
+        }
+    }
+
+    let store = SharedStore::default();
+    let (_, mut swarm) = build_node();
+    let restored = swarm
+        .behaviour_mut()
+        .set_routing_table_store(store.clone())
+        .unwrap();
+    assert_eq!(restored, 0);
+
+    let peers = (0..4).map(|_| PeerId::random()).collect::<Vec<_>>();
+    let addr: Multiaddr = Protocol::Memory(1).into();
+    for peer in &peers[..3] {
+        swarm.behaviour_mut().add_address(peer, addr.clone());
+    }
+    swarm.behaviour_mut().remove_peer(&peers[0]);
+    swarm.behaviour_mut().add_address(&peers[3], addr.clone());
+
+    // A crash tore the last change.
+    let mut bytes = store.0.lock().unwrap().bytes().to_vec();
+    bytes.pop();
+
+    let (_, mut restarted) = build_node();
+    let restored = restarted
+        .behaviour_mut()
+        .set_routing_table_store(MemoryRoutingTableStore::new(bytes))
+        .unwrap();
+    assert_eq!(restored, 2);
+
+    let mut in_table = restarted
+        .behaviour_mut()
+        .kbuckets()
+        .flat_map(|bucket| {
+            bucket
+                .iter()
+                .map(|entry| *entry.node.key.preimage())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    in_table.sort();
+    let mut expected = peers[1..3].to_vec();
+    expected.sort();
+    assert_eq!(in_table, expected);
+}
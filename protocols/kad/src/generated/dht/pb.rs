@@ -70,6 +70,7 @@ pub struct Message {
     pub record: Option<dht::pb::Record>,
     pub closerPeers: Vec<dht::pb::mod_Message::Peer>,
     pub providerPeers: Vec<dht::pb::mod_Message::Peer>,
+    pub storeError: u32,
 }
 
 impl<'a> MessageRead<'a> for Message {
@@ -83,6 +84,7 @@ impl<'a> MessageRead<'a> for Message {
                 Ok(26) => msg.record = Some(r.read_message::<dht::pb::Record>(bytes)?),
                 Ok(66) => msg.closerPeers.push(r.read_message::<dht::pb::mod_Message::Peer>(bytes)?),
                 Ok(74) => msg.providerPeers.push(r.read_message::<dht::pb::mod_Message::Peer>(bytes)?),
+                Ok(7104) => msg.storeError = r.read_uint32(bytes)?,
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -100,6 +102,7 @@ impl MessageWrite for Message {
         + self.record.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
         + self.closerPeers.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
         + self.providerPeers.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
+        + if self.storeError == 0u32 { 0 } else { 2 + sizeof_varint(*(&self.storeError) as u64) }
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -109,6 +112,7 @@ impl MessageWrite for Message {
         if let Some(ref s) = self.record { w.write_with_tag(26, |w| w.write_message(s))?; }
         for s in &self.closerPeers { w.write_with_tag(66, |w| w.write_message(s))?; }
         for s in &self.providerPeers { w.write_with_tag(74, |w| w.write_message(s))?; }
+        if self.storeError != 0u32 { w.write_with_tag(7104, |w| w.write_uint32(*&self.storeError))?; }
         Ok(())
     }
 }
@@ -22,12 +22,13 @@ use crate::behaviour::Mode;
 use crate::protocol::{
     KadInStreamSink, KadOutStreamSink, KadPeer, KadRequestMsg, KadResponseMsg, ProtocolConfig,
 };
-use crate::record::{self, Record};
+use crate::record::{self, store, Record};
 use crate::QueryId;
 use either::Either;
 use futures::channel::oneshot;
 use futures::prelude::*;
 use futures::stream::SelectAll;
+use futures_timer::Delay;
 use libp2p_core::{upgrade, ConnectedPoint};
 use libp2p_identity::PeerId;
 use libp2p_swarm::handler::{ConnectionEvent, FullyNegotiatedInbound, FullyNegotiatedOutbound};
@@ -35,6 +36,7 @@ use libp2p_swarm::{
     ConnectionHandler, ConnectionHandlerEvent, Stream, StreamUpgradeError, SubstreamProtocol,
     SupportedProtocols,
 };
+use smallvec::{smallvec, SmallVec};
 use std::collections::VecDeque;
 use std::task::Waker;
 use std::time::Duration;
@@ -42,6 +44,12 @@ use std::{error, fmt, io, marker::PhantomData, pin::Pin, task::Context, task::Po
 
 const MAX_NUM_STREAMS: usize = 32;
 
+/// The time after which an idle outbound substream is closed, if reuse is enabled.
+const OUTBOUND_STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The result of a request on an outbound substream, including the substream if it can be reused.
+type OutboundResult = io::Result<(Option<KadResponseMsg>, Option<KadOutStreamSink<Stream>>)>;
+
 /// Protocol handler that manages substreams for the Kademlia protocol
 /// on a single connection with a peer.
 ///
@@ -60,16 +68,20 @@ pub struct Handler {
     next_connec_unique_id: UniqueConnecId,
 
     /// List of active outbound streams.
-    outbound_substreams:
-        futures_bounded::FuturesTupleSet<io::Result<Option<KadResponseMsg>>, QueryId>,
+    outbound_substreams: futures_bounded::FuturesTupleSet<OutboundResult, QueryId>,
+
+    /// Outbound streams that completed their request and wait to be reused.
+    idle_outbound_substreams: VecDeque<(KadOutStreamSink<Stream>, Delay)>,
 
     /// Contains one [`oneshot::Sender`] per outbound stream that we have requested.
     pending_streams:
         VecDeque<oneshot::Sender<Result<KadOutStreamSink<Stream>, StreamUpgradeError<io::Error>>>>,
 
     /// List of outbound substreams that are waiting to become active next.
-    /// Contains the request we want to send, and the user data if we expect an answer.
-    pending_messages: VecDeque<(KadRequestMsg, QueryId)>,
+    /// Contains the requests we want to send, and the user data if we expect an answer.
+    ///
+    /// All requests of an entry are sent on the same stream, only the last one may have an answer.
+    pending_messages: VecDeque<(SmallVec<[KadRequestMsg; 1]>, QueryId)>,
 
     /// List of active inbound substreams with the state they are in.
     inbound_substreams: SelectAll<InboundSubstreamState>,
@@ -268,6 +280,8 @@ pub enum HandlerEvent {
         key: record::Key,
         /// The value of the stored record.
         value: Vec<u8>,
+        /// The reason the remote did not store the record, if any.
+        error: Option<store::Error>,
         /// The user data passed to the `PutValue`.
         query_id: QueryId,
     },
@@ -375,6 +389,19 @@ pub enum HandlerIn {
         query_id: QueryId,
     },
 
+    /// Indicates that this provider is known for all of these keys.
+    ///
+    /// The announcements are sent on a single stream. Like for [`HandlerIn::AddProvider`],
+    /// the API of the handler doesn't expose whether this succeeded.
+    AddProviders {
+        /// Keys for which we should add providers.
+        keys: Vec<record::Key>,
+        /// Known provider for these keys.
+        provider: KadPeer,
+        /// ID of the query that generated this request.
+        query_id: QueryId,
+    },
+
     /// Request to retrieve a record from the DHT.
     GetRecord {
         /// The key of the record.
@@ -406,6 +433,8 @@ pub enum HandlerIn {
         key: record::Key,
         /// Value that was put.
         value: Vec<u8>,
+        /// The reason the record was not stored, if any.
+        error: Option<store::Error>,
         /// Identifier of the request that was made by the remote.
         request_id: RequestId,
     },
@@ -458,6 +487,7 @@ impl Handler {
                 Duration::from_secs(10),
                 MAX_NUM_STREAMS,
             ),
+            idle_outbound_substreams: Default::default(),
             pending_streams: Default::default(),
             pending_messages: Default::default(),
             protocol_status: None,
@@ -514,7 +544,7 @@ impl Handler {
             });
         }
 
-        if self.inbound_substreams.len() == MAX_NUM_STREAMS {
+        if self.inbound_substreams.len() >= self.protocol_config.max_inbound_streams() {
             if let Some(s) = self.inbound_substreams.iter_mut().find(|s| {
                 matches!(
                     s,
@@ -548,38 +578,57 @@ impl Handler {
             });
     }
 
-    /// Takes the given [`KadRequestMsg`] and composes it into an outbound request-response protocol handshake using a [`oneshot::channel`].
-    fn queue_new_stream(&mut self, id: QueryId, msg: KadRequestMsg) {
+    /// Takes the given [`KadRequestMsg`]s and composes it into an outbound request-response protocol handshake using a [`oneshot::channel`].
+    fn queue_new_stream(&mut self, id: QueryId, msgs: SmallVec<[KadRequestMsg; 1]>) {
         let (sender, receiver) = oneshot::channel();
 
         self.pending_streams.push_back(sender);
+        self.queue_request(id, msgs, async move {
+            receiver
+                .await
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?
+                .map_err(|e| match e {
+                    StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout => {
+                        io::ErrorKind::TimedOut.into()
+                    }
+                    StreamUpgradeError::Apply(e) => e,
+                    StreamUpgradeError::NegotiationFailed => {
+                        io::Error::new(io::ErrorKind::ConnectionRefused, "protocol not supported")
+                    }
+                    StreamUpgradeError::Io(e) => e,
+                })
+        });
+    }
+
+    /// Sends the given [`KadRequestMsg`]s on the given stream, once it is available.
+    fn queue_request(
+        &mut self,
+        id: QueryId,
+        msgs: SmallVec<[KadRequestMsg; 1]>,
+        stream: impl Future<Output = io::Result<KadOutStreamSink<Stream>>> + Send + 'static,
+    ) {
+        let reuse = self.protocol_config.reuse_outbound_streams();
         let result = self.outbound_substreams.try_push(
             async move {
-                let mut stream = receiver
-                    .await
-                    .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?
-                    .map_err(|e| match e {
-                        StreamUpgradeError::Timeout => io::ErrorKind::TimedOut.into(),
-                        StreamUpgradeError::Apply(e) => e,
-                        StreamUpgradeError::NegotiationFailed => io::Error::new(
-                            io::ErrorKind::ConnectionRefused,
-                            "protocol not supported",
-                        ),
-                        StreamUpgradeError::Io(e) => e,
-                    })?;
-
-                let has_answer = !matches!(msg, KadRequestMsg::AddProvider { .. });
-
-                stream.send(msg).await?;
-                stream.close().await?;
+                let mut stream = stream.await?;
+
+                let has_answer = !matches!(msgs.last(), Some(KadRequestMsg::AddProvider { .. }));
+
+                for msg in msgs {
+                    stream.feed(msg).await?;
+                }
+                stream.flush().await?;
+                if !reuse {
+                    stream.close().await?;
+                }
 
                 if !has_answer {
-                    return Ok(None);
+                    return Ok((None, reuse.then_some(stream)));
                 }
 
                 let msg = stream.next().await.ok_or(io::ErrorKind::UnexpectedEof)??;
 
-                Ok(Some(msg))
+                Ok((Some(msg), reuse.then_some(stream)))
             },
             id,
         );
@@ -624,7 +673,7 @@ impl ConnectionHandler for Handler {
             }
             HandlerIn::FindNodeReq { key, query_id } => {
                 let msg = KadRequestMsg::FindNode { key };
-                self.pending_messages.push_back((msg, query_id));
+                self.pending_messages.push_back((smallvec![msg], query_id));
             }
             HandlerIn::FindNodeRes {
                 closer_peers,
@@ -632,7 +681,7 @@ impl ConnectionHandler for Handler {
             } => self.answer_pending_request(request_id, KadResponseMsg::FindNode { closer_peers }),
             HandlerIn::GetProvidersReq { key, query_id } => {
                 let msg = KadRequestMsg::GetProviders { key };
-                self.pending_messages.push_back((msg, query_id));
+                self.pending_messages.push_back((smallvec![msg], query_id));
             }
             HandlerIn::GetProvidersRes {
                 closer_peers,
@@ -651,15 +700,29 @@ impl ConnectionHandler for Handler {
                 query_id,
             } => {
                 let msg = KadRequestMsg::AddProvider { key, provider };
-                self.pending_messages.push_back((msg, query_id));
+                self.pending_messages.push_back((smallvec![msg], query_id));
+            }
+            HandlerIn::AddProviders {
+                keys,
+                provider,
+                query_id,
+            } => {
+                let msgs = keys
+                    .into_iter()
+                    .map(|key| KadRequestMsg::AddProvider {
+                        key,
+                        provider: provider.clone(),
+                    })
+                    .collect();
+                self.pending_messages.push_back((msgs, query_id));
             }
             HandlerIn::GetRecord { key, query_id } => {
                 let msg = KadRequestMsg::GetValue { key };
-                self.pending_messages.push_back((msg, query_id));
+                self.pending_messages.push_back((smallvec![msg], query_id));
             }
             HandlerIn::PutRecord { record, query_id } => {
                 let msg = KadRequestMsg::PutValue { record };
-                self.pending_messages.push_back((msg, query_id));
+                self.pending_messages.push_back((smallvec![msg], query_id));
             }
             HandlerIn::GetRecordRes {
                 record,
@@ -678,8 +741,12 @@ impl ConnectionHandler for Handler {
                 key,
                 request_id,
                 value,
+                error,
             } => {
-                self.answer_pending_request(request_id, KadResponseMsg::PutValue { key, value });
+                self.answer_pending_request(
+                    request_id,
+                    KadResponseMsg::PutValue { key, value, error },
+                );
             }
             HandlerIn::ReconfigureMode { new_mode } => {
                 let peer = self.remote_peer_id;
@@ -733,13 +800,19 @@ impl ConnectionHandler for Handler {
             }
 
             match self.outbound_substreams.poll_unpin(cx) {
-                Poll::Ready((Ok(Ok(Some(response))), query_id)) => {
-                    return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
-                        process_kad_response(response, query_id),
-                    ))
-                }
-                Poll::Ready((Ok(Ok(None)), _)) => {
-                    continue;
+                Poll::Ready((Ok(Ok((response, stream))), query_id)) => {
+                    if let Some(stream) = stream {
+                        self.idle_outbound_substreams
+                            .push_back((stream, Delay::new(OUTBOUND_STREAM_IDLE_TIMEOUT)));
+                    }
+                    match response {
+                        Some(response) => {
+                            return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                                process_kad_response(response, query_id),
+                            ))
+                        }
+                        None => continue,
+                    }
                 }
                 Poll::Ready((Ok(Err(e)), query_id)) => {
                     return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
@@ -764,9 +837,16 @@ impl ConnectionHandler for Handler {
                 return Poll::Ready(event);
             }
 
+            self.idle_outbound_substreams
+                .retain_mut(|(_, idle_timeout)| idle_timeout.poll_unpin(cx).is_pending());
+
             if self.outbound_substreams.len() < MAX_NUM_STREAMS {
-                if let Some((msg, id)) = self.pending_messages.pop_front() {
-                    self.queue_new_stream(id, msg);
+                if let Some((msgs, id)) = self.pending_messages.pop_front() {
+                    if let Some((stream, _)) = self.idle_outbound_substreams.pop_front() {
+                        self.queue_request(id, msgs, future::ready(Ok(stream)));
+                        continue;
+                    }
+                    self.queue_new_stream(id, msgs);
                     return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                         protocol: SubstreamProtocol::new(self.protocol_config.clone(), ()),
                     });
@@ -853,6 +933,16 @@ fn compute_new_protocol_status(
 
 impl Handler {
     fn answer_pending_request(&mut self, request_id: RequestId, mut msg: KadResponseMsg) {
+        if let Some(max_size) = self.protocol_config.max_response_size() {
+            let removed = msg.truncate(max_size);
+            if removed > 0 {
+                tracing::debug!(
+                    peer=%self.remote_peer_id,
+                    "Removed {removed} peers from response exceeding {max_size} bytes"
+                );
+            }
+        }
+
         for state in self.inbound_substreams.iter_mut() {
             match state.try_answer_with(request_id, msg) {
                 Ok(()) => return,
@@ -1048,9 +1138,10 @@ fn process_kad_response(event: KadResponseMsg, query_id: QueryId) -> HandlerEven
             closer_peers,
             query_id,
         },
-        KadResponseMsg::PutValue { key, value, .. } => HandlerEvent::PutRecordRes {
+        KadResponseMsg::PutValue { key, value, error } => HandlerEvent::PutRecordRes {
             key,
             value,
+            error,
             query_id,
         },
     }
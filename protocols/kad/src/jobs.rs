@@ -44,8 +44,9 @@
 //!   * [`PutRecordJob`]: For (re-)publication and (re-)replication of
 //!     regular (value-)records.
 //!
-//!   * [`AddProviderJob`]: For (re-)publication of provider records.
-//!     Provider records currently have no separate replication mechanism.
+//!   * [`AddProviderJob`]: For (re-)publication of provider records,
+//!     scheduled by a [`ReprovideStrategy`]. Provider records currently
+//!     have no separate replication mechanism.
 //!
 //! A periodic job is driven like a `Future` or `Stream` by `poll`ing it.
 //! Once a job starts running it emits records to send to the `k` closest
@@ -62,6 +63,7 @@
 //! > out of the job to the consumer, where they can be dropped after being sent.
 
 use crate::record::{self, store::RecordStore, ProviderRecord, Record};
+use crate::reprovide::{KeyspaceRegion, PeriodicSweep, ReprovideStrategy};
 use futures::prelude::*;
 use futures_timer::Delay;
 use libp2p_identity::PeerId;
@@ -79,6 +81,9 @@ pub(crate) const JOBS_MAX_QUERIES: usize = 100;
 /// The maximum number of new queries started by a background job
 /// per invocation of `Behaviour::poll`.
 pub(crate) const JOBS_MAX_NEW_QUERIES: usize = 10;
+/// The maximum number of provider records republished by a single
+/// batched query started by the provider job.
+pub(crate) const JOBS_MAX_PROVIDER_BATCH_SIZE: usize = 100;
 /// A background job run periodically.
 #[derive(Debug)]
 struct PeriodicJob<T> {
@@ -256,39 +261,53 @@ impl PutRecordJob {
 //////////////////////////////////////////////////////////////////////////////
 // AddProviderJob
 
-/// Periodic job for replicating provider records.
+/// Job for replicating provider records, scheduled by a [`ReprovideStrategy`].
 pub(crate) struct AddProviderJob {
-    inner: PeriodicJob<vec::IntoIter<ProviderRecord>>,
+    strategy: Box<dyn ReprovideStrategy>,
+    /// Whether the next run covers the whole keyspace, regardless of the strategy.
+    #[cfg(test)]
+    asap: bool,
+    running: Option<vec::IntoIter<ProviderRecord>>,
 }
 
 impl AddProviderJob {
     /// Creates a new periodic job for provider announcements.
     pub(crate) fn new(interval: Duration) -> Self {
-        let now = Instant::now();
+        Self::with_strategy(PeriodicSweep::new(interval))
+    }
+
+    /// Creates a new job for provider announcements scheduled by the given strategy.
+    pub(crate) fn with_strategy(strategy: impl ReprovideStrategy) -> Self {
         Self {
-            inner: PeriodicJob {
-                interval,
-                state: {
-                    let deadline = now + interval;
-                    PeriodicJobState::Waiting(Delay::new(interval), deadline)
-                },
-            },
+            strategy: Box::new(strategy),
+            #[cfg(test)]
+            asap: false,
+            running: None,
         }
     }
 
     /// Checks whether the job is currently running.
     #[cfg(test)]
     pub(crate) fn is_running(&self) -> bool {
-        self.inner.is_running()
+        self.running.is_some()
     }
 
-    /// Cuts short the remaining delay, if the job is currently waiting
-    /// for the delay to expire.
+    /// Makes the next run cover the whole keyspace, if the job is
+    /// currently not running.
     ///
     /// The job is guaranteed to run on the next invocation of `poll`.
     #[cfg(test)]
     pub(crate) fn asap(&mut self) {
-        self.inner.asap()
+        self.asap = true;
+    }
+
+    /// Polls the strategy for the region of the keyspace to run the job for.
+    fn poll_next_region(&mut self, cx: &mut Context<'_>, now: Instant) -> Poll<KeyspaceRegion> {
+        #[cfg(test)]
+        if std::mem::take(&mut self.asap) {
+            return Poll::Ready(KeyspaceRegion::ALL);
+        }
+        self.strategy.poll_next_region(cx, now)
     }
 
     /// Polls the job for provider records to replicate.
@@ -305,31 +324,29 @@ impl AddProviderJob {
     where
         T: RecordStore,
     {
-        if self.inner.check_ready(cx, now) {
+        loop {
+            if let Some(records) = &mut self.running {
+                for r in records {
+                    if r.is_expired(now) {
+                        store.remove_provider(&r.key, &r.provider)
+                    } else {
+                        return Poll::Ready(r);
+                    }
+                }
+                self.running = None;
+            }
+
+            let Poll::Ready(region) = self.poll_next_region(cx, now) else {
+                return Poll::Pending;
+            };
             let records = store
                 .provided()
+                .filter(|r| region.contains(&r.key))
                 .map(|r| r.into_owned())
                 .collect::<Vec<_>>()
                 .into_iter();
-            self.inner.state = PeriodicJobState::Running(records);
+            self.running = Some(records);
         }
-
-        if let PeriodicJobState::Running(keys) = &mut self.inner.state {
-            for r in keys {
-                if r.is_expired(now) {
-                    store.remove_provider(&r.key, &r.provider)
-                } else {
-                    return Poll::Ready(r);
-                }
-            }
-
-            let deadline = now + self.inner.interval;
-            let delay = Delay::new(self.inner.interval);
-            self.inner.state = PeriodicJobState::Waiting(delay, deadline);
-            assert!(!self.inner.check_ready(cx, now));
-        }
-
-        Poll::Pending
     }
 }
 
@@ -337,6 +354,7 @@ impl AddProviderJob {
 mod tests {
     use super::*;
     use crate::record::store::MemoryStore;
+    use crate::reprovide::RegionSweep;
     use futures::{executor::block_on, future::poll_fn};
     use quickcheck::*;
     use rand::Rng;
@@ -350,17 +368,17 @@ mod tests {
         PutRecordJob::new(id, replicate_interval, publish_interval, record_ttl)
     }
 
-    fn rand_add_provider_job() -> AddProviderJob {
+    fn rand_add_provider_job() -> (AddProviderJob, Duration) {
         let mut rng = rand::thread_rng();
         let interval = Duration::from_secs(rng.gen_range(1..60));
-        AddProviderJob::new(interval)
+        (AddProviderJob::new(interval), interval)
     }
 
     #[test]
     fn new_job_not_running() {
         let job = rand_put_record_job();
         assert!(!job.is_running());
-        let job = rand_add_provider_job();
+        let (job, _) = rand_add_provider_job();
         assert!(!job.is_running());
     }
 
@@ -395,7 +413,7 @@ mod tests {
     #[test]
     fn run_add_provider_job() {
         fn prop(records: Vec<ProviderRecord>) {
-            let mut job = rand_add_provider_job();
+            let (mut job, interval) = rand_add_provider_job();
             let id = PeerId::random();
             // Fill a record store.
             let mut store = MemoryStore::new(id);
@@ -405,7 +423,7 @@ mod tests {
             }
 
             block_on(poll_fn(|ctx| {
-                let now = Instant::now() + job.inner.interval;
+                let now = Instant::now() + interval;
                 // All (non-expired) records in the store must be yielded by the job.
                 for r in store.provided().map(|r| r.into_owned()).collect::<Vec<_>>() {
                     if !r.is_expired(now) {
@@ -421,4 +439,38 @@ mod tests {
 
         quickcheck(prop as fn(_))
     }
+
+    #[test]
+    fn run_add_provider_job_by_region() {
+        let interval = Duration::from_secs(60);
+        let mut job = AddProviderJob::with_strategy(RegionSweep::new(interval, 1));
+        let id = PeerId::random();
+        let mut store = MemoryStore::new(id);
+        for i in 0..20u32 {
+            let key = record::Key::new(&i.to_be_bytes());
+            store
+                .add_provider(ProviderRecord::new(key, id, Vec::new()))
+                .unwrap();
+        }
+
+        block_on(poll_fn(|ctx| {
+            let start = Instant::now();
+            for (prefix, now) in [(0, start + interval / 2), (1, start + interval)] {
+                let region = KeyspaceRegion::new(prefix, 1);
+                let mut expected = store
+                    .provided()
+                    .filter(|r| region.contains(&r.key))
+                    .map(|r| r.into_owned())
+                    .collect::<Vec<_>>();
+                let mut yielded = Vec::new();
+                while let Poll::Ready(r) = job.poll(ctx, &mut store, now) {
+                    yielded.push(r);
+                }
+                expected.sort_by(|a, b| a.key.as_ref().cmp(b.key.as_ref()));
+                yielded.sort_by(|a, b| a.key.as_ref().cmp(b.key.as_ref()));
+                assert_eq!(yielded, expected);
+            }
+            Poll::Ready(())
+        }));
+    }
 }
@@ -221,6 +221,19 @@ where
         }
     }
 
+    /// Records that a lookup for the given key completed successfully at `now`.
+    ///
+    /// Does nothing if the given key refers to the local key.
+    pub(crate) fn on_lookup_finished<K>(&mut self, key: &K, now: Instant)
+    where
+        K: AsRef<KeyBytes>,
+    {
+        let d = self.local_key.as_ref().distance(key);
+        if let Some(index) = BucketIndex::new(&d) {
+            self.buckets[index.get()].set_last_lookup(now);
+        }
+    }
+
     /// Consumes the next applied pending entry, if any.
     ///
     /// When an entry is attempted to be inserted and the respective bucket is full,
@@ -491,6 +504,12 @@ where
         self.bucket.num_entries()
     }
 
+    /// Returns the time at which a lookup for a key falling into this bucket
+    /// last completed successfully, if any.
+    pub fn last_lookup(&self) -> Option<Instant> {
+        self.bucket.last_lookup()
+    }
+
     /// Returns true if the bucket has a pending node.
     pub fn has_pending(&self) -> bool {
         self.bucket.pending().map_or(false, |n| !n.is_ready())
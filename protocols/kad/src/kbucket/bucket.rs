@@ -121,6 +121,10 @@ pub(crate) struct KBucket<TKey, TVal> {
     /// if the least-recently connected node is not updated as being connected
     /// in the meantime.
     pending_timeout: Duration,
+
+    /// The time at which a lookup for a key falling into this bucket last
+    /// completed successfully, if any.
+    last_lookup: Option<Instant>,
 }
 
 /// The result of inserting an entry into a bucket.
@@ -168,9 +172,20 @@ where
             first_connected_pos: None,
             pending: None,
             pending_timeout,
+            last_lookup: None,
         }
     }
 
+    /// Returns the time at which a lookup for a key in this bucket last completed successfully.
+    pub(crate) fn last_lookup(&self) -> Option<Instant> {
+        self.last_lookup
+    }
+
+    /// Records that a lookup for a key in this bucket completed successfully at `now`.
+    pub(crate) fn set_last_lookup(&mut self, now: Instant) {
+        self.last_lookup = Some(now);
+    }
+
     /// Returns a reference to the pending node of the bucket, if there is any.
     pub(crate) fn pending(&self) -> Option<&PendingNode<TKey, TVal>> {
         self.pending.as_ref()
@@ -41,9 +41,14 @@ mod bootstrap;
 mod handler;
 mod jobs;
 mod kbucket;
+mod multi;
 mod protocol;
 mod query;
 mod record;
+mod reprovide;
+mod response_cache;
+mod routing_log;
+mod scorer;
 
 mod proto {
     #![allow(unreachable_pub)]
@@ -54,24 +59,30 @@ mod proto {
     };
 }
 
-pub use addresses::Addresses;
+pub use addresses::{AddressStaleness, Addresses, StaleAddress};
 pub use behaviour::{
     AddProviderContext, AddProviderError, AddProviderOk, AddProviderPhase, AddProviderResult,
     BootstrapError, BootstrapOk, BootstrapResult, GetClosestPeersError, GetClosestPeersOk,
     GetClosestPeersResult, GetProvidersError, GetProvidersOk, GetProvidersResult, GetRecordError,
-    GetRecordOk, GetRecordResult, InboundRequest, Mode, NoKnownPeers, PeerRecord, PutRecordContext,
-    PutRecordError, PutRecordOk, PutRecordPhase, PutRecordResult, QueryInfo, QueryMut, QueryRef,
-    QueryResult, QueryStats, RoutingUpdate,
+    GetRecordOk, GetRecordResult, GetRecordsOk, GetRecordsResult, InboundRequest, Mode,
+    ModeChangeReason, NoKnownPeers, PeerRecord, PutRecordContext, PutRecordError, PutRecordOk,
+    PutRecordPhase, PutRecordResult, QueryInfo, QueryMut, QueryRef, QueryResult, QueryStats,
+    RoutingUpdate,
 };
 pub use behaviour::{
-    Behaviour, BucketInserts, Caching, Config, Event, ProgressStep, Quorum, StoreInserts,
+    AddProvidersPhase, Behaviour, BootstrapCriteria, BootstrapReport, BucketInserts, Caching,
+    Config, Event, ProgressStep, Quorum, RecordPlacement, StoreInserts,
 };
 pub use kbucket::{
     Distance as KBucketDistance, EntryView, KBucketRef, Key as KBucketKey, NodeStatus,
 };
+pub use multi::MultiBehaviour;
 pub use protocol::ConnectionType;
-pub use query::QueryId;
+pub use query::{PathStats, QueryId, QueryTracer};
 pub use record::{store, Key as RecordKey, ProviderRecord, Record};
+pub use reprovide::{KeyspaceRegion, PeriodicSweep, RegionSweep, ReprovideStrategy};
+pub use routing_log::{FileRoutingTableStore, MemoryRoutingTableStore, RoutingTableStore};
+pub use scorer::{MinimumUptime, PeerObservation, PeerScorer, RespondedToQuery};
 
 use libp2p_swarm::StreamProtocol;
 use std::num::NonZeroUsize;
@@ -0,0 +1,209 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! A Kademlia behaviour participating in several DHTs at once.
+//!
+//! [`MultiBehaviour`] maintains one [`Behaviour`], and thus one routing table and one
+//! record store, per protocol name. Inbound streams are routed to the [`Behaviour`]
+//! of the protocol negotiated on the stream.
+
+use crate::handler::Handler;
+use crate::record::store::RecordStore;
+use crate::{Behaviour, Config, Event};
+use libp2p_core::{Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use libp2p_swarm::handler::multi::MultiHandler;
+use libp2p_swarm::{
+    ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, StreamProtocol, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use std::collections::HashMap;
+use std::task::{Context, Poll};
+
+/// A [`NetworkBehaviour`] running one Kademlia DHT per protocol name.
+///
+/// Each DHT has its own routing table and record store but shares the
+/// configuration the [`MultiBehaviour`] was created with. Events are reported
+/// together with the protocol name of the DHT they originate from.
+pub struct MultiBehaviour<TStore> {
+    behaviours: HashMap<StreamProtocol, Behaviour<TStore>>,
+}
+
+impl<TStore> MultiBehaviour<TStore>
+where
+    TStore: RecordStore + Send + 'static,
+{
+    /// Creates a new `MultiBehaviour` with one DHT per given protocol name and record store.
+    ///
+    /// The protocol names configured on `config` are ignored, each DHT only speaks
+    /// its own protocol name. If a protocol name is given more than once, the last
+    /// record store wins.
+    pub fn new<I>(id: PeerId, config: Config, stores: I) -> Self
+    where
+        I: IntoIterator<Item = (StreamProtocol, TStore)>,
+    {
+        let behaviours = stores
+            .into_iter()
+            .map(|(protocol, store)| {
+                let config = config.with_protocol_name(protocol.clone());
+                (protocol, Behaviour::with_config(id, store, config))
+            })
+            .collect();
+
+        MultiBehaviour { behaviours }
+    }
+
+    /// Returns an iterator over the protocol names of all DHTs.
+    pub fn protocol_names(&self) -> impl Iterator<Item = &StreamProtocol> {
+        self.behaviours.keys()
+    }
+
+    /// Returns the [`Behaviour`] of the DHT with the given protocol name, if any.
+    pub fn get(&self, protocol: &StreamProtocol) -> Option<&Behaviour<TStore>> {
+        self.behaviours.get(protocol)
+    }
+
+    /// Returns the [`Behaviour`] of the DHT with the given protocol name mutably, if any.
+    pub fn get_mut(&mut self, protocol: &StreamProtocol) -> Option<&mut Behaviour<TStore>> {
+        self.behaviours.get_mut(protocol)
+    }
+
+    /// Returns an iterator over the [`Behaviour`]s of all DHTs.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&StreamProtocol, &mut Behaviour<TStore>)> {
+        self.behaviours.iter_mut()
+    }
+}
+
+impl<TStore> NetworkBehaviour for MultiBehaviour<TStore>
+where
+    TStore: RecordStore + Send + 'static,
+{
+    type ConnectionHandler = MultiHandler<StreamProtocol, Handler>;
+    type ToSwarm = (StreamProtocol, Event);
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let handlers = self
+            .behaviours
+            .iter_mut()
+            .map(|(protocol, behaviour)| {
+                let handler = behaviour.handle_established_inbound_connection(
+                    connection_id,
+                    peer,
+                    local_addr,
+                    remote_addr,
+                )?;
+                Ok((protocol.clone(), handler))
+            })
+            .collect::<Result<Vec<_>, ConnectionDenied>>()?;
+
+        MultiHandler::try_from_iter(handlers).map_err(ConnectionDenied::new)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let handlers = self
+            .behaviours
+            .iter_mut()
+            .map(|(protocol, behaviour)| {
+                let handler = behaviour.handle_established_outbound_connection(
+                    connection_id,
+                    peer,
+                    addr,
+                    role_override,
+                )?;
+                Ok((protocol.clone(), handler))
+            })
+            .collect::<Result<Vec<_>, ConnectionDenied>>()?;
+
+        MultiHandler::try_from_iter(handlers).map_err(ConnectionDenied::new)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        let mut peer_addrs = Vec::new();
+
+        for behaviour in self.behaviours.values_mut() {
+            for addr in behaviour.handle_pending_outbound_connection(
+                connection_id,
+                maybe_peer,
+                addresses,
+                effective_role,
+            )? {
+                if !peer_addrs.contains(&addr) {
+                    peer_addrs.push(addr);
+                }
+            }
+        }
+
+        Ok(peer_addrs)
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        (protocol, event): THandlerOutEvent<Self>,
+    ) {
+        if let Some(behaviour) = self.behaviours.get_mut(&protocol) {
+            behaviour.on_connection_handler_event(peer_id, connection_id, event)
+        } else {
+            tracing::error!(%protocol, "Received handler event for unknown DHT")
+        }
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        for (protocol, behaviour) in self.behaviours.iter_mut() {
+            if let Poll::Ready(event) = behaviour.poll(cx) {
+                return Poll::Ready(
+                    event
+                        .map_in(|e| (protocol.clone(), e))
+                        .map_out(|e| (protocol.clone(), e)),
+                );
+            }
+        }
+
+        Poll::Pending
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        for behaviour in self.behaviours.values_mut() {
+            behaviour.on_swarm_event(event);
+        }
+    }
+}
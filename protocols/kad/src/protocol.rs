@@ -27,7 +27,7 @@
 //! is used to send messages to remote peers.
 
 use crate::proto;
-use crate::record::{self, Record};
+use crate::record::{self, store, Record};
 use asynchronous_codec::{Decoder, Encoder, Framed};
 use bytes::BytesMut;
 use futures::prelude::*;
@@ -35,6 +35,7 @@ use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_core::Multiaddr;
 use libp2p_identity::PeerId;
 use libp2p_swarm::StreamProtocol;
+use quick_protobuf::{sizeofs::sizeof_len, BytesReader, MessageRead, MessageWrite, Writer};
 use std::marker::PhantomData;
 use std::time::Duration;
 use std::{io, iter};
@@ -45,6 +46,12 @@ use web_time::Instant;
 pub(crate) const DEFAULT_PROTO_NAME: StreamProtocol = StreamProtocol::new("/ipfs/kad/1.0.0");
 /// The default maximum size for a varint length-delimited packet.
 pub(crate) const DEFAULT_MAX_PACKET_SIZE: usize = 16 * 1024;
+/// The default maximum number of concurrent inbound substreams per connection.
+pub(crate) const DEFAULT_MAX_INBOUND_STREAMS: usize = 32;
+/// The suffix of the protocol names of the compressed variant of the protocol.
+pub(crate) const COMPRESSION_SUFFIX: &str = "/deflate";
+/// The compression level used for messages exceeding the compression threshold.
+const COMPRESSION_LEVEL: u8 = 6;
 /// Status of our connection to a node reported by the Kademlia protocol.
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum ConnectionType {
@@ -141,6 +148,14 @@ pub struct ProtocolConfig {
     protocol_names: Vec<StreamProtocol>,
     /// Maximum allowed size of a packet.
     max_packet_size: usize,
+    /// Maximum number of concurrent inbound substreams per connection.
+    max_inbound_streams: usize,
+    /// Whether outbound substreams are reused for subsequent requests.
+    reuse_outbound_streams: bool,
+    /// Maximum encoded size of a response, beyond which it is truncated.
+    max_response_size: Option<usize>,
+    /// Encoded size of a message beyond which it is compressed, if compression is enabled.
+    compression_threshold: Option<usize>,
 }
 
 impl ProtocolConfig {
@@ -149,6 +164,10 @@ impl ProtocolConfig {
         ProtocolConfig {
             protocol_names: vec![protocol_name],
             max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            max_inbound_streams: DEFAULT_MAX_INBOUND_STREAMS,
+            reuse_outbound_streams: false,
+            max_response_size: None,
+            compression_threshold: None,
         }
     }
 
@@ -175,6 +194,57 @@ impl ProtocolConfig {
     pub fn set_max_packet_size(&mut self, size: usize) {
         self.max_packet_size = size;
     }
+
+    /// Returns the maximum number of concurrent inbound substreams per connection.
+    pub fn max_inbound_streams(&self) -> usize {
+        self.max_inbound_streams
+    }
+
+    /// Modifies the maximum number of concurrent inbound substreams per connection.
+    pub fn set_max_inbound_streams(&mut self, num: usize) {
+        self.max_inbound_streams = num;
+    }
+
+    /// Returns whether outbound substreams are reused for subsequent requests.
+    pub fn reuse_outbound_streams(&self) -> bool {
+        self.reuse_outbound_streams
+    }
+
+    /// Modifies whether outbound substreams are reused for subsequent requests.
+    pub fn set_reuse_outbound_streams(&mut self, reuse: bool) {
+        self.reuse_outbound_streams = reuse;
+    }
+
+    /// Returns the maximum encoded size of a response, if any.
+    pub fn max_response_size(&self) -> Option<usize> {
+        self.max_response_size
+    }
+
+    /// Modifies the maximum encoded size of a response, beyond which it is truncated.
+    pub fn set_max_response_size(&mut self, size: Option<usize>) {
+        self.max_response_size = size;
+    }
+
+    /// Returns the encoded size of a message beyond which it is compressed, if any.
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    /// Modifies the encoded size of a message beyond which it is compressed.
+    ///
+    /// When set, the compressed variant of each protocol name, suffixed with `/deflate`,
+    /// is offered in addition to and preferred over the plain protocol name.
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    /// Returns the compression threshold to use on a stream of the given protocol.
+    fn compression_for(&self, protocol: &StreamProtocol) -> Option<usize> {
+        if self.protocol_names.contains(protocol) {
+            return None;
+        }
+        self.compression_threshold
+    }
 }
 
 impl Default for ProtocolConfig {
@@ -185,6 +255,10 @@ impl Default for ProtocolConfig {
         ProtocolConfig {
             protocol_names: iter::once(DEFAULT_PROTO_NAME).collect(),
             max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            max_inbound_streams: DEFAULT_MAX_INBOUND_STREAMS,
+            reuse_outbound_streams: false,
+            max_response_size: None,
+            compression_threshold: None,
         }
     }
 }
@@ -194,19 +268,63 @@ impl UpgradeInfo for ProtocolConfig {
     type InfoIter = std::vec::IntoIter<Self::Info>;
 
     fn protocol_info(&self) -> Self::InfoIter {
-        self.protocol_names.clone().into_iter()
+        if self.compression_threshold.is_none() {
+            return self.protocol_names.clone().into_iter();
+        }
+
+        self.protocol_names
+            .iter()
+            .filter_map(|name| {
+                StreamProtocol::try_from_owned(format!("{name}{COMPRESSION_SUFFIX}")).ok()
+            })
+            .chain(self.protocol_names.iter().cloned())
+            .collect::<Vec<_>>()
+            .into_iter()
     }
 }
 
 /// Codec for Kademlia inbound and outbound message framing.
 pub struct Codec<A, B> {
-    codec: quick_protobuf_codec::Codec<proto::Message>,
+    framing: Framing,
     __phantom: PhantomData<(A, B)>,
 }
+
+enum Framing {
+    Plain(quick_protobuf_codec::Codec<proto::Message>),
+    /// Each frame starts with a flag byte indicating whether the message is compressed.
+    Compressed {
+        codec: unsigned_varint::codec::UviBytes,
+        threshold: usize,
+        max_packet_size: usize,
+    },
+}
+
+/// Flag of a frame carrying an uncompressed message.
+const FRAME_PLAIN: u8 = 0;
+/// Flag of a frame carrying a deflate-compressed message.
+const FRAME_DEFLATE: u8 = 1;
+
 impl<A, B> Codec<A, B> {
     fn new(max_packet_size: usize) -> Self {
         Codec {
-            codec: quick_protobuf_codec::Codec::new(max_packet_size),
+            framing: Framing::Plain(quick_protobuf_codec::Codec::new(max_packet_size)),
+            __phantom: PhantomData,
+        }
+    }
+
+    fn with_compression(max_packet_size: usize, threshold: Option<usize>) -> Self {
+        let Some(threshold) = threshold else {
+            return Codec::new(max_packet_size);
+        };
+        let mut codec = unsigned_varint::codec::UviBytes::default();
+        codec.set_max_len(max_packet_size);
+
+        Codec {
+            framing: Framing::Compressed {
+                codec,
+                threshold,
+                max_packet_size,
+            },
             __phantom: PhantomData,
         }
     }
@@ -217,7 +335,31 @@ impl<A: Into<proto::Message>, B> Encoder for Codec<A, B> {
     type Item<'a> = A;
 
     fn encode(&mut self, item: Self::Item<'_>, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        Ok(self.codec.encode(item.into(), dst)?)
+        let (codec, threshold) = match &mut self.framing {
+            Framing::Plain(codec) => return Ok(codec.encode(item.into(), dst)?),
+            Framing::Compressed {
+                codec, threshold, ..
+            } => (codec, *threshold),
+        };
+
+        let message: proto::Message = item.into();
+        let mut encoded = Vec::with_capacity(message.get_size());
+        message
+            .write_message(&mut Writer::new(&mut encoded))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let mut frame = BytesMut::with_capacity(encoded.len() + 1);
+        if encoded.len() > threshold {
+            let compressed = miniz_oxide::deflate::compress_to_vec(&encoded, COMPRESSION_LEVEL);
+            if compressed.len() < encoded.len() {
+                frame.extend_from_slice(&[FRAME_DEFLATE]);
+                frame.extend_from_slice(&compressed);
+                return codec.encode(frame.freeze(), dst);
+            }
+        }
+        frame.extend_from_slice(&[FRAME_PLAIN]);
+        frame.extend_from_slice(&encoded);
+        codec.encode(frame.freeze(), dst)
     }
 }
 impl<A, B: TryFrom<proto::Message, Error = io::Error>> Decoder for Codec<A, B> {
@@ -225,7 +367,30 @@ impl<A, B: TryFrom<proto::Message, Error = io::Error>> Decoder for Codec<A, B> {
     type Item = B;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        self.codec.decode(src)?.map(B::try_from).transpose()
+        let (codec, max_packet_size) = match &mut self.framing {
+            Framing::Plain(codec) => return codec.decode(src)?.map(B::try_from).transpose(),
+            Framing::Compressed {
+                codec,
+                max_packet_size,
+                ..
+            } => (codec, *max_packet_size),
+        };
+
+        let Some(frame) = codec.decode(src)? else {
+            return Ok(None);
+        };
+        let encoded = match frame.split_first() {
+            Some((&FRAME_PLAIN, encoded)) => encoded.to_vec(),
+            Some((&FRAME_DEFLATE, compressed)) => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(compressed, max_packet_size)
+                    .map_err(|_| invalid_data("invalid compressed message"))?
+            }
+            _ => return Err(invalid_data("invalid frame flag")),
+        };
+        let message = proto::Message::from_reader(&mut BytesReader::from_bytes(&encoded), &encoded)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        B::try_from(message).map(Some)
     }
 }
 
@@ -242,8 +407,8 @@ where
     type Future = future::Ready<Result<Self::Output, io::Error>>;
     type Error = io::Error;
 
-    fn upgrade_inbound(self, incoming: C, _: Self::Info) -> Self::Future {
-        let codec = Codec::new(self.max_packet_size);
+    fn upgrade_inbound(self, incoming: C, protocol: Self::Info) -> Self::Future {
+        let codec = Codec::with_compression(self.max_packet_size, self.compression_for(&protocol));
 
         future::ok(Framed::new(incoming, codec))
     }
@@ -257,8 +422,8 @@ where
     type Future = future::Ready<Result<Self::Output, io::Error>>;
     type Error = io::Error;
 
-    fn upgrade_outbound(self, incoming: C, _: Self::Info) -> Self::Future {
-        let codec = Codec::new(self.max_packet_size);
+    fn upgrade_outbound(self, incoming: C, protocol: Self::Info) -> Self::Future {
+        let codec = Codec::with_compression(self.max_packet_size, self.compression_for(&protocol));
 
         future::ok(Framed::new(incoming, codec))
     }
@@ -336,9 +501,52 @@ pub enum KadResponseMsg {
         key: record::Key,
         /// Value of the record.
         value: Vec<u8>,
+        /// The reason the record was not stored, if any.
+        error: Option<store::Error>,
     },
 }
 
+impl KadResponseMsg {
+    /// Removes peers from the response until its encoded size, including the
+    /// length prefix, does not exceed `max_size`.
+    ///
+    /// Closer peers are removed before provider peers, each starting with the
+    /// last one, i.e. the one furthest from the key. Records are never removed,
+    /// hence the response may still exceed `max_size`.
+    ///
+    /// Returns the number of removed peers.
+    pub(crate) fn truncate(&mut self, max_size: usize) -> usize {
+        let mut size = resp_msg_to_proto(self.clone()).get_size();
+        if sizeof_len(size) <= max_size {
+            return 0;
+        }
+
+        let (closer_peers, provider_peers) = match self {
+            KadResponseMsg::FindNode { closer_peers }
+            | KadResponseMsg::GetValue { closer_peers, .. } => (closer_peers, None),
+            KadResponseMsg::GetProviders {
+                closer_peers,
+                provider_peers,
+            } => (closer_peers, Some(provider_peers)),
+            KadResponseMsg::Pong | KadResponseMsg::PutValue { .. } => return 0,
+        };
+
+        let mut removed = 0;
+        for peers in iter::once(closer_peers).chain(provider_peers) {
+            while sizeof_len(size) > max_size {
+                let Some(peer) = peers.pop() else {
+                    break;
+                };
+                // Each peer is a length-delimited field with a single byte tag.
+                size -= 1 + sizeof_len(proto::Peer::from(peer).get_size());
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+}
+
 impl From<KadRequestMsg> for proto::Message {
     fn from(kad_msg: KadRequestMsg) -> Self {
         req_msg_to_proto(kad_msg)
@@ -438,7 +646,7 @@ fn resp_msg_to_proto(kad_msg: KadResponseMsg) -> proto::Message {
             record: record.map(record_to_proto),
             ..proto::Message::default()
         },
-        KadResponseMsg::PutValue { key, value } => proto::Message {
+        KadResponseMsg::PutValue { key, value, error } => proto::Message {
             type_pb: proto::MessageType::PUT_VALUE,
             key: key.to_vec(),
             record: Some(proto::Record {
@@ -446,6 +654,7 @@ fn resp_msg_to_proto(kad_msg: KadResponseMsg) -> proto::Message {
                 value,
                 ..proto::Record::default()
             }),
+            storeError: store_error_to_proto(error),
             ..proto::Message::default()
         },
     }
@@ -550,6 +759,7 @@ fn proto_to_resp_msg(message: proto::Message) -> Result<KadResponseMsg, io::Erro
             Ok(KadResponseMsg::PutValue {
                 key,
                 value: rec.value,
+                error: store_error_from_proto(message.storeError)?,
             })
         }
 
@@ -585,6 +795,26 @@ fn record_from_proto(record: proto::Record) -> Result<Record, io::Error> {
     })
 }
 
+/// Encodes the reason a record was not stored, `0` meaning it was stored.
+fn store_error_to_proto(error: Option<store::Error>) -> u32 {
+    match error {
+        None => 0,
+        Some(store::Error::MaxRecords) => 1,
+        Some(store::Error::MaxProvidedKeys) => 2,
+        Some(store::Error::ValueTooLarge) => 3,
+    }
+}
+
+fn store_error_from_proto(code: u32) -> Result<Option<store::Error>, io::Error> {
+    match code {
+        0 => Ok(None),
+        1 => Ok(Some(store::Error::MaxRecords)),
+        2 => Ok(Some(store::Error::MaxProvidedKeys)),
+        3 => Ok(Some(store::Error::ValueTooLarge)),
+        _ => Err(invalid_data(format!("unknown store error {code}"))),
+    }
+}
+
 fn record_to_proto(record: Record) -> proto::Record {
     proto::Record {
         key: record.key.to_vec(),
@@ -617,6 +847,50 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn compressed_protocol_names_are_preferred() {
+        let mut config = ProtocolConfig::new(DEFAULT_PROTO_NAME);
+        config.set_compression_threshold(Some(1024));
+
+        let names = config.protocol_info().collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                StreamProtocol::new("/ipfs/kad/1.0.0/deflate"),
+                DEFAULT_PROTO_NAME
+            ]
+        );
+        assert_eq!(config.compression_for(&names[0]), Some(1024));
+        assert_eq!(config.compression_for(&names[1]), None);
+    }
+
+    #[test]
+    fn messages_above_threshold_are_compressed() {
+        let record = Record::new(record::Key::new(&"key"), vec![7; 8 * 1024]);
+        let msg = KadRequestMsg::PutValue {
+            record: record.clone(),
+        };
+        let encode = |codec: &mut Codec<KadRequestMsg, KadRequestMsg>, msg: KadRequestMsg| {
+            let mut buf = BytesMut::new();
+            codec.encode(msg, &mut buf).unwrap();
+            buf
+        };
+
+        let plain = encode(&mut Codec::new(DEFAULT_MAX_PACKET_SIZE), msg.clone());
+        let mut codec = Codec::with_compression(DEFAULT_MAX_PACKET_SIZE, Some(1024));
+        let mut compressed = encode(&mut codec, msg.clone());
+        assert!(compressed.len() < plain.len() / 10);
+        assert_eq!(codec.decode(&mut compressed).unwrap(), Some(msg));
+
+        // Small messages are sent as is, apart from the frame flag.
+        let mut small = encode(&mut codec, KadRequestMsg::Ping);
+        assert_eq!(
+            small.len(),
+            encode(&mut Codec::new(1024), KadRequestMsg::Ping).len() + 1
+        );
+        assert_eq!(codec.decode(&mut small).unwrap(), Some(KadRequestMsg::Ping));
+    }
+
     #[test]
     fn append_p2p() {
         let peer_id = PeerId::random();
@@ -667,6 +941,59 @@ mod tests {
         assert_eq!(peer.multiaddrs, vec![valid_multiaddr])
     }
 
+    #[test]
+    fn truncate_removes_closer_peers_before_provider_peers() {
+        let peer = |n: u8| KadPeer {
+            node_id: PeerId::random(),
+            multiaddrs: vec![format!("/ip4/10.0.0.{n}/tcp/4001").parse().unwrap()],
+            connection_ty: ConnectionType::NotConnected,
+        };
+        let closer_peers = (0..10).map(peer).collect::<Vec<_>>();
+        let provider_peers = (10..20).map(peer).collect::<Vec<_>>();
+        let msg = KadResponseMsg::GetProviders {
+            closer_peers: closer_peers.clone(),
+            provider_peers: provider_peers.clone(),
+        };
+        let encoded_len = |msg: &KadResponseMsg| {
+            let mut buf = BytesMut::new();
+            Codec::<KadResponseMsg, KadResponseMsg>::new(usize::MAX)
+                .encode(msg.clone(), &mut buf)
+                .unwrap();
+            buf.len()
+        };
+        let size = encoded_len(&msg);
+
+        let mut untouched = msg.clone();
+        assert_eq!(untouched.truncate(size), 0);
+        assert_eq!(untouched, msg);
+
+        let mut truncated = msg.clone();
+        let removed = truncated.truncate(size - 1);
+        assert_eq!(removed, 1);
+        assert_eq!(
+            truncated,
+            KadResponseMsg::GetProviders {
+                closer_peers: closer_peers[..9].to_vec(),
+                provider_peers: provider_peers.clone(),
+            }
+        );
+
+        let mut truncated = msg.clone();
+        let max_size = size / 4;
+        let removed = truncated.truncate(max_size);
+        let KadResponseMsg::GetProviders {
+            closer_peers: ref remaining_closer,
+            provider_peers: ref remaining_providers,
+        } = truncated
+        else {
+            unreachable!()
+        };
+        assert!(remaining_closer.is_empty());
+        assert_eq!(remaining_providers[..], provider_peers[..20 - removed]);
+        assert!(encoded_len(&truncated) <= max_size);
+        assert!(removed < 20);
+    }
+
     /*// TODO: restore
     use self::libp2p_tcp::TcpTransport;
     use self::tokio::runtime::current_thread::Runtime;
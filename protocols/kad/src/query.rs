@@ -26,12 +26,15 @@ use peers::closest::{
 use peers::fixed::FixedPeersIter;
 use peers::PeersIterState;
 
-use crate::kbucket::{Key, KeyBytes};
+pub(crate) use peers::closest::PeerLatencyFn;
+
+use crate::kbucket::{Distance, Key, KeyBytes};
 use crate::{ALPHA_VALUE, K_VALUE};
 use either::Either;
 use fnv::FnvHashMap;
 use libp2p_identity::PeerId;
-use std::{num::NonZeroUsize, time::Duration};
+use std::sync::Arc;
+use std::{fmt, num::NonZeroUsize, time::Duration};
 use web_time::Instant;
 
 /// A `QueryPool` provides an aggregate state machine for driving `Query`s to completion.
@@ -108,7 +111,7 @@ impl<TInner> QueryPool<TInner> {
         assert!(!self.queries.contains_key(&id));
         let parallelism = self.config.replication_factor;
         let peer_iter = QueryPeerIter::Fixed(FixedPeersIter::new(peers, parallelism));
-        let query = Query::new(id, peer_iter, inner);
+        let query = Query::new(id, peer_iter, inner, &self.config);
         self.queries.insert(id, query);
     }
 
@@ -123,6 +126,24 @@ impl<TInner> QueryPool<TInner> {
         id
     }
 
+    /// Adds a query to the pool that iterates towards the `num_results` closest peers
+    /// to the target, instead of the configured replication factor.
+    pub(crate) fn add_iter_closest_n<T, I>(
+        &mut self,
+        target: T,
+        peers: I,
+        inner: TInner,
+        num_results: NonZeroUsize,
+    ) -> QueryId
+    where
+        T: Into<KeyBytes> + Clone,
+        I: IntoIterator<Item = Key<PeerId>>,
+    {
+        let id = self.next_query_id();
+        self.insert_iter_closest(id, target, peers, inner, num_results);
+        id
+    }
+
     /// Adds a query to the pool that iterates towards the closest peers to the target.
     pub(crate) fn continue_iter_closest<T, I>(
         &mut self,
@@ -133,10 +154,26 @@ impl<TInner> QueryPool<TInner> {
     ) where
         T: Into<KeyBytes> + Clone,
         I: IntoIterator<Item = Key<PeerId>>,
+    {
+        let num_results = self.config.replication_factor;
+        self.insert_iter_closest(id, target, peers, inner, num_results);
+    }
+
+    fn insert_iter_closest<T, I>(
+        &mut self,
+        id: QueryId,
+        target: T,
+        peers: I,
+        inner: TInner,
+        num_results: NonZeroUsize,
+    ) where
+        T: Into<KeyBytes> + Clone,
+        I: IntoIterator<Item = Key<PeerId>>,
     {
         let cfg = ClosestPeersIterConfig {
-            num_results: self.config.replication_factor,
+            num_results,
             parallelism: self.config.parallelism,
+            latency_fn: self.config.latency_fn.clone(),
             ..ClosestPeersIterConfig::default()
         };
 
@@ -148,7 +185,7 @@ impl<TInner> QueryPool<TInner> {
             QueryPeerIter::Closest(ClosestPeersIter::with_config(cfg, target, peers))
         };
 
-        let query = Query::new(id, peer_iter, inner);
+        let query = Query::new(id, peer_iter, inner, &self.config);
         self.queries.insert(id, query);
     }
 
@@ -176,6 +213,10 @@ impl<TInner> QueryPool<TInner> {
 
         for (&query_id, query) in self.queries.iter_mut() {
             query.stats.start = query.stats.start.or(Some(now));
+            if let Some(peer) = query.next_retry(now) {
+                waiting = Some((query_id, peer));
+                break;
+            }
             match query.next(now) {
                 PeersIterState::Finished => {
                     finished = Some(query_id);
@@ -204,12 +245,18 @@ impl<TInner> QueryPool<TInner> {
         if let Some(query_id) = finished {
             let mut query = self.queries.remove(&query_id).expect("s.a.");
             query.stats.end = Some(now);
+            if let Some(tracer) = &query.tracer {
+                tracer.on_finished(query_id, &query.stats);
+            }
             return QueryPoolState::Finished(query);
         }
 
         if let Some(query_id) = timeout {
             let mut query = self.queries.remove(&query_id).expect("s.a.");
             query.stats.end = Some(now);
+            if let Some(tracer) = &query.tracer {
+                tracer.on_timeout(query_id, &query.stats);
+            }
             return QueryPoolState::Timeout(query);
         }
 
@@ -232,7 +279,7 @@ impl std::fmt::Display for QueryId {
 }
 
 /// The configuration for queries in a `QueryPool`.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub(crate) struct QueryConfig {
     /// Timeout of a single query.
     ///
@@ -250,6 +297,37 @@ pub(crate) struct QueryConfig {
     ///
     /// See [`crate::behaviour::Config::disjoint_query_paths`] for details.
     pub(crate) disjoint_query_paths: bool,
+    /// The function providing the observed latency of peers, if any.
+    ///
+    /// See [`crate::behaviour::Config::set_peer_latency_fn`] for details.
+    pub(crate) latency_fn: Option<PeerLatencyFn>,
+    /// The tracer observing the state transitions of queries, if any.
+    ///
+    /// See [`crate::behaviour::Config::set_query_tracer`] for details.
+    pub(crate) tracer: Option<Arc<dyn QueryTracer>>,
+    /// The number of times a failed request to a peer is retried within a query.
+    ///
+    /// See [`crate::behaviour::Config::set_max_rpc_retries`] for details.
+    pub(crate) max_rpc_retries: usize,
+    /// The base delay before retrying a failed request to a peer.
+    ///
+    /// See [`crate::behaviour::Config::set_rpc_retry_backoff`] for details.
+    pub(crate) rpc_retry_backoff: Duration,
+}
+
+impl fmt::Debug for QueryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryConfig")
+            .field("timeout", &self.timeout)
+            .field("replication_factor", &self.replication_factor)
+            .field("parallelism", &self.parallelism)
+            .field("disjoint_query_paths", &self.disjoint_query_paths)
+            .field("latency_fn", &self.latency_fn)
+            .field("tracer", &self.tracer.is_some())
+            .field("max_rpc_retries", &self.max_rpc_retries)
+            .field("rpc_retry_backoff", &self.rpc_retry_backoff)
+            .finish()
+    }
 }
 
 impl Default for QueryConfig {
@@ -259,6 +337,10 @@ impl Default for QueryConfig {
             replication_factor: NonZeroUsize::new(K_VALUE.get()).expect("K_VALUE > 0"),
             parallelism: ALPHA_VALUE,
             disjoint_query_paths: false,
+            latency_fn: None,
+            tracer: None,
+            max_rpc_retries: 0,
+            rpc_retry_backoff: Duration::from_millis(500),
         }
     }
 }
@@ -273,6 +355,22 @@ pub(crate) struct Query<TInner> {
     stats: QueryStats,
     /// The opaque inner query state.
     pub(crate) inner: TInner,
+    /// The tracer observing the state transitions of the query, if any.
+    tracer: Option<Arc<dyn QueryTracer>>,
+    /// The retries of failed requests to peers of the query.
+    retries: RpcRetries,
+}
+
+/// The per-peer retry budget for failed requests of a query.
+struct RpcRetries {
+    /// The maximum number of retries per peer.
+    max: usize,
+    /// The base delay before a retry, growing linearly with the number of attempts.
+    backoff: Duration,
+    /// The number of retries done so far per peer.
+    attempts: FnvHashMap<PeerId, usize>,
+    /// The retries waiting for their backoff to elapse.
+    scheduled: Vec<(PeerId, Instant)>,
 }
 
 /// The peer selection strategies that can be used by queries.
@@ -284,12 +382,23 @@ enum QueryPeerIter {
 
 impl<TInner> Query<TInner> {
     /// Creates a new query without starting it.
-    fn new(id: QueryId, peer_iter: QueryPeerIter, inner: TInner) -> Self {
+    fn new(id: QueryId, peer_iter: QueryPeerIter, inner: TInner, config: &QueryConfig) -> Self {
+        let mut stats = QueryStats::empty();
+        if let QueryPeerIter::ClosestDisjoint(iter) = &peer_iter {
+            stats.paths = vec![PathStats::default(); iter.num_paths()];
+        }
         Query {
             id,
             inner,
             peer_iter,
-            stats: QueryStats::empty(),
+            stats,
+            tracer: config.tracer.clone(),
+            retries: RpcRetries {
+                max: config.max_rpc_retries,
+                backoff: config.rpc_retry_backoff,
+                attempts: Default::default(),
+                scheduled: Vec::new(),
+            },
         }
     }
 
@@ -298,13 +407,63 @@ impl<TInner> Query<TInner> {
         self.id
     }
 
+    /// Returns the index of the disjoint path that contacted `peer`, if the query
+    /// uses disjoint paths.
+    pub(crate) fn path_of(&self, peer: &PeerId) -> Option<usize> {
+        match &self.peer_iter {
+            QueryPeerIter::ClosestDisjoint(iter) => iter.path_of(peer),
+            QueryPeerIter::Closest(_) | QueryPeerIter::Fixed(_) => None,
+        }
+    }
+
+    /// Returns the statistics of the disjoint path that contacted `peer`, if any.
+    fn path_stats_mut(&mut self, peer: &PeerId) -> Option<&mut PathStats> {
+        let path = self.path_of(peer)?;
+        self.stats.paths.get_mut(path)
+    }
+
     /// Gets the current execution statistics of the query.
     pub(crate) fn stats(&self) -> &QueryStats {
         &self.stats
     }
 
+    /// Informs the query that a request to `peer` failed, e.g. because the stream
+    /// was dropped, scheduling a retry if the retry budget of `peer` is not yet exhausted.
+    ///
+    /// Returns `false` if no retry was scheduled, in which case the failure is final
+    /// and must be reported via [`Query::on_failure`].
+    pub(crate) fn try_retry(&mut self, peer: &PeerId, now: Instant) -> bool {
+        let attempts = self.retries.attempts.entry(*peer).or_default();
+        if *attempts >= self.retries.max {
+            return false;
+        }
+        *attempts += 1;
+        let backoff = self.retries.backoff * *attempts as u32;
+        self.retries.scheduled.push((*peer, now + backoff));
+        true
+    }
+
+    /// Returns a peer whose request is due to be retried, if any.
+    fn next_retry(&mut self, now: Instant) -> Option<PeerId> {
+        let pos = self
+            .retries
+            .scheduled
+            .iter()
+            .position(|(_, deadline)| *deadline <= now)?;
+        let (peer, _) = self.retries.scheduled.swap_remove(pos);
+        self.stats.requests += 1;
+        if let Some(path) = self.path_stats_mut(&peer) {
+            path.requests += 1;
+        }
+        if let Some(tracer) = &self.tracer {
+            tracer.on_peer_contacted(self.id, &peer);
+        }
+        Some(peer)
+    }
+
     /// Informs the query that the attempt to contact `peer` failed.
     pub(crate) fn on_failure(&mut self, peer: &PeerId) {
+        self.retries.scheduled.retain(|(p, _)| p != peer);
         let updated = match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_failure(peer),
             QueryPeerIter::ClosestDisjoint(iter) => iter.on_failure(peer),
@@ -312,9 +471,29 @@ impl<TInner> Query<TInner> {
         };
         if updated {
             self.stats.failure += 1;
+            if let Some(path) = self.path_stats_mut(peer) {
+                path.failure += 1;
+            }
+            if let Some(tracer) = &self.tracer {
+                tracer.on_failure(self.id, peer);
+            }
         }
     }
 
+    /// Informs the query that the request to `peer` was dropped while waiting for a
+    /// connection, in favour of requests to peers closer to the target.
+    pub(crate) fn on_evicted(&mut self, peer: &PeerId) {
+        self.stats.evicted += 1;
+        self.on_failure(peer);
+    }
+
+    /// Informs the query that a request was sent after waiting `waited` for a
+    /// connection to its peer.
+    pub(crate) fn on_connection_wait(&mut self, waited: Duration) {
+        self.stats.connection_wait += waited;
+        self.stats.max_connection_wait = self.stats.max_connection_wait.max(waited);
+    }
+
     /// Informs the query that the attempt to contact `peer` succeeded,
     /// possibly resulting in new peers that should be incorporated into
     /// the query, if applicable.
@@ -322,6 +501,29 @@ impl<TInner> Query<TInner> {
     where
         I: IntoIterator<Item = PeerId>,
     {
+        let Some(tracer) = self.tracer.clone() else {
+            self.on_success_untraced(peer, new_peers);
+            return;
+        };
+
+        let new_peers = new_peers.into_iter().collect::<Vec<_>>();
+        let num_closer_peers = new_peers.len();
+        let closest = self.closest_distance();
+        if self.on_success_untraced(peer, new_peers) {
+            tracer.on_response(self.id, peer, num_closer_peers);
+            match (closest, self.closest_distance()) {
+                (_, None) => {}
+                (Some(before), Some(after)) if after >= before => {}
+                (_, Some(after)) => tracer.on_narrowed(self.id, after),
+            }
+        }
+    }
+
+    fn on_success_untraced<I>(&mut self, peer: &PeerId, new_peers: I) -> bool
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        self.retries.scheduled.retain(|(p, _)| p != peer);
         let updated = match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_success(peer, new_peers),
             QueryPeerIter::ClosestDisjoint(iter) => iter.on_success(peer, new_peers),
@@ -329,6 +531,43 @@ impl<TInner> Query<TInner> {
         };
         if updated {
             self.stats.success += 1;
+            if let Some(path) = self.path_stats_mut(peer) {
+                path.success += 1;
+            }
+        }
+        updated
+    }
+
+    /// Incorporates peers learned outside of the query, if the query iterates towards
+    /// the closest peers of a key.
+    ///
+    /// Returns `true` if any of the given peers was not yet known to the query.
+    pub(crate) fn inject_peers<I>(&mut self, peers: I) -> bool
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        match &mut self.peer_iter {
+            QueryPeerIter::Closest(iter) => iter.inject_peers(peers),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.inject_peers(peers),
+            QueryPeerIter::Fixed(_) => false,
+        }
+    }
+
+    /// Returns the target of the query, if it iterates towards the closest peers of a key.
+    pub(crate) fn target(&self) -> Option<&KeyBytes> {
+        match &self.peer_iter {
+            QueryPeerIter::Closest(iter) => Some(iter.target()),
+            QueryPeerIter::ClosestDisjoint(iter) => Some(iter.target()),
+            QueryPeerIter::Fixed(_) => None,
+        }
+    }
+
+    /// Returns the distance of the closest peer to the target known to the query, if applicable.
+    fn closest_distance(&self) -> Option<Distance> {
+        match &self.peer_iter {
+            QueryPeerIter::Closest(iter) => iter.closest_distance(),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.closest_distance(),
+            QueryPeerIter::Fixed(_) => None,
         }
     }
 
@@ -336,12 +575,23 @@ impl<TInner> Query<TInner> {
     fn next(&mut self, now: Instant) -> PeersIterState<'_> {
         let state = match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.next(now),
-            QueryPeerIter::ClosestDisjoint(iter) => iter.next(now),
+            QueryPeerIter::ClosestDisjoint(iter) => {
+                let state = iter.next(now);
+                if let PeersIterState::Waiting(Some(peer)) = &state {
+                    if let Some(path) = iter.path_of(peer) {
+                        self.stats.paths[path].requests += 1;
+                    }
+                }
+                state
+            }
             QueryPeerIter::Fixed(iter) => iter.next(),
         };
 
-        if let PeersIterState::Waiting(Some(_)) = state {
+        if let PeersIterState::Waiting(Some(peer)) = &state {
             self.stats.requests += 1;
+            if let Some(tracer) = &self.tracer {
+                tracer.on_peer_contacted(self.id, peer);
+            }
         }
 
         state
@@ -420,6 +670,37 @@ impl<TInner> Query<TInner> {
     }
 }
 
+/// A tracer observing the state transitions of queries, e.g. to reconstruct
+/// full lookup traces for research and debugging.
+///
+/// All methods have a default implementation that does nothing, such that
+/// implementations only need to override the transitions of interest. The
+/// methods are called from within the [`NetworkBehaviour`](libp2p_swarm::NetworkBehaviour)
+/// and must therefore not block.
+///
+/// See [`crate::Config::set_query_tracer`].
+pub trait QueryTracer: Send + Sync + 'static {
+    /// A request is about to be sent to `peer`.
+    fn on_peer_contacted(&self, _query: QueryId, _peer: &PeerId) {}
+
+    /// A response was received from `peer`, reporting `num_closer_peers` peers
+    /// closer to the target.
+    fn on_response(&self, _query: QueryId, _peer: &PeerId, _num_closer_peers: usize) {}
+
+    /// The request to `peer` failed.
+    fn on_failure(&self, _query: QueryId, _peer: &PeerId) {}
+
+    /// The closest peer to the target known to an iterative query got closer,
+    /// now being at the given distance.
+    fn on_narrowed(&self, _query: QueryId, _closest: Distance) {}
+
+    /// The query finished.
+    fn on_finished(&self, _query: QueryId, _stats: &QueryStats) {}
+
+    /// The query timed out.
+    fn on_timeout(&self, _query: QueryId, _stats: &QueryStats) {}
+}
+
 /// The result of a `Query`.
 pub(crate) struct QueryResult<TInner, TPeers> {
     /// The opaque inner query state.
@@ -436,6 +717,10 @@ pub struct QueryStats {
     requests: u32,
     success: u32,
     failure: u32,
+    evicted: u32,
+    connection_wait: Duration,
+    max_connection_wait: Duration,
+    paths: Vec<PathStats>,
     start: Option<Instant>,
     end: Option<Instant>,
 }
@@ -446,6 +731,10 @@ impl QueryStats {
             requests: 0,
             success: 0,
             failure: 0,
+            evicted: 0,
+            connection_wait: Duration::ZERO,
+            max_connection_wait: Duration::ZERO,
+            paths: Vec::new(),
             start: None,
             end: None,
         }
@@ -474,6 +763,32 @@ impl QueryStats {
         self.requests - (self.success + self.failure)
     }
 
+    /// Gets the number of requests that were dropped while waiting for a connection
+    /// to their peer, see [`crate::Config::set_max_pending_rpcs`].
+    ///
+    /// Evicted requests are counted as failures.
+    pub fn num_evicted(&self) -> u32 {
+        self.evicted
+    }
+
+    /// Gets the total time requests spent waiting for a connection to their peer.
+    pub fn connection_wait(&self) -> Duration {
+        self.connection_wait
+    }
+
+    /// Gets the longest time a single request spent waiting for a connection to its peer.
+    pub fn max_connection_wait(&self) -> Duration {
+        self.max_connection_wait
+    }
+
+    /// Gets the statistics of each disjoint path, indexed by path.
+    ///
+    /// Empty unless the query uses disjoint paths, see
+    /// [`crate::Config::disjoint_query_paths`].
+    pub fn paths(&self) -> &[PathStats] {
+        &self.paths
+    }
+
     /// Gets the duration of the query.
     ///
     /// If the query has not yet finished, the duration is measured from the
@@ -500,10 +815,23 @@ impl QueryStats {
     /// start and end of the queries are taken as the minimum and
     /// maximum, respectively.
     pub fn merge(self, other: QueryStats) -> Self {
+        let (mut paths, other_paths) = if self.paths.len() >= other.paths.len() {
+            (self.paths, other.paths)
+        } else {
+            (other.paths, self.paths)
+        };
+        for (path, other) in paths.iter_mut().zip(other_paths) {
+            *path = path.merge(other);
+        }
+
         QueryStats {
             requests: self.requests + other.requests,
             success: self.success + other.success,
             failure: self.failure + other.failure,
+            evicted: self.evicted + other.evicted,
+            connection_wait: self.connection_wait + other.connection_wait,
+            max_connection_wait: self.max_connection_wait.max(other.max_connection_wait),
+            paths,
             start: match (self.start, other.start) {
                 (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
                 (a, b) => a.or(b),
@@ -512,3 +840,82 @@ impl QueryStats {
         }
     }
 }
+
+/// Execution statistics of a single disjoint path of a query.
+///
+/// See [`QueryStats::paths`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathStats {
+    requests: u32,
+    success: u32,
+    failure: u32,
+}
+
+impl PathStats {
+    /// Gets the number of requests initiated by the path.
+    pub fn num_requests(&self) -> u32 {
+        self.requests
+    }
+
+    /// Gets the number of successful requests of the path.
+    pub fn num_successes(&self) -> u32 {
+        self.success
+    }
+
+    /// Gets the number of failed requests of the path.
+    pub fn num_failures(&self) -> u32 {
+        self.failure
+    }
+
+    fn merge(self, other: PathStats) -> Self {
+        PathStats {
+            requests: self.requests + other.requests,
+            success: self.success + other.success,
+            failure: self.failure + other.failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_requests_are_retried_within_budget() {
+        let config = QueryConfig {
+            max_rpc_retries: 1,
+            rpc_retry_backoff: Duration::from_secs(1),
+            ..QueryConfig::default()
+        };
+        let mut pool = QueryPool::new(config);
+        let peer = PeerId::random();
+        let now = Instant::now();
+        pool.add_iter_closest(Key::from(PeerId::random()), [Key::from(peer)], ());
+
+        match pool.poll(now) {
+            QueryPoolState::Waiting(Some((query, p))) => {
+                assert_eq!(p, peer);
+                assert!(query.try_retry(&peer, now));
+            }
+            _ => panic!("Expected a request to the peer."),
+        }
+
+        // The retry is only sent once the backoff elapsed.
+        assert!(matches!(pool.poll(now), QueryPoolState::Waiting(None)));
+        match pool.poll(now + Duration::from_secs(1)) {
+            QueryPoolState::Waiting(Some((query, p))) => {
+                assert_eq!(p, peer);
+                assert_eq!(query.stats().num_requests(), 2);
+                // The retry budget of the peer is exhausted.
+                assert!(!query.try_retry(&peer, now));
+                query.on_failure(&peer);
+            }
+            _ => panic!("Expected a retried request to the peer."),
+        }
+
+        assert!(matches!(
+            pool.poll(now + Duration::from_secs(1)),
+            QueryPoolState::Finished(_)
+        ));
+    }
+}
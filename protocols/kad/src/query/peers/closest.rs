@@ -23,7 +23,8 @@ use super::*;
 use crate::kbucket::{Distance, Key, KeyBytes};
 use crate::{ALPHA_VALUE, K_VALUE};
 use std::collections::btree_map::{BTreeMap, Entry};
-use std::{num::NonZeroUsize, time::Duration};
+use std::sync::Arc;
+use std::{fmt, num::NonZeroUsize, time::Duration};
 use web_time::Instant;
 
 pub(crate) mod disjoint;
@@ -70,6 +71,13 @@ pub struct ClosestPeersIterConfig {
     /// the peer when evaluating the termination conditions, until and unless a
     /// result is delivered. Defaults to `10` seconds.
     pub peer_timeout: Duration,
+
+    /// The function providing the observed latency of a peer, if any.
+    ///
+    /// When set, the iterator prefers peers with a lower latency among the
+    /// not yet contacted peers that are equally close to the target, i.e. that
+    /// fall into the same bucket w.r.t. the target. Defaults to `None`.
+    pub latency_fn: Option<PeerLatencyFn>,
 }
 
 impl Default for ClosestPeersIterConfig {
@@ -78,10 +86,36 @@ impl Default for ClosestPeersIterConfig {
             parallelism: ALPHA_VALUE,
             num_results: K_VALUE,
             peer_timeout: Duration::from_secs(10),
+            latency_fn: None,
         }
     }
 }
 
+/// A function returning the observed latency (e.g. round-trip time) of a peer, if known.
+///
+/// See [`crate::Config::set_peer_latency_fn`].
+#[derive(Clone)]
+pub struct PeerLatencyFn(Arc<dyn Fn(&PeerId) -> Option<Duration> + Send + Sync + 'static>);
+
+impl PeerLatencyFn {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(&PeerId) -> Option<Duration> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    fn latency(&self, peer: &PeerId) -> Option<Duration> {
+        (self.0)(peer)
+    }
+}
+
+impl fmt::Debug for PeerLatencyFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PeerLatencyFn").finish()
+    }
+}
+
 impl ClosestPeersIter {
     /// Creates a new iterator with a default configuration.
     pub fn new<I>(target: KeyBytes, known_closest_peers: I) -> Self
@@ -272,6 +306,50 @@ impl ClosestPeersIter {
         true
     }
 
+    /// Incorporates peers learned outside of the iterator, e.g. out-of-band,
+    /// as if they had been reported by a peer that has been contacted.
+    ///
+    /// If one of the new peers is closer to the target than the furthest of the
+    /// `num_results` closest peers seen so far, a stalled iterator resumes iterating.
+    ///
+    /// Returns `true` if any of the given peers was not yet known to the iterator.
+    /// If the iterator is finished, calling this function has no effect and `false`
+    /// is returned.
+    pub fn inject_peers<I>(&mut self, peers: I) -> bool
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        if let State::Finished = self.state {
+            return false;
+        }
+
+        let cur_range = self
+            .closest_peers
+            .keys()
+            .nth(self.config.num_results.get() - 1)
+            .copied();
+        let mut inserted = false;
+        let mut progress = false;
+        for peer in peers {
+            let key = Key::from(peer);
+            let distance = self.target.distance(&key);
+            if let Entry::Vacant(entry) = self.closest_peers.entry(distance) {
+                entry.insert(Peer {
+                    key,
+                    state: PeerState::NotContacted,
+                });
+                inserted = true;
+                progress = progress || cur_range.map_or(true, |range| distance < range);
+            }
+        }
+
+        if progress {
+            self.state = State::Iterating { no_progress: 0 };
+        }
+
+        inserted
+    }
+
     /// Returns the list of peers for which the iterator is currently waiting
     /// for results.
     pub fn waiting(&self) -> impl Iterator<Item = &PeerId> {
@@ -310,7 +388,10 @@ impl ClosestPeersIter {
         // Check if the iterator is at capacity w.r.t. the allowed parallelism.
         let at_capacity = self.at_capacity();
 
-        for peer in self.closest_peers.values_mut() {
+        // The closest peer that has not yet been contacted, if the iterator may contact it.
+        let mut next_candidate = None;
+
+        for (distance, peer) in self.closest_peers.iter_mut() {
             match peer.state {
                 PeerState::Waiting(timeout) => {
                     if now >= timeout {
@@ -347,10 +428,8 @@ impl ClosestPeersIter {
 
                 PeerState::NotContacted => {
                     if !at_capacity {
-                        let timeout = now + self.config.peer_timeout;
-                        peer.state = PeerState::Waiting(timeout);
-                        self.num_waiting += 1;
-                        return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())));
+                        next_candidate = Some(*distance);
+                        break;
                     } else {
                         return PeersIterState::WaitingAtCapacity;
                     }
@@ -362,6 +441,18 @@ impl ClosestPeersIter {
             }
         }
 
+        if let Some(closest) = next_candidate {
+            let distance = self.select_candidate(closest);
+            let timeout = now + self.config.peer_timeout;
+            let peer = self
+                .closest_peers
+                .get_mut(&distance)
+                .expect("Selected candidate to be among the closest peers.");
+            peer.state = PeerState::Waiting(timeout);
+            self.num_waiting += 1;
+            return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())));
+        }
+
         if self.num_waiting > 0 {
             // The iterator is still waiting for results and not at capacity w.r.t.
             // the allowed parallelism, but there are no new peers to contact
@@ -385,6 +476,16 @@ impl ClosestPeersIter {
         self.state == State::Finished
     }
 
+    /// Returns the target of the iterator.
+    pub fn target(&self) -> &KeyBytes {
+        &self.target
+    }
+
+    /// Returns the distance of the closest peer to the target known to the iterator.
+    pub fn closest_distance(&self) -> Option<Distance> {
+        self.closest_peers.keys().next().copied()
+    }
+
     /// Consumes the iterator, returning the closest peers.
     pub fn into_result(self) -> impl Iterator<Item = PeerId> {
         self.closest_peers
@@ -399,6 +500,32 @@ impl ClosestPeersIter {
             .take(self.config.num_results.get())
     }
 
+    /// Selects the next peer to contact, given the distance of the closest
+    /// peer that has not yet been contacted.
+    ///
+    /// Without a configured [`PeerLatencyFn`] that is always the closest peer.
+    /// Otherwise, the not yet contacted peer with the lowest known latency
+    /// among those in the same bucket w.r.t. the target as the closest one is
+    /// selected, favouring closer peers on equal latency. Peers with an unknown
+    /// latency are only selected if no latency is known for any candidate.
+    fn select_candidate(&self, closest: Distance) -> Distance {
+        let Some(latency_fn) = self.config.latency_fn.as_ref() else {
+            return closest;
+        };
+
+        let bucket = closest.ilog2();
+        self.closest_peers
+            .range(closest..)
+            .take_while(|(distance, _)| distance.ilog2() == bucket)
+            .filter(|(_, peer)| matches!(peer.state, PeerState::NotContacted))
+            .min_by_key(|(_, peer)| match latency_fn.latency(peer.key.preimage()) {
+                Some(latency) => (false, latency),
+                None => (true, Duration::ZERO),
+            })
+            .map(|(distance, _)| *distance)
+            .unwrap_or(closest)
+    }
+
     /// Checks if the iterator is at capacity w.r.t. the permitted parallelism.
     ///
     /// While the iterator is stalled, up to `num_results` parallel requests
@@ -538,6 +665,7 @@ mod tests {
                 parallelism: NonZeroUsize::new(g.gen_range(1..10)).unwrap(),
                 num_results: NonZeroUsize::new(g.gen_range(1..25)).unwrap(),
                 peer_timeout: Duration::from_secs(g.gen_range(10..30)),
+                latency_fn: None,
             };
             ClosestPeersIter::with_config(config, target, known_closest_peers)
         }
@@ -832,4 +960,67 @@ mod tests {
 
         QuickCheck::new().tests(10).quickcheck(prop as fn(_))
     }
+
+    #[test]
+    fn prefers_faster_peer_among_equally_close() {
+        let mut rng = StdRng::from_entropy();
+        let target = Key::from(random_peers(1, &mut rng)[0]);
+
+        // Find two peers that fall into the same bucket w.r.t. the target.
+        let (closer, farther) = loop {
+            let mut peers = random_peers(2, &mut rng)
+                .into_iter()
+                .map(Key::from)
+                .collect::<Vec<_>>();
+            peers.sort_by_key(|p| target.distance(p));
+            if target.distance(&peers[0]).ilog2() == target.distance(&peers[1]).ilog2() {
+                break (*peers[0].preimage(), *peers[1].preimage());
+            }
+        };
+
+        let config = ClosestPeersIterConfig {
+            latency_fn: Some(PeerLatencyFn::new(move |peer| {
+                if *peer == farther {
+                    Some(Duration::from_millis(10))
+                } else {
+                    Some(Duration::from_millis(100))
+                }
+            })),
+            ..ClosestPeersIterConfig::default()
+        };
+        let mut iter = ClosestPeersIter::with_config(
+            config,
+            target,
+            [closer, farther].into_iter().map(Key::from),
+        );
+
+        let now = Instant::now();
+        assert_eq!(
+            PeersIterState::Waiting(Some(Cow::Borrowed(&farther))),
+            iter.next(now)
+        );
+        assert_eq!(
+            PeersIterState::Waiting(Some(Cow::Borrowed(&closer))),
+            iter.next(now)
+        );
+    }
+
+    #[test]
+    fn injected_peers_resume_stalled_iterator() {
+        let mut rng = StdRng::from_entropy();
+        let target = Key::from(random_peers(1, &mut rng)[0]);
+        let mut iter = ClosestPeersIter::new(target.into(), iter::empty());
+        iter.state = State::Stalled;
+        let peer = random_peers(1, &mut rng)[0];
+        assert!(iter.inject_peers([peer]));
+        assert!(!iter.inject_peers([peer]), "Peer is already known.");
+        assert_eq!(State::Iterating { no_progress: 0 }, iter.state);
+        assert_eq!(
+            PeersIterState::Waiting(Some(Cow::Borrowed(&peer))),
+            iter.next(Instant::now())
+        );
+
+        iter.finish();
+        assert!(!iter.inject_peers(random_peers(1, &mut rng)));
+    }
 }
@@ -186,7 +186,48 @@ impl ClosestDisjointPeersIter {
         updated
     }
 
-    pub(crate) fn next(&mut self, now: Instant) -> PeersIterState<'_> {
+    /// Incorporates peers learned outside of the iterator into all paths.
+    ///
+    /// Like the initially known peers, the injected peers are shared by all paths,
+    /// given that they were not reported by a peer of any particular path.
+    ///
+    /// Returns `true` if any of the given peers was not yet known to any of the paths.
+    pub(crate) fn inject_peers<I>(&mut self, peers: I) -> bool
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        let peers = peers.into_iter().collect::<Vec<_>>();
+        let mut inserted = false;
+        for iter in &mut self.iters {
+            inserted |= iter.inject_peers(peers.iter().copied());
+        }
+
+        inserted
+    }
+
+    /// Returns the target of the iterator.
+    pub(crate) fn target(&self) -> &KeyBytes {
+        &self.target
+    }
+
+    /// Returns the distance of the closest peer to the target known to any of the iterators.
+    pub(crate) fn closest_distance(&self) -> Option<Distance> {
+        self.iters.iter().filter_map(|i| i.closest_distance()).min()
+    }
+
+    /// Returns the number of disjoint paths.
+    pub(crate) fn num_paths(&self) -> usize {
+        self.iters.len()
+    }
+
+    /// Returns the index of the path that contacted the given peer, if any.
+    pub(crate) fn path_of(&self, peer: &PeerId) -> Option<usize> {
+        self.contacted_peers
+            .get(peer)
+            .map(|state| state.initiated_by.0)
+    }
+
+    pub(crate) fn next(&mut self, now: Instant) -> PeersIterState<'static> {
         let mut state = None;
 
         // Ensure querying each iterator at most once.
@@ -595,6 +636,7 @@ mod tests {
                 parallelism: Parallelism::arbitrary(g).0,
                 num_results: NumResults::arbitrary(g).0,
                 peer_timeout: Duration::from_secs(1),
+                latency_fn: None,
             }
         }
     }
@@ -29,6 +29,7 @@ use libp2p_identity::PeerId;
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::hash::{Hash, Hasher};
+use std::time::Duration;
 use web_time::Instant;
 
 /// The (opaque) key of a record.
@@ -99,6 +100,17 @@ impl Record {
         }
     }
 
+    /// Sets the expiration of the record to `ttl` from now.
+    ///
+    /// The expiration takes precedence over the configured record TTL (see
+    /// [`crate::Config::set_record_ttl`]) on the local node as well as on the
+    /// remote nodes the record is stored at, unless their configured TTL
+    /// expires earlier.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.expires = Some(Instant::now() + ttl);
+        self
+    }
+
     /// Checks whether the record is expired w.r.t. the given `Instant`.
     pub fn is_expired(&self, now: Instant) -> bool {
         self.expires.map_or(false, |t| now >= t)
@@ -163,7 +175,6 @@ mod tests {
     use super::*;
     use crate::SHA_256_MH;
     use quickcheck::*;
-    use std::time::Duration;
 
     impl Arbitrary for Key {
         fn arbitrary(g: &mut Gen) -> Key {
@@ -183,7 +194,7 @@ mod tests {
                     None
                 },
                 expires: if bool::arbitrary(g) {
-                    Some(Instant::now() + Duration::from_secs(g.gen_range(0..60)))
+                    Some(Instant::now() + Duration::from_secs(g.gen_range(1..60)))
                 } else {
                     None
                 },
@@ -31,7 +31,7 @@ use std::borrow::Cow;
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// The possible errors of a `RecordStore` operation.
-#[derive(Error, Debug, Clone)]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     /// The store is at capacity w.r.t. the total number of stored records.
     #[error("the store cannot contain any more records")]
@@ -104,7 +104,10 @@ impl RecordStore for MemoryStore {
     >;
 
     fn get(&self, k: &Key) -> Option<Cow<'_, Record>> {
-        self.records.get(k).map(Cow::Borrowed)
+        self.records
+            .get(k)
+            .filter(|r| !r.is_expired(Instant::now()))
+            .map(Cow::Borrowed)
     }
 
     fn put(&mut self, r: Record) -> Result<()> {
@@ -112,6 +115,12 @@ impl RecordStore for MemoryStore {
             return Err(Error::ValueTooLarge);
         }
 
+        if self.records.len() >= self.config.max_records && !self.records.contains_key(&r.key) {
+            // Make room by dropping expired records first.
+            let now = Instant::now();
+            self.records.retain(|_, r| !r.is_expired(now));
+        }
+
         let num_records = self.records.len();
 
         match self.records.entry(r.key.clone()) {
@@ -302,6 +311,26 @@ mod tests {
         assert_eq!(vec![rec.clone()], store.providers(&rec.key).to_vec());
     }
 
+    #[test]
+    fn expired_records() {
+        let mut store = MemoryStore::with_config(
+            PeerId::random(),
+            MemoryStoreConfig {
+                max_records: 1,
+                ..Default::default()
+            },
+        );
+        let mut expired = Record::new(random_multihash(), vec![1]);
+        expired.expires = Some(Instant::now());
+        assert!(store.put(expired.clone()).is_ok());
+        assert!(store.get(&expired.key).is_none());
+
+        // The expired record makes room for a new one.
+        let rec = Record::new(random_multihash(), vec![2]).with_ttl(Duration::from_secs(60));
+        assert!(store.put(rec.clone()).is_ok());
+        assert_eq!(Some(Cow::Borrowed(&rec)), store.get(&rec.key));
+    }
+
     #[test]
     fn max_provided_keys() {
         let mut store = MemoryStore::new(PeerId::random());
@@ -0,0 +1,209 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Strategies for republishing the provider records of the local node.
+//!
+//! A [`ReprovideStrategy`] decides when the provider records of which
+//! [`KeyspaceRegion`] are republished. The [`PeriodicSweep`] republishes all
+//! records at once, whereas the [`RegionSweep`] walks the keyspace region by
+//! region, spreading the republication of huge sets of provided keys evenly
+//! over the publication interval.
+
+use crate::kbucket;
+use crate::record;
+use futures::prelude::*;
+use futures_timer::Delay;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Decides when the provider records of the local node are republished.
+///
+/// See [`Behaviour::set_reprovide_strategy`](crate::Behaviour::set_reprovide_strategy).
+pub trait ReprovideStrategy: Send + 'static {
+    /// Polls for the next region of the keyspace whose provider records are due
+    /// for republication.
+    ///
+    /// When `Poll::Pending` is returned, the strategy must arrange for the current
+    /// task to be woken up once the next region is due.
+    fn poll_next_region(&mut self, cx: &mut Context<'_>, now: Instant) -> Poll<KeyspaceRegion>;
+}
+
+/// A region of the keyspace, i.e. all keys whose hash starts with the same prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyspaceRegion {
+    prefix: u32,
+    bits: u8,
+}
+
+impl KeyspaceRegion {
+    /// The region covering the whole keyspace.
+    pub const ALL: KeyspaceRegion = KeyspaceRegion { prefix: 0, bits: 0 };
+
+    /// Creates the region of all keys whose hash starts with the `bits` lowest bits of
+    /// `prefix`.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is greater than 32.
+    pub fn new(prefix: u32, bits: u8) -> Self {
+        assert!(bits <= 32, "prefix of more than 32 bits");
+        let prefix = prefix.checked_shl(32 - u32::from(bits)).unwrap_or(0);
+        KeyspaceRegion { prefix, bits }
+    }
+
+    /// Returns whether the region contains the given key.
+    pub fn contains(&self, key: &record::Key) -> bool {
+        if self.bits == 0 {
+            return true;
+        }
+        let hash = kbucket::Key::new(key.clone());
+        let bytes = hash.hashed_bytes();
+        let hash = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let shift = 32 - u32::from(self.bits);
+        hash >> shift == self.prefix >> shift
+    }
+}
+
+/// Republishes all provider records at once, every `interval`.
+///
+/// This is the default strategy, using the
+/// [provider publication interval](crate::Config::set_provider_publication_interval).
+#[derive(Debug)]
+pub struct PeriodicSweep {
+    interval: Duration,
+    delay: Delay,
+    deadline: Instant,
+}
+
+impl PeriodicSweep {
+    /// Creates a strategy republishing all provider records every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        PeriodicSweep {
+            interval,
+            delay: Delay::new(interval),
+            deadline: Instant::now() + interval,
+        }
+    }
+}
+
+impl ReprovideStrategy for PeriodicSweep {
+    fn poll_next_region(&mut self, cx: &mut Context<'_>, now: Instant) -> Poll<KeyspaceRegion> {
+        if now < self.deadline && self.delay.poll_unpin(cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.deadline = now + self.interval;
+        self.delay.reset(self.interval);
+
+        Poll::Ready(KeyspaceRegion::ALL)
+    }
+}
+
+/// Walks the keyspace in `2^bits` regions, republishing the provider records of
+/// one region at a time, such that every record is republished once per `interval`.
+///
+/// Compared to the [`PeriodicSweep`], this bounds the number of records republished
+/// at once for nodes providing a huge number of keys. Since the keys of a region share
+/// a prefix, they are also close to each other in the keyspace.
+#[derive(Debug)]
+pub struct RegionSweep {
+    step: Duration,
+    bits: u8,
+    next: u32,
+    delay: Delay,
+    deadline: Instant,
+}
+
+impl RegionSweep {
+    /// Creates a strategy walking the keyspace in `2^bits` regions per `interval`.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is greater than 16.
+    pub fn new(interval: Duration, bits: u8) -> Self {
+        assert!(bits <= 16, "more than 2^16 regions");
+        let step = interval / (1 << bits);
+        RegionSweep {
+            step,
+            bits,
+            next: 0,
+            delay: Delay::new(step),
+            deadline: Instant::now() + step,
+        }
+    }
+}
+
+impl ReprovideStrategy for RegionSweep {
+    fn poll_next_region(&mut self, cx: &mut Context<'_>, now: Instant) -> Poll<KeyspaceRegion> {
+        if now < self.deadline && self.delay.poll_unpin(cx).is_pending() {
+            return Poll::Pending;
+        }
+        self.deadline = now + self.step;
+        self.delay.reset(self.step);
+
+        let region = KeyspaceRegion::new(self.next, self.bits);
+        self.next = (self.next + 1) % (1 << self.bits);
+        Poll::Ready(region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future::poll_fn};
+
+    #[test]
+    fn regions_partition_the_keyspace() {
+        let bits = 3;
+        let keys = (0..100u32)
+            .map(|i| record::Key::new(&i.to_be_bytes()))
+            .collect::<Vec<_>>();
+
+        for key in &keys {
+            let regions = (0..1 << bits)
+                .filter(|prefix| KeyspaceRegion::new(*prefix, bits).contains(key))
+                .count();
+            assert_eq!(regions, 1);
+            assert!(KeyspaceRegion::ALL.contains(key));
+        }
+    }
+
+    #[test]
+    fn region_sweep_walks_all_regions() {
+        let mut sweep = RegionSweep::new(Duration::from_secs(80), 3);
+
+        block_on(poll_fn(|cx| {
+            let mut now = Instant::now();
+            assert!(sweep.poll_next_region(cx, now).is_pending());
+
+            for round in 0..2 {
+                for prefix in 0..8 {
+                    now += Duration::from_secs(10);
+                    assert_eq!(
+                        sweep.poll_next_region(cx, now),
+                        Poll::Ready(KeyspaceRegion::new(prefix, 3)),
+                        "round {round}"
+                    );
+                }
+            }
+            Poll::Ready(())
+        }));
+    }
+}
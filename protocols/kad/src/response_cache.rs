@@ -0,0 +1,140 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Short-lived cache of the closer peers returned for inbound `FIND_NODE` requests.
+//!
+//! Nodes used for bootstrapping receive many identical requests, e.g. for their own
+//! key or for the keys of the peers bootstrapping through them. Within the TTL of an
+//! entry, the closer peers of a key are served from the cache instead of being looked
+//! up in the routing table. The cache is cleared on every change of the routing table.
+
+use crate::protocol::KadPeer;
+use std::collections::HashMap;
+use std::time::Duration;
+use web_time::Instant;
+
+/// The maximum number of keys whose closer peers are cached.
+const MAX_ENTRIES: usize = 256;
+
+#[derive(Debug)]
+pub(crate) struct FindNodeCache {
+    /// The TTL of the entries, `None` if caching is disabled.
+    ttl: Option<Duration>,
+    entries: HashMap<Vec<u8>, CacheEntry>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    expires: Instant,
+    peers: Vec<KadPeer>,
+}
+
+impl FindNodeCache {
+    pub(crate) fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.ttl.is_some()
+    }
+
+    /// Returns the cached closer peers of the key, unless they expired.
+    pub(crate) fn get(&self, key: &[u8], now: Instant) -> Option<&[KadPeer]> {
+        self.entries
+            .get(key)
+            .filter(|entry| entry.expires > now)
+            .map(|entry| entry.peers.as_slice())
+    }
+
+    /// Caches the closer peers of the key.
+    ///
+    /// If the cache is full, expired entries are evicted. If there are none,
+    /// the peers are not cached.
+    pub(crate) fn insert(&mut self, key: Vec<u8>, peers: Vec<KadPeer>, now: Instant) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        if self.entries.len() >= MAX_ENTRIES && !self.entries.contains_key(&key) {
+            self.entries.retain(|_, entry| entry.expires > now);
+            if self.entries.len() >= MAX_ENTRIES {
+                return;
+            }
+        }
+        self.entries.insert(
+            key,
+            CacheEntry {
+                expires: now + ttl,
+                peers,
+            },
+        );
+    }
+
+    /// Removes all cached entries, e.g. after a change of the routing table.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ConnectionType;
+    use libp2p_identity::PeerId;
+
+    fn peer() -> KadPeer {
+        KadPeer {
+            node_id: PeerId::random(),
+            multiaddrs: Vec::new(),
+            connection_ty: ConnectionType::Connected,
+        }
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let mut cache = FindNodeCache::new(Some(Duration::from_secs(1)));
+        let now = Instant::now();
+        let peers = vec![peer()];
+
+        cache.insert(b"key".to_vec(), peers.clone(), now);
+
+        assert_eq!(cache.get(b"key", now), Some(peers.as_slice()));
+        assert_eq!(cache.get(b"key", now + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn full_cache_only_evicts_expired_entries() {
+        let mut cache = FindNodeCache::new(Some(Duration::from_secs(1)));
+        let now = Instant::now();
+        for i in 0..MAX_ENTRIES {
+            cache.insert(i.to_be_bytes().to_vec(), vec![peer()], now);
+        }
+
+        cache.insert(b"key".to_vec(), vec![peer()], now);
+        assert!(cache.get(b"key", now).is_none());
+
+        let later = now + Duration::from_secs(2);
+        cache.insert(b"key".to_vec(), vec![peer()], later);
+        assert!(cache.get(b"key", later).is_some());
+        assert_eq!(cache.entries.len(), 1);
+    }
+}
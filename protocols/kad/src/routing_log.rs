@@ -0,0 +1,409 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Incremental persistence of the routing table as an append-only log of its changes, see
+//! [`Behaviour::set_routing_table_store`](crate::Behaviour::set_routing_table_store).
+//!
+//! Every change is appended as an entry framed by its length and a checksum, so that a log torn
+//! by a crash or partially corrupted can still be replayed up to, or around, the damaged entries.
+//! Once the log grows large relative to the routing table, it is compacted into a snapshot of the
+//! routing table.
+
+use crate::Addresses;
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// The minimum number of entries of a log before it is compacted.
+const MIN_COMPACTION_ENTRIES: usize = 64;
+
+/// The size of the checksum of an entry.
+const CHECKSUM_LEN: usize = 4;
+
+const TAG_UPSERTED: u8 = 0;
+const TAG_REMOVED: u8 = 1;
+
+/// Storage for the log of the changes of the routing table.
+///
+/// The store only deals with opaque bytes: the entries are encoded, checksummed and replayed by
+/// the [`Behaviour`](crate::Behaviour).
+pub trait RoutingTableStore: Send + 'static {
+    /// Appends the given bytes to the log.
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// Reads the whole log.
+    fn read(&mut self) -> io::Result<Vec<u8>>;
+
+    /// Replaces the whole log with the given bytes, ideally atomically.
+    fn replace(&mut self, bytes: &[u8]) -> io::Result<()>;
+}
+
+/// A [`RoutingTableStore`] keeping the log in memory, e.g. for tests.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryRoutingTableStore(Vec<u8>);
+
+impl MemoryRoutingTableStore {
+    /// Creates a store from the bytes of an existing log.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of the log.
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl RoutingTableStore for MemoryRoutingTableStore {
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read(&mut self) -> io::Result<Vec<u8>> {
+        Ok(self.0.clone())
+    }
+
+    fn replace(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0 = bytes.to_vec();
+        Ok(())
+    }
+}
+
+/// A [`RoutingTableStore`] keeping the log in a file.
+///
+/// Compactions are written to a temporary file next to the log first, which then replaces the
+/// log by renaming it.
+#[derive(Debug)]
+pub struct FileRoutingTableStore {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl FileRoutingTableStore {
+    /// Creates a store for the log at the given path, which is created on the first change.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+        }
+    }
+}
+
+impl RoutingTableStore for FileRoutingTableStore {
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            ),
+        };
+        file.write_all(bytes)?;
+        file.flush()
+    }
+
+    fn read(&mut self) -> io::Result<Vec<u8>> {
+        match fs::read(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    fn replace(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+
+        // Appends go to the new log.
+        self.file = None;
+        Ok(())
+    }
+}
+
+/// A change of the routing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RoutingChange {
+    /// The peer was inserted into the routing table, or its addresses changed.
+    Upserted {
+        peer: PeerId,
+        addresses: Vec<Multiaddr>,
+    },
+    /// The peer was removed from the routing table.
+    Removed { peer: PeerId },
+}
+
+impl RoutingChange {
+    pub(crate) fn upserted(peer: PeerId, addresses: &Addresses) -> Self {
+        RoutingChange::Upserted {
+            peer,
+            addresses: addresses.iter().cloned().collect(),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let mut payload = Vec::new();
+        let peer = match self {
+            RoutingChange::Upserted { peer, .. } => {
+                payload.push(TAG_UPSERTED);
+                peer
+            }
+            RoutingChange::Removed { peer } => {
+                payload.push(TAG_REMOVED);
+                peer
+            }
+        };
+        push_bytes(&mut payload, &peer.to_bytes());
+        if let RoutingChange::Upserted { addresses, .. } = self {
+            push_len(&mut payload, addresses.len());
+            for address in addresses {
+                push_bytes(&mut payload, &address.to_vec());
+            }
+        }
+
+        push_len(buf, payload.len());
+        buf.extend_from_slice(&checksum(&payload));
+        buf.extend_from_slice(&payload);
+    }
+
+    fn decode(mut payload: &[u8]) -> Option<Self> {
+        let (&tag, rest) = payload.split_first()?;
+        payload = rest;
+        let peer = PeerId::from_bytes(take_bytes(&mut payload)?).ok()?;
+
+        let change = match tag {
+            TAG_UPSERTED => {
+                let len = take_len(&mut payload)?;
+                let mut addresses = Vec::new();
+                for _ in 0..len {
+                    addresses.push(Multiaddr::try_from(take_bytes(&mut payload)?.to_vec()).ok()?);
+                }
+                RoutingChange::Upserted { peer, addresses }
+            }
+            TAG_REMOVED => RoutingChange::Removed { peer },
+            _ => return None,
+        };
+
+        payload.is_empty().then_some(change)
+    }
+}
+
+/// The routing table as replayed from a log.
+#[derive(Debug, Default)]
+pub(crate) struct Replayed {
+    /// The peers in the routing table along with their addresses.
+    pub(crate) peers: HashMap<PeerId, Vec<Multiaddr>>,
+    /// The number of damaged entries that were skipped.
+    pub(crate) skipped: usize,
+    /// Whether the log ended in a truncated entry, e.g. torn by a crash.
+    pub(crate) truncated: bool,
+}
+
+/// Replays the given log, skipping the entries whose checksum does not match and stopping at a
+/// truncated entry.
+pub(crate) fn replay(mut log: &[u8]) -> Replayed {
+    let mut replayed = Replayed::default();
+
+    while !log.is_empty() {
+        let Some(len) = take_len(&mut log) else {
+            replayed.truncated = true;
+            break;
+        };
+        if log.len() < CHECKSUM_LEN + len {
+            replayed.truncated = true;
+            break;
+        }
+        let (sum, rest) = log.split_at(CHECKSUM_LEN);
+        let (payload, rest) = rest.split_at(len);
+        log = rest;
+
+        let change = (checksum(payload) == sum)
+            .then(|| RoutingChange::decode(payload))
+            .flatten();
+        match change {
+            Some(RoutingChange::Upserted { peer, addresses }) => {
+                replayed.peers.insert(peer, addresses);
+            }
+            Some(RoutingChange::Removed { peer }) => {
+                replayed.peers.remove(&peer);
+            }
+            None => replayed.skipped += 1,
+        }
+    }
+
+    replayed
+}
+
+/// The log of the changes of the routing table, along with its store.
+pub(crate) struct RoutingLog {
+    store: Box<dyn RoutingTableStore>,
+    /// The number of entries in the log.
+    entries: usize,
+}
+
+impl RoutingLog {
+    pub(crate) fn new(store: Box<dyn RoutingTableStore>) -> Self {
+        Self { store, entries: 0 }
+    }
+
+    /// Reads and replays the log of the store.
+    pub(crate) fn replay(&mut self) -> io::Result<Replayed> {
+        Ok(replay(&self.store.read()?))
+    }
+
+    /// Appends the given change to the log.
+    pub(crate) fn append(&mut self, change: &RoutingChange) -> io::Result<()> {
+        let mut buf = Vec::new();
+        change.encode(&mut buf);
+        self.store.append(&buf)?;
+        self.entries += 1;
+
+        Ok(())
+    }
+
+    /// Returns whether the log is to be compacted, given the number of peers in the routing
+    /// table.
+    pub(crate) fn needs_compaction(&self, num_peers: usize) -> bool {
+        self.entries >= MIN_COMPACTION_ENTRIES.max(2 * num_peers)
+    }
+
+    /// Replaces the log with the insertion of the given peers.
+    pub(crate) fn compact(
+        &mut self,
+        peers: impl IntoIterator<Item = (PeerId, Vec<Multiaddr>)>,
+    ) -> io::Result<()> {
+        let mut buf = Vec::new();
+        let mut entries = 0;
+        for (peer, addresses) in peers {
+            RoutingChange::Upserted { peer, addresses }.encode(&mut buf);
+            entries += 1;
+        }
+        self.store.replace(&buf)?;
+        self.entries = entries;
+
+        Ok(())
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = Sha256::digest(payload);
+    digest[..CHECKSUM_LEN]
+        .try_into()
+        .expect("digest has 32 bytes")
+}
+
+fn push_len(buf: &mut Vec<u8>, len: usize) {
+    let mut len_buf = unsigned_varint::encode::usize_buffer();
+    buf.extend_from_slice(unsigned_varint::encode::usize(len, &mut len_buf));
+}
+
+fn push_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    push_len(buf, bytes.len());
+    buf.extend_from_slice(bytes);
+}
+
+fn take_len(buf: &mut &[u8]) -> Option<usize> {
+    let (len, rest) = unsigned_varint::decode::usize(buf).ok()?;
+    *buf = rest;
+    Some(len)
+}
+
+fn take_bytes<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = take_len(buf)?;
+    if buf.len() < len {
+        return None;
+    }
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upserted(peer: PeerId, port: u16) -> RoutingChange {
+        RoutingChange::Upserted {
+            peer,
+            addresses: vec![format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap()],
+        }
+    }
+
+    #[test]
+    fn replay_skips_corrupt_entries_and_stops_at_truncation() {
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let mut log = RoutingLog::new(Box::new(MemoryRoutingTableStore::default()));
+        log.append(&upserted(a, 1)).unwrap();
+        log.append(&upserted(b, 2)).unwrap();
+        log.append(&upserted(a, 3)).unwrap();
+        log.append(&RoutingChange::Removed { peer: b }).unwrap();
+        log.append(&upserted(c, 4)).unwrap();
+
+        let replayed = log.replay().unwrap();
+        assert_eq!(replayed.peers.len(), 2);
+        assert_eq!(
+            replayed.peers[&a],
+            vec!["/ip4/127.0.0.1/tcp/3".parse::<Multiaddr>().unwrap()]
+        );
+        assert!(replayed.peers.contains_key(&c));
+        assert_eq!((replayed.skipped, replayed.truncated), (0, false));
+
+        // Corrupt the last byte of the first entry and tear the last one.
+        let mut bytes = log.store.read().unwrap();
+        let mut first = Vec::new();
+        upserted(a, 1).encode(&mut first);
+        bytes[first.len() - 1] ^= 0xff;
+        bytes.truncate(bytes.len() - 1);
+
+        let replayed = replay(&bytes);
+        assert_eq!(replayed.peers.len(), 1);
+        assert!(replayed.peers.contains_key(&a));
+        assert_eq!((replayed.skipped, replayed.truncated), (1, true));
+    }
+
+    #[test]
+    fn compaction_replaces_log_with_snapshot() {
+        let peer = PeerId::random();
+        let mut log = RoutingLog::new(Box::new(MemoryRoutingTableStore::default()));
+        for port in 0..MIN_COMPACTION_ENTRIES as u16 {
+            log.append(&upserted(peer, port)).unwrap();
+        }
+        assert!(log.needs_compaction(1));
+
+        let RoutingChange::Upserted { addresses, .. } = upserted(peer, 0) else {
+            unreachable!()
+        };
+        log.compact([(peer, addresses.clone())]).unwrap();
+        assert!(!log.needs_compaction(1));
+
+        let replayed = log.replay().unwrap();
+        assert_eq!(replayed.peers, HashMap::from([(peer, addresses)]));
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Scoring of peers before they are inserted into the routing table.
+//!
+//! A [`PeerScorer`] protects the routing table against peers that are merely
+//! advertised in (possibly poisoned) query responses, by requiring a peer to
+//! prove itself before it may occupy a bucket entry.
+
+use libp2p_identity::PeerId;
+use std::time::Duration;
+use web_time::Instant;
+
+/// Decides whether a newly connected peer may be inserted into the routing table.
+///
+/// The scorer is consulted whenever a peer that is not yet in the routing table
+/// would be inserted. Peers that are rejected are considered again whenever they
+/// respond to one of the local node's requests, for as long as they stay connected.
+///
+/// Peers added explicitly through [`Behaviour::add_address`](crate::Behaviour::add_address)
+/// are not subject to the scorer.
+///
+/// See [`Config::set_peer_scorer`](crate::Config::set_peer_scorer).
+pub trait PeerScorer: Send + Sync + 'static {
+    /// Returns whether `peer` may be inserted into the routing table.
+    fn allow_insert(&self, peer: &PeerId, observation: &PeerObservation) -> bool;
+}
+
+impl<F> PeerScorer for F
+where
+    F: Fn(&PeerId, &PeerObservation) -> bool + Send + Sync + 'static,
+{
+    fn allow_insert(&self, peer: &PeerId, observation: &PeerObservation) -> bool {
+        self(peer, observation)
+    }
+}
+
+/// What the local node observed about a connected peer.
+#[derive(Debug, Clone)]
+pub struct PeerObservation {
+    pub(crate) connected_since: Instant,
+    pub(crate) num_responses: usize,
+}
+
+impl PeerObservation {
+    pub(crate) fn new() -> Self {
+        Self {
+            connected_since: Instant::now(),
+            num_responses: 0,
+        }
+    }
+
+    /// The time elapsed since the peer's first connection was established.
+    pub fn uptime(&self) -> Duration {
+        self.connected_since.elapsed()
+    }
+
+    /// The number of successful responses the peer sent to requests of the local node.
+    pub fn num_responses(&self) -> usize {
+        self.num_responses
+    }
+}
+
+/// A [`PeerScorer`] only admitting peers that have been connected for at least the given duration.
+#[derive(Debug, Clone, Copy)]
+pub struct MinimumUptime(pub Duration);
+
+impl PeerScorer for MinimumUptime {
+    fn allow_insert(&self, _: &PeerId, observation: &PeerObservation) -> bool {
+        observation.uptime() >= self.0
+    }
+}
+
+/// A [`PeerScorer`] only admitting peers that responded to at least one request of the local node.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RespondedToQuery;
+
+impl PeerScorer for RespondedToQuery {
+    fn allow_insert(&self, _: &PeerId, observation: &PeerObservation) -> bool {
+        observation.num_responses > 0
+    }
+}
@@ -1,7 +1,7 @@
 use libp2p_identify as identify;
 use libp2p_identity as identity;
 use libp2p_kad::store::MemoryStore;
-use libp2p_kad::{Behaviour, Config, Event, Mode};
+use libp2p_kad::{Behaviour, Config, Event, Mode, ModeChangeReason};
 use libp2p_swarm::{Swarm, SwarmEvent};
 use libp2p_swarm_test::SwarmExt;
 use tracing_subscriber::EnvFilter;
@@ -95,15 +95,19 @@ async fn adding_an_external_addresses_activates_server_mode_on_existing_connecti
     }
 
     // Server learns its external address (this could be through AutoNAT or some other mechanism).
-    server.add_external_address(memory_addr);
+    assert_eq!(server.behaviour().kad.mode(), Mode::Client);
+    server.add_external_address(memory_addr.clone());
 
     // The server reconfigured its connection to the client to be in server mode, pushes that information to client which as a result updates its routing table and triggers a mode change to Mode::Server.
     match libp2p_swarm_test::drive(&mut client, &mut server).await {
         (
-            [Identify(identify::Event::Received { .. }), Kad(RoutingUpdated { peer: peer1, .. })],
-            [Kad(ModeChanged { new_mode }), Identify(identify::Event::Pushed { .. })],
+            [Identify(identify::Event::Received { .. }), Identify(identify::Event::ProtocolsChanged { added, .. }), Kad(RoutingUpdated { peer: peer1, .. })],
+            [Kad(ModeChanged { new_mode, reason }), Identify(identify::Event::Pushed { .. })],
         ) => {
+            assert!(added.contains(&libp2p_kad::PROTOCOL_NAME));
             assert_eq!(new_mode, Mode::Server);
+            assert_eq!(reason, ModeChangeReason::ExternalAddrConfirmed(memory_addr));
+            assert_eq!(server.behaviour().kad.mode(), Mode::Server);
             assert_eq!(peer1, server_peer_id);
         }
         other => panic!("Unexpected events: {other:?}"),
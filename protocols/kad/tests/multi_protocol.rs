@@ -0,0 +1,103 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::future::{self, Either};
+use futures::FutureExt;
+use libp2p_kad::store::MemoryStore;
+use libp2p_kad::{Config, Event, InboundRequest, MultiBehaviour, QueryResult};
+use libp2p_swarm::{StreamProtocol, Swarm, SwarmEvent};
+use libp2p_swarm_test::SwarmExt;
+use tracing_subscriber::EnvFilter;
+
+const PUBLIC: StreamProtocol = StreamProtocol::new("/ipfs/kad/1.0.0");
+const PRIVATE: StreamProtocol = StreamProtocol::new("/private/kad/1.0.0");
+
+#[async_std::test]
+async fn inbound_requests_are_routed_to_the_dht_of_the_negotiated_protocol() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let mut server = Swarm::new_ephemeral(|key| {
+        let id = key.public().to_peer_id();
+        MultiBehaviour::new(
+            id,
+            Config::new(PUBLIC),
+            [
+                (PUBLIC, MemoryStore::new(id)),
+                (PRIVATE, MemoryStore::new(id)),
+            ],
+        )
+    });
+    let mut client = Swarm::new_ephemeral(|key| {
+        let id = key.public().to_peer_id();
+        MultiBehaviour::new(id, Config::new(PUBLIC), [(PRIVATE, MemoryStore::new(id))])
+    });
+
+    server.listen().with_memory_addr_external().await;
+    client.connect(&mut server).await;
+
+    let server_peer_id = *server.local_peer_id();
+    let server_addr = server.external_addresses().next().cloned().unwrap();
+    let kad = client.behaviour_mut().get_mut(&PRIVATE).unwrap();
+    kad.add_address(&server_peer_id, server_addr);
+    kad.get_closest_peers(server_peer_id);
+
+    let mut inbound = None;
+    let mut outbound = None;
+    while inbound.is_none() || outbound.is_none() {
+        match future::select(
+            server.next_swarm_event().boxed(),
+            client.next_swarm_event().boxed(),
+        )
+        .await
+        {
+            Either::Left((
+                SwarmEvent::Behaviour((
+                    protocol,
+                    Event::InboundRequest {
+                        request: InboundRequest::FindNode { .. },
+                    },
+                )),
+                _,
+            )) => inbound = Some(protocol),
+            Either::Right((
+                SwarmEvent::Behaviour((
+                    protocol,
+                    Event::OutboundQueryProgressed {
+                        result: QueryResult::GetClosestPeers(Ok(_)),
+                        ..
+                    },
+                )),
+                _,
+            )) => outbound = Some(protocol),
+            _ => {}
+        }
+    }
+
+    assert_eq!(inbound, Some(PRIVATE));
+    assert_eq!(outbound, Some(PRIVATE));
+    assert!(server
+        .behaviour_mut()
+        .get_mut(&PUBLIC)
+        .unwrap()
+        .kbuckets()
+        .all(|b| b.num_entries() == 0));
+}
@@ -151,6 +151,9 @@ where
     /// Handles to tasks running the mDNS queries.
     if_tasks: HashMap<IpAddr, P::TaskHandle>,
 
+    /// Senders to request an immediate query from the tasks running the mDNS queries.
+    query_now_senders: HashMap<IpAddr, mpsc::Sender<()>>,
+
     query_response_receiver: mpsc::Receiver<(PeerId, Multiaddr, Instant)>,
     query_response_sender: mpsc::Sender<(PeerId, Multiaddr, Instant)>,
 
@@ -186,6 +189,7 @@ where
             config,
             if_watch: P::new_watcher()?,
             if_tasks: Default::default(),
+            query_now_senders: Default::default(),
             query_response_receiver: rx,
             query_response_sender: tx,
             discovered_nodes: Default::default(),
@@ -206,6 +210,18 @@ where
         self.discovered_nodes.iter().map(|(p, _, _)| p)
     }
 
+    /// Sends an mDNS query on all interfaces immediately, followed by a burst of queries
+    /// at increasing intervals, e.g. when the application knows that a new device joined
+    /// the network.
+    ///
+    /// This also resets the backoff of periodic queries, see [`Config::max_query_interval`].
+    pub fn query_now(&mut self) {
+        for sender in self.query_now_senders.values_mut() {
+            // A full channel means a query is already about to be sent.
+            let _ = sender.try_send(());
+        }
+    }
+
     /// Expires a node before the ttl.
     #[deprecated(note = "Unused API. Will be removed in the next release.")]
     pub fn expire_node(&mut self, peer_id: &PeerId) {
@@ -301,15 +317,18 @@ where
                         continue;
                     }
                     if let Entry::Vacant(e) = self.if_tasks.entry(addr) {
+                        let (query_now_sender, query_now_receiver) = mpsc::channel(0);
                         match InterfaceState::<P::Socket, P::Timer>::new(
                             addr,
                             self.config.clone(),
                             self.local_peer_id,
                             self.listen_addresses.clone(),
                             self.query_response_sender.clone(),
+                            query_now_receiver,
                         ) {
                             Ok(iface_state) => {
                                 e.insert(P::spawn(iface_state));
+                                self.query_now_senders.insert(addr, query_now_sender);
                             }
                             Err(err) => {
                                 tracing::error!("failed to create `InterfaceState`: {}", err)
@@ -318,6 +337,7 @@ where
                     }
                 }
                 Ok(IfEvent::Down(inet)) => {
+                    self.query_now_senders.remove(&inet.addr());
                     if let Some(handle) = self.if_tasks.remove(&inet.addr()) {
                         tracing::info!(instance=%inet.addr(), "dropping instance");
 
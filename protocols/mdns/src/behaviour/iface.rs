@@ -34,7 +34,7 @@ use socket2::{Domain, Socket, Type};
 use std::future::Future;
 use std::sync::{Arc, RwLock};
 use std::{
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     pin::Pin,
@@ -92,8 +92,19 @@ pub(crate) struct InterfaceState<U, T> {
     send_buffer: VecDeque<Vec<u8>>,
     /// Discovery interval.
     query_interval: Duration,
+    /// The configured discovery interval, to which `query_interval` is reset
+    /// whenever a new peer is discovered.
+    min_query_interval: Duration,
+    /// The upper bound of `query_interval` while no new peers are discovered.
+    max_query_interval: Duration,
+    /// Whether a new peer was discovered since the last query.
+    discovered_new_peer: bool,
+    /// The peers discovered on this interface so far.
+    known_peers: HashSet<PeerId>,
     /// Discovery timer.
     timeout: T,
+    /// Requests to query immediately, see [`crate::Behaviour::query_now`].
+    query_now_receiver: mpsc::Receiver<()>,
     /// Multicast address.
     multicast_addr: IpAddr,
     /// Discovered addresses.
@@ -116,6 +127,7 @@ where
         local_peer_id: PeerId,
         listen_addresses: Arc<RwLock<ListenAddresses>>,
         query_response_sender: mpsc::Sender<(PeerId, Multiaddr, Instant)>,
+        query_now_receiver: mpsc::Receiver<()>,
     ) -> io::Result<Self> {
         tracing::info!(address=%addr, "creating instance on iface address");
         let recv_socket = match addr {
@@ -175,7 +187,12 @@ where
             send_buffer: Default::default(),
             discovered: Default::default(),
             query_interval,
+            min_query_interval: query_interval,
+            max_query_interval: config.max_query_interval.max(query_interval),
+            discovered_new_peer: false,
+            known_peers: Default::default(),
             timeout: T::interval_at(Instant::now(), INITIAL_TIMEOUT_INTERVAL),
+            query_now_receiver,
             multicast_addr,
             ttl: config.ttl,
             probe_state: Default::default(),
@@ -205,21 +222,42 @@ where
         let this = self.get_mut();
 
         loop {
+            // 1st priority: Low latency: Create packet ASAP when asked to.
+            if let Poll::Ready(Some(())) = this.query_now_receiver.poll_next_unpin(cx) {
+                tracing::trace!(address=%this.addr, "sending immediate query on iface");
+                this.send_buffer.push_back(build_query());
+
+                // Probe again, starting from the initial interval.
+                this.query_interval = this.min_query_interval;
+                this.probe_state = ProbeState::default();
+                this.reset_timer();
+            }
+
             // 1st priority: Low latency: Create packet ASAP after timeout.
             if this.timeout.poll_next_unpin(cx).is_ready() {
                 tracing::trace!(address=%this.addr, "sending query on iface");
                 this.send_buffer.push_back(build_query());
                 tracing::trace!(address=%this.addr, probe_state=?this.probe_state, "tick");
 
-                // Stop to probe when the initial interval reach the query interval
-                if let ProbeState::Probing(interval) = this.probe_state {
-                    let interval = interval * 2;
-                    this.probe_state = if interval >= this.query_interval {
-                        ProbeState::Finished(this.query_interval)
-                    } else {
-                        ProbeState::Probing(interval)
-                    };
+                match this.probe_state {
+                    // Stop to probe when the initial interval reach the query interval
+                    ProbeState::Probing(interval) => {
+                        let interval = interval * 2;
+                        this.probe_state = if interval >= this.query_interval {
+                            ProbeState::Finished(this.query_interval)
+                        } else {
+                            ProbeState::Probing(interval)
+                        };
+                    }
+                    // Back off while the set of peers on the network is stable.
+                    ProbeState::Finished(_) if !this.discovered_new_peer => {
+                        this.query_interval =
+                            (this.query_interval * 2).min(this.max_query_interval);
+                        this.probe_state = ProbeState::Finished(this.query_interval);
+                    }
+                    ProbeState::Finished(_) => {}
                 }
+                this.discovered_new_peer = false;
 
                 this.reset_timer();
             }
@@ -289,8 +327,17 @@ where
                         "received response from remote address on address"
                     );
 
+                    let len = this.discovered.len();
                     this.discovered
                         .extend(response.extract_discovered(Instant::now(), this.local_peer_id));
+                    let mut new_peers = false;
+                    for (peer, _, _) in this.discovered.range(len..) {
+                        new_peers |= this.known_peers.insert(*peer);
+                    }
+                    if new_peers {
+                        this.discovered_new_peer = true;
+                        this.query_interval = this.min_query_interval;
+                    }
 
                     // Stop probing when we have a valid response
                     if !this.discovered.is_empty() {
@@ -70,6 +70,13 @@ pub struct Config {
     /// peer joins the network. Receiving an mdns packet resets the timer
     /// preventing unnecessary traffic.
     pub query_interval: Duration,
+    /// Upper bound of the interval at which to poll the network for new peers.
+    ///
+    /// While no new peers are discovered, the interval between two queries is doubled
+    /// after every query, up to this value, reducing multicast traffic on networks with
+    /// a stable set of peers. Discovering a new peer resets the interval to
+    /// `query_interval`. Values below `query_interval` disable the backoff.
+    pub max_query_interval: Duration,
     /// Use IPv6 instead of IPv4.
     pub enable_ipv6: bool,
 }
@@ -79,6 +86,7 @@ impl Default for Config {
         Self {
             ttl: Duration::from_secs(6 * 60),
             query_interval: Duration::from_secs(5 * 60),
+            max_query_interval: Duration::from_secs(5 * 60),
             enable_ipv6: false,
         }
     }
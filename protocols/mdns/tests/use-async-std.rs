@@ -127,6 +127,54 @@ async fn test_no_expiration_on_close_async_std() {
     .await;
 }
 
+#[async_std::test]
+async fn test_query_now_async_std() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let config = Config {
+        ttl: Duration::from_secs(1),
+        query_interval: Duration::from_secs(120),
+        ..Default::default()
+    };
+
+    let mut a = create_swarm(config.clone()).await;
+
+    let b = create_swarm(config).await;
+    let b_peer_id = *b.local_peer_id();
+    async_std::task::spawn(b.loop_on_next());
+
+    // 1. Discover `b` and wait for the record to expire.
+    a.wait(|event| match event {
+        SwarmEvent::Behaviour(Event::Discovered(peers)) => {
+            peers.into_iter().any(|(p, _)| p == b_peer_id).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+    a.wait(|event| match event {
+        SwarmEvent::Behaviour(Event::Expired(peers)) => {
+            peers.into_iter().any(|(p, _)| p == b_peer_id).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+
+    // 2. Rediscover `b` well before the next periodic query.
+    a.behaviour_mut().query_now();
+    let rediscovered = async_std::future::timeout(
+        Duration::from_secs(10),
+        a.wait(|event| match event {
+            SwarmEvent::Behaviour(Event::Discovered(peers)) => {
+                peers.into_iter().any(|(p, _)| p == b_peer_id).then_some(())
+            }
+            _ => None,
+        }),
+    )
+    .await;
+    assert!(rediscovered.is_ok());
+}
+
 async fn run_discovery_test(config: Config) {
     let mut a = create_swarm(config.clone()).await;
     let a_peer_id = *a.local_peer_id();
@@ -30,6 +30,7 @@ use libp2p_swarm::{
     ConnectionHandler, ConnectionHandlerEvent, Stream, StreamProtocol, StreamUpgradeError,
     SubstreamProtocol,
 };
+use rand::RngCore;
 use std::collections::VecDeque;
 use std::{
     error::Error,
@@ -46,6 +47,10 @@ pub struct Config {
     timeout: Duration,
     /// The duration between outbound pings.
     interval: Duration,
+    /// The size of the payloads of outbound pings.
+    payload_size: PayloadSize,
+    /// The content of the payloads of outbound pings.
+    payload_pattern: PayloadPattern,
 }
 
 impl Config {
@@ -53,6 +58,8 @@ impl Config {
     ///
     ///   * [`Config::with_interval`] 15s
     ///   * [`Config::with_timeout`] 20s
+    ///   * [`Config::with_payload_size`] 32 bytes
+    ///   * [`Config::with_payload_pattern`] random
     ///
     /// These settings have the following effect:
     ///
@@ -63,6 +70,8 @@ impl Config {
         Self {
             timeout: Duration::from_secs(20),
             interval: Duration::from_secs(15),
+            payload_size: PayloadSize::Fixed(protocol::PING_SIZE),
+            payload_pattern: PayloadPattern::Random,
         }
     }
 
@@ -77,6 +86,77 @@ impl Config {
         self.interval = d;
         self
     }
+
+    /// Sets the size of the ping payloads, e.g. a sweep over increasing sizes to detect the path
+    /// MTU of a connection before sending large messages over it.
+    pub fn with_payload_size(mut self, size: PayloadSize) -> Self {
+        self.payload_size = size;
+        self
+    }
+
+    /// Sets the content of the ping payloads.
+    pub fn with_payload_pattern(mut self, pattern: PayloadPattern) -> Self {
+        self.payload_pattern = pattern;
+        self
+    }
+}
+
+/// The size of the payloads of outbound pings.
+///
+/// Sizes are rounded up to a multiple of 32 bytes, the size of the chunks in which remotes
+/// answer pings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadSize {
+    /// Every ping carries a payload of the given size.
+    Fixed(usize),
+    /// The pings carry payloads of the given sizes in ascending order until a ping fails, e.g.
+    /// because its payload exceeds the path MTU or a middlebox interferes with it.
+    ///
+    /// The failure ending the sweep is not reported, unless no size succeeded yet. Subsequent
+    /// pings carry the largest successful size, see [`Event::max_payload_size`](crate::Event).
+    Sweep(Vec<usize>),
+}
+
+impl PayloadSize {
+    /// Returns the payload sizes of the pings on a connection in ascending order.
+    fn sizes(&self) -> VecDeque<usize> {
+        let sizes = match self {
+            PayloadSize::Fixed(size) => std::slice::from_ref(size),
+            PayloadSize::Sweep(sizes) => sizes.as_slice(),
+        };
+        let mut sizes = sizes
+            .iter()
+            .map(|size| size.max(&1).div_ceil(protocol::PING_SIZE) * protocol::PING_SIZE)
+            .collect::<Vec<_>>();
+        if sizes.is_empty() {
+            sizes.push(protocol::PING_SIZE);
+        }
+        sizes.sort_unstable();
+        sizes.dedup();
+        sizes.into()
+    }
+}
+
+/// The content of the payloads of outbound pings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadPattern {
+    /// Random bytes.
+    Random,
+    /// The given byte repeated, e.g. to detect middleboxes altering low-entropy payloads.
+    Repeat(u8),
+}
+
+impl PayloadPattern {
+    fn payload(&self, size: usize) -> Vec<u8> {
+        match self {
+            PayloadPattern::Random => {
+                let mut payload = vec![0; size];
+                rand::thread_rng().fill_bytes(&mut payload);
+                payload
+            }
+            PayloadPattern::Repeat(byte) => vec![*byte; size],
+        }
+    }
 }
 
 impl Default for Config {
@@ -146,6 +226,10 @@ pub struct Handler {
     inbound: Option<PongFuture>,
     /// Tracks the state of our handler.
     state: State,
+    /// The payload sizes of the upcoming outbound pings, starting with the current one.
+    payload_sizes: VecDeque<usize>,
+    /// The largest payload size of a successful outbound ping.
+    max_payload_size: Option<usize>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -165,6 +249,8 @@ impl Handler {
     /// Builds a new [`Handler`] with the given configuration.
     pub fn new(config: Config) -> Self {
         Handler {
+            payload_sizes: config.payload_size.sizes(),
+            max_payload_size: None,
             config,
             interval: Delay::new(Duration::new(0, 0)),
             pending_errors: VecDeque::with_capacity(2),
@@ -175,6 +261,17 @@ impl Handler {
         }
     }
 
+    /// Sends a ping with the payload size of the current outbound ping.
+    fn send_ping(&self, stream: Stream) -> PingFuture {
+        let size = self.payload_sizes[0];
+        send_ping(
+            stream,
+            self.config.payload_pattern.payload(size),
+            self.config.timeout,
+        )
+        .boxed()
+    }
+
     fn on_dial_upgrade_error(
         &mut self,
         DialUpgradeError { error, .. }: DialUpgradeError<
@@ -204,7 +301,7 @@ impl Handler {
                 return;
             }
             // Note: This timeout only covers protocol negotiation.
-            StreamUpgradeError::Timeout => Failure::Other {
+            StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout => Failure::Other {
                 error: Box::new(std::io::Error::new(
                     std::io::ErrorKind::TimedOut,
                     "ping protocol negotiation timed out",
@@ -220,7 +317,8 @@ impl Handler {
 
 impl ConnectionHandler for Handler {
     type FromBehaviour = Void;
-    type ToBehaviour = Result<Duration, Failure>;
+    /// The result of an outbound ping and the largest successful payload size so far.
+    type ToBehaviour = (Result<Duration, Failure>, Option<usize>);
     type InboundProtocol = ReadyUpgrade<StreamProtocol>;
     type OutboundProtocol = ReadyUpgrade<StreamProtocol>;
     type OutboundOpenInfo = ();
@@ -236,16 +334,16 @@ impl ConnectionHandler for Handler {
     fn poll(
         &mut self,
         cx: &mut Context<'_>,
-    ) -> Poll<ConnectionHandlerEvent<ReadyUpgrade<StreamProtocol>, (), Result<Duration, Failure>>>
-    {
+    ) -> Poll<ConnectionHandlerEvent<ReadyUpgrade<StreamProtocol>, (), Self::ToBehaviour>> {
         match self.state {
             State::Inactive { reported: true } => {
                 return Poll::Pending; // nothing to do on this connection
             }
             State::Inactive { reported: false } => {
                 self.state = State::Inactive { reported: true };
-                return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(Err(
-                    Failure::Unsupported,
+                return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour((
+                    Err(Failure::Unsupported),
+                    self.max_payload_size,
                 )));
             }
             State::Active => {}
@@ -281,7 +379,10 @@ impl ConnectionHandler for Handler {
                 // that use a single substream, since every successful ping
                 // resets `failures` to `0`.
                 if self.failures > 1 {
-                    return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(Err(error)));
+                    return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour((
+                        Err(error),
+                        self.max_payload_size,
+                    )));
                 }
             }
 
@@ -293,15 +394,34 @@ impl ConnectionHandler for Handler {
                         break;
                     }
                     Poll::Ready(Ok((stream, rtt))) => {
-                        tracing::debug!(?rtt, "ping succeeded");
+                        let size = self.payload_sizes[0];
+                        tracing::debug!(?rtt, %size, "ping succeeded");
                         self.failures = 0;
                         self.interval.reset(self.config.interval);
                         self.outbound = Some(OutboundState::Idle(stream));
-                        return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(Ok(rtt)));
+                        self.max_payload_size = Some(size);
+                        // Continue the sweep with the next larger size.
+                        if self.payload_sizes.len() > 1 {
+                            self.payload_sizes.pop_front();
+                        }
+                        return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour((
+                            Ok(rtt),
+                            self.max_payload_size,
+                        )));
                     }
                     Poll::Ready(Err(e)) => {
                         self.interval.reset(self.config.interval);
-                        self.pending_errors.push_front(e);
+                        match self.max_payload_size {
+                            // The size exceeds what the connection supports, end the sweep.
+                            Some(max) if max < self.payload_sizes[0] => {
+                                tracing::debug!(
+                                    size=%self.payload_sizes[0],
+                                    "ping sweep ended: {e:?}"
+                                );
+                                self.payload_sizes = VecDeque::from([max]);
+                            }
+                            _ => self.pending_errors.push_front(e),
+                        }
                     }
                 },
                 Some(OutboundState::Idle(stream)) => match self.interval.poll_unpin(cx) {
@@ -310,9 +430,7 @@ impl ConnectionHandler for Handler {
                         break;
                     }
                     Poll::Ready(()) => {
-                        self.outbound = Some(OutboundState::Ping(
-                            send_ping(stream, self.config.timeout).boxed(),
-                        ));
+                        self.outbound = Some(OutboundState::Ping(self.send_ping(stream)));
                     }
                 },
                 Some(OutboundState::OpenStream) => {
@@ -357,9 +475,7 @@ impl ConnectionHandler for Handler {
                 ..
             }) => {
                 stream.ignore_for_keep_alive();
-                self.outbound = Some(OutboundState::Ping(
-                    send_ping(stream, self.config.timeout).boxed(),
-                ));
+                self.outbound = Some(OutboundState::Ping(self.send_ping(stream)));
             }
             ConnectionEvent::DialUpgradeError(dial_upgrade_error) => {
                 self.on_dial_upgrade_error(dial_upgrade_error)
@@ -383,8 +499,12 @@ enum OutboundState {
 }
 
 /// A wrapper around [`protocol::send_ping`] that enforces a time out.
-async fn send_ping(stream: Stream, timeout: Duration) -> Result<(Stream, Duration), Failure> {
-    let ping = protocol::send_ping(stream);
+async fn send_ping(
+    stream: Stream,
+    payload: Vec<u8>,
+    timeout: Duration,
+) -> Result<(Stream, Duration), Failure> {
+    let ping = protocol::send_ping(stream, payload);
     futures::pin_mut!(ping);
 
     match future::select(ping, Delay::new(timeout)).await {
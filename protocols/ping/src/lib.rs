@@ -64,7 +64,7 @@ use std::{
 };
 
 pub use self::protocol::PROTOCOL_NAME;
-pub use handler::{Config, Failure};
+pub use handler::{Config, Failure, PayloadPattern, PayloadSize};
 
 /// A [`NetworkBehaviour`] that responds to inbound pings and
 /// periodically sends outbound pings on every established connection.
@@ -86,6 +86,9 @@ pub struct Event {
     pub connection: ConnectionId,
     /// The result of an inbound or outbound ping.
     pub result: Result<Duration, Failure>,
+    /// The largest payload size of a successful outbound ping on the connection, see
+    /// [`Config::with_payload_size`].
+    pub max_payload_size: Option<usize>,
 }
 
 impl Behaviour {
@@ -132,12 +135,13 @@ impl NetworkBehaviour for Behaviour {
         &mut self,
         peer: PeerId,
         connection: ConnectionId,
-        result: THandlerOutEvent<Self>,
+        (result, max_payload_size): THandlerOutEvent<Self>,
     ) {
         self.events.push_front(Event {
             peer,
             connection,
             result,
+            max_payload_size,
         })
     }
 
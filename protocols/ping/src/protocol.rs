@@ -20,7 +20,6 @@
 
 use futures::prelude::*;
 use libp2p_swarm::StreamProtocol;
-use rand::{distributions, prelude::*};
 use std::{io, time::Duration};
 use web_time::Instant;
 
@@ -33,6 +32,10 @@ pub const PROTOCOL_NAME: StreamProtocol = StreamProtocol::new("/ipfs/ping/1.0.0"
 /// the same bytes as a response. At the same time, incoming pings
 /// on inbound substreams are answered by sending back the received bytes.
 ///
+/// Since pings are answered in chunks of 32 bytes, larger payloads of
+/// a multiple of 32 bytes are echoed entirely, which allows checking
+/// the path MTU of a connection.
+///
 /// At most a single inbound and outbound substream is kept open at
 /// any time. In case of a ping timeout or another error on a substream, the
 /// substream is dropped.
@@ -46,18 +49,20 @@ pub const PROTOCOL_NAME: StreamProtocol = StreamProtocol::new("/ipfs/ping/1.0.0"
 /// >           connections.
 #[derive(Default, Debug, Copy, Clone)]
 pub(crate) struct Ping;
-const PING_SIZE: usize = 32;
+pub(crate) const PING_SIZE: usize = 32;
 
-/// Sends a ping and waits for the pong.
-pub(crate) async fn send_ping<S>(mut stream: S) -> io::Result<(S, Duration)>
+/// Sends a ping with the given payload and waits for the pong.
+///
+/// The payload must be a multiple of [`PING_SIZE`] bytes long.
+pub(crate) async fn send_ping<S>(mut stream: S, payload: Vec<u8>) -> io::Result<(S, Duration)>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    let payload: [u8; PING_SIZE] = thread_rng().sample(distributions::Standard);
+    debug_assert_eq!(payload.len() % PING_SIZE, 0);
     stream.write_all(&payload).await?;
     stream.flush().await?;
     let started = Instant::now();
-    let mut recv_payload = [0u8; PING_SIZE];
+    let mut recv_payload = vec![0u8; payload.len()];
     stream.read_exact(&mut recv_payload).await?;
     if recv_payload == payload {
         Ok((stream, started.elapsed()))
@@ -89,9 +94,19 @@ mod tests {
         multiaddr::multiaddr,
         transport::{memory::MemoryTransport, ListenerId, Transport},
     };
+    use rand::prelude::*;
 
     #[test]
     fn ping_pong() {
+        ping_pong_with_payload(thread_rng().gen::<[u8; PING_SIZE]>().to_vec());
+    }
+
+    #[test]
+    fn ping_pong_with_large_payload() {
+        ping_pong_with_payload(vec![0xAA; 50 * PING_SIZE]);
+    }
+
+    fn ping_pong_with_payload(payload: Vec<u8>) {
         let mem_addr = multiaddr![Memory(thread_rng().gen::<u64>())];
         let mut transport = MemoryTransport::new().boxed();
         transport.listen_on(ListenerId::next(), mem_addr).unwrap();
@@ -105,8 +120,10 @@ mod tests {
         async_std::task::spawn(async move {
             let transport_event = transport.next().await.unwrap();
             let (listener_upgrade, _) = transport_event.into_incoming().unwrap();
-            let conn = listener_upgrade.await.unwrap();
-            recv_ping(conn).await.unwrap();
+            let mut conn = listener_upgrade.await.unwrap();
+            while let Ok(answered) = recv_ping(conn).await {
+                conn = answered;
+            }
         });
 
         async_std::task::block_on(async move {
@@ -115,7 +132,7 @@ mod tests {
                 .unwrap()
                 .await
                 .unwrap();
-            let (_, rtt) = send_ping(c).await.unwrap();
+            let (_, rtt) = send_ping(c, payload).await.unwrap();
             assert!(rtt > Duration::from_secs(0));
         });
     }
@@ -55,6 +55,38 @@ fn ping_pong() {
     QuickCheck::new().tests(10).quickcheck(prop as fn(_))
 }
 
+#[test]
+fn payload_size_sweep_reports_largest_successful_size() {
+    let cfg = ping::Config::new().with_interval(Duration::from_millis(10));
+    let sweep = cfg
+        .clone()
+        .with_payload_size(ping::PayloadSize::Sweep(vec![4000, 1, 1024]))
+        .with_payload_pattern(ping::PayloadPattern::Repeat(0));
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| ping::Behaviour::new(cfg));
+    let mut swarm2 = Swarm::new_ephemeral(|_| ping::Behaviour::new(sweep));
+
+    async_std::task::block_on(async {
+        swarm1.listen().with_memory_addr_external().await;
+        swarm2.connect(&mut swarm1).await;
+
+        // Sizes are rounded up to multiples of 32 bytes and swept in ascending order.
+        for expected in [
+            32,
+            1024,
+            4000_usize.div_ceil(32) * 32,
+            4000_usize.div_ceil(32) * 32,
+        ] {
+            let ([e1], [e2]): ([ping::Event; 1], [ping::Event; 1]) =
+                libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await;
+
+            assert_eq!(e1.max_payload_size, Some(32));
+            assert_eq!(e2.max_payload_size, Some(expected));
+            assert!(e2.result.is_ok());
+        }
+    });
+}
+
 fn assert_ping_rtt_less_than_50ms(e: ping::Event) {
     let rtt = e.result.expect("a ping success");
 
@@ -32,11 +32,11 @@ use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::behaviour::{ConnectionClosed, FromSwarm};
 use libp2p_swarm::{
-    dummy, ConnectionDenied, ConnectionId, ExternalAddresses, NetworkBehaviour, NotifyHandler,
-    THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    dummy, CloseConnection, ConnectionDenied, ConnectionId, ExternalAddresses, NetworkBehaviour,
+    NotifyHandler, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
 use std::collections::{hash_map, HashMap, HashSet, VecDeque};
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroU64};
 use std::ops::Add;
 use std::task::{Context, Poll};
 use std::time::Duration;
@@ -58,6 +58,21 @@ pub struct Config {
     pub max_circuit_duration: Duration,
     pub max_circuit_bytes: u64,
     pub circuit_src_rate_limiters: Vec<Box<dyn rate_limiter::RateLimiter>>,
+    /// Bandwidth each circuit is shaped to, or `None` to relay the bytes of a circuit as fast as
+    /// possible within [`Config::max_circuit_bytes`].
+    pub circuit_bandwidth: Option<CircuitBandwidth>,
+    /// Overrides of [`Config::circuit_bandwidth`] for the circuits to specific peers holding a
+    /// reservation.
+    pub reservation_circuit_bandwidths: HashMap<PeerId, CircuitBandwidth>,
+
+    /// Interval at which the liveness of peers holding a reservation or relaying
+    /// a circuit is probed, or `None` to disable liveness checks.
+    ///
+    /// A peer that does not answer a probe within [`Config::liveness_timeout`] is
+    /// disconnected, tearing down its reservation and circuits.
+    pub liveness_interval: Option<Duration>,
+    /// Time a peer has to answer a liveness probe.
+    pub liveness_timeout: Duration,
 }
 
 impl Config {
@@ -92,6 +107,56 @@ impl Config {
             ));
         self
     }
+
+    /// Shapes each circuit to the given bandwidth, unless overridden for the reservation of the
+    /// destination via [`Config::reservation_circuit_bandwidth`].
+    pub fn circuit_bandwidth(mut self, bandwidth: CircuitBandwidth) -> Self {
+        self.circuit_bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// Shapes each circuit to the peer holding a reservation to the given bandwidth.
+    pub fn reservation_circuit_bandwidth(
+        mut self,
+        peer_id: PeerId,
+        bandwidth: CircuitBandwidth,
+    ) -> Self {
+        self.reservation_circuit_bandwidths
+            .insert(peer_id, bandwidth);
+        self
+    }
+
+    fn circuit_bandwidth_to(&self, dst_peer_id: &PeerId) -> Option<CircuitBandwidth> {
+        self.reservation_circuit_bandwidths
+            .get(dst_peer_id)
+            .copied()
+            .or(self.circuit_bandwidth)
+    }
+}
+
+/// Token bucket shaping the bytes relayed over a circuit, in both directions combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBandwidth {
+    /// Sustained rate at which bytes are relayed.
+    pub bytes_per_second: NonZeroU64,
+    /// Number of bytes that may be relayed at once after the circuit has been idle.
+    pub burst: NonZeroU64,
+}
+
+impl CircuitBandwidth {
+    /// Shapes a circuit to the given rate, allowing bursts of one second worth of bytes.
+    pub fn new(bytes_per_second: NonZeroU64) -> Self {
+        Self {
+            bytes_per_second,
+            burst: bytes_per_second,
+        }
+    }
+
+    /// Sets the number of bytes that may be relayed at once after the circuit has been idle.
+    pub fn with_burst(mut self, burst: NonZeroU64) -> Self {
+        self.burst = burst;
+        self
+    }
 }
 
 impl std::fmt::Debug for Config {
@@ -112,6 +177,13 @@ impl std::fmt::Debug for Config {
                 "circuit_src_rate_limiters",
                 &format!("[{} rate limiters]", self.circuit_src_rate_limiters.len()),
             )
+            .field("circuit_bandwidth", &self.circuit_bandwidth)
+            .field(
+                "reservation_circuit_bandwidths",
+                &self.reservation_circuit_bandwidths,
+            )
+            .field("liveness_interval", &self.liveness_interval)
+            .field("liveness_timeout", &self.liveness_timeout)
             .finish()
     }
 }
@@ -155,6 +227,11 @@ impl Default for Config {
             max_circuit_duration: Duration::from_secs(2 * 60),
             max_circuit_bytes: 1 << 17, // 128 kibibyte
             circuit_src_rate_limiters,
+            circuit_bandwidth: None,
+            reservation_circuit_bandwidths: HashMap::new(),
+
+            liveness_interval: None,
+            liveness_timeout: Duration::from_secs(5),
         }
     }
 }
@@ -231,6 +308,12 @@ pub enum Event {
         dst_peer_id: PeerId,
         error: Option<std::io::Error>,
     },
+    /// A peer holding a reservation or relaying a circuit did not answer a liveness
+    /// probe and is disconnected.
+    LivenessCheckFailed {
+        peer_id: PeerId,
+        error: std::io::Error,
+    },
 }
 
 /// [`NetworkBehaviour`] implementation of the relay server
@@ -314,6 +397,8 @@ impl NetworkBehaviour for Behaviour {
                 reservation_duration: self.config.reservation_duration,
                 max_circuit_duration: self.config.max_circuit_duration,
                 max_circuit_bytes: self.config.max_circuit_bytes,
+                liveness_interval: self.config.liveness_interval,
+                liveness_timeout: self.config.liveness_timeout,
             },
             ConnectedPoint::Listener {
                 local_addr: local_addr.clone(),
@@ -339,6 +424,8 @@ impl NetworkBehaviour for Behaviour {
                 reservation_duration: self.config.reservation_duration,
                 max_circuit_duration: self.config.max_circuit_duration,
                 max_circuit_bytes: self.config.max_circuit_bytes,
+                liveness_interval: self.config.liveness_interval,
+                liveness_timeout: self.config.liveness_timeout,
             },
             ConnectedPoint::Dialer {
                 address: addr.clone(),
@@ -618,6 +705,7 @@ impl NetworkBehaviour for Behaviour {
                     event: Either::Left(handler::In::AcceptAndDriveCircuit {
                         circuit_id,
                         dst_peer_id: event_source,
+                        bandwidth: self.config.circuit_bandwidth_to(&event_source),
                         inbound_circuit_req,
                         dst_stream,
                         dst_pending_data,
@@ -690,6 +778,17 @@ impl NetworkBehaviour for Behaviour {
                         error,
                     }));
             }
+            handler::Event::LivenessCheckFailed { error } => {
+                self.queued_actions.push_back(ToSwarm::CloseConnection {
+                    peer_id: event_source,
+                    connection: CloseConnection::One(connection),
+                });
+                self.queued_actions
+                    .push_back(ToSwarm::GenerateEvent(Event::LivenessCheckFailed {
+                        peer_id: event_source,
+                        error,
+                    }));
+            }
         }
     }
 
@@ -18,14 +18,14 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::behaviour::CircuitId;
+use crate::behaviour::{CircuitBandwidth, CircuitId};
 use crate::copy_future::CopyFuture;
 use crate::protocol::{inbound_hop, outbound_stop};
 use crate::{proto, HOP_PROTOCOL_NAME, STOP_PROTOCOL_NAME};
 use bytes::Bytes;
 use either::Either;
 use futures::future::{BoxFuture, FutureExt, TryFutureExt};
-use futures::io::AsyncWriteExt;
+use futures::io::{AsyncReadExt, AsyncWriteExt};
 use futures::stream::{FuturesUnordered, StreamExt};
 use futures_timer::Delay;
 use libp2p_core::upgrade::ReadyUpgrade;
@@ -47,11 +47,18 @@ use web_time::Instant;
 const MAX_CONCURRENT_STREAMS_PER_CONNECTION: usize = 10;
 const STREAM_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// Liveness probes use the ping protocol, which is supported by virtually all peers. Peers
+/// not supporting it still prove their liveness by rejecting the protocol.
+const LIVENESS_PROTOCOL_NAME: StreamProtocol = StreamProtocol::new("/ipfs/ping/1.0.0");
+const LIVENESS_PAYLOAD_SIZE: usize = 32;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub reservation_duration: Duration,
     pub max_circuit_duration: Duration,
     pub max_circuit_bytes: u64,
+    pub liveness_interval: Option<Duration>,
+    pub liveness_timeout: Duration,
 }
 
 pub enum In {
@@ -77,6 +84,7 @@ pub enum In {
     AcceptAndDriveCircuit {
         circuit_id: CircuitId,
         dst_peer_id: PeerId,
+        bandwidth: Option<CircuitBandwidth>,
         inbound_circuit_req: inbound_hop::CircuitReq,
         dst_stream: Stream,
         dst_pending_data: Bytes,
@@ -124,12 +132,14 @@ impl fmt::Debug for In {
                 circuit_id,
                 inbound_circuit_req: _,
                 dst_peer_id,
+                bandwidth,
                 dst_stream: _,
                 dst_pending_data: _,
             } => f
                 .debug_struct("In::AcceptAndDriveCircuit")
                 .field("circuit_id", circuit_id)
                 .field("dst_peer_id", dst_peer_id)
+                .field("bandwidth", bandwidth)
                 .finish(),
         }
     }
@@ -210,6 +220,8 @@ pub enum Event {
         dst_peer_id: PeerId,
         error: Option<std::io::Error>,
     },
+    /// The peer did not answer a liveness probe.
+    LivenessCheckFailed { error: std::io::Error },
 }
 
 impl fmt::Debug for Event {
@@ -321,6 +333,10 @@ impl fmt::Debug for Event {
                 .field("dst_peer_id", dst_peer_id)
                 .field("error", error)
                 .finish(),
+            Event::LivenessCheckFailed { error } => f
+                .debug_struct("Event::LivenessCheckFailed")
+                .field("error", error)
+                .finish(),
         }
     }
 }
@@ -370,6 +386,9 @@ pub struct Handler {
         CircuitId,
         Result<outbound_stop::Circuit, outbound_stop::Error>,
     >,
+
+    /// State of the liveness checks, `None` if they are disabled.
+    liveness: Option<Liveness>,
 }
 
 impl Handler {
@@ -384,7 +403,6 @@ impl Handler {
                 MAX_CONCURRENT_STREAMS_PER_CONNECTION,
             ),
             endpoint,
-            config,
             queued_events: Default::default(),
             idle_at: None,
             reservation_request_future: Default::default(),
@@ -394,6 +412,10 @@ impl Handler {
             active_reservation: Default::default(),
             pending_connect_requests: Default::default(),
             active_connect_requests: Default::default(),
+            liveness: config
+                .liveness_interval
+                .map(|interval| Liveness::Idle(Delay::new(interval))),
+            config,
         }
     }
 
@@ -412,7 +434,23 @@ impl Handler {
         }
     }
 
-    fn on_fully_negotiated_outbound(&mut self, stream: Stream) {
+    fn on_fully_negotiated_outbound(&mut self, stream: Stream, info: OutboundStream) {
+        if let OutboundStream::LivenessProbe = info {
+            let probe = probe_liveness(stream).boxed();
+            let timeout = Delay::new(self.config.liveness_timeout).map(|()| {
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "liveness probe timed out",
+                ))
+            });
+            self.liveness = Some(Liveness::Probing(
+                futures::future::select(probe, timeout)
+                    .map(|either| either.factor_first().0)
+                    .boxed(),
+            ));
+            return;
+        }
+
         let connect = self
             .pending_connect_requests
             .pop_front()
@@ -440,13 +478,29 @@ impl Handler {
 
     fn on_dial_upgrade_error(
         &mut self,
-        DialUpgradeError { error, .. }: DialUpgradeError<
+        DialUpgradeError { info, error }: DialUpgradeError<
             <Self as ConnectionHandler>::OutboundOpenInfo,
             <Self as ConnectionHandler>::OutboundProtocol,
         >,
     ) {
+        if let OutboundStream::LivenessProbe = info {
+            let error = match error {
+                // The peer answered, it just does not support the protocol.
+                StreamUpgradeError::NegotiationFailed => None,
+                StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout => {
+                    Some(io::ErrorKind::TimedOut.into())
+                }
+                StreamUpgradeError::Io(e) => Some(e),
+                StreamUpgradeError::Apply(v) => void::unreachable(v),
+            };
+            self.on_liveness_probe_done(error);
+            return;
+        }
+
         let error = match error {
-            StreamUpgradeError::Timeout => outbound_stop::Error::Io(io::ErrorKind::TimedOut.into()),
+            StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout => {
+                outbound_stop::Error::Io(io::ErrorKind::TimedOut.into())
+            }
             StreamUpgradeError::NegotiationFailed => outbound_stop::Error::Unsupported,
             StreamUpgradeError::Io(e) => outbound_stop::Error::Io(e),
             StreamUpgradeError::Apply(v) => void::unreachable(v),
@@ -469,6 +523,105 @@ impl Handler {
                 },
             ));
     }
+
+    fn on_liveness_probe_done(&mut self, error: Option<io::Error>) {
+        let Some(interval) = self.config.liveness_interval else {
+            return;
+        };
+        self.liveness = Some(Liveness::Idle(Delay::new(interval)));
+
+        if let Some(error) = error {
+            self.queued_events
+                .push_back(ConnectionHandlerEvent::NotifyBehaviour(
+                    Event::LivenessCheckFailed { error },
+                ));
+        }
+    }
+
+    fn poll_liveness(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        ConnectionHandlerEvent<
+            <Self as ConnectionHandler>::OutboundProtocol,
+            <Self as ConnectionHandler>::OutboundOpenInfo,
+            <Self as ConnectionHandler>::ToBehaviour,
+        >,
+    > {
+        if let Some(Liveness::Probing(probe)) = self.liveness.as_mut() {
+            let Poll::Ready(result) = probe.poll_unpin(cx) else {
+                return Poll::Pending;
+            };
+            self.on_liveness_probe_done(result.err());
+            if let Some(event) = self.queued_events.pop_front() {
+                return Poll::Ready(event);
+            }
+        }
+
+        let Some(Liveness::Idle(delay)) = self.liveness.as_mut() else {
+            return Poll::Pending;
+        };
+        if delay.poll_unpin(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        // Only peers we relay for are probed, all other connections are idle anyway.
+        if self.active_reservation.is_none() && self.circuits.is_empty() {
+            let interval = self
+                .config
+                .liveness_interval
+                .expect("liveness checks to be enabled");
+            delay.reset(interval);
+            let _ = delay.poll_unpin(cx);
+            return Poll::Pending;
+        }
+
+        self.liveness = Some(Liveness::Requested);
+        Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
+            protocol: SubstreamProtocol::new(
+                ReadyUpgrade::new(LIVENESS_PROTOCOL_NAME),
+                OutboundStream::LivenessProbe,
+            )
+            .with_timeout(self.config.liveness_timeout),
+        })
+    }
+}
+
+/// The purpose of an outbound stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboundStream {
+    /// A `STOP` stream for a pending `CONNECT` request.
+    Stop,
+    /// A stream probing the liveness of the peer.
+    LivenessProbe,
+}
+
+enum Liveness {
+    /// Waiting for the next probe to be due.
+    Idle(Delay),
+    /// A stream for the probe has been requested.
+    Requested,
+    /// The probe is in flight.
+    Probing(BoxFuture<'static, io::Result<()>>),
+}
+
+/// Sends a random payload to the peer and waits for it to be echoed back.
+async fn probe_liveness(mut stream: Stream) -> io::Result<()> {
+    let payload: [u8; LIVENESS_PAYLOAD_SIZE] = rand::random();
+    stream.write_all(&payload).await?;
+    stream.flush().await?;
+
+    let mut echo = [0u8; LIVENESS_PAYLOAD_SIZE];
+    stream.read_exact(&mut echo).await?;
+    if echo != payload {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "liveness probe payload mismatch",
+        ));
+    }
+    stream.close().await?;
+
+    Ok(())
 }
 
 enum ReservationRequestFuture {
@@ -484,7 +637,7 @@ impl ConnectionHandler for Handler {
     type InboundProtocol = ReadyUpgrade<StreamProtocol>;
     type InboundOpenInfo = ();
     type OutboundProtocol = ReadyUpgrade<StreamProtocol>;
-    type OutboundOpenInfo = ();
+    type OutboundOpenInfo = OutboundStream;
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
         SubstreamProtocol::new(ReadyUpgrade::new(HOP_PROTOCOL_NAME), ())
@@ -535,7 +688,10 @@ impl ConnectionHandler for Handler {
                 ));
                 self.queued_events
                     .push_back(ConnectionHandlerEvent::OutboundSubstreamRequest {
-                        protocol: SubstreamProtocol::new(ReadyUpgrade::new(STOP_PROTOCOL_NAME), ()),
+                        protocol: SubstreamProtocol::new(
+                            ReadyUpgrade::new(STOP_PROTOCOL_NAME),
+                            OutboundStream::Stop,
+                        ),
                     });
             }
             In::DenyCircuitReq {
@@ -555,6 +711,7 @@ impl ConnectionHandler for Handler {
             In::AcceptAndDriveCircuit {
                 circuit_id,
                 dst_peer_id,
+                bandwidth,
                 inbound_circuit_req,
                 dst_stream,
                 dst_pending_data,
@@ -570,6 +727,7 @@ impl ConnectionHandler for Handler {
                             dst_peer_id,
                             dst_stream,
                             dst_pending_data,
+                            bandwidth,
                         })
                         .map_err(move |e| (circuit_id, dst_peer_id, e))
                         .boxed(),
@@ -750,6 +908,7 @@ impl ConnectionHandler for Handler {
                         dst_peer_id,
                         mut dst_stream,
                         dst_pending_data,
+                        bandwidth,
                     } = parts;
                     let max_circuit_duration = self.config.max_circuit_duration;
                     let max_circuit_bytes = self.config.max_circuit_bytes;
@@ -768,6 +927,7 @@ impl ConnectionHandler for Handler {
                             dst_stream,
                             max_circuit_duration,
                             max_circuit_bytes,
+                            bandwidth,
                         )
                         .await?;
 
@@ -854,6 +1014,11 @@ impl ConnectionHandler for Handler {
             None => {}
         }
 
+        // Probe the liveness of the peer.
+        if let Poll::Ready(event) = self.poll_liveness(cx) {
+            return Poll::Ready(event);
+        }
+
         // Check keep alive status.
         if self.active_reservation.is_none() {
             if self.idle_at.is_none() {
@@ -884,9 +1049,9 @@ impl ConnectionHandler for Handler {
             }
             ConnectionEvent::FullyNegotiatedOutbound(FullyNegotiatedOutbound {
                 protocol: stream,
-                ..
+                info,
             }) => {
-                self.on_fully_negotiated_outbound(stream);
+                self.on_fully_negotiated_outbound(stream, info);
             }
             ConnectionEvent::DialUpgradeError(dial_upgrade_error) => {
                 self.on_dial_upgrade_error(dial_upgrade_error);
@@ -903,6 +1068,7 @@ struct CircuitParts {
     dst_peer_id: PeerId,
     dst_stream: Stream,
     dst_pending_data: Bytes,
+    bandwidth: Option<CircuitBandwidth>,
 }
 
 /// Holds everything we know about a to-be-issued `CONNECT` request to a peer.
@@ -24,6 +24,7 @@
 //!
 //! Inspired by [`futures::io::Copy`].
 
+use crate::behaviour::CircuitBandwidth;
 use futures::future::Future;
 use futures::future::FutureExt;
 use futures::io::{AsyncBufRead, BufReader};
@@ -34,6 +35,7 @@ use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
+use web_time::Instant;
 
 pub(crate) struct CopyFuture<S, D> {
     src: BufReader<S>,
@@ -42,6 +44,8 @@ pub(crate) struct CopyFuture<S, D> {
     max_circuit_duration: Delay,
     max_circuit_bytes: u64,
     bytes_sent: u64,
+    /// Shapes the bytes relayed in both directions, `None` if they are not shaped.
+    bandwidth: Option<TokenBucket>,
 }
 
 impl<S: AsyncRead, D: AsyncRead> CopyFuture<S, D> {
@@ -50,6 +54,7 @@ impl<S: AsyncRead, D: AsyncRead> CopyFuture<S, D> {
         dst: D,
         max_circuit_duration: Duration,
         max_circuit_bytes: u64,
+        bandwidth: Option<CircuitBandwidth>,
     ) -> Self {
         CopyFuture {
             src: BufReader::new(src),
@@ -57,6 +62,7 @@ impl<S: AsyncRead, D: AsyncRead> CopyFuture<S, D> {
             max_circuit_duration: Delay::new(max_circuit_duration),
             max_circuit_bytes,
             bytes_sent: Default::default(),
+            bandwidth: bandwidth.map(TokenBucket::new),
         }
     }
 }
@@ -85,21 +91,35 @@ where
                 Progressed,
             }
 
-            let src_status = match forward_data(&mut this.src, &mut this.dst, cx) {
+            let limit = this
+                .bandwidth
+                .as_mut()
+                .map_or(u64::MAX, TokenBucket::available);
+            let src_status = match forward_data(&mut this.src, &mut this.dst, limit, cx) {
                 Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
                 Poll::Ready(Ok(0)) => Status::Done,
                 Poll::Ready(Ok(i)) => {
                     this.bytes_sent += i;
+                    if let Some(bandwidth) = this.bandwidth.as_mut() {
+                        bandwidth.consume(i);
+                    }
                     Status::Progressed
                 }
                 Poll::Pending => Status::Pending,
             };
 
-            let dst_status = match forward_data(&mut this.dst, &mut this.src, cx) {
+            let limit = this
+                .bandwidth
+                .as_mut()
+                .map_or(u64::MAX, TokenBucket::available);
+            let dst_status = match forward_data(&mut this.dst, &mut this.src, limit, cx) {
                 Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
                 Poll::Ready(Ok(0)) => Status::Done,
                 Poll::Ready(Ok(i)) => {
                     this.bytes_sent += i;
+                    if let Some(bandwidth) = this.bandwidth.as_mut() {
+                        bandwidth.consume(i);
+                    }
                     Status::Progressed
                 }
                 Poll::Pending => Status::Pending,
@@ -123,19 +143,82 @@ where
             return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
         }
 
+        // Get woken up once the bandwidth allows to relay more bytes.
+        if let Some(bandwidth) = this.bandwidth.as_mut() {
+            if bandwidth.poll_refill(cx).is_ready() {
+                cx.waker().wake_by_ref();
+            }
+        }
+
         Poll::Pending
     }
 }
 
+/// Token bucket limiting the rate at which the bytes of a circuit are relayed.
+struct TokenBucket {
+    bytes_per_second: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+    refill: Delay,
+}
+
+impl TokenBucket {
+    fn new(bandwidth: CircuitBandwidth) -> Self {
+        let burst = bandwidth.burst.get() as f64;
+        Self {
+            bytes_per_second: bandwidth.bytes_per_second.get() as f64,
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+            refill: Delay::new(Duration::ZERO),
+        }
+    }
+
+    /// Returns the number of bytes that may be relayed right now.
+    fn available(&mut self) -> u64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.bytes_per_second).min(self.burst);
+        self.last_refill = now;
+
+        self.tokens as u64
+    }
+
+    fn consume(&mut self, bytes: u64) {
+        self.tokens -= bytes as f64;
+    }
+
+    /// Polls the timer firing once at least one more byte may be relayed, if the bucket is
+    /// currently empty.
+    fn poll_refill(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.available() > 0 {
+            return Poll::Pending;
+        }
+
+        let missing = 1.0 - self.tokens;
+        self.refill
+            .reset(Duration::from_secs_f64(missing / self.bytes_per_second));
+        self.refill.poll_unpin(cx)
+    }
+}
+
 /// Forwards data from `source` to `destination`.
 ///
 /// Returns `0` when done, i.e. `source` having reached EOF, returns number of bytes sent otherwise,
-/// thus indicating progress.
+/// thus indicating progress. At most `limit` bytes are sent, flushing `destination` instead if
+/// `limit` is `0`.
 fn forward_data<S: AsyncBufRead + Unpin, D: AsyncWrite + Unpin>(
     mut src: &mut S,
     mut dst: &mut D,
+    limit: u64,
     cx: &mut Context<'_>,
 ) -> Poll<io::Result<u64>> {
+    if limit == 0 {
+        let _ = Pin::new(&mut dst).poll_flush(cx)?;
+        return Poll::Pending;
+    }
+
     let buffer = match Pin::new(&mut src).poll_fill_buf(cx)? {
         Poll::Ready(buffer) => buffer,
         Poll::Pending => {
@@ -150,6 +233,7 @@ fn forward_data<S: AsyncBufRead + Unpin, D: AsyncWrite + Unpin>(
         return Poll::Ready(Ok(0));
     }
 
+    let buffer = &buffer[..buffer.len().min(limit.try_into().unwrap_or(usize::MAX))];
     let i = ready!(Pin::new(dst).poll_write(cx, buffer))?;
     if i == 0 {
         return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
@@ -166,6 +250,7 @@ mod tests {
     use futures::io::BufWriter;
     use quickcheck::QuickCheck;
     use std::io::ErrorKind;
+    use std::num::NonZeroU64;
 
     #[test]
     fn quickcheck() {
@@ -227,6 +312,7 @@ mod tests {
                 connection_b,
                 Duration::from_secs(60),
                 max_circuit_bytes,
+                None,
             );
 
             match block_on(&mut copy_future) {
@@ -288,6 +374,7 @@ mod tests {
             PendingConnection {},
             Duration::from_millis(1),
             u64::MAX,
+            None,
         );
 
         std::thread::sleep(Duration::from_millis(2));
@@ -297,6 +384,76 @@ mod tests {
         assert_eq!(error.kind(), ErrorKind::TimedOut);
     }
 
+    #[test]
+    fn circuit_bandwidth_is_shaped() {
+        struct Connection {
+            read: Vec<u8>,
+            write: Vec<u8>,
+        }
+
+        impl AsyncWrite for Connection {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<std::io::Result<usize>> {
+                Pin::new(&mut self.write).poll_write(cx, buf)
+            }
+
+            fn poll_flush(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Pin::new(&mut self.write).poll_flush(cx)
+            }
+
+            fn poll_close(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Pin::new(&mut self.write).poll_close(cx)
+            }
+        }
+
+        impl AsyncRead for Connection {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<std::io::Result<usize>> {
+                let n = std::cmp::min(self.read.len(), buf.len());
+                buf[0..n].copy_from_slice(&self.read[0..n]);
+                self.read = self.read.split_off(n);
+                Poll::Ready(Ok(n))
+            }
+        }
+
+        let mut copy_future = CopyFuture::new(
+            Connection {
+                read: vec![1; 2_000],
+                write: Vec::new(),
+            },
+            Connection {
+                read: vec![2; 1_000],
+                write: Vec::new(),
+            },
+            Duration::from_secs(60),
+            u64::MAX,
+            Some(CircuitBandwidth {
+                bytes_per_second: NonZeroU64::new(10_000).unwrap(),
+                burst: NonZeroU64::new(1_000).unwrap(),
+            }),
+        );
+
+        // 2000 of the 3000 bytes exceed the burst and are relayed at 10 kB/s.
+        let start = Instant::now();
+        block_on(&mut copy_future).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(190));
+
+        assert_eq!(copy_future.src.into_inner().write, vec![2; 1_000]);
+        assert_eq!(copy_future.dst.into_inner().write, vec![1; 2_000]);
+    }
+
     #[test]
     fn forward_data_should_flush_on_pending_source() {
         struct NeverEndingSource {
@@ -374,7 +531,7 @@ mod tests {
 
         assert!(
             matches!(
-                forward_data(&mut source, &mut destination, &mut cx),
+                forward_data(&mut source, &mut destination, u64::MAX, &mut cx),
                 Poll::Ready(Ok(1)),
             ),
             "Expect `forward_data` to forward one read from the source to the wrapped destination."
@@ -388,7 +545,7 @@ mod tests {
 
         assert!(
             matches!(
-                forward_data(&mut source, &mut destination, &mut cx),
+                forward_data(&mut source, &mut destination, u64::MAX, &mut cx),
                 Poll::Ready(Ok(1)),
             ),
             "Expect `forward_data` to forward one read from the source to the wrapped destination."
@@ -402,7 +559,7 @@ mod tests {
 
         assert!(
             matches!(
-                forward_data(&mut source, &mut destination, &mut cx),
+                forward_data(&mut source, &mut destination, u64::MAX, &mut cx),
                 Poll::Pending,
             ),
             "The source has no more reads available, but does not close i.e. does not return \
@@ -580,7 +580,7 @@ impl Reservation {
 
 fn into_reserve_error(e: StreamUpgradeError<Void>) -> outbound_hop::ReserveError {
     match e {
-        StreamUpgradeError::Timeout => {
+        StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout => {
             outbound_hop::ReserveError::Io(io::ErrorKind::TimedOut.into())
         }
         StreamUpgradeError::Apply(never) => void::unreachable(never),
@@ -591,7 +591,7 @@ fn into_reserve_error(e: StreamUpgradeError<Void>) -> outbound_hop::ReserveError
 
 fn into_connect_error(e: StreamUpgradeError<Void>) -> outbound_hop::ConnectError {
     match e {
-        StreamUpgradeError::Timeout => {
+        StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout => {
             outbound_hop::ConnectError::Io(io::ErrorKind::TimedOut.into())
         }
         StreamUpgradeError::Apply(never) => void::unreachable(never),
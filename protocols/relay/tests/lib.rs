@@ -268,6 +268,63 @@ async fn connection_established_to(
     }
 }
 
+#[test]
+fn liveness_checks_keep_responsive_peers_connected() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config {
+        liveness_interval: Some(Duration::from_millis(50)),
+        liveness_timeout: Duration::from_secs(1),
+        ..Default::default()
+    });
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone());
+
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+    let client_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id))
+        .with(Protocol::P2pCircuit);
+
+    client.listen_on(client_addr).unwrap();
+    spawn_swarm_on_pool(&pool, client);
+
+    pool.run_until(async {
+        loop {
+            if let SwarmEvent::Behaviour(RelayEvent::Relay(
+                relay::Event::ReservationReqAccepted { src_peer_id, .. },
+            )) = relay.select_next_some().await
+            {
+                assert_eq!(src_peer_id, client_peer_id);
+                break;
+            }
+        }
+    });
+
+    // The reserving peer is probed several times and answers every probe.
+    let mut elapsed = futures_timer::Delay::new(Duration::from_millis(500)).fuse();
+    pool.run_until(async {
+        loop {
+            futures::select! {
+                event = relay.select_next_some() => match event {
+                    SwarmEvent::Behaviour(RelayEvent::Relay(
+                        relay::Event::LivenessCheckFailed { .. },
+                    ))
+                    | SwarmEvent::ConnectionClosed { .. } => panic!("{event:?}"),
+                    _ => {}
+                },
+                () = elapsed => break,
+            }
+        }
+    });
+}
+
 #[test]
 fn handle_dial_failure() {
     let _ = tracing_subscriber::fmt()
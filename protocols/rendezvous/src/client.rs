@@ -19,7 +19,9 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::codec::Message::*;
-use crate::codec::{Cookie, ErrorCode, Message, Namespace, NewRegistration, Registration, Ttl};
+use crate::codec::{
+    Cookie, ErrorCode, Message, Namespace, NewRegistration, Registration, Tags, Ttl,
+};
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
 use futures::stream::FuturesUnordered;
@@ -32,7 +34,6 @@ use libp2p_swarm::{
     THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
 use std::collections::HashMap;
-use std::iter;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
@@ -41,15 +42,15 @@ pub struct Behaviour {
 
     keypair: Keypair,
 
-    waiting_for_register: HashMap<OutboundRequestId, (PeerId, Namespace)>,
-    waiting_for_discovery: HashMap<OutboundRequestId, (PeerId, Option<Namespace>)>,
+    waiting_for_register: HashMap<OutboundRequestId, (PeerId, Namespace, Tags)>,
+    waiting_for_discovery: HashMap<OutboundRequestId, (PeerId, Option<Namespace>, Tags)>,
 
     /// Hold addresses of all peers that we have discovered so far.
     ///
     /// Storing these internally allows us to assist the [`libp2p_swarm::Swarm`] in dialing by returning addresses from [`NetworkBehaviour::handle_pending_outbound_connection`].
     discovered_peers: HashMap<(PeerId, Namespace), Vec<Multiaddr>>,
 
-    registered_namespaces: HashMap<(PeerId, Namespace), Ttl>,
+    registered_namespaces: HashMap<(PeerId, Namespace), (Ttl, Tags)>,
 
     /// Tracks the expiry of registrations that we have discovered and stored in `discovered_peers` otherwise we have a memory leak.
     expiring_registrations: FuturesUnordered<BoxFuture<'static, (PeerId, Namespace)>>,
@@ -63,7 +64,10 @@ impl Behaviour {
         Self {
             inner: libp2p_request_response::Behaviour::with_codec(
                 crate::codec::Codec::default(),
-                iter::once((crate::PROTOCOL_IDENT, ProtocolSupport::Outbound)),
+                [
+                    (crate::TAGS_PROTOCOL_IDENT, ProtocolSupport::Outbound),
+                    (crate::PROTOCOL_IDENT, ProtocolSupport::Outbound),
+                ],
                 libp2p_request_response::Config::default(),
             ),
             keypair,
@@ -87,6 +91,20 @@ impl Behaviour {
         namespace: Namespace,
         rendezvous_node: PeerId,
         ttl: Option<Ttl>,
+    ) -> Result<(), RegisterError> {
+        self.register_with_tags(namespace, rendezvous_node, ttl, Tags::default())
+    }
+
+    /// Register our external addresses in the given namespace with the given rendezvous peer,
+    /// attaching the given tags to the registration.
+    ///
+    /// Tags are only transmitted if the rendezvous peer supports the tags extension of the protocol.
+    pub fn register_with_tags(
+        &mut self,
+        namespace: Namespace,
+        rendezvous_node: PeerId,
+        ttl: Option<Ttl>,
+        tags: Tags,
     ) -> Result<(), RegisterError> {
         let external_addresses = self.external_addresses.iter().cloned().collect::<Vec<_>>();
         if external_addresses.is_empty() {
@@ -96,10 +114,12 @@ impl Behaviour {
         let peer_record = PeerRecord::new(&self.keypair, external_addresses)?;
         let req_id = self.inner.send_request(
             &rendezvous_node,
-            Register(NewRegistration::new(namespace.clone(), peer_record, ttl)),
+            Register(
+                NewRegistration::new(namespace.clone(), peer_record, ttl).with_tags(tags.clone()),
+            ),
         );
         self.waiting_for_register
-            .insert(req_id, (rendezvous_node, namespace));
+            .insert(req_id, (rendezvous_node, namespace, tags));
 
         Ok(())
     }
@@ -126,6 +146,22 @@ impl Behaviour {
         cookie: Option<Cookie>,
         limit: Option<u64>,
         rendezvous_node: PeerId,
+    ) {
+        self.discover_with_tags(namespace, cookie, limit, Tags::default(), rendezvous_node)
+    }
+
+    /// Discover other peers at a given rendezvous peer whose registrations carry all of the given tags.
+    ///
+    /// Filtering happens on the rendezvous peer if it supports the tags extension of the protocol.
+    /// Registrations are filtered locally as well, hence a rendezvous peer without tag support
+    /// yields no registrations for a non-empty filter.
+    pub fn discover_with_tags(
+        &mut self,
+        namespace: Option<Namespace>,
+        cookie: Option<Cookie>,
+        limit: Option<u64>,
+        tags: Tags,
+        rendezvous_node: PeerId,
     ) {
         let req_id = self.inner.send_request(
             &rendezvous_node,
@@ -133,11 +169,12 @@ impl Behaviour {
                 namespace: namespace.clone(),
                 cookie,
                 limit,
+                tags: tags.clone(),
             },
         );
 
         self.waiting_for_discovery
-            .insert(req_id, (rendezvous_node, namespace));
+            .insert(req_id, (rendezvous_node, namespace, tags));
     }
 }
 
@@ -230,8 +267,8 @@ impl NetworkBehaviour for Behaviour {
 
         if changed && self.external_addresses.iter().count() > 0 {
             let registered = self.registered_namespaces.clone();
-            for ((rz_node, ns), ttl) in registered {
-                if let Err(e) = self.register(ns, rz_node, Some(ttl)) {
+            for ((rz_node, ns), (ttl, tags)) in registered {
+                if let Err(e) = self.register_with_tags(ns, rz_node, Some(ttl), tags) {
                     tracing::warn!("refreshing registration failed: {e}")
                 }
             }
@@ -329,7 +366,7 @@ impl NetworkBehaviour for Behaviour {
 
 impl Behaviour {
     fn event_for_outbound_failure(&mut self, req_id: &OutboundRequestId) -> Option<Event> {
-        if let Some((rendezvous_node, namespace)) = self.waiting_for_register.remove(req_id) {
+        if let Some((rendezvous_node, namespace, _)) = self.waiting_for_register.remove(req_id) {
             return Some(Event::RegisterFailed {
                 rendezvous_node,
                 namespace,
@@ -337,7 +374,7 @@ impl Behaviour {
             });
         };
 
-        if let Some((rendezvous_node, namespace)) = self.waiting_for_discovery.remove(req_id) {
+        if let Some((rendezvous_node, namespace, _)) = self.waiting_for_discovery.remove(req_id) {
             return Some(Event::DiscoverFailed {
                 rendezvous_node,
                 namespace,
@@ -355,11 +392,11 @@ impl Behaviour {
     ) -> Option<Event> {
         match response {
             RegisterResponse(Ok(ttl)) => {
-                if let Some((rendezvous_node, namespace)) =
+                if let Some((rendezvous_node, namespace, tags)) =
                     self.waiting_for_register.remove(request_id)
                 {
                     self.registered_namespaces
-                        .insert((rendezvous_node, namespace.clone()), ttl);
+                        .insert((rendezvous_node, namespace.clone()), (ttl, tags));
 
                     return Some(Event::Registered {
                         rendezvous_node,
@@ -371,7 +408,7 @@ impl Behaviour {
                 None
             }
             RegisterResponse(Err(error_code)) => {
-                if let Some((rendezvous_node, namespace)) =
+                if let Some((rendezvous_node, namespace, _)) =
                     self.waiting_for_register.remove(request_id)
                 {
                     return Some(Event::RegisterFailed {
@@ -383,9 +420,12 @@ impl Behaviour {
 
                 None
             }
-            DiscoverResponse(Ok((registrations, cookie))) => {
-                if let Some((rendezvous_node, _ns)) = self.waiting_for_discovery.remove(request_id)
+            DiscoverResponse(Ok((mut registrations, cookie))) => {
+                if let Some((rendezvous_node, _ns, tags)) =
+                    self.waiting_for_discovery.remove(request_id)
                 {
+                    registrations.retain(|registration| registration.tags.matches(&tags));
+
                     self.discovered_peers
                         .extend(registrations.iter().map(|registration| {
                             let peer_id = registration.record.peer_id();
@@ -418,7 +458,9 @@ impl Behaviour {
                 None
             }
             DiscoverResponse(Err(error_code)) => {
-                if let Some((rendezvous_node, ns)) = self.waiting_for_discovery.remove(request_id) {
+                if let Some((rendezvous_node, ns, _)) =
+                    self.waiting_for_discovery.remove(request_id)
+                {
                     return Some(Event::DiscoverFailed {
                         rendezvous_node,
                         namespace: ns,
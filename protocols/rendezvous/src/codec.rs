@@ -27,6 +27,7 @@ use libp2p_core::{peer_record, signed_envelope, PeerRecord, SignedEnvelope};
 use libp2p_swarm::StreamProtocol;
 use quick_protobuf_codec::Codec as ProtobufCodec;
 use rand::RngCore;
+use std::collections::BTreeMap;
 use std::{fmt, io};
 
 pub type Ttl = u64;
@@ -44,6 +45,7 @@ pub enum Message {
         namespace: Option<Namespace>,
         cookie: Option<Cookie>,
         limit: Option<Limit>,
+        tags: Tags,
     },
     DiscoverResponse(Result<(Vec<Registration>, Cookie), ErrorCode>),
 }
@@ -100,6 +102,84 @@ impl PartialEq<Namespace> for str {
 #[error("Namespace is too long")]
 pub struct NamespaceTooLong;
 
+/// Small, application-defined key/value pairs attached to a registration.
+///
+/// Discover requests can carry tags as well, in which case only registrations carrying all of
+/// them (exact match of key and value) are returned.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
+pub struct Tags(BTreeMap<String, String>);
+
+impl Tags {
+    /// Inserts a tag, replacing any previous value for the same key.
+    ///
+    /// Fails if the key or value exceeds [`crate::MAX_TAG_LEN`] or if the tags would exceed [`crate::MAX_TAGS`] entries.
+    pub fn insert(&mut self, key: String, value: String) -> Result<(), TagsTooLarge> {
+        if key.len() > crate::MAX_TAG_LEN || value.len() > crate::MAX_TAG_LEN {
+            return Err(TagsTooLarge);
+        }
+        if self.0.len() >= crate::MAX_TAGS && !self.0.contains_key(&key) {
+            return Err(TagsTooLarge);
+        }
+
+        self.0.insert(key, value);
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether these tags contain every tag of `filter` with an identical value.
+    pub fn matches(&self, filter: &Tags) -> bool {
+        filter
+            .0
+            .iter()
+            .all(|(key, value)| self.0.get(key) == Some(value))
+    }
+}
+
+impl TryFrom<Vec<proto::Tag>> for Tags {
+    type Error = TagsTooLarge;
+
+    fn try_from(tags: Vec<proto::Tag>) -> Result<Self, Self::Error> {
+        let mut result = Tags::default();
+        for tag in tags {
+            result.insert(tag.key.unwrap_or_default(), tag.value.unwrap_or_default())?;
+        }
+
+        Ok(result)
+    }
+}
+
+impl From<Tags> for Vec<proto::Tag> {
+    fn from(tags: Tags) -> Self {
+        tags.0
+            .into_iter()
+            .map(|(key, value)| proto::Tag {
+                key: Some(key),
+                value: Some(value),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Too many tags or tag too long")]
+pub struct TagsTooLarge;
+
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct Cookie {
     id: u64,
@@ -172,6 +252,7 @@ pub struct NewRegistration {
     pub namespace: Namespace,
     pub record: PeerRecord,
     pub ttl: Option<u64>,
+    pub tags: Tags,
 }
 
 impl NewRegistration {
@@ -180,9 +261,16 @@ impl NewRegistration {
             namespace,
             record,
             ttl,
+            tags: Tags::default(),
         }
     }
 
+    /// Attaches the given tags to this registration.
+    pub fn with_tags(mut self, tags: Tags) -> Self {
+        self.tags = tags;
+        self
+    }
+
     pub fn effective_ttl(&self) -> Ttl {
         self.ttl.unwrap_or(DEFAULT_TTL)
     }
@@ -193,6 +281,7 @@ pub struct Registration {
     pub namespace: Namespace,
     pub record: PeerRecord,
     pub ttl: Ttl,
+    pub tags: Tags,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -213,6 +302,7 @@ impl Encoder for Codec {
     fn encode(&mut self, item: Self::Item<'_>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let mut pb: ProtobufCodec<proto::Message> = ProtobufCodec::new(MAX_MESSAGE_LEN_BYTES);
 
+        let item = if self.tags { item } else { item.without_tags() };
         pb.encode(proto::Message::from(item), dst)?;
 
         Ok(())
@@ -229,13 +319,63 @@ impl Decoder for Codec {
         let Some(message) = pb.decode(src)? else {
             return Ok(None);
         };
+        let message = Message::try_from(message)?;
+
+        if self.tags {
+            return Ok(Some(message));
+        }
 
-        Ok(Some(message.try_into()?))
+        Ok(Some(message.without_tags()))
     }
 }
 
 #[derive(Clone, Default)]
-pub struct Codec {}
+pub struct Codec {
+    /// Whether the negotiated protocol supports tags.
+    tags: bool,
+}
+
+impl Codec {
+    fn for_protocol(protocol: &StreamProtocol) -> Self {
+        Self {
+            tags: protocol == &crate::TAGS_PROTOCOL_IDENT,
+        }
+    }
+}
+
+impl Message {
+    /// Strips all tags, for peers that did not negotiate [`crate::TAGS_PROTOCOL_IDENT`].
+    fn without_tags(self) -> Self {
+        match self {
+            Message::Register(registration) => {
+                Message::Register(registration.with_tags(Tags::default()))
+            }
+            Message::Discover {
+                namespace,
+                cookie,
+                limit,
+                ..
+            } => Message::Discover {
+                namespace,
+                cookie,
+                limit,
+                tags: Tags::default(),
+            },
+            Message::DiscoverResponse(Ok((registrations, cookie))) => {
+                let registrations = registrations
+                    .into_iter()
+                    .map(|registration| Registration {
+                        tags: Tags::default(),
+                        ..registration
+                    })
+                    .collect();
+
+                Message::DiscoverResponse(Ok((registrations, cookie)))
+            }
+            other => other,
+        }
+    }
+}
 
 #[async_trait]
 impl libp2p_request_response::Codec for Codec {
@@ -243,11 +383,15 @@ impl libp2p_request_response::Codec for Codec {
     type Request = Message;
     type Response = Message;
 
-    async fn read_request<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Self::Request>
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
     where
         T: AsyncRead + Unpin + Send,
     {
-        let message = FramedRead::new(io, self.clone())
+        let message = FramedRead::new(io, Codec::for_protocol(protocol))
             .next()
             .await
             .ok_or(io::ErrorKind::UnexpectedEof)??;
@@ -257,13 +401,13 @@ impl libp2p_request_response::Codec for Codec {
 
     async fn read_response<T>(
         &mut self,
-        _: &Self::Protocol,
+        protocol: &Self::Protocol,
         io: &mut T,
     ) -> io::Result<Self::Response>
     where
         T: AsyncRead + Unpin + Send,
     {
-        let message = FramedRead::new(io, self.clone())
+        let message = FramedRead::new(io, Codec::for_protocol(protocol))
             .next()
             .await
             .ok_or(io::ErrorKind::UnexpectedEof)??;
@@ -273,28 +417,32 @@ impl libp2p_request_response::Codec for Codec {
 
     async fn write_request<T>(
         &mut self,
-        _: &Self::Protocol,
+        protocol: &Self::Protocol,
         io: &mut T,
         req: Self::Request,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
     {
-        FramedWrite::new(io, self.clone()).send(req).await?;
+        FramedWrite::new(io, Codec::for_protocol(protocol))
+            .send(req)
+            .await?;
 
         Ok(())
     }
 
     async fn write_response<T>(
         &mut self,
-        _: &Self::Protocol,
+        protocol: &Self::Protocol,
         io: &mut T,
         res: Self::Response,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
     {
-        FramedWrite::new(io, self.clone()).send(res).await?;
+        FramedWrite::new(io, Codec::for_protocol(protocol))
+            .send(res)
+            .await?;
 
         Ok(())
     }
@@ -327,12 +475,14 @@ impl From<Message> for proto::Message {
                 namespace,
                 record,
                 ttl,
+                tags,
             }) => proto::Message {
                 type_pb: Some(proto::MessageType::REGISTER),
                 register: Some(proto::Register {
                     ns: Some(namespace.into()),
                     ttl,
                     signedPeerRecord: Some(record.into_signed_envelope().into_protobuf_encoding()),
+                    tags: tags.into(),
                 }),
                 registerResponse: None,
                 unregister: None,
@@ -378,12 +528,14 @@ impl From<Message> for proto::Message {
                 namespace,
                 cookie,
                 limit,
+                tags,
             } => proto::Message {
                 type_pb: Some(proto::MessageType::DISCOVER),
                 discover: Some(proto::Discover {
                     ns: namespace.map(|ns| ns.into()),
                     cookie: cookie.map(|cookie| cookie.into_wire_encoding()),
                     limit,
+                    tags: tags.into(),
                 }),
                 register: None,
                 registerResponse: None,
@@ -401,6 +553,7 @@ impl From<Message> for proto::Message {
                             signedPeerRecord: Some(
                                 reggo.record.into_signed_envelope().into_protobuf_encoding(),
                             ),
+                            tags: reggo.tags.into(),
                         })
                         .collect(),
                     status: Some(proto::ResponseStatus::OK),
@@ -441,6 +594,7 @@ impl TryFrom<proto::Message> for Message {
                         ns,
                         ttl,
                         signedPeerRecord: Some(signed_peer_record),
+                        tags,
                     }),
                 ..
             } => Message::Register(NewRegistration {
@@ -452,6 +606,7 @@ impl TryFrom<proto::Message> for Message {
                 record: PeerRecord::from_signed_envelope(SignedEnvelope::from_protobuf_encoding(
                     &signed_peer_record,
                 )?)?,
+                tags: tags.try_into()?,
             }),
             proto::Message {
                 type_pb: Some(proto::MessageType::REGISTER_RESPONSE),
@@ -465,12 +620,19 @@ impl TryFrom<proto::Message> for Message {
             } => Message::RegisterResponse(Ok(ttl.ok_or(ConversionError::MissingTtl)?)),
             proto::Message {
                 type_pb: Some(proto::MessageType::DISCOVER),
-                discover: Some(proto::Discover { ns, limit, cookie }),
+                discover:
+                    Some(proto::Discover {
+                        ns,
+                        limit,
+                        cookie,
+                        tags,
+                    }),
                 ..
             } => Message::Discover {
                 namespace: ns.map(Namespace::new).transpose()?,
                 cookie: cookie.map(Cookie::from_wire_encoding).transpose()?,
                 limit,
+                tags: tags.try_into()?,
             },
             proto::Message {
                 type_pb: Some(proto::MessageType::DISCOVER_RESPONSE),
@@ -500,6 +662,7 @@ impl TryFrom<proto::Message> for Message {
                                 )?,
                             )?,
                             ttl: reggo.ttl.ok_or(ConversionError::MissingTtl)?,
+                            tags: reggo.tags.try_into()?,
                         })
                     })
                     .collect::<Result<Vec<_>, ConversionError>>()?;
@@ -549,6 +712,8 @@ pub enum ConversionError {
     MissingNamespace,
     #[error("Invalid namespace")]
     InvalidNamespace(#[from] NamespaceTooLong),
+    #[error("Invalid tags")]
+    InvalidTags(#[from] TagsTooLarge),
     #[error("Missing signed peer record field")]
     MissingSignedPeerRecord,
     #[error("Missing TTL field")]
@@ -581,6 +746,7 @@ impl ConversionError {
             ConversionError::PoWDifficultyOutOfRange => ErrorCode::InternalError,
             ConversionError::BadPoWHash => ErrorCode::InternalError,
             ConversionError::InvalidNamespace(_) => ErrorCode::InvalidNamespace,
+            ConversionError::InvalidTags(_) => ErrorCode::InternalError,
         }
     }
 }
@@ -652,6 +818,51 @@ mod tests {
         assert_eq!(parsed, cookie);
     }
 
+    #[test]
+    fn tags_are_size_capped() {
+        let mut tags = Tags::default();
+        for i in 0..crate::MAX_TAGS {
+            tags.insert(format!("key{i}"), "value".to_owned()).unwrap();
+        }
+
+        assert!(tags
+            .insert("one-more".to_owned(), "value".to_owned())
+            .is_err());
+        assert!(tags
+            .insert("key0".to_owned(), "a".repeat(crate::MAX_TAG_LEN + 1))
+            .is_err());
+        assert!(tags
+            .insert("key0".to_owned(), "replaced".to_owned())
+            .is_ok());
+        assert_eq!(tags.len(), crate::MAX_TAGS);
+    }
+
+    #[test]
+    fn tags_are_stripped_without_extension() {
+        let mut tags = Tags::default();
+        tags.insert("role".to_owned(), "relay".to_owned()).unwrap();
+        let message = Message::Discover {
+            namespace: None,
+            cookie: None,
+            limit: None,
+            tags,
+        };
+
+        let mut legacy = Codec::for_protocol(&crate::PROTOCOL_IDENT);
+        let mut extended = Codec::for_protocol(&crate::TAGS_PROTOCOL_IDENT);
+
+        let mut buf = BytesMut::new();
+        extended.encode(message.clone(), &mut buf).unwrap();
+        assert_eq!(extended.decode(&mut buf).unwrap().unwrap(), message);
+
+        let mut buf = BytesMut::new();
+        legacy.encode(message.clone(), &mut buf).unwrap();
+        assert_eq!(
+            extended.decode(&mut buf).unwrap().unwrap(),
+            message.without_tags()
+        );
+    }
+
     #[test]
     fn cookie_wire_encoding_length() {
         let cookie = Cookie::for_namespace(Namespace::from_static("foo"));
@@ -69,12 +69,49 @@ pub mod mod_Message {
 
 use super::*;
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Tag {
+    pub key: Option<String>,
+    pub value: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for Tag {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.key = Some(r.read_string(bytes)?.to_owned()),
+                Ok(18) => msg.value = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Tag {
+    fn get_size(&self) -> usize {
+        0
+        + self.key.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.value.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.key { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.value { w.write_with_tag(18, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct Register {
     pub ns: Option<String>,
     pub signedPeerRecord: Option<Vec<u8>>,
     pub ttl: Option<u64>,
+    pub tags: Vec<rendezvous::pb::mod_Message::Tag>,
 }
 
 impl<'a> MessageRead<'a> for Register {
@@ -85,6 +122,7 @@ impl<'a> MessageRead<'a> for Register {
                 Ok(10) => msg.ns = Some(r.read_string(bytes)?.to_owned()),
                 Ok(18) => msg.signedPeerRecord = Some(r.read_bytes(bytes)?.to_owned()),
                 Ok(24) => msg.ttl = Some(r.read_uint64(bytes)?),
+                Ok(34) => msg.tags.push(r.read_message::<rendezvous::pb::mod_Message::Tag>(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -99,12 +137,14 @@ impl MessageWrite for Register {
         + self.ns.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
         + self.signedPeerRecord.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
         + self.ttl.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.tags.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
         if let Some(ref s) = self.ns { w.write_with_tag(10, |w| w.write_string(&**s))?; }
         if let Some(ref s) = self.signedPeerRecord { w.write_with_tag(18, |w| w.write_bytes(&**s))?; }
         if let Some(ref s) = self.ttl { w.write_with_tag(24, |w| w.write_uint64(*s))?; }
+        for s in &self.tags { w.write_with_tag(34, |w| w.write_message(s))?; }
         Ok(())
     }
 }
@@ -191,6 +231,7 @@ pub struct Discover {
     pub ns: Option<String>,
     pub limit: Option<u64>,
     pub cookie: Option<Vec<u8>>,
+    pub tags: Vec<rendezvous::pb::mod_Message::Tag>,
 }
 
 impl<'a> MessageRead<'a> for Discover {
@@ -201,6 +242,7 @@ impl<'a> MessageRead<'a> for Discover {
                 Ok(10) => msg.ns = Some(r.read_string(bytes)?.to_owned()),
                 Ok(16) => msg.limit = Some(r.read_uint64(bytes)?),
                 Ok(26) => msg.cookie = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(34) => msg.tags.push(r.read_message::<rendezvous::pb::mod_Message::Tag>(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -215,12 +257,14 @@ impl MessageWrite for Discover {
         + self.ns.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
         + self.limit.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
         + self.cookie.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.tags.iter().map(|s| 1 + sizeof_len((s).get_size())).sum::<usize>()
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
         if let Some(ref s) = self.ns { w.write_with_tag(10, |w| w.write_string(&**s))?; }
         if let Some(ref s) = self.limit { w.write_with_tag(16, |w| w.write_uint64(*s))?; }
         if let Some(ref s) = self.cookie { w.write_with_tag(26, |w| w.write_bytes(&**s))?; }
+        for s in &self.tags { w.write_with_tag(34, |w| w.write_message(s))?; }
         Ok(())
     }
 }
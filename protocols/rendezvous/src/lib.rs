@@ -22,7 +22,9 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
-pub use self::codec::{Cookie, ErrorCode, Namespace, NamespaceTooLong, Registration, Ttl};
+pub use self::codec::{
+    Cookie, ErrorCode, Namespace, NamespaceTooLong, Registration, Tags, TagsTooLarge, Ttl,
+};
 use libp2p_swarm::StreamProtocol;
 
 mod codec;
@@ -47,7 +49,19 @@ pub const MAX_TTL: Ttl = 60 * 60 * 72;
 /// <https://github.com/libp2p/specs/tree/master/rendezvous#recommendations-for-rendezvous-points-configurations>.
 pub const MAX_NAMESPACE: usize = 255;
 
+/// The maximum number of tags that can be attached to a registration or discover request.
+pub const MAX_TAGS: usize = 16;
+
+/// The maximum length of a single tag key or value.
+pub const MAX_TAG_LEN: usize = 64;
+
 pub(crate) const PROTOCOL_IDENT: StreamProtocol = StreamProtocol::new("/rendezvous/1.0.0");
 
+/// Extension of [`PROTOCOL_IDENT`] that carries application tags on registrations and discover requests.
+///
+/// Peers speaking only [`PROTOCOL_IDENT`] never see any tags.
+pub(crate) const TAGS_PROTOCOL_IDENT: StreamProtocol =
+    StreamProtocol::new("/rendezvous/tags/1.0.0");
+
 pub mod client;
 pub mod server;
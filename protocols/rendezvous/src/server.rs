@@ -18,12 +18,13 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::codec::{Cookie, ErrorCode, Message, Namespace, NewRegistration, Registration, Ttl};
+use crate::codec::{
+    Cookie, ErrorCode, Message, Namespace, NewRegistration, Registration, Tags, Ttl,
+};
 use crate::{MAX_TTL, MIN_TTL};
 use bimap::BiMap;
-use futures::future::BoxFuture;
-use futures::stream::FuturesUnordered;
-use futures::{FutureExt, StreamExt};
+use futures::FutureExt;
+use futures_timer::Delay;
 use libp2p_core::{Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_request_response::ProtocolSupport;
@@ -32,10 +33,11 @@ use libp2p_swarm::{
     ConnectionDenied, ConnectionId, NetworkBehaviour, THandler, THandlerInEvent, THandlerOutEvent,
     ToSwarm,
 };
-use std::collections::{HashMap, HashSet};
-use std::iter;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::task::{ready, Context, Poll};
 use std::time::Duration;
+use web_time::Instant;
 
 pub struct Behaviour {
     inner: libp2p_request_response::Behaviour<crate::codec::Codec>,
@@ -75,7 +77,10 @@ impl Behaviour {
         Self {
             inner: libp2p_request_response::Behaviour::with_codec(
                 crate::codec::Codec::default(),
-                iter::once((crate::PROTOCOL_IDENT, ProtocolSupport::Inbound)),
+                [
+                    (crate::TAGS_PROTOCOL_IDENT, ProtocolSupport::Inbound),
+                    (crate::PROTOCOL_IDENT, ProtocolSupport::Inbound),
+                ],
                 libp2p_request_response::Config::default(),
             ),
 
@@ -293,7 +298,8 @@ fn handle_request(
             namespace,
             cookie,
             limit,
-        } => match registrations.get(namespace, cookie, limit) {
+            tags,
+        } => match registrations.get_with_tags(namespace, cookie, limit, &tags) {
             Ok((registrations, cookie)) => {
                 let discovered = registrations.cloned().collect::<Vec<_>>();
 
@@ -324,7 +330,7 @@ fn handle_request(
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, PartialOrd, Ord)]
 struct RegistrationId(u64);
 
 impl RegistrationId {
@@ -342,7 +348,11 @@ pub struct Registrations {
     cookies: HashMap<Cookie, HashSet<RegistrationId>>,
     min_ttl: Ttl,
     max_ttl: Ttl,
-    next_expiry: FuturesUnordered<BoxFuture<'static, RegistrationId>>,
+    /// The expiries of the registrations, earliest first. Entries of registrations that were
+    /// removed or renewed in the meantime are skipped once they expire.
+    expiries: BinaryHeap<Reverse<(Instant, RegistrationId)>>,
+    /// Fires at the given expiry, the earliest one of `expiries` when last polled.
+    next_expiry: Option<(Instant, Delay)>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -367,7 +377,8 @@ impl Registrations {
             min_ttl: config.min_ttl,
             max_ttl: config.max_ttl,
             cookies: Default::default(),
-            next_expiry: FuturesUnordered::from_iter(vec![futures::future::pending().boxed()]),
+            expiries: BinaryHeap::new(),
+            next_expiry: None,
         }
     }
 
@@ -408,15 +419,15 @@ impl Registrations {
             namespace,
             record: new_registration.record,
             ttl,
+            tags: new_registration.tags,
         };
         self.registrations
             .insert(registration_id, registration.clone());
 
-        let next_expiry = futures_timer::Delay::new(Duration::from_secs(ttl))
-            .map(move |_| registration_id)
-            .boxed();
-
-        self.next_expiry.push(next_expiry);
+        self.expiries.push(Reverse((
+            Instant::now() + Duration::from_secs(ttl),
+            registration_id,
+        )));
 
         Ok(registration)
     }
@@ -437,6 +448,18 @@ impl Registrations {
         cookie: Option<Cookie>,
         limit: Option<u64>,
     ) -> Result<(impl Iterator<Item = &Registration> + '_, Cookie), CookieNamespaceMismatch> {
+        self.get_with_tags(discover_namespace, cookie, limit, &Tags::default())
+    }
+
+    /// Like [`Registrations::get`] but only returns registrations carrying all of the given tags.
+    pub fn get_with_tags<'a>(
+        &'a mut self,
+        discover_namespace: Option<Namespace>,
+        cookie: Option<Cookie>,
+        limit: Option<u64>,
+        tags: &Tags,
+    ) -> Result<(impl Iterator<Item = &'a Registration> + 'a, Cookie), CookieNamespaceMismatch>
+    {
         let cookie_namespace = cookie.as_ref().and_then(|cookie| cookie.namespace());
 
         match (discover_namespace.as_ref(), cookie_namespace) {
@@ -464,6 +487,14 @@ impl Registrations {
                         return None;
                     }
 
+                    if !self
+                        .registrations
+                        .get(registration_id)
+                        .is_some_and(|registration| registration.tags.matches(tags))
+                    {
+                        return None;
+                    }
+
                     match discover_namespace.as_ref() {
                         Some(discover_namespace) if discover_namespace == namespace => {
                             Some(registration_id)
@@ -495,9 +526,24 @@ impl Registrations {
 
     fn poll(&mut self, cx: &mut Context<'_>) -> Poll<ExpiredRegistration> {
         loop {
-            let expired_registration = ready!(self.next_expiry.poll_next_unpin(cx)).expect(
-                "This stream should never finish because it is initialised with a pending future",
-            );
+            let Some(Reverse((expiry, expired_registration))) = self.expiries.peek().copied()
+            else {
+                self.next_expiry = None;
+                return Poll::Pending;
+            };
+
+            let now = Instant::now();
+            if expiry > now {
+                match &mut self.next_expiry {
+                    Some((at, timer)) if *at == expiry => {
+                        ready!(timer.poll_unpin(cx));
+                        self.next_expiry = None;
+                    }
+                    _ => self.next_expiry = Some((expiry, Delay::new(expiry - now))),
+                }
+                continue;
+            }
+            self.expiries.pop();
 
             // clean up our cookies
             self.cookies.retain(|_, registrations| {
@@ -683,10 +729,8 @@ mod tests {
         registrations.no_event_for(3).await
     }
 
-    /// FuturesUnordered stop polling for ready futures when poll_next() is called until a None
-    /// value is returned. To prevent the next_expiry future from going to "sleep", next_expiry
-    /// is initialised with a future that always returns pending. This test ensures that
-    /// FuturesUnordered does not stop polling for ready futures.
+    /// Ensures that the expiry timer is armed again for registrations added after all previous
+    /// ones expired.
     #[tokio::test]
     async fn given_all_registrations_expired_then_successfully_handle_new_registration_and_expiry()
     {
@@ -705,6 +749,24 @@ mod tests {
         let _ = registrations.next_event_in_at_most(2).await;
     }
 
+    #[tokio::test]
+    async fn registrations_expire_in_order_of_their_ttl() {
+        let mut registrations = Registrations::with_config(Config {
+            min_ttl: 1,
+            max_ttl: 10,
+        });
+        let long = new_dummy_registration_with_ttl("foo", 3);
+        let short = new_dummy_registration_with_ttl("foo", 1);
+
+        registrations.add(long.clone()).unwrap();
+        registrations.add(short.clone()).unwrap();
+
+        let ExpiredRegistration(expired) = registrations.next_event_in_at_most(2).await;
+        assert_eq!(expired.record.peer_id(), short.record.peer_id());
+        let ExpiredRegistration(expired) = registrations.next_event_in_at_most(3).await;
+        assert_eq!(expired.record.peer_id(), long.record.peer_id());
+    }
+
     #[tokio::test]
     async fn cookies_are_cleaned_up_if_registrations_expire() {
         let mut registrations = Registrations::with_config(Config {
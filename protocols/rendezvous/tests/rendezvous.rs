@@ -74,6 +74,7 @@ async fn given_successful_registration_then_successful_discovery() {
                 namespace: registered_namespace,
                 record,
                 ttl,
+                ..
             }] => {
                 assert_eq!(*ttl, rendezvous::DEFAULT_TTL);
                 assert_eq!(record.peer_id(), *alice.local_peer_id());
@@ -85,6 +86,74 @@ async fn given_successful_registration_then_successful_discovery() {
     }
 }
 
+#[tokio::test]
+async fn discover_filters_registrations_by_tags() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+    let namespace = rendezvous::Namespace::from_static("some-namespace");
+    let ([mut alice, mut bob], mut robert) =
+        new_server_with_connected_clients(rendezvous::server::Config::default()).await;
+
+    let mut tags = rendezvous::Tags::default();
+    tags.insert("role".to_owned(), "relay".to_owned()).unwrap();
+
+    alice
+        .behaviour_mut()
+        .register_with_tags(
+            namespace.clone(),
+            *robert.local_peer_id(),
+            None,
+            tags.clone(),
+        )
+        .unwrap();
+    match libp2p_swarm_test::drive(&mut alice, &mut robert).await {
+        (
+            [rendezvous::client::Event::Registered { .. }],
+            [rendezvous::server::Event::PeerRegistered { registration, .. }],
+        ) => assert_eq!(registration.tags, tags),
+        events => panic!("Unexpected events: {events:?}"),
+    }
+
+    bob.behaviour_mut()
+        .register(namespace.clone(), *robert.local_peer_id(), None)
+        .unwrap();
+    match libp2p_swarm_test::drive(&mut bob, &mut robert).await {
+        (
+            [rendezvous::client::Event::Registered { .. }],
+            [rendezvous::server::Event::PeerRegistered { .. }],
+        ) => {}
+        events => panic!("Unexpected events: {events:?}"),
+    }
+
+    bob.behaviour_mut().discover_with_tags(
+        Some(namespace.clone()),
+        None,
+        None,
+        tags.clone(),
+        *robert.local_peer_id(),
+    );
+    match libp2p_swarm_test::drive(&mut bob, &mut robert).await {
+        (
+            [rendezvous::client::Event::Discovered { registrations, .. }],
+            [rendezvous::server::Event::DiscoverServed {
+                registrations: served,
+                ..
+            }],
+        ) => {
+            assert_eq!(served.len(), 1);
+            match registrations.as_slice() {
+                [registration] => {
+                    assert_eq!(registration.record.peer_id(), *alice.local_peer_id());
+                    assert_eq!(registration.tags, tags);
+                }
+                _ => panic!("Expected exactly one registration to be returned from discover"),
+            }
+        }
+        events => panic!("Unexpected events: {events:?}"),
+    }
+}
+
 #[tokio::test]
 async fn should_return_error_when_no_external_addresses() {
     let _ = tracing_subscriber::fmt()
@@ -0,0 +1,402 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Versioned envelopes around the messages of another [`Codec`](crate::Codec).
+//!
+//! Every message is preceded by a header made of a version byte, a flags byte and the
+//! length of the message body as a big-endian `u32`. The body is encoded by the wrapped
+//! codec. Since the body is length-delimited, a peer reading a message of a newer version
+//! can skip data it does not understand, e.g. fields appended to the message, and tell
+//! from the version which fields to expect. This allows a protocol to evolve its message
+//! format without negotiating a new protocol name for every change.
+//!
+//! # Example
+//!
+//! ```
+//! # use libp2p_request_response::{envelope, Codec, ProtocolSupport, self as request_response};
+//! # use libp2p_swarm::StreamProtocol;
+//! fn behaviour<C>(codec: C) -> envelope::Behaviour<C>
+//! where
+//!     C: Codec<Protocol = StreamProtocol> + Clone + Send + 'static,
+//! {
+//!     envelope::Behaviour::with_codec(
+//!         envelope::Codec::new(codec),
+//!         [(StreamProtocol::new("/my-protocol"), ProtocolSupport::Full)],
+//!         request_response::Config::default(),
+//!     )
+//! }
+//! ```
+
+use async_trait::async_trait;
+use futures::io::Cursor;
+use futures::prelude::*;
+use std::io;
+
+/// A request-response behaviour wrapping the messages of the codec `C` in [`Envelope`]s.
+pub type Behaviour<C> = crate::Behaviour<Codec<C>>;
+
+/// Length of the header preceding every message body.
+const HEADER_LEN: usize = 6;
+/// Default maximum size of a message body in bytes.
+const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// A message together with the version and flags of its envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope<T> {
+    /// The version of the message format.
+    pub version: u8,
+    /// Flags whose meaning is defined by the protocol.
+    pub flags: u8,
+    /// The message itself.
+    pub message: T,
+}
+
+impl<T> Envelope<T> {
+    /// Wraps the message in an envelope of the given version without any flags set.
+    pub fn new(version: u8, message: T) -> Self {
+        Envelope {
+            version,
+            flags: 0,
+            message,
+        }
+    }
+}
+
+/// A [`Codec`](crate::Codec) wrapping the messages of the codec `C` in [`Envelope`]s.
+#[derive(Debug, Clone, Default)]
+pub struct Codec<C> {
+    inner: C,
+    max_body_size: Option<usize>,
+}
+
+impl<C> Codec<C> {
+    /// Creates a codec wrapping the messages of the given codec.
+    pub fn new(inner: C) -> Self {
+        Codec {
+            inner,
+            max_body_size: None,
+        }
+    }
+
+    /// Sets the maximum size of a message body in bytes.
+    ///
+    /// Messages with a larger body are neither read nor written.
+    ///
+    /// * Default to 10 MiB.
+    pub fn with_max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = Some(size);
+        self
+    }
+
+    fn max_body_size(&self) -> usize {
+        self.max_body_size
+            .unwrap_or(DEFAULT_MAX_BODY_SIZE)
+            .min(u32::MAX as usize)
+    }
+}
+
+/// Reads the header and the body of a message.
+async fn read_envelope<T>(io: &mut T, max_body_size: usize) -> io::Result<Envelope<Cursor<Vec<u8>>>>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let mut header = [0; HEADER_LEN];
+    io.read_exact(&mut header).await?;
+    let len = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+    if len > max_body_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message body exceeds maximum size",
+        ));
+    }
+
+    let mut body = vec![0; len];
+    io.read_exact(&mut body).await?;
+
+    Ok(Envelope {
+        version: header[0],
+        flags: header[1],
+        message: Cursor::new(body),
+    })
+}
+
+/// Writes the header and the body of a message.
+async fn write_envelope<T>(
+    io: &mut T,
+    envelope: Envelope<Vec<u8>>,
+    max_body_size: usize,
+) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin + Send,
+{
+    let body = envelope.message;
+    if body.len() > max_body_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "message body exceeds maximum size",
+        ));
+    }
+
+    let mut header = [envelope.version, envelope.flags, 0, 0, 0, 0];
+    header[2..].copy_from_slice(&(body.len() as u32).to_be_bytes());
+    io.write_all(&header).await?;
+    io.write_all(&body).await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl<C> crate::Codec for Codec<C>
+where
+    C: crate::Codec + Send,
+    C::Protocol: Sync,
+{
+    type Protocol = C::Protocol;
+    type Request = Envelope<C::Request>;
+    type Response = Envelope<C::Response>;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut envelope = read_envelope(io, self.max_body_size()).await?;
+        let message = self
+            .inner
+            .read_request(protocol, &mut envelope.message)
+            .await?;
+
+        Ok(Envelope {
+            version: envelope.version,
+            flags: envelope.flags,
+            message,
+        })
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut envelope = read_envelope(io, self.max_body_size()).await?;
+        let message = self
+            .inner
+            .read_response(protocol, &mut envelope.message)
+            .await?;
+
+        Ok(Envelope {
+            version: envelope.version,
+            flags: envelope.flags,
+            message,
+        })
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let mut body = Cursor::new(Vec::new());
+        self.inner
+            .write_request(protocol, &mut body, req.message)
+            .await?;
+
+        write_envelope(
+            io,
+            Envelope {
+                version: req.version,
+                flags: req.flags,
+                message: body.into_inner(),
+            },
+            self.max_body_size(),
+        )
+        .await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let mut body = Cursor::new(Vec::new());
+        self.inner
+            .write_response(protocol, &mut body, res.message)
+            .await?;
+
+        write_envelope(
+            io,
+            Envelope {
+                version: res.version,
+                flags: res.flags,
+                message: body.into_inner(),
+            },
+            self.max_body_size(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Codec as _;
+    use futures_ringbuf::Endpoint;
+    use libp2p_swarm::StreamProtocol;
+
+    /// Version 1 of the messages is a `u32`, version 2 appends a `u16`.
+    #[derive(Debug, Clone, Default)]
+    struct Numbers;
+
+    #[async_trait]
+    impl crate::Codec for Numbers {
+        type Protocol = StreamProtocol;
+        type Request = (u32, Option<u16>);
+        type Response = u32;
+
+        async fn read_request<T>(
+            &mut self,
+            _: &StreamProtocol,
+            io: &mut T,
+        ) -> io::Result<Self::Request>
+        where
+            T: AsyncRead + Unpin + Send,
+        {
+            let mut buf = [0; 4];
+            io.read_exact(&mut buf).await?;
+            Ok((u32::from_be_bytes(buf), None))
+        }
+
+        async fn read_response<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<u32>
+        where
+            T: AsyncRead + Unpin + Send,
+        {
+            let mut buf = [0; 4];
+            io.read_exact(&mut buf).await?;
+            Ok(u32::from_be_bytes(buf))
+        }
+
+        async fn write_request<T>(
+            &mut self,
+            _: &StreamProtocol,
+            io: &mut T,
+            (n, extension): Self::Request,
+        ) -> io::Result<()>
+        where
+            T: AsyncWrite + Unpin + Send,
+        {
+            io.write_all(&n.to_be_bytes()).await?;
+            if let Some(extension) = extension {
+                io.write_all(&extension.to_be_bytes()).await?;
+            }
+            Ok(())
+        }
+
+        async fn write_response<T>(
+            &mut self,
+            _: &StreamProtocol,
+            io: &mut T,
+            n: u32,
+        ) -> io::Result<()>
+        where
+            T: AsyncWrite + Unpin + Send,
+        {
+            io.write_all(&n.to_be_bytes()).await
+        }
+    }
+
+    const PROTOCOL: StreamProtocol = StreamProtocol::new("/numbers");
+
+    #[async_std::test]
+    async fn messages_are_read_with_their_envelope() {
+        let mut codec = Codec::new(Numbers);
+
+        let (mut a, mut b) = Endpoint::pair(124, 124);
+        let response = Envelope {
+            version: 1,
+            flags: 0b101,
+            message: 42,
+        };
+        codec
+            .write_response(&PROTOCOL, &mut a, response.clone())
+            .await
+            .unwrap();
+        a.close().await.unwrap();
+
+        let actual = codec.read_response(&PROTOCOL, &mut b).await.unwrap();
+        assert_eq!(actual, response);
+    }
+
+    #[async_std::test]
+    async fn unknown_trailing_data_of_newer_versions_is_skipped() {
+        let mut codec = Codec::new(Numbers);
+
+        let (mut a, mut b) = Endpoint::pair(124, 124);
+        codec
+            .write_request(&PROTOCOL, &mut a, Envelope::new(2, (7, Some(8))))
+            .await
+            .unwrap();
+        codec
+            .write_request(&PROTOCOL, &mut a, Envelope::new(1, (9, None)))
+            .await
+            .unwrap();
+        a.close().await.unwrap();
+
+        // The reader only knows version 1 of the format.
+        let actual = codec.read_request(&PROTOCOL, &mut b).await.unwrap();
+        assert_eq!(actual, Envelope::new(2, (7, None)));
+        let actual = codec.read_request(&PROTOCOL, &mut b).await.unwrap();
+        assert_eq!(actual, Envelope::new(1, (9, None)));
+    }
+
+    #[async_std::test]
+    async fn bodies_exceeding_maximum_size_are_rejected() {
+        let mut codec = Codec::new(Numbers).with_max_body_size(4);
+
+        let (mut a, mut b) = Endpoint::pair(124, 124);
+        let error = codec
+            .write_request(&PROTOCOL, &mut a, Envelope::new(2, (7, Some(8))))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        Codec::new(Numbers)
+            .write_request(&PROTOCOL, &mut a, Envelope::new(2, (7, Some(8))))
+            .await
+            .unwrap();
+        a.close().await.unwrap();
+
+        let error = codec.read_request(&PROTOCOL, &mut b).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
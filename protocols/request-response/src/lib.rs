@@ -47,6 +47,9 @@
 //! - [`cbor::Behaviour`] for CBOR-encoded messages
 //! - [`json::Behaviour`] for JSON-encoded messages
 //!
+//! To evolve the message format of a protocol without negotiating a new protocol name,
+//! the messages of any codec can be wrapped in versioned envelopes via [`envelope::Codec`].
+//!
 //! ## Protocol Families
 //!
 //! A single [`Behaviour`] instance can be used with an entire
@@ -69,6 +72,7 @@
 #[cfg(feature = "cbor")]
 pub mod cbor;
 mod codec;
+pub mod envelope;
 mod handler;
 #[cfg(feature = "json")]
 pub mod json;
@@ -82,7 +86,7 @@ use handler::Handler;
 use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::{
-    behaviour::{AddressChange, ConnectionClosed, DialFailure, FromSwarm},
+    behaviour::{AddressChange, ConnectionClosed, DialFailure, DisconnectRequested, FromSwarm},
     dial_opts::DialOpts,
     ConnectionDenied, ConnectionHandler, ConnectionId, NetworkBehaviour, NotifyHandler,
     PeerAddresses, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
@@ -361,6 +365,9 @@ where
     /// Requests that have not yet been sent and are waiting for a connection
     /// to be established.
     pending_outbound_requests: HashMap<PeerId, SmallVec<[OutboundMessage<TCodec>; 10]>>,
+    /// Peers whose connections are about to be closed gracefully. No new requests are sent to
+    /// them, such that the connections can close once the in-flight requests are finished.
+    disconnecting: HashSet<PeerId>,
 }
 
 impl<TCodec> Behaviour<TCodec>
@@ -406,6 +413,7 @@ where
             pending_events: VecDeque::new(),
             connected: HashMap::new(),
             pending_outbound_requests: HashMap::new(),
+            disconnecting: HashSet::new(),
             addresses: PeerAddresses::default(),
         }
     }
@@ -422,8 +430,23 @@ where
     /// > address discovery, or known addresses of peers must be
     /// > managed via [`Behaviour::add_address`] and
     /// > [`Behaviour::remove_address`].
+    ///
+    /// If the connections to the peer are about to be closed via
+    /// [`Swarm::disconnect_peer_id_gracefully`](libp2p_swarm::Swarm::disconnect_peer_id_gracefully),
+    /// the request fails with [`OutboundFailure::ConnectionClosed`] without being sent.
     pub fn send_request(&mut self, peer: &PeerId, request: TCodec::Request) -> OutboundRequestId {
         let request_id = self.next_outbound_request_id();
+
+        if self.disconnecting.contains(peer) {
+            self.pending_events
+                .push_back(ToSwarm::GenerateEvent(Event::OutboundFailure {
+                    peer: *peer,
+                    request_id,
+                    error: OutboundFailure::ConnectionClosed,
+                }));
+
+            return request_id;
+        }
         let request = OutboundMessage {
             request_id,
             request,
@@ -651,6 +674,7 @@ where
         debug_assert_eq!(connections.is_empty(), remaining_established == 0);
         if connections.is_empty() {
             self.connected.remove(&peer_id);
+            self.disconnecting.remove(&peer_id);
         }
 
         for request_id in connection.pending_inbound_responses {
@@ -693,6 +717,15 @@ where
         }
     }
 
+    fn on_disconnect_requested(
+        &mut self,
+        DisconnectRequested { peer_id, .. }: DisconnectRequested,
+    ) {
+        if self.connected.contains_key(&peer_id) {
+            self.disconnecting.insert(peer_id);
+        }
+    }
+
     /// Preloads a new [`Handler`] with requests that are waiting to be sent to the newly connected peer.
     fn preload_new_handler(
         &mut self,
@@ -800,6 +833,9 @@ where
             }
             FromSwarm::AddressChange(address_change) => self.on_address_change(address_change),
             FromSwarm::DialFailure(dial_failure) => self.on_dial_failure(dial_failure),
+            FromSwarm::DisconnectRequested(disconnect_requested) => {
+                self.on_disconnect_requested(disconnect_requested)
+            }
             _ => {}
         }
     }
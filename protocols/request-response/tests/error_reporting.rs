@@ -71,6 +71,71 @@ async fn report_outbound_failure_on_read_response() {
     futures::future::select(server_task, client_task).await;
 }
 
+#[async_std::test]
+async fn report_outbound_failure_on_graceful_disconnect() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let (peer1_id, mut swarm1) = new_swarm();
+    let (peer2_id, mut swarm2) = new_swarm();
+
+    swarm1.listen().with_memory_addr_external().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let server_task = async move {
+        let (peer, req_id, action, resp_channel) = wait_request(&mut swarm1).await.unwrap();
+        assert_eq!(peer, peer2_id);
+        swarm1
+            .behaviour_mut()
+            .send_response(resp_channel, action)
+            .unwrap();
+
+        let (peer, req_id_done) = wait_response_sent(&mut swarm1).await.unwrap();
+        assert_eq!(peer, peer2_id);
+        assert_eq!(req_id_done, req_id);
+
+        wait_no_events(&mut swarm1).await;
+    };
+
+    // Expects the in-flight request to finish and the new one to fail with `ConnectionClosed`
+    let client_task = async move {
+        let req_id = swarm2
+            .behaviour_mut()
+            .send_request(&peer1_id, Action::FailOnMaxStreams);
+        swarm2
+            .disconnect_peer_id_gracefully(peer1_id, Duration::from_secs(60))
+            .unwrap();
+        let rejected_req_id = swarm2
+            .behaviour_mut()
+            .send_request(&peer1_id, Action::FailOnMaxStreams);
+
+        let (peer, req_id_done, error) = wait_outbound_failure(&mut swarm2).await.unwrap();
+        assert_eq!(peer, peer1_id);
+        assert_eq!(req_id_done, rejected_req_id);
+        assert!(matches!(error, OutboundFailure::ConnectionClosed));
+
+        loop {
+            match swarm2.select_next_some().await.try_into_behaviour_event() {
+                Ok(request_response::Event::Message {
+                    peer,
+                    message: request_response::Message::Response { request_id, .. },
+                }) => {
+                    assert_eq!(peer, peer1_id);
+                    assert_eq!(request_id, req_id);
+                    break;
+                }
+                Ok(ev) => panic!("Unexpected event: {ev:?}"),
+                Err(..) => {}
+            }
+        }
+    };
+
+    let server_task = pin!(server_task);
+    let client_task = pin!(client_task);
+    futures::future::select(server_task, client_task).await;
+}
+
 #[async_std::test]
 async fn report_outbound_failure_on_write_request() {
     let _ = tracing_subscriber::fmt()
@@ -140,7 +140,7 @@ impl ConnectionHandler for Handler {
                 };
 
                 let error = match error {
-                    swarm::StreamUpgradeError::Timeout => {
+                    swarm::StreamUpgradeError::Timeout | swarm::StreamUpgradeError::OpenTimeout => {
                         OpenStreamError::Io(io::Error::from(io::ErrorKind::TimedOut))
                     }
                     swarm::StreamUpgradeError::Apply(v) => void::unreachable(v),
@@ -18,17 +18,19 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+mod dial_history;
 mod either;
 mod external_addresses;
 mod listen_addresses;
 mod peer_addresses;
 pub mod toggle;
 
+pub use dial_history::{DialAttempt, DialHistory, DialOutcome};
 pub use external_addresses::ExternalAddresses;
 pub use listen_addresses::ListenAddresses;
 pub use peer_addresses::PeerAddresses;
 
-use crate::connection::ConnectionId;
+use crate::connection::{ConnectionData, ConnectionId};
 use crate::dial_opts::DialOpts;
 use crate::listen_opts::ListenOpts;
 use crate::{
@@ -37,7 +39,7 @@ use crate::{
 };
 use libp2p_core::{transport::ListenerId, ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
-use std::{task::Context, task::Poll};
+use std::{any::Any, task::Context, task::Poll, time::Duration};
 
 /// A [`NetworkBehaviour`] defines the behaviour of the local node on the network.
 ///
@@ -314,6 +316,20 @@ pub enum ToSwarm<TOutEvent, TInEvent> {
 
     /// Reports external address of a remote peer to the [`Swarm`](crate::Swarm) and through that to other [`NetworkBehaviour`]s.
     NewExternalAddrOfPeer { peer_id: PeerId, address: Multiaddr },
+
+    /// Instructs the [`Swarm`](crate::Swarm) to attach data to a pending or established connection,
+    /// replacing the data of the same type.
+    ///
+    /// The data is passed to all [`NetworkBehaviour`]s via [`FromSwarm::ConnectionEstablished`] and
+    /// [`FromSwarm::ConnectionClosed`] and dropped once the connection is closed or failed.
+    /// Attaching data right after a [`ToSwarm::Dial`] with the connection's [`DialOpts::connection_id`]
+    /// makes it available on establishment.
+    ///
+    /// If the connection does not exist, the data is silently dropped.
+    InsertConnectionData {
+        connection_id: ConnectionId,
+        data: Box<dyn Any + Send>,
+    },
 }
 
 impl<TOutEvent, TInEventOld> ToSwarm<TOutEvent, TInEventOld> {
@@ -353,6 +369,13 @@ impl<TOutEvent, TInEventOld> ToSwarm<TOutEvent, TInEventOld> {
                 address: addr,
                 peer_id,
             },
+            ToSwarm::InsertConnectionData {
+                connection_id,
+                data,
+            } => ToSwarm::InsertConnectionData {
+                connection_id,
+                data,
+            },
         }
     }
 }
@@ -391,6 +414,13 @@ impl<TOutEvent, THandlerIn> ToSwarm<TOutEvent, THandlerIn> {
                 address: addr,
                 peer_id,
             },
+            ToSwarm::InsertConnectionData {
+                connection_id,
+                data,
+            } => ToSwarm::InsertConnectionData {
+                connection_id,
+                data,
+            },
         }
     }
 }
@@ -459,6 +489,14 @@ pub enum FromSwarm<'a> {
     ExternalAddrExpired(ExternalAddrExpired<'a>),
     /// Informs the behaviour that we have discovered a new external address for a remote peer.
     NewExternalAddrOfPeer(NewExternalAddrOfPeer<'a>),
+    /// Informs the behaviour that the connections to a peer are about to be closed via
+    /// [`Swarm::disconnect_peer_id_gracefully`](crate::Swarm::disconnect_peer_id_gracefully).
+    ///
+    /// Behaviours can use the remaining time to notify the remote, e.g. by leaving shared state
+    /// or finishing in-flight requests, before the connections are closed. A connection is closed
+    /// early once it is idle, thus work still in progress needs to keep it alive, e.g. through an
+    /// active stream or [`ConnectionHandler::connection_keep_alive`].
+    DisconnectRequested(DisconnectRequested),
 }
 
 /// [`FromSwarm`] variant that informs the behaviour about a newly established connection to a peer.
@@ -469,6 +507,8 @@ pub struct ConnectionEstablished<'a> {
     pub endpoint: &'a ConnectedPoint,
     pub failed_addresses: &'a [Multiaddr],
     pub other_established: usize,
+    /// The data attached to the connection while it was pending.
+    pub data: &'a ConnectionData,
 }
 
 /// [`FromSwarm`] variant that informs the behaviour about a closed connection to a peer.
@@ -482,6 +522,8 @@ pub struct ConnectionClosed<'a> {
     pub connection_id: ConnectionId,
     pub endpoint: &'a ConnectedPoint,
     pub remaining_established: usize,
+    /// The data attached to the connection during its lifetime.
+    pub data: &'a ConnectionData,
 }
 
 /// [`FromSwarm`] variant that informs the behaviour that the [`ConnectedPoint`] of an existing
@@ -578,3 +620,12 @@ pub struct NewExternalAddrOfPeer<'a> {
     pub peer_id: PeerId,
     pub addr: &'a Multiaddr,
 }
+
+/// [`FromSwarm`] variant that informs the behaviour that the connections to a peer are about to be
+/// closed.
+#[derive(Clone, Copy, Debug)]
+pub struct DisconnectRequested {
+    pub peer_id: PeerId,
+    /// The time left until the connections are closed at the latest.
+    pub timeout: Duration,
+}
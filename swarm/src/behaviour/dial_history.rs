@@ -0,0 +1,259 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::behaviour::{ConnectionEstablished, FromSwarm};
+use crate::{DialError, DialFailure};
+
+use libp2p_core::transport::TransportError;
+use libp2p_core::{ConnectedPoint, Multiaddr};
+use libp2p_identity::PeerId;
+
+use lru::LruCache;
+use web_time::Instant;
+
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+
+/// The maximum number of dial attempts remembered per peer.
+const MAX_ATTEMPTS_PER_PEER: usize = 10;
+
+/// Struct for tracking the outcomes of the recent dials of peers by the [`Swarm`](crate::Swarm),
+/// e.g. to base redial and backoff decisions on.
+///
+/// Dials of unknown peers are not tracked.
+#[derive(Debug)]
+pub struct DialHistory(LruCache<PeerId, VecDeque<DialAttempt>>);
+
+/// The outcome of a dial attempt, see [`DialHistory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialAttempt {
+    /// The dialed address, if the outcome is specific to one.
+    pub address: Option<Multiaddr>,
+    /// When the outcome was reported.
+    pub time: Instant,
+    /// The outcome of the attempt.
+    pub outcome: DialOutcome,
+}
+
+/// The class of outcome of a dial attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DialOutcome {
+    /// A connection was established.
+    Connected,
+    /// The transport does not support the address.
+    Unsupported,
+    /// The transport failed to reach the peer on the address.
+    Unreachable,
+    /// The remote turned out to be another peer.
+    WrongPeerId,
+    /// A behaviour denied the connection.
+    Denied,
+    /// The dial was aborted.
+    Aborted,
+    /// No addresses were known for the peer.
+    NoAddresses,
+}
+
+impl DialOutcome {
+    /// Returns whether the attempt failed.
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, DialOutcome::Connected)
+    }
+}
+
+impl DialHistory {
+    /// Creates a [`DialHistory`] with capacity for the given number of peers.
+    ///
+    /// For each peer, we will at most store 10 attempts.
+    pub fn new(number_of_peers: NonZeroUsize) -> Self {
+        Self(LruCache::new(number_of_peers))
+    }
+
+    /// Feed a [`FromSwarm`] event to this struct.
+    ///
+    /// Returns whether the event added a dial attempt.
+    pub fn on_swarm_event(&mut self, event: &FromSwarm) -> bool {
+        let now = Instant::now();
+        match event {
+            FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id,
+                endpoint: ConnectedPoint::Dialer { address, .. },
+                failed_addresses,
+                ..
+            }) => {
+                for address in failed_addresses.iter() {
+                    self.add(
+                        *peer_id,
+                        Some(address.clone()),
+                        now,
+                        DialOutcome::Unreachable,
+                    );
+                }
+                self.add(*peer_id, Some(address.clone()), now, DialOutcome::Connected);
+                true
+            }
+            FromSwarm::DialFailure(DialFailure {
+                peer_id: Some(peer_id),
+                error,
+                ..
+            }) => {
+                let (address, outcome) = match error {
+                    DialError::Transport(errors) => {
+                        for (address, error) in errors {
+                            let outcome = match error {
+                                TransportError::MultiaddrNotSupported(_) => {
+                                    DialOutcome::Unsupported
+                                }
+                                TransportError::Other(_) => DialOutcome::Unreachable,
+                            };
+                            self.add(*peer_id, Some(address.clone()), now, outcome);
+                        }
+                        return !errors.is_empty();
+                    }
+                    DialError::WrongPeerId { endpoint, .. } => (
+                        Some(endpoint.get_remote_address().clone()),
+                        DialOutcome::WrongPeerId,
+                    ),
+                    DialError::Denied { .. } => (None, DialOutcome::Denied),
+                    DialError::Aborted => (None, DialOutcome::Aborted),
+                    DialError::NoAddresses => (None, DialOutcome::NoAddresses),
+                    // No attempt was made.
+                    DialError::LocalPeerId { .. } | DialError::DialPeerConditionFalse(_) => {
+                        return false
+                    }
+                };
+                self.add(*peer_id, address, now, outcome);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds a dial attempt of the given peer, evicting its oldest attempt if needed.
+    pub fn add(
+        &mut self,
+        peer: PeerId,
+        address: Option<Multiaddr>,
+        time: Instant,
+        outcome: DialOutcome,
+    ) {
+        let attempts = self
+            .0
+            .get_or_insert_mut(peer, || VecDeque::with_capacity(MAX_ATTEMPTS_PER_PEER));
+        if attempts.len() == MAX_ATTEMPTS_PER_PEER {
+            attempts.pop_front();
+        }
+        attempts.push_back(DialAttempt {
+            address,
+            time,
+            outcome,
+        });
+    }
+
+    /// Returns the recent dial attempts of the given peer, oldest first.
+    pub fn get(&self, peer: &PeerId) -> impl DoubleEndedIterator<Item = &DialAttempt> + '_ {
+        self.0.peek(peer).into_iter().flatten()
+    }
+
+    /// Returns the number of failed dial attempts of the given peer since its last successful
+    /// one.
+    pub fn consecutive_failures(&self, peer: &PeerId) -> usize {
+        self.get(peer)
+            .rev()
+            .take_while(|attempt| attempt.outcome.is_failure())
+            .count()
+    }
+
+    /// Forgets the dial attempts of the given peer.
+    pub fn remove(&mut self, peer: &PeerId) -> bool {
+        self.0.pop(peer).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConnectionId;
+    use libp2p_core::Endpoint;
+    use std::io;
+
+    #[test]
+    fn tracks_recent_attempts_per_peer() {
+        let mut history = DialHistory::new(NonZeroUsize::new(1).unwrap());
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().unwrap();
+
+        let error = DialError::Transport(vec![
+            (
+                addr.clone(),
+                TransportError::Other(io::Error::other("refused")),
+            ),
+            (
+                addr.clone(),
+                TransportError::MultiaddrNotSupported(addr.clone()),
+            ),
+        ]);
+        assert!(history.on_swarm_event(&FromSwarm::DialFailure(DialFailure {
+            peer_id: Some(peer),
+            error: &error,
+            connection_id: ConnectionId::new_unchecked(0),
+        })));
+        assert!(history.on_swarm_event(&FromSwarm::DialFailure(DialFailure {
+            peer_id: Some(peer),
+            error: &DialError::Aborted,
+            connection_id: ConnectionId::new_unchecked(1),
+        })));
+
+        let outcomes = history.get(&peer).map(|a| a.outcome).collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                DialOutcome::Unreachable,
+                DialOutcome::Unsupported,
+                DialOutcome::Aborted
+            ]
+        );
+        assert_eq!(history.consecutive_failures(&peer), 3);
+
+        let endpoint = ConnectedPoint::Dialer {
+            address: addr.clone(),
+            role_override: Endpoint::Dialer,
+        };
+        history.on_swarm_event(&FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id: peer,
+            connection_id: ConnectionId::new_unchecked(2),
+            endpoint: &endpoint,
+            failed_addresses: &[],
+            other_established: 0,
+            data: &Default::default(),
+        }));
+        assert_eq!(history.consecutive_failures(&peer), 0);
+        assert_eq!(
+            history.get(&peer).last().and_then(|a| a.address.clone()),
+            Some(addr)
+        );
+
+        // The least recently updated peer is evicted.
+        let other = PeerId::random();
+        history.add(other, None, Instant::now(), DialOutcome::NoAddresses);
+        assert_eq!(history.get(&peer).count(), 0);
+        assert_eq!(history.consecutive_failures(&other), 1);
+    }
+}
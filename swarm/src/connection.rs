@@ -18,11 +18,13 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+mod data;
 mod error;
 
 pub(crate) mod pool;
 mod supported_protocols;
 
+pub use data::ConnectionData;
 pub use error::ConnectionError;
 pub(crate) use error::{
     PendingConnectionError, PendingInboundConnectionError, PendingOutboundConnectionError,
@@ -39,7 +41,7 @@ use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend};
 use crate::{
     ConnectionHandlerEvent, Stream, StreamProtocol, StreamUpgradeError, SubstreamProtocol,
 };
-use futures::future::BoxFuture;
+use futures::future::{self, BoxFuture};
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use futures::{stream, FutureExt};
@@ -58,6 +60,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::task::Waker;
 use std::time::Duration;
 use std::{fmt, io, mem, pin::Pin, task::Context, task::Poll};
+use void::Void;
 use web_time::Instant;
 
 static NEXT_CONNECTION_ID: AtomicUsize = AtomicUsize::new(1);
@@ -89,6 +92,51 @@ impl Display for ConnectionId {
     }
 }
 
+/// How long the phases of establishing a connection took, see
+/// [`SwarmEvent::ConnectionEstablished`](crate::SwarmEvent::ConnectionEstablished).
+///
+/// The security handshake and the negotiation of the stream multiplexer are only known for
+/// connections upgraded with a [`Builder`](libp2p_core::transport::upgrade::Builder), e.g. over
+/// TCP or WebSocket. The phases of transports securing and multiplexing connections themselves,
+/// e.g. QUIC, are all part of the transport phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConnectionPhases {
+    /// Establishing the connection with the underlying transport, e.g. the TCP or QUIC handshake.
+    /// For outgoing connections, this includes the failed concurrent dials.
+    pub transport: Duration,
+    /// The security handshake authenticating the remote, e.g. Noise or TLS.
+    pub security: Option<Duration>,
+    /// The negotiation of the stream multiplexer, e.g. Yamux.
+    pub muxer: Option<Duration>,
+}
+
+impl ConnectionPhases {
+    /// Splits the time between the start of the dial, or the acceptance of the incoming
+    /// connection, and its establishment into phases.
+    pub(crate) fn new(started: Instant, muxer: &StreamMuxerBox) -> Self {
+        match muxer.upgrade_timings() {
+            Some(timings) => ConnectionPhases {
+                transport: timings.connected.saturating_duration_since(started),
+                security: Some(
+                    timings
+                        .authenticated
+                        .saturating_duration_since(timings.connected),
+                ),
+                muxer: Some(
+                    timings
+                        .multiplexed
+                        .saturating_duration_since(timings.authenticated),
+                ),
+            },
+            None => ConnectionPhases {
+                transport: started.elapsed(),
+                security: None,
+                muxer: None,
+            },
+        }
+    }
+}
+
 /// Information about a successfully established connection.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct Connected {
@@ -155,7 +203,13 @@ where
 
     local_supported_protocols: HashSet<StreamProtocol>,
     remote_supported_protocols: HashSet<StreamProtocol>,
+    /// Protocols that must not be negotiated on this connection, neither inbound nor outbound.
+    ///
+    /// See [`Swarm::ban_protocol`](crate::Swarm::ban_protocol).
+    banned_protocols: HashSet<StreamProtocol>,
     idle_timeout: Duration,
+    /// How long opening and negotiating an outbound substream may take, if bounded.
+    substream_open_timeout: Option<Duration>,
     stream_counter: ActiveStreamCounter,
 }
 
@@ -185,6 +239,7 @@ where
         substream_upgrade_protocol_override: Option<upgrade::Version>,
         max_negotiating_inbound_streams: usize,
         idle_timeout: Duration,
+        substream_open_timeout: Option<Duration>,
     ) -> Self {
         let initial_protocols = gather_supported_protocols(&handler);
         if !initial_protocols.is_empty() {
@@ -203,11 +258,25 @@ where
             requested_substreams: Default::default(),
             local_supported_protocols: initial_protocols,
             remote_supported_protocols: Default::default(),
+            banned_protocols: Default::default(),
             idle_timeout,
+            substream_open_timeout,
             stream_counter: ActiveStreamCounter::default(),
         }
     }
 
+    /// Replaces the set of protocols that must not be negotiated on this connection.
+    ///
+    /// Only affects streams that start negotiating after this call.
+    pub(crate) fn set_banned_protocols(&mut self, protocols: HashSet<StreamProtocol>) {
+        self.banned_protocols = protocols;
+    }
+
+    /// Closes the connection as soon as it is idle instead of after the idle timeout.
+    pub(crate) fn close_when_idle(&mut self) {
+        self.idle_timeout = Duration::ZERO;
+    }
+
     /// Notifies the connection handler of an event.
     pub(crate) fn on_behaviour_event(&mut self, event: THandler::FromBehaviour) {
         self.handler.on_behaviour_event(event);
@@ -250,7 +319,9 @@ where
             substream_upgrade_protocol_override,
             local_supported_protocols: supported_protocols,
             remote_supported_protocols,
+            banned_protocols,
             idle_timeout,
+            substream_open_timeout,
             stream_counter,
             ..
         } = self.get_mut();
@@ -258,11 +329,11 @@ where
         loop {
             match requested_substreams.poll_next_unpin(cx) {
                 Poll::Ready(Some(Ok(()))) => continue,
-                Poll::Ready(Some(Err(info))) => {
+                Poll::Ready(Some(Err((info, error)))) => {
                     handler.on_connection_event(ConnectionEvent::DialUpgradeError(
                         DialUpgradeError {
                             info,
-                            error: StreamUpgradeError::Timeout,
+                            error: error.map_upgrade_err(|v| void::unreachable(v)),
                         },
                     ));
                     continue;
@@ -274,10 +345,12 @@ where
             match handler.poll(cx) {
                 Poll::Pending => {}
                 Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest { protocol }) => {
-                    let timeout = *protocol.timeout();
+                    let timeouts =
+                        OutboundTimeouts::new(*protocol.timeout(), *substream_open_timeout);
                     let (upgrade, user_data) = protocol.into_upgrade();
 
-                    requested_substreams.push(SubstreamRequested::new(user_data, timeout, upgrade));
+                    requested_substreams
+                        .push(SubstreamRequested::new(user_data, timeouts, upgrade));
                     continue; // Poll handler until exhausted.
                 }
                 Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(event)) => {
@@ -351,7 +424,10 @@ where
                     tracing::debug!("no protocol could be agreed upon for inbound stream");
                     continue;
                 }
-                Poll::Ready(Some((_, Err(StreamUpgradeError::Timeout)))) => {
+                Poll::Ready(Some((
+                    _,
+                    Err(StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout),
+                ))) => {
                     tracing::debug!("inbound stream upgrade timed out");
                     continue;
                 }
@@ -398,14 +474,15 @@ where
                 match muxing.poll_outbound_unpin(cx)? {
                     Poll::Pending => {}
                     Poll::Ready(substream) => {
-                        let (user_data, timeout, upgrade) = requested_substream.extract();
+                        let (user_data, timeouts, upgrade) = requested_substream.extract();
 
                         negotiating_out.push(StreamUpgrade::new_outbound(
                             substream,
                             user_data,
-                            timeout,
+                            timeouts,
                             upgrade,
                             *substream_upgrade_protocol_override,
+                            banned_protocols,
                             stream_counter.clone(),
                         ));
 
@@ -423,6 +500,7 @@ where
                         negotiating_in.push(StreamUpgrade::new_inbound(
                             substream,
                             protocol,
+                            banned_protocols,
                             stream_counter.clone(),
                         ));
 
@@ -431,6 +509,12 @@ where
                 }
             }
 
+            // Avoid collecting the supported protocols on every wakeup if they did not change,
+            // which is the common case for idle connections.
+            if listens_on_protocols(handler, supported_protocols) {
+                return Poll::Pending;
+            }
+
             let new_protocols = gather_supported_protocols(handler);
             let changes = ProtocolsChange::from_full_sets(supported_protocols, &new_protocols);
 
@@ -463,6 +547,24 @@ fn gather_supported_protocols(handler: &impl ConnectionHandler) -> HashSet<Strea
         .collect()
 }
 
+/// Returns whether the handler listens on exactly the given protocols, without allocating.
+fn listens_on_protocols(
+    handler: &impl ConnectionHandler,
+    protocols: &HashSet<StreamProtocol>,
+) -> bool {
+    let mut num_protocols = 0;
+    let all_known = handler
+        .listen_protocol()
+        .upgrade()
+        .protocol_info()
+        .all(|info| {
+            num_protocols += 1;
+            protocols.iter().any(|p| p.as_ref() == info.as_ref())
+        });
+
+    all_known && num_protocols == protocols.len()
+}
+
 fn compute_new_shutdown(
     handler_keep_alive: bool,
     current_shutdown: &Shutdown,
@@ -524,9 +626,10 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
     fn new_outbound<Upgrade>(
         substream: SubstreamBox,
         user_data: UserData,
-        timeout: Delay,
+        timeouts: OutboundTimeouts,
         upgrade: Upgrade,
         version_override: Option<upgrade::Version>,
+        banned_protocols: &HashSet<StreamProtocol>,
         counter: ActiveStreamCounter,
     ) -> Self
     where
@@ -544,19 +647,32 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
             }
             _ => upgrade::Version::default(),
         };
-        let protocols = upgrade.protocol_info();
+        let protocols = without_banned(upgrade.protocol_info(), banned_protocols);
+
+        let OutboundTimeouts {
+            upgrade: timeout,
+            open: open_timeout,
+        } = timeouts;
 
         Self {
             user_data: Some(user_data),
             timeout,
             upgrade: Box::pin(async move {
-                let (info, stream) = multistream_select::dialer_select_proto(
+                let negotiation = std::pin::pin!(multistream_select::dialer_select_proto(
                     substream,
                     protocols,
                     effective_version,
-                )
-                .await
-                .map_err(to_stream_upgrade_error)?;
+                ));
+                let open_timeout = std::pin::pin!(async move {
+                    match open_timeout {
+                        Some(open_timeout) => open_timeout.await,
+                        None => future::pending().await,
+                    }
+                });
+                let (info, stream) = match future::select(negotiation, open_timeout).await {
+                    future::Either::Left((result, _)) => result.map_err(to_stream_upgrade_error)?,
+                    future::Either::Right(((), _)) => return Err(StreamUpgradeError::OpenTimeout),
+                };
 
                 let output = upgrade
                     .upgrade_outbound(Stream::new(stream, counter), info)
@@ -573,6 +689,7 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
     fn new_inbound<Upgrade>(
         substream: SubstreamBox,
         protocol: SubstreamProtocol<Upgrade, UserData>,
+        banned_protocols: &HashSet<StreamProtocol>,
         counter: ActiveStreamCounter,
     ) -> Self
     where
@@ -580,7 +697,7 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
     {
         let timeout = *protocol.timeout();
         let (upgrade, open_info) = protocol.into_upgrade();
-        let protocols = upgrade.protocol_info();
+        let protocols = without_banned(upgrade.protocol_info(), banned_protocols);
 
         Self {
             user_data: Some(open_info),
@@ -602,6 +719,20 @@ impl<UserData, TOk, TErr> StreamUpgrade<UserData, TOk, TErr> {
     }
 }
 
+/// Removes all protocols contained in `banned` from the given protocols.
+///
+/// If every protocol is banned, negotiation of the stream fails as if the remote did not support any of them.
+fn without_banned<I>(protocols: I, banned: &HashSet<StreamProtocol>) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    protocols
+        .into_iter()
+        .filter(|p| !banned.iter().any(|b| b.as_ref() == p.as_ref()))
+        .collect()
+}
+
 fn to_stream_upgrade_error<T>(e: NegotiationError) -> StreamUpgradeError<T> {
     match e {
         NegotiationError::Failed => StreamUpgradeError::NegotiationFailed,
@@ -641,10 +772,27 @@ impl<UserData, TOk, TErr> Future for StreamUpgrade<UserData, TOk, TErr> {
     }
 }
 
+/// The timeouts of an outbound stream.
+struct OutboundTimeouts {
+    /// Bounds the time until the stream is upgraded, see [`SubstreamProtocol::with_timeout`].
+    upgrade: Delay,
+    /// Bounds the time until the stream is opened and negotiated, see [`crate::Config::with_substream_open_timeout`].
+    open: Option<Delay>,
+}
+
+impl OutboundTimeouts {
+    fn new(upgrade: Duration, open: Option<Duration>) -> Self {
+        Self {
+            upgrade: Delay::new(upgrade),
+            open: open.map(Delay::new),
+        }
+    }
+}
+
 enum SubstreamRequested<UserData, Upgrade> {
     Waiting {
         user_data: UserData,
-        timeout: Delay,
+        timeouts: OutboundTimeouts,
         upgrade: Upgrade,
         /// A waker to notify our [`FuturesUnordered`] that we have extracted the data.
         ///
@@ -656,20 +804,20 @@ enum SubstreamRequested<UserData, Upgrade> {
 }
 
 impl<UserData, Upgrade> SubstreamRequested<UserData, Upgrade> {
-    fn new(user_data: UserData, timeout: Duration, upgrade: Upgrade) -> Self {
+    fn new(user_data: UserData, timeouts: OutboundTimeouts, upgrade: Upgrade) -> Self {
         Self::Waiting {
             user_data,
-            timeout: Delay::new(timeout),
+            timeouts,
             upgrade,
             extracted_waker: None,
         }
     }
 
-    fn extract(&mut self) -> (UserData, Delay, Upgrade) {
+    fn extract(&mut self) -> (UserData, OutboundTimeouts, Upgrade) {
         match mem::replace(self, Self::Done) {
             SubstreamRequested::Waiting {
                 user_data,
-                timeout,
+                timeouts,
                 upgrade,
                 extracted_waker: waker,
             } => {
@@ -677,7 +825,7 @@ impl<UserData, Upgrade> SubstreamRequested<UserData, Upgrade> {
                     waker.wake();
                 }
 
-                (user_data, timeout, upgrade)
+                (user_data, timeouts, upgrade)
             }
             SubstreamRequested::Done => panic!("cannot extract twice"),
         }
@@ -687,7 +835,7 @@ impl<UserData, Upgrade> SubstreamRequested<UserData, Upgrade> {
 impl<UserData, Upgrade> Unpin for SubstreamRequested<UserData, Upgrade> {}
 
 impl<UserData, Upgrade> Future for SubstreamRequested<UserData, Upgrade> {
-    type Output = Result<(), UserData>;
+    type Output = Result<(), (UserData, StreamUpgradeError<Void>)>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
@@ -696,20 +844,26 @@ impl<UserData, Upgrade> Future for SubstreamRequested<UserData, Upgrade> {
             SubstreamRequested::Waiting {
                 user_data,
                 upgrade,
-                mut timeout,
+                mut timeouts,
                 ..
-            } => match timeout.poll_unpin(cx) {
-                Poll::Ready(()) => Poll::Ready(Err(user_data)),
-                Poll::Pending => {
-                    *this = Self::Waiting {
-                        user_data,
-                        upgrade,
-                        timeout,
-                        extracted_waker: Some(cx.waker().clone()),
-                    };
-                    Poll::Pending
+            } => {
+                if timeouts.upgrade.poll_unpin(cx).is_ready() {
+                    return Poll::Ready(Err((user_data, StreamUpgradeError::Timeout)));
                 }
-            },
+                if let Some(Poll::Ready(())) =
+                    timeouts.open.as_mut().map(|open| open.poll_unpin(cx))
+                {
+                    return Poll::Ready(Err((user_data, StreamUpgradeError::OpenTimeout)));
+                }
+
+                *this = Self::Waiting {
+                    user_data,
+                    upgrade,
+                    timeouts,
+                    extracted_waker: Some(cx.waker().clone()),
+                };
+                Poll::Pending
+            }
             SubstreamRequested::Done => Poll::Ready(Ok(())),
         }
     }
@@ -741,7 +895,9 @@ mod tests {
     use futures::future;
     use futures::AsyncRead;
     use futures::AsyncWrite;
-    use libp2p_core::upgrade::{DeniedUpgrade, InboundUpgrade, OutboundUpgrade, UpgradeInfo};
+    use libp2p_core::upgrade::{
+        DeniedUpgrade, InboundUpgrade, OutboundUpgrade, ReadyUpgrade, UpgradeInfo,
+    };
     use libp2p_core::StreamMuxer;
     use quickcheck::*;
     use std::sync::{Arc, Weak};
@@ -767,6 +923,7 @@ mod tests {
                 None,
                 max_negotiating_inbound_streams,
                 Duration::ZERO,
+                None,
             );
 
             let result = connection.poll_noop_waker();
@@ -782,30 +939,93 @@ mod tests {
         QuickCheck::new().quickcheck(prop as fn(_));
     }
 
-    #[test]
-    fn outbound_stream_timeout_starts_on_request() {
-        let upgrade_timeout = Duration::from_secs(1);
+    #[tokio::test]
+    async fn outbound_stream_timeout_starts_on_request() {
+        let upgrade_timeout = Duration::from_millis(100);
         let mut connection = Connection::new(
             StreamMuxerBox::new(PendingStreamMuxer),
             MockConnectionHandler::new(upgrade_timeout),
             None,
             2,
             Duration::ZERO,
+            None,
         );
 
         connection.handler.open_new_outbound();
-        let _ = connection.poll_noop_waker();
 
-        std::thread::sleep(upgrade_timeout + Duration::from_secs(1));
+        assert!(matches!(
+            poll_until_dial_upgrade_error(&mut connection).await,
+            StreamUpgradeError::Timeout
+        ))
+    }
 
-        let _ = connection.poll_noop_waker();
+    #[tokio::test]
+    async fn outbound_stream_negotiation_is_bounded_by_open_timeout() {
+        let open_timeout = Duration::from_millis(100);
+        let new_connection = |open_timeout| {
+            Connection::new(
+                StreamMuxerBox::new(SilentStreamMuxer {
+                    counter: Arc::new(()),
+                }),
+                MockConnectionHandler::new(Duration::from_secs(10)),
+                None,
+                2,
+                Duration::ZERO,
+                open_timeout,
+            )
+        };
+        let mut bounded = new_connection(Some(open_timeout));
+        let mut unbounded = new_connection(None);
+
+        bounded.handler.open_new_outbound();
+        unbounded.handler.open_new_outbound();
+        let _ = bounded.poll_noop_waker();
+        let _ = unbounded.poll_noop_waker();
+        assert_eq!(bounded.negotiating_out.len(), 1);
 
         assert!(matches!(
-            connection.handler.error.unwrap(),
-            StreamUpgradeError::Timeout
+            poll_until_dial_upgrade_error(&mut bounded).await,
+            StreamUpgradeError::OpenTimeout
+        ));
+        let _ = unbounded.poll_noop_waker();
+        assert!(unbounded.handler.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn outbound_stream_open_timeout_is_independent_of_upgrade_timeout() {
+        let open_timeout = Duration::from_millis(100);
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            MockConnectionHandler::new(Duration::from_secs(10)),
+            None,
+            2,
+            Duration::ZERO,
+            Some(open_timeout),
+        );
+
+        connection.handler.open_new_outbound();
+
+        assert!(matches!(
+            poll_until_dial_upgrade_error(&mut connection).await,
+            StreamUpgradeError::OpenTimeout
         ))
     }
 
+    /// Drives `connection` until its handler was notified about a failed outbound stream.
+    async fn poll_until_dial_upgrade_error(
+        connection: &mut Connection<MockConnectionHandler>,
+    ) -> StreamUpgradeError<Void> {
+        future::poll_fn(|cx| {
+            let _ = Pin::new(&mut *connection).poll(cx);
+
+            match connection.handler.error.take() {
+                Some(error) => Poll::Ready(error),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+
     #[test]
     fn propagates_changes_to_supported_inbound_protocols() {
         let mut connection = Connection::new(
@@ -814,6 +1034,7 @@ mod tests {
             None,
             0,
             Duration::ZERO,
+            None,
         );
 
         // First, start listening on a single protocol.
@@ -853,6 +1074,7 @@ mod tests {
             None,
             0,
             Duration::ZERO,
+            None,
         );
 
         // First, remote supports a single protocol.
@@ -906,6 +1128,7 @@ mod tests {
             None,
             0,
             idle_timeout,
+            None,
         );
 
         assert!(connection.poll_noop_waker().is_pending());
@@ -1015,6 +1238,43 @@ mod tests {
         }
     }
 
+    /// A [`StreamMuxer`] whose outbound streams are never answered by the remote.
+    struct SilentStreamMuxer {
+        counter: Arc<()>,
+    }
+
+    impl StreamMuxer for SilentStreamMuxer {
+        type Substream = PendingSubstream;
+        type Error = Void;
+
+        fn poll_inbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            Poll::Pending
+        }
+
+        fn poll_outbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            Poll::Ready(Ok(PendingSubstream {
+                _weak: Arc::downgrade(&self.counter),
+            }))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+            Poll::Pending
+        }
+    }
+
     /// A [`StreamMuxer`] which never returns a stream.
     struct PendingStreamMuxer;
 
@@ -1138,7 +1398,7 @@ mod tests {
         type FromBehaviour = Void;
         type ToBehaviour = Void;
         type InboundProtocol = DeniedUpgrade;
-        type OutboundProtocol = DeniedUpgrade;
+        type OutboundProtocol = ReadyUpgrade<StreamProtocol>;
         type InboundOpenInfo = ();
         type OutboundOpenInfo = ();
 
@@ -1162,14 +1422,11 @@ mod tests {
                     protocol,
                     ..
                 }) => void::unreachable(protocol),
-                ConnectionEvent::FullyNegotiatedOutbound(FullyNegotiatedOutbound {
-                    protocol,
-                    ..
-                }) => void::unreachable(protocol),
                 ConnectionEvent::DialUpgradeError(DialUpgradeError { error, .. }) => {
                     self.error = Some(error)
                 }
-                ConnectionEvent::AddressChange(_)
+                ConnectionEvent::FullyNegotiatedOutbound(_)
+                | ConnectionEvent::AddressChange(_)
                 | ConnectionEvent::ListenUpgradeError(_)
                 | ConnectionEvent::LocalProtocolsChange(_)
                 | ConnectionEvent::RemoteProtocolsChange(_) => {}
@@ -1197,8 +1454,11 @@ mod tests {
             if self.outbound_requested {
                 self.outbound_requested = false;
                 return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
-                    protocol: SubstreamProtocol::new(DeniedUpgrade, ())
-                        .with_timeout(self.upgrade_timeout),
+                    protocol: SubstreamProtocol::new(
+                        ReadyUpgrade::new(StreamProtocol::new("/mock")),
+                        (),
+                    )
+                    .with_timeout(self.upgrade_timeout),
                 });
             }
 
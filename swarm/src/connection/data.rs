@@ -0,0 +1,72 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Typed data attached to a connection, holding at most one value per type.
+///
+/// Data can be attached by the application via
+/// [`Swarm::insert_connection_data`](crate::Swarm::insert_connection_data) and by
+/// [`NetworkBehaviour`](crate::NetworkBehaviour)s via
+/// [`ToSwarm::InsertConnectionData`](crate::ToSwarm::InsertConnectionData).
+/// Behaviours read it from [`ConnectionEstablished`](crate::behaviour::ConnectionEstablished) and
+/// [`ConnectionClosed`](crate::behaviour::ConnectionClosed).
+#[derive(Debug, Default)]
+pub struct ConnectionData {
+    data: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl ConnectionData {
+    /// Inserts a value, returning the value of the same type previously attached.
+    pub fn insert<T: Send + 'static>(&mut self, value: T) -> Option<T> {
+        self.insert_any(Box::new(value))
+            .map(|previous| *previous.downcast().expect("data to be stored by its type"))
+    }
+
+    /// Returns the value of type `T`.
+    pub fn get<T: Send + 'static>(&self) -> Option<&T> {
+        self.data.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Returns the value of type `T`, mutably.
+    pub fn get_mut<T: Send + 'static>(&mut self) -> Option<&mut T> {
+        self.data.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// Removes and returns the value of type `T`.
+    pub fn remove<T: Send + 'static>(&mut self) -> Option<T> {
+        let value = self.data.remove(&TypeId::of::<T>())?;
+
+        Some(*value.downcast().expect("data to be stored by its type"))
+    }
+
+    /// Returns whether no data is attached.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn insert_any(&mut self, value: Box<dyn Any + Send>) -> Option<Box<dyn Any + Send>> {
+        // Deref to get the type of the value rather than the one of the box.
+        let type_id = (*value).type_id();
+
+        self.data.insert(type_id, value)
+    }
+}
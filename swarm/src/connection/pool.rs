@@ -18,14 +18,14 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
-use crate::connection::{Connection, ConnectionId, PendingPoint};
+use crate::connection::{Connection, ConnectionId, ConnectionPhases, PendingPoint};
 use crate::{
     connection::{
         Connected, ConnectionError, IncomingInfo, PendingConnectionError,
         PendingInboundConnectionError, PendingOutboundConnectionError,
     },
     transport::TransportError,
-    ConnectedPoint, ConnectionHandler, Executor, Multiaddr, PeerId,
+    ConnectedPoint, ConnectionHandler, Executor, Multiaddr, PeerId, StreamProtocol,
 };
 use concurrent_dial::ConcurrentDial;
 use fnv::FnvHashMap;
@@ -41,7 +41,7 @@ use libp2p_core::connection::Endpoint;
 use libp2p_core::muxing::{StreamMuxerBox, StreamMuxerExt};
 use std::task::Waker;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt,
     num::{NonZeroU8, NonZeroUsize},
     pin::Pin,
@@ -99,6 +99,9 @@ where
     /// The pending connections that are currently being negotiated.
     pending: HashMap<ConnectionId, PendingConnection>,
 
+    /// The protocols that must not be negotiated with a given peer.
+    banned_protocols: FnvHashMap<PeerId, HashSet<StreamProtocol>>,
+
     /// Size of the task command buffer (per task).
     task_command_buffer_size: usize,
 
@@ -139,6 +142,9 @@ where
 
     /// How long a connection should be kept alive once it starts idling.
     idle_connection_timeout: Duration,
+
+    /// How long opening and negotiating an outbound substream may take, if bounded.
+    substream_open_timeout: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -191,6 +197,34 @@ impl<TInEvent> EstablishedConnection<TInEvent> {
             Err(e) => assert!(e.is_disconnected(), "No capacity for close command."),
         };
     }
+
+    /// Replaces the set of protocols that must not be negotiated on the connection.
+    ///
+    /// Has no effect if the connection is already closing.
+    fn set_banned_protocols(&mut self, protocols: HashSet<StreamProtocol>) {
+        // Clone the sender so that we are guaranteed to have
+        // capacity for the command (every sender gets a slot).
+        match self
+            .sender
+            .clone()
+            .try_send(task::Command::SetBannedProtocols(protocols))
+        {
+            Ok(()) => {}
+            Err(e) => assert!(e.is_disconnected(), "No capacity for ban command."),
+        };
+    }
+
+    /// Closes the connection as soon as it is idle, regardless of the idle timeout.
+    ///
+    /// Has no effect if the connection is already closing.
+    fn close_when_idle(&mut self) {
+        // Clone the sender so that we are guaranteed to have
+        // capacity for the command (every sender gets a slot).
+        match self.sender.clone().try_send(task::Command::CloseWhenIdle) {
+            Ok(()) => {}
+            Err(e) => assert!(e.is_disconnected(), "No capacity for close command."),
+        };
+    }
 }
 
 struct PendingConnection {
@@ -239,6 +273,8 @@ pub(crate) enum PoolEvent<ToBehaviour> {
         concurrent_dial_errors: Option<Vec<(Multiaddr, TransportError<std::io::Error>)>>,
         /// How long it took to establish this connection.
         established_in: std::time::Duration,
+        /// How long the phases of establishing this connection took.
+        phases: ConnectionPhases,
     },
 
     /// An established connection was closed.
@@ -320,12 +356,14 @@ where
             counters: ConnectionCounters::new(),
             established: Default::default(),
             pending: Default::default(),
+            banned_protocols: Default::default(),
             task_command_buffer_size: config.task_command_buffer_size,
             dial_concurrency_factor: config.dial_concurrency_factor,
             substream_upgrade_protocol_override: config.substream_upgrade_protocol_override,
             max_negotiating_inbound_streams: config.max_negotiating_inbound_streams,
             per_connection_event_buffer_size: config.per_connection_event_buffer_size,
             idle_connection_timeout: config.idle_connection_timeout,
+            substream_open_timeout: config.substream_open_timeout,
             executor,
             pending_connection_events_tx,
             pending_connection_events_rx,
@@ -350,6 +388,24 @@ where
             .find_map(|connections| connections.get_mut(&id))
     }
 
+    /// Returns true if a connection with the given ID is pending or established.
+    pub(crate) fn contains(&self, id: ConnectionId) -> bool {
+        self.pending.contains_key(&id)
+            || self
+                .established
+                .values()
+                .any(|connections| connections.contains_key(&id))
+    }
+
+    /// Closes all established connections to the given peer as soon as they are idle.
+    pub(crate) fn close_when_idle(&mut self, peer: PeerId) {
+        if let Some(conns) = self.established.get_mut(&peer) {
+            for conn in conns.values_mut() {
+                conn.close_when_idle();
+            }
+        }
+    }
+
     /// Returns true if we are connected to the given peer.
     ///
     /// This will return true only after a `NodeReached` event has been produced by `poll()`.
@@ -384,6 +440,61 @@ where
         }
     }
 
+    /// Refuses the negotiation of `protocol` on all current and future connections to `peer`.
+    ///
+    /// Returns `true` if the protocol was not already banned for this peer.
+    pub(crate) fn ban_protocol(&mut self, peer: PeerId, protocol: StreamProtocol) -> bool {
+        let banned = self.banned_protocols.entry(peer).or_default();
+        if !banned.insert(protocol) {
+            return false;
+        }
+
+        self.propagate_banned_protocols(peer);
+
+        true
+    }
+
+    /// Allows the negotiation of `protocol` with `peer` again.
+    ///
+    /// Returns `true` if the protocol was banned for this peer.
+    pub(crate) fn unban_protocol(&mut self, peer: PeerId, protocol: &StreamProtocol) -> bool {
+        let Some(banned) = self.banned_protocols.get_mut(&peer) else {
+            return false;
+        };
+        if !banned.remove(protocol) {
+            return false;
+        }
+        if banned.is_empty() {
+            self.banned_protocols.remove(&peer);
+        }
+
+        self.propagate_banned_protocols(peer);
+
+        true
+    }
+
+    /// Returns the protocols that are banned for the given peer.
+    pub(crate) fn banned_protocols(&self, peer: &PeerId) -> impl Iterator<Item = &StreamProtocol> {
+        self.banned_protocols.get(peer).into_iter().flatten()
+    }
+
+    fn propagate_banned_protocols(&mut self, peer: PeerId) {
+        let banned = self
+            .banned_protocols
+            .get(&peer)
+            .cloned()
+            .unwrap_or_default();
+
+        for conn in self
+            .established
+            .get_mut(&peer)
+            .into_iter()
+            .flat_map(|c| c.values_mut())
+        {
+            conn.set_banned_protocols(banned.clone());
+        }
+    }
+
     /// Returns an iterator over all established connections of `peer`.
     pub(crate) fn iter_established_connections_of_peer(
         &mut self,
@@ -523,13 +634,17 @@ where
             waker.wake();
         }
 
-        let connection = Connection::new(
+        let mut connection = Connection::new(
             connection,
             handler,
             self.substream_upgrade_protocol_override,
             self.max_negotiating_inbound_streams,
             self.idle_connection_timeout,
+            self.substream_open_timeout,
         );
+        if let Some(banned) = self.banned_protocols.get(&obtained_peer_id) {
+            connection.set_banned_protocols(banned.clone());
+        }
 
         let span = tracing::debug_span!(parent: tracing::Span::none(), "new_established_connection", remote_addr = %endpoint.get_remote_address(), %id, peer = %obtained_peer_id);
         span.follows_from(tracing::Span::current());
@@ -733,6 +848,7 @@ where
                     }
 
                     let established_in = accepted_at.elapsed();
+                    let phases = ConnectionPhases::new(accepted_at, &muxer);
 
                     let (connection, drop_listener) = NewConnection::new(muxer);
                     self.new_connection_dropped_listeners.push(drop_listener);
@@ -744,6 +860,7 @@ where
                         connection,
                         concurrent_dial_errors,
                         established_in,
+                        phases,
                     });
                 }
                 task::PendingConnectionEvent::PendingFailed { id, error } => {
@@ -961,6 +1078,8 @@ pub(crate) struct PoolConfig {
     pub(crate) dial_concurrency_factor: NonZeroU8,
     /// How long a connection should be kept alive once it is idling.
     pub(crate) idle_connection_timeout: Duration,
+    /// How long opening and negotiating an outbound substream may take, if bounded.
+    pub(crate) substream_open_timeout: Option<Duration>,
     /// The configured override for substream protocol upgrades, if any.
     substream_upgrade_protocol_override: Option<libp2p_core::upgrade::Version>,
 
@@ -978,6 +1097,7 @@ impl PoolConfig {
             per_connection_event_buffer_size: 7,
             dial_concurrency_factor: NonZeroU8::new(8).expect("8 > 0"),
             idle_connection_timeout: Duration::ZERO,
+            substream_open_timeout: None,
             substream_upgrade_protocol_override: None,
             max_negotiating_inbound_streams: 128,
         }
@@ -28,7 +28,7 @@ use crate::{
         PendingOutboundConnectionError,
     },
     transport::TransportError,
-    ConnectionHandler, Multiaddr, PeerId,
+    ConnectionHandler, Multiaddr, PeerId, StreamProtocol,
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -36,6 +36,7 @@ use futures::{
     SinkExt, StreamExt,
 };
 use libp2p_core::muxing::StreamMuxerBox;
+use std::collections::HashSet;
 use std::pin::Pin;
 use void::Void;
 
@@ -44,6 +45,10 @@ use void::Void;
 pub(crate) enum Command<T> {
     /// Notify the connection handler of an event.
     NotifyHandler(T),
+    /// Replace the set of protocols that must not be negotiated on the connection.
+    SetBannedProtocols(HashSet<StreamProtocol>),
+    /// Close the connection as soon as it is idle, regardless of the idle timeout.
+    CloseWhenIdle,
     /// Gracefully close the connection (active close) before
     /// terminating the task.
     Close,
@@ -184,6 +189,10 @@ pub(crate) async fn new_for_established_connection<THandler>(
         {
             Either::Left((Some(command), _)) => match command {
                 Command::NotifyHandler(event) => connection.on_behaviour_event(event),
+                Command::SetBannedProtocols(protocols) => {
+                    connection.set_banned_protocols(protocols)
+                }
+                Command::CloseWhenIdle => connection.close_when_idle(),
                 Command::Close => {
                     command_receiver.close();
                     let (remaining_events, closing_muxer) = connection.close();
@@ -103,7 +103,7 @@ impl crate::handler::ConnectionHandler for ConnectionHandler {
                 protocol, ..
             }) => void::unreachable(protocol),
             ConnectionEvent::DialUpgradeError(DialUpgradeError { info: _, error }) => match error {
-                StreamUpgradeError::Timeout => unreachable!(),
+                StreamUpgradeError::Timeout | StreamUpgradeError::OpenTimeout => unreachable!(),
                 StreamUpgradeError::Apply(e) => void::unreachable(e),
                 StreamUpgradeError::NegotiationFailed | StreamUpgradeError::Io(_) => {
                     unreachable!("Denied upgrade does not support any protocols")
@@ -635,6 +635,9 @@ impl<TConnectionUpgrade, TOutboundOpenInfo, TCustom>
 pub enum StreamUpgradeError<TUpgrErr> {
     /// The opening attempt timed out before the negotiation was fully completed.
     Timeout,
+    /// The substream could not be opened and its protocol negotiated within the
+    /// timeout configured via [`Config::with_substream_open_timeout`](crate::Config::with_substream_open_timeout).
+    OpenTimeout,
     /// The upgrade produced an error.
     Apply(TUpgrErr),
     /// No protocol could be agreed upon.
@@ -651,6 +654,7 @@ impl<TUpgrErr> StreamUpgradeError<TUpgrErr> {
     {
         match self {
             StreamUpgradeError::Timeout => StreamUpgradeError::Timeout,
+            StreamUpgradeError::OpenTimeout => StreamUpgradeError::OpenTimeout,
             StreamUpgradeError::Apply(e) => StreamUpgradeError::Apply(f(e)),
             StreamUpgradeError::NegotiationFailed => StreamUpgradeError::NegotiationFailed,
             StreamUpgradeError::Io(e) => StreamUpgradeError::Io(e),
@@ -667,6 +671,9 @@ where
             StreamUpgradeError::Timeout => {
                 write!(f, "Timeout error while opening a substream")
             }
+            StreamUpgradeError::OpenTimeout => {
+                write!(f, "Timeout error while opening and negotiating a substream")
+            }
             StreamUpgradeError::Apply(err) => {
                 write!(f, "Apply: ")?;
                 crate::print_error_chain(f, err)
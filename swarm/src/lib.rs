@@ -76,6 +76,7 @@ pub mod derive_prelude {
     pub use crate::behaviour::ConnectionClosed;
     pub use crate::behaviour::ConnectionEstablished;
     pub use crate::behaviour::DialFailure;
+    pub use crate::behaviour::DisconnectRequested;
     pub use crate::behaviour::ExpiredListenAddr;
     pub use crate::behaviour::ExternalAddrConfirmed;
     pub use crate::behaviour::ExternalAddrExpired;
@@ -107,13 +108,16 @@ pub mod derive_prelude {
 }
 
 pub use behaviour::{
-    AddressChange, CloseConnection, ConnectionClosed, DialFailure, ExpiredListenAddr,
-    ExternalAddrExpired, ExternalAddresses, FromSwarm, ListenAddresses, ListenFailure,
-    ListenerClosed, ListenerError, NetworkBehaviour, NewExternalAddrCandidate,
-    NewExternalAddrOfPeer, NewListenAddr, NotifyHandler, PeerAddresses, ToSwarm,
+    AddressChange, CloseConnection, ConnectionClosed, DialAttempt, DialFailure, DialHistory,
+    DialOutcome, DisconnectRequested, ExpiredListenAddr, ExternalAddrExpired, ExternalAddresses,
+    FromSwarm, ListenAddresses, ListenFailure, ListenerClosed, ListenerError, NetworkBehaviour,
+    NewExternalAddrCandidate, NewExternalAddrOfPeer, NewListenAddr, NotifyHandler, PeerAddresses,
+    ToSwarm,
 };
 pub use connection::pool::ConnectionCounters;
-pub use connection::{ConnectionError, ConnectionId, SupportedProtocols};
+pub use connection::{
+    ConnectionData, ConnectionError, ConnectionId, ConnectionPhases, SupportedProtocols,
+};
 pub use executor::Executor;
 pub use handler::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerSelect, OneShotHandler,
@@ -134,6 +138,7 @@ use connection::{
 };
 use dial_opts::{DialOpts, PeerCondition};
 use futures::{prelude::*, stream::FusedStream};
+use futures_timer::Delay;
 use libp2p_core::{
     connection::ConnectedPoint,
     muxing::StreamMuxerBox,
@@ -189,6 +194,8 @@ pub enum SwarmEvent<TBehaviourOutEvent> {
         concurrent_dial_errors: Option<Vec<(Multiaddr, TransportError<io::Error>)>>,
         /// How long it took to establish this connection
         established_in: std::time::Duration,
+        /// How long the phases of establishing this connection took.
+        phases: ConnectionPhases,
     },
     /// A connection with the given peer has been closed,
     /// possibly as a result of an error.
@@ -348,6 +355,22 @@ where
     pending_handler_event: Option<(PeerId, PendingNotifyHandler, THandlerInEvent<TBehaviour>)>,
 
     pending_swarm_events: VecDeque<SwarmEvent<TBehaviour::ToSwarm>>,
+
+    /// The maximum number of items handled in a single call to [`Swarm::poll_next_event`].
+    poll_budget: NonZeroUsize,
+
+    /// Peers to disconnect once their delay fires, see [`Swarm::disconnect_peer_id_gracefully`].
+    pending_disconnects: HashMap<PeerId, Delay>,
+
+    /// Peers whose connections are to be closed once idle, as soon as the behaviour had the chance
+    /// to react to [`FromSwarm::DisconnectRequested`].
+    idle_disconnects: Vec<PeerId>,
+
+    /// Data attached to pending or established connections, see [`Swarm::insert_connection_data`].
+    connection_data: HashMap<ConnectionId, ConnectionData>,
+
+    /// The outcomes of the recent dials of peers, see [`Swarm::dial_history`].
+    dial_history: DialHistory,
 }
 
 impl<TBehaviour> Unpin for Swarm<TBehaviour> where TBehaviour: NetworkBehaviour {}
@@ -376,9 +399,27 @@ where
             listened_addrs: HashMap::new(),
             pending_handler_event: None,
             pending_swarm_events: VecDeque::default(),
+            poll_budget: config.poll_budget,
+            pending_disconnects: HashMap::new(),
+            idle_disconnects: Vec::new(),
+            connection_data: HashMap::new(),
+            dial_history: DialHistory::new(config.dial_history_size),
         }
     }
 
+    /// Returns the outcomes of the recent dials of peers.
+    ///
+    /// Behaviours can maintain their own [`DialHistory`] by feeding it their [`FromSwarm`] events.
+    pub fn dial_history(&self) -> &DialHistory {
+        &self.dial_history
+    }
+
+    /// Notifies the [`DialHistory`] and the behaviour about a dial outcome.
+    fn on_dial_event(&mut self, event: FromSwarm) {
+        self.dial_history.on_swarm_event(&event);
+        self.behaviour.on_swarm_event(event);
+    }
+
     /// Returns information about the connections underlying the [`Swarm`].
     pub fn network_info(&self) -> NetworkInfo {
         let num_peers = self.pool.num_peers();
@@ -456,12 +497,11 @@ where
         if !should_dial {
             let e = DialError::DialPeerConditionFalse(condition);
 
-            self.behaviour
-                .on_swarm_event(FromSwarm::DialFailure(DialFailure {
-                    peer_id,
-                    error: &e,
-                    connection_id,
-                }));
+            self.on_dial_event(FromSwarm::DialFailure(DialFailure {
+                peer_id,
+                error: &e,
+                connection_id,
+            }));
 
             return Err(e);
         }
@@ -493,12 +533,11 @@ where
                 Err(cause) => {
                     let error = DialError::Denied { cause };
 
-                    self.behaviour
-                        .on_swarm_event(FromSwarm::DialFailure(DialFailure {
-                            peer_id,
-                            error: &error,
-                            connection_id,
-                        }));
+                    self.on_dial_event(FromSwarm::DialFailure(DialFailure {
+                        peer_id,
+                        error: &error,
+                        connection_id,
+                    }));
 
                     return Err(error);
                 }
@@ -512,12 +551,11 @@ where
 
             if addresses_from_opts.is_empty() {
                 let error = DialError::NoAddresses;
-                self.behaviour
-                    .on_swarm_event(FromSwarm::DialFailure(DialFailure {
-                        peer_id,
-                        error: &error,
-                        connection_id,
-                    }));
+                self.on_dial_event(FromSwarm::DialFailure(DialFailure {
+                    peer_id,
+                    error: &error,
+                    connection_id,
+                }));
                 return Err(error);
             };
 
@@ -654,6 +692,66 @@ where
         }
     }
 
+    /// Disconnects a peer by its peer ID after giving the [`NetworkBehaviour`]s the chance to
+    /// wind down their protocols with it.
+    ///
+    /// The behaviours are first notified via [`FromSwarm::DisconnectRequested`], allowing them to
+    /// flush their last messages to the peer. Each connection is then closed as soon as it is idle,
+    /// i.e. once no [`ConnectionHandler`] keeps it alive and no streams are active, regardless of the
+    /// configured idle timeout. Connections that are still in use after `timeout` are closed as with
+    /// [`Swarm::disconnect_peer_id`].
+    ///
+    /// Returns `Ok(())` if there was one or more established connections to the peer.
+    #[allow(clippy::result_unit_err)]
+    pub fn disconnect_peer_id_gracefully(
+        &mut self,
+        peer_id: PeerId,
+        timeout: Duration,
+    ) -> Result<(), ()> {
+        if !self.pool.is_connected(peer_id) {
+            return Err(());
+        }
+
+        self.behaviour
+            .on_swarm_event(FromSwarm::DisconnectRequested(DisconnectRequested {
+                peer_id,
+                timeout,
+            }));
+        self.pending_disconnects
+            .insert(peer_id, Delay::new(timeout));
+        self.idle_disconnects.push(peer_id);
+
+        Ok(())
+    }
+
+    /// Refuses the negotiation of `protocol` with `peer_id`, both for inbound and outbound streams.
+    ///
+    /// The ban applies to all current and future connections to the peer and is enforced by the
+    /// [`Swarm`] itself, independently of the [`NetworkBehaviour`] that serves the protocol.
+    /// Inbound streams for a banned protocol are rejected during negotiation, outbound streams fail
+    /// with [`StreamUpgradeError::NegotiationFailed`].
+    /// Streams that are already negotiated are not affected.
+    ///
+    /// Like closing a connection, updating the existing connections is asynchronous: streams
+    /// requested by a handler before the connection has processed the ban may still be negotiated.
+    ///
+    /// Returns `true` if the protocol was not already banned for this peer.
+    pub fn ban_protocol(&mut self, peer_id: PeerId, protocol: StreamProtocol) -> bool {
+        self.pool.ban_protocol(peer_id, protocol)
+    }
+
+    /// Lifts a ban previously placed via [`Swarm::ban_protocol`].
+    ///
+    /// Returns `true` if the protocol was banned for this peer.
+    pub fn unban_protocol(&mut self, peer_id: PeerId, protocol: &StreamProtocol) -> bool {
+        self.pool.unban_protocol(peer_id, protocol)
+    }
+
+    /// Returns the protocols that are currently banned for the given peer.
+    pub fn banned_protocols(&self, peer_id: &PeerId) -> impl Iterator<Item = &StreamProtocol> {
+        self.pool.banned_protocols(peer_id)
+    }
+
     /// Attempt to gracefully close a connection.
     ///
     /// Closing a connection is asynchronous but this function will return immediately.
@@ -672,6 +770,55 @@ where
         false
     }
 
+    /// Attaches data of type `T` to a pending or established connection, replacing and returning
+    /// the data of that type previously attached to it.
+    ///
+    /// The data is passed to the [`NetworkBehaviour`] via [`FromSwarm::ConnectionEstablished`] and
+    /// [`FromSwarm::ConnectionClosed`] and dropped once the connection is closed or failed. Returns
+    /// the data back as an error if no connection with this ID exists.
+    pub fn insert_connection_data<T: Send + 'static>(
+        &mut self,
+        connection_id: ConnectionId,
+        data: T,
+    ) -> Result<Option<T>, T> {
+        if !self.pool.contains(connection_id) {
+            return Err(data);
+        }
+
+        Ok(self
+            .connection_data
+            .entry(connection_id)
+            .or_default()
+            .insert(data))
+    }
+
+    /// Returns the data of type `T` attached to a connection.
+    pub fn connection_data<T: Send + 'static>(&self, connection_id: ConnectionId) -> Option<&T> {
+        self.connection_data.get(&connection_id)?.get()
+    }
+
+    /// Returns the data of type `T` attached to a connection, mutably.
+    pub fn connection_data_mut<T: Send + 'static>(
+        &mut self,
+        connection_id: ConnectionId,
+    ) -> Option<&mut T> {
+        self.connection_data.get_mut(&connection_id)?.get_mut()
+    }
+
+    /// Detaches the data of type `T` from a connection.
+    pub fn remove_connection_data<T: Send + 'static>(
+        &mut self,
+        connection_id: ConnectionId,
+    ) -> Option<T> {
+        let data = self.connection_data.get_mut(&connection_id)?;
+        let removed = data.remove()?;
+        if data.is_empty() {
+            self.connection_data.remove(&connection_id);
+        }
+
+        Some(removed)
+    }
+
     /// Checks whether there is an established connection to a peer.
     pub fn is_connected(&self, peer_id: &PeerId) -> bool {
         self.pool.is_connected(*peer_id)
@@ -701,6 +848,7 @@ where
                 connection,
                 concurrent_dial_errors,
                 established_in,
+                phases,
             } => {
                 let handler = match endpoint.clone() {
                     ConnectedPoint::Dialer {
@@ -716,13 +864,12 @@ where
                             Ok(handler) => handler,
                             Err(cause) => {
                                 let dial_error = DialError::Denied { cause };
-                                self.behaviour.on_swarm_event(FromSwarm::DialFailure(
-                                    DialFailure {
-                                        connection_id: id,
-                                        error: &dial_error,
-                                        peer_id: Some(peer_id),
-                                    },
-                                ));
+                                self.connection_data.remove(&id);
+                                self.on_dial_event(FromSwarm::DialFailure(DialFailure {
+                                    connection_id: id,
+                                    error: &dial_error,
+                                    peer_id: Some(peer_id),
+                                }));
 
                                 self.pending_swarm_events.push_back(
                                     SwarmEvent::OutgoingConnectionError {
@@ -748,6 +895,7 @@ where
                             Ok(handler) => handler,
                             Err(cause) => {
                                 let listen_error = ListenError::Denied { cause };
+                                self.connection_data.remove(&id);
                                 self.behaviour.on_swarm_event(FromSwarm::ListenFailure(
                                     ListenFailure {
                                         local_addr: &local_addr,
@@ -805,16 +953,20 @@ where
                             .collect::<Vec<Multiaddr>>()
                     })
                     .unwrap_or_default();
-                self.behaviour
-                    .on_swarm_event(FromSwarm::ConnectionEstablished(
-                        behaviour::ConnectionEstablished {
-                            peer_id,
-                            connection_id: id,
-                            endpoint: &endpoint,
-                            failed_addresses: &failed_addresses,
-                            other_established: other_established_connection_ids.len(),
-                        },
-                    ));
+                let data = self.connection_data.remove(&id).unwrap_or_default();
+                self.on_dial_event(FromSwarm::ConnectionEstablished(
+                    behaviour::ConnectionEstablished {
+                        peer_id,
+                        connection_id: id,
+                        endpoint: &endpoint,
+                        failed_addresses: &failed_addresses,
+                        other_established: other_established_connection_ids.len(),
+                        data: &data,
+                    },
+                ));
+                if !data.is_empty() {
+                    self.connection_data.insert(id, data);
+                }
                 self.supported_protocols = supported_protocols;
                 self.pending_swarm_events
                     .push_back(SwarmEvent::ConnectionEstablished {
@@ -824,6 +976,7 @@ where
                         endpoint,
                         concurrent_dial_errors,
                         established_in,
+                        phases,
                     });
             }
             PoolEvent::PendingOutboundConnectionError {
@@ -832,13 +985,13 @@ where
                 peer,
             } => {
                 let error = error.into();
+                self.connection_data.remove(&connection_id);
 
-                self.behaviour
-                    .on_swarm_event(FromSwarm::DialFailure(DialFailure {
-                        peer_id: peer,
-                        error: &error,
-                        connection_id,
-                    }));
+                self.on_dial_event(FromSwarm::DialFailure(DialFailure {
+                    peer_id: peer,
+                    error: &error,
+                    connection_id,
+                }));
 
                 if let Some(peer) = peer {
                     tracing::debug!(%peer, "Connection attempt to peer failed with {:?}.", error,);
@@ -860,6 +1013,7 @@ where
                 error,
             } => {
                 let error = error.into();
+                self.connection_data.remove(&id);
 
                 tracing::debug!("Incoming connection failed: {:?}", error);
                 self.behaviour
@@ -903,6 +1057,10 @@ where
                 let endpoint = connected.endpoint;
                 let num_established =
                     u32::try_from(remaining_established_connection_ids.len()).unwrap();
+                let data = self.connection_data.remove(&id).unwrap_or_default();
+                if num_established == 0 {
+                    self.pending_disconnects.remove(&peer_id);
+                }
 
                 self.behaviour
                     .on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
@@ -910,6 +1068,7 @@ where
                         connection_id: id,
                         endpoint: &endpoint,
                         remaining_established: num_established as usize,
+                        data: &data,
                     }));
                 self.pending_swarm_events
                     .push_back(SwarmEvent::ConnectionClosed {
@@ -1179,6 +1338,17 @@ where
                 self.pending_swarm_events
                     .push_back(SwarmEvent::ExternalAddrExpired { address: addr });
             }
+            ToSwarm::InsertConnectionData {
+                connection_id,
+                data,
+            } => {
+                if self.pool.contains(connection_id) {
+                    self.connection_data
+                        .entry(connection_id)
+                        .or_default()
+                        .insert_any(data);
+                }
+            }
             ToSwarm::CloseConnection {
                 peer_id,
                 connection,
@@ -1225,11 +1395,30 @@ where
         // (1) is polled before (2) to prioritize local work over work coming from a remote.
         //
         // (2) is polled before (3) to prioritize existing connections over upgrading new incoming connections.
+        //
+        // Every iteration handles at most one item. Once the budget is exhausted, the task yields
+        // back to the executor, such that a busy component can't starve other tasks.
+        let pool = &mut this.pool;
+        this.pending_disconnects.retain(|peer_id, delay| {
+            if delay.poll_unpin(cx).is_pending() {
+                return true;
+            }
+            pool.disconnect(*peer_id);
+            false
+        });
+
+        let mut budget = this.poll_budget.get();
         loop {
             if let Some(swarm_event) = this.pending_swarm_events.pop_front() {
                 return Poll::Ready(swarm_event);
             }
 
+            if budget == 0 {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            budget -= 1;
+
             match this.pending_handler_event.take() {
                 // Try to deliver the pending event emitted by the [`NetworkBehaviour`] in the previous
                 // iteration to the connection handler(s).
@@ -1257,7 +1446,12 @@ where
                 },
                 // No pending event. Allow the [`NetworkBehaviour`] to make progress.
                 None => match this.behaviour.poll(cx) {
-                    Poll::Pending => {}
+                    Poll::Pending => {
+                        // The behaviour had the chance to react to the disconnect requests.
+                        for peer_id in this.idle_disconnects.drain(..) {
+                            this.pool.close_when_idle(peer_id);
+                        }
+                    }
                     Poll::Ready(behaviour_event) => {
                         this.handle_behaviour_event(behaviour_event);
 
@@ -1405,6 +1599,8 @@ where
 
 pub struct Config {
     pool_config: PoolConfig,
+    poll_budget: NonZeroUsize,
+    dial_history_size: NonZeroUsize,
 }
 
 impl Config {
@@ -1413,6 +1609,8 @@ impl Config {
     pub fn with_executor(executor: impl Executor + Send + 'static) -> Self {
         Self {
             pool_config: PoolConfig::new(Some(Box::new(executor))),
+            poll_budget: NonZeroUsize::new(128).expect("128 > 0"),
+            dial_history_size: NonZeroUsize::new(100).expect("100 > 0"),
         }
     }
 
@@ -1523,6 +1721,40 @@ impl Config {
         self.pool_config.idle_connection_timeout = timeout;
         self
     }
+
+    /// How long opening an outbound substream, including the negotiation of its protocol, may take.
+    ///
+    /// This is independent of the dial timeout of the transport and of the upgrade timeout
+    /// of the individual [`SubstreamProtocol`]s, which also covers the application-specific
+    /// part of the upgrade. If the timeout elapses first, the [`ConnectionHandler`] is notified
+    /// via a [`StreamUpgradeError::OpenTimeout`].
+    ///
+    /// Unbounded by default, i.e. only the upgrade timeouts of the [`SubstreamProtocol`]s apply.
+    pub fn with_substream_open_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_config.substream_open_timeout = Some(timeout);
+        self
+    }
+
+    /// The maximum number of items, e.g. events of the [`NetworkBehaviour`] or of connections,
+    /// handled in a single poll of the [`Swarm`] without emitting a [`SwarmEvent`].
+    ///
+    /// Once the budget is exhausted, the [`Swarm`] yields back to the executor and schedules
+    /// itself to be polled again, bounding the latency of other tasks on the same executor
+    /// while a single busy [`NetworkBehaviour`] or connection produces items continuously.
+    ///
+    /// Defaults to 128.
+    pub fn with_poll_budget(mut self, budget: NonZeroUsize) -> Self {
+        self.poll_budget = budget;
+        self
+    }
+
+    /// The number of peers whose recent dial attempts are kept in the [`Swarm::dial_history`].
+    ///
+    /// Defaults to 100.
+    pub fn with_dial_history_size(mut self, number_of_peers: NonZeroUsize) -> Self {
+        self.dial_history_size = number_of_peers;
+        self
+    }
 }
 
 /// Possible errors when trying to establish or upgrade an outbound connection.
@@ -2245,6 +2477,165 @@ mod tests {
         .unwrap();
     }
 
+    #[tokio::test]
+    async fn connection_data_is_dropped_on_close() {
+        let mut swarm1 = new_test_swarm(Config::with_tokio_executor());
+        let mut swarm2 = new_test_swarm(Config::with_tokio_executor());
+
+        let addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr.clone()).unwrap();
+        swarm1.dial(addr).unwrap();
+
+        let connection_id = future::poll_fn(|cx| loop {
+            while swarm2.poll_next_unpin(cx).is_ready() {}
+            match swarm1.poll_next_unpin(cx) {
+                Poll::Ready(Some(SwarmEvent::ConnectionEstablished { connection_id, .. })) => {
+                    return Poll::Ready(connection_id)
+                }
+                Poll::Ready(_) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await;
+
+        assert_eq!(
+            swarm1.insert_connection_data(ConnectionId::next(), 1u32),
+            Err(1)
+        );
+        assert_eq!(swarm1.insert_connection_data(connection_id, 1u32), Ok(None));
+        assert_eq!(
+            swarm1.insert_connection_data(connection_id, "data"),
+            Ok(None)
+        );
+        *swarm1.connection_data_mut::<u32>(connection_id).unwrap() += 1;
+        assert_eq!(swarm1.connection_data::<u32>(connection_id), Some(&2));
+        assert_eq!(
+            swarm1.remove_connection_data::<&str>(connection_id),
+            Some("data")
+        );
+        assert_eq!(swarm1.connection_data::<&str>(connection_id), None);
+
+        assert!(swarm1.close_connection(connection_id));
+        future::poll_fn(|cx| loop {
+            while swarm2.poll_next_unpin(cx).is_ready() {}
+            match swarm1.poll_next_unpin(cx) {
+                Poll::Ready(Some(SwarmEvent::ConnectionClosed { .. })) => return Poll::Ready(()),
+                Poll::Ready(_) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await;
+        assert_eq!(swarm1.connection_data::<u32>(connection_id), None);
+        assert!(swarm1.connection_data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn behaviour_reads_connection_data_it_attached() {
+        #[derive(Default)]
+        struct Behaviour {
+            actions: VecDeque<ToSwarm<(), void::Void>>,
+            established: Vec<Option<u32>>,
+            closed: Vec<Option<u32>>,
+        }
+
+        impl NetworkBehaviour for Behaviour {
+            type ConnectionHandler = dummy::ConnectionHandler;
+            type ToSwarm = ();
+
+            fn handle_established_inbound_connection(
+                &mut self,
+                _: ConnectionId,
+                _: PeerId,
+                _: &Multiaddr,
+                _: &Multiaddr,
+            ) -> Result<THandler<Self>, ConnectionDenied> {
+                Ok(dummy::ConnectionHandler)
+            }
+
+            fn handle_established_outbound_connection(
+                &mut self,
+                _: ConnectionId,
+                _: PeerId,
+                _: &Multiaddr,
+                _: Endpoint,
+            ) -> Result<THandler<Self>, ConnectionDenied> {
+                Ok(dummy::ConnectionHandler)
+            }
+
+            fn on_swarm_event(&mut self, event: FromSwarm) {
+                match event {
+                    FromSwarm::ConnectionEstablished(e) => {
+                        self.established.push(e.data.get::<u32>().copied());
+                        self.actions.push_back(ToSwarm::InsertConnectionData {
+                            connection_id: e.connection_id,
+                            data: Box::new(2u32),
+                        });
+                    }
+                    FromSwarm::ConnectionClosed(e) => {
+                        self.closed.push(e.data.get::<u32>().copied());
+                    }
+                    _ => {}
+                }
+            }
+
+            fn on_connection_handler_event(
+                &mut self,
+                _: PeerId,
+                _: ConnectionId,
+                event: THandlerOutEvent<Self>,
+            ) {
+                void::unreachable(event)
+            }
+
+            fn poll(
+                &mut self,
+                _: &mut Context<'_>,
+            ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+                self.actions.pop_front().map_or(Poll::Pending, Poll::Ready)
+            }
+        }
+
+        let id_keys = identity::Keypair::generate_ed25519();
+        let transport = transport::MemoryTransport::default()
+            .upgrade(upgrade::Version::V1)
+            .authenticate(plaintext::Config::new(&id_keys))
+            .multiplex(yamux::Config::default())
+            .boxed();
+        let mut swarm1 = Swarm::new(
+            transport,
+            Behaviour::default(),
+            id_keys.public().to_peer_id(),
+            Config::with_tokio_executor().with_idle_connection_timeout(Duration::from_millis(100)),
+        );
+        let mut swarm2 = new_test_swarm(Config::with_tokio_executor());
+
+        let addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr.clone()).unwrap();
+        let opts = DialOpts::unknown_peer_id().address(addr).build();
+        let connection_id = opts.connection_id();
+        swarm1.behaviour_mut().actions.extend([
+            ToSwarm::Dial { opts },
+            ToSwarm::InsertConnectionData {
+                connection_id,
+                data: Box::new(1u32),
+            },
+        ]);
+
+        future::poll_fn(|cx| loop {
+            while swarm2.poll_next_unpin(cx).is_ready() {}
+            match swarm1.poll_next_unpin(cx) {
+                Poll::Ready(Some(SwarmEvent::ConnectionClosed { .. })) => return Poll::Ready(()),
+                Poll::Ready(_) => {}
+                Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await;
+
+        assert_eq!(swarm1.behaviour().established, vec![Some(1)]);
+        assert_eq!(swarm1.behaviour().closed, vec![Some(2)]);
+        assert!(swarm1.connection_data.is_empty());
+    }
+
     #[tokio::test]
     async fn dial_self_by_id() {
         // Trying to dial self by passing the same `PeerId` shouldn't even be possible in the first
@@ -2272,6 +2663,7 @@ mod tests {
             multiaddr![Udp(rand::random::<u16>())],
             multiaddr![Udp(rand::random::<u16>())],
         ]);
+        let num_addresses = addresses.len();
 
         swarm
             .dial(
@@ -2300,6 +2692,12 @@ mod tests {
             }
             e => panic!("Unexpected event: {e:?}"),
         }
+
+        // Every failed address is recorded in the dial history.
+        assert_eq!(
+            swarm.dial_history().consecutive_failures(&target),
+            num_addresses
+        );
     }
 
     #[tokio::test]
@@ -2355,4 +2753,89 @@ mod tests {
         // Unfortunately, we have some "empty" errors that lead to multiple colons without text but that is the best we can do.
         assert_eq!("Failed to negotiate transport protocol(s): [(/ip4/127.0.0.1/tcp/80: : No listener on the given port.)]", string)
     }
+
+    #[test]
+    fn busy_behaviour_yields_once_poll_budget_is_exhausted() {
+        /// A behaviour that always has work to do, without ever emitting a [`SwarmEvent`].
+        #[derive(Default)]
+        struct Busy {
+            polls: usize,
+        }
+
+        impl NetworkBehaviour for Busy {
+            type ConnectionHandler = dummy::ConnectionHandler;
+            type ToSwarm = void::Void;
+
+            fn handle_established_inbound_connection(
+                &mut self,
+                _: ConnectionId,
+                _: PeerId,
+                _: &Multiaddr,
+                _: &Multiaddr,
+            ) -> Result<THandler<Self>, ConnectionDenied> {
+                Ok(dummy::ConnectionHandler)
+            }
+
+            fn handle_established_outbound_connection(
+                &mut self,
+                _: ConnectionId,
+                _: PeerId,
+                _: &Multiaddr,
+                _: Endpoint,
+            ) -> Result<THandler<Self>, ConnectionDenied> {
+                Ok(dummy::ConnectionHandler)
+            }
+
+            fn on_swarm_event(&mut self, _: FromSwarm) {}
+
+            fn on_connection_handler_event(
+                &mut self,
+                _: PeerId,
+                _: ConnectionId,
+                event: THandlerOutEvent<Self>,
+            ) {
+                void::unreachable(event)
+            }
+
+            fn poll(
+                &mut self,
+                _: &mut Context<'_>,
+            ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+                self.polls += 1;
+                Poll::Ready(ToSwarm::CloseConnection {
+                    peer_id: PeerId::random(),
+                    connection: CloseConnection::All,
+                })
+            }
+        }
+
+        struct Woken(std::sync::atomic::AtomicBool);
+
+        impl futures::task::ArcWake for Woken {
+            fn wake_by_ref(arc_self: &std::sync::Arc<Self>) {
+                arc_self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        let id_keys = identity::Keypair::generate_ed25519();
+        let transport = transport::MemoryTransport::default()
+            .upgrade(upgrade::Version::V1)
+            .authenticate(plaintext::Config::new(&id_keys))
+            .multiplex(yamux::Config::default())
+            .boxed();
+        let mut swarm = Swarm::new(
+            transport,
+            Busy::default(),
+            id_keys.public().to_peer_id(),
+            Config::with_async_std_executor().with_poll_budget(NonZeroUsize::new(10).unwrap()),
+        );
+
+        let woken = std::sync::Arc::new(Woken(Default::default()));
+        let waker = futures::task::waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(swarm.poll_next_unpin(&mut cx).is_pending());
+        assert_eq!(swarm.behaviour().polls, 10);
+        assert!(woken.0.load(std::sync::atomic::Ordering::SeqCst));
+    }
 }
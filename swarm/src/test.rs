@@ -249,6 +249,7 @@ where
             endpoint,
             failed_addresses,
             other_established,
+            data,
         }: ConnectionEstablished,
     ) {
         let mut other_peer_connections = self
@@ -291,6 +292,7 @@ where
                 endpoint,
                 failed_addresses,
                 other_established,
+                data,
             }));
     }
 
@@ -301,6 +303,7 @@ where
             connection_id,
             endpoint,
             remaining_established,
+            data,
         }: ConnectionClosed,
     ) {
         let mut other_closed_connections = self
@@ -350,6 +353,7 @@ where
                 connection_id,
                 endpoint,
                 remaining_established,
+                data,
             }));
     }
 }
@@ -9,6 +9,7 @@ use libp2p_swarm::{
 };
 use libp2p_swarm_test::SwarmExt;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use void::Void;
 
 #[async_std::test]
@@ -30,17 +31,84 @@ async fn sends_remaining_events_to_behaviour_on_connection_close() {
     }
 }
 
+#[async_std::test]
+async fn notifies_behaviour_before_graceful_disconnect() {
+    let mut swarm1 = Swarm::new_ephemeral(|_| Behaviour::new(3));
+    let mut swarm2 = Swarm::new_ephemeral(|_| Behaviour::new(3));
+
+    swarm2.listen().with_memory_addr_external().await;
+    swarm1.connect(&mut swarm2).await;
+
+    let peer2 = *swarm2.local_peer_id();
+    swarm1
+        .disconnect_peer_id_gracefully(peer2, Duration::from_millis(100))
+        .unwrap();
+
+    assert_eq!(swarm1.behaviour().disconnect_requested, Some(peer2));
+    assert!(swarm1.is_connected(&peer2));
+
+    match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+        ([SwarmEvent::ConnectionClosed { .. }], [SwarmEvent::ConnectionClosed { .. }]) => {
+            assert_eq!(swarm1.behaviour().state, 0);
+            assert_eq!(swarm2.behaviour().state, 0);
+        }
+        (e1, e2) => panic!("Unexpected events: {:?} {:?}", e1, e2),
+    }
+    assert!(swarm2.behaviour().disconnect_requested.is_none());
+}
+
+#[async_std::test]
+async fn graceful_disconnect_closes_idle_connections_early() {
+    let mut swarm1 = Swarm::new_ephemeral(|_| Behaviour::idle(3));
+    let mut swarm2 = Swarm::new_ephemeral(|_| Behaviour::idle(3));
+
+    swarm2.listen().with_memory_addr_external().await;
+    swarm1.connect(&mut swarm2).await;
+
+    let started = Instant::now();
+    swarm1
+        .disconnect_peer_id_gracefully(*swarm2.local_peer_id(), Duration::from_secs(60))
+        .unwrap();
+
+    match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+        ([SwarmEvent::ConnectionClosed { .. }], [SwarmEvent::ConnectionClosed { .. }]) => {
+            assert_eq!(swarm1.behaviour().state, 0);
+            assert_eq!(swarm2.behaviour().state, 0);
+        }
+        (e1, e2) => panic!("Unexpected events: {:?} {:?}", e1, e2),
+    }
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "expect the connection to close before the idle timeout"
+    );
+}
+
 struct HandlerWithState {
     precious_state: u64,
+    keep_alive: bool,
 }
 
 struct Behaviour {
     state: u64,
+    keep_alive: bool,
+    disconnect_requested: Option<PeerId>,
 }
 
 impl Behaviour {
     fn new(state: u64) -> Self {
-        Behaviour { state }
+        Behaviour {
+            state,
+            keep_alive: true,
+            disconnect_requested: None,
+        }
+    }
+
+    /// Creates a behaviour whose handlers don't keep their connections alive.
+    fn idle(state: u64) -> Self {
+        Behaviour {
+            keep_alive: false,
+            ..Behaviour::new(state)
+        }
     }
 }
 
@@ -57,6 +125,7 @@ impl NetworkBehaviour for Behaviour {
     ) -> Result<THandler<Self>, ConnectionDenied> {
         Ok(HandlerWithState {
             precious_state: self.state,
+            keep_alive: self.keep_alive,
         })
     }
 
@@ -69,12 +138,15 @@ impl NetworkBehaviour for Behaviour {
     ) -> Result<THandler<Self>, ConnectionDenied> {
         Ok(HandlerWithState {
             precious_state: self.state,
+            keep_alive: self.keep_alive,
         })
     }
 
     fn on_swarm_event(&mut self, event: FromSwarm) {
-        if let FromSwarm::ConnectionClosed(_) = event {
-            assert_eq!(self.state, 0);
+        match event {
+            FromSwarm::ConnectionClosed(_) => assert_eq!(self.state, 0),
+            FromSwarm::DisconnectRequested(e) => self.disconnect_requested = Some(e.peer_id),
+            _ => {}
         }
     }
 
@@ -106,7 +178,7 @@ impl ConnectionHandler for HandlerWithState {
     }
 
     fn connection_keep_alive(&self) -> bool {
-        true
+        self.keep_alive
     }
 
     fn poll(
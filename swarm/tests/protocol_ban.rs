@@ -0,0 +1,124 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::future::{self, Either};
+use futures::StreamExt;
+use libp2p_identity::PeerId;
+use libp2p_ping as ping;
+use libp2p_stream as stream;
+use libp2p_swarm::{StreamProtocol, Swarm, SwarmEvent};
+use libp2p_swarm_test::SwarmExt;
+use std::pin::pin;
+use std::time::Duration;
+
+const PROTOCOL: StreamProtocol = StreamProtocol::new("/test");
+
+#[async_std::test]
+async fn banned_protocol_is_refused_in_both_directions() {
+    let mut swarm1 = Swarm::new_ephemeral(|_| ping::Behaviour::default());
+    let mut swarm2 = Swarm::new_ephemeral(|_| ping::Behaviour::default());
+
+    assert!(swarm1.ban_protocol(*swarm2.local_peer_id(), ping::PROTOCOL_NAME));
+    assert!(!swarm1.ban_protocol(*swarm2.local_peer_id(), ping::PROTOCOL_NAME));
+
+    swarm2.listen().with_memory_addr_external().await;
+    swarm1.connect(&mut swarm2).await;
+
+    let (outbound, inbound) =
+        futures::future::join(swarm1.wait(ping_result), swarm2.wait(ping_result)).await;
+
+    assert!(matches!(outbound, Err(ping::Failure::Unsupported)));
+    assert!(matches!(inbound, Err(ping::Failure::Unsupported)));
+}
+
+#[async_std::test]
+async fn unbanned_protocol_is_negotiated_on_existing_connection() {
+    let mut swarm1 = Swarm::new_ephemeral(|_| stream::Behaviour::new());
+    let mut swarm2 = Swarm::new_ephemeral(|_| stream::Behaviour::new());
+    let peer2 = *swarm2.local_peer_id();
+
+    let mut control = swarm1.behaviour().new_control();
+    let mut incoming = swarm2.behaviour().new_control().accept(PROTOCOL).unwrap();
+
+    swarm2.listen().with_memory_addr_external().await;
+    swarm1.connect(&mut swarm2).await;
+
+    async_std::task::spawn(async move { while incoming.next().await.is_some() {} });
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    assert!(open_stream(&mut swarm1, &mut control, peer2).await.is_ok());
+
+    assert!(swarm1.ban_protocol(peer2, PROTOCOL));
+    assert!(
+        open_stream_until(&mut swarm1, &mut control, peer2, |result| matches!(
+            result,
+            Err(stream::OpenStreamError::UnsupportedProtocol(_))
+        ))
+        .await
+    );
+
+    assert!(swarm1.unban_protocol(peer2, &PROTOCOL));
+    assert!(!swarm1.unban_protocol(peer2, &PROTOCOL));
+    assert_eq!(swarm1.banned_protocols(&peer2).count(), 0);
+    assert!(open_stream_until(&mut swarm1, &mut control, peer2, Result::is_ok).await);
+    assert!(swarm1.is_connected(&peer2));
+}
+
+/// Repeatedly opens streams to `peer` until `predicate` holds for the result.
+///
+/// (Un)banning a protocol reaches existing connections asynchronously, thus streams that are
+/// requested right after the call may still see the previous set of banned protocols.
+async fn open_stream_until(
+    swarm: &mut Swarm<stream::Behaviour>,
+    control: &mut stream::Control,
+    peer: PeerId,
+    predicate: impl Fn(&Result<libp2p_swarm::Stream, stream::OpenStreamError>) -> bool,
+) -> bool {
+    for _ in 0..10 {
+        if predicate(&open_stream(swarm, control, peer).await) {
+            return true;
+        }
+        async_std::task::sleep(Duration::from_millis(10)).await;
+    }
+
+    false
+}
+
+/// Opens a stream to `peer` while driving `swarm`.
+async fn open_stream(
+    swarm: &mut Swarm<stream::Behaviour>,
+    control: &mut stream::Control,
+    peer: PeerId,
+) -> Result<libp2p_swarm::Stream, stream::OpenStreamError> {
+    let mut open = pin!(control.open_stream(peer, PROTOCOL));
+    loop {
+        match future::select(open.as_mut(), swarm.select_next_some()).await {
+            Either::Left((result, _)) => return result,
+            Either::Right(_) => {}
+        }
+    }
+}
+
+fn ping_result(event: SwarmEvent<ping::Event>) -> Option<Result<Duration, ping::Failure>> {
+    match event {
+        SwarmEvent::Behaviour(ping::Event { result, .. }) => Some(result),
+        _ => None,
+    }
+}
@@ -69,6 +69,9 @@ pub struct Config {
 
     /// Parameters governing MTU discovery. See [`MtuDiscoveryConfig`] for details.
     mtu_discovery_config: Option<MtuDiscoveryConfig>,
+
+    /// Size of the buffer for incoming datagrams, `None` if datagrams are disabled.
+    datagram_receive_buffer_size: Option<usize>,
 }
 
 impl Config {
@@ -95,6 +98,7 @@ impl Config {
             max_stream_data: 10_000_000,
             keypair: keypair.clone(),
             mtu_discovery_config: Some(Default::default()),
+            datagram_receive_buffer_size: None,
         }
     }
 
@@ -111,6 +115,22 @@ impl Config {
         self.mtu_discovery_config = None;
         self
     }
+
+    /// Enable unreliable datagrams on all connections (they are disabled by default),
+    /// buffering up to `receive_buffer_size` bytes of incoming datagrams.
+    ///
+    /// Datagrams can be sent and received through the [`Connection::datagram_channel`]
+    /// of connections to peers that enabled datagrams as well.
+    ///
+    /// [`Connection::datagram_channel`]: crate::Connection::datagram_channel
+    pub fn enable_datagrams(mut self, receive_buffer_size: usize) -> Self {
+        self.datagram_receive_buffer_size = Some(receive_buffer_size);
+        self
+    }
+
+    pub(crate) fn datagrams_enabled(&self) -> bool {
+        self.datagram_receive_buffer_size.is_some()
+    }
 }
 
 /// Represents the inner configuration for [`quinn`].
@@ -135,13 +155,14 @@ impl From<Config> for QuinnConfig {
             handshake_timeout: _,
             keypair,
             mtu_discovery_config,
+            datagram_receive_buffer_size,
         } = config;
         let mut transport = quinn::TransportConfig::default();
         // Disable uni-directional streams.
         transport.max_concurrent_uni_streams(0u32.into());
         transport.max_concurrent_bidi_streams(max_concurrent_stream_limit.into());
-        // Disable datagrams.
-        transport.datagram_receive_buffer_size(None);
+        // Datagrams are disabled unless explicitly enabled.
+        transport.datagram_receive_buffer_size(datagram_receive_buffer_size);
         transport.keep_alive_interval(Some(keep_alive_interval));
         transport.max_idle_timeout(Some(VarInt::from_u32(max_idle_timeout).into()));
         transport.allow_spin(false);
@@ -19,9 +19,11 @@
 // DEALINGS IN THE SOFTWARE.
 
 mod connecting;
+mod datagram;
 mod stream;
 
 pub use connecting::Connecting;
+pub use datagram::DatagramChannel;
 pub use stream::Stream;
 
 use crate::{ConnectionError, Error};
@@ -47,6 +49,8 @@ pub struct Connection {
     >,
     /// Future to wait for the connection to be closed.
     closing: Option<BoxFuture<'static, quinn::ConnectionError>>,
+    /// Whether datagrams are enabled locally.
+    datagrams: bool,
 }
 
 impl Connection {
@@ -54,14 +58,27 @@ impl Connection {
     ///
     /// This function assumes that the [`quinn::Connection`] is completely fresh and none of
     /// its methods has ever been called. Failure to comply might lead to logic errors and panics.
-    fn new(connection: quinn::Connection) -> Self {
+    fn new(connection: quinn::Connection, datagrams: bool) -> Self {
         Self {
             connection,
             incoming: None,
             outgoing: None,
             closing: None,
+            datagrams,
         }
     }
+
+    /// Returns a channel for sending and receiving unreliable datagrams on this connection.
+    ///
+    /// Returns `None` if datagrams are not enabled in the [`Config`](crate::Config) of both
+    /// peers.
+    pub fn datagram_channel(&self) -> Option<DatagramChannel> {
+        if !self.datagrams {
+            return None;
+        }
+        self.connection.max_datagram_size()?;
+        Some(DatagramChannel::new(self.connection.clone()))
+    }
 }
 
 impl StreamMuxer for Connection {
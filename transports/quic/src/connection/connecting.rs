@@ -39,12 +39,14 @@ use std::{
 #[derive(Debug)]
 pub struct Connecting {
     connecting: Select<quinn::Connecting, Delay>,
+    datagrams: bool,
 }
 
 impl Connecting {
-    pub(crate) fn new(connection: quinn::Connecting, timeout: Duration) -> Self {
+    pub(crate) fn new(connection: quinn::Connecting, timeout: Duration, datagrams: bool) -> Self {
         Connecting {
             connecting: select(connection, Delay::new(timeout)),
+            datagrams,
         }
     }
 }
@@ -77,7 +79,7 @@ impl Future for Connecting {
         };
 
         let peer_id = Self::remote_peer_id(&connection);
-        let muxer = Connection::new(connection);
+        let muxer = Connection::new(connection, self.datagrams);
         Poll::Ready(Ok((peer_id, muxer)))
     }
 }
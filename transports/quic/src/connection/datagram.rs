@@ -0,0 +1,64 @@
+// Copyright 2024 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{ConnectionError, DatagramError};
+use bytes::Bytes;
+
+/// An unreliable and unordered message channel on a QUIC connection.
+///
+/// Datagrams are neither retransmitted nor flow controlled: they may be dropped or
+/// arrive out of order, and are discarded by the remote once its receive buffer is full.
+/// Obtained through [`Connection::datagram_channel`](crate::Connection::datagram_channel).
+#[derive(Debug, Clone)]
+pub struct DatagramChannel {
+    connection: quinn::Connection,
+}
+
+impl DatagramChannel {
+    pub(super) fn new(connection: quinn::Connection) -> Self {
+        Self { connection }
+    }
+
+    /// The maximum size of a datagram that can currently be sent.
+    ///
+    /// The size depends on the path MTU and thus may change over the lifetime of the
+    /// connection.
+    pub fn max_size(&self) -> usize {
+        self.connection.max_datagram_size().unwrap_or(0)
+    }
+
+    /// Queues a datagram for sending.
+    ///
+    /// Fails if the datagram is larger than [`DatagramChannel::max_size`] or the connection
+    /// is closed. If the send buffer is full, older datagrams are dropped.
+    pub fn send(&self, data: Bytes) -> Result<(), DatagramError> {
+        self.connection.send_datagram(data).map_err(DatagramError)
+    }
+
+    /// Receives the next datagram from the remote.
+    ///
+    /// Fails once the connection is closed.
+    pub async fn recv(&self) -> Result<Bytes, ConnectionError> {
+        self.connection
+            .read_datagram()
+            .await
+            .map_err(ConnectionError)
+    }
+}
@@ -66,7 +66,7 @@ mod transport;
 use std::net::SocketAddr;
 
 pub use config::Config;
-pub use connection::{Connecting, Connection, Stream};
+pub use connection::{Connecting, Connection, DatagramChannel, Stream};
 
 #[cfg(feature = "async-std")]
 pub use provider::async_std;
@@ -112,3 +112,8 @@ pub struct ConnectError(quinn::ConnectError);
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 pub struct ConnectionError(quinn::ConnectionError);
+
+/// Sending a datagram on a [`DatagramChannel`] failed.
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct DatagramError(quinn::SendDatagramError);
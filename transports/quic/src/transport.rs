@@ -69,6 +69,8 @@ pub struct GenTransport<P: Provider> {
     handshake_timeout: Duration,
     /// Whether draft-29 is supported for dialing and listening.
     support_draft_29: bool,
+    /// Whether datagrams are enabled on new connections.
+    datagrams: bool,
     /// Streams of active [`Listener`]s.
     listeners: SelectAll<Listener<P>>,
     /// Dialer for each socket family if no matching listener exists.
@@ -84,6 +86,7 @@ impl<P: Provider> GenTransport<P> {
     pub fn new(config: Config) -> Self {
         let handshake_timeout = config.handshake_timeout;
         let support_draft_29 = config.support_draft_29;
+        let datagrams = config.datagrams_enabled();
         let quinn_config = config.into();
         Self {
             listeners: SelectAll::new(),
@@ -92,6 +95,7 @@ impl<P: Provider> GenTransport<P> {
             dialer: HashMap::new(),
             waker: None,
             support_draft_29,
+            datagrams,
             hole_punch_attempts: Default::default(),
         }
     }
@@ -220,6 +224,7 @@ impl<P: Provider> Transport for GenTransport<P> {
             socket_c,
             endpoint,
             self.handshake_timeout,
+            self.datagrams,
             version,
         )?;
         self.listeners.push(listener);
@@ -287,6 +292,7 @@ impl<P: Provider> Transport for GenTransport<P> {
             Some(listener) => listener.endpoint.clone(),
         };
         let handshake_timeout = self.handshake_timeout;
+        let datagrams = self.datagrams;
         let mut client_config = self.quinn_config.client_config.clone();
         if version == ProtocolVersion::Draft29 {
             client_config.version(0xff00_001d);
@@ -298,7 +304,7 @@ impl<P: Provider> Transport for GenTransport<P> {
             let connecting = endpoint
                 .connect_with(client_config, socket_addr, "l")
                 .map_err(ConnectError)?;
-            Connecting::new(connecting, handshake_timeout).await
+            Connecting::new(connecting, handshake_timeout, datagrams).await
         }))
     }
 
@@ -428,6 +434,8 @@ struct Listener<P: Provider> {
     accept: BoxFuture<'static, Option<quinn::Incoming>>,
     /// Timeout for connection establishment on inbound connections.
     handshake_timeout: Duration,
+    /// Whether datagrams are enabled on inbound connections.
+    datagrams: bool,
 
     /// Watcher for network interface changes.
     ///
@@ -452,6 +460,7 @@ impl<P: Provider> Listener<P> {
         socket: UdpSocket,
         endpoint: quinn::Endpoint,
         handshake_timeout: Duration,
+        datagrams: bool,
         version: ProtocolVersion,
     ) -> Result<Self, Error> {
         let if_watcher;
@@ -481,6 +490,7 @@ impl<P: Provider> Listener<P> {
             listener_id,
             version,
             handshake_timeout,
+            datagrams,
             if_watcher,
             is_closed: false,
             pending_event,
@@ -602,7 +612,11 @@ impl<P: Provider> Stream for Listener<P> {
                     let send_back_addr = socketaddr_to_multiaddr(&remote_addr, self.version);
 
                     let event = TransportEvent::Incoming {
-                        upgrade: Connecting::new(connecting, self.handshake_timeout),
+                        upgrade: Connecting::new(
+                            connecting,
+                            self.handshake_timeout,
+                            self.datagrams,
+                        ),
                         local_addr,
                         send_back_addr,
                         listener_id: self.listener_id,
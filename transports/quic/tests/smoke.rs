@@ -1,5 +1,6 @@
 #![cfg(any(feature = "async-std", feature = "tokio"))]
 
+use bytes::Bytes;
 use futures::channel::{mpsc, oneshot};
 use futures::future::BoxFuture;
 use futures::future::{poll_fn, Either};
@@ -399,6 +400,62 @@ async fn read_after_peer_dropped_stream() {
     assert_eq!(data, buf)
 }
 
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn datagrams() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let a_channels = Arc::new(Mutex::new(Vec::new()));
+    let b_channels = Arc::new(Mutex::new(Vec::new()));
+    let mut a_transport = create_datagram_transport::<quic::async_std::Provider>(
+        |c| c.enable_datagrams(1024),
+        a_channels.clone(),
+    );
+    let mut b_transport = create_datagram_transport::<quic::async_std::Provider>(
+        |c| c.enable_datagrams(1024),
+        b_channels.clone(),
+    );
+
+    let addr = start_listening(&mut a_transport, "/ip4/127.0.0.1/udp/0/quic-v1").await;
+    let (_a_connection, _b_connection) = connect(&mut a_transport, &mut b_transport, addr).await;
+
+    let a_channel = a_channels.lock().unwrap()[0].clone().unwrap();
+    let b_channel = b_channels.lock().unwrap()[0].clone().unwrap();
+
+    a_channel.send(Bytes::from_static(b"ping")).unwrap();
+    assert_eq!(b_channel.recv().await.unwrap(), &b"ping"[..]);
+    b_channel.send(Bytes::from_static(b"pong")).unwrap();
+    assert_eq!(a_channel.recv().await.unwrap(), &b"pong"[..]);
+
+    let oversized = vec![0; a_channel.max_size() + 1];
+    assert!(a_channel.send(oversized.into()).is_err());
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn datagrams_require_both_peers() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+
+    let a_channels = Arc::new(Mutex::new(Vec::new()));
+    let b_channels = Arc::new(Mutex::new(Vec::new()));
+    let mut a_transport = create_datagram_transport::<quic::async_std::Provider>(
+        |c| c.enable_datagrams(1024),
+        a_channels.clone(),
+    );
+    let mut b_transport =
+        create_datagram_transport::<quic::async_std::Provider>(|c| c, b_channels.clone());
+
+    let addr = start_listening(&mut a_transport, "/ip4/127.0.0.1/udp/0/quic-v1").await;
+    let (_a_connection, _b_connection) = connect(&mut a_transport, &mut b_transport, addr).await;
+
+    assert!(a_channels.lock().unwrap()[0].is_none());
+    assert!(b_channels.lock().unwrap()[0].is_none());
+}
+
 #[cfg(feature = "async-std")]
 #[async_std::test]
 #[should_panic]
@@ -558,6 +615,20 @@ fn create_transport<P: Provider>(
     (peer_id, transport)
 }
 
+/// Creates a transport recording the [`quic::DatagramChannel`] of every new connection.
+fn create_datagram_transport<P: Provider>(
+    with_config: impl FnOnce(quic::Config) -> quic::Config,
+    channels: Arc<Mutex<Vec<Option<quic::DatagramChannel>>>>,
+) -> Boxed<(PeerId, StreamMuxerBox)> {
+    let config = with_config(quic::Config::new(&generate_tls_keypair()));
+    quic::GenTransport::<P>::new(config)
+        .map(move |(p, c), _| {
+            channels.lock().unwrap().push(c.datagram_channel());
+            (p, StreamMuxerBox::new(c))
+        })
+        .boxed()
+}
+
 async fn start_listening(transport: &mut Boxed<(PeerId, StreamMuxerBox)>, addr: &str) -> Multiaddr {
     transport
         .listen_on(ListenerId::next(), addr.parse().unwrap())